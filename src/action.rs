@@ -0,0 +1,542 @@
+//! What input does, depending on what's on screen.
+//!
+//! Key presses are mapped to an [`Action`] for the current
+//! [`InputContext`], so that a key can mean different things in the
+//! table and in a prompt, and then applied with `App::update`.
+
+use crate::{hitmap::Region, state::App, widgets::Edit};
+use crossterm::event::{
+    Event, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+};
+
+/// Something the user asked the interface to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    /// Try fetching the data again after it failed.
+    Retry,
+    /// Fetch the data from the next mirror after refreshes kept failing.
+    NextMirror,
+    /// Move the table selection, or through a prompt's rows or history.
+    MoveSelection(i32),
+    /// Move the table selection by pages.
+    MovePage(i32),
+    ToggleTab,
+    /// Switch to a "tab" by index.
+    SelectTab(usize),
+    CycleSort,
+    /// Sort by a column, or reverse the sort if it's already sorted by it.
+    SortByColumn(usize),
+    ReverseSort,
+    ResetSort,
+    /// Clear the selected "tab"'s filter.
+    ClearFilter,
+    /// Clear every "tab"'s filter.
+    ClearAllFilters,
+    ScrollColumns(i32),
+    CycleRulesFilter,
+    ToggleNoFlightPlan,
+    ToggleNoAtis,
+    CyclePhaseFilter,
+    ToggleCoverage,
+    ToggleRangeFilter,
+    ToggleDepartingSoon,
+    /// Show only pilots who look to be flying military or special operations.
+    ToggleSpecialOnly,
+    /// Hide pilots whose records look broken.
+    ToggleHideBadData,
+    /// Split the pilots at the filtered airport into inbound and outbound.
+    ToggleSplit,
+    /// Keep favorites at the top of the pilots and controllers.
+    TogglePinFavorites,
+    /// Show the pilots in the selected controller's airspace squawking
+    /// other than their assigned code.
+    ToggleSquawkMismatches,
+    ToggleDebug,
+    /// Switch between one and two lines per row.
+    ToggleDensity,
+    /// Hide or show clients whose records have stopped updating.
+    ToggleHideStale,
+    /// Include or leave out probable ghost connections in counts and alerts.
+    ToggleCountGhosts,
+    /// Widen or narrow the sorted column.
+    ResizeColumn(i16),
+    OpenTune,
+    /// Choose which kinds of position the controllers show.
+    OpenFacilityPicker,
+    OpenFilterBuilder,
+    OpenSearch,
+    OpenPopup,
+    ClosePopup,
+    ExportSummary,
+    /// Copy and save the trail of the pilot in the popup as GeoJSON.
+    ExportTrail,
+    OpenStats,
+    /// Type a character into the open prompt.
+    InputChar(char),
+    /// Move the cursor or delete text in the open prompt.
+    Edit(Edit),
+    /// Tick or untick the selected item in the open list.
+    ToggleItem,
+    /// Apply the open prompt.
+    Submit,
+    /// Close the open prompt without applying it.
+    Cancel,
+    /// Add a row to the filter builder.
+    AddRow,
+    /// Remove a row from the filter builder.
+    RemoveRow,
+    /// Move to the next part of the filter builder row.
+    NextFocus,
+    /// Search back through the query history.
+    ReverseSearch,
+    /// Complete the word being typed, or show the next completion.
+    Complete,
+    /// Open or close the log panel.
+    ToggleLogPanel,
+    /// Keep the log panel on the newest line.
+    FollowLog,
+    /// Show fewer log levels in the log panel.
+    CycleLogLevel,
+}
+
+/// What's on screen, which decides what keys do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputContext {
+    /// Waiting on the first data, or the fetch failed.
+    Loading {
+        failed: bool,
+    },
+    FilterBuilder,
+    Search,
+    Tune,
+    FacilityPicker,
+    Popup,
+    /// The log panel is open.
+    LogPanel,
+    Table,
+}
+
+/// Work for the interface loop that the state can't do by itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Effect {
+    Quit,
+    /// Ask the worker for fresh data.
+    Fetch,
+    /// Ask the worker for fresh data from the next mirror.
+    NextMirror,
+    /// Copy and save a summary of the row in the popup.
+    ExportSummary,
+    /// Copy and save the trail of the pilot in the popup.
+    ExportTrail,
+    /// Open the selected row's page in a web browser.
+    OpenStats,
+}
+
+/// The action for a terminal event in the app's current context, if any.
+pub fn map_event(app: &App, event: &Event) -> Option<Action> {
+    match event {
+        Event::Key(key)
+            if app.input_context() == InputContext::Table && app.shows_refresh_error() =>
+        {
+            map_refresh_error_key(*key).or_else(|| map_key(InputContext::Table, *key))
+        }
+        Event::Key(key) => map_key(app.input_context(), *key),
+        Event::Mouse(mouse) if app.input_context() == InputContext::Table => map_click(app, *mouse),
+        _ => None,
+    }
+}
+
+/// The action for clicking on a tab label or column header, if any.
+fn map_click(app: &App, mouse: MouseEvent) -> Option<Action> {
+    if mouse.kind != MouseEventKind::Down(MouseButton::Left) {
+        return None;
+    }
+    let action = match app.hit(mouse.column, mouse.row)? {
+        Region::Tab(tab_index) => Action::SelectTab(tab_index),
+        Region::Column(column) => Action::SortByColumn(column),
+    };
+    Some(action)
+}
+
+/// The action for a key press in the table while the refresh error panel
+/// is showing, which takes over some of the table's keys.
+fn map_refresh_error_key(key: KeyEvent) -> Option<Action> {
+    match key.code {
+        KeyCode::Char('r') => Some(Action::Retry),
+        KeyCode::Char('M') => Some(Action::NextMirror),
+        _ => None,
+    }
+}
+
+/// The action for a key press in a context, if any.
+fn map_key(context: InputContext, key: KeyEvent) -> Option<Action> {
+    match context {
+        InputContext::Loading { failed } => match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => Some(Action::Quit),
+            KeyCode::Char('r') if failed => Some(Action::Retry),
+            _ => None,
+        },
+        InputContext::FilterBuilder | InputContext::Search | InputContext::Tune => {
+            map_prompt_key(context, key)
+        }
+        InputContext::FacilityPicker => map_check_list_key(key),
+        InputContext::Popup if key.code == KeyCode::Char('s') => Some(Action::ExportSummary),
+        InputContext::Popup if key.code == KeyCode::Char('g') => Some(Action::ExportTrail),
+        InputContext::Popup => map_shared_key(key),
+        InputContext::LogPanel => map_log_key(key),
+        InputContext::Table => map_shared_key(key).or_else(|| map_table_key(key)),
+    }
+}
+
+/// The action for a key press in a prompt, where most keys type.
+fn map_prompt_key(context: InputContext, key: KeyEvent) -> Option<Action> {
+    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+    let builder = context == InputContext::FilterBuilder;
+    let history = context == InputContext::Search;
+    let action = match key.code {
+        KeyCode::Enter => Action::Submit,
+        KeyCode::Esc => Action::Cancel,
+        KeyCode::Char('n') if ctrl && builder => Action::AddRow,
+        KeyCode::Char('d') if ctrl && builder => Action::RemoveRow,
+        KeyCode::Char('r') if ctrl && history => Action::ReverseSearch,
+        KeyCode::Char('w') if ctrl => Action::Edit(Edit::DeleteWord),
+        KeyCode::Char('u') if ctrl => Action::Edit(Edit::DeleteToStart),
+        KeyCode::Backspace => Action::Edit(Edit::Backspace),
+        KeyCode::Delete => Action::Edit(Edit::Delete),
+        KeyCode::Left => Action::Edit(Edit::Left),
+        KeyCode::Right => Action::Edit(Edit::Right),
+        KeyCode::Home => Action::Edit(Edit::Home),
+        KeyCode::End => Action::Edit(Edit::End),
+        KeyCode::Tab if builder => Action::NextFocus,
+        KeyCode::Tab if history => Action::Complete,
+        KeyCode::Up if builder || history => Action::MoveSelection(-1),
+        KeyCode::Down if builder || history => Action::MoveSelection(1),
+        KeyCode::Char(c) if !ctrl => Action::InputChar(c),
+        _ => return None,
+    };
+    Some(action)
+}
+
+/// The action for a key press in a list of items to tick.
+fn map_check_list_key(key: KeyEvent) -> Option<Action> {
+    let action = match key.code {
+        KeyCode::Enter => Action::Submit,
+        KeyCode::Esc => Action::Cancel,
+        KeyCode::Char(' ' | 'x') => Action::ToggleItem,
+        KeyCode::Down => Action::MoveSelection(1),
+        KeyCode::Up => Action::MoveSelection(-1),
+        _ => return None,
+    };
+    Some(action)
+}
+
+/// The action for a key press that works in the table and with the popup open.
+fn map_shared_key(key: KeyEvent) -> Option<Action> {
+    let action = match key.code {
+        KeyCode::Char('q') => Action::Quit,
+        KeyCode::Char('D') => Action::ToggleDebug,
+        KeyCode::Char('o') => Action::OpenStats,
+        KeyCode::Enter => Action::OpenPopup,
+        KeyCode::Esc => Action::ClosePopup,
+        _ => return None,
+    };
+    Some(action)
+}
+
+/// The action for a key press with the log panel open.
+fn map_log_key(key: KeyEvent) -> Option<Action> {
+    let action = match key.code {
+        KeyCode::Char('q') => Action::Quit,
+        KeyCode::Char('L') | KeyCode::Esc => Action::ToggleLogPanel,
+        KeyCode::Down => Action::MoveSelection(1),
+        KeyCode::Up => Action::MoveSelection(-1),
+        KeyCode::PageDown => Action::MovePage(1),
+        KeyCode::PageUp => Action::MovePage(-1),
+        KeyCode::End | KeyCode::Char('f') => Action::FollowLog,
+        KeyCode::Char('v') => Action::CycleLogLevel,
+        _ => return None,
+    };
+    Some(action)
+}
+
+/// The action for a key press in the table.
+fn map_table_key(key: KeyEvent) -> Option<Action> {
+    let alt = key.modifiers.contains(KeyModifiers::ALT);
+    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+    let shift = key.modifiers.contains(KeyModifiers::SHIFT);
+    let action = match key.code {
+        KeyCode::Char('L') if ctrl => Action::ClearAllFilters,
+        KeyCode::Char('l') if ctrl && shift => Action::ClearAllFilters,
+        KeyCode::Char('l') if ctrl => Action::ClearFilter,
+        KeyCode::Char('L') => Action::ToggleLogPanel,
+        KeyCode::Right if alt => Action::ResizeColumn(1),
+        KeyCode::Left if alt => Action::ResizeColumn(-1),
+        KeyCode::Char('>') => Action::ResizeColumn(1),
+        KeyCode::Char('<') => Action::ResizeColumn(-1),
+        KeyCode::Down => Action::MoveSelection(1),
+        KeyCode::Up => Action::MoveSelection(-1),
+        KeyCode::PageDown => Action::MovePage(1),
+        KeyCode::PageUp => Action::MovePage(-1),
+        KeyCode::Tab => Action::ToggleTab,
+        KeyCode::Char('s') => Action::CycleSort,
+        KeyCode::Char('S') => Action::ReverseSort,
+        KeyCode::Char('0') => Action::ResetSort,
+        KeyCode::Right | KeyCode::Char('l') => Action::ScrollColumns(1),
+        KeyCode::Left | KeyCode::Char('h') => Action::ScrollColumns(-1),
+        KeyCode::Char('i') => Action::CycleRulesFilter,
+        KeyCode::Char('n') => Action::ToggleNoFlightPlan,
+        KeyCode::Char('A') => Action::ToggleNoAtis,
+        KeyCode::Char('p') => Action::CyclePhaseFilter,
+        KeyCode::Char('P') => Action::TogglePinFavorites,
+        KeyCode::Char('c') => Action::ToggleCoverage,
+        KeyCode::Char('R') => Action::ToggleRangeFilter,
+        KeyCode::Char('e') => Action::ToggleDepartingSoon,
+        KeyCode::Char('m') => Action::ToggleSpecialOnly,
+        KeyCode::Char('b') => Action::ToggleHideBadData,
+        KeyCode::Char('w') => Action::ToggleSplit,
+        KeyCode::Char('t') => Action::OpenTune,
+        KeyCode::Char('T') => Action::ToggleSquawkMismatches,
+        KeyCode::Char('V') => Action::OpenFacilityPicker,
+        KeyCode::Char('F') => Action::OpenFilterBuilder,
+        KeyCode::Char('/') => Action::OpenSearch,
+        KeyCode::Char('z') => Action::ToggleDensity,
+        KeyCode::Char('x') => Action::ToggleHideStale,
+        KeyCode::Char('g') => Action::ToggleCountGhosts,
+        _ => return None,
+    };
+    Some(action)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{models::V3ResponseData, testing};
+
+    fn key(code: KeyCode) -> Event {
+        Event::Key(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    fn ctrl(c: char) -> Event {
+        Event::Key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL))
+    }
+
+    fn loaded_app() -> App {
+        let mut app = App::new();
+        app.set_data(V3ResponseData {
+            pilots: vec![testing::flying("BAW123", "EGLL", "KJFK")],
+            controllers: vec![testing::controller("EGLL_TWR", 4)],
+            ..V3ResponseData::default()
+        });
+        app
+    }
+
+    /// Map an event and apply its action, like the interface loop does.
+    fn press(app: &mut App, event: &Event) -> Option<Effect> {
+        map_event(app, event).and_then(|action| app.update(action))
+    }
+
+    #[test]
+    fn q_quits_from_the_table() {
+        let mut app = loaded_app();
+        assert_eq!(app.input_context(), InputContext::Table);
+        assert_eq!(
+            map_event(&app, &key(KeyCode::Char('q'))),
+            Some(Action::Quit)
+        );
+        assert_eq!(
+            press(&mut app, &key(KeyCode::Char('q'))),
+            Some(Effect::Quit)
+        );
+    }
+
+    #[test]
+    fn refresh_error_panel_takes_over_r_and_m() {
+        let mut app = loaded_app();
+        assert_eq!(map_event(&app, &key(KeyCode::Char('r'))), None);
+        assert_eq!(map_event(&app, &key(KeyCode::Char('M'))), None);
+        for _ in 0..3 {
+            app.refresh_failed(&anyhow::anyhow!("timed out"));
+        }
+        assert_eq!(
+            press(&mut app, &key(KeyCode::Char('r'))),
+            Some(Effect::Fetch)
+        );
+        assert_eq!(
+            press(&mut app, &key(KeyCode::Char('M'))),
+            Some(Effect::NextMirror)
+        );
+        // the rest of the table's keys still work
+        assert_eq!(
+            map_event(&app, &key(KeyCode::Char('q'))),
+            Some(Action::Quit)
+        );
+        // and prompts get the letters
+        assert_eq!(press(&mut app, &key(KeyCode::Char('/'))), None);
+        assert_eq!(
+            map_event(&app, &key(KeyCode::Char('r'))),
+            Some(Action::InputChar('r'))
+        );
+        assert_eq!(press(&mut app, &key(KeyCode::Esc)), None);
+
+        app.refresh_succeeded();
+        assert_eq!(map_event(&app, &key(KeyCode::Char('r'))), None);
+    }
+
+    #[test]
+    fn q_types_into_the_search_prompt() {
+        let mut app = loaded_app();
+        assert_eq!(press(&mut app, &key(KeyCode::Char('/'))), None);
+        assert_eq!(app.input_context(), InputContext::Search);
+        assert_eq!(
+            map_event(&app, &key(KeyCode::Char('q'))),
+            Some(Action::InputChar('q'))
+        );
+        for c in "qatar".chars() {
+            assert_eq!(press(&mut app, &key(KeyCode::Char(c))), None);
+        }
+        let search = app.get_view_data().search.unwrap();
+        assert_eq!(search.input.text(), "qatar");
+
+        assert_eq!(press(&mut app, &key(KeyCode::Esc)), None);
+        assert_eq!(app.input_context(), InputContext::Table);
+    }
+
+    #[test]
+    fn prompt_keys_depend_on_the_prompt() {
+        let action = |context, event: Event| match event {
+            Event::Key(key) => map_key(context, key),
+            _ => unreachable!(),
+        };
+        assert_eq!(
+            action(InputContext::Search, ctrl('r')),
+            Some(Action::ReverseSearch)
+        );
+        assert_eq!(action(InputContext::Tune, ctrl('r')), None);
+        assert_eq!(
+            action(InputContext::FilterBuilder, ctrl('n')),
+            Some(Action::AddRow)
+        );
+        assert_eq!(action(InputContext::Search, ctrl('n')), None);
+        assert_eq!(
+            action(InputContext::Tune, ctrl('w')),
+            Some(Action::Edit(Edit::DeleteWord))
+        );
+        assert_eq!(
+            action(InputContext::FilterBuilder, key(KeyCode::Tab)),
+            Some(Action::NextFocus)
+        );
+        assert_eq!(
+            action(InputContext::Search, key(KeyCode::Tab)),
+            Some(Action::Complete)
+        );
+        assert_eq!(action(InputContext::Tune, key(KeyCode::Up)), None);
+    }
+
+    #[test]
+    fn table_keys_need_the_table() {
+        for context in [
+            InputContext::Popup,
+            InputContext::FacilityPicker,
+            InputContext::LogPanel,
+            InputContext::Loading { failed: false },
+        ] {
+            let mapped = map_key(
+                context,
+                KeyEvent::new(KeyCode::Char('s'), KeyModifiers::NONE),
+            );
+            assert_ne!(mapped, Some(Action::CycleSort), "{:?}", context);
+        }
+        assert_eq!(
+            map_key(
+                InputContext::Table,
+                KeyEvent::new(KeyCode::Char('s'), KeyModifiers::NONE)
+            ),
+            Some(Action::CycleSort)
+        );
+        assert_eq!(
+            map_key(
+                InputContext::Popup,
+                KeyEvent::new(KeyCode::Char('s'), KeyModifiers::NONE)
+            ),
+            Some(Action::ExportSummary)
+        );
+    }
+
+    #[test]
+    fn t_tunes_and_shift_t_shows_squawk_mismatches() {
+        let key = |c, modifiers| {
+            map_key(
+                InputContext::Table,
+                KeyEvent::new(KeyCode::Char(c), modifiers),
+            )
+        };
+        assert_eq!(key('t', KeyModifiers::NONE), Some(Action::OpenTune));
+        assert_eq!(
+            key('T', KeyModifiers::SHIFT),
+            Some(Action::ToggleSquawkMismatches)
+        );
+    }
+
+    #[test]
+    fn retry_only_after_a_failed_load() {
+        let r = KeyEvent::new(KeyCode::Char('r'), KeyModifiers::NONE);
+        assert_eq!(map_key(InputContext::Loading { failed: false }, r), None);
+        assert_eq!(
+            map_key(InputContext::Loading { failed: true }, r),
+            Some(Action::Retry)
+        );
+        // in the table, R is the range filter
+        assert_eq!(map_key(InputContext::Table, r), None);
+        assert_eq!(
+            map_key(
+                InputContext::Table,
+                KeyEvent::new(KeyCode::Char('R'), KeyModifiers::SHIFT)
+            ),
+            Some(Action::ToggleRangeFilter)
+        );
+    }
+
+    #[test]
+    fn popup_opens_and_closes() {
+        let mut app = loaded_app();
+        assert_eq!(press(&mut app, &key(KeyCode::Enter)), None);
+        assert_eq!(app.input_context(), InputContext::Popup);
+        assert_eq!(
+            press(&mut app, &key(KeyCode::Char('s'))),
+            Some(Effect::ExportSummary)
+        );
+        assert_eq!(press(&mut app, &key(KeyCode::Esc)), None);
+        assert_eq!(app.input_context(), InputContext::Table);
+        // exporting needs the popup
+        assert_eq!(app.update(Action::ExportSummary), None);
+    }
+
+    #[test]
+    fn clearing_filters_with_control_l() {
+        assert_eq!(
+            map_key(
+                InputContext::Table,
+                KeyEvent::new(KeyCode::Char('l'), KeyModifiers::CONTROL)
+            ),
+            Some(Action::ClearFilter)
+        );
+        assert_eq!(
+            map_key(
+                InputContext::Table,
+                KeyEvent::new(
+                    KeyCode::Char('l'),
+                    KeyModifiers::CONTROL | KeyModifiers::SHIFT
+                )
+            ),
+            Some(Action::ClearAllFilters)
+        );
+        assert_eq!(
+            map_key(
+                InputContext::Table,
+                KeyEvent::new(KeyCode::Char('L'), KeyModifiers::NONE)
+            ),
+            Some(Action::ToggleLogPanel)
+        );
+    }
+}
@@ -0,0 +1,211 @@
+//! Small embedded airport database.
+//!
+//! This isn't meant to be exhaustive; it covers the busier airports on the
+//! network so that controller callsigns and flight plan airports can be
+//! resolved to names and coordinates without a network lookup.
+
+/// A single airport.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Airport {
+    /// 4-letter ICAO code, like "KSFO".
+    pub icao: &'static str,
+    /// 3-letter IATA/FAA code, like "SFO".
+    pub iata: &'static str,
+    /// Name used on the radio, like "San Francisco".
+    pub name: &'static str,
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+/// Shorthand for building the table below.
+const fn airport(
+    icao: &'static str,
+    iata: &'static str,
+    name: &'static str,
+    latitude: f64,
+    longitude: f64,
+) -> Airport {
+    Airport {
+        icao,
+        iata,
+        name,
+        latitude,
+        longitude,
+    }
+}
+
+/// Embedded airport data.
+static AIRPORTS: &[Airport] = &[
+    // United States
+    airport("KATL", "ATL", "Atlanta", 33.64, -84.43),
+    airport("KAUS", "AUS", "Austin", 30.19, -97.67),
+    airport("KBNA", "BNA", "Nashville", 36.12, -86.68),
+    airport("KBOS", "BOS", "Boston", 42.36, -71.01),
+    airport("KBUR", "BUR", "Burbank", 34.20, -118.36),
+    airport("KBWI", "BWI", "Baltimore", 39.18, -76.67),
+    airport("KCLE", "CLE", "Cleveland", 41.41, -81.85),
+    airport("KCLT", "CLT", "Charlotte", 35.21, -80.94),
+    airport("KCVG", "CVG", "Cincinnati", 39.05, -84.67),
+    airport("KDCA", "DCA", "Washington", 38.85, -77.04),
+    airport("KDEN", "DEN", "Denver", 39.86, -104.67),
+    airport("KDFW", "DFW", "Dallas-Fort Worth", 32.90, -97.04),
+    airport("KDTW", "DTW", "Detroit", 42.21, -83.35),
+    airport("KEWR", "EWR", "Newark", 40.69, -74.17),
+    airport("KFLL", "FLL", "Fort Lauderdale", 26.07, -80.15),
+    airport("KHOU", "HOU", "Hobby", 29.65, -95.28),
+    airport("KIAD", "IAD", "Dulles", 38.95, -77.46),
+    airport("KIAH", "IAH", "Houston", 29.98, -95.34),
+    airport("KIND", "IND", "Indianapolis", 39.72, -86.29),
+    airport("KJFK", "JFK", "Kennedy", 40.64, -73.78),
+    airport("KLAS", "LAS", "Las Vegas", 36.08, -115.15),
+    airport("KLAX", "LAX", "Los Angeles", 33.94, -118.41),
+    airport("KLGA", "LGA", "LaGuardia", 40.78, -73.87),
+    airport("KMCI", "MCI", "Kansas City", 39.30, -94.71),
+    airport("KMCO", "MCO", "Orlando", 28.43, -81.31),
+    airport("KMDW", "MDW", "Midway", 41.79, -87.75),
+    airport("KMEM", "MEM", "Memphis", 35.04, -89.98),
+    airport("KMIA", "MIA", "Miami", 25.80, -80.29),
+    airport("KMSP", "MSP", "Minneapolis", 44.88, -93.22),
+    airport("KMSY", "MSY", "New Orleans", 29.99, -90.26),
+    airport("KOAK", "OAK", "Oakland", 37.72, -122.22),
+    airport("KONT", "ONT", "Ontario", 34.06, -117.60),
+    airport("KORD", "ORD", "Chicago O'Hare", 41.98, -87.90),
+    airport("KPDX", "PDX", "Portland", 45.59, -122.60),
+    airport("KPHL", "PHL", "Philadelphia", 39.87, -75.24),
+    airport("KPHX", "PHX", "Phoenix", 33.43, -112.01),
+    airport("KPIT", "PIT", "Pittsburgh", 40.49, -80.23),
+    airport("KRDU", "RDU", "Raleigh-Durham", 35.88, -78.79),
+    airport("KSAN", "SAN", "San Diego", 32.73, -117.19),
+    airport("KSEA", "SEA", "Seattle", 47.45, -122.31),
+    airport("KSFO", "SFO", "San Francisco", 37.62, -122.38),
+    airport("KSJC", "SJC", "San Jose", 37.36, -121.93),
+    airport("KSLC", "SLC", "Salt Lake City", 40.79, -111.98),
+    airport("KSMF", "SMF", "Sacramento", 38.70, -121.59),
+    airport("KSNA", "SNA", "John Wayne", 33.68, -117.87),
+    airport("KSTL", "STL", "St. Louis", 38.75, -90.37),
+    airport("KTPA", "TPA", "Tampa", 27.98, -82.53),
+    airport("PANC", "ANC", "Anchorage", 61.17, -150.00),
+    airport("PHNL", "HNL", "Honolulu", 21.32, -157.92),
+    // Canada
+    airport("CYEG", "YEG", "Edmonton", 53.31, -113.58),
+    airport("CYHZ", "YHZ", "Halifax", 44.88, -63.51),
+    airport("CYOW", "YOW", "Ottawa", 45.32, -75.67),
+    airport("CYUL", "YUL", "Montreal", 45.47, -73.74),
+    airport("CYVR", "YVR", "Vancouver", 49.19, -123.18),
+    airport("CYWG", "YWG", "Winnipeg", 49.91, -97.24),
+    airport("CYYC", "YYC", "Calgary", 51.13, -114.01),
+    airport("CYYZ", "YYZ", "Toronto", 43.68, -79.63),
+    // Europe
+    airport("BIKF", "KEF", "Keflavik", 63.98, -22.61),
+    airport("EBBR", "BRU", "Brussels", 50.90, 4.48),
+    airport("EDDB", "BER", "Berlin", 52.37, 13.50),
+    airport("EDDF", "FRA", "Frankfurt", 50.03, 8.57),
+    airport("EDDH", "HAM", "Hamburg", 53.63, 9.99),
+    airport("EDDK", "CGN", "Cologne", 50.87, 7.14),
+    airport("EDDL", "DUS", "Dusseldorf", 51.29, 6.77),
+    airport("EDDM", "MUC", "Munich", 48.35, 11.79),
+    airport("EDDS", "STR", "Stuttgart", 48.69, 9.22),
+    airport("EFHK", "HEL", "Helsinki", 60.32, 24.96),
+    airport("EGBB", "BHX", "Birmingham", 52.45, -1.75),
+    airport("EGCC", "MAN", "Manchester", 53.35, -2.27),
+    airport("EGGD", "BRS", "Bristol", 51.38, -2.72),
+    airport("EGGW", "LTN", "Luton", 51.87, -0.37),
+    airport("EGKK", "LGW", "Gatwick", 51.15, -0.19),
+    airport("EGLC", "LCY", "London City", 51.51, 0.06),
+    airport("EGLL", "LHR", "Heathrow", 51.47, -0.45),
+    airport("EGNX", "EMA", "East Midlands", 52.83, -1.33),
+    airport("EGPF", "GLA", "Glasgow", 55.87, -4.43),
+    airport("EGPH", "EDI", "Edinburgh", 55.95, -3.37),
+    airport("EGSS", "STN", "Stansted", 51.89, 0.24),
+    airport("EHAM", "AMS", "Schiphol", 52.31, 4.76),
+    airport("EIDW", "DUB", "Dublin", 53.42, -6.27),
+    airport("EKCH", "CPH", "Copenhagen", 55.62, 12.66),
+    airport("ENGM", "OSL", "Oslo", 60.19, 11.10),
+    airport("EPWA", "WAW", "Warsaw", 52.17, 20.97),
+    airport("ESSA", "ARN", "Arlanda", 59.65, 17.92),
+    airport("LEBL", "BCN", "Barcelona", 41.30, 2.08),
+    airport("LEMD", "MAD", "Madrid", 40.47, -3.56),
+    airport("LEPA", "PMI", "Palma", 39.55, 2.74),
+    airport("LFLL", "LYS", "Lyon", 45.73, 5.08),
+    airport("LFMN", "NCE", "Nice", 43.66, 7.22),
+    airport("LFPG", "CDG", "De Gaulle", 49.01, 2.55),
+    airport("LFPO", "ORY", "Orly", 48.72, 2.38),
+    airport("LGAV", "ATH", "Athens", 37.94, 23.94),
+    airport("LHBP", "BUD", "Budapest", 47.44, 19.26),
+    airport("LIMC", "MXP", "Malpensa", 45.63, 8.72),
+    airport("LIRF", "FCO", "Roma", 41.80, 12.25),
+    airport("LKPR", "PRG", "Praha", 50.10, 14.26),
+    airport("LOWW", "VIE", "Wien", 48.11, 16.57),
+    airport("LPPT", "LIS", "Lisboa", 38.78, -9.13),
+    airport("LSGG", "GVA", "Geneva", 46.24, 6.11),
+    airport("LSZH", "ZRH", "Zurich", 47.46, 8.55),
+    airport("LTFM", "IST", "Istanbul", 41.26, 28.74),
+    airport("UUEE", "SVO", "Sheremetyevo", 55.97, 37.41),
+    // Middle East and Asia
+    airport("LLBG", "TLV", "Ben Gurion", 32.01, 34.89),
+    airport("OERK", "RUH", "Riyadh", 24.96, 46.70),
+    airport("OMDB", "DXB", "Dubai", 25.25, 55.36),
+    airport("OTHH", "DOH", "Hamad", 25.27, 51.61),
+    airport("RJAA", "NRT", "Narita", 35.76, 140.39),
+    airport("RJTT", "HND", "Tokyo", 35.55, 139.78),
+    airport("RKSI", "ICN", "Incheon", 37.46, 126.44),
+    airport("RPLL", "MNL", "Manila", 14.51, 121.02),
+    airport("VABB", "BOM", "Mumbai", 19.09, 72.87),
+    airport("VHHH", "HKG", "Hong Kong", 22.31, 113.92),
+    airport("VIDP", "DEL", "Delhi", 28.57, 77.10),
+    airport("VTBS", "BKK", "Bangkok", 13.69, 100.75),
+    airport("WIII", "CGK", "Jakarta", -6.13, 106.66),
+    airport("WMKK", "KUL", "Kuala Lumpur", 2.75, 101.71),
+    airport("WSSS", "SIN", "Singapore", 1.36, 103.99),
+    airport("ZBAA", "PEK", "Beijing", 40.08, 116.58),
+    airport("ZSPD", "PVG", "Pudong", 31.14, 121.81),
+    // Oceania
+    airport("NZAA", "AKL", "Auckland", -37.01, 174.79),
+    airport("NZCH", "CHC", "Christchurch", -43.49, 172.53),
+    airport("NZWN", "WLG", "Wellington", -41.33, 174.81),
+    airport("YBBN", "BNE", "Brisbane", -27.38, 153.12),
+    airport("YMML", "MEL", "Melbourne", -37.67, 144.84),
+    airport("YPPH", "PER", "Perth", -31.94, 115.97),
+    airport("YSSY", "SYD", "Sydney", -33.95, 151.18),
+    // Central and South America
+    airport("MMMX", "MEX", "Mexico", 19.44, -99.07),
+    airport("MMUN", "CUN", "Cancun", 21.04, -86.87),
+    airport("MPTO", "PTY", "Tocumen", 9.07, -79.38),
+    airport("SAEZ", "EZE", "Ezeiza", -34.82, -58.54),
+    airport("SBGL", "GIG", "Galeao", -22.81, -43.25),
+    airport("SBGR", "GRU", "Guarulhos", -23.43, -46.47),
+    airport("SCEL", "SCL", "Santiago", -33.39, -70.79),
+    airport("SKBO", "BOG", "Bogota", 4.70, -74.15),
+    airport("SPJC", "LIM", "Lima", -12.02, -77.11),
+    airport("TNCM", "SXM", "Juliana", 18.04, -63.11),
+    // Africa
+    airport("DNMM", "LOS", "Lagos", 6.58, 3.32),
+    airport("FACT", "CPT", "Cape Town", -33.97, 18.60),
+    airport("FAOR", "JNB", "Johannesburg", -26.14, 28.25),
+    airport("GMMN", "CMN", "Casablanca", 33.37, -7.59),
+    airport("HECA", "CAI", "Cairo", 30.12, 31.41),
+    airport("HKJK", "NBO", "Nairobi", -1.32, 36.93),
+];
+
+/// Look up an airport by its 4-letter ICAO code.
+#[must_use]
+pub fn by_icao(icao: &str) -> Option<&'static Airport> {
+    AIRPORTS
+        .iter()
+        .find(|airport| airport.icao.eq_ignore_ascii_case(icao))
+}
+
+/// Look up an airport by the prefix of a controller callsign.
+///
+/// Prefixes are usually the ICAO code ("EGLL"), but in North America
+/// the 3-letter FAA/IATA code ("SFO") is the norm.
+#[must_use]
+pub fn by_prefix(prefix: &str) -> Option<&'static Airport> {
+    match prefix.len() {
+        4 => by_icao(prefix),
+        3 => AIRPORTS
+            .iter()
+            .find(|airport| airport.iata.eq_ignore_ascii_case(prefix)),
+        _ => None,
+    }
+}
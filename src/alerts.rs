@@ -0,0 +1,379 @@
+//! Sounds for things worth looking up from another window for.
+//!
+//! The sound is the terminal bell, or a command that plays one, like
+//! `paplay bell.oga`; playing audio files in-process would take an audio
+//! stack the builds don't otherwise need, so it's left to the command.
+
+use crate::{
+    diff::{self, ChangeEvent},
+    favorites::Favorites,
+    models::V3ResponseData,
+};
+use anyhow::{anyhow, Result};
+use log::warn;
+use std::{
+    collections::HashSet,
+    io::Write,
+    process::{self, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Transponder codes for hijacking, lost radio, and general emergencies.
+const EMERGENCY_SQUAWKS: [&str; 3] = ["7500", "7600", "7700"];
+
+/// Something that can set off an alert.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AlertKind {
+    /// A favorite pilot or controller connected.
+    FavoriteOnline,
+    /// A pilot started squawking an emergency code.
+    Emergency,
+}
+
+impl AlertKind {
+    const ALL: [AlertKind; 2] = [AlertKind::FavoriteOnline, AlertKind::Emergency];
+
+    /// Name of the kind on the command line.
+    fn name(self) -> &'static str {
+        match self {
+            AlertKind::FavoriteOnline => "favorites",
+            AlertKind::Emergency => "emergency",
+        }
+    }
+}
+
+/// Which alerts are on, and how often one can sound.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AlertSettings {
+    pub enabled: HashSet<AlertKind>,
+    pub min_interval: Duration,
+}
+
+/// Parse an alert kind by its name on the command line, like "favorites".
+pub fn parse_kind(name: &str) -> Result<AlertKind> {
+    AlertKind::ALL
+        .into_iter()
+        .find(|kind| kind.name().eq_ignore_ascii_case(name.trim()))
+        .ok_or_else(|| {
+            anyhow!(
+                "Unknown alert '{}', expected: {}",
+                name.trim(),
+                AlertKind::ALL.map(AlertKind::name).join(", ")
+            )
+        })
+}
+
+/// Parse comma-separated alert kinds from the command line, like "favorites,emergency".
+pub fn parse_kinds(value: &str) -> Result<HashSet<AlertKind>> {
+    value
+        .split(',')
+        .filter(|name| !name.trim().is_empty())
+        .map(parse_kind)
+        .collect()
+}
+
+/// Whether a pilot is squawking an emergency code.
+fn is_emergency(transponder: &str) -> bool {
+    EMERGENCY_SQUAWKS.contains(&transponder.trim())
+}
+
+/// What in the new data is worth an alert, given the changes from the old
+/// data.
+pub fn detect(
+    events: &[ChangeEvent],
+    old: &V3ResponseData,
+    new: &V3ResponseData,
+    favorites: &Favorites,
+) -> Vec<AlertKind> {
+    let mut kinds = Vec::new();
+    let favorite_joined = events.iter().any(|event| match event {
+        ChangeEvent::PilotConnected { cid, callsign }
+        | ChangeEvent::ControllerOnline { cid, callsign } => favorites.contains(*cid, callsign),
+        _ => false,
+    });
+    if favorite_joined {
+        kinds.push(AlertKind::FavoriteOnline);
+    }
+    let old_emergencies: HashSet<i64> = old
+        .pilots
+        .iter()
+        .filter(|p| is_emergency(&p.transponder))
+        .map(|p| p.cid)
+        .collect();
+    if new
+        .pilots
+        .iter()
+        .any(|p| is_emergency(&p.transponder) && !old_emergencies.contains(&p.cid))
+    {
+        kinds.push(AlertKind::Emergency);
+    }
+    kinds
+}
+
+/// What in the latest refresh is worth an alert, until it's taken to
+/// sound.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PendingAlerts {
+    kinds: Vec<AlertKind>,
+}
+
+impl PendingAlerts {
+    /// Find what's worth an alert in the changes from the old data to the
+    /// new, replacing anything not taken.
+    pub fn update(&mut self, old: &V3ResponseData, new: &V3ResponseData, favorites: &Favorites) {
+        let events = diff::diff_snapshots(old, new);
+        self.kinds = detect(&events, old, new, favorites);
+    }
+
+    /// What was found, if it hasn't been taken yet.
+    pub fn take(&mut self) -> Vec<AlertKind> {
+        std::mem::take(&mut self.kinds)
+    }
+}
+
+/// Makes the alert sound.
+pub trait Sound {
+    fn play(&mut self);
+}
+
+/// The terminal bell.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Bell;
+
+impl Sound for Bell {
+    fn play(&mut self) {
+        let mut stdout = std::io::stdout();
+        let _ = stdout.write_all(b"\x07").and_then(|()| stdout.flush());
+    }
+}
+
+/// The sound alerts make.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum AlertSound {
+    #[default]
+    Bell,
+    /// A shell command, run without waiting for it.
+    Command(String),
+}
+
+impl Sound for AlertSound {
+    fn play(&mut self) {
+        match self {
+            AlertSound::Bell => Bell.play(),
+            AlertSound::Command(command) => {
+                if let Err(e) = run_detached(command) {
+                    warn!("Could not run alert command '{}': {}", command, e);
+                }
+            }
+        }
+    }
+}
+
+/// Start a shell command with nothing to read or write, reaping it on
+/// another thread once it's done.
+fn run_detached(command: &str) -> std::io::Result<()> {
+    let (shell, flag) = if cfg!(windows) {
+        ("cmd", "/C")
+    } else {
+        ("sh", "-c")
+    };
+    let mut child = process::Command::new(shell)
+        .args([flag, command])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let _ = thread::spawn(move || child.wait());
+    Ok(())
+}
+
+/// Decides which alerts sound, no more often than the minimum interval.
+#[derive(Debug)]
+pub struct Alerter<S: Sound> {
+    settings: AlertSettings,
+    sound: S,
+    last_played: Option<Instant>,
+}
+
+impl<S: Sound> Alerter<S> {
+    pub fn new(settings: AlertSettings, sound: S) -> Self {
+        Self {
+            settings,
+            sound,
+            last_played: None,
+        }
+    }
+
+    /// Whether an alert of this kind should sound now.
+    fn should_play(&self, kind: AlertKind, now: Instant) -> bool {
+        self.settings.enabled.contains(&kind)
+            && self
+                .last_played
+                .is_none_or(|last| now.duration_since(last) >= self.settings.min_interval)
+    }
+
+    /// Sound one alert for the kinds that are on, if it's been long enough
+    /// since the last one.
+    pub fn alert(&mut self, kinds: &[AlertKind], now: Instant) {
+        if kinds.iter().any(|&kind| self.should_play(kind, now)) {
+            self.sound.play();
+            self.last_played = Some(now);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{models::Pilot, testing};
+
+    /// Counts how many times it was played.
+    #[derive(Debug, Default)]
+    struct Recording {
+        plays: usize,
+    }
+
+    impl Sound for Recording {
+        fn play(&mut self) {
+            self.plays += 1;
+        }
+    }
+
+    fn alerter(enabled: &[AlertKind]) -> Alerter<Recording> {
+        let settings = AlertSettings {
+            enabled: enabled.iter().copied().collect(),
+            min_interval: Duration::from_secs(30),
+        };
+        Alerter::new(settings, Recording::default())
+    }
+
+    fn squawking(cid: i64, transponder: &str) -> Pilot {
+        Pilot {
+            cid,
+            transponder: transponder.to_owned(),
+            ..testing::pilot("BAW1")
+        }
+    }
+
+    fn pilots(pilots: Vec<Pilot>) -> V3ResponseData {
+        V3ResponseData {
+            pilots,
+            ..V3ResponseData::default()
+        }
+    }
+
+    #[test]
+    fn parses_kinds() {
+        assert_eq!(
+            parse_kinds(" Favorites, emergency,").unwrap(),
+            HashSet::from([AlertKind::FavoriteOnline, AlertKind::Emergency])
+        );
+        assert!(parse_kinds("").unwrap().is_empty());
+        assert_eq!(
+            parse_kinds("favorites,inbound").unwrap_err().to_string(),
+            "Unknown alert 'inbound', expected: favorites, emergency"
+        );
+    }
+
+    #[test]
+    fn detects_favorites_connecting() {
+        let mut favorites = Favorites::default();
+        assert!(favorites.add("EGLL_TWR").unwrap());
+        let data = V3ResponseData::default();
+        let online = |callsign: &str| ChangeEvent::ControllerOnline {
+            cid: 1,
+            callsign: callsign.to_owned(),
+        };
+        assert_eq!(
+            detect(&[online("EGLL_TWR")], &data, &data, &favorites),
+            [AlertKind::FavoriteOnline]
+        );
+        assert!(detect(&[online("EGKK_TWR")], &data, &data, &favorites).is_empty());
+        let offline = ChangeEvent::ControllerOffline {
+            cid: 1,
+            callsign: "EGLL_TWR".to_owned(),
+        };
+        assert!(detect(&[offline], &data, &data, &favorites).is_empty());
+    }
+
+    #[test]
+    fn detects_only_new_emergencies() {
+        let favorites = Favorites::default();
+        let quiet = pilots(vec![squawking(1, "2000"), squawking(2, "7700")]);
+        let hijack = pilots(vec![squawking(1, " 7500 "), squawking(2, "7700")]);
+        assert_eq!(
+            detect(&[], &quiet, &hijack, &favorites),
+            [AlertKind::Emergency]
+        );
+        assert!(detect(&[], &quiet, &quiet, &favorites).is_empty());
+        assert!(detect(&[], &hijack, &hijack, &favorites).is_empty());
+        let resolved = pilots(vec![squawking(1, "2000")]);
+        assert!(detect(&[], &hijack, &resolved, &favorites).is_empty());
+    }
+
+    #[test]
+    fn pending_alerts_are_taken_once() {
+        let quiet = pilots(vec![squawking(1, "2000")]);
+        let hijack = pilots(vec![squawking(1, "7500")]);
+        let mut pending = PendingAlerts::default();
+        pending.update(&quiet, &hijack, &Favorites::default());
+        assert_eq!(pending.take(), [AlertKind::Emergency]);
+        assert!(pending.take().is_empty());
+    }
+
+    #[test]
+    fn disabled_by_default() {
+        let mut alerter = Alerter::new(AlertSettings::default(), Recording::default());
+        alerter.alert(
+            &[AlertKind::FavoriteOnline, AlertKind::Emergency],
+            Instant::now(),
+        );
+        assert_eq!(alerter.sound.plays, 0);
+    }
+
+    #[test]
+    fn only_enabled_kinds_sound() {
+        let mut alerter = alerter(&[AlertKind::Emergency]);
+        let now = Instant::now();
+        alerter.alert(&[AlertKind::FavoriteOnline], now);
+        assert_eq!(alerter.sound.plays, 0);
+        alerter.alert(&[AlertKind::FavoriteOnline, AlertKind::Emergency], now);
+        assert_eq!(alerter.sound.plays, 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn commands_play_without_blocking() {
+        let dir = tempfile::tempdir().unwrap();
+        let played = dir.path().join("played");
+        let mut sound = AlertSound::Command(format!("sleep 0.2; touch '{}'", played.display()));
+        let start = Instant::now();
+        sound.play();
+        assert!(start.elapsed() < Duration::from_millis(200));
+        while !played.exists() {
+            assert!(start.elapsed() < Duration::from_secs(10));
+            thread::sleep(Duration::from_millis(10));
+        }
+        // a command that can't run is only logged
+        AlertSound::Command("exit 1".to_owned()).play();
+    }
+
+    #[test]
+    fn rate_limited_to_the_interval() {
+        let mut alerter = alerter(&[AlertKind::FavoriteOnline, AlertKind::Emergency]);
+        let start = Instant::now();
+        alerter.alert(&[AlertKind::Emergency], start);
+        alerter.alert(
+            &[AlertKind::FavoriteOnline],
+            start + Duration::from_secs(29),
+        );
+        assert_eq!(alerter.sound.plays, 1);
+        alerter.alert(&[], start + Duration::from_secs(30));
+        assert_eq!(alerter.sound.plays, 1);
+        alerter.alert(&[AlertKind::Emergency], start + Duration::from_secs(30));
+        assert_eq!(alerter.sound.plays, 2);
+        alerter.alert(&[AlertKind::Emergency], start + Duration::from_secs(59));
+        assert_eq!(alerter.sound.plays, 2);
+    }
+}
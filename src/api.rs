@@ -1,81 +1,470 @@
-use crate::models::{Status, V3ResponseData};
-use anyhow::{anyhow, Result};
-use log::debug;
-use rand::seq::SliceRandom;
-use reqwest::blocking::{Client, ClientBuilder};
+use crate::{
+    dump::{self, DumpSettings},
+    mirrors::{self, Head, MirrorLatency, MirrorSelection, Mirrors},
+    models::{Event, EventsResponse, Status, V3ResponseData},
+};
+use anyhow::{anyhow, bail, Result};
+use chrono::Utc;
+use log::{debug, warn};
+use reqwest::{
+    blocking::{Client, ClientBuilder},
+    header::HeaderValue,
+};
+use std::{
+    path::PathBuf,
+    thread,
+    time::{Duration, Instant},
+};
 
-/// Initial VATSIM API requests are made to this endpoint.
-const STATUS_URL: &str = "https://status.vatsim.net/status.json";
+/// User agent sent with requests unless overridden.
+pub const DEFAULT_USER_AGENT: &str =
+    concat!("github.com/celeo/vatsim_online/", env!("CARGO_PKG_VERSION"));
+
+/// Parse a user agent override, rejecting text that can't be sent in a header.
+///
+/// # Errors
+///
+/// If the value is empty or has characters a header can't carry.
+pub fn parse_user_agent(value: &str) -> Result<HeaderValue> {
+    let value = value.trim();
+    if value.is_empty() {
+        bail!("user agent can't be empty");
+    }
+    HeaderValue::from_str(value).map_err(|_| {
+        anyhow!(
+            "user agent {:?} can only contain visible ASCII characters and spaces",
+            value
+        )
+    })
+}
 
 /// API struct.
+#[derive(Debug)]
 pub struct Vatsim {
     client: Client,
-    v3_url: String,
+    mirrors: Mirrors,
+    timings: FetchTimings,
+    /// Where to save raw V3 bodies, if anywhere.
+    dumps: Option<DumpSettings>,
+    /// Body saved since the last call to `take_dump`.
+    last_dump: Option<PathBuf>,
+    /// Why the last `get_data` call moved on from a mirror, if it did.
+    fallbacks: Vec<String>,
+    /// V3 mirrors the last `get_data` call failed on, in order.
+    failed_mirrors: Vec<String>,
+}
+
+/// How long the last V3 data fetch spent on the network and parsing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FetchTimings {
+    pub fetch: Duration,
+    pub parse: Duration,
+}
+
+/// A V3 response before its body is parsed.
+#[derive(Debug)]
+pub enum RawResponse {
+    /// The server said the data hasn't changed since the last request.
+    NotModified,
+    Body {
+        content_type: Option<String>,
+        body: Vec<u8>,
+    },
+}
+
+/// Outcome of querying the V3 endpoint.
+#[derive(Debug)]
+pub enum DataResponse {
+    Changed(V3ResponseData),
+    /// The server said the data hasn't changed since the last request.
+    NotModified,
+    /// The server sent the same body as last time, so it wasn't parsed.
+    IdenticalBody,
 }
 
 impl Vatsim {
     /// New API struct instance.
     ///
     /// Makes the API call to the status endpoint to get the endpoint
-    /// to make V3 API calls.
-    pub fn new() -> Result<Self> {
+    /// to make V3 API calls. If `use_cache` is set, a recently-cached
+    /// status document is used instead of making the call.
+    ///
+    /// In `low_bandwidth` mode, responses are requested compressed and
+    /// data requests are made conditional on the data having changed.
+    ///
+    /// # Errors
+    ///
+    /// If the client can't be built, or the status document can't be
+    /// fetched or lists no V3 mirrors.
+    pub fn new(use_cache: bool, low_bandwidth: bool, user_agent: &HeaderValue) -> Result<Self> {
+        Vatsim::with_mirror(
+            use_cache,
+            low_bandwidth,
+            user_agent,
+            &MirrorSelection::default(),
+        )
+    }
+
+    /// New API struct instance using a chosen V3 mirror.
+    ///
+    /// A mirror given by URL is used without calling the status endpoint,
+    /// unless failover is allowed and the other mirrors are needed.
+    ///
+    /// # Errors
+    ///
+    /// If the client can't be built, the status document is needed and
+    /// can't be fetched, or the chosen mirror isn't in it.
+    pub fn with_mirror(
+        use_cache: bool,
+        low_bandwidth: bool,
+        user_agent: &HeaderValue,
+        selection: &MirrorSelection,
+    ) -> Result<Self> {
         debug!("Creating VATSIM struct instance");
         let client = ClientBuilder::new()
-            .user_agent("github.com/celeo/vatsim_online")
+            .user_agent(user_agent.clone())
+            .gzip(low_bandwidth)
             .build()?;
-        let url = Vatsim::get_v3_url(&client)?;
+        let status = if selection.needs_status() {
+            match mirrors::cached_status(use_cache) {
+                Some(status) => Some(status),
+                None => Some(Vatsim::get_status(&client)?),
+            }
+        } else {
+            None
+        };
+        let latencies = Vatsim::race(
+            &client,
+            &Mirrors::to_race(status.as_ref(), selection),
+            mirrors::RACE_TIMEOUT,
+        );
+        let mirrors = Mirrors::new(status, low_bandwidth, selection, latencies)?;
         Ok(Self {
             client,
-            v3_url: url,
+            mirrors,
+            timings: FetchTimings::default(),
+            dumps: None,
+            last_dump: None,
+            fallbacks: Vec::new(),
+            failed_mirrors: Vec::new(),
         })
     }
 
-    /// Get the V3 URL by querying the status endpoint.
-    fn get_v3_url(client: &Client) -> Result<String> {
-        debug!("Getting V3 url from status page");
-        let response = client.get(STATUS_URL).send()?;
-        if !response.status().is_success() {
-            return Err(anyhow!(
-                "Got status {} from status endpoint",
-                response.status().as_u16()
-            ));
+    /// Send a HEAD request to each mirror at once, timing how long each
+    /// takes to answer.
+    ///
+    /// Each request gives up after `timeout`, so this takes at most that
+    /// long.
+    fn race(client: &Client, urls: &[String], timeout: Duration) -> Vec<MirrorLatency> {
+        if urls.is_empty() {
+            return Vec::new();
         }
+        debug!("Racing {} V3 mirrors", urls.len());
+        thread::scope(|scope| {
+            let racers: Vec<_> = urls
+                .iter()
+                .map(|url| {
+                    scope.spawn(move || {
+                        let start = Instant::now();
+                        let status = client
+                            .head(url)
+                            .timeout(timeout)
+                            .send()
+                            .map(|response| response.status());
+                        MirrorLatency::measured(url, start.elapsed(), status)
+                    })
+                })
+                .collect();
+            racers
+                .into_iter()
+                .zip(urls)
+                .map(|(racer, url)| {
+                    racer.join().unwrap_or_else(|_| MirrorLatency {
+                        url: url.clone(),
+                        latency: None,
+                    })
+                })
+                .collect()
+        })
+    }
+
+    /// Query the status endpoint.
+    fn get_status(client: &Client) -> Result<Status> {
+        debug!("Getting status page");
+        let response = client.get(mirrors::status_url()).send()?;
+        mirrors::check_status(response.status(), "status")?;
         let data: Status = response.json()?;
-        let url = data
-            .data
-            .v3
-            .choose(&mut rand::thread_rng())
-            .ok_or_else(|| anyhow!("No V3 URLs returned"))?
-            .clone();
-        debug!("V3 URL: {}", url);
-        Ok(url)
-    }
-
-    /// Query the stored V3 endpoint.
-    pub fn get_data(&self) -> Result<V3ResponseData> {
-        debug!("Getting current data");
-        let response = self.client.get(&self.v3_url).send()?;
-        if !response.status().is_success() {
-            return Err(anyhow!(
-                "Got status {} from status endpoint",
-                response.status().as_u16()
-            ));
-        }
-        let mut data: V3ResponseData = response.json()?;
-        data.pilots
-            .sort_by(|a, b| a.callsign.partial_cmp(&b.callsign).unwrap());
-        data.controllers
-            .sort_by(|a, b| a.callsign.partial_cmp(&b.callsign).unwrap());
+        mirrors::status_fetched(&data);
         Ok(data)
     }
 
-    /// Look up a controller's rating in the data.
+    /// Number of V3 mirrors listed in the status document.
+    #[must_use]
+    pub fn mirror_count(&self) -> usize {
+        self.mirrors.count()
+    }
+
+    /// How quickly each mirror answered, if they were raced.
+    #[must_use]
+    pub fn mirror_latencies(&self) -> &[MirrorLatency] {
+        self.mirrors.latencies()
+    }
+
+    /// URL of the V3 mirror in use.
+    #[must_use]
+    pub fn mirror_url(&self) -> &str {
+        self.mirrors.url()
+    }
+
+    /// Use the next V3 mirror from now on.
+    pub fn next_mirror(&mut self) {
+        self.mirrors.next();
+    }
+
+    /// Use the first V3 mirror from the current one on that isn't in
+    /// `tried`, if there is one.
+    pub fn skip_tried_mirrors(&mut self, tried: &[String]) {
+        self.mirrors.skip_tried(tried);
+    }
+
+    /// Whether the status document listed a METAR endpoint.
+    #[must_use]
+    pub fn has_metar(&self) -> bool {
+        self.mirrors.metar_url().is_some()
+    }
+
+    /// Get the current METAR for an airport.
+    ///
+    /// # Errors
+    ///
+    /// If the status document listed no METAR endpoint, the request
+    /// fails, or it has no METAR for the airport.
+    pub fn get_metar(&self, icao: &str) -> Result<String> {
+        let url = self
+            .mirrors
+            .metar_url()
+            .ok_or_else(|| anyhow!("No METAR URLs returned"))?;
+        debug!("Getting METAR for {} from {}", icao, url);
+        let response = self.client.get(url).query(&[("id", icao)]).send()?;
+        mirrors::check_status(response.status(), "METAR")?;
+        mirrors::read_metar(icao, &response.text()?)
+    }
+
+    /// Get the current and upcoming events.
+    ///
+    /// If `use_cache` is set, a recently-cached list is used instead
+    /// of making the call.
+    ///
+    /// # Errors
+    ///
+    /// If the request fails or the list can't be parsed.
+    pub fn get_events(&self, use_cache: bool) -> Result<Vec<Event>> {
+        if let Some(events) = mirrors::cached_events(use_cache) {
+            return Ok(events);
+        }
+        let url = mirrors::events_url();
+        debug!("Getting events from {}", url);
+        let response = self.client.get(url).send()?;
+        mirrors::check_status(response.status(), "events")?;
+        let data: EventsResponse = response.json()?;
+        mirrors::events_fetched(&data.data);
+        Ok(data.data)
+    }
+
+    /// Query the V3 endpoint.
+    ///
+    /// If the mirror sends something other than JSON or a server error,
+    /// or can't be reached, the other mirrors are tried in turn. In low-bandwidth mode, unchanged data is
+    /// reported without being downloaded or parsed again where possible.
+    ///
+    /// # Errors
     ///
-    /// Transforms number into name like "S1", "C3", "L1", etc.
-    pub fn controller_rating_lookup(data: &V3ResponseData, rating: i8) -> String {
-        data.ratings
-            .iter()
-            .find(|&item| item.id == rating)
-            .map_or_else(|| String::from("?"), |item| item.short.clone())
+    /// If the request fails or the body can't be parsed, once every
+    /// mirror that can be tried has been.
+    pub fn get_data(&mut self) -> Result<DataResponse> {
+        let mut attempts = 1;
+        self.timings = FetchTimings::default();
+        self.fallbacks.clear();
+        self.failed_mirrors.clear();
+        loop {
+            let result = self.get_mirror_data();
+            if result.is_err() {
+                self.failed_mirrors.push(self.mirrors.url().to_owned());
+            }
+            match result {
+                Err(e) if self.mirrors.should_fail_over(&e, attempts) => {
+                    let fallback = format!("{}, trying next mirror", e);
+                    warn!("{}", fallback);
+                    self.fallbacks.push(fallback);
+                    self.mirrors.next();
+                    attempts += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Whether a mirror serving stale data was switched away from since
+    /// the last call.
+    pub fn take_stale_switch(&mut self) -> bool {
+        self.mirrors.take_stale_switch()
+    }
+
+    /// Why the last `get_data` call moved on from each mirror it failed
+    /// over from.
+    #[must_use]
+    pub fn last_fallbacks(&self) -> &[String] {
+        &self.fallbacks
+    }
+
+    /// URLs of the V3 mirrors the last `get_data` call failed on, in the
+    /// order they were tried, including the last one if the call failed.
+    #[must_use]
+    pub fn last_failed_mirrors(&self) -> &[String] {
+        &self.failed_mirrors
+    }
+
+    /// Save raw V3 bodies with these settings, or stop saving them.
+    pub fn set_dumps(&mut self, dumps: Option<DumpSettings>) {
+        self.dumps = dumps;
+    }
+
+    /// Where a raw body was saved since the last call, if one was.
+    pub fn take_dump(&mut self) -> Option<PathBuf> {
+        self.last_dump.take()
+    }
+
+    /// How long the last `get_data` call spent on the network and parsing,
+    /// across all the mirrors it tried.
+    #[must_use]
+    pub fn last_timings(&self) -> FetchTimings {
+        self.timings
+    }
+
+    /// Query the V3 endpoint on the current mirror, timing the fetch and the parse.
+    fn get_mirror_data(&mut self) -> Result<DataResponse> {
+        let start = Instant::now();
+        let raw = self.fetch_raw();
+        self.timings.fetch += start.elapsed();
+        let raw = raw?;
+        let start = Instant::now();
+        let data = self.parse_raw(&raw);
+        self.timings.parse += start.elapsed();
+        if let RawResponse::Body { body, .. } = &raw {
+            self.dump(body, data.is_err());
+        }
+        data
+    }
+
+    /// Save a raw body if the settings call for it, logging where.
+    fn dump(&mut self, body: &[u8], failed: bool) {
+        let Some(dumps) = &self.dumps else {
+            return;
+        };
+        if !failed && !dumps.all {
+            return;
+        }
+        match dump::write(&dumps.dir, body, Utc::now()) {
+            Ok(path) => {
+                warn!("Saved the raw V3 response to {}", path.display());
+                self.last_dump = Some(path);
+            }
+            Err(e) => warn!("Could not save the raw V3 response: {:#}", e),
+        }
+    }
+
+    /// Download the V3 data from the current mirror without parsing it.
+    ///
+    /// # Errors
+    ///
+    /// If the request fails or the mirror answers with an error status.
+    pub fn fetch_raw(&mut self) -> Result<RawResponse> {
+        let url = self.mirrors.url();
+        debug!("Getting current data from {}", url);
+        let response = self
+            .client
+            .get(url)
+            .headers(self.mirrors.conditional_headers())
+            .send()?;
+        let content_type = match self
+            .mirrors
+            .read_head(response.status(), response.headers())?
+        {
+            Head::NotModified => return Ok(RawResponse::NotModified),
+            Head::Body(content_type) => content_type,
+        };
+        Ok(RawResponse::Body {
+            content_type,
+            body: response.bytes()?.into(),
+        })
+    }
+
+    /// Parse V3 data downloaded with `fetch_raw`.
+    ///
+    /// # Errors
+    ///
+    /// If the body isn't V3 JSON.
+    pub fn parse_raw(&mut self, raw: &RawResponse) -> Result<DataResponse> {
+        match raw {
+            RawResponse::NotModified => Ok(DataResponse::NotModified),
+            RawResponse::Body { content_type, body } => {
+                self.mirrors.read_body(content_type.as_deref(), body)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn races_mirrors_together_with_a_timeout() {
+        let urls = [
+            testing::slow_server(200, Duration::from_millis(300)),
+            testing::slow_server(200, Duration::ZERO),
+            testing::slow_server(503, Duration::ZERO),
+            testing::slow_server(200, Duration::from_secs(10)),
+            testing::refused_url(),
+        ];
+        let start = Instant::now();
+        let latencies = Vatsim::race(&Client::new(), &urls, Duration::from_secs(1));
+        // together, not one after another
+        assert!(
+            start.elapsed() < Duration::from_secs(3),
+            "{:?}",
+            start.elapsed()
+        );
+        testing::check_race(&urls, &latencies);
+        assert!(Vatsim::race(&Client::new(), &[], Duration::from_secs(1)).is_empty());
+    }
+
+    #[test]
+    fn default_user_agent_has_the_version() {
+        assert_eq!(
+            DEFAULT_USER_AGENT,
+            format!(
+                "github.com/celeo/vatsim_online/{}",
+                env!("CARGO_PKG_VERSION")
+            )
+        );
+        assert!(HeaderValue::from_str(DEFAULT_USER_AGENT).is_ok());
+    }
+
+    #[test]
+    fn user_agent_overrides_are_trimmed() {
+        assert_eq!(
+            parse_user_agent("  my-tracker/2.1 (ops@example.com) ").unwrap(),
+            "my-tracker/2.1 (ops@example.com)"
+        );
+    }
+
+    #[test]
+    fn user_agent_overrides_must_fit_in_a_header() {
+        assert!(parse_user_agent("").is_err());
+        assert!(parse_user_agent("   ").is_err());
+        let error = parse_user_agent("tracker\nInjected: yes").unwrap_err();
+        assert!(error.to_string().contains("visible ASCII"), "{}", error);
     }
 }
@@ -0,0 +1,263 @@
+//! Async version of the API client, for embedding in an async application.
+//!
+//! It shares the mirror selection, failover, and parsing with the
+//! blocking client in `api`, so the two behave the same.
+
+use crate::{
+    api::DataResponse,
+    mirrors::{self, Head, MirrorLatency, MirrorSelection, Mirrors},
+    models::{Event, EventsResponse, Status},
+};
+use anyhow::{anyhow, Result};
+use futures_util::future;
+use log::{debug, warn};
+use reqwest::{header::HeaderValue, Client, ClientBuilder};
+use std::time::{Duration, Instant};
+
+/// Async API struct.
+#[derive(Debug)]
+pub struct AsyncVatsim {
+    client: Client,
+    mirrors: Mirrors,
+    /// Why the last `get_data` call moved on from a mirror, if it did.
+    fallbacks: Vec<String>,
+    /// V3 mirrors the last `get_data` call failed on, in order.
+    failed_mirrors: Vec<String>,
+}
+
+impl AsyncVatsim {
+    /// New API struct instance.
+    ///
+    /// Same as `Vatsim::new`, but without blocking.
+    ///
+    /// # Errors
+    ///
+    /// Same as `Vatsim::new`.
+    pub async fn new(
+        use_cache: bool,
+        low_bandwidth: bool,
+        user_agent: &HeaderValue,
+    ) -> Result<Self> {
+        AsyncVatsim::with_mirror(
+            use_cache,
+            low_bandwidth,
+            user_agent,
+            &MirrorSelection::default(),
+        )
+        .await
+    }
+
+    /// Same as `Vatsim::with_mirror`, but without blocking.
+    ///
+    /// # Errors
+    ///
+    /// Same as `Vatsim::with_mirror`.
+    pub async fn with_mirror(
+        use_cache: bool,
+        low_bandwidth: bool,
+        user_agent: &HeaderValue,
+        selection: &MirrorSelection,
+    ) -> Result<Self> {
+        debug!("Creating async VATSIM struct instance");
+        let client = ClientBuilder::new()
+            .user_agent(user_agent.clone())
+            .gzip(low_bandwidth)
+            .build()?;
+        let status = if selection.needs_status() {
+            match mirrors::cached_status(use_cache) {
+                Some(status) => Some(status),
+                None => Some(AsyncVatsim::get_status(&client).await?),
+            }
+        } else {
+            None
+        };
+        let latencies = AsyncVatsim::race(
+            &client,
+            &Mirrors::to_race(status.as_ref(), selection),
+            mirrors::RACE_TIMEOUT,
+        )
+        .await;
+        let mirrors = Mirrors::new(status, low_bandwidth, selection, latencies)?;
+        Ok(Self {
+            client,
+            mirrors,
+            fallbacks: Vec::new(),
+            failed_mirrors: Vec::new(),
+        })
+    }
+
+    /// Same as `Vatsim::race`, polling the requests together rather than
+    /// on threads.
+    async fn race(client: &Client, urls: &[String], timeout: Duration) -> Vec<MirrorLatency> {
+        if urls.is_empty() {
+            return Vec::new();
+        }
+        debug!("Racing {} V3 mirrors", urls.len());
+        future::join_all(urls.iter().map(|url| async move {
+            let start = Instant::now();
+            let status = client
+                .head(url)
+                .timeout(timeout)
+                .send()
+                .await
+                .map(|response| response.status());
+            MirrorLatency::measured(url, start.elapsed(), status)
+        }))
+        .await
+    }
+
+    /// Query the status endpoint.
+    async fn get_status(client: &Client) -> Result<Status> {
+        debug!("Getting status page");
+        let response = client.get(mirrors::status_url()).send().await?;
+        mirrors::check_status(response.status(), "status")?;
+        let data: Status = response.json().await?;
+        mirrors::status_fetched(&data);
+        Ok(data)
+    }
+
+    /// Number of V3 mirrors listed in the status document.
+    #[must_use]
+    pub fn mirror_count(&self) -> usize {
+        self.mirrors.count()
+    }
+
+    /// URL of the V3 mirror in use.
+    #[must_use]
+    pub fn mirror_url(&self) -> &str {
+        self.mirrors.url()
+    }
+
+    /// Get the current METAR for an airport.
+    ///
+    /// # Errors
+    ///
+    /// Same as `Vatsim::get_metar`.
+    pub async fn get_metar(&self, icao: &str) -> Result<String> {
+        let url = self
+            .mirrors
+            .metar_url()
+            .ok_or_else(|| anyhow!("No METAR URLs returned"))?;
+        debug!("Getting METAR for {} from {}", icao, url);
+        let response = self.client.get(url).query(&[("id", icao)]).send().await?;
+        mirrors::check_status(response.status(), "METAR")?;
+        mirrors::read_metar(icao, &response.text().await?)
+    }
+
+    /// Get the current and upcoming events, like `Vatsim::get_events`.
+    ///
+    /// # Errors
+    ///
+    /// Same as `Vatsim::get_events`.
+    pub async fn get_events(&self, use_cache: bool) -> Result<Vec<Event>> {
+        if let Some(events) = mirrors::cached_events(use_cache) {
+            return Ok(events);
+        }
+        let url = mirrors::events_url();
+        debug!("Getting events from {}", url);
+        let response = self.client.get(url).send().await?;
+        mirrors::check_status(response.status(), "events")?;
+        let data: EventsResponse = response.json().await?;
+        mirrors::events_fetched(&data.data);
+        Ok(data.data)
+    }
+
+    /// Query the V3 endpoint, failing over like `Vatsim::get_data`.
+    ///
+    /// # Errors
+    ///
+    /// Same as `Vatsim::get_data`.
+    pub async fn get_data(&mut self) -> Result<DataResponse> {
+        let mut attempts = 1;
+        self.fallbacks.clear();
+        self.failed_mirrors.clear();
+        loop {
+            let result = self.get_mirror_data().await;
+            if result.is_err() {
+                self.failed_mirrors.push(self.mirrors.url().to_owned());
+            }
+            match result {
+                Err(e) if self.mirrors.should_fail_over(&e, attempts) => {
+                    let fallback = format!("{}, trying next mirror", e);
+                    warn!("{}", fallback);
+                    self.fallbacks.push(fallback);
+                    self.mirrors.next();
+                    attempts += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Whether a mirror serving stale data was switched away from since
+    /// the last call.
+    pub fn take_stale_switch(&mut self) -> bool {
+        self.mirrors.take_stale_switch()
+    }
+
+    /// Same as `Vatsim::last_fallbacks`.
+    #[must_use]
+    pub fn last_fallbacks(&self) -> &[String] {
+        &self.fallbacks
+    }
+
+    /// Same as `Vatsim::last_failed_mirrors`.
+    #[must_use]
+    pub fn last_failed_mirrors(&self) -> &[String] {
+        &self.failed_mirrors
+    }
+
+    /// Query the V3 endpoint on the current mirror.
+    async fn get_mirror_data(&mut self) -> Result<DataResponse> {
+        let url = self.mirrors.url();
+        debug!("Getting current data from {}", url);
+        let response = self
+            .client
+            .get(url)
+            .headers(self.mirrors.conditional_headers())
+            .send()
+            .await?;
+        let content_type = match self
+            .mirrors
+            .read_head(response.status(), response.headers())?
+        {
+            Head::NotModified => return Ok(DataResponse::NotModified),
+            Head::Body(content_type) => content_type,
+        };
+        let body = response.bytes().await?;
+        self.mirrors.read_body(content_type.as_deref(), &body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn races_mirrors_together_with_a_timeout() {
+        let urls = [
+            testing::slow_server(200, Duration::from_millis(300)),
+            testing::slow_server(200, Duration::ZERO),
+            testing::slow_server(503, Duration::ZERO),
+            testing::slow_server(200, Duration::from_secs(10)),
+            testing::refused_url(),
+        ];
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let start = Instant::now();
+        let latencies = runtime.block_on(AsyncVatsim::race(
+            &Client::new(),
+            &urls,
+            Duration::from_secs(1),
+        ));
+        assert!(
+            start.elapsed() < Duration::from_secs(3),
+            "{:?}",
+            start.elapsed()
+        );
+        testing::check_race(&urls, &latencies);
+    }
+}
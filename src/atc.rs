@@ -0,0 +1,235 @@
+//! Checking whether an airport has ATC online, for the `atc` subcommand.
+
+use crate::{
+    callsign,
+    models::V3ResponseData,
+    reference::{Facility, ReferenceNames},
+    report::{fetch_data, DataSource, Reporter},
+};
+use anyhow::{bail, Result};
+use serde::Serialize;
+use std::collections::HashSet;
+
+/// Short names of the facilities that can be asked for.
+const POSITION_NAMES: &str = "DEL, GND, TWR, APP, CTR, FSS";
+
+/// Parse comma-separated position types from the command line, like "TWR,APP".
+pub fn parse_positions(value: &str) -> Result<HashSet<Facility>> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(|name| match Facility::from_short_name(name) {
+            Some(facility) if facility != Facility::Observer => Ok(facility),
+            _ => bail!("Unknown position '{}', expected: {}", name, POSITION_NAMES),
+        })
+        .collect()
+}
+
+/// A controller online at the airport.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OnlinePosition {
+    pub callsign: String,
+    /// Short name of the facility, like "TWR".
+    pub facility: String,
+    pub frequency: String,
+    pub name: String,
+}
+
+/// Who's controlling an airport.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AtcReport {
+    pub airport: String,
+    pub online: bool,
+    pub positions: Vec<OnlinePosition>,
+}
+
+/// Find the controllers at an airport, of the given facilities or of any
+/// if none are given, sorted by callsign.
+///
+/// Positions are placed by their callsign prefix, so area positions like
+/// centers only count when their prefix is the airport's.
+pub fn check(data: &V3ResponseData, icao: &str, positions: &HashSet<Facility>) -> AtcReport {
+    let icao = icao.trim().to_uppercase();
+    let names = ReferenceNames::new(data);
+    let mut online: Vec<OnlinePosition> = data
+        .controllers
+        .iter()
+        .filter(|c| callsign::is_at_airport(&c.callsign, &icao))
+        .filter_map(|c| {
+            let facility = c.facility_enum()?;
+            if facility == Facility::Observer
+                || !(positions.is_empty() || positions.contains(&facility))
+            {
+                return None;
+            }
+            Some(OnlinePosition {
+                callsign: c.callsign.clone(),
+                facility: names.facility(facility),
+                frequency: c.frequency.clone(),
+                name: c.name.clone(),
+            })
+        })
+        .collect();
+    online.sort_by(|a, b| a.callsign.cmp(&b.callsign));
+    AtcReport {
+        airport: icao,
+        online: !online.is_empty(),
+        positions: online,
+    }
+}
+
+/// The report as lines of text.
+pub fn text(report: &AtcReport) -> String {
+    if report.positions.is_empty() {
+        return format!("No ATC online at {}", report.airport);
+    }
+    report
+        .positions
+        .iter()
+        .map(|p| {
+            format!(
+                "{} ({}) on {}: {}",
+                p.callsign, p.facility, p.frequency, p.name
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The process exit code for a check: 0 if ATC is online, 1 if not, and 2
+/// if the check failed.
+pub fn exit_code(result: &Result<bool>) -> i32 {
+    match result {
+        Ok(true) => 0,
+        Ok(false) => 1,
+        Err(_) => 2,
+    }
+}
+
+/// Check an airport's ATC and print the report.
+///
+/// Returns whether any matching controller is online.
+pub fn run(
+    icao: &str,
+    positions: &HashSet<Facility>,
+    json: bool,
+    source: &DataSource,
+    reporter: &mut Reporter,
+) -> Result<bool> {
+    let data = fetch_data(source, reporter)?;
+    let report = check(&data, icao, positions);
+    if json {
+        reporter.data(serde_json::to_string_pretty(&report)?)?;
+    } else {
+        reporter.data(text(&report))?;
+    }
+    Ok(report.online)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use anyhow::anyhow;
+
+    /// Positions around San Francisco and one at Heathrow.
+    fn data() -> V3ResponseData {
+        V3ResponseData {
+            controllers: vec![
+                testing::controller("KSFO_TWR", 4),
+                testing::controller("SFO_GND", 3),
+                testing::controller("KSFO_DEL", 2),
+                testing::controller("NCT_SFO_APP", 5),
+                testing::controller("OAK_CTR", 6),
+                testing::controller("KSFO_OBS", 0),
+                testing::controller("EGLL_TWR", 4),
+            ],
+            ..V3ResponseData::default()
+        }
+    }
+
+    fn callsigns(report: &AtcReport) -> Vec<&str> {
+        report
+            .positions
+            .iter()
+            .map(|p| p.callsign.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn positions_from_the_command_line() {
+        assert_eq!(
+            parse_positions(" twr, APP,,").unwrap(),
+            HashSet::from([Facility::Tower, Facility::Approach])
+        );
+        assert!(parse_positions("").unwrap().is_empty());
+        let error = parse_positions("TWR,RMP").unwrap_err().to_string();
+        assert_eq!(
+            error,
+            "Unknown position 'RMP', expected: DEL, GND, TWR, APP, CTR, FSS"
+        );
+        assert!(parse_positions("OBS").is_err());
+    }
+
+    #[test]
+    fn any_position_at_the_airport() {
+        let report = check(&data(), " ksfo ", &HashSet::new());
+        assert_eq!(report.airport, "KSFO");
+        assert!(report.online);
+        // centers are areas and observers never count
+        assert_eq!(
+            callsigns(&report),
+            ["KSFO_DEL", "KSFO_TWR", "NCT_SFO_APP", "SFO_GND"]
+        );
+        assert_eq!(exit_code(&Ok(report.online)), 0);
+        assert_eq!(
+            text(&report).lines().nth(1),
+            Some("KSFO_TWR (TWR) on 118.500: Test Controller")
+        );
+    }
+
+    #[test]
+    fn only_the_asked_for_positions() {
+        let positions = parse_positions("TWR,APP").unwrap();
+        let report = check(&data(), "KSFO", &positions);
+        assert_eq!(callsigns(&report), ["KSFO_TWR", "NCT_SFO_APP"]);
+        assert_eq!(report.positions[0].facility, "TWR");
+        assert_eq!(exit_code(&Ok(report.online)), 0);
+    }
+
+    #[test]
+    fn no_matching_position_exits_one() {
+        let report = check(&data(), "KSFO", &parse_positions("CTR").unwrap());
+        assert!(!report.online);
+        assert!(report.positions.is_empty());
+        assert_eq!(text(&report), "No ATC online at KSFO");
+        assert_eq!(exit_code(&Ok(report.online)), 1);
+
+        let unknown = check(&data(), "ZZZZ", &HashSet::new());
+        assert_eq!(text(&unknown), "No ATC online at ZZZZ");
+        assert_eq!(exit_code(&Ok(unknown.online)), 1);
+    }
+
+    #[test]
+    fn errors_exit_two() {
+        assert_eq!(exit_code(&Err(anyhow!("No data returned from VATSIM"))), 2);
+        let result =
+            parse_positions("XYZ").map(|positions| check(&data(), "KSFO", &positions).online);
+        assert_eq!(exit_code(&result), 2);
+    }
+
+    #[test]
+    fn json_report() {
+        let report = check(&data(), "EGLL", &HashSet::new());
+        let json: serde_json::Value = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["airport"], "EGLL");
+        assert_eq!(json["online"], true);
+        assert_eq!(json["positions"][0]["callsign"], "EGLL_TWR");
+        assert_eq!(json["positions"][0]["facility"], "TWR");
+        assert_eq!(
+            serde_json::to_value(check(&data(), "KJFK", &HashSet::new())).unwrap(),
+            serde_json::json!({"airport": "KJFK", "online": false, "positions": []})
+        );
+    }
+}
@@ -0,0 +1,254 @@
+//! Noticing when controllers change their ATIS text between refreshes.
+
+use crate::models::Controller;
+use std::collections::HashMap;
+
+/// Refreshes a change stays marked for, including the one it was seen in.
+const MARKED_REFRESHES: u8 = 3;
+
+/// A line in the difference between two texts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffLine {
+    Same(String),
+    Removed(String),
+    Added(String),
+}
+
+/// The lines removed from `old` and added in `new` to get from one to the
+/// other, keeping as many lines as possible in place.
+///
+/// This is the longest common subsequence of the lines; ATIS texts are
+/// only a few lines, so the quadratic table is fine.
+pub fn diff_lines(old: &[String], new: &[String]) -> Vec<DiffLine> {
+    // common[i][j] is the length of the LCS of old[i..] and new[j..]
+    let mut common = vec![vec![0_usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut lines = Vec::with_capacity(old.len().max(new.len()));
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            lines.push(DiffLine::Same(old[i].clone()));
+            i += 1;
+            j += 1;
+        } else if common[i + 1][j] >= common[i][j + 1] {
+            lines.push(DiffLine::Removed(old[i].clone()));
+            i += 1;
+        } else {
+            lines.push(DiffLine::Added(new[j].clone()));
+            j += 1;
+        }
+    }
+    lines.extend(old[i..].iter().cloned().map(DiffLine::Removed));
+    lines.extend(new[j..].iter().cloned().map(DiffLine::Added));
+    lines
+}
+
+/// A recent change to a controller's ATIS.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct AtisChange {
+    diff: Vec<DiffLine>,
+    /// Refreshes left before the change stops being marked.
+    refreshes_left: u8,
+}
+
+/// Each controller's ATIS from the last refresh, and which changed recently.
+#[derive(Debug, Clone, Default)]
+pub struct AtisTracker {
+    /// ATIS lines by CID.
+    previous: HashMap<i64, Vec<String>>,
+    /// Recent changes by CID.
+    changes: HashMap<i64, AtisChange>,
+}
+
+impl AtisTracker {
+    /// Compare the controllers' ATIS to the last refresh, and age the
+    /// changes seen before.
+    ///
+    /// Controllers seen for the first time haven't changed anything.
+    pub fn update<'a>(&mut self, controllers: impl IntoIterator<Item = &'a Controller>) {
+        let mut current = HashMap::new();
+        let mut changes = HashMap::new();
+        for controller in controllers {
+            let lines = controller.info_lines();
+            match self.previous.get(&controller.cid) {
+                Some(previous) if previous.as_slice() != lines => {
+                    let _ = changes.insert(
+                        controller.cid,
+                        AtisChange {
+                            diff: diff_lines(previous, lines),
+                            refreshes_left: MARKED_REFRESHES - 1,
+                        },
+                    );
+                }
+                _ => {
+                    if let Some(change) = self.changes.remove(&controller.cid) {
+                        if change.refreshes_left > 0 {
+                            let _ = changes.insert(
+                                controller.cid,
+                                AtisChange {
+                                    refreshes_left: change.refreshes_left - 1,
+                                    ..change
+                                },
+                            );
+                        }
+                    }
+                }
+            }
+            let _ = current.insert(controller.cid, lines.to_vec());
+        }
+        self.previous = current;
+        self.changes = changes;
+    }
+
+    /// The difference from a controller's last ATIS, if it changed recently.
+    pub fn change(&self, cid: i64) -> Option<&[DiffLine]> {
+        self.changes.get(&cid).map(|change| change.diff.as_slice())
+    }
+
+    /// Number of controllers tracked and changes marked.
+    pub fn entries(&self) -> usize {
+        self.previous.len() + self.changes.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.split('|').map(str::to_owned).collect()
+    }
+
+    /// The diff as text, with "-" before removed lines and "+" before
+    /// added ones.
+    fn diff(old: &str, new: &str) -> Vec<String> {
+        let lines = |text: &str| {
+            if text.is_empty() {
+                Vec::new()
+            } else {
+                lines(text)
+            }
+        };
+        diff_lines(&lines(old), &lines(new))
+            .into_iter()
+            .map(|line| match line {
+                DiffLine::Same(line) => format!(" {}", line),
+                DiffLine::Removed(line) => format!("-{}", line),
+                DiffLine::Added(line) => format!("+{}", line),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn new_information_letter() {
+        assert_eq!(
+            diff(
+                "KJFK ATIS INFO A|RWY 31L|ALTIMETER 2992",
+                "KJFK ATIS INFO B|RWY 31L|ALTIMETER 2990"
+            ),
+            [
+                "-KJFK ATIS INFO A",
+                "+KJFK ATIS INFO B",
+                " RWY 31L",
+                "-ALTIMETER 2992",
+                "+ALTIMETER 2990",
+            ]
+        );
+    }
+
+    #[test]
+    fn lines_added_and_removed_in_the_middle() {
+        assert_eq!(
+            diff("INFO A|RWY 22R|NOTAMS|END", "INFO A|RWY 22R|RWY 22L|END"),
+            [" INFO A", " RWY 22R", "-NOTAMS", "+RWY 22L", " END"]
+        );
+        assert_eq!(
+            diff("INFO A|END", "INFO A|BIRDS|END"),
+            [" INFO A", "+BIRDS", " END"]
+        );
+        assert_eq!(diff("A|B|C", "A|C"), [" A", "-B", " C"]);
+    }
+
+    #[test]
+    fn empty_and_identical_texts() {
+        assert_eq!(diff("", "A|B"), ["+A", "+B"]);
+        assert_eq!(diff("A|B", ""), ["-A", "-B"]);
+        assert!(diff("", "").is_empty());
+        assert_eq!(diff("A|B", "A|B"), [" A", " B"]);
+        // a moved line is removed from one place and added in the other
+        assert_eq!(diff("A|B|C", "C|A|B"), ["+C", " A", " B", "-C"]);
+    }
+
+    fn controller(cid: i64, atis: &str) -> Controller {
+        Controller {
+            cid,
+            text_atis: (!atis.is_empty()).then(|| lines(atis)),
+            ..testing::controller("KJFK_ATIS", 4)
+        }
+    }
+
+    #[test]
+    fn changes_stay_marked_for_three_refreshes() {
+        let mut tracker = AtisTracker::default();
+        tracker.update(&[controller(1, "INFO A")]);
+        // seen for the first time
+        assert_eq!(tracker.change(1), None);
+        tracker.update(&[controller(1, "INFO B")]);
+        assert_eq!(
+            tracker.change(1),
+            Some(
+                [
+                    DiffLine::Removed("INFO A".to_owned()),
+                    DiffLine::Added("INFO B".to_owned())
+                ]
+                .as_slice()
+            )
+        );
+        tracker.update(&[controller(1, "INFO B")]);
+        tracker.update(&[controller(1, "INFO B")]);
+        assert!(tracker.change(1).is_some());
+        tracker.update(&[controller(1, "INFO B")]);
+        assert_eq!(tracker.change(1), None);
+    }
+
+    #[test]
+    fn another_change_starts_over() {
+        let mut tracker = AtisTracker::default();
+        tracker.update(&[controller(1, "INFO A")]);
+        tracker.update(&[controller(1, "INFO B")]);
+        tracker.update(&[controller(1, "INFO B")]);
+        tracker.update(&[controller(1, "INFO C")]);
+        assert_eq!(
+            tracker.change(1).unwrap()[0],
+            DiffLine::Removed("INFO B".to_owned())
+        );
+        tracker.update(&[controller(1, "INFO C")]);
+        tracker.update(&[controller(1, "INFO C")]);
+        assert!(tracker.change(1).is_some());
+    }
+
+    #[test]
+    fn controllers_are_matched_by_cid_and_forgotten_when_gone() {
+        let mut tracker = AtisTracker::default();
+        tracker.update(&[controller(1, "INFO A"), controller(2, "")]);
+        tracker.update(&[controller(1, "INFO A"), controller(2, "INFO K")]);
+        assert_eq!(tracker.change(1), None);
+        assert!(tracker.change(2).is_some());
+        assert_eq!(tracker.entries(), 3);
+        // CID 2 logs off, then back on with a new ATIS
+        tracker.update(&[controller(1, "INFO A")]);
+        assert_eq!(tracker.change(2), None);
+        assert_eq!(tracker.entries(), 1);
+        tracker.update(&[controller(1, "INFO A"), controller(2, "INFO L")]);
+        assert_eq!(tracker.change(2), None);
+    }
+}
@@ -0,0 +1,533 @@
+//! Arrivals and departures board for an airport.
+
+use crate::{
+    airports::{self, Airport},
+    api::{DataResponse, Vatsim},
+    callsign, flight,
+    format::{self, DisplayZone, TimeStyle},
+    frequency, geo,
+    mirrors::MirrorSelection,
+    models::{Controller, FlightPlan, Pilot, V3ResponseData},
+    output::{OutputFormat, Table},
+    report::Reporter,
+    sanity::{self, Limits},
+    terminal::{Mode, Plain},
+};
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use crossterm::{
+    event::{self, Event, KeyCode},
+    execute,
+    terminal::{self, disable_raw_mode, LeaveAlternateScreen},
+};
+use log::warn;
+use reqwest::header::HeaderValue;
+use std::time::{Duration, Instant};
+use tui::{
+    backend::{Backend, CrosstermBackend},
+    layout::{Constraint, Direction, Layout},
+    style::{Modifier, Style},
+    widgets::{Block, Borders, Paragraph, Row, Table as TableWidget},
+    Frame, Terminal,
+};
+
+/// A pilot headed to the airport.
+#[derive(Debug, Clone)]
+pub struct Arrival<'a> {
+    pub pilot: &'a Pilot,
+    /// Distance to the airport, if it's in the airport database.
+    pub distance_nm: Option<f64>,
+    pub eta: Option<DateTime<Utc>>,
+}
+
+/// A pilot on the ground who filed to depart from the airport.
+#[derive(Debug, Clone)]
+pub struct Departure<'a> {
+    pub pilot: &'a Pilot,
+    /// Filed departure time, if one was given.
+    pub departs: Option<DateTime<Utc>>,
+}
+
+/// What's going on at an airport.
+#[derive(Debug, Clone)]
+pub struct Board<'a> {
+    /// ICAO code, in capitals.
+    pub icao: String,
+    pub airport: Option<&'static Airport>,
+    /// Airborne inbound pilots, soonest first.
+    pub arrivals: Vec<Arrival<'a>>,
+    /// Outbound pilots on the ground, earliest filed departure first.
+    pub departures: Vec<Departure<'a>>,
+    /// Controllers and ATIS stations at the airport, grouped by position.
+    pub controllers: Vec<&'a Controller>,
+}
+
+/// Pilots on the ground within this distance of the airport are at it.
+const AT_AIRPORT_NM: f64 = 10.0;
+
+/// Sort `None` after any time.
+fn time_key(time: Option<DateTime<Utc>>) -> (bool, Option<DateTime<Utc>>) {
+    (time.is_none(), time)
+}
+
+impl<'a> Board<'a> {
+    /// Gather the board for an airport from the data.
+    pub fn build(icao: &str, data: &'a V3ResponseData, now: DateTime<Utc>) -> Self {
+        let icao = icao.trim().to_uppercase();
+        let airport = airports::by_icao(&icao);
+        // a pilot who landed elsewhere still has this airport as the departure
+        let at_airport = |pilot: &Pilot| {
+            airport.is_none_or(|airport| {
+                geo::distance_nm(
+                    pilot.latitude,
+                    pilot.longitude,
+                    airport.latitude,
+                    airport.longitude,
+                ) <= AT_AIRPORT_NM
+            })
+        };
+        let filed = |pilot: &Pilot, airport: fn(&FlightPlan) -> &str| {
+            pilot
+                .flight_plan
+                .as_ref()
+                .is_some_and(|plan| airport(plan).eq_ignore_ascii_case(&icao))
+        };
+        let mut arrivals: Vec<Arrival> = data
+            .pilots
+            .iter()
+            .filter(|p| filed(p, |plan| &plan.arrival) && !flight::on_ground(p))
+            .map(|pilot| {
+                let trusted = sanity::check(pilot, None, &Limits::default()).is_none();
+                Arrival {
+                    pilot,
+                    distance_nm: trusted
+                        .then(|| flight::distance_to_arrival(pilot))
+                        .flatten(),
+                    eta: trusted
+                        .then(|| flight::estimated_arrival(pilot, now))
+                        .flatten(),
+                }
+            })
+            .collect();
+        arrivals.sort_by(|a, b| {
+            time_key(a.eta)
+                .cmp(&time_key(b.eta))
+                .then_with(|| a.pilot.callsign.cmp(&b.pilot.callsign))
+        });
+        let mut departures: Vec<Departure> = data
+            .pilots
+            .iter()
+            .filter(|p| filed(p, |plan| &plan.departure) && flight::on_ground(p) && at_airport(p))
+            .map(|pilot| Departure {
+                pilot,
+                departs: pilot
+                    .flight_plan
+                    .as_ref()
+                    .and_then(|plan| flight::parse_deptime(&plan.deptime))
+                    .map(|time| flight::departure_time(time, now)),
+            })
+            .collect();
+        departures.sort_by(|a, b| {
+            time_key(a.departs)
+                .cmp(&time_key(b.departs))
+                .then_with(|| a.pilot.callsign.cmp(&b.pilot.callsign))
+        });
+        let mut controllers: Vec<&Controller> = data
+            .controllers
+            .iter()
+            .chain(&data.atis)
+            .filter(|c| callsign::is_at_airport(&c.callsign, &icao))
+            .collect();
+        controllers.sort_by_cached_key(|c| callsign::grouped_key(&c.callsign));
+        Self {
+            icao,
+            airport,
+            arrivals,
+            departures,
+            controllers,
+        }
+    }
+
+    /// Heading for the board, like "KJFK (Kennedy)".
+    pub fn heading(&self) -> String {
+        match self.airport {
+            Some(airport) => format!("{} ({})", self.icao, airport.name),
+            None => self.icao.clone(),
+        }
+    }
+
+    /// The arrivals, departures, and ATC as tables.
+    pub fn tables(&self, zone: DisplayZone, now: DateTime<Utc>) -> [Table; 3] {
+        let time = |time: Option<DateTime<Utc>>| {
+            time.map_or_else(
+                || "\u{2014}".to_owned(),
+                |time| {
+                    format!(
+                        "{} ({} min)",
+                        format::format_time(time, zone, TimeStyle::Clock),
+                        (time - now).num_minutes()
+                    )
+                },
+            )
+        };
+        let aircraft = |pilot: &Pilot| pilot.aircraft().unwrap_or("???").to_owned();
+        let arrivals = Table {
+            title: format!("Arrivals ({})", self.arrivals.len()),
+            headers: vec!["Callsign", "Aircraft", "From", "Distance", "ETA"],
+            rows: self
+                .arrivals
+                .iter()
+                .map(|arrival| {
+                    vec![
+                        arrival.pilot.callsign.clone(),
+                        aircraft(arrival.pilot),
+                        arrival
+                            .pilot
+                            .flight_plan
+                            .as_ref()
+                            .map(|plan| plan.departure.clone())
+                            .unwrap_or_default(),
+                        arrival.distance_nm.map_or_else(
+                            || "\u{2014}".to_owned(),
+                            |nm| format!("{} nm", format::whole(nm)),
+                        ),
+                        time(arrival.eta),
+                    ]
+                })
+                .collect(),
+        };
+        let departures = Table {
+            title: format!("Departures ({})", self.departures.len()),
+            headers: vec!["Callsign", "Aircraft", "To", "Departs"],
+            rows: self
+                .departures
+                .iter()
+                .map(|departure| {
+                    vec![
+                        departure.pilot.callsign.clone(),
+                        aircraft(departure.pilot),
+                        departure
+                            .pilot
+                            .flight_plan
+                            .as_ref()
+                            .map(|plan| plan.arrival.clone())
+                            .unwrap_or_default(),
+                        time(departure.departs),
+                    ]
+                })
+                .collect(),
+        };
+        let controllers = Table {
+            title: format!("ATC ({})", self.controllers.len()),
+            headers: vec!["Callsign", "Position", "Frequency", "Name"],
+            rows: self
+                .controllers
+                .iter()
+                .map(|controller| {
+                    vec![
+                        controller.callsign.clone(),
+                        callsign::position_name(&controller.callsign),
+                        frequency::display(&controller.frequency),
+                        controller.name.clone(),
+                    ]
+                })
+                .collect(),
+        };
+        [arrivals, departures, controllers]
+    }
+
+    /// The board as text, one table after another.
+    pub fn text(&self, zone: DisplayZone, now: DateTime<Utc>) -> String {
+        let formatter = OutputFormat::Text.formatter();
+        let mut sections = vec![format!(
+            "{}, {}",
+            self.heading(),
+            format::format_time(now, zone, TimeStyle::Clock)
+        )];
+        for table in self.tables(zone, now) {
+            sections.push(if table.rows.is_empty() {
+                format!("{}\nnone", table.title)
+            } else {
+                format!("{}\n{}", table.title, formatter.format(&table))
+            });
+        }
+        sections.join("\n\n")
+    }
+}
+
+/// Fetch the data, or `None` if it hasn't changed since the last fetch.
+fn fetch(api: &mut Vatsim) -> Result<Option<V3ResponseData>> {
+    match api.get_data()? {
+        DataResponse::Changed(data) => Ok(Some(data)),
+        DataResponse::NotModified | DataResponse::IdenticalBody => Ok(None),
+    }
+}
+
+/// Fetch the current data and print the board, again every `interval` if
+/// it's given.
+pub fn run(
+    icao: &str,
+    interval: Option<Duration>,
+    zone: DisplayZone,
+    use_cache: bool,
+    mirror: &MirrorSelection,
+    user_agent: &HeaderValue,
+    reporter: &mut Reporter,
+) -> Result<()> {
+    let mut api = Vatsim::with_mirror(use_cache, false, user_agent, mirror)?;
+    let mut data = None;
+    loop {
+        if let Some(new) = fetch(&mut api)? {
+            data = Some(new);
+        }
+        let Some(data) = &data else {
+            bail!("No data returned from VATSIM");
+        };
+        let now = Utc::now();
+        let Some(interval) = interval else {
+            reporter.data(Board::build(icao, data, now).text(zone, now))?;
+            return Ok(());
+        };
+        execute!(
+            std::io::stdout(),
+            terminal::Clear(terminal::ClearType::All),
+            crossterm::cursor::MoveTo(0, 0)
+        )?;
+        reporter.data(Board::build(icao, data, now).text(zone, now))?;
+        std::thread::sleep(interval);
+    }
+}
+
+/// Draw the board, with a table for each section.
+fn draw<B: Backend>(f: &mut Frame<B>, board: &Board, zone: DisplayZone, now: DateTime<Utc>) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(1),
+            Constraint::Percentage(45),
+            Constraint::Percentage(35),
+            Constraint::Min(4),
+        ])
+        .split(f.size());
+    f.render_widget(
+        Paragraph::new(format!(
+            " {}, updated {}. Q to exit.",
+            board.heading(),
+            format::format_time(now, zone, TimeStyle::Clock)
+        )),
+        chunks[0],
+    );
+    for (table, &area) in board.tables(zone, now).iter().zip(&chunks[1..]) {
+        let mut widths: Vec<u16> = table
+            .headers
+            .iter()
+            .map(|h| u16::try_from(h.len()).unwrap_or(u16::MAX))
+            .collect();
+        for row in &table.rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(u16::try_from(cell.chars().count()).unwrap_or(u16::MAX));
+            }
+        }
+        let widths: Vec<Constraint> = widths.into_iter().map(Constraint::Length).collect();
+        let widget = TableWidget::new(table.rows.iter().map(|row| Row::new(row.clone())))
+            .header(
+                Row::new(table.headers.clone())
+                    .style(Style::default().add_modifier(Modifier::BOLD)),
+            )
+            .block(
+                Block::default()
+                    .title(table.title.as_str())
+                    .borders(Borders::ALL),
+            )
+            .widths(&widths)
+            .column_spacing(2);
+        f.render_widget(widget, area);
+    }
+}
+
+/// Show the board in its own full-screen view until Q or Esc is pressed,
+/// refreshing every `interval` if it's given.
+pub fn run_tui(
+    icao: &str,
+    interval: Option<Duration>,
+    zone: DisplayZone,
+    use_cache: bool,
+    mirror: &MirrorSelection,
+    user_agent: &HeaderValue,
+) -> Result<()> {
+    let mut api = Vatsim::with_mirror(use_cache, false, user_agent, mirror)?;
+    let Some(mut data) = fetch(&mut api)? else {
+        bail!("No data returned from VATSIM");
+    };
+    let mut stdout = std::io::stdout();
+    let mode = crate::terminal::set_up(&mut stdout, false)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout))?;
+    terminal.hide_cursor()?;
+    let mut refreshed = Instant::now();
+    let result = loop {
+        let now = Utc::now();
+        let board = Board::build(icao, &data, now);
+        let drawn = terminal.draw(|f| {
+            draw(f, &board, zone, now);
+            if mode == Mode::Plain {
+                f.render_widget(Plain, f.size());
+            }
+        });
+        if let Err(e) = drawn {
+            break Err(e.into());
+        }
+        let wait = interval.map_or(Duration::from_secs(1), |interval| {
+            interval.saturating_sub(refreshed.elapsed())
+        });
+        match event::poll(wait) {
+            Ok(true) => match event::read() {
+                Ok(Event::Key(key)) if matches!(key.code, KeyCode::Char('q') | KeyCode::Esc) => {
+                    break Ok(());
+                }
+                Ok(_) => {}
+                Err(e) => break Err(e.into()),
+            },
+            Ok(false) => {}
+            Err(e) => break Err(e.into()),
+        }
+        if interval.is_some_and(|interval| refreshed.elapsed() >= interval) {
+            refreshed = Instant::now();
+            match fetch(&mut api) {
+                Ok(Some(new)) => data = new,
+                Ok(None) => {}
+                // keep showing the old board; the next refresh tries again
+                Err(e) => warn!("Could not refresh VATSIM data: {}", e),
+            }
+        }
+    };
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    terminal.show_cursor()?;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use chrono::TimeZone;
+
+    /// Kennedy's position in the airport database.
+    const KJFK: (f64, f64) = (40.64, -73.78);
+
+    fn now() -> DateTime<Utc> {
+        Utc.ymd(2022, 8, 1).and_hms(12, 0, 0)
+    }
+
+    /// A pilot `nm` due north of Kennedy at a ground speed.
+    fn pilot(callsign: &str, from: &str, to: &str, nm: f64, groundspeed: i64) -> Pilot {
+        Pilot {
+            latitude: KJFK.0 + nm / 60.0,
+            longitude: KJFK.1,
+            groundspeed,
+            altitude: if groundspeed > 0 { 10_000 } else { 13 },
+            ..testing::flying(callsign, from, to)
+        }
+    }
+
+    fn departing(callsign: &str, deptime: &str) -> Pilot {
+        let mut pilot = pilot(callsign, "KJFK", "EGLL", 0.5, 0);
+        if let Some(plan) = &mut pilot.flight_plan {
+            plan.deptime = deptime.to_owned();
+        }
+        pilot
+    }
+
+    /// Two inbounds, one landed, three outbounds, a pilot who left Kennedy
+    /// and landed at Heathrow, and no ATC.
+    fn scenario() -> V3ResponseData {
+        let mut landed_elsewhere = pilot("DAL4", "KJFK", "EGLL", 0.0, 0);
+        landed_elsewhere.latitude = 51.47;
+        landed_elsewhere.longitude = -0.45;
+        V3ResponseData {
+            pilots: vec![
+                pilot("AAL1", "KLAX", "KJFK", 120.0, 240),
+                pilot("UAL2", "KSFO", "kjfk", 30.0, 300),
+                pilot("JBU3", "KBOS", "KJFK", 1.0, 10),
+                departing("BAW5", "1230"),
+                departing("VIR6", "1215"),
+                departing("AFR7", ""),
+                landed_elsewhere,
+                pilot("SWA8", "KLAS", "KDEN", 10.0, 400),
+            ],
+            ..V3ResponseData::default()
+        }
+    }
+
+    fn callsigns<'a>(pilots: impl Iterator<Item = &'a Pilot>) -> Vec<&'a str> {
+        pilots.map(|p| p.callsign.as_str()).collect()
+    }
+
+    #[test]
+    fn inbounds_by_eta_and_outbounds_by_deptime() {
+        let data = scenario();
+        let board = Board::build(" kjfk", &data, now());
+        assert_eq!(board.icao, "KJFK");
+        assert_eq!(
+            callsigns(board.arrivals.iter().map(|a| a.pilot)),
+            ["UAL2", "AAL1"]
+        );
+        let etas: Vec<Option<i64>> = board
+            .arrivals
+            .iter()
+            .map(|a| a.eta.map(|eta| (eta - now()).num_minutes()))
+            .collect();
+        assert_eq!(etas, [Some(6), Some(30)]);
+        assert_eq!(
+            callsigns(board.departures.iter().map(|d| d.pilot)),
+            ["VIR6", "BAW5", "AFR7"]
+        );
+        assert!(board.controllers.is_empty());
+    }
+
+    #[test]
+    fn text_with_no_atc() {
+        let data = scenario();
+        let board = Board::build("KJFK", &data, now());
+        let text = board.text(DisplayZone::Utc, now());
+        assert!(text.starts_with("KJFK (Kennedy), 12:00z"), "{}", text);
+        assert!(text.contains("Arrivals (2)"), "{}", text);
+        assert!(text.contains("Departures (3)"), "{}", text);
+        assert!(text.ends_with("ATC (0)\nnone"), "{}", text);
+    }
+
+    #[test]
+    fn atc_at_the_airport_grouped_by_position() {
+        let mut data = scenario();
+        data.controllers = vec![
+            testing::controller("JFK_TWR", 4),
+            testing::controller("KJFK_GND", 3),
+            testing::controller("LGA_TWR", 4),
+            testing::controller("JFK_1_TWR", 4),
+        ];
+        data.atis = vec![testing::controller("KJFK_ATIS", 4)];
+        let board = Board::build("KJFK", &data, now());
+        let controllers: Vec<&str> = board
+            .controllers
+            .iter()
+            .map(|c| c.callsign.as_str())
+            .collect();
+        assert_eq!(
+            controllers,
+            ["JFK_TWR", "JFK_1_TWR", "KJFK_ATIS", "KJFK_GND"]
+        );
+    }
+
+    #[test]
+    fn airports_not_in_the_database() {
+        let mut data = scenario();
+        data.pilots.push(pilot("N123", "KXYZ", "KJFK", 1.0, 0));
+        let board = Board::build("KXYZ", &data, now());
+        assert_eq!(board.heading(), "KXYZ");
+        // with nowhere to measure from, any pilot on the ground is at it
+        assert_eq!(
+            callsigns(board.departures.iter().map(|d| d.pilot)),
+            ["N123"]
+        );
+    }
+}
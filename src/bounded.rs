@@ -0,0 +1,117 @@
+//! Collections that stop growing at a fixed size, for state kept across refreshes.
+
+use std::collections::VecDeque;
+
+/// Distinct items, most recently pushed first, holding at most `capacity`.
+///
+/// Pushing an item that's already in the list moves it to the front;
+/// pushing past the capacity drops the least recently pushed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MruList<T> {
+    items: Vec<T>,
+    capacity: usize,
+}
+
+impl<T: PartialEq> MruList<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            items: Vec::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Put an item at the front.
+    pub fn push(&mut self, item: T) {
+        self.items.retain(|existing| existing != &item);
+        self.items.insert(0, item);
+        self.items.truncate(self.capacity);
+    }
+
+    /// Items, most recently pushed first.
+    pub fn as_slice(&self) -> &[T] {
+        &self.items
+    }
+}
+
+/// The last `capacity` items pushed, oldest first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RingBuffer<T> {
+    items: VecDeque<T>,
+    capacity: usize,
+}
+
+impl<T> RingBuffer<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            items: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Add an item at the end, dropping the oldest if it's full.
+    pub fn push(&mut self, item: T) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.items.len() == self.capacity {
+            let _ = self.items.pop_front();
+        }
+        self.items.push_back(item);
+    }
+
+    /// Items, oldest first.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.items.iter()
+    }
+
+    /// The most recently pushed item.
+    pub fn last(&self) -> Option<&T> {
+        self.items.back()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mru_list_moves_repeats_to_the_front() {
+        let mut list = MruList::new(3);
+        for item in ["a", "b", "a", "c"] {
+            list.push(item);
+        }
+        assert_eq!(list.as_slice(), ["c", "a", "b"]);
+        list.push("b");
+        assert_eq!(list.as_slice(), ["b", "c", "a"]);
+    }
+
+    #[test]
+    fn mru_list_stays_at_its_capacity() {
+        let mut list = MruList::new(20);
+        for i in 0..10_000 {
+            list.push(i);
+        }
+        assert_eq!(list.as_slice().len(), 20);
+        assert_eq!(list.as_slice()[..2], [9_999, 9_998]);
+        let mut empty = MruList::new(0);
+        empty.push(1);
+        assert!(empty.as_slice().is_empty());
+    }
+
+    #[test]
+    fn ring_buffer_keeps_the_newest() {
+        let mut ring = RingBuffer::new(3);
+        assert_eq!(ring.last(), None);
+        for i in 0..10_000 {
+            ring.push(i);
+        }
+        assert_eq!(
+            ring.iter().copied().collect::<Vec<_>>(),
+            [9_997, 9_998, 9_999]
+        );
+        assert_eq!(ring.last(), Some(&9_999));
+        let mut empty = RingBuffer::new(0);
+        empty.push(1);
+        assert_eq!(empty.last(), None);
+    }
+}
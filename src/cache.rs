@@ -0,0 +1,219 @@
+//! On-disk cache of the VATSIM status document and events list.
+
+use crate::{
+    models::{Event, Status},
+    persist,
+};
+use anyhow::{anyhow, Result};
+use chrono::{Duration, Utc};
+use log::{debug, warn};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+/// Name of the status cache file in the cache directory.
+const STATUS_FILE_NAME: &str = "status.json";
+/// Name of the events cache file in the cache directory.
+const EVENTS_FILE_NAME: &str = "events.json";
+
+/// How long a cached status document is used before fetching it again.
+#[must_use]
+pub fn status_max_age() -> Duration {
+    Duration::hours(24)
+}
+
+/// How long the cached events list is used before fetching it again.
+#[must_use]
+pub fn events_max_age() -> Duration {
+    Duration::minutes(30)
+}
+
+/// A cached document with the time it was fetched.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct Cached<T> {
+    /// Unix timestamp of when the document was fetched.
+    fetched_at: i64,
+    #[serde(alias = "status")]
+    data: T,
+}
+
+/// Directory for the cache files, if the platform has one.
+///
+/// Tests get a directory of their own, to keep off the real cache.
+fn cache_dir() -> Option<PathBuf> {
+    if cfg!(test) {
+        let name = format!("vatsim_online-test-{}", std::process::id());
+        return Some(std::env::temp_dir().join(name));
+    }
+    dirs::cache_dir().map(|dir| dir.join("vatsim_online"))
+}
+
+/// Path to a cache file, if the platform has a cache directory.
+fn cache_path(file_name: &str) -> Option<PathBuf> {
+    cache_dir().map(|dir| dir.join(file_name))
+}
+
+/// Load a cached document, if it's younger than `max_age`.
+fn load<T: DeserializeOwned>(file_name: &str, max_age: Duration) -> Option<T> {
+    load_from(&cache_path(file_name)?, max_age)
+}
+
+/// Load a cached document from a file, if it's younger than `max_age`.
+///
+/// Missing, stale, and corrupt cache files are ignored.
+fn load_from<T: DeserializeOwned>(path: &Path, max_age: Duration) -> Option<T> {
+    let content = fs::read_to_string(path).ok()?;
+    let cached: Cached<T> = match serde_json::from_str(&content) {
+        Ok(cached) => cached,
+        Err(e) => {
+            warn!("Ignoring corrupt cache {}: {}", path.display(), e);
+            return None;
+        }
+    };
+    let age = Utc::now().timestamp() - cached.fetched_at;
+    if age < 0 || age > max_age.num_seconds() {
+        debug!("Cache {} is stale ({} seconds old)", path.display(), age);
+        return None;
+    }
+    debug!("Using cache {} ({} seconds old)", path.display(), age);
+    Some(cached.data)
+}
+
+/// Save a freshly-fetched document to the cache.
+fn save<T: Serialize>(file_name: &str, data: T) -> Result<()> {
+    let path =
+        cache_path(file_name).ok_or_else(|| anyhow!("No cache directory on this platform"))?;
+    save_to(&path, data)
+}
+
+/// Save a freshly-fetched document to a cache file.
+fn save_to<T: Serialize>(path: &Path, data: T) -> Result<()> {
+    let cached = Cached {
+        fetched_at: Utc::now().timestamp(),
+        data,
+    };
+    persist::write_atomic(path, serde_json::to_string(&cached)?.as_bytes())?;
+    debug!("Saved cache to {}", path.display());
+    Ok(())
+}
+
+/// Load the cached status document, if it's younger than `max_age`.
+#[must_use]
+pub fn load_status(max_age: Duration) -> Option<Status> {
+    load(STATUS_FILE_NAME, max_age)
+}
+
+/// Save a freshly-fetched status document to the cache.
+pub fn save_status(status: &Status) -> Result<()> {
+    save(STATUS_FILE_NAME, status)
+}
+
+/// Load the cached events list, if it's younger than `max_age`.
+#[must_use]
+pub fn load_events(max_age: Duration) -> Option<Vec<Event>> {
+    load(EVENTS_FILE_NAME, max_age)
+}
+
+/// Save a freshly-fetched events list to the cache.
+pub fn save_events(events: &[Event]) -> Result<()> {
+    save(EVENTS_FILE_NAME, events)
+}
+
+/// Delete the cached status document and events list.
+///
+/// Returns whether there was a file to delete.
+pub fn clear() -> Result<bool> {
+    let mut cleared = false;
+    for file_name in [STATUS_FILE_NAME, EVENTS_FILE_NAME] {
+        let path =
+            cache_path(file_name).ok_or_else(|| anyhow!("No cache directory on this platform"))?;
+        match fs::remove_file(&path) {
+            Ok(()) => cleared = true,
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(cleared)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::StatusData;
+
+    fn status() -> Status {
+        Status {
+            data: StatusData {
+                v3: vec!["https://data.vatsim.net/v3/vatsim-data.json".to_owned()],
+                transceivers: Vec::new(),
+                servers: Vec::new(),
+                servers_sweatbox: Vec::new(),
+                servers_all: Vec::new(),
+            },
+            user: Vec::new(),
+            metar: Vec::new(),
+        }
+    }
+
+    /// Write a cache file fetched `age` ago.
+    fn write_aged(path: &Path, age: Duration) {
+        let cached = Cached {
+            fetched_at: (Utc::now() - age).timestamp(),
+            data: status(),
+        };
+        fs::write(path, serde_json::to_string(&cached).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn fresh_cache_is_used() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(STATUS_FILE_NAME);
+        save_to(&path, status()).unwrap();
+        let loaded: Status = load_from(&path, status_max_age()).unwrap();
+        assert_eq!(loaded.data.v3, status().data.v3);
+    }
+
+    #[test]
+    fn stale_cache_expires() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(STATUS_FILE_NAME);
+        write_aged(&path, Duration::hours(23));
+        assert!(load_from::<Status>(&path, status_max_age()).is_some());
+        write_aged(&path, Duration::hours(25));
+        assert!(load_from::<Status>(&path, status_max_age()).is_none());
+    }
+
+    #[test]
+    fn cache_from_the_future_is_ignored() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(STATUS_FILE_NAME);
+        write_aged(&path, Duration::hours(-1));
+        assert!(load_from::<Status>(&path, status_max_age()).is_none());
+    }
+
+    #[test]
+    fn corrupt_and_missing_caches_are_ignored() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(STATUS_FILE_NAME);
+        assert!(load_from::<Status>(&path, status_max_age()).is_none());
+        for corrupt in ["", "{", "not json", r#"{"fetched_at": 1}"#, "\u{0}\u{0}"] {
+            fs::write(&path, corrupt).unwrap();
+            assert!(load_from::<Status>(&path, status_max_age()).is_none());
+        }
+    }
+
+    #[test]
+    fn old_status_caches_still_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(STATUS_FILE_NAME);
+        let old = serde_json::json!({
+            "fetched_at": Utc::now().timestamp(),
+            "status": status(),
+        });
+        fs::write(&path, old.to_string()).unwrap();
+        assert!(load_from::<Status>(&path, status_max_age()).is_some());
+    }
+}
@@ -0,0 +1,496 @@
+//! Callsign parsing: airlines for pilots, and position names for controllers.
+
+use crate::airports::{self, Airport};
+
+/// What kind of pilot callsign this is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PilotCallsign<'a> {
+    /// An airline flight, with the three-letter ICAO airline code: "DLH4AB".
+    Airline(&'a str),
+    /// An aircraft registration: "N123AB", "GABCD", or "HB-JVA".
+    Registration,
+    /// Anything else, like IATA-style "BA123" or made-up callsigns.
+    Other,
+}
+
+/// Work out whether a pilot callsign is an airline flight or a registration.
+///
+/// An airline callsign is three letters and then a flight number starting
+/// with a digit; letters after that, like "DAL12HEAVY", are allowed.
+pub fn classify_pilot(callsign: &str) -> PilotCallsign<'_> {
+    let callsign = callsign.trim();
+    let is_alphanumeric = callsign.chars().all(|c| c.is_ascii_alphanumeric());
+    let bytes = callsign.as_bytes();
+    if is_alphanumeric
+        && bytes.len() > 3
+        && bytes[..3].iter().all(u8::is_ascii_alphabetic)
+        && bytes[3].is_ascii_digit()
+    {
+        return PilotCallsign::Airline(&callsign[..3]);
+    }
+    let us_registration = bytes.len() > 1
+        && bytes.len() <= 6
+        && bytes[0].eq_ignore_ascii_case(&b'N')
+        && bytes[1].is_ascii_digit()
+        && is_alphanumeric;
+    let letters_only = (4..=6).contains(&bytes.len()) && bytes.iter().all(u8::is_ascii_alphabetic);
+    let dashed = callsign.split_once('-').is_some_and(|(country, rest)| {
+        (1..=2).contains(&country.len())
+            && !rest.is_empty()
+            && country
+                .chars()
+                .chain(rest.chars())
+                .all(|c| c.is_ascii_alphanumeric())
+    });
+    if us_registration || letters_only || dashed {
+        PilotCallsign::Registration
+    } else {
+        PilotCallsign::Other
+    }
+}
+
+/// The ICAO airline code of a pilot callsign, uppercased, if it's an airline flight.
+pub fn airline(callsign: &str) -> Option<String> {
+    match classify_pilot(callsign) {
+        PilotCallsign::Airline(code) => Some(code.to_uppercase()),
+        PilotCallsign::Registration | PilotCallsign::Other => None,
+    }
+}
+
+/// A position worked alongside another of the same name, shown by a
+/// callsign infix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PositionRole {
+    /// Relieving or splitting a position, like "SFO_1_TWR".
+    Relief,
+    /// A mentor with a student, like "SFO_M_TWR".
+    Mentor,
+    /// An instructor with a student, like "SFO_I_GND".
+    Instructor,
+}
+
+impl PositionRole {
+    /// Parse a callsign infix as a role.
+    ///
+    /// Only single letters and numbers are roles; longer infixes like the
+    /// "SC" in "LON_SC_CTR" name sectors.
+    fn from_infix(infix: &str) -> Option<Self> {
+        if !infix.is_empty() && infix.len() <= 2 && infix.bytes().all(|b| b.is_ascii_digit()) {
+            return Some(PositionRole::Relief);
+        }
+        match infix.to_uppercase().as_str() {
+            "M" => Some(PositionRole::Mentor),
+            "I" => Some(PositionRole::Instructor),
+            _ => None,
+        }
+    }
+
+    /// Short label for the controllers table.
+    pub fn badge(self) -> &'static str {
+        match self {
+            PositionRole::Relief => "relief",
+            PositionRole::Mentor => "mentor",
+            PositionRole::Instructor => "instr",
+        }
+    }
+}
+
+/// A controller callsign split into its segments.
+///
+/// "SFO_1_TWR" becomes a prefix of "SFO", infixes of `["1"]`,
+/// and a suffix of "TWR".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControllerCallsign<'a> {
+    pub prefix: &'a str,
+    pub infixes: Vec<&'a str>,
+    pub suffix: &'a str,
+}
+
+impl<'a> ControllerCallsign<'a> {
+    /// Split a callsign into its segments.
+    ///
+    /// Returns `None` for callsigns without at least a prefix and a suffix.
+    pub fn parse(callsign: &'a str) -> Option<Self> {
+        let mut parts: Vec<&str> = callsign.split('_').collect();
+        if parts.len() < 2 || parts.iter().any(|part| part.is_empty()) {
+            return None;
+        }
+        let prefix = parts.remove(0);
+        let suffix = parts.pop()?;
+        Some(Self {
+            prefix,
+            infixes: parts,
+            suffix,
+        })
+    }
+
+    /// The relief, mentor, or instructor role shown by the infixes, if any.
+    pub fn role(&self) -> Option<PositionRole> {
+        self.infixes
+            .iter()
+            .find_map(|infix| PositionRole::from_infix(infix))
+    }
+
+    /// The callsign of the position this one works alongside, without
+    /// role infixes: "SFO_TWR" for "SFO_M_TWR".
+    pub fn primary(&self) -> String {
+        let mut parts = vec![self.prefix];
+        parts.extend(
+            self.infixes
+                .iter()
+                .filter(|infix| PositionRole::from_infix(infix).is_none()),
+        );
+        parts.push(self.suffix);
+        parts.join("_")
+    }
+}
+
+/// The role badge for a controller callsign, if it has one.
+pub fn role_badge(callsign: &str) -> Option<&'static str> {
+    ControllerCallsign::parse(callsign)?
+        .role()
+        .map(PositionRole::badge)
+}
+
+/// Key that sorts a controller callsign right after the position it works
+/// alongside, grouping "SFO_1_TWR" and "SFO_M_TWR" with "SFO_TWR".
+pub fn grouped_key(callsign: &str) -> String {
+    match ControllerCallsign::parse(callsign).map(|parsed| parsed.primary()) {
+        Some(primary) if primary != callsign => format!("{} {}", primary, callsign),
+        _ => callsign.to_owned(),
+    }
+}
+
+/// A named area that isn't an airport: a FIR, ARTCC, or TRACON.
+struct Area {
+    prefix: &'static str,
+    name: &'static str,
+    /// Word used for "_CTR" positions: "Center", "Control", etc.
+    control: &'static str,
+}
+
+/// Shorthand for building the table below.
+const fn area(prefix: &'static str, name: &'static str, control: &'static str) -> Area {
+    Area {
+        prefix,
+        name,
+        control,
+    }
+}
+
+/// Embedded callsign prefixes for areas that aren't airports.
+static AREAS: &[Area] = &[
+    // United States ARTCCs, by their VATSIM callsign prefixes
+    area("ABQ", "Albuquerque", "Center"),
+    area("ANC", "Anchorage", "Center"),
+    area("ATL", "Atlanta", "Center"),
+    area("BOS", "Boston", "Center"),
+    area("CHI", "Chicago", "Center"),
+    area("CLE", "Cleveland", "Center"),
+    area("DC", "Washington", "Center"),
+    area("DEN", "Denver", "Center"),
+    area("FTW", "Fort Worth", "Center"),
+    area("HCF", "Honolulu", "Control"),
+    area("HOU", "Houston", "Center"),
+    area("IND", "Indianapolis", "Center"),
+    area("JAX", "Jacksonville", "Center"),
+    area("KC", "Kansas City", "Center"),
+    area("LAX", "Los Angeles", "Center"),
+    area("MEM", "Memphis", "Center"),
+    area("MIA", "Miami", "Center"),
+    area("MPLS", "Minneapolis", "Center"),
+    area("NY", "New York", "Center"),
+    area("OAK", "Oakland", "Center"),
+    area("SEA", "Seattle", "Center"),
+    area("SLC", "Salt Lake", "Center"),
+    // United States TRACONs
+    area("A80", "Atlanta", "Approach"),
+    area("A90", "Boston", "Approach"),
+    area("C90", "Chicago", "Approach"),
+    area("D01", "Denver", "Approach"),
+    area("D10", "Regional", "Approach"),
+    area("F11", "Orlando", "Approach"),
+    area("I90", "Houston", "Approach"),
+    area("L30", "Las Vegas", "Approach"),
+    area("M98", "Minneapolis", "Approach"),
+    area("N90", "New York", "Approach"),
+    area("NCT", "NorCal", "Approach"),
+    area("P50", "Phoenix", "Approach"),
+    area("P80", "Portland", "Approach"),
+    area("PCT", "Potomac", "Approach"),
+    area("S46", "Seattle", "Approach"),
+    area("S56", "Salt Lake", "Approach"),
+    area("SCT", "SoCal", "Approach"),
+    // Canada
+    area("CZEG", "Edmonton", "Center"),
+    area("CZQM", "Moncton", "Center"),
+    area("CZQX", "Gander", "Center"),
+    area("CZUL", "Montreal", "Center"),
+    area("CZVR", "Vancouver", "Center"),
+    area("CZWG", "Winnipeg", "Center"),
+    area("CZYZ", "Toronto", "Center"),
+    // Europe
+    area("BIRD", "Reykjavik", "Control"),
+    area("EBBU", "Brussels", "Control"),
+    area("EDGG", "Langen", "Radar"),
+    area("EDMM", "Munich", "Radar"),
+    area("EDWW", "Bremen", "Radar"),
+    area("EFIN", "Helsinki", "Control"),
+    area("EGPX", "Scottish", "Control"),
+    area("EGTT", "London", "Control"),
+    area("EHAA", "Amsterdam", "Radar"),
+    area("EISN", "Shannon", "Control"),
+    area("EKDK", "Copenhagen", "Control"),
+    area("ENOR", "Polaris", "Control"),
+    area("EPWW", "Warszawa", "Control"),
+    area("ESOS", "Sweden", "Control"),
+    area("LECB", "Barcelona", "Control"),
+    area("LECM", "Madrid", "Control"),
+    area("LFBB", "Bordeaux", "Control"),
+    area("LFEE", "Reims", "Control"),
+    area("LFFF", "Paris", "Control"),
+    area("LFMM", "Marseille", "Control"),
+    area("LFRR", "Brest", "Control"),
+    area("LHCC", "Budapest", "Control"),
+    area("LIMM", "Milano", "Control"),
+    area("LIRR", "Roma", "Control"),
+    area("LKAA", "Praha", "Control"),
+    area("LON", "London", "Control"),
+    area("LOVV", "Wien", "Radar"),
+    area("LPPC", "Lisboa", "Control"),
+    area("LSAS", "Swiss", "Radar"),
+    area("LTC", "London", "Control"),
+    area("SCO", "Scottish", "Control"),
+    // Oceania
+    area("NZZC", "New Zealand", "Control"),
+    area("YBBB", "Brisbane", "Centre"),
+    area("YMMM", "Melbourne", "Centre"),
+];
+
+/// Look up a named area by callsign prefix.
+fn area_by_prefix(prefix: &str) -> Option<&'static Area> {
+    AREAS
+        .iter()
+        .find(|area| area.prefix.eq_ignore_ascii_case(prefix))
+}
+
+/// Word used on the radio for a callsign's facility suffix.
+fn facility_word(suffix: &str) -> Option<&'static str> {
+    let word = match suffix.to_ascii_uppercase().as_str() {
+        "DEL" => "Delivery",
+        "RMP" => "Ramp",
+        "GND" => "Ground",
+        "TWR" => "Tower",
+        "APP" => "Approach",
+        "DEP" => "Departure",
+        "CTR" => "Control",
+        "FSS" => "Radio",
+        "ATIS" => "ATIS",
+        _ => return None,
+    };
+    Some(word)
+}
+
+/// Resolve a controller callsign to the name of the position.
+///
+/// "SFO_TWR" becomes "San Francisco Tower" and "LON_SC_CTR" becomes
+/// "London Control". Relief and instructor infixes are ignored. This
+/// is best-effort; the callsign itself is returned when it can't be
+/// resolved.
+pub fn position_name(callsign: &str) -> String {
+    resolve_position_name(callsign).unwrap_or_else(|| callsign.to_owned())
+}
+
+/// The airport a controller's position is at, to place them on the map.
+///
+/// The prefix is tried first, then the infixes, for positions like
+/// "N90_JFK_APP". Area positions like centers can't be placed.
+pub fn controller_airport(callsign: &str) -> Option<&'static Airport> {
+    let parsed = ControllerCallsign::parse(callsign)?;
+    std::iter::once(parsed.prefix)
+        .chain(parsed.infixes)
+        .find_map(airports::by_prefix)
+}
+
+/// Whether a controller's position is at an airport, by its ICAO code.
+///
+/// Positions whose prefix is an airport in the database are resolved
+/// through it, so "JFK_TWR" is at KJFK; otherwise the prefix has to be
+/// the ICAO code.
+pub fn is_at_airport(callsign: &str, icao: &str) -> bool {
+    match controller_airport(callsign) {
+        Some(airport) => airport.icao.eq_ignore_ascii_case(icao),
+        None => ControllerCallsign::parse(callsign)
+            .is_some_and(|parsed| parsed.prefix.eq_ignore_ascii_case(icao)),
+    }
+}
+
+/// Resolve a controller callsign to the name of the position, if possible.
+fn resolve_position_name(callsign: &str) -> Option<String> {
+    let parsed = ControllerCallsign::parse(callsign)?;
+    let word = facility_word(parsed.suffix)?;
+    let is_center = word == "Control";
+    // centers are usually named for an area, everything else for an airport
+    let area = area_by_prefix(parsed.prefix);
+    let airport = airports::by_prefix(parsed.prefix);
+    match (is_center, area, airport) {
+        (true, Some(area), _) => Some(format!("{} {}", area.name, area.control)),
+        (_, _, Some(airport)) => Some(format!("{} {}", airport.name, word)),
+        (false, Some(area), None) => Some(format!("{} {}", area.name, word)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_pilot_callsigns() {
+        assert_eq!(classify_pilot("AAL123"), PilotCallsign::Airline("AAL"));
+        assert_eq!(classify_pilot("DAL12HEAVY"), PilotCallsign::Airline("DAL"));
+        assert_eq!(classify_pilot("DLH4AB"), PilotCallsign::Airline("DLH"));
+        assert_eq!(classify_pilot(" baw1 "), PilotCallsign::Airline("baw"));
+        assert_eq!(classify_pilot("N123AB"), PilotCallsign::Registration);
+        assert_eq!(classify_pilot("n5"), PilotCallsign::Registration);
+        assert_eq!(classify_pilot("GABCD"), PilotCallsign::Registration);
+        assert_eq!(classify_pilot("HB-JVA"), PilotCallsign::Registration);
+        for other in ["BA123", "12345", "AAL", "AALX12", "N1234567", "AAL-12", ""] {
+            assert_eq!(classify_pilot(other), PilotCallsign::Other, "{:?}", other);
+        }
+    }
+
+    #[test]
+    fn airline_codes_are_uppercased() {
+        assert_eq!(airline("dlh4ab").as_deref(), Some("DLH"));
+        assert_eq!(airline("N123AB"), None);
+        assert_eq!(airline("BA123"), None);
+    }
+
+    #[test]
+    fn splits_multi_segment_callsigns() {
+        let parsed = ControllerCallsign::parse("SFO_1_TWR").unwrap();
+        assert_eq!(
+            (parsed.prefix, parsed.infixes, parsed.suffix),
+            ("SFO", vec!["1"], "TWR")
+        );
+        let parsed = ControllerCallsign::parse("N90_JFK_I_APP").unwrap();
+        assert_eq!(parsed.infixes, ["JFK", "I"]);
+        assert_eq!(
+            ControllerCallsign::parse("SFO_TWR").unwrap().infixes.len(),
+            0
+        );
+        for bad in ["", "SFO", "SFO_", "_TWR", "SFO__TWR"] {
+            assert_eq!(ControllerCallsign::parse(bad), None, "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn roles_from_infixes() {
+        let role = |callsign| ControllerCallsign::parse(callsign).unwrap().role();
+        assert_eq!(role("SFO_1_TWR"), Some(PositionRole::Relief));
+        assert_eq!(role("SFO_12_TWR"), Some(PositionRole::Relief));
+        assert_eq!(role("SFO_I_GND"), Some(PositionRole::Instructor));
+        assert_eq!(role("sfo_m_twr"), Some(PositionRole::Mentor));
+        assert_eq!(role("LON_SC_CTR"), None);
+        assert_eq!(role("SFO_123_TWR"), None);
+        assert_eq!(role_badge("SFO_I_GND"), Some("instr"));
+        assert_eq!(role_badge("SFO_TWR"), None);
+    }
+
+    #[test]
+    fn primary_drops_role_infixes_only() {
+        let primary = |callsign| ControllerCallsign::parse(callsign).unwrap().primary();
+        assert_eq!(primary("SFO_M_TWR"), "SFO_TWR");
+        assert_eq!(primary("SFO_1_I_TWR"), "SFO_TWR");
+        assert_eq!(primary("LON_SC_CTR"), "LON_SC_CTR");
+    }
+
+    #[test]
+    fn parses_real_world_callsigns() {
+        let check = |callsign, parts: (&str, &[&str], &str), role| {
+            let parsed = ControllerCallsign::parse(callsign).unwrap();
+            assert_eq!(
+                (parsed.prefix, parsed.infixes.as_slice(), parsed.suffix),
+                parts,
+                "{}",
+                callsign
+            );
+            assert_eq!(parsed.role(), role, "{}", callsign);
+        };
+        check("KJFK_GND", ("KJFK", &[], "GND"), None);
+        check("EDDF_N_APP", ("EDDF", &["N"], "APP"), None);
+        check("LON_SC_CTR", ("LON", &["SC"], "CTR"), None);
+        check("NY_CAM_APP", ("NY", &["CAM"], "APP"), None);
+        check("VATSIM_SUP", ("VATSIM", &[], "SUP"), None);
+        check(
+            "SFO_2_TWR",
+            ("SFO", &["2"], "TWR"),
+            Some(PositionRole::Relief),
+        );
+        check(
+            "LFPG_M_DEL",
+            ("LFPG", &["M"], "DEL"),
+            Some(PositionRole::Mentor),
+        );
+        check(
+            "EGTT_I_CTR",
+            ("EGTT", &["I"], "CTR"),
+            Some(PositionRole::Instructor),
+        );
+    }
+
+    #[test]
+    fn grouped_positions_sort_after_their_primary() {
+        let mut callsigns = vec![
+            "SFO_UTWR",
+            "SFO_M_TWR",
+            "SFO_1_TWR",
+            "SFO_TWR",
+            "SFOX",
+            "SFO_GND",
+        ];
+        callsigns.sort_by_cached_key(|callsign| grouped_key(callsign));
+        assert_eq!(
+            callsigns,
+            [
+                "SFOX",
+                "SFO_GND",
+                "SFO_TWR",
+                "SFO_1_TWR",
+                "SFO_M_TWR",
+                "SFO_UTWR"
+            ]
+        );
+    }
+
+    #[test]
+    fn position_names() {
+        assert_eq!(position_name("SFO_TWR"), "San Francisco Tower");
+        assert_eq!(position_name("SFO_1_TWR"), "San Francisco Tower");
+        assert_eq!(position_name("SFO_I_GND"), "San Francisco Ground");
+        assert_eq!(position_name("KSFO_DEL"), "San Francisco Delivery");
+        assert_eq!(position_name("LON_SC_CTR"), "London Control");
+        assert_eq!(position_name("YMMM_CTR"), "Melbourne Centre");
+    }
+
+    #[test]
+    fn unresolved_positions_keep_the_callsign() {
+        for callsign in ["XXXX_TWR", "SFO_OBS", "SFO", "ZZZ_1_CTR", "EDDF_X_PLAN"] {
+            assert_eq!(position_name(callsign), callsign);
+        }
+    }
+
+    #[test]
+    fn airports_from_prefix_or_infix() {
+        assert_eq!(controller_airport("SFO_TWR").unwrap().icao, "KSFO");
+        assert_eq!(controller_airport("egll_n_app").unwrap().icao, "EGLL");
+        assert_eq!(controller_airport("N90_JFK_APP").unwrap().icao, "KJFK");
+        assert!(controller_airport("LON_SC_CTR").is_none());
+        assert!(controller_airport("XXXX_TWR").is_none());
+        assert!(controller_airport("EGLL").is_none());
+        assert!(is_at_airport("SFO_1_TWR", "ksfo"));
+        assert!(is_at_airport("XXXX_TWR", "XXXX"));
+        assert!(!is_at_airport("SFO_TWR", "KLAX"));
+    }
+}
@@ -0,0 +1,318 @@
+//! Health check of the connection to VATSIM, for diagnosing startup problems.
+
+use crate::{
+    api::{DataResponse, Vatsim},
+    format,
+    report::Reporter,
+};
+use anyhow::Result;
+use reqwest::header::HeaderValue;
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+/// Airport whose METAR is requested to check the METAR endpoint.
+const METAR_AIRPORT: &str = "EGLL";
+
+/// How a step of the check went.
+#[derive(Debug)]
+enum Outcome {
+    Passed(String),
+    Failed(String),
+    /// The step couldn't run, with the reason.
+    Skipped(String),
+}
+
+/// One line of the report.
+#[derive(Debug)]
+struct Step {
+    name: &'static str,
+    outcome: Outcome,
+    elapsed: Option<Duration>,
+}
+
+impl Step {
+    /// Run a step, timing it.
+    fn timed<T>(
+        name: &'static str,
+        run: impl FnOnce() -> Result<T>,
+        describe: impl FnOnce(&T) -> String,
+    ) -> (Self, Option<T>) {
+        let start = Instant::now();
+        let result = run();
+        let elapsed = Some(start.elapsed());
+        match result {
+            Ok(value) => (
+                Self {
+                    name,
+                    outcome: Outcome::Passed(describe(&value)),
+                    elapsed,
+                },
+                Some(value),
+            ),
+            Err(e) => (
+                Self {
+                    name,
+                    outcome: Outcome::Failed(format!("{:#}", e)),
+                    elapsed,
+                },
+                None,
+            ),
+        }
+    }
+
+    /// A step that couldn't run.
+    fn skipped(name: &'static str, reason: &str) -> Self {
+        Self {
+            name,
+            outcome: Outcome::Skipped(reason.to_owned()),
+            elapsed: None,
+        }
+    }
+
+    /// Whether the step didn't fail.
+    ///
+    /// A skipped step isn't a failure itself; the step it needed is.
+    fn passed(&self) -> bool {
+        !matches!(self.outcome, Outcome::Failed(_))
+    }
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (label, detail) = match &self.outcome {
+            Outcome::Passed(detail) => ("ok", detail),
+            Outcome::Failed(detail) => ("FAIL", detail),
+            Outcome::Skipped(detail) => ("skip", detail),
+        };
+        write!(f, "[{:>4}] {}: {}", label, self.name, detail)?;
+        if let Some(elapsed) = self.elapsed {
+            write!(f, " ({} ms)", elapsed.as_millis())?;
+        }
+        Ok(())
+    }
+}
+
+/// The API calls the check makes, so the report can be tested without
+/// a server.
+trait Connection {
+    fn mirror_count(&self) -> usize;
+    fn get_data(&mut self) -> Result<DataResponse>;
+    fn has_metar(&self) -> bool;
+    fn get_metar(&self, icao: &str) -> Result<String>;
+}
+
+impl Connection for Vatsim {
+    fn mirror_count(&self) -> usize {
+        Vatsim::mirror_count(self)
+    }
+
+    fn get_data(&mut self) -> Result<DataResponse> {
+        Vatsim::get_data(self)
+    }
+
+    fn has_metar(&self) -> bool {
+        Vatsim::has_metar(self)
+    }
+
+    fn get_metar(&self, icao: &str) -> Result<String> {
+        Vatsim::get_metar(self, icao)
+    }
+}
+
+/// Check each link in the chain from the status endpoint to the data,
+/// printing a line for each. Returns whether everything passed.
+///
+/// The status document is always fetched fresh, and the data is fetched
+/// with the same mirror failover as the interface.
+pub fn run(user_agent: &HeaderValue, reporter: &mut Reporter) -> bool {
+    check(|| Vatsim::new(false, false, user_agent), reporter)
+}
+
+/// Run the check on the connection `connect` makes to the status endpoint.
+fn check<C: Connection>(connect: impl FnOnce() -> Result<C>, reporter: &mut Reporter) -> bool {
+    let mut steps = Vec::new();
+    let (step, vatsim) = Step::timed("Status endpoint", connect, |vatsim| {
+        format!("{} V3 mirrors listed", vatsim.mirror_count())
+    });
+    steps.push(step);
+    if let Some(mut vatsim) = vatsim {
+        let (step, _) = Step::timed(
+            "V3 data",
+            || vatsim.get_data(),
+            |response| match response {
+                DataResponse::Changed(data) => format!(
+                    "{} pilots, {} controllers",
+                    format::thousands(data.pilots.len()),
+                    format::thousands(data.controllers.len())
+                ),
+                _ => "data unchanged".to_owned(),
+            },
+        );
+        steps.push(step);
+        if vatsim.has_metar() {
+            let (step, _) = Step::timed(
+                "METAR endpoint",
+                || vatsim.get_metar(METAR_AIRPORT),
+                String::clone,
+            );
+            steps.push(step);
+        } else {
+            steps.push(Step::skipped(
+                "METAR endpoint",
+                "none listed in the status document",
+            ));
+        }
+    } else {
+        steps.push(Step::skipped("V3 data", "needs the status endpoint"));
+        steps.push(Step::skipped("METAR endpoint", "needs the status endpoint"));
+    }
+    for step in &steps {
+        let _ = reporter.data(step);
+    }
+    steps.iter().all(Step::passed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{models::V3ResponseData, report::Verbosity, testing};
+    use anyhow::anyhow;
+
+    /// A connection with canned answers.
+    struct Fake {
+        data: Option<DataResponse>,
+        has_metar: bool,
+        metar: Option<String>,
+    }
+
+    impl Connection for Fake {
+        fn mirror_count(&self) -> usize {
+            2
+        }
+
+        fn get_data(&mut self) -> Result<DataResponse> {
+            self.data
+                .take()
+                .ok_or_else(|| anyhow!("mirror returned non-JSON response (text/html)"))
+        }
+
+        fn has_metar(&self) -> bool {
+            self.has_metar
+        }
+
+        fn get_metar(&self, _icao: &str) -> Result<String> {
+            self.metar
+                .clone()
+                .ok_or_else(|| anyhow!("Got status 500 from METAR endpoint"))
+        }
+    }
+
+    /// A connection where every call works.
+    fn working() -> Fake {
+        Fake {
+            data: Some(DataResponse::Changed(V3ResponseData {
+                pilots: vec![testing::pilot("BAW123")],
+                controllers: vec![testing::controller("EGLL_TWR", 4)],
+                ..V3ResponseData::default()
+            })),
+            has_metar: true,
+            metar: Some("EGLL 011220Z 24010KT CAVOK 20/10 Q1015".to_owned()),
+        }
+    }
+
+    /// Run the check, returning whether it passed and the report lines
+    /// without their timings.
+    fn run_check(connection: Result<Fake>) -> (bool, Vec<String>) {
+        let (mut reporter, out, _) = Reporter::captured(Verbosity::Silent);
+        let passed = check(|| connection, &mut reporter);
+        let lines = out
+            .text()
+            .lines()
+            .map(|line| match line.rsplit_once(" (") {
+                Some((line, timing)) if timing.ends_with(" ms)") => line.to_owned(),
+                _ => line.to_owned(),
+            })
+            .collect();
+        (passed, lines)
+    }
+
+    #[test]
+    fn everything_passes() {
+        let (passed, lines) = run_check(Ok(working()));
+        assert!(passed);
+        assert_eq!(
+            lines,
+            [
+                "[  ok] Status endpoint: 2 V3 mirrors listed",
+                "[  ok] V3 data: 1 pilots, 1 controllers",
+                "[  ok] METAR endpoint: EGLL 011220Z 24010KT CAVOK 20/10 Q1015",
+            ]
+        );
+    }
+
+    #[test]
+    fn status_endpoint_down() {
+        let (passed, lines) = run_check(Err(anyhow!("Got status 503 from status endpoint")));
+        assert!(!passed);
+        assert_eq!(
+            lines,
+            [
+                "[FAIL] Status endpoint: Got status 503 from status endpoint",
+                "[skip] V3 data: needs the status endpoint",
+                "[skip] METAR endpoint: needs the status endpoint",
+            ]
+        );
+    }
+
+    #[test]
+    fn no_mirror_has_data() {
+        let (passed, lines) = run_check(Ok(Fake {
+            data: None,
+            ..working()
+        }));
+        assert!(!passed);
+        assert_eq!(
+            lines[1],
+            "[FAIL] V3 data: mirror returned non-JSON response (text/html)"
+        );
+        assert!(lines[2].starts_with("[  ok] METAR endpoint"));
+    }
+
+    #[test]
+    fn metar_endpoint_down() {
+        let (passed, lines) = run_check(Ok(Fake {
+            metar: None,
+            ..working()
+        }));
+        assert!(!passed);
+        assert_eq!(
+            lines[2],
+            "[FAIL] METAR endpoint: Got status 500 from METAR endpoint"
+        );
+    }
+
+    #[test]
+    fn no_metar_endpoint_is_optional() {
+        let (passed, lines) = run_check(Ok(Fake {
+            has_metar: false,
+            ..working()
+        }));
+        assert!(passed);
+        assert_eq!(
+            lines[2],
+            "[skip] METAR endpoint: none listed in the status document"
+        );
+    }
+
+    #[test]
+    fn failed_steps_are_timed() {
+        let (mut reporter, out, _) = Reporter::captured(Verbosity::Normal);
+        let _ = check(|| Err::<Fake, _>(anyhow!("timed out")), &mut reporter);
+        let text = out.text();
+        let first = text.lines().next().unwrap();
+        assert!(first.ends_with(" ms)"), "{}", first);
+        assert!(!text.lines().nth(1).unwrap().ends_with(" ms)"));
+    }
+}
@@ -0,0 +1,454 @@
+//! The command line arguments.
+//!
+//! Kept in their own module so that the lint the clap derive trips over can
+//! be allowed for its expansion alone.
+
+// the derive names items like `ToString` by their full paths
+#![allow(unused_qualifications)]
+
+use crate::{
+    alerts::{self, AlertKind},
+    atc, config,
+    count::CountExpr,
+    favorites,
+    filter::AirlineFilter,
+    output::OutputFormat,
+    polling::PollInterval,
+    query::Query,
+};
+use clap::{ArgAction, Parser, Subcommand};
+use reqwest::header::HeaderValue;
+use serde::Deserialize;
+use std::{collections::HashSet, net::SocketAddr, path::PathBuf};
+use vatsim_online::{
+    api,
+    format::{DisplayZone, Locale},
+    mirrors::MirrorChoice,
+    privacy::Privacy,
+    reference::Facility,
+    sanity::Limits,
+    sort::DefaultSort,
+};
+
+/// Fewest seconds between refreshes with `--interval auto`, by default.
+pub const DEFAULT_MIN_INTERVAL_SECS: u64 = 15;
+/// Most seconds between refreshes with `--interval auto`, by default.
+pub const DEFAULT_MAX_INTERVAL_SECS: u64 = 300;
+/// Seconds to wait for the first data, by default.
+pub const DEFAULT_STARTUP_TIMEOUT_SECS: u64 = 30;
+/// Minutes without input before refreshing pauses, by default.
+pub const DEFAULT_IDLE_MINUTES: u64 = 30;
+/// Minutes without an update before a client is dimmed, by default.
+pub const DEFAULT_STALE_MINUTES: i64 = 5;
+/// Fewest seconds between alerts, by default.
+pub const DEFAULT_ALERT_INTERVAL_SECS: u64 = 30;
+
+#[derive(Debug, Parser)]
+#[clap(author, version, about, long_about = None)]
+pub struct Args {
+    #[clap(flatten)]
+    pub settings: Settings,
+
+    /// Print only the requested data from the commands that print; -qq also
+    /// hides warnings, leaving errors and exit codes
+    #[clap(short, long, global = true, action = ArgAction::Count)]
+    pub quiet: u8,
+
+    /// With another instance running, show the data it fetches instead of
+    /// fetching it too
+    #[clap(long)]
+    pub attach: bool,
+
+    /// Print network statistics as key=value lines and exit
+    #[clap(long)]
+    pub summary: bool,
+
+    /// Print the JSON Schema of the commands' JSON output and exit
+    #[clap(long)]
+    pub schema: bool,
+
+    /// Print how many clients match and exit: "pilots", "controllers", or a
+    /// query like "arrival=KJFK"
+    #[clap(long, value_parser = CountExpr::parse)]
+    pub count: Option<CountExpr>,
+
+    /// Read V3 data from this file instead of fetching it, for --count
+    #[clap(long, requires = "count")]
+    pub input: Option<PathBuf>,
+
+    /// Use the settings of this profile from the config file, over its base
+    /// settings; options given here still win
+    #[clap(long)]
+    pub profile: Option<String>,
+
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+}
+
+impl Args {
+    /// The settings given on the command line.
+    pub fn given(&self) -> Settings {
+        Settings {
+            quiet: (self.quiet > 0).then_some(self.quiet),
+            ..self.settings.clone()
+        }
+    }
+}
+
+/// The options that can also be set in the config file, each `None` where
+/// it isn't given.
+///
+/// Switches can be given as `--switch=false`, to turn off one that the
+/// config file turns on. In the config file, options taking text take a
+/// string, or a number where the option is one, and repeatable ones a list.
+#[derive(Debug, Clone, Default, PartialEq, clap::Args, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Settings {
+    /// Enable debug logging to a 'vatsim_online.log' file
+    #[clap(
+        short,
+        long,
+        value_name = "BOOL",
+        min_values = 0,
+        max_values = 1,
+        require_equals = true,
+        default_missing_value = "true"
+    )]
+    pub debug: Option<bool>,
+
+    /// Restore the tab and selection from the last run
+    #[clap(
+        short,
+        long,
+        value_name = "BOOL",
+        min_values = 0,
+        max_values = 1,
+        require_equals = true,
+        default_missing_value = "true"
+    )]
+    pub restore: Option<bool>,
+
+    /// Quit without saving the session, keeping the one saved before
+    #[clap(
+        long,
+        value_name = "BOOL",
+        min_values = 0,
+        max_values = 1,
+        require_equals = true,
+        default_missing_value = "true"
+    )]
+    pub no_save_on_exit: Option<bool>,
+
+    /// How many times -q is given; set from the config file only
+    #[clap(skip)]
+    pub quiet: Option<u8>,
+
+    /// Always fetch the status document instead of using the cached copy
+    #[clap(
+        long,
+        value_name = "BOOL",
+        min_values = 0,
+        max_values = 1,
+        require_equals = true,
+        default_missing_value = "true"
+    )]
+    pub no_cache: Option<bool>,
+
+    /// Save bandwidth with compressed, conditional requests and less frequent refreshes
+    #[clap(
+        long,
+        value_name = "BOOL",
+        min_values = 0,
+        max_values = 1,
+        require_equals = true,
+        default_missing_value = "true"
+    )]
+    pub low_bandwidth: Option<bool>,
+
+    /// Seconds between refreshes, or 'auto' to refresh faster while the
+    /// network is changing quickly
+    #[clap(long, value_parser = PollInterval::parse)]
+    #[serde(deserialize_with = "config::parsed")]
+    pub interval: Option<PollInterval>,
+
+    /// Fewest seconds between refreshes with --interval auto; 15 by default
+    #[clap(long)]
+    pub min_interval: Option<u64>,
+
+    /// Most seconds between refreshes with --interval auto; 300 by default
+    #[clap(long)]
+    pub max_interval: Option<u64>,
+
+    /// Give each table row a second line with details; Z switches while running
+    #[clap(
+        long,
+        value_name = "BOOL",
+        min_values = 0,
+        max_values = 1,
+        require_equals = true,
+        default_missing_value = "true"
+    )]
+    pub comfortable: Option<bool>,
+
+    /// Give up on the first data after this many seconds and show the error
+    /// screen; 0 waits forever, and 30 by default
+    #[clap(long)]
+    pub startup_timeout: Option<u64>,
+
+    /// Pause refreshing after this many minutes without input; 0 never
+    /// pauses, and 30 by default
+    #[clap(long)]
+    pub idle_minutes: Option<u64>,
+
+    /// Dim clients whose records haven't updated for this many minutes; 0
+    /// never dims, and 5 by default
+    #[clap(long)]
+    pub stale_minutes: Option<i64>,
+
+    /// Sound an alert for these events: favorites (connecting) and
+    /// emergency (squawks); none by default
+    #[clap(long, value_parser = alerts::parse_kinds)]
+    #[serde(deserialize_with = "config::alert_kinds")]
+    pub alert: Option<HashSet<AlertKind>>,
+
+    /// Sound alerts at most once in this many seconds; 30 by default
+    #[clap(long)]
+    pub alert_interval: Option<u64>,
+
+    /// Run this shell command for alerts instead of ringing the bell, like
+    /// "paplay /usr/share/sounds/freedesktop/stereo/bell.oga"
+    #[clap(long, value_name = "COMMAND")]
+    pub alert_sound: Option<String>,
+
+    /// Also save summaries exported from the detail popup to this directory
+    #[clap(long)]
+    pub summary_dir: Option<PathBuf>,
+
+    /// Save raw V3 responses that fail to parse to this directory, keeping
+    /// only the most recent
+    #[clap(long)]
+    pub debug_dump: Option<PathBuf>,
+
+    /// With --debug-dump, save every response, not just those that fail
+    #[clap(
+        long,
+        value_name = "BOOL",
+        min_values = 0,
+        max_values = 1,
+        require_equals = true,
+        default_missing_value = "true"
+    )]
+    pub debug_dump_all: Option<bool>,
+
+    /// Limits past which a pilot's record is taken to be broken, like
+    /// "speed=2500,min-altitude=-1500,max-altitude=100000,jump=600"
+    #[clap(long, value_parser = Limits::parse)]
+    #[serde(deserialize_with = "config::parsed")]
+    pub sanity_limits: Option<Limits>,
+
+    /// With --summary, compare with a day ago using the snapshots in this
+    /// directory, and record a new one there
+    #[clap(long)]
+    pub snapshot_dir: Option<PathBuf>,
+
+    /// Use this V3 mirror: an index into the status document's list, or a URL
+    /// to use without asking the status endpoint
+    #[clap(long, value_parser = MirrorChoice::parse)]
+    #[serde(deserialize_with = "config::parsed")]
+    pub mirror: Option<MirrorChoice>,
+
+    /// Let a mirror chosen with --mirror fail over to the others
+    #[clap(
+        long,
+        value_name = "BOOL",
+        min_values = 0,
+        max_values = 1,
+        require_equals = true,
+        default_missing_value = "true"
+    )]
+    pub allow_failover: Option<bool>,
+
+    /// Race the V3 mirrors at startup and use the fastest, falling back to
+    /// the others from fastest to slowest
+    #[clap(
+        long,
+        value_name = "BOOL",
+        min_values = 0,
+        max_values = 1,
+        require_equals = true,
+        default_missing_value = "true"
+    )]
+    pub fastest_mirror: Option<bool>,
+
+    /// Seed the random order the V3 mirrors are tried in, to reproduce
+    /// a run when debugging
+    #[clap(long)]
+    pub seed: Option<u64>,
+
+    /// Send this User-Agent header instead of the default
+    #[clap(long, value_parser = api::parse_user_agent)]
+    #[serde(deserialize_with = "config::parsed")]
+    pub user_agent: Option<HeaderValue>,
+
+    /// Show times in this time zone: local, utc, an offset like +05:30 or
+    /// UTC-3, or a zone like Europe/London; local by default
+    #[clap(long, value_parser = DisplayZone::parse)]
+    #[serde(deserialize_with = "config::parsed")]
+    pub timezone: Option<DisplayZone>,
+
+    /// Write numbers and dates for this locale: auto for the environment's,
+    /// or a tag like de-DE or en-US; 1,234.5 and "16 Oct" by default
+    #[clap(long, value_parser = Locale::parse)]
+    #[serde(deserialize_with = "config::parsed")]
+    pub locale: Option<Locale>,
+
+    /// Hide real names, for streaming: --privacy shows CIDs in their place,
+    /// --privacy=initials first names and initials, like "John S."
+    #[clap(
+        long,
+        value_name = "LEVEL",
+        value_parser = Privacy::parse,
+        min_values = 0,
+        max_values = 1,
+        require_equals = true,
+        default_missing_value = "cid"
+    )]
+    #[serde(deserialize_with = "config::parsed")]
+    pub privacy: Option<Privacy>,
+
+    /// Filter the table, like "departure=EG* and (altitude>30000 or groundspeed<100)"
+    #[clap(long, value_parser = Query::parse)]
+    #[serde(deserialize_with = "config::parsed")]
+    pub query: Option<Query>,
+
+    /// Only show pilots flying for these airlines, like "DLH,BAW"
+    #[clap(long, value_parser = AirlineFilter::parse)]
+    #[serde(deserialize_with = "config::parsed")]
+    pub airline: Option<AirlineFilter>,
+
+    /// Sort a tab to start with and when 0 is pressed, like "pilots:dep:desc" or
+    /// "controllers:position,callsign"; can be repeated
+    #[clap(long, value_parser = DefaultSort::parse)]
+    #[serde(deserialize_with = "config::parsed_list")]
+    pub sort: Option<Vec<DefaultSort>>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Print an arrivals and departures board for an airport, with its ATC
+    Airport {
+        /// ICAO code of the airport, like KJFK
+        icao: String,
+
+        /// Show the board in its own full-screen view
+        #[clap(long)]
+        tui: bool,
+
+        /// Refresh the board every this many seconds
+        #[clap(long)]
+        interval: Option<u64>,
+    },
+    /// Check whether an airport has ATC online, exiting with 0 if so, 1 if
+    /// not, and 2 on errors
+    Atc {
+        /// ICAO code of the airport, like KSFO
+        icao: String,
+
+        /// Only count these position types, like TWR,APP; any by default
+        #[clap(long, value_parser = atc::parse_positions)]
+        positions: Option<HashSet<Facility>>,
+
+        /// Print the result as JSON
+        #[clap(long)]
+        json: bool,
+    },
+    /// Manage the on-disk cache
+    Cache {
+        #[clap(subcommand)]
+        action: CacheAction,
+    },
+    /// Check that VATSIM's status endpoint, data mirrors, and METAR endpoint respond
+    Check,
+    /// Compare pilot and controller counts with the same time yesterday
+    CompareDay {
+        /// Directory of snapshots recorded by --summary --snapshot-dir
+        dir: PathBuf,
+    },
+    /// Print a heat map of where pilots are, in 10 degree cells
+    Density {
+        /// Use digits instead of shaded blocks
+        #[clap(long)]
+        ascii: bool,
+    },
+    /// Check whether clients listed in a file are online
+    Lookup {
+        /// File with one CID or callsign per line; '#' starts a comment
+        #[clap(long)]
+        file: PathBuf,
+
+        /// Print the results as JSON
+        #[clap(long)]
+        json: bool,
+
+        /// Exit with an error code if any client is offline
+        #[clap(long)]
+        fail_if_missing: bool,
+    },
+    /// Print the current METAR for an airport
+    Metar {
+        /// ICAO code of the airport, like EGLL
+        icao: String,
+
+        /// Also decode it into wind, visibility, ceiling, and flight category
+        #[clap(long)]
+        decode: bool,
+    },
+    /// Print the pilots or controllers table, filtered by --query and sorted by --sort
+    List {
+        /// List controllers instead of pilots
+        #[clap(long)]
+        controllers: bool,
+
+        /// Output format: text, csv, json, markdown, or html
+        #[clap(long, default_value = "text", value_parser = OutputFormat::parse)]
+        format: OutputFormat,
+    },
+    /// Manage favorite clients, which are highlighted in the table
+    Favorites {
+        #[clap(subcommand)]
+        action: favorites::FavoritesAction,
+    },
+    /// Serve what changes on the network as JSON at /events, the last
+    /// events or those after ?since=<id>
+    Serve {
+        /// Address to listen on
+        #[clap(long, default_value = "127.0.0.1:7878")]
+        address: SocketAddr,
+
+        /// How many events to keep
+        #[clap(long, default_value_t = 1000)]
+        keep: usize,
+
+        /// Refresh every this many seconds
+        #[clap(long, default_value_t = 15)]
+        interval: u64,
+    },
+    /// Print the tour of the interface shown on first run
+    Tour,
+    /// Print what changes on the network as it happens, one event a line
+    Watch {
+        /// Print each event as a JSON object
+        #[clap(long)]
+        json: bool,
+
+        /// Refresh every this many seconds
+        #[clap(long, default_value_t = 15)]
+        interval: u64,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum CacheAction {
+    /// Delete the cached status document and events list
+    Clear,
+}
@@ -0,0 +1,382 @@
+//! Table columns and the math for fitting them on screen.
+
+/// A column that can be shown in the table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Column {
+    pub header: &'static str,
+    /// Minimum width in cells for the column to be readable.
+    pub width: u16,
+}
+
+/// Shorthand for building the tables below.
+const fn column(header: &'static str, width: u16) -> Column {
+    Column { header, width }
+}
+
+/// Columns in the pilots table, in order.
+pub static PILOT_COLUMNS: &[Column] = &[
+    column("Callsign", 10),
+    column("Name", 24),
+    column("Aircraft", 8),
+    column("Rules", 5),
+    column("Phase", 5),
+    column("Lat", 10),
+    column("Long", 11),
+    column("TOD", 8),
+    column("Dep", 5),
+    column("Special", 7),
+    column("Enroute", 7),
+    column("Fuel", 12),
+    column("ATC", 10),
+];
+
+/// Column in [`PILOT_COLUMNS`] with the filed departure time.
+pub const PILOT_DEPARTURE_COLUMN: usize = 8;
+
+/// Column in [`PILOT_COLUMNS`] with the special operations badge.
+pub const PILOT_SPECIAL_COLUMN: usize = 9;
+
+/// Column in [`PILOT_COLUMNS`] with the filed enroute time.
+pub const PILOT_ENROUTE_COLUMN: usize = 10;
+
+/// Column in [`PILOT_COLUMNS`] with the filed fuel time.
+pub const PILOT_FUEL_COLUMN: usize = 11;
+
+/// Column in [`PILOT_COLUMNS`] with the controller covering the pilot.
+pub const PILOT_ATC_COLUMN: usize = 12;
+
+/// Columns in the pilots table when filtered to a controller's range.
+pub static PILOT_RANGE_COLUMNS: &[Column] = &[
+    column("Callsign", 10),
+    column("Name", 24),
+    column("Aircraft", 8),
+    column("Rules", 5),
+    column("Phase", 5),
+    column("Lat", 10),
+    column("Long", 11),
+    column("TOD", 8),
+    column("Dep", 5),
+    column("Special", 7),
+    column("Enroute", 7),
+    column("Fuel", 12),
+    column("ATC", 10),
+    column("Distance", 8),
+];
+
+/// Column in [`PILOT_RANGE_COLUMNS`] with the distance from the controller.
+pub const PILOT_DISTANCE_COLUMN: usize = 13;
+
+/// Columns in the pilots table when filtered to squawk mismatches.
+pub static PILOT_SQUAWK_COLUMNS: &[Column] = &[
+    column("Callsign", 10),
+    column("Name", 24),
+    column("Aircraft", 8),
+    column("Rules", 5),
+    column("Phase", 5),
+    column("Lat", 10),
+    column("Long", 11),
+    column("TOD", 8),
+    column("Dep", 5),
+    column("Special", 7),
+    column("Enroute", 7),
+    column("Fuel", 12),
+    column("ATC", 10),
+    column("Squawk", 6),
+    column("Assigned", 8),
+];
+
+/// Column in [`PILOT_SQUAWK_COLUMNS`] with the code squawked.
+pub const PILOT_SQUAWK_COLUMN: usize = 13;
+
+/// Column in [`PILOT_SQUAWK_COLUMNS`] with the assigned code.
+pub const PILOT_ASSIGNED_COLUMN: usize = 14;
+
+/// Columns in the controllers table, in order.
+pub static CONTROLLER_COLUMNS: &[Column] = &[
+    column("Callsign", 12),
+    column("Position", 24),
+    column("Name", 24),
+    column("Frequency", 9),
+    column("Rating", 6),
+    column("Traffic", 7),
+    column("Role", 6),
+    column("Load", 6),
+];
+
+/// Columns in the inbound pane of the airport split.
+pub static INBOUND_COLUMNS: &[Column] = &[
+    column("Callsign", 12),
+    column("Aircraft", 8),
+    column("From", 5),
+    column("ETA", 6),
+    column("Phase", 5),
+];
+
+/// Columns in the outbound pane of the airport split.
+pub static OUTBOUND_COLUMNS: &[Column] = &[
+    column("Callsign", 12),
+    column("Aircraft", 8),
+    column("To", 5),
+    column("Departs", 7),
+    column("Phase", 5),
+];
+
+/// Columns in the controller coverage view, in order.
+pub static COVERAGE_COLUMNS: &[Column] = &[
+    column("Callsign", 12),
+    column("Position", 24),
+    column("Online", 8),
+    column("Logon", 6),
+    column("Notes", 16),
+];
+
+/// Column in [`COVERAGE_COLUMNS`] with the logon time.
+pub const COVERAGE_LOGON_COLUMN: usize = 3;
+
+/// Columns in the events table, in order.
+pub static EVENT_COLUMNS: &[Column] = &[
+    column("Name", 32),
+    column("Starts", 10),
+    column("Ends", 10),
+    column("Airports", 16),
+    column("Status", 8),
+];
+
+/// Column in [`EVENT_COLUMNS`] with the start time.
+pub const EVENT_START_COLUMN: usize = 1;
+
+/// Space between columns.
+pub const COLUMN_SPACING: u16 = 1;
+
+/// Narrowest a column can be resized to.
+const MIN_RESIZED_WIDTH: u16 = 3;
+
+/// Width taken up by a set of columns, including the spacing between them.
+fn total_width(widths: &[u16], indexes: &[usize]) -> u16 {
+    let columns: u16 = indexes.iter().map(|&i| widths[i]).sum();
+    let spacing = u16::try_from(indexes.len().saturating_sub(1)).unwrap_or(u16::MAX);
+    columns.saturating_add(spacing * COLUMN_SPACING)
+}
+
+/// Pick the columns to show in `available` cells.
+///
+/// The first column is always shown, followed by as many columns as fit
+/// starting at `offset`. At least one column after the first is shown
+/// even if it doesn't fully fit.
+#[must_use]
+pub fn visible_columns(widths: &[u16], offset: usize, available: u16) -> Vec<usize> {
+    if widths.is_empty() {
+        return Vec::new();
+    }
+    let mut visible = vec![0];
+    for index in offset.max(1)..widths.len() {
+        visible.push(index);
+        if visible.len() > 2 && total_width(widths, &visible) > available {
+            let _ = visible.pop();
+            break;
+        }
+    }
+    visible
+}
+
+/// The largest useful scroll offset: the one that brings the last column
+/// into view with as many columns before it as fit.
+#[must_use]
+pub fn max_offset(widths: &[u16], available: u16) -> usize {
+    let mut offset = widths.len().saturating_sub(1).max(1);
+    while offset > 1 {
+        let candidate = offset - 1;
+        let visible = visible_columns(widths, candidate, available);
+        if visible.last() != Some(&(widths.len() - 1)) {
+            break;
+        }
+        offset = candidate;
+    }
+    offset
+}
+
+/// Split `amount` cells between columns in proportion to their weights,
+/// giving any remainder to the heaviest columns first.
+fn spread(amount: u16, weights: &[u16]) -> Vec<u16> {
+    let total: u32 = weights.iter().map(|&w| u32::from(w)).sum();
+    if total == 0 {
+        return vec![0; weights.len()];
+    }
+    let mut shares: Vec<u16> = weights
+        .iter()
+        .map(|&w| u16::try_from(u32::from(amount) * u32::from(w) / total).unwrap_or(0))
+        .collect();
+    let mut order: Vec<usize> = (0..weights.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse(weights[i]));
+    let mut left = amount.saturating_sub(shares.iter().sum());
+    for &i in &order {
+        if left == 0 {
+            break;
+        }
+        if weights[i] > 0 {
+            shares[i] += 1;
+            left -= 1;
+        }
+    }
+    shares
+}
+
+/// Widths to draw the visible columns at in `available` cells.
+///
+/// When every column is shown, the spare room is spread over them in
+/// proportion to their widths. Columns that run past the end are cut
+/// short, so the widths always fit.
+#[must_use]
+pub fn drawn_widths(widths: &[u16], visible: &[usize], available: u16) -> Vec<u16> {
+    let mut drawn: Vec<u16> = visible.iter().map(|&i| widths[i]).collect();
+    let used = total_width(widths, visible);
+    if visible.len() == widths.len() && used < available {
+        let extras = spread(available - used, &drawn);
+        for (width, extra) in drawn.iter_mut().zip(extras) {
+            *width += extra;
+        }
+    }
+    let mut left = available;
+    for width in &mut drawn {
+        *width = (*width).min(left);
+        left = left.saturating_sub(*width).saturating_sub(COLUMN_SPACING);
+    }
+    drawn
+}
+
+/// Widen one column by `delta` cells, or narrow it for a negative
+/// `delta`, keeping the total width the same.
+///
+/// The room comes from, or goes to, the other columns in proportion to
+/// their widths, and no column gets narrower than a few cells.
+#[must_use]
+pub fn resize(widths: &[u16], index: usize, delta: i16) -> Vec<u16> {
+    let mut resized = widths.to_vec();
+    if index >= widths.len() || widths.len() < 2 {
+        return resized;
+    }
+    let others: Vec<usize> = (0..widths.len()).filter(|&i| i != index).collect();
+    let amount = delta.unsigned_abs();
+    if delta > 0 {
+        let room: Vec<u16> = others
+            .iter()
+            .map(|&i| widths[i].saturating_sub(MIN_RESIZED_WIDTH))
+            .collect();
+        let amount = amount.min(room.iter().sum());
+        for (&i, share) in others.iter().zip(spread(amount, &room)) {
+            resized[i] -= share;
+        }
+        resized[index] += amount;
+    } else {
+        let amount = amount.min(widths[index].saturating_sub(MIN_RESIZED_WIDTH));
+        let weights: Vec<u16> = others.iter().map(|&i| widths[i]).collect();
+        for (&i, share) in others.iter().zip(spread(amount, &weights)) {
+            resized[i] += share;
+        }
+        resized[index] -= amount;
+    }
+    resized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIDTHS: [u16; 4] = [10, 24, 8, 5];
+
+    #[test]
+    fn packs_columns_after_the_frozen_first() {
+        assert_eq!(visible_columns(&WIDTHS, 1, 100), [0, 1, 2, 3]);
+        assert_eq!(visible_columns(&WIDTHS, 1, 30), [0, 1]);
+        assert_eq!(visible_columns(&WIDTHS, 2, 30), [0, 2, 3]);
+        assert_eq!(visible_columns(&WIDTHS, 0, 30), [0, 1]);
+        assert_eq!(visible_columns(&WIDTHS, 3, 5), [0, 3]);
+        assert!(visible_columns(&[], 1, 30).is_empty());
+        assert_eq!(visible_columns(&[10], 1, 30), [0]);
+    }
+
+    #[test]
+    fn drawn_widths_fill_the_room_or_are_cut_short() {
+        assert_eq!(drawn_widths(&WIDTHS, &[0, 1, 2, 3], 60), [12, 30, 9, 6]);
+        assert_eq!(drawn_widths(&WIDTHS, &[0, 1, 2, 3], 50), WIDTHS);
+        assert_eq!(drawn_widths(&WIDTHS, &[0, 2, 3], 60), [10, 8, 5]);
+        assert_eq!(drawn_widths(&WIDTHS, &[0, 1], 30), [10, 19]);
+        assert_eq!(drawn_widths(&WIDTHS, &[0, 3], 5), [5, 0]);
+    }
+
+    fn total(widths: &[u16]) -> u16 {
+        widths.iter().sum()
+    }
+
+    #[test]
+    fn spread_is_proportional_and_exact() {
+        assert_eq!(spread(10, &[1, 1]), [5, 5]);
+        assert_eq!(spread(10, &[3, 1]), [8, 2]);
+        // the remainder goes to the heaviest first
+        assert_eq!(spread(5, &[2, 4, 2]), [1, 3, 1]);
+        assert_eq!(spread(3, &[0, 5]), [0, 3]);
+        assert_eq!(spread(4, &[0, 0]), [0, 0]);
+        for amount in 0..40 {
+            assert_eq!(total(&spread(amount, &WIDTHS)), amount);
+        }
+    }
+
+    #[test]
+    fn resize_keeps_the_total_width() {
+        for index in 0..WIDTHS.len() {
+            for delta in [-30, -5, -1, 1, 5, 30] {
+                let resized = resize(&WIDTHS, index, delta);
+                assert_eq!(total(&resized), total(&WIDTHS), "{} by {}", index, delta);
+                assert!(
+                    resized.iter().all(|&w| w >= MIN_RESIZED_WIDTH),
+                    "{} by {}: {:?}",
+                    index,
+                    delta,
+                    resized
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn resize_takes_room_in_proportion() {
+        // a single cell comes from, or goes to, the widest
+        assert_eq!(resize(&WIDTHS, 1, 1), [9, 25, 8, 5]);
+        assert_eq!(resize(&WIDTHS, 1, -1), [11, 23, 8, 5]);
+        assert_eq!(resize(&WIDTHS, 0, 6), [16, 19, 7, 5]);
+        assert_eq!(resize(&[20, 20, 20], 0, 10), [30, 15, 15]);
+        assert_eq!(resize(&[20, 20, 20], 0, -10), [10, 25, 25]);
+    }
+
+    #[test]
+    fn resize_stops_at_the_minimum() {
+        // the others can only give up to the minimum
+        assert_eq!(resize(&[10, 5, 4], 0, 10), [13, 3, 3]);
+        assert_eq!(resize(&[10, 5, 4], 0, 10), resize(&[10, 5, 4], 0, 3));
+        // and a column only shrinks to it
+        assert_eq!(resize(&[5, 10, 10], 0, -10), [3, 11, 11]);
+        assert_eq!(resize(&[3, 10, 10], 0, -1), [3, 10, 10]);
+    }
+
+    #[test]
+    fn resize_ignores_what_it_cant_resize() {
+        assert_eq!(resize(&WIDTHS, 4, 1), WIDTHS);
+        assert_eq!(resize(&[10], 0, 1), [10]);
+        assert!(resize(&[], 0, 1).is_empty());
+        assert_eq!(resize(&WIDTHS, 1, 0), WIDTHS);
+    }
+
+    #[test]
+    fn max_offset_brings_the_last_column_into_view() {
+        assert_eq!(max_offset(&WIDTHS, 100), 1);
+        assert_eq!(max_offset(&WIDTHS, 30), 2);
+        assert_eq!(max_offset(&WIDTHS, 10), 3);
+        assert_eq!(max_offset(&[10], 30), 1);
+        let widths: Vec<u16> = PILOT_COLUMNS.iter().map(|c| c.width).collect();
+        let offset = max_offset(&widths, 58);
+        assert_eq!(
+            visible_columns(&widths, offset, 58).last(),
+            Some(&(widths.len() - 1))
+        );
+    }
+}
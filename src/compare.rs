@@ -0,0 +1,242 @@
+//! Whether the network is busier or quieter than at the same time yesterday.
+
+use crate::{
+    format::{self, DisplayZone, TimeStyle},
+    report::{fetch_data, DataSource, Reporter},
+    snapshots::{self, Counts},
+};
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use std::path::Path;
+
+/// How far from exactly a day ago a snapshot can be and still count.
+const TOLERANCE_MINUTES: i64 = 15;
+
+/// Counts now and from a snapshot about a day ago.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DayComparison {
+    pub now: Counts,
+    pub then: Counts,
+    /// When the snapshot was recorded.
+    pub then_time: DateTime<Utc>,
+}
+
+/// Percentage change from `then` to `now`, or `None` if `then` is zero.
+#[allow(clippy::cast_precision_loss)]
+pub fn percent_change(now: usize, then: usize) -> Option<f64> {
+    (then > 0).then(|| (now as f64 - then as f64) / then as f64 * 100.0)
+}
+
+/// A count now and then with the change, like "1,234 vs 1,100 (+12.2%)".
+pub fn delta_text(now: usize, then: usize) -> String {
+    let change = match percent_change(now, then) {
+        Some(change) => {
+            // Round first, so a tiny drop shows as "+0.0%" rather than "-0.0%".
+            let change = (change * 10.0).round() / 10.0 + 0.0;
+            format!(
+                "{}{}%",
+                if change >= 0.0 { "+" } else { "" },
+                format::decimal(change, 1)
+            )
+        }
+        None if now == 0 => "no change".to_owned(),
+        None => "up from none".to_owned(),
+    };
+    format!(
+        "{} vs {} ({})",
+        format::thousands(now),
+        format::thousands(then),
+        change
+    )
+}
+
+/// Compare the counts with the snapshot closest to a day before `now`.
+///
+/// `None` if the directory has no snapshot close enough.
+pub fn yesterday(dir: &Path, now: DateTime<Utc>, counts: Counts) -> Result<Option<DayComparison>> {
+    let snapshots = snapshots::scan(dir)?;
+    let Some(snapshot) = snapshots::closest(
+        &snapshots,
+        now - Duration::days(1),
+        Duration::minutes(TOLERANCE_MINUTES),
+    ) else {
+        return Ok(None);
+    };
+    Ok(Some(DayComparison {
+        now: counts,
+        then: snapshots::load_counts(&snapshot.path)?,
+        then_time: snapshot.time,
+    }))
+}
+
+/// One line for the `--summary` output.
+pub fn summary_line(comparison: Option<&DayComparison>) -> String {
+    let value = comparison.map_or_else(
+        || "no snapshot from around this time yesterday".to_owned(),
+        |comparison| {
+            format!(
+                "pilots {}, controllers {}",
+                delta_text(comparison.now.pilots, comparison.then.pilots),
+                delta_text(comparison.now.controllers, comparison.then.controllers)
+            )
+        },
+    );
+    format!("vs_yesterday={}", value)
+}
+
+/// Lines for the `compare-day` subcommand.
+pub fn text(comparison: Option<&DayComparison>, zone: DisplayZone) -> String {
+    let Some(comparison) = comparison else {
+        return format!(
+            "No snapshot from within {} minutes of this time yesterday to compare with",
+            TOLERANCE_MINUTES
+        );
+    };
+    let verdict = match comparison.now.pilots.cmp(&comparison.then.pilots) {
+        std::cmp::Ordering::Greater => "Busier than yesterday",
+        std::cmp::Ordering::Less => "Quieter than yesterday",
+        std::cmp::Ordering::Equal => "As busy as yesterday",
+    };
+    format!(
+        "{}, compared with {}:\nPilots: {}\nControllers: {}",
+        verdict,
+        format::format_time(comparison.then_time, zone, TimeStyle::Day),
+        delta_text(comparison.now.pilots, comparison.then.pilots),
+        delta_text(comparison.now.controllers, comparison.then.controllers)
+    )
+}
+
+/// Print how the network now compares with the snapshots in `dir` from a
+/// day ago.
+pub fn run(
+    dir: &Path,
+    zone: DisplayZone,
+    source: &DataSource,
+    reporter: &mut Reporter,
+) -> Result<()> {
+    let data = fetch_data(source, reporter)?;
+    let comparison = yesterday(dir, Utc::now(), Counts::from_data(&data))?;
+    reporter.data(text(comparison.as_ref(), zone))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use std::fs;
+
+    fn counts(pilots: usize, controllers: usize) -> Counts {
+        Counts {
+            pilots,
+            controllers,
+        }
+    }
+
+    fn noon() -> DateTime<Utc> {
+        Utc.ymd(2026, 10, 16).and_hms(12, 0, 0)
+    }
+
+    #[test]
+    fn percent_changes() {
+        let text = |now, then| percent_change(now, then).map(|change| format::decimal(change, 2));
+        assert_eq!(text(150, 100).as_deref(), Some("50.00"));
+        assert_eq!(text(50, 100).as_deref(), Some("-50.00"));
+        assert_eq!(text(100, 100).as_deref(), Some("0.00"));
+        assert_eq!(text(0, 100).as_deref(), Some("-100.00"));
+        assert_eq!(text(1, 3).as_deref(), Some("-66.67"));
+        assert_eq!(text(5, 0), None);
+        assert_eq!(text(0, 0), None);
+    }
+
+    #[test]
+    fn delta_texts() {
+        assert_eq!(delta_text(1234, 1100), "1,234 vs 1,100 (+12.2%)");
+        assert_eq!(delta_text(900, 1200), "900 vs 1,200 (-25.0%)");
+        assert_eq!(delta_text(40, 40), "40 vs 40 (+0.0%)");
+        assert_eq!(delta_text(9999, 10000), "9,999 vs 10,000 (+0.0%)");
+        assert_eq!(delta_text(12, 0), "12 vs 0 (up from none)");
+        assert_eq!(delta_text(0, 0), "0 vs 0 (no change)");
+    }
+
+    #[test]
+    fn compared_with_the_snapshot_from_a_day_ago() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |name: &str, pilots: usize| {
+            let text = format!("pilots_total={}\ncontrollers_total=100\n", pilots);
+            fs::write(dir.path().join(name), text).unwrap();
+        };
+        write("20261015T114000Z.txt", 700);
+        write("20261015T120800Z.txt", 800);
+        write("20261015T130000Z.txt", 900);
+        write("20261016T114500Z.txt", 1000);
+
+        let comparison = yesterday(dir.path(), noon(), counts(1000, 90))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            comparison,
+            DayComparison {
+                now: counts(1000, 90),
+                then: counts(800, 100),
+                then_time: Utc.ymd(2026, 10, 15).and_hms(12, 8, 0),
+            }
+        );
+        assert_eq!(
+            summary_line(Some(&comparison)),
+            "vs_yesterday=pilots 1,000 vs 800 (+25.0%), controllers 90 vs 100 (-10.0%)"
+        );
+        assert_eq!(
+            text(Some(&comparison), DisplayZone::Utc),
+            "Busier than yesterday, compared with 15 Oct 12:08z:\n\
+             Pilots: 1,000 vs 800 (+25.0%)\n\
+             Controllers: 90 vs 100 (-10.0%)"
+        );
+
+        // nothing within 15 minutes of 12:30 yesterday
+        assert_eq!(
+            yesterday(dir.path(), noon() + Duration::minutes(30), counts(1, 1)).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn no_history_is_said_rather_than_zeros() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(
+            yesterday(dir.path(), noon(), counts(1000, 90)).unwrap(),
+            None
+        );
+        assert_eq!(
+            yesterday(&dir.path().join("missing"), noon(), counts(1000, 90)).unwrap(),
+            None
+        );
+        assert_eq!(
+            summary_line(None),
+            "vs_yesterday=no snapshot from around this time yesterday"
+        );
+        assert_eq!(
+            text(None, DisplayZone::Utc),
+            "No snapshot from within 15 minutes of this time yesterday to compare with"
+        );
+    }
+
+    #[test]
+    fn verdict_follows_the_pilot_count() {
+        let verdict = |now, then| {
+            let comparison = DayComparison {
+                now: counts(now, 0),
+                then: counts(then, 50),
+                then_time: noon(),
+            };
+            text(Some(&comparison), DisplayZone::Utc)
+                .lines()
+                .next()
+                .unwrap()
+                .to_owned()
+        };
+        assert!(verdict(10, 5).starts_with("Busier than yesterday"));
+        assert!(verdict(5, 10).starts_with("Quieter than yesterday"));
+        assert!(verdict(5, 5).starts_with("As busy as yesterday"));
+    }
+}
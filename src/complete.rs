@@ -0,0 +1,144 @@
+//! Tab completion of field names and values in the search prompt.
+
+use crate::filter::Field;
+use std::collections::BTreeSet;
+
+/// Most candidates offered for one completion.
+const MAX_CANDIDATES: usize = 50;
+
+/// Characters that end a field name and start a value in a query.
+const OPERATOR_CHARS: [char; 4] = ['=', '!', '<', '>'];
+
+/// What the word being completed is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    /// A field name, before any comparison.
+    Field,
+    /// The value compared against a field.
+    Value(Field),
+}
+
+/// The word at the end of the input to complete: where it starts, and
+/// whether it's a field name or a field's value.
+///
+/// Returns `None` if the word is a value for a field that doesn't exist.
+pub fn target(input: &str) -> Option<(usize, Target)> {
+    let word_start = input
+        .rfind(|c: char| c.is_whitespace() || c == '(')
+        .map_or(0, |i| i + 1);
+    let word = &input[word_start..];
+    let Some(op_start) = word.find(OPERATOR_CHARS) else {
+        return Some((word_start, Target::Field));
+    };
+    let field = Field::from_name(&word[..op_start])?;
+    let value_start = word.rfind(OPERATOR_CHARS).map_or(op_start, |i| i + 1);
+    Some((word_start + value_start, Target::Value(field)))
+}
+
+/// Values that start with `prefix`, ignoring case: unique, sorted, and at
+/// most [`MAX_CANDIDATES`] of them.
+pub fn candidates<'a>(values: impl IntoIterator<Item = &'a str>, prefix: &str) -> Vec<String> {
+    let prefix = prefix.to_uppercase();
+    values
+        .into_iter()
+        .filter(|value| !value.is_empty() && value.to_uppercase().starts_with(&prefix))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .take(MAX_CANDIDATES)
+        .map(str::to_owned)
+        .collect()
+}
+
+/// Completions of a word, cycled through with repeated presses of Tab.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Completion {
+    /// Where in the input the completed word starts.
+    start: usize,
+    pub candidates: Vec<String>,
+    /// Candidate in the input now.
+    pub index: usize,
+}
+
+impl Completion {
+    /// Start completing the word at `start` with the candidates, putting
+    /// the first in the input.
+    ///
+    /// Returns `None` if there's nothing to complete with.
+    pub fn start(input: &mut String, start: usize, candidates: Vec<String>) -> Option<Self> {
+        let first = candidates.first()?;
+        input.truncate(start);
+        input.push_str(first);
+        Some(Self {
+            start,
+            candidates,
+            index: 0,
+        })
+    }
+
+    /// Put the next candidate in the input, going back to the first after the last.
+    pub fn next(&mut self, input: &mut String) {
+        self.index = (self.index + 1) % self.candidates.len();
+        input.truncate(self.start);
+        input.push_str(&self.candidates[self.index]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn targets_the_last_word() {
+        assert_eq!(target(""), Some((0, Target::Field)));
+        assert_eq!(target("dep"), Some((0, Target::Field)));
+        assert_eq!(target("callsign=BAW* and (arr"), Some((19, Target::Field)));
+        assert_eq!(
+            target("departure=eg"),
+            Some((10, Target::Value(Field::Departure)))
+        );
+        assert_eq!(
+            target("x=1 and Server!=U"),
+            Some((16, Target::Value(Field::Server)))
+        );
+        assert_eq!(
+            target("altitude>="),
+            Some((10, Target::Value(Field::Altitude)))
+        );
+        assert_eq!(target("nonsense=a"), None);
+    }
+
+    #[test]
+    fn candidates_are_unique_sorted_and_ignore_case() {
+        let values = ["EGLL", "KJFK", "egkk", "EGLL", "", "EDDF"];
+        assert_eq!(candidates(values, "eg"), ["EGLL", "egkk"]);
+        assert_eq!(candidates(values, ""), ["EDDF", "EGLL", "KJFK", "egkk"]);
+        assert!(candidates(values, "X").is_empty());
+    }
+
+    #[test]
+    fn candidates_are_bounded() {
+        let values: Vec<String> = (0..100).map(|i| format!("BAW{:03}", i)).collect();
+        let found = candidates(values.iter().map(String::as_str), "baw");
+        assert_eq!(found.len(), MAX_CANDIDATES);
+        assert_eq!(
+            (found[0].as_str(), found[49].as_str()),
+            ("BAW000", "BAW049")
+        );
+    }
+
+    #[test]
+    fn tab_cycles_through_candidates() {
+        let mut input = "departure=eg".to_owned();
+        let candidates = vec!["EGKK".to_owned(), "EGLL".to_owned()];
+        let mut completion = Completion::start(&mut input, 10, candidates).unwrap();
+        assert_eq!((input.as_str(), completion.index), ("departure=EGKK", 0));
+        completion.next(&mut input);
+        assert_eq!((input.as_str(), completion.index), ("departure=EGLL", 1));
+        completion.next(&mut input);
+        assert_eq!((input.as_str(), completion.index), ("departure=EGKK", 0));
+
+        let mut input = "departure=x".to_owned();
+        assert_eq!(Completion::start(&mut input, 10, Vec::new()), None);
+        assert_eq!(input, "departure=x");
+    }
+}
@@ -0,0 +1,530 @@
+//! Settings from a config file, with named profiles for different ways of
+//! using the program.
+//!
+//! The file is `config.toml` in the config directory. Settings are named
+//! after the long command line options, and a profile's settings replace
+//! the base ones when it's chosen with `--profile`:
+//!
+//! ```toml
+//! timezone = "utc"
+//!
+//! [profile.events]
+//! interval = "auto"
+//! alert = { favorites = true, emergency = true }
+//! alert-sound = "paplay bell.oga"
+//!
+//! [profile.casual]
+//! comfortable = true
+//! sort = ["pilots:dep"]
+//! ```
+//!
+//! Options given on the command line win over both. TOML, unlike the JSON
+//! the other files are kept in, is written by hand: it takes comments, and
+//! the profiles read as sections.
+
+use crate::{
+    alerts::{self, AlertKind},
+    cli::Settings,
+    filter::AirlineFilter,
+    polling::PollInterval,
+    query::Query,
+};
+use anyhow::{anyhow, bail, Context, Result};
+use reqwest::header::HeaderValue;
+use serde::{
+    de::{self, Error as _},
+    Deserialize, Deserializer,
+};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+use toml::value::{Table, Value};
+use vatsim_online::{
+    api,
+    format::{DisplayZone, Locale},
+    mirrors::MirrorChoice,
+    privacy::Privacy,
+    sanity::Limits,
+    sort::DefaultSort,
+};
+
+/// Version of the config file format.
+const CONFIG_VERSION: i64 = 1;
+/// Name of the config file in the config directory.
+const CONFIG_FILE_NAME: &str = "config.toml";
+
+/// The config file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Config {
+    /// Settings used with or without a profile.
+    settings: Settings,
+    profiles: BTreeMap<String, Settings>,
+}
+
+impl Config {
+    /// Parse and check the contents of a config file.
+    ///
+    /// The settings are the top-level keys, besides an optional `version`
+    /// and the `profile` table.
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut table: Table = toml::from_str(content)?;
+        match table.remove("version") {
+            None | Some(Value::Integer(CONFIG_VERSION)) => {}
+            Some(version) => bail!(
+                "The config file has version {} (expected {})",
+                version,
+                CONFIG_VERSION
+            ),
+        }
+        let profiles = match table.remove("profile") {
+            Some(profiles) => profiles.try_into().context("Invalid profile")?,
+            None => BTreeMap::new(),
+        };
+        Ok(Self {
+            settings: Value::Table(table).try_into()?,
+            profiles,
+        })
+    }
+
+    /// A profile's settings, or an error listing the profiles there are.
+    pub fn profile(&self, name: &str) -> Result<&Settings> {
+        self.profiles.get(name).ok_or_else(|| {
+            if self.profiles.is_empty() {
+                anyhow!("Unknown profile '{}': the config file has none", name)
+            } else {
+                let names: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+                anyhow!(
+                    "Unknown profile '{}', expected one of: {}",
+                    name,
+                    names.join(", ")
+                )
+            }
+        })
+    }
+
+    /// The settings to run with: these, then the named profile's, then the
+    /// ones given on the command line.
+    pub fn resolve(&self, profile: Option<&str>, given: Settings) -> Result<Settings> {
+        let profile = profile.map(|name| self.profile(name)).transpose()?;
+        let settings = merge(&self.settings, profile, given);
+        check(&settings)?;
+        Ok(settings)
+    }
+}
+
+/// Path to the config file, if the platform has a config directory.
+pub fn config_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("vatsim_online").join(CONFIG_FILE_NAME))
+}
+
+/// Load the config from a file. A missing file has no settings.
+pub fn load_from(path: &Path) -> Result<Config> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Config::default()),
+        Err(e) => return Err(e).with_context(|| format!("Could not read {}", path.display())),
+    };
+    Config::parse(&content).with_context(|| format!("Invalid config in {}", path.display()))
+}
+
+/// Load the config, if the platform has a config directory.
+pub fn load() -> Result<Config> {
+    config_path().map_or_else(|| Ok(Config::default()), |path| load_from(&path))
+}
+
+impl Settings {
+    /// These settings, with `under`'s wherever these have none.
+    #[must_use]
+    fn or(self, under: Settings) -> Settings {
+        // destructured, so a new setting can't be left out
+        let Settings {
+            debug,
+            restore,
+            no_save_on_exit,
+            quiet,
+            no_cache,
+            low_bandwidth,
+            interval,
+            min_interval,
+            max_interval,
+            comfortable,
+            startup_timeout,
+            idle_minutes,
+            stale_minutes,
+            alert,
+            alert_interval,
+            alert_sound,
+            summary_dir,
+            debug_dump,
+            debug_dump_all,
+            sanity_limits,
+            snapshot_dir,
+            mirror,
+            allow_failover,
+            fastest_mirror,
+            seed,
+            user_agent,
+            timezone,
+            locale,
+            privacy,
+            query,
+            airline,
+            sort,
+        } = self;
+        Settings {
+            debug: debug.or(under.debug),
+            restore: restore.or(under.restore),
+            no_save_on_exit: no_save_on_exit.or(under.no_save_on_exit),
+            quiet: quiet.or(under.quiet),
+            no_cache: no_cache.or(under.no_cache),
+            low_bandwidth: low_bandwidth.or(under.low_bandwidth),
+            interval: interval.or(under.interval),
+            min_interval: min_interval.or(under.min_interval),
+            max_interval: max_interval.or(under.max_interval),
+            comfortable: comfortable.or(under.comfortable),
+            startup_timeout: startup_timeout.or(under.startup_timeout),
+            idle_minutes: idle_minutes.or(under.idle_minutes),
+            stale_minutes: stale_minutes.or(under.stale_minutes),
+            alert: alert.or(under.alert),
+            alert_interval: alert_interval.or(under.alert_interval),
+            alert_sound: alert_sound.or(under.alert_sound),
+            summary_dir: summary_dir.or(under.summary_dir),
+            debug_dump: debug_dump.or(under.debug_dump),
+            debug_dump_all: debug_dump_all.or(under.debug_dump_all),
+            sanity_limits: sanity_limits.or(under.sanity_limits),
+            snapshot_dir: snapshot_dir.or(under.snapshot_dir),
+            mirror: mirror.or(under.mirror),
+            allow_failover: allow_failover.or(under.allow_failover),
+            fastest_mirror: fastest_mirror.or(under.fastest_mirror),
+            seed: seed.or(under.seed),
+            user_agent: user_agent.or(under.user_agent),
+            timezone: timezone.or(under.timezone),
+            locale: locale.or(under.locale),
+            privacy: privacy.or(under.privacy),
+            query: query.or(under.query),
+            airline: airline.or(under.airline),
+            sort: sort.or(under.sort),
+        }
+    }
+}
+
+/// The base settings, with a profile's over them, and the ones given on
+/// the command line over both.
+pub fn merge(base: &Settings, profile: Option<&Settings>, given: Settings) -> Settings {
+    let configured = match profile {
+        Some(profile) => profile.clone().or(base.clone()),
+        None => base.clone(),
+    };
+    given.or(configured)
+}
+
+/// Check the settings that only make sense together, which can come from
+/// different places.
+fn check(settings: &Settings) -> Result<()> {
+    if settings.debug_dump_all == Some(true) && settings.debug_dump.is_none() {
+        bail!("debug-dump-all needs debug-dump");
+    }
+    if settings.allow_failover == Some(true) && settings.mirror.is_none() {
+        bail!("allow-failover needs mirror");
+    }
+    if settings.fastest_mirror == Some(true) && settings.mirror.is_some() {
+        bail!("fastest-mirror can't be used with mirror");
+    }
+    Ok(())
+}
+
+/// A setting given as text in the config file, parsed like its option.
+pub trait FromText: Sized {
+    fn from_text(text: &str) -> Result<Self>;
+}
+
+macro_rules! from_text {
+    ($($kind:ty => $parse:expr),* $(,)?) => {
+        $(
+            impl FromText for $kind {
+                fn from_text(text: &str) -> Result<Self> {
+                    $parse(text).map_err(Into::into)
+                }
+            }
+        )*
+    };
+}
+
+from_text! {
+    AirlineFilter => AirlineFilter::parse,
+    DefaultSort => DefaultSort::parse,
+    DisplayZone => DisplayZone::parse,
+    HeaderValue => api::parse_user_agent,
+    Limits => Limits::parse,
+    Locale => Locale::parse,
+    MirrorChoice => MirrorChoice::parse,
+    PollInterval => PollInterval::parse,
+    Privacy => Privacy::parse,
+    Query => Query::parse,
+}
+
+/// Text in the config file; options like interval take numbers too.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Text {
+    Text(String),
+    Number(i64),
+}
+
+impl Text {
+    fn parse<T: FromText, E: de::Error>(self) -> Result<T, E> {
+        let text = match self {
+            Text::Text(text) => text,
+            Text::Number(number) => number.to_string(),
+        };
+        T::from_text(&text).map_err(|e| E::custom(format!("{:#}", e)))
+    }
+}
+
+/// Deserialize a setting given as text.
+pub fn parsed<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromText,
+{
+    Text::deserialize(deserializer)?.parse().map(Some)
+}
+
+/// Deserialize the alerts: text like the option's, a list of kinds, or a
+/// table turning each on or off, like `{ favorites = true }`.
+pub fn alert_kinds<'de, D>(deserializer: D) -> Result<Option<HashSet<AlertKind>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Kinds {
+        Text(String),
+        List(Vec<String>),
+        Table(BTreeMap<String, bool>),
+    }
+    let kinds: Result<HashSet<AlertKind>> = match Kinds::deserialize(deserializer)? {
+        Kinds::Text(text) => alerts::parse_kinds(&text),
+        Kinds::List(names) => names.iter().map(|name| alerts::parse_kind(name)).collect(),
+        Kinds::Table(table) => table
+            .into_iter()
+            .filter_map(|(name, on)| match alerts::parse_kind(&name) {
+                Ok(kind) => on.then_some(Ok(kind)),
+                Err(e) => Some(Err(e)),
+            })
+            .collect(),
+    };
+    kinds
+        .map(Some)
+        .map_err(|e| D::Error::custom(format!("{:#}", e)))
+}
+
+/// Deserialize a repeatable setting, given as a list of text.
+pub fn parsed_list<'de, D, T>(deserializer: D) -> Result<Option<Vec<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromText,
+{
+    Vec::<Text>::deserialize(deserializer)?
+        .into_iter()
+        .map(Text::parse)
+        .collect::<Result<_, _>>()
+        .map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::Args;
+    use clap::Parser;
+
+    fn settings(toml: &str) -> Settings {
+        Config::parse(toml).unwrap().settings
+    }
+
+    fn given(args: &[&str]) -> Settings {
+        Args::try_parse_from(std::iter::once("vatsim_online").chain(args.iter().copied()))
+            .unwrap()
+            .given()
+    }
+
+    #[test]
+    fn parsing_checks_the_version_and_fields() {
+        let config = Config::parse("version = 1\ntimezone = \"utc\"").unwrap();
+        assert_eq!(config.settings.timezone, Some(DisplayZone::Utc));
+        assert!(config.profiles.is_empty());
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+        let error = Config::parse("version = 2").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The config file has version 2 (expected 1)"
+        );
+        let error = Config::parse("colour = \"red\"").unwrap_err();
+        assert!(error.to_string().contains("unknown field `colour`"));
+        // only the options that are settings
+        assert!(Config::parse("profile = \"events\"").is_err());
+        assert!(Config::parse("summary = true").is_err());
+        assert!(Config::parse("[profile.events]\ncolour = \"red\"").is_err());
+    }
+
+    #[test]
+    fn settings_of_each_kind() {
+        let settings = settings(
+            r#"
+            comfortable = true
+            restore = false
+            quiet = 2
+            interval = 30
+            min-interval = 10
+            privacy = "initials"
+            alert = "favorites,emergency"
+            sort = ["pilots:dep", "controllers:position"]
+            "#,
+        );
+        assert_eq!(settings.comfortable, Some(true));
+        assert_eq!(settings.restore, Some(false));
+        assert_eq!(settings.quiet, Some(2));
+        assert_eq!(settings.interval, Some(PollInterval::parse("30").unwrap()));
+        assert_eq!(settings.min_interval, Some(10));
+        assert_eq!(settings.privacy, Some(Privacy::Initials));
+        assert_eq!(settings.alert.map(|kinds| kinds.len()), Some(2));
+        assert_eq!(settings.sort.map(|sorts| sorts.len()), Some(2));
+        assert_eq!(settings.debug, None);
+    }
+
+    #[test]
+    fn bad_settings_are_errors() {
+        let error = |toml| Config::parse(toml).unwrap_err().to_string();
+        assert!(
+            error("debug = \"yes\"").contains("invalid type: string \"yes\", expected a boolean")
+        );
+        assert!(error("quiet = -1").contains("invalid value: integer `-1`"));
+        assert!(error("timezone = \"Mars/Olympus\"").contains("Unknown time zone"));
+        assert!(error("sort = [\"nowhere:dep\"]").contains("nowhere"));
+        assert!(error("sort = \"pilots:dep\"").contains("expected a sequence"));
+        assert!(error("profile = 1").contains("Invalid profile"));
+    }
+
+    #[test]
+    fn alerts_are_text_a_list_or_a_table() {
+        let both = HashSet::from([AlertKind::FavoriteOnline, AlertKind::Emergency]);
+        for toml in [
+            "alert = \"favorites,emergency\"",
+            "alert = [\"favorites\", \"emergency\"]",
+            "alert = { favorites = true, emergency = true }",
+        ] {
+            assert_eq!(settings(toml).alert.as_ref(), Some(&both));
+        }
+        let toml =
+            "alert-sound = \"paplay bell.oga\"\n[alert]\nfavorites = true\nemergency = false";
+        let settings = settings(toml);
+        assert_eq!(
+            settings.alert,
+            Some(HashSet::from([AlertKind::FavoriteOnline]))
+        );
+        assert_eq!(settings.alert_sound.as_deref(), Some("paplay bell.oga"));
+        let error = Config::parse("alert = { inbound = false }").unwrap_err();
+        assert!(error.to_string().contains("Unknown alert 'inbound'"));
+    }
+
+    #[test]
+    fn unknown_profiles_list_the_known_ones() {
+        let config =
+            Config::parse("[profile.events]\n[profile.casual]\ncomfortable = true").unwrap();
+        assert_eq!(config.profile("casual").unwrap().comfortable, Some(true));
+        assert_eq!(
+            config.profile("work").unwrap_err().to_string(),
+            "Unknown profile 'work', expected one of: casual, events"
+        );
+        assert_eq!(
+            Config::default().profile("work").unwrap_err().to_string(),
+            "Unknown profile 'work': the config file has none"
+        );
+    }
+
+    #[test]
+    fn loading_a_missing_or_invalid_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE_NAME);
+        assert_eq!(load_from(&path).unwrap(), Config::default());
+
+        fs::write(&path, "debug = true").unwrap();
+        assert_eq!(load_from(&path).unwrap().settings.debug, Some(true));
+
+        fs::write(&path, "debug = ").unwrap();
+        let error = load_from(&path).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!("Invalid config in {}", path.display())
+        );
+    }
+
+    #[test]
+    fn profiles_replace_base_settings_and_the_command_line_both() {
+        let base = settings("timezone = \"utc\"\ncomfortable = true\ninterval = 30\ndebug = true");
+        let profile = settings("timezone = \"+05:30\"\ninterval = \"auto\"");
+        assert_eq!(merge(&base, None, Settings::default()), base);
+        let merged = merge(&base, Some(&profile), Settings::default());
+        assert_eq!(merged.timezone, DisplayZone::parse("+05:30").ok());
+        assert_eq!(merged.interval, Some(PollInterval::Auto));
+        assert_eq!((merged.comfortable, merged.debug), (Some(true), Some(true)));
+
+        let merged = merge(
+            &base,
+            Some(&profile),
+            given(&["--timezone", "+01:00", "--comfortable=false", "-q"]),
+        );
+        assert_eq!(merged.timezone, DisplayZone::parse("+01:00").ok());
+        assert_eq!(merged.comfortable, Some(false));
+        assert_eq!(merged.interval, Some(PollInterval::Auto));
+        assert_eq!(merged.quiet, Some(1));
+    }
+
+    #[test]
+    fn switches_on_the_command_line() {
+        let switches = given(&["-dr", "--privacy", "--no-cache=false"]);
+        assert_eq!(switches.debug, Some(true));
+        assert_eq!(switches.restore, Some(true));
+        assert_eq!(switches.no_cache, Some(false));
+        assert_eq!(switches.privacy, Some(Privacy::Cid));
+        assert_eq!(switches.comfortable, None);
+        assert_eq!(given(&[]), Settings::default());
+        // the subcommand's own options aren't settings, but global ones are
+        let subcommand = given(&["airport", "KJFK", "--interval", "5", "-qq"]);
+        assert_eq!(subcommand.interval, None);
+        assert_eq!(subcommand.quiet, Some(2));
+    }
+
+    #[test]
+    fn settings_needing_others_can_come_from_anywhere() {
+        let config = Config::parse("mirror = 1\n[profile.dump]\ndebug-dump = \"dumps\"").unwrap();
+        assert!(config.resolve(None, given(&["--allow-failover"])).is_ok());
+        assert!(config
+            .resolve(Some("dump"), given(&["--debug-dump-all"]))
+            .is_ok());
+        assert_eq!(
+            config
+                .resolve(None, given(&["--debug-dump-all"]))
+                .unwrap_err()
+                .to_string(),
+            "debug-dump-all needs debug-dump"
+        );
+        assert_eq!(
+            config
+                .resolve(None, given(&["--fastest-mirror"]))
+                .unwrap_err()
+                .to_string(),
+            "fastest-mirror can't be used with mirror"
+        );
+        assert_eq!(
+            Config::default()
+                .resolve(None, given(&["--allow-failover"]))
+                .unwrap_err()
+                .to_string(),
+            "allow-failover needs mirror"
+        );
+    }
+}
@@ -0,0 +1,160 @@
+//! Counting clients, for the `--count` one-shot mode.
+
+use crate::{
+    mirrors,
+    models::V3ResponseData,
+    query::{ParseError, Query},
+    report::{fetch_data, DataSource, Reporter},
+};
+use anyhow::Result;
+use std::{fs, path::Path};
+
+/// What to count.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CountExpr {
+    Pilots,
+    Controllers,
+    /// Pilots and controllers matching a query.
+    Query(Query),
+}
+
+impl CountExpr {
+    /// Parse "pilots", "controllers", or a query like "arrival=KJFK".
+    pub fn parse(value: &str) -> Result<Self, ParseError> {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("pilots") {
+            Ok(CountExpr::Pilots)
+        } else if value.eq_ignore_ascii_case("controllers") {
+            Ok(CountExpr::Controllers)
+        } else {
+            Query::parse(value).map(CountExpr::Query)
+        }
+    }
+
+    /// How many clients in the data are counted.
+    ///
+    /// Fields only pilots have never match controllers, and the other way
+    /// around, so "arrival=KJFK" only counts pilots.
+    pub fn count(&self, data: &V3ResponseData) -> usize {
+        match self {
+            CountExpr::Pilots => data.pilots.len(),
+            CountExpr::Controllers => data.controllers.len(),
+            CountExpr::Query(query) => {
+                data.pilots
+                    .iter()
+                    .filter(|p| query.matches_pilot(p))
+                    .count()
+                    + data
+                        .controllers
+                        .iter()
+                        .filter(|c| query.matches_controller(c))
+                        .count()
+            }
+        }
+    }
+}
+
+/// Count clients in a V3 data file, or in the current data if there's no file.
+pub fn run(
+    expr: &CountExpr,
+    input: Option<&Path>,
+    source: &DataSource,
+    reporter: &mut Reporter,
+) -> Result<usize> {
+    if let Some(path) = input {
+        return Ok(expr.count(&mirrors::parse_data(&fs::read(path)?)?));
+    }
+    let data = fetch_data(source, reporter)?;
+    Ok(expr.count(&data))
+}
+
+/// Print the count alone on a line, so `$(...)` captures just the number.
+pub fn print(
+    expr: &CountExpr,
+    input: Option<&Path>,
+    source: &DataSource,
+    reporter: &mut Reporter,
+) -> Result<()> {
+    let count = run(expr, input, source, reporter)?;
+    reporter.data(count)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{mirrors::MirrorSelection, privacy::Privacy, report::Verbosity, testing};
+    use reqwest::header::HeaderValue;
+
+    fn data() -> V3ResponseData {
+        V3ResponseData {
+            pilots: vec![
+                testing::flying("BAW1", "EGLL", "KJFK"),
+                testing::flying("DLH1", "EDDF", "KJFK"),
+                testing::pilot("N123AB"),
+            ],
+            controllers: vec![
+                testing::controller("KJFK_TWR", 4),
+                testing::controller("EGLL_TWR", 4),
+            ],
+            ..V3ResponseData::default()
+        }
+    }
+
+    fn count(expr: &str) -> usize {
+        CountExpr::parse(expr).unwrap().count(&data())
+    }
+
+    #[test]
+    fn counts_each_form() {
+        assert_eq!(count("pilots"), 3);
+        assert_eq!(count(" Controllers "), 2);
+        assert_eq!(count("arrival=KJFK"), 2);
+        assert_eq!(count("callsign=KJFK_*"), 1);
+        assert_eq!(count("callsign=*1"), 2);
+        assert_eq!(count(""), 5);
+        assert!(CountExpr::parse("arrival>KJFK").is_err());
+    }
+
+    #[test]
+    fn prints_only_the_number_from_a_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("v3.json");
+        fs::write(&path, serde_json::to_vec(&data()).unwrap()).unwrap();
+        let expr = CountExpr::parse("arrival=KJFK").unwrap();
+        let mirror = MirrorSelection::default();
+        let user_agent = HeaderValue::from_static("vatsim_online tests");
+        let source = DataSource {
+            use_cache: false,
+            mirror: &mirror,
+            user_agent: &user_agent,
+            privacy: Privacy::Full,
+        };
+        for verbosity in [Verbosity::Normal, Verbosity::Quiet, Verbosity::Silent] {
+            let (mut reporter, out, err) = Reporter::captured(verbosity);
+            print(&expr, Some(&path), &source, &mut reporter).unwrap();
+            assert_eq!((out.text().as_str(), err.text().as_str()), ("2\n", ""));
+        }
+    }
+
+    #[test]
+    fn unreadable_files_fail() {
+        let dir = tempfile::tempdir().unwrap();
+        let mirror = MirrorSelection::default();
+        let user_agent = HeaderValue::from_static("vatsim_online tests");
+        let source = DataSource {
+            use_cache: false,
+            mirror: &mirror,
+            user_agent: &user_agent,
+            privacy: Privacy::Full,
+        };
+        let missing = dir.path().join("missing.json");
+        let (mut reporter, out, err) = Reporter::captured(Verbosity::Normal);
+        assert!(run(&CountExpr::Pilots, Some(&missing), &source, &mut reporter).is_err());
+        let truncated = dir.path().join("truncated.json");
+        fs::write(&truncated, r#"{"pilots": ["#).unwrap();
+        assert!(run(&CountExpr::Pilots, Some(&truncated), &source, &mut reporter).is_err());
+        // main reports the error, so nothing is printed here
+        assert_eq!((out.text().as_str(), err.text().as_str()), ("", ""));
+    }
+}
@@ -0,0 +1,424 @@
+//! Extra table columns defined in a config file, showing any field of a
+//! record by its path in the V3 JSON.
+//!
+//! The file is `columns.json` in the config directory, like:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "pilots": [{ "header": "Remarks", "path": "flight_plan.remarks" }],
+//!   "controllers": [{ "header": "Range", "path": "visual_range" }]
+//! }
+//! ```
+
+use crate::columns::{self, Column};
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+/// Version of the columns file format.
+const COLUMNS_VERSION: u32 = 1;
+/// Name of the columns file in the config directory.
+const COLUMNS_FILE_NAME: &str = "columns.json";
+/// Width a custom column starts at.
+const CUSTOM_WIDTH: u16 = 12;
+
+/// One column as written in the file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ColumnEntry {
+    header: String,
+    path: String,
+}
+
+/// The columns file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ColumnsFile {
+    version: u32,
+    #[serde(default)]
+    pilots: Vec<ColumnEntry>,
+    #[serde(default)]
+    controllers: Vec<ColumnEntry>,
+}
+
+/// A dotted path into a record, like "flight_plan.remarks". Numbers
+/// index into lists, like "text_atis.0".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldPath(Vec<String>);
+
+impl FieldPath {
+    /// Parse a path, checking each part is a field name or an index.
+    pub fn parse(value: &str) -> Result<Self> {
+        let value = value.trim();
+        if value.is_empty() {
+            bail!("The path is empty");
+        }
+        let parts: Vec<String> = value.split('.').map(str::to_owned).collect();
+        for part in &parts {
+            if part.is_empty() {
+                bail!(
+                    "\"{}\" has an empty part; parts are separated by one dot",
+                    value
+                );
+            }
+            if !part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                bail!(
+                    "\"{}\" in \"{}\" isn't a field name; use letters, digits, and underscores, like \"flight_plan.remarks\"",
+                    part,
+                    value
+                );
+            }
+        }
+        Ok(Self(parts))
+    }
+
+    /// The value at this path in a record, if there is one.
+    pub fn resolve<'a>(&self, record: &'a Value) -> Option<&'a Value> {
+        self.0.iter().try_fold(record, |value, part| match value {
+            Value::Object(fields) => fields.get(part),
+            Value::Array(items) => items.get(part.parse::<usize>().ok()?),
+            _ => None,
+        })
+    }
+}
+
+/// Text for a value in a cell: strings as they are, lists of values
+/// joined with spaces, and anything else as JSON. Nulls are missing.
+fn cell_text(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::String(text) => Some(text.clone()),
+        Value::Array(items) => Some(
+            items
+                .iter()
+                .filter_map(cell_text)
+                .collect::<Vec<_>>()
+                .join(" "),
+        ),
+        other => Some(other.to_string()),
+    }
+}
+
+/// A column from the file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CustomColumn {
+    pub column: Column,
+    pub path: FieldPath,
+}
+
+/// Custom columns for the pilots and controllers tables.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CustomColumns {
+    pub pilots: Vec<CustomColumn>,
+    pub controllers: Vec<CustomColumn>,
+}
+
+/// Check a table's entries, making columns from them.
+///
+/// Headers have to be unique in the table, including the built-in ones in
+/// any of its layouts.
+fn table_columns(
+    table: &str,
+    entries: Vec<ColumnEntry>,
+    built_in: &[&[Column]],
+) -> Result<Vec<CustomColumn>> {
+    let mut headers: Vec<String> = built_in
+        .iter()
+        .flat_map(|layout| layout.iter())
+        .map(|c| c.header.to_lowercase())
+        .collect();
+    entries
+        .into_iter()
+        .enumerate()
+        .map(|(index, entry)| {
+            let context = || format!("Custom {} column {}", table, index + 1);
+            let header = entry.header.trim();
+            if header.is_empty() {
+                bail!("{} has no header", context());
+            }
+            if headers.contains(&header.to_lowercase()) {
+                bail!(
+                    "{} has the header \"{}\", which is already used",
+                    context(),
+                    header
+                );
+            }
+            headers.push(header.to_lowercase());
+            let path = FieldPath::parse(&entry.path).with_context(context)?;
+            let width = u16::try_from(header.chars().count())
+                .unwrap_or(u16::MAX)
+                .max(CUSTOM_WIDTH);
+            // loaded once at startup, so the header lives for the whole run
+            let header: &'static str = Box::leak(header.to_owned().into_boxed_str());
+            Ok(CustomColumn {
+                column: Column { header, width },
+                path,
+            })
+        })
+        .collect()
+}
+
+/// Parse and check the contents of a columns file.
+pub fn parse(content: &str) -> Result<CustomColumns> {
+    let file: ColumnsFile = serde_json::from_str(content)?;
+    if file.version != COLUMNS_VERSION {
+        bail!(
+            "The columns file has version {} (expected {})",
+            file.version,
+            COLUMNS_VERSION
+        );
+    }
+    Ok(CustomColumns {
+        pilots: table_columns(
+            "pilots",
+            file.pilots,
+            &[columns::PILOT_RANGE_COLUMNS, columns::PILOT_SQUAWK_COLUMNS],
+        )?,
+        controllers: table_columns(
+            "controllers",
+            file.controllers,
+            &[columns::CONTROLLER_COLUMNS],
+        )?,
+    })
+}
+
+/// Path to the columns file, if the platform has a config directory.
+pub fn columns_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("vatsim_online").join(COLUMNS_FILE_NAME))
+}
+
+/// Load custom columns from a file. A missing file has none.
+pub fn load_from(path: &Path) -> Result<CustomColumns> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(CustomColumns::default()),
+        Err(e) => return Err(e).with_context(|| format!("Could not read {}", path.display())),
+    };
+    parse(&content).with_context(|| format!("Invalid custom columns in {}", path.display()))
+}
+
+/// Load the custom columns, if the platform has a config directory.
+pub fn load() -> Result<CustomColumns> {
+    columns_path().map_or_else(|| Ok(CustomColumns::default()), |path| load_from(&path))
+}
+
+/// Custom column cells for each record in a table, by callsign.
+///
+/// Records are only converted to JSON when new data arrives, not every
+/// time the table is drawn.
+pub fn cells<T: Serialize>(
+    columns: &[CustomColumn],
+    records: &[T],
+    callsign: impl Fn(&T) -> &str,
+) -> HashMap<String, Vec<Option<String>>> {
+    if columns.is_empty() {
+        return HashMap::new();
+    }
+    records
+        .iter()
+        .map(|record| {
+            let value = serde_json::to_value(record).unwrap_or(Value::Null);
+            let cells = columns
+                .iter()
+                .map(|column| column.path.resolve(&value).and_then(cell_text))
+                .collect();
+            (callsign(record).to_owned(), cells)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use serde_json::json;
+
+    fn path(value: &str) -> FieldPath {
+        FieldPath::parse(value).unwrap()
+    }
+
+    fn error(content: &str) -> String {
+        format!("{:#}", parse(content).unwrap_err())
+    }
+
+    #[test]
+    fn paths_resolve_fields_and_indexes() {
+        let record = json!({
+            "callsign": "BAW1",
+            "visual_range": 50,
+            "flight_plan": { "remarks": "/V/", "altitude": "35000" },
+            "text_atis": ["Heathrow information A", "Runway 27R"],
+            "server": null,
+        });
+        let text = |value: &str| path(value).resolve(&record).and_then(cell_text);
+        assert_eq!(text("callsign").as_deref(), Some("BAW1"));
+        assert_eq!(text(" flight_plan.remarks ").as_deref(), Some("/V/"));
+        assert_eq!(text("visual_range").as_deref(), Some("50"));
+        assert_eq!(text("text_atis.1").as_deref(), Some("Runway 27R"));
+        assert_eq!(
+            text("text_atis").as_deref(),
+            Some("Heathrow information A Runway 27R")
+        );
+        assert_eq!(
+            text("flight_plan").as_deref(),
+            Some(r#"{"altitude":"35000","remarks":"/V/"}"#)
+        );
+        for missing in [
+            "server",
+            "missing",
+            "flight_plan.route",
+            "text_atis.5",
+            "text_atis.x",
+            "callsign.0",
+        ] {
+            assert_eq!(text(missing), None, "{}", missing);
+        }
+    }
+
+    #[test]
+    fn malformed_paths() {
+        assert_eq!(
+            FieldPath::parse(" ").unwrap_err().to_string(),
+            "The path is empty"
+        );
+        assert_eq!(
+            FieldPath::parse("flight_plan..remarks")
+                .unwrap_err()
+                .to_string(),
+            "\"flight_plan..remarks\" has an empty part; parts are separated by one dot"
+        );
+        assert!(FieldPath::parse(".remarks").is_err());
+        let message = FieldPath::parse("flight_plan/remarks")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            message.starts_with(
+                "\"flight_plan/remarks\" in \"flight_plan/remarks\" isn't a field name"
+            ),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn a_columns_file() {
+        let columns = parse(
+            r#"{
+                "version": 1,
+                "pilots": [
+                    { "header": " Remarks ", "path": "flight_plan.remarks" },
+                    { "header": "A very long header", "path": "server" }
+                ],
+                "controllers": [{ "header": "Range", "path": "visual_range" }]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            columns.pilots[0],
+            CustomColumn {
+                column: Column {
+                    header: "Remarks",
+                    width: CUSTOM_WIDTH
+                },
+                path: path("flight_plan.remarks"),
+            }
+        );
+        assert_eq!(columns.pilots[1].column.width, 18);
+        assert_eq!(columns.controllers[0].column.header, "Range");
+        assert_eq!(
+            parse(r#"{"version": 1}"#).unwrap(),
+            CustomColumns::default()
+        );
+    }
+
+    #[test]
+    fn errors_name_the_entry_at_fault() {
+        assert_eq!(
+            error(r#"{"version": 2}"#),
+            "The columns file has version 2 (expected 1)"
+        );
+        assert!(error(r#"{"version": 1, "pilot": []}"#).contains("unknown field `pilot`"));
+        assert!(
+            error(r#"{"version": 1, "pilots": [{"header": "X", "path": "a", "width": 3}]}"#)
+                .contains("unknown field `width`")
+        );
+        assert_eq!(
+            error(
+                r#"{"version": 1, "pilots": [{"header": "X", "path": "a"}, {"header": " ", "path": "b"}]}"#
+            ),
+            "Custom pilots column 2 has no header"
+        );
+        assert_eq!(
+            error(r#"{"version": 1, "controllers": [{"header": "frequency", "path": "a"}]}"#),
+            "Custom controllers column 1 has the header \"frequency\", which is already used"
+        );
+        // the columns added while filtered count too
+        assert_eq!(
+            error(r#"{"version": 1, "pilots": [{"header": "Squawk", "path": "transponder"}]}"#),
+            "Custom pilots column 1 has the header \"Squawk\", which is already used"
+        );
+        assert_eq!(
+            error(
+                r#"{"version": 1, "pilots": [{"header": "X", "path": "a"}, {"header": "x", "path": "b"}]}"#
+            ),
+            "Custom pilots column 2 has the header \"x\", which is already used"
+        );
+        assert_eq!(
+            error(r#"{"version": 1, "pilots": [{"header": "X", "path": "a..b"}]}"#),
+            "Custom pilots column 1: \"a..b\" has an empty part; parts are separated by one dot"
+        );
+        // a header can be reused in the other table
+        assert!(
+            parse(r#"{"version": 1, "pilots": [{"header": "Frequency", "path": "a"}]}"#).is_ok()
+        );
+    }
+
+    #[test]
+    fn loading_from_a_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(COLUMNS_FILE_NAME);
+        assert_eq!(load_from(&path).unwrap(), CustomColumns::default());
+        fs::write(
+            &path,
+            r#"{"version": 1, "pilots": [{"header": "Server", "path": "serv er"}]}"#,
+        )
+        .unwrap();
+        let message = format!("{:#}", load_from(&path).unwrap_err());
+        assert!(
+            message.starts_with("Invalid custom columns in"),
+            "{}",
+            message
+        );
+        assert!(message.contains("Custom pilots column 1"), "{}", message);
+    }
+
+    #[test]
+    fn cells_by_callsign() {
+        let columns = parse(
+            r#"{"version": 1, "pilots": [
+                {"header": "Server", "path": "server"},
+                {"header": "Remarks", "path": "flight_plan.remarks"}
+            ]}"#,
+        )
+        .unwrap()
+        .pilots;
+        let pilots = [
+            testing::pilot("N123"),
+            testing::flying("BAW1", "EGLL", "KJFK"),
+        ];
+        let cells = cells(&columns, &pilots, |p| &p.callsign);
+        assert_eq!(cells["N123"], [Some("USA-EAST".to_owned()), None]);
+        assert_eq!(
+            cells["BAW1"][1],
+            Some(pilots[1].flight_plan.as_ref().unwrap().remarks.clone())
+        );
+        assert!(super::cells(&[], &pilots, |p| &p.callsign).is_empty());
+    }
+}
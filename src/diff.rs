@@ -0,0 +1,680 @@
+//! What changed between two snapshots of the network, as events, so
+//! everything that reacts to a refresh sees the same changes.
+
+use crate::models::{Controller, Pilot, V3ResponseData};
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+/// Height of the altitude bands pilots are tracked through, in feet.
+pub const ALTITUDE_BAND_FT: i64 = 10_000;
+
+/// Something that changed between two snapshots.
+///
+/// Serialized as an object whose `event` field names the variant, like
+/// `{"event": "pilot_connected", "cid": 1234567, "callsign": "BAW1"}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ChangeEvent {
+    PilotConnected {
+        cid: i64,
+        callsign: String,
+    },
+    PilotDisconnected {
+        cid: i64,
+        callsign: String,
+    },
+    /// A pilot filed a flight plan, or a new revision of one.
+    FlightPlanFiled {
+        cid: i64,
+        callsign: String,
+        departure: String,
+        arrival: String,
+    },
+    /// A pilot climbed or descended into another band; the bands are
+    /// given by their floors, in feet.
+    AltitudeBandChanged {
+        cid: i64,
+        callsign: String,
+        from: i64,
+        to: i64,
+    },
+    ControllerOnline {
+        cid: i64,
+        callsign: String,
+    },
+    ControllerOffline {
+        cid: i64,
+        callsign: String,
+    },
+    /// A controller's or ATIS station's info lines changed.
+    AtisUpdated {
+        cid: i64,
+        callsign: String,
+    },
+    /// A client reconnected under another callsign, like a pilot correcting
+    /// a typo or a controller moving from ground to tower.
+    CallsignChanged {
+        cid: i64,
+        from: String,
+        to: String,
+    },
+}
+
+impl ChangeEvent {
+    /// CID of the client the event is about.
+    pub fn cid(&self) -> i64 {
+        match self {
+            ChangeEvent::PilotConnected { cid, .. }
+            | ChangeEvent::PilotDisconnected { cid, .. }
+            | ChangeEvent::FlightPlanFiled { cid, .. }
+            | ChangeEvent::AltitudeBandChanged { cid, .. }
+            | ChangeEvent::ControllerOnline { cid, .. }
+            | ChangeEvent::ControllerOffline { cid, .. }
+            | ChangeEvent::AtisUpdated { cid, .. }
+            | ChangeEvent::CallsignChanged { cid, .. } => *cid,
+        }
+    }
+}
+
+impl fmt::Display for ChangeEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChangeEvent::PilotConnected { cid, callsign } => {
+                write!(f, "{} ({}) connected", callsign, cid)
+            }
+            ChangeEvent::PilotDisconnected { cid, callsign } => {
+                write!(f, "{} ({}) disconnected", callsign, cid)
+            }
+            ChangeEvent::FlightPlanFiled {
+                cid,
+                callsign,
+                departure,
+                arrival,
+            } => write!(
+                f,
+                "{} ({}) filed {} to {}",
+                callsign, cid, departure, arrival
+            ),
+            ChangeEvent::AltitudeBandChanged {
+                cid,
+                callsign,
+                from,
+                to,
+            } => write!(
+                f,
+                "{} ({}) {} from {} ft to {} ft",
+                callsign,
+                cid,
+                if to > from { "climbed" } else { "descended" },
+                from,
+                to
+            ),
+            ChangeEvent::ControllerOnline { cid, callsign } => {
+                write!(f, "{} ({}) online", callsign, cid)
+            }
+            ChangeEvent::ControllerOffline { cid, callsign } => {
+                write!(f, "{} ({}) offline", callsign, cid)
+            }
+            ChangeEvent::AtisUpdated { cid, callsign } => {
+                write!(f, "{} ({}) updated its info", callsign, cid)
+            }
+            ChangeEvent::CallsignChanged { cid, from, to } => {
+                write!(f, "{} ({}) reconnected as {}", from, cid, to)
+            }
+        }
+    }
+}
+
+/// Each CID's callsign, for CIDs connected exactly once.
+///
+/// A CID connected more than once, like an observer with a second
+/// connection, can't be matched up between snapshots, so it's left out.
+fn single_callsigns<'a>(clients: impl Iterator<Item = (i64, &'a str)>) -> HashMap<i64, &'a str> {
+    let mut callsigns: HashMap<i64, Option<&str>> = HashMap::new();
+    for (cid, callsign) in clients {
+        let _ = callsigns
+            .entry(cid)
+            .and_modify(|existing| *existing = None)
+            .or_insert(Some(callsign));
+    }
+    callsigns
+        .into_iter()
+        .filter_map(|(cid, callsign)| Some((cid, callsign?)))
+        .collect()
+}
+
+/// A connection's CID and callsign.
+type Id<'a> = (i64, &'a str);
+
+/// Connections of one kind of client, by CID and callsign.
+fn connections<'a, T>(
+    clients: &'a [T],
+    key: impl Fn(&T) -> (i64, &str),
+) -> HashMap<(i64, &'a str), &'a T> {
+    clients.iter().map(|client| (key(client), client)).collect()
+}
+
+/// How one kind of client changed: callsign changes, then the
+/// connections that went and came, then those in both snapshots.
+struct Compared<'a, T> {
+    renamed: Vec<ChangeEvent>,
+    gone: Vec<(i64, &'a str)>,
+    came: Vec<(i64, &'a str)>,
+    kept: Vec<(&'a T, &'a T)>,
+}
+
+/// Match up the connections of one kind of client in two snapshots.
+///
+/// A CID connected once in both under different callsigns is a callsign
+/// change rather than one client leaving and another joining.
+fn compare<'a, T>(old: &'a [T], new: &'a [T], key: impl Fn(&T) -> (i64, &str)) -> Compared<'a, T> {
+    let old_single = single_callsigns(old.iter().map(&key));
+    let new_single = single_callsigns(new.iter().map(&key));
+    let mut renamed: Vec<ChangeEvent> = new_single
+        .iter()
+        .filter_map(|(&cid, &to)| {
+            let from = *old_single.get(&cid)?;
+            (from != to).then(|| ChangeEvent::CallsignChanged {
+                cid,
+                from: from.to_owned(),
+                to: to.to_owned(),
+            })
+        })
+        .collect();
+    renamed.sort_by_key(ChangeEvent::cid);
+    let renamed_cids: HashSet<i64> = renamed.iter().map(ChangeEvent::cid).collect();
+
+    let old = connections(old, &key);
+    let new = connections(new, &key);
+    let mut gone: Vec<(i64, &str)> = old
+        .keys()
+        .filter(|id| !new.contains_key(*id) && !renamed_cids.contains(&id.0))
+        .copied()
+        .collect();
+    gone.sort_unstable();
+    let mut came: Vec<(i64, &str)> = new
+        .keys()
+        .filter(|id| !old.contains_key(*id) && !renamed_cids.contains(&id.0))
+        .copied()
+        .collect();
+    came.sort_unstable();
+    // a renamed client is still the same client, so its other changes count
+    let mut kept: Vec<(Id, Id)> = new
+        .keys()
+        .filter(|id| old.contains_key(*id))
+        .map(|&id| (id, id))
+        .chain(
+            renamed_cids
+                .iter()
+                .map(|cid| ((*cid, old_single[cid]), (*cid, new_single[cid]))),
+        )
+        .collect();
+    kept.sort_unstable_by_key(|&(_, id)| id);
+    Compared {
+        renamed,
+        gone,
+        came,
+        kept: kept
+            .iter()
+            .map(|(old_id, new_id)| (old[old_id], new[new_id]))
+            .collect(),
+    }
+}
+
+/// A pilot's CID and callsign.
+fn pilot_key(pilot: &Pilot) -> (i64, &str) {
+    (pilot.cid, &pilot.callsign)
+}
+
+/// A controller's CID and callsign.
+fn controller_key(controller: &Controller) -> (i64, &str) {
+    (controller.cid, &controller.callsign)
+}
+
+/// Floor of the altitude band an altitude is in, in feet.
+///
+/// Altitudes below sea level, like on the ground at Schiphol, are in the
+/// lowest band, so taxiing there doesn't flap between bands.
+fn altitude_band(altitude: i64) -> i64 {
+    altitude.max(0) / ALTITUDE_BAND_FT * ALTITUDE_BAND_FT
+}
+
+/// Events for a pilot connected in both snapshots.
+fn pilot_changes(old: &Pilot, new: &Pilot, events: &mut Vec<ChangeEvent>) {
+    if let Some(plan) = &new.flight_plan {
+        let refiled = old
+            .flight_plan
+            .as_ref()
+            .is_none_or(|old_plan| old_plan.revision_id != plan.revision_id);
+        if refiled {
+            events.push(ChangeEvent::FlightPlanFiled {
+                cid: new.cid,
+                callsign: new.callsign.clone(),
+                departure: plan.departure.clone(),
+                arrival: plan.arrival.clone(),
+            });
+        }
+    }
+    let (from, to) = (altitude_band(old.altitude), altitude_band(new.altitude));
+    if from != to {
+        events.push(ChangeEvent::AltitudeBandChanged {
+            cid: new.cid,
+            callsign: new.callsign.clone(),
+            from,
+            to,
+        });
+    }
+}
+
+/// Events for a controller or ATIS station connected in both snapshots.
+fn controller_changes(old: &Controller, new: &Controller, events: &mut Vec<ChangeEvent>) {
+    if old.info_lines() != new.info_lines() {
+        events.push(ChangeEvent::AtisUpdated {
+            cid: new.cid,
+            callsign: new.callsign.clone(),
+        });
+    }
+}
+
+/// Everything that changed from one snapshot to the next.
+///
+/// Pilots are compared with pilots and controllers with controllers, so
+/// someone flying and controlling at once isn't taken for a change. ATIS
+/// stations only give [`ChangeEvent::AtisUpdated`], as they come and go
+/// with their controller. The events come in a fixed order:
+/// callsign changes, disconnections, connections, then changes to clients
+/// in both, each by CID.
+pub fn diff_snapshots(prev: &V3ResponseData, next: &V3ResponseData) -> Vec<ChangeEvent> {
+    let pilots = compare(&prev.pilots, &next.pilots, pilot_key);
+    let controllers = compare(&prev.controllers, &next.controllers, controller_key);
+    let atis = compare(&prev.atis, &next.atis, controller_key);
+
+    let mut events = Vec::new();
+    let mut renamed: Vec<ChangeEvent> = pilots
+        .renamed
+        .into_iter()
+        .chain(controllers.renamed)
+        .collect();
+    // stable, so a CID's pilot change stays ahead of its controller one
+    renamed.sort_by_key(ChangeEvent::cid);
+    events.extend(renamed);
+
+    events.extend(
+        pilots
+            .gone
+            .iter()
+            .map(|(cid, callsign)| ChangeEvent::PilotDisconnected {
+                cid: *cid,
+                callsign: (*callsign).to_owned(),
+            }),
+    );
+    events.extend(
+        controllers
+            .gone
+            .iter()
+            .map(|(cid, callsign)| ChangeEvent::ControllerOffline {
+                cid: *cid,
+                callsign: (*callsign).to_owned(),
+            }),
+    );
+    events.extend(
+        pilots
+            .came
+            .iter()
+            .map(|(cid, callsign)| ChangeEvent::PilotConnected {
+                cid: *cid,
+                callsign: (*callsign).to_owned(),
+            }),
+    );
+    events.extend(
+        controllers
+            .came
+            .iter()
+            .map(|(cid, callsign)| ChangeEvent::ControllerOnline {
+                cid: *cid,
+                callsign: (*callsign).to_owned(),
+            }),
+    );
+
+    for (old, new) in &pilots.kept {
+        pilot_changes(old, new, &mut events);
+    }
+    for (old, new) in controllers.kept.iter().chain(&atis.kept) {
+        controller_changes(old, new, &mut events);
+    }
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{models::FlightPlan, testing};
+
+    fn pilot(cid: i64, callsign: &str) -> Pilot {
+        Pilot {
+            cid,
+            ..testing::flying(callsign, "EGLL", "KJFK")
+        }
+    }
+
+    fn controller(cid: i64, callsign: &str) -> Controller {
+        Controller {
+            cid,
+            ..testing::controller(callsign, 4)
+        }
+    }
+
+    fn with_lines(controller: Controller, lines: &[&str]) -> Controller {
+        Controller {
+            text_atis: Some(lines.iter().map(|&line| line.to_owned()).collect()),
+            ..controller
+        }
+    }
+
+    /// A busy snapshot: pilots with and without plans, a controller, an
+    /// ATIS station, and an observer connected twice.
+    fn snapshot() -> V3ResponseData {
+        V3ResponseData {
+            pilots: vec![
+                pilot(1, "BAW1"),
+                Pilot {
+                    flight_plan: None,
+                    ..pilot(2, "N123AB")
+                },
+                Pilot {
+                    altitude: 35_000,
+                    ..pilot(3, "DLH4")
+                },
+            ],
+            controllers: vec![
+                with_lines(controller(10, "EGLL_TWR"), &["Tower"]),
+                controller(11, "EGLL_OBS"),
+                controller(11, "EGKK_OBS"),
+            ],
+            atis: vec![with_lines(controller(12, "EGLL_ATIS"), &["Info A"])],
+            ..V3ResponseData::default()
+        }
+    }
+
+    fn changed(edit: impl FnOnce(&mut V3ResponseData)) -> Vec<ChangeEvent> {
+        let old = snapshot();
+        let mut new = snapshot();
+        edit(&mut new);
+        diff_snapshots(&old, &new)
+    }
+
+    #[test]
+    fn nothing_changed() {
+        assert_eq!(diff_snapshots(&snapshot(), &snapshot()), []);
+        let empty = V3ResponseData::default();
+        assert_eq!(diff_snapshots(&empty, &empty), []);
+        // moving along within a band, and the order of the lists
+        assert_eq!(
+            changed(|data| {
+                data.pilots[2].altitude = 39_000;
+                data.pilots[0].latitude = 51.0;
+                data.pilots.reverse();
+                data.controllers.reverse();
+            }),
+            []
+        );
+    }
+
+    #[test]
+    fn connections_and_disconnections() {
+        let events = changed(|data| {
+            let _ = data.pilots.remove(0);
+            data.pilots.push(pilot(4, "AFR5"));
+            let _ = data.controllers.remove(0);
+            data.controllers.push(controller(13, "LON_CTR"));
+        });
+        assert_eq!(
+            events,
+            [
+                ChangeEvent::PilotDisconnected {
+                    cid: 1,
+                    callsign: "BAW1".to_owned()
+                },
+                ChangeEvent::ControllerOffline {
+                    cid: 10,
+                    callsign: "EGLL_TWR".to_owned()
+                },
+                ChangeEvent::PilotConnected {
+                    cid: 4,
+                    callsign: "AFR5".to_owned()
+                },
+                // a new connection's plan isn't a filing
+                ChangeEvent::ControllerOnline {
+                    cid: 13,
+                    callsign: "LON_CTR".to_owned()
+                },
+            ]
+        );
+        // ATIS stations come and go with their controllers
+        assert_eq!(changed(|data| data.atis.clear()), []);
+    }
+
+    #[test]
+    fn callsign_changes() {
+        let events = changed(|data| {
+            data.pilots[0].callsign = "BAW1A".to_owned();
+            data.controllers[0].callsign = "EGLL_N_TWR".to_owned();
+        });
+        assert_eq!(
+            events,
+            [
+                ChangeEvent::CallsignChanged {
+                    cid: 1,
+                    from: "BAW1".to_owned(),
+                    to: "BAW1A".to_owned()
+                },
+                ChangeEvent::CallsignChanged {
+                    cid: 10,
+                    from: "EGLL_TWR".to_owned(),
+                    to: "EGLL_N_TWR".to_owned()
+                },
+            ]
+        );
+        // a CID connected twice can't be matched up, so it's a second
+        // connection going and another coming
+        let events = changed(|data| data.controllers[2].callsign = "EGSS_OBS".to_owned());
+        assert_eq!(
+            events,
+            [
+                ChangeEvent::ControllerOffline {
+                    cid: 11,
+                    callsign: "EGKK_OBS".to_owned()
+                },
+                ChangeEvent::ControllerOnline {
+                    cid: 11,
+                    callsign: "EGSS_OBS".to_owned()
+                },
+            ]
+        );
+        // someone flying and controlling at once isn't a change
+        assert_eq!(
+            changed(|data| data.controllers.push(controller(1, "EGLL_DEL"))).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn a_renamed_pilot_still_files_and_climbs() {
+        let events = changed(|data| {
+            let pilot = &mut data.pilots[0];
+            pilot.callsign = "BAW1A".to_owned();
+            pilot.altitude = 12_000;
+            pilot.flight_plan = Some(FlightPlan {
+                revision_id: 2,
+                ..testing::flight_plan("EGLL", "EDDF")
+            });
+        });
+        assert_eq!(
+            events,
+            [
+                ChangeEvent::CallsignChanged {
+                    cid: 1,
+                    from: "BAW1".to_owned(),
+                    to: "BAW1A".to_owned()
+                },
+                ChangeEvent::FlightPlanFiled {
+                    cid: 1,
+                    callsign: "BAW1A".to_owned(),
+                    departure: "EGLL".to_owned(),
+                    arrival: "EDDF".to_owned()
+                },
+                ChangeEvent::AltitudeBandChanged {
+                    cid: 1,
+                    callsign: "BAW1A".to_owned(),
+                    from: 0,
+                    to: 10_000
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn flight_plans_filed_and_refiled() {
+        let filed = |cid: i64, callsign: &str, arrival: &str| ChangeEvent::FlightPlanFiled {
+            cid,
+            callsign: callsign.to_owned(),
+            departure: "EGLL".to_owned(),
+            arrival: arrival.to_owned(),
+        };
+        // a first plan
+        assert_eq!(
+            changed(|data| data.pilots[1].flight_plan = Some(testing::flight_plan("EGLL", "EGCC"))),
+            [filed(2, "N123AB", "EGCC")]
+        );
+        // a new revision
+        assert_eq!(
+            changed(|data| {
+                let plan = data.pilots[0].flight_plan.as_mut().unwrap();
+                plan.revision_id = 2;
+                plan.arrival = "KBOS".to_owned();
+            }),
+            [filed(1, "BAW1", "KBOS")]
+        );
+        // the same revision, or a plan taken away
+        assert_eq!(
+            changed(|data| data.pilots[0].flight_plan.as_mut().unwrap().route = "CPT".to_owned()),
+            []
+        );
+        assert_eq!(changed(|data| data.pilots[0].flight_plan = None), []);
+    }
+
+    #[test]
+    fn altitude_bands() {
+        let band = |from: i64, to: i64| {
+            let mut old = snapshot();
+            old.pilots[0].altitude = from;
+            let mut new = snapshot();
+            new.pilots[0].altitude = to;
+            diff_snapshots(&old, &new)
+                .into_iter()
+                .map(|event| match event {
+                    ChangeEvent::AltitudeBandChanged { from, to, .. } => (from, to),
+                    other => panic!("{:?}", other),
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(band(9_999, 10_000), [(0, 10_000)]);
+        assert_eq!(band(10_000, 9_999), [(10_000, 0)]);
+        assert_eq!(band(2_000, 37_000), [(0, 30_000)]);
+        assert_eq!(band(10_000, 19_999), []);
+        // below sea level is the lowest band
+        assert_eq!(band(-11, 50), []);
+        assert_eq!(band(-11, 10_500), [(0, 10_000)]);
+    }
+
+    #[test]
+    fn atis_updates() {
+        let updated = |cid: i64, callsign: &str| ChangeEvent::AtisUpdated {
+            cid,
+            callsign: callsign.to_owned(),
+        };
+        assert_eq!(
+            changed(|data| data.atis[0] = with_lines(controller(12, "EGLL_ATIS"), &["Info B"])),
+            [updated(12, "EGLL_ATIS")]
+        );
+        assert_eq!(
+            changed(|data| data.controllers[0].text_atis = None),
+            [updated(10, "EGLL_TWR")]
+        );
+        assert_eq!(
+            changed(|data| data.controllers[1] = with_lines(controller(11, "EGLL_OBS"), &["Hi"])),
+            [updated(11, "EGLL_OBS")]
+        );
+        // no lines and empty lines are the same
+        assert_eq!(
+            changed(|data| data.controllers[1].text_atis = Some(Vec::new())),
+            []
+        );
+    }
+
+    #[test]
+    fn events_come_in_a_fixed_order() {
+        let events = changed(|data| {
+            data.pilots[2].altitude = 5_000;
+            data.pilots[1].callsign = "N123AC".to_owned();
+            data.atis[0].text_atis = None;
+            let _ = data.pilots.remove(0);
+            data.pilots.push(pilot(0, "EZY9"));
+        });
+        let kinds: Vec<(&str, i64)> = events
+            .iter()
+            .map(|event| {
+                let kind = match event {
+                    ChangeEvent::CallsignChanged { .. } => "renamed",
+                    ChangeEvent::PilotDisconnected { .. } => "gone",
+                    ChangeEvent::PilotConnected { .. } => "came",
+                    ChangeEvent::AltitudeBandChanged { .. } => "band",
+                    ChangeEvent::AtisUpdated { .. } => "atis",
+                    _ => "other",
+                };
+                (kind, event.cid())
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                ("renamed", 2),
+                ("gone", 1),
+                ("came", 0),
+                ("band", 3),
+                ("atis", 12)
+            ]
+        );
+    }
+
+    #[test]
+    fn events_read_as_lines_and_json() {
+        let events = changed(|data| {
+            data.pilots[2].altitude = 24_000;
+            data.pilots.push(pilot(4, "EZY9"));
+            data.controllers[0].callsign = "EGLL_GND".to_owned();
+        });
+        let lines: Vec<String> = events.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            lines,
+            [
+                "EGLL_TWR (10) reconnected as EGLL_GND",
+                "EZY9 (4) connected",
+                "DLH4 (3) descended from 30000 ft to 20000 ft",
+            ]
+        );
+        assert_eq!(
+            serde_json::to_value(&events[2]).unwrap(),
+            serde_json::json!({
+                "event": "altitude_band_changed",
+                "cid": 3,
+                "callsign": "DLH4",
+                "from": 30_000,
+                "to": 20_000,
+            })
+        );
+    }
+}
@@ -0,0 +1,191 @@
+//! Saving raw V3 response bodies, to attach to bug reports about parsing.
+
+use crate::persist;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Start of the names of saved bodies, so other files are left alone.
+const PREFIX: &str = "v3-";
+/// Most saved bodies kept; the oldest go first.
+const MAX_FILES: usize = 20;
+/// Most bytes of saved bodies kept, about 10 full responses.
+const MAX_BYTES: u64 = 50 * 1024 * 1024;
+
+/// Where raw bodies are saved, and which.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DumpSettings {
+    pub dir: PathBuf,
+    /// Save every body, not just those that fail to parse.
+    pub all: bool,
+}
+
+/// Name of the file a body received at `time` is saved to.
+fn file_name(time: DateTime<Utc>) -> String {
+    format!("{}{}.json", PREFIX, time.format("%Y%m%dT%H%M%S%.3fZ"))
+}
+
+/// Save a body to the directory, then remove old ones over the limits.
+pub fn write(dir: &Path, body: &[u8], time: DateTime<Utc>) -> Result<PathBuf> {
+    let path = dir.join(file_name(time));
+    persist::write_atomic(&path, body)?;
+    let _ = prune(dir, MAX_FILES, MAX_BYTES)?;
+    Ok(path)
+}
+
+/// Remove bodies left half-saved when the program was stopped while
+/// saving them, returning how many were removed.
+pub fn discard_partial(dir: &Path) -> Result<usize> {
+    persist::remove_partial(dir, |name| name.starts_with(PREFIX))
+}
+
+/// Remove the oldest saved bodies until at most `max_files` are left,
+/// taking up at most `max_bytes`, returning how many were removed.
+///
+/// The newest is always kept, even if it's over the size limit alone.
+pub fn prune(dir: &Path, max_files: usize, max_bytes: u64) -> Result<usize> {
+    let entries = fs::read_dir(dir).with_context(|| format!("Could not read {}", dir.display()))?;
+    let mut dumps: Vec<(String, PathBuf, u64)> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let name = entry.file_name().to_str()?.to_owned();
+            let path = entry.path();
+            if !name.starts_with(PREFIX) || path.extension()? != "json" {
+                return None;
+            }
+            let size = entry.metadata().ok()?.len();
+            Some((name, path, size))
+        })
+        .collect();
+    // the timestamps in the names sort oldest first
+    dumps.sort();
+    let mut total: u64 = dumps.iter().map(|(_, _, size)| size).sum();
+    let mut count = dumps.len();
+    let mut removed = 0;
+    for (_, path, size) in &dumps {
+        if count <= 1 || (count <= max_files && total <= max_bytes) {
+            break;
+        }
+        fs::remove_file(path).with_context(|| format!("Could not remove {}", path.display()))?;
+        total -= size;
+        count -= 1;
+        removed += 1;
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    fn at(second: u32) -> DateTime<Utc> {
+        Utc.ymd(2026, 10, 16).and_hms(12, 0, second)
+    }
+
+    /// Names of the files in a directory, sorted.
+    fn names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<_> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn names_sort_by_time() {
+        assert_eq!(
+            file_name(at(5) + Duration::milliseconds(42)),
+            "v3-20261016T120005.042Z.json"
+        );
+        assert!(file_name(at(9)) < file_name(at(10)));
+    }
+
+    #[test]
+    fn writing_creates_the_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let dumps = dir.path().join("dumps");
+        let path = write(&dumps, b"{\"general\":", at(0)).unwrap();
+        assert_eq!(path, dumps.join("v3-20261016T120000.000Z.json"));
+        assert_eq!(fs::read(path).unwrap(), b"{\"general\":");
+    }
+
+    #[test]
+    fn the_oldest_go_first_over_the_count() {
+        let dir = tempfile::tempdir().unwrap();
+        for second in 0..5 {
+            fs::write(dir.path().join(file_name(at(second))), "{}").unwrap();
+        }
+        assert_eq!(prune(dir.path(), 3, MAX_BYTES).unwrap(), 2);
+        assert_eq!(
+            names(dir.path()),
+            [file_name(at(2)), file_name(at(3)), file_name(at(4))]
+        );
+        assert_eq!(prune(dir.path(), 3, MAX_BYTES).unwrap(), 0);
+    }
+
+    #[test]
+    fn the_oldest_go_first_over_the_size() {
+        let dir = tempfile::tempdir().unwrap();
+        for (second, size) in [(0, 400), (1, 300), (2, 200), (3, 100)] {
+            fs::write(dir.path().join(file_name(at(second))), vec![b' '; size]).unwrap();
+        }
+        assert_eq!(prune(dir.path(), 10, 350).unwrap(), 2);
+        assert_eq!(names(dir.path()), [file_name(at(2)), file_name(at(3))]);
+    }
+
+    #[test]
+    fn the_newest_is_kept_even_if_too_big() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join(file_name(at(0))), vec![b' '; 10]).unwrap();
+        fs::write(dir.path().join(file_name(at(1))), vec![b' '; 1000]).unwrap();
+        assert_eq!(prune(dir.path(), 10, 100).unwrap(), 1);
+        assert_eq!(names(dir.path()), [file_name(at(1))]);
+    }
+
+    #[test]
+    fn other_files_are_left_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let others = ["notes.json", "v3-notes.txt", "vatsim.log"];
+        for name in others {
+            fs::write(dir.path().join(name), vec![b' '; 1000]).unwrap();
+        }
+        for second in 0..3 {
+            let _ = write(dir.path(), b"{}", at(second)).unwrap();
+        }
+        assert_eq!(prune(dir.path(), 1, 10).unwrap(), 2);
+        let mut expected: Vec<String> = others.map(str::to_owned).to_vec();
+        expected.push(file_name(at(2)));
+        expected.sort();
+        assert_eq!(names(dir.path()), expected);
+    }
+
+    #[test]
+    fn writing_keeps_within_the_limits() {
+        let dir = tempfile::tempdir().unwrap();
+        for second in 0..25 {
+            let _ = write(dir.path(), b"{}", at(second)).unwrap();
+        }
+        let kept = names(dir.path());
+        assert_eq!(kept.len(), MAX_FILES);
+        assert_eq!(kept[0], file_name(at(5)));
+    }
+
+    #[test]
+    fn partial_saves_are_discarded() {
+        let dir = tempfile::tempdir().unwrap();
+        let partial = format!(".{}.tmp", file_name(at(0)));
+        fs::write(dir.path().join(&partial), "{").unwrap();
+        fs::write(dir.path().join(".other.tmp"), "{").unwrap();
+        fs::write(dir.path().join(file_name(at(1))), "{}").unwrap();
+        assert_eq!(discard_partial(dir.path()).unwrap(), 1);
+        assert_eq!(
+            names(dir.path()),
+            [".other.tmp".to_owned(), file_name(at(1))]
+        );
+    }
+}
@@ -0,0 +1,346 @@
+//! Values derived from a pilot's record, computed once per refresh so every
+//! view shows the same answers.
+
+use crate::{
+    flight::{self, Phase, PilotKinematics},
+    ghosts::Ghosts,
+    models::{Pilot, V3ResponseData},
+    sanity::{self, BadData, Limits},
+};
+use chrono::{DateTime, Duration, Utc};
+use std::{collections::HashMap, hash::BuildHasher};
+
+/// What the derived values depend on besides the pilot's record.
+#[derive(Debug, Clone, Copy)]
+pub struct EnrichOptions<'a> {
+    /// When the values are computed, for the ETA and session length.
+    pub now: DateTime<Utc>,
+    /// Vertical speeds in feet per minute by CID, estimated from the
+    /// previous refresh.
+    pub vertical_rates: &'a HashMap<i64, f64>,
+    /// Each pilot's record in the previous refresh, by callsign.
+    pub previous: &'a HashMap<&'a str, &'a Pilot>,
+    /// Past these values a record is taken to be broken.
+    pub limits: Limits,
+}
+
+/// What tells one pilot's connection from another's. A ghost has its
+/// primary's CID and often its callsign too, but not its update time.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ConnectionKey {
+    pub cid: i64,
+    pub callsign: String,
+    pub last_updated: String,
+}
+
+impl ConnectionKey {
+    /// The key of a pilot's connection.
+    #[must_use]
+    pub fn of(pilot: &Pilot) -> Self {
+        Self {
+            cid: pilot.cid,
+            callsign: pilot.callsign.clone(),
+            last_updated: pilot.last_updated.clone(),
+        }
+    }
+}
+
+/// A pilot's derived values.
+///
+/// Values that need the arrival airport are `None` when it's not in the
+/// embedded airport database.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnrichedPilot {
+    pub cid: i64,
+    pub callsign: String,
+    /// When the record was last updated, as sent.
+    pub last_updated: String,
+    /// Whether another connection with the same CID was updated more
+    /// recently, making this one probably a ghost. A ghost's phase is
+    /// guessed without a vertical speed, and its record isn't compared
+    /// with the previous refresh, as both of those belong to its primary.
+    pub ghost: bool,
+    /// Filed cruise altitude in feet, parsed from free text.
+    pub filed_altitude_ft: Option<i64>,
+    /// Phase of flight. Heuristic: it's guessed from speed, altitude, and
+    /// the vertical speed since the previous refresh.
+    pub phase: Phase,
+    /// Great-circle distance to the arrival airport.
+    pub distance_remaining_nm: Option<f64>,
+    /// Arrival time at the current ground speed, straight to the airport.
+    /// Heuristic: it ignores routing, winds, and slowing down to land.
+    pub eta: Option<DateTime<Utc>>,
+    /// How long the pilot has been connected.
+    pub online_for: Option<Duration>,
+    /// ICAO aircraft type without the wake category and equipment codes,
+    /// like "B738".
+    pub aircraft_type: Option<String>,
+    /// Why the record looks broken, if it does. Broken records have no
+    /// distance or ETA.
+    pub bad_data: Option<BadData>,
+}
+
+impl EnrichedPilot {
+    /// The key of the pilot's connection.
+    #[must_use]
+    pub fn key(&self) -> ConnectionKey {
+        ConnectionKey {
+            cid: self.cid,
+            callsign: self.callsign.clone(),
+            last_updated: self.last_updated.clone(),
+        }
+    }
+}
+
+/// A pilot's ICAO aircraft type, like "B738" from "H/B738/L".
+///
+/// The short type is used if the flight plan has one; otherwise the FAA
+/// form is stripped of its one-letter prefix and suffix.
+pub fn aircraft_type(pilot: &Pilot) -> Option<String> {
+    let plan = pilot.flight_plan.as_ref()?;
+    let short = plan.aircraft_short.trim();
+    if !short.is_empty() {
+        return Some(short.to_uppercase());
+    }
+    let aircraft = pilot.aircraft()?;
+    aircraft
+        .split('/')
+        .map(str::trim)
+        .find(|part| part.len() > 1)
+        .map(str::to_uppercase)
+}
+
+/// A pilot's phase of flight, using the estimated vertical speeds.
+#[must_use]
+pub fn phase<S: BuildHasher>(pilot: &Pilot, vertical_rates: &HashMap<i64, f64, S>) -> Phase {
+    flight::phase_of_flight(&PilotKinematics::new(
+        pilot,
+        vertical_rates.get(&pilot.cid).copied(),
+    ))
+}
+
+/// Derive a pilot's values.
+#[must_use]
+pub fn pilot(pilot: &Pilot, options: &EnrichOptions) -> EnrichedPilot {
+    let bad_data = sanity::check(
+        pilot,
+        options.previous.get(pilot.callsign.as_str()).copied(),
+        &options.limits,
+    );
+    let trusted = bad_data.is_none();
+    EnrichedPilot {
+        cid: pilot.cid,
+        callsign: pilot.callsign.clone(),
+        last_updated: pilot.last_updated.clone(),
+        ghost: false,
+        filed_altitude_ft: pilot
+            .flight_plan
+            .as_ref()
+            .and_then(|plan| flight::parse_filed_altitude(&plan.altitude)),
+        phase: phase(pilot, options.vertical_rates),
+        distance_remaining_nm: trusted
+            .then(|| flight::distance_to_arrival(pilot))
+            .flatten(),
+        eta: trusted
+            .then(|| flight::estimated_arrival(pilot, options.now))
+            .flatten(),
+        online_for: pilot.logon().map(|logon| options.now - logon),
+        aircraft_type: aircraft_type(pilot),
+        bad_data,
+    }
+}
+
+/// Derive every pilot's values, in the same order as the data.
+#[must_use]
+pub fn enrich(data: &V3ResponseData, options: &EnrichOptions) -> Vec<EnrichedPilot> {
+    enrich_with(data, &Ghosts::find(data), options)
+}
+
+/// Derive every pilot's values, in the same order as the data, with its
+/// ghosts already found.
+#[must_use]
+pub fn enrich_with(
+    data: &V3ResponseData,
+    ghosts: &Ghosts,
+    options: &EnrichOptions,
+) -> Vec<EnrichedPilot> {
+    let no_rates = HashMap::new();
+    let no_previous = HashMap::new();
+    let ghost_options = EnrichOptions {
+        vertical_rates: &no_rates,
+        previous: &no_previous,
+        ..*options
+    };
+    data.pilots
+        .iter()
+        .map(|p| {
+            if ghosts.has_pilot(p) {
+                EnrichedPilot {
+                    ghost: true,
+                    ..pilot(p, &ghost_options)
+                }
+            } else {
+                pilot(p, options)
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use chrono::TimeZone;
+
+    /// San Francisco's position in the airport database.
+    const KSFO: (f64, f64) = (37.62, -122.38);
+
+    /// A pilot bound for an airport, `nm` due north of San Francisco.
+    fn pilot(callsign: &str, arrival: &str, nm: f64, altitude: i64, groundspeed: i64) -> Pilot {
+        Pilot {
+            latitude: KSFO.0 + nm / 60.0,
+            longitude: KSFO.1,
+            altitude,
+            groundspeed,
+            ..testing::flying(callsign, "KSEA", arrival)
+        }
+    }
+
+    /// A refresh with a pilot of each kind, by CID.
+    fn fixture() -> V3ResponseData {
+        let mut pilots = vec![
+            pilot("ASA1", "KSFO", 60.0, 11_000, 450),
+            pilot("ASA2", "KSFO", 0.0, 4, 0),
+            pilot("ASA3", "KZZZ", 200.0, 35_000, 480),
+            pilot("ASA4", "KSFO", 300.0, 35_000, 30_000),
+            pilot("ASA5", "KSFO", 400.0, 35_000, 450),
+            Pilot {
+                logon_time: "garbage".to_owned(),
+                ..testing::pilot("N123")
+            },
+        ];
+        if let Some(plan) = &mut pilots[2].flight_plan {
+            plan.aircraft_short = String::new();
+            plan.aircraft_faa = "H/b77w/L".to_owned();
+            plan.altitude = "VFR".to_owned();
+        }
+        for (cid, pilot) in (1..).zip(&mut pilots) {
+            pilot.cid = cid;
+        }
+        V3ResponseData {
+            pilots,
+            ..V3ResponseData::default()
+        }
+    }
+
+    fn enriched(previous: &[Pilot]) -> Vec<EnrichedPilot> {
+        let vertical_rates: HashMap<i64, f64> = [(1, -1_500.0), (2, 0.0), (3, 0.0)].into();
+        let previous = previous.iter().map(|p| (p.callsign.as_str(), p)).collect();
+        let options = EnrichOptions {
+            now: Utc.ymd(2022, 8, 1).and_hms(12, 30, 0),
+            vertical_rates: &vertical_rates,
+            previous: &previous,
+            limits: Limits::default(),
+        };
+        enrich(&fixture(), &options)
+    }
+
+    #[test]
+    fn one_for_each_pilot_in_order() {
+        let callsigns: Vec<String> = enriched(&[]).into_iter().map(|e| e.callsign).collect();
+        assert_eq!(callsigns, ["ASA1", "ASA2", "ASA3", "ASA4", "ASA5", "N123"]);
+    }
+
+    #[test]
+    fn inbound_pilot() {
+        let inbound = &enriched(&[])[0];
+        assert_eq!(inbound.cid, 1);
+        assert_eq!(inbound.filed_altitude_ft, Some(35_000));
+        assert_eq!(inbound.phase, Phase::Descent);
+        let distance = inbound.distance_remaining_nm.unwrap();
+        assert!((distance - 60.0).abs() < 0.1, "{}", distance);
+        assert_eq!(inbound.eta, Some(Utc.ymd(2022, 8, 1).and_hms(12, 38, 0)));
+        assert_eq!(inbound.online_for, Some(Duration::minutes(30)));
+        assert_eq!(inbound.aircraft_type.as_deref(), Some("B738"));
+        assert_eq!(inbound.bad_data, None);
+    }
+
+    #[test]
+    fn on_the_ground_at_the_arrival() {
+        let parked = &enriched(&[])[1];
+        assert_eq!(parked.phase, Phase::Ground);
+        assert!(parked.distance_remaining_nm.unwrap() < 0.1);
+        // no ETA without moving
+        assert_eq!(parked.eta, None);
+    }
+
+    #[test]
+    fn arrival_not_in_the_airport_database() {
+        let unknown = &enriched(&[])[2];
+        assert_eq!(unknown.distance_remaining_nm, None);
+        assert_eq!(unknown.eta, None);
+        assert_eq!(unknown.filed_altitude_ft, None);
+        // the FAA form, without its prefix and suffix
+        assert_eq!(unknown.aircraft_type.as_deref(), Some("B77W"));
+    }
+
+    #[test]
+    fn broken_records_have_no_distance_or_eta() {
+        let broken = &enriched(&[])[3];
+        assert_eq!(broken.bad_data, Some(BadData::Groundspeed(30_000)));
+        assert_eq!((broken.distance_remaining_nm, broken.eta), (None, None));
+
+        // a pilot who jumped across the globe since the previous refresh
+        let mut before = fixture().pilots[4].clone();
+        before.latitude = -30.0;
+        let jumped = &enriched(&[before])[4];
+        assert!(matches!(jumped.bad_data, Some(BadData::Jump(_))));
+        assert_eq!(jumped.eta, None);
+    }
+
+    #[test]
+    fn ghosts_are_enriched_without_their_primaries_rates() {
+        let mut data = fixture();
+        // ASA1's connection left behind before it reconnected
+        let ghost = Pilot {
+            last_updated: "2022-08-01T11:00:00Z".to_owned(),
+            ..data.pilots[0].clone()
+        };
+        data.pilots.push(ghost);
+        let vertical_rates: HashMap<i64, f64> = [(1, -1_500.0)].into();
+        let previous = HashMap::new();
+        let options = EnrichOptions {
+            now: Utc.ymd(2022, 8, 1).and_hms(12, 30, 0),
+            vertical_rates: &vertical_rates,
+            previous: &previous,
+            limits: Limits::default(),
+        };
+        let enriched = enrich(&data, &options);
+        let (primary, ghost) = (&enriched[0], &enriched[6]);
+        assert_eq!((primary.phase, primary.ghost), (Phase::Descent, false));
+        assert!(ghost.ghost);
+        assert_ne!(ghost.phase, Phase::Descent);
+        assert_eq!(ghost.aircraft_type.as_deref(), Some("B738"));
+        // same CID and callsign, but a connection of its own
+        assert_ne!(primary.key(), ghost.key());
+        assert_eq!(ghost.key(), ConnectionKey::of(&data.pilots[6]));
+    }
+
+    #[test]
+    fn without_a_flight_plan_or_previous_refresh() {
+        let enriched = enriched(&[]);
+        // not in the previous refresh, so there's no vertical rate
+        assert_eq!(enriched[4].phase, Phase::Unknown);
+        let no_plan = &enriched[5];
+        assert_eq!(no_plan.phase, Phase::Unknown);
+        assert_eq!(
+            (
+                no_plan.filed_altitude_ft,
+                no_plan.distance_remaining_nm,
+                no_plan.online_for,
+                no_plan.aircraft_type.as_deref()
+            ),
+            (None, None, None, None)
+        );
+    }
+}
@@ -0,0 +1,103 @@
+//! The panel that takes over the header while refreshes keep failing.
+
+use crate::action::{Action, Effect};
+
+/// Refreshes in a row that fail before the panel takes over the header;
+/// fewer only show in the status.
+const FAILURES_BEFORE_PANEL: usize = 3;
+/// Start of the status shown for a failed refresh.
+pub const FAILED_STATUS: &str = "Could not refresh: ";
+
+/// Refreshes that failed in a row, shown in place of the header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RefreshError {
+    /// The last error.
+    pub message: String,
+    pub failures: usize,
+    /// V3 mirrors tried since the last success.
+    pub mirrors_tried: Vec<String>,
+}
+
+/// Refreshes that failed since the last one that didn't.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ErrorPanel {
+    failures: usize,
+    /// The last error, while refreshes keep failing.
+    message: Option<String>,
+}
+
+impl ErrorPanel {
+    /// Record a failed refresh, returning the status to show for it if
+    /// the panel isn't showing yet.
+    pub fn failed(&mut self, error: &anyhow::Error) -> Option<String> {
+        self.failures += 1;
+        let message = format!("{:#}", error);
+        let status = (!self.is_showing()).then(|| format!("{}{}", FAILED_STATUS, message));
+        self.message = Some(message);
+        status
+    }
+
+    /// Record that a refresh succeeded, closing the panel.
+    pub fn succeeded(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Whether the panel is showing, so keys can retry or switch mirrors.
+    pub fn is_showing(&self) -> bool {
+        self.failures >= FAILURES_BEFORE_PANEL
+    }
+
+    /// The panel, if refreshing has failed enough times in a row.
+    pub fn view(&self, mirrors_tried: &[String]) -> Option<RefreshError> {
+        if !self.is_showing() {
+            return None;
+        }
+        Some(RefreshError {
+            message: self.message.clone()?,
+            failures: self.failures,
+            mirrors_tried: mirrors_tried.to_vec(),
+        })
+    }
+
+    /// Apply an action while the panel shows: retrying, or trying the
+    /// next mirror.
+    pub fn update(&self, action: Action) -> Option<Effect> {
+        if !self.is_showing() {
+            return None;
+        }
+        match action {
+            Action::Retry => Some(Effect::Fetch),
+            Action::NextMirror => Some(Effect::NextMirror),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    #[test]
+    fn shows_after_enough_failures_in_a_row() {
+        let mut panel = ErrorPanel::default();
+        assert_eq!(
+            panel.failed(&anyhow!("timed out")),
+            Some("Could not refresh: timed out".to_owned())
+        );
+        assert_eq!(panel.update(Action::Retry), None);
+        assert!(panel.failed(&anyhow!("timed out")).is_some());
+        assert_eq!(panel.failed(&anyhow!("refused")), None);
+        assert_eq!(panel.update(Action::Retry), Some(Effect::Fetch));
+        assert_eq!(panel.update(Action::Quit), None);
+        assert_eq!(
+            panel.view(&[]).map(|error| (error.message, error.failures)),
+            Some(("refused".to_owned(), 3))
+        );
+
+        panel.succeeded();
+        assert!(!panel.is_showing());
+        assert_eq!(panel.view(&[]), None);
+        assert!(panel.failed(&anyhow!("timed out")).is_some());
+    }
+}
@@ -0,0 +1,192 @@
+//! Which VATSIM events are worth showing, and when.
+
+use crate::models::Event;
+use chrono::{DateTime, Duration, Utc};
+use std::{collections::HashSet, fmt};
+
+/// How far ahead upcoming events are listed, in hours.
+const UPCOMING_HOURS: i64 = 24;
+
+/// Where an event is in time, relative to now.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timing {
+    /// Started and not yet over.
+    Active,
+    /// Starting within the next day.
+    Upcoming,
+}
+
+impl fmt::Display for Timing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Timing::Active => "Active",
+            Timing::Upcoming => "Upcoming",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Where an event is in time, or `None` if it's over, too far off to
+/// list, or its times don't parse.
+pub fn timing(event: &Event, now: DateTime<Utc>) -> Option<Timing> {
+    let start = event.start()?;
+    let end = event.end()?;
+    if now >= end {
+        None
+    } else if now >= start {
+        Some(Timing::Active)
+    } else if start - now <= Duration::hours(UPCOMING_HOURS) {
+        Some(Timing::Upcoming)
+    } else {
+        None
+    }
+}
+
+/// Indexes of the events to list, in the order they're given.
+pub fn listed(events: &[Event], now: DateTime<Utc>) -> Vec<usize> {
+    events
+        .iter()
+        .enumerate()
+        .filter(|(_, event)| timing(event, now).is_some())
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// ICAO codes of the airports in events that are on now.
+pub fn active_airports(events: &[Event], now: DateTime<Utc>) -> HashSet<&str> {
+    events
+        .iter()
+        .filter(|event| timing(event, now) == Some(Timing::Active))
+        .flat_map(|event| event.airports.iter().map(|airport| airport.icao.as_str()))
+        .collect()
+}
+
+/// The event's airports, comma-separated.
+pub fn airports_text(event: &Event) -> String {
+    event
+        .airports
+        .iter()
+        .map(|airport| airport.icao.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The event's organising regions, divisions, and subdivisions, most
+/// specific first.
+pub fn organisers_text(event: &Event) -> String {
+    event
+        .organisers
+        .iter()
+        .map(|organiser| {
+            [
+                &organiser.subdivision,
+                &organiser.division,
+                &organiser.region,
+            ]
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join(" / ")
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::EventsResponse;
+    use chrono::TimeZone;
+
+    /// The events in `src/testdata/events.json`, shaped like the API's.
+    fn fixture() -> Vec<Event> {
+        let path =
+            std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("src/testdata/events.json");
+        let text = std::fs::read_to_string(path).unwrap();
+        serde_json::from_str::<EventsResponse>(&text).unwrap().data
+    }
+
+    /// Midnight on the Saturday, in the middle of Friday Night Ops.
+    fn now() -> DateTime<Utc> {
+        Utc.ymd(2022, 8, 6).and_hms(0, 0, 0)
+    }
+
+    #[test]
+    fn parses_the_fixture() {
+        let events = fixture();
+        assert_eq!(events.len(), 5);
+        let first = &events[0];
+        assert_eq!(first.id, 101);
+        assert_eq!(first.name, "Friday Night Ops");
+        assert_eq!(first.start(), Some(Utc.ymd(2022, 8, 5).and_hms(23, 0, 0)));
+        assert_eq!(first.end(), Some(Utc.ymd(2022, 8, 6).and_hms(2, 0, 0)));
+        assert_eq!(airports_text(first), "KSFO, KOAK");
+        assert_eq!(organisers_text(first), "ZOA / USA / AMAS");
+        assert_eq!(organisers_text(&events[1]), "EUD / EMEA");
+        // missing lists default to empty
+        assert!(events[2].organisers.is_empty());
+        assert_eq!(organisers_text(&events[2]), "");
+        assert_eq!(airports_text(&events[4]), "");
+        assert_eq!(events[4].start(), None);
+    }
+
+    #[test]
+    fn timestamps_without_a_zone_are_utc() {
+        let mut event = fixture().remove(0);
+        event.start_time = "2022-08-05T23:00:00".to_owned();
+        assert_eq!(event.start(), Some(Utc.ymd(2022, 8, 5).and_hms(23, 0, 0)));
+    }
+
+    #[test]
+    fn classifies_by_time_window() {
+        let events = fixture();
+        let timings: Vec<_> = events.iter().map(|e| timing(e, now())).collect();
+        assert_eq!(
+            timings,
+            [
+                Some(Timing::Active),
+                Some(Timing::Upcoming),
+                // more than a day off
+                None,
+                // over
+                None,
+                // times don't parse
+                None,
+            ]
+        );
+        assert_eq!(listed(&events, now()), [0, 1]);
+    }
+
+    #[test]
+    fn window_edges() {
+        let event = &fixture()[2];
+        let start = event.start().unwrap();
+        let end = event.end().unwrap();
+        assert_eq!(
+            timing(event, start - Duration::hours(UPCOMING_HOURS)),
+            Some(Timing::Upcoming)
+        );
+        assert_eq!(
+            timing(
+                event,
+                start - Duration::hours(UPCOMING_HOURS) - Duration::seconds(1)
+            ),
+            None
+        );
+        assert_eq!(timing(event, start), Some(Timing::Active));
+        assert_eq!(
+            timing(event, end - Duration::seconds(1)),
+            Some(Timing::Active)
+        );
+        assert_eq!(timing(event, end), None);
+    }
+
+    #[test]
+    fn only_active_events_mark_airports() {
+        let events = fixture();
+        let mut airports: Vec<_> = active_airports(&events, now()).into_iter().collect();
+        airports.sort_unstable();
+        assert_eq!(airports, ["KOAK", "KSFO"]);
+    }
+}
@@ -0,0 +1,141 @@
+//! Choosing which kinds of position the Controllers tab shows.
+
+use crate::{models::Controller, reference::Facility};
+use std::collections::BTreeSet;
+
+/// Kinds of position that can be shown or hidden, in the order they're
+/// listed and summarized.
+pub const KINDS: [&str; 8] = ["DEL", "GND", "TWR", "APP", "CTR", "FSS", "ATIS", "OBS"];
+
+/// The kind of position a controller is working, one of [`KINDS`].
+///
+/// ATIS stations log on with a tower facility, so they're told apart by
+/// callsign. Facilities the data doesn't know are counted as observers.
+pub fn kind_of(controller: &Controller) -> &'static str {
+    if controller.callsign.to_uppercase().ends_with("_ATIS") {
+        return "ATIS";
+    }
+    match controller.facility_enum() {
+        Some(Facility::Delivery) => "DEL",
+        Some(Facility::Ground) => "GND",
+        Some(Facility::Tower) => "TWR",
+        Some(Facility::Approach) => "APP",
+        Some(Facility::Center) => "CTR",
+        Some(Facility::FlightService) => "FSS",
+        Some(Facility::Observer | Facility::Unknown(_)) | None => "OBS",
+    }
+}
+
+/// Kinds of position hidden from the Controllers tab; by default none are.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FacilityFilter {
+    hidden: BTreeSet<&'static str>,
+}
+
+impl FacilityFilter {
+    /// A filter hiding the named kinds, ignoring names that aren't kinds.
+    pub fn hiding<S: AsRef<str>>(names: &[S]) -> Self {
+        let hidden = KINDS
+            .iter()
+            .copied()
+            .filter(|kind| {
+                names
+                    .iter()
+                    .any(|name| name.as_ref().eq_ignore_ascii_case(kind))
+            })
+            .collect();
+        Self { hidden }
+    }
+
+    /// The hidden kinds, in the order of [`KINDS`], for saving.
+    pub fn hidden(&self) -> Vec<String> {
+        KINDS
+            .iter()
+            .filter(|kind| self.hidden.contains(*kind))
+            .map(|&kind| kind.to_owned())
+            .collect()
+    }
+
+    /// Whether a kind of position is shown.
+    pub fn shows(&self, kind: &str) -> bool {
+        !self.hidden.contains(kind)
+    }
+
+    /// Whether a controller is shown.
+    pub fn matches(&self, controller: &Controller) -> bool {
+        self.shows(kind_of(controller))
+    }
+
+    /// The kinds shown, like "TWR+APP+CTR", or nothing if they all are.
+    pub fn label(&self) -> Option<String> {
+        if self.hidden.is_empty() {
+            return None;
+        }
+        let shown: Vec<&str> = KINDS
+            .iter()
+            .copied()
+            .filter(|kind| self.shows(kind))
+            .collect();
+        Some(if shown.is_empty() {
+            "No facilities".to_owned()
+        } else {
+            shown.join("+")
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn kinds_come_from_the_facility() {
+        let kind = |callsign, facility| kind_of(&testing::controller(callsign, facility));
+        assert_eq!(kind("EGLL_OBS", 0), "OBS");
+        assert_eq!(kind("EGGX_FSS", 1), "FSS");
+        assert_eq!(kind("EGLL_DEL", 2), "DEL");
+        assert_eq!(kind("EGLL_GND", 3), "GND");
+        assert_eq!(kind("EGLL_TWR", 4), "TWR");
+        assert_eq!(kind("EGLL_APP", 5), "APP");
+        assert_eq!(kind("LON_CTR", 6), "CTR");
+        // towers by facility, but ATIS by callsign
+        assert_eq!(kind("EGLL_ATIS", 4), "ATIS");
+        assert_eq!(kind("kjfk_d_atis", 4), "ATIS");
+        // facilities the data doesn't know
+        assert_eq!(kind("EGLL_XYZ", 42), "OBS");
+        assert_eq!(kind("EGLL_XYZ", 1_000), "OBS");
+    }
+
+    #[test]
+    fn hidden_kinds_round_trip_in_order() {
+        let filter = FacilityFilter::hiding(&["obs", "ATIS", "DEL", "nonsense", "DEL"]);
+        assert_eq!(filter.hidden(), ["DEL", "ATIS", "OBS"]);
+        assert_eq!(FacilityFilter::hiding(&filter.hidden()), filter);
+        assert!(!filter.shows("DEL"));
+        assert!(filter.shows("TWR"));
+        assert!(!filter.matches(&testing::controller("EGLL_ATIS", 4)));
+        assert!(filter.matches(&testing::controller("EGLL_TWR", 4)));
+        assert_eq!(
+            FacilityFilter::hiding::<&str>(&[]),
+            FacilityFilter::default()
+        );
+    }
+
+    #[test]
+    fn label_lists_the_shown_kinds() {
+        assert_eq!(FacilityFilter::default().label(), None);
+        assert_eq!(
+            FacilityFilter::hiding(&["DEL", "GND", "FSS", "ATIS", "OBS"]).label(),
+            Some("TWR+APP+CTR".to_owned())
+        );
+        assert_eq!(
+            FacilityFilter::hiding(&["OBS"]).label(),
+            Some("DEL+GND+TWR+APP+CTR+FSS+ATIS".to_owned())
+        );
+        assert_eq!(
+            FacilityFilter::hiding(&KINDS).label(),
+            Some("No facilities".to_owned())
+        );
+    }
+}
@@ -0,0 +1,434 @@
+//! Favorite clients, highlighted in the interface and managed from the command line.
+
+use crate::report::Reporter;
+use crate::{
+    lookup::Target,
+    persist::{self, Loaded, Versioned},
+};
+use anyhow::{anyhow, bail, Context, Result};
+use clap::Subcommand;
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// Version of the favorites file format.
+const FAVORITES_VERSION: u32 = 1;
+/// Name of the favorites file in the config directory.
+const FAVORITES_FILE_NAME: &str = "favorites.json";
+
+/// What the `favorites` subcommand does.
+#[derive(Debug, Subcommand)]
+pub enum FavoritesAction {
+    /// Add a CID or callsign
+    Add { id: String },
+    /// Remove a CID or callsign
+    Remove { id: String },
+    /// Print the favorites
+    List,
+    /// Merge favorites from a file exported elsewhere
+    Import { path: PathBuf },
+    /// Write the favorites to a file
+    Export { path: PathBuf },
+}
+
+/// CIDs and callsigns of favorite clients.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Favorites {
+    version: u32,
+    entries: Vec<String>,
+}
+
+impl Default for Favorites {
+    fn default() -> Self {
+        Self {
+            version: FAVORITES_VERSION,
+            entries: Vec::new(),
+        }
+    }
+}
+
+/// Outcome of merging one favorites list into another.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MergeReport {
+    pub added: Vec<String>,
+    pub duplicates: Vec<String>,
+}
+
+/// Normalize a CID or callsign for storing and comparing.
+fn normalize(id: &str) -> String {
+    id.trim().to_uppercase()
+}
+
+impl Versioned for Favorites {
+    const VERSION: u32 = FAVORITES_VERSION;
+}
+
+impl Favorites {
+    /// Favorite CIDs and callsigns, in the order they were added.
+    pub fn entries(&self) -> &[String] {
+        &self.entries
+    }
+
+    /// Add a CID or callsign. Returns false if it was already a favorite.
+    pub fn add(&mut self, id: &str) -> Result<bool> {
+        let id = normalize(id);
+        if id.is_empty() {
+            bail!("A CID or callsign is required");
+        }
+        if self.entries.contains(&id) {
+            return Ok(false);
+        }
+        self.entries.push(id);
+        Ok(true)
+    }
+
+    /// Remove a CID or callsign. Returns false if it wasn't a favorite.
+    pub fn remove(&mut self, id: &str) -> bool {
+        let id = normalize(id);
+        let before = self.entries.len();
+        self.entries.retain(|entry| *entry != id);
+        self.entries.len() != before
+    }
+
+    /// Add every entry from another list that isn't already here.
+    pub fn merge(&mut self, other: &Favorites) -> MergeReport {
+        let mut report = MergeReport::default();
+        for entry in &other.entries {
+            if self.entries.contains(entry) {
+                report.duplicates.push(entry.clone());
+            } else {
+                self.entries.push(entry.clone());
+                report.added.push(entry.clone());
+            }
+        }
+        report
+    }
+
+    /// Whether a client with this CID and callsign is a favorite.
+    pub fn contains(&self, cid: i64, callsign: &str) -> bool {
+        self.entries
+            .iter()
+            .any(|entry| Target::parse(entry).matches(cid, callsign))
+    }
+}
+
+/// Path to the favorites file, if the platform has a config directory.
+pub fn favorites_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("vatsim_online").join(FAVORITES_FILE_NAME))
+}
+
+/// Favorites with their entries normalized, since hand-edited files
+/// might not be.
+fn normalized(mut favorites: Favorites) -> Favorites {
+    favorites.entries = favorites.entries.iter().map(|e| normalize(e)).collect();
+    favorites
+}
+
+/// Load favorites from a file to import. A missing file is an empty list.
+pub fn load_from(path: &Path) -> Result<Favorites> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Favorites::default()),
+        Err(e) => return Err(e).with_context(|| format!("Could not read {}", path.display())),
+    };
+    let favorites = persist::parse(&content)
+        .with_context(|| format!("Could not parse favorites in {}", path.display()))?;
+    Ok(normalized(favorites))
+}
+
+/// Save favorites to a file.
+pub fn save_to(path: &Path, favorites: &Favorites) -> Result<()> {
+    persist::save(path, favorites)
+}
+
+/// Load the favorites store, moving a corrupt file aside.
+fn load_store(path: &Path) -> Loaded<Favorites> {
+    let loaded = persist::load(path);
+    Loaded {
+        value: loaded.value.map(normalized),
+        warning: loaded.warning,
+    }
+}
+
+/// Run a favorites subcommand on the favorites store.
+pub fn run(action: FavoritesAction, reporter: &mut Reporter) -> Result<()> {
+    let path = favorites_path().ok_or_else(|| anyhow!("No config directory on this platform"))?;
+    run_at(&path, action, reporter)
+}
+
+/// Run a favorites subcommand on the store at a path.
+fn run_at(store_path: &Path, action: FavoritesAction, reporter: &mut Reporter) -> Result<()> {
+    let loaded = load_store(store_path);
+    if let Some(warning) = loaded.warning {
+        reporter.warn(warning);
+    }
+    let mut store = loaded.value.unwrap_or_default();
+    match action {
+        FavoritesAction::Add { id } => {
+            if store.add(&id)? {
+                save_to(store_path, &store)?;
+                reporter.note(format!("Added {}", id));
+            } else {
+                reporter.note(format!("{} is already a favorite", id));
+            }
+        }
+        FavoritesAction::Remove { id } => {
+            if store.remove(&id) {
+                save_to(store_path, &store)?;
+                reporter.note(format!("Removed {}", id));
+            } else {
+                reporter.note(format!("{} is not a favorite", id));
+            }
+        }
+        FavoritesAction::List => {
+            for entry in store.entries() {
+                reporter.data(entry)?;
+            }
+        }
+        FavoritesAction::Import { path } => {
+            let imported = load_from(&path)?;
+            let report = store.merge(&imported);
+            save_to(store_path, &store)?;
+            reporter.note(format!("Imported {} favorites", report.added.len()));
+            if !report.duplicates.is_empty() {
+                reporter.note(format!(
+                    "Skipped {} already present: {}",
+                    report.duplicates.len(),
+                    report.duplicates.join(", ")
+                ));
+            }
+        }
+        FavoritesAction::Export { path } => {
+            save_to(&path, &store)?;
+            reporter.note(format!(
+                "Exported {} favorites to {}",
+                store.entries().len(),
+                path.display()
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// The favorites store, reloaded when the file changes on disk.
+#[derive(Debug, Default)]
+pub struct FavoritesFile {
+    favorites: Favorites,
+    modified: Option<SystemTime>,
+    /// Why the file couldn't be used, until it's shown.
+    warning: Option<String>,
+}
+
+impl FavoritesFile {
+    /// Load the favorites store, treating problems as an empty list.
+    pub fn load() -> Self {
+        let mut file = Self::default();
+        let _ = file.reload_if_changed();
+        file
+    }
+
+    /// The current favorites.
+    pub fn favorites(&self) -> &Favorites {
+        &self.favorites
+    }
+
+    /// Reload the favorites if the file's modification time changed.
+    ///
+    /// Returns whether the favorites were reloaded.
+    pub fn reload_if_changed(&mut self) -> bool {
+        favorites_path().is_some_and(|path| self.reload_from(&path))
+    }
+
+    /// Reload the favorites from a path if its modification time changed.
+    fn reload_from(&mut self, path: &Path) -> bool {
+        let modified = fs::metadata(path).and_then(|m| m.modified()).ok();
+        if modified == self.modified {
+            return false;
+        }
+        self.modified = modified;
+        let loaded = load_store(path);
+        self.favorites = loaded.value.unwrap_or_default();
+        if loaded.warning.is_some() {
+            self.warning = loaded.warning;
+        }
+        debug!("Loaded {} favorites", self.favorites.entries.len());
+        true
+    }
+
+    /// Why the file couldn't be used when it was last loaded, if it
+    /// couldn't, once.
+    pub fn take_warning(&mut self) -> Option<String> {
+        self.warning.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::{Captured, Verbosity};
+    use std::time::Duration;
+
+    fn favorites(ids: &[&str]) -> Favorites {
+        let mut favorites = Favorites::default();
+        for id in ids {
+            let _ = favorites.add(id).unwrap();
+        }
+        favorites
+    }
+
+    /// Run a subcommand on a store, returning what it printed.
+    fn run(store: &Path, action: FavoritesAction) -> (Captured, Captured) {
+        let (mut reporter, out, err) = Reporter::captured(Verbosity::Normal);
+        run_at(store, action, &mut reporter).unwrap();
+        (out, err)
+    }
+
+    fn add(id: &str) -> FavoritesAction {
+        FavoritesAction::Add { id: id.to_owned() }
+    }
+
+    #[test]
+    fn add_normalizes_and_skips_duplicates() {
+        let mut favorites = Favorites::default();
+        assert!(favorites.add(" baw123 ").unwrap());
+        assert!(!favorites.add("BAW123").unwrap());
+        assert!(favorites.add("1234567").unwrap());
+        assert!(favorites.add("  ").is_err());
+        assert_eq!(favorites.entries(), ["BAW123", "1234567"]);
+
+        assert!(favorites.remove("baw123"));
+        assert!(!favorites.remove("baw123"));
+        assert_eq!(favorites.entries(), ["1234567"]);
+    }
+
+    #[test]
+    fn matches_cids_and_callsigns() {
+        let favorites = favorites(&["1234567", "egll_twr"]);
+        assert!(favorites.contains(1_234_567, "DLH1"));
+        assert!(favorites.contains(1, "EGLL_TWR"));
+        assert!(!favorites.contains(7_654_321, "EGLL_APP"));
+    }
+
+    #[test]
+    fn merge_adds_new_entries_and_reports_duplicates() {
+        let mut mine = favorites(&["BAW123", "1234567"]);
+        let theirs = favorites(&["1234567", "EGLL_TWR", "BAW123", "DLH1"]);
+        let report = mine.merge(&theirs);
+        assert_eq!(report.added, ["EGLL_TWR", "DLH1"]);
+        assert_eq!(report.duplicates, ["1234567", "BAW123"]);
+        assert_eq!(mine.entries(), ["BAW123", "1234567", "EGLL_TWR", "DLH1"]);
+        assert_eq!(mine.merge(&theirs).added, Vec::<String>::new());
+    }
+
+    #[test]
+    fn hand_edited_files_are_normalized() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("import.json");
+        fs::write(
+            &path,
+            r#"{"version": 1, "entries": [" baw123", "egll_twr "]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            load_from(&path).unwrap(),
+            favorites(&["BAW123", "EGLL_TWR"])
+        );
+        assert_eq!(
+            load_from(&dir.path().join("missing.json")).unwrap(),
+            Favorites::default()
+        );
+    }
+
+    #[test]
+    fn add_remove_and_list() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = dir.path().join(FAVORITES_FILE_NAME);
+        let (out, _) = run(&store, add("baw123"));
+        assert_eq!(out.text(), "Added baw123\n");
+        let (out, _) = run(&store, add("BAW123"));
+        assert_eq!(out.text(), "BAW123 is already a favorite\n");
+        let _ = run(&store, add("1234567"));
+
+        let (out, _) = run(&store, FavoritesAction::List);
+        assert_eq!(out.text(), "BAW123\n1234567\n");
+
+        let (out, _) = run(
+            &store,
+            FavoritesAction::Remove {
+                id: "baw123".to_owned(),
+            },
+        );
+        assert_eq!(out.text(), "Removed baw123\n");
+        let (out, _) = run(&store, FavoritesAction::List);
+        assert_eq!(out.text(), "1234567\n");
+    }
+
+    #[test]
+    fn export_then_import_merges() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = dir.path().join(FAVORITES_FILE_NAME);
+        let exported = dir.path().join("exported.json");
+        let _ = run(&store, add("BAW123"));
+        let _ = run(&store, add("EGLL_TWR"));
+        let (out, _) = run(
+            &store,
+            FavoritesAction::Export {
+                path: exported.clone(),
+            },
+        );
+        assert!(out.text().starts_with("Exported 2 favorites to"));
+
+        let other = dir.path().join("other.json");
+        let _ = run(&other, add("EGLL_TWR"));
+        let _ = run(&other, add("DLH1"));
+        let (out, _) = run(&other, FavoritesAction::Import { path: exported });
+        assert_eq!(
+            out.text(),
+            "Imported 1 favorites\nSkipped 1 already present: EGLL_TWR\n"
+        );
+        assert_eq!(
+            load_from(&other).unwrap(),
+            favorites(&["EGLL_TWR", "DLH1", "BAW123"])
+        );
+    }
+
+    #[test]
+    fn corrupt_store_warns_and_starts_over() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = dir.path().join(FAVORITES_FILE_NAME);
+        fs::write(&store, "not json").unwrap();
+        let (out, err) = run(&store, add("BAW123"));
+        assert_eq!(out.text(), "Added BAW123\n");
+        assert!(!err.text().is_empty());
+        assert_eq!(load_from(&store).unwrap(), favorites(&["BAW123"]));
+    }
+
+    #[test]
+    fn reloads_when_the_file_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = dir.path().join(FAVORITES_FILE_NAME);
+        let mut file = FavoritesFile::default();
+        assert!(!file.reload_from(&store));
+
+        save_to(&store, &favorites(&["BAW123"])).unwrap();
+        assert!(file.reload_from(&store));
+        assert_eq!(file.favorites().entries(), ["BAW123"]);
+        assert!(!file.reload_from(&store));
+
+        save_to(&store, &favorites(&["BAW123", "DLH1"])).unwrap();
+        let later = SystemTime::now() + Duration::from_secs(10);
+        fs::File::options()
+            .write(true)
+            .open(&store)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        assert!(file.reload_from(&store));
+        assert_eq!(file.favorites().entries(), ["BAW123", "DLH1"]);
+    }
+}
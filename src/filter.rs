@@ -0,0 +1,963 @@
+//! Filtering table rows by predicates on their fields.
+
+use crate::{
+    callsign::{self, ControllerCallsign},
+    flight, frequency, geo,
+    models::{Controller, Pilot},
+    reference::Facility,
+    regions,
+    widgets::{Edit, TextInput},
+};
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, Duration, Utc};
+use std::fmt;
+
+/// A field of a pilot or controller that can be filtered on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Callsign,
+    Name,
+    Cid,
+    Server,
+    Departure,
+    Arrival,
+    Aircraft,
+    Altitude,
+    Groundspeed,
+    /// Cruise altitude from the flight plan.
+    FiledAltitude,
+    Frequency,
+    Facility,
+    Rating,
+    /// Country or region of a controller's position, like "UK".
+    Region,
+}
+
+impl Field {
+    /// All fields, in the order they're offered in the filter builder.
+    pub const ALL: [Field; 14] = [
+        Field::Callsign,
+        Field::Name,
+        Field::Cid,
+        Field::Server,
+        Field::Departure,
+        Field::Arrival,
+        Field::Aircraft,
+        Field::Altitude,
+        Field::Groundspeed,
+        Field::FiledAltitude,
+        Field::Frequency,
+        Field::Facility,
+        Field::Rating,
+        Field::Region,
+    ];
+
+    /// Name of the field in filter strings.
+    pub fn name(self) -> &'static str {
+        match self {
+            Field::Callsign => "callsign",
+            Field::Name => "name",
+            Field::Cid => "cid",
+            Field::Server => "server",
+            Field::Departure => "departure",
+            Field::Arrival => "arrival",
+            Field::Aircraft => "aircraft",
+            Field::Altitude => "altitude",
+            Field::Groundspeed => "groundspeed",
+            Field::FiledAltitude => "filed_altitude",
+            Field::Frequency => "frequency",
+            Field::Facility => "facility",
+            Field::Rating => "rating",
+            Field::Region => "region",
+        }
+    }
+
+    /// Look up a field by its name in filter strings.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Field::ALL
+            .into_iter()
+            .find(|field| field.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Whether the field holds a number rather than text.
+    pub fn is_numeric(self) -> bool {
+        matches!(
+            self,
+            Field::Cid
+                | Field::Altitude
+                | Field::Groundspeed
+                | Field::FiledAltitude
+                | Field::Frequency
+                | Field::Rating
+        )
+    }
+}
+
+/// A comparison between a field and a value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Operator {
+    /// All operators, in the order they're offered in the filter builder.
+    pub const ALL: [Operator; 6] = [
+        Operator::Equal,
+        Operator::NotEqual,
+        Operator::Less,
+        Operator::LessOrEqual,
+        Operator::Greater,
+        Operator::GreaterOrEqual,
+    ];
+
+    /// Symbol for the operator in filter strings.
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Operator::Equal => "=",
+            Operator::NotEqual => "!=",
+            Operator::Less => "<",
+            Operator::LessOrEqual => "<=",
+            Operator::Greater => ">",
+            Operator::GreaterOrEqual => ">=",
+        }
+    }
+
+    /// Whether the operator compares order, which only makes sense for numbers.
+    pub fn is_ordering(self) -> bool {
+        !matches!(self, Operator::Equal | Operator::NotEqual)
+    }
+}
+
+/// The value of a field for a specific pilot or controller.
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Text(String),
+    Number(f64),
+    /// The field doesn't apply, or the client hasn't filled it in.
+    Missing,
+}
+
+impl Value {
+    /// Text value, treating empty strings as missing.
+    fn text(value: &str) -> Self {
+        if value.is_empty() {
+            Value::Missing
+        } else {
+            Value::Text(value.to_owned())
+        }
+    }
+}
+
+/// Characters that end a bare value in filter strings, so values with
+/// any of them are written in quotes.
+pub const DELIMITERS: [char; 7] = ['(', ')', '"', '=', '!', '<', '>'];
+
+/// A single condition on a field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Predicate {
+    pub field: Field,
+    pub operator: Operator,
+    pub value: String,
+}
+
+impl Predicate {
+    /// Check that the value and operator make sense for the field.
+    pub fn validate(&self) -> Result<()> {
+        if self.field.is_numeric() {
+            if self.value.trim().parse::<f64>().is_err() {
+                bail!(
+                    "'{}' needs a number, got '{}'",
+                    self.field.name(),
+                    self.value
+                );
+            }
+        } else if self.operator.is_ordering() {
+            bail!(
+                "'{}' is text and can't be compared with '{}'",
+                self.field.name(),
+                self.operator.symbol()
+            );
+        }
+        Ok(())
+    }
+
+    /// Whether a pilot matches the predicate.
+    pub fn matches_pilot(&self, pilot: &Pilot) -> bool {
+        self.matches(&pilot_value(pilot, self.field))
+    }
+
+    /// Whether a controller matches the predicate.
+    pub fn matches_controller(&self, controller: &Controller) -> bool {
+        self.matches(&controller_value(controller, self.field))
+    }
+
+    /// Compare a field's value to the predicate's value.
+    ///
+    /// Missing values never match, not even with "!=".
+    fn matches(&self, actual: &Value) -> bool {
+        match actual {
+            Value::Missing => false,
+            Value::Text(text) => {
+                let equal = glob_match(&self.value, text);
+                match self.operator {
+                    Operator::Equal => equal,
+                    Operator::NotEqual => !equal,
+                    _ => false,
+                }
+            }
+            Value::Number(number) => {
+                let Ok(expected) = self.value.trim().parse::<f64>() else {
+                    return false;
+                };
+                match self.operator {
+                    Operator::Equal => (number - expected).abs() < f64::EPSILON,
+                    Operator::NotEqual => (number - expected).abs() >= f64::EPSILON,
+                    Operator::Less => *number < expected,
+                    Operator::LessOrEqual => *number <= expected,
+                    Operator::Greater => *number > expected,
+                    Operator::GreaterOrEqual => *number >= expected,
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Predicate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.field.name(), self.operator.symbol())?;
+        let bare = !self.value.is_empty()
+            && !self
+                .value
+                .contains(|c: char| c.is_whitespace() || DELIMITERS.contains(&c));
+        if bare {
+            write!(f, "{}", self.value)
+        } else {
+            // backslashes escape quotes, and themselves
+            let escaped = self.value.replace('\\', "\\\\").replace('"', "\\\"");
+            write!(f, "\"{}\"", escaped)
+        }
+    }
+}
+
+/// Value of a field for a pilot.
+fn pilot_value(pilot: &Pilot, field: Field) -> Value {
+    let plan = pilot.flight_plan.as_ref();
+    match field {
+        Field::Callsign => Value::text(&pilot.callsign),
+        Field::Name => Value::text(&pilot.name),
+        #[allow(clippy::cast_precision_loss)]
+        Field::Cid => Value::Number(pilot.cid as f64),
+        Field::Server => Value::text(&pilot.server),
+        Field::Departure => plan.map_or(Value::Missing, |fp| Value::text(&fp.departure)),
+        Field::Arrival => plan.map_or(Value::Missing, |fp| Value::text(&fp.arrival)),
+        Field::Aircraft => pilot.aircraft().map_or(Value::Missing, Value::text),
+        #[allow(clippy::cast_precision_loss)]
+        Field::Altitude => Value::Number(pilot.altitude as f64),
+        #[allow(clippy::cast_precision_loss)]
+        Field::Groundspeed => Value::Number(pilot.groundspeed as f64),
+        #[allow(clippy::cast_precision_loss)]
+        Field::FiledAltitude => plan
+            .and_then(|fp| flight::parse_filed_altitude(&fp.altitude))
+            .map_or(Value::Missing, |feet| Value::Number(feet as f64)),
+        Field::Frequency | Field::Facility | Field::Rating | Field::Region => Value::Missing,
+    }
+}
+
+/// Value of a field for a controller.
+fn controller_value(controller: &Controller, field: Field) -> Value {
+    match field {
+        Field::Callsign => Value::text(&controller.callsign),
+        Field::Name => Value::text(&controller.name),
+        #[allow(clippy::cast_precision_loss)]
+        Field::Cid => Value::Number(controller.cid as f64),
+        Field::Server => Value::text(&controller.server),
+        Field::Frequency => frequency::parse_feed(&controller.frequency)
+            .map_or(Value::Missing, |khz| Value::Number(f64::from(khz) / 1000.0)),
+        Field::Facility => ControllerCallsign::parse(&controller.callsign)
+            .map_or(Value::Missing, |parsed| Value::text(parsed.suffix)),
+        Field::Rating => Value::Number(f64::from(controller.rating)),
+        Field::Region => Value::text(regions::of_controller(controller)),
+        Field::Departure
+        | Field::Arrival
+        | Field::Aircraft
+        | Field::Altitude
+        | Field::Groundspeed
+        | Field::FiledAltitude => Value::Missing,
+    }
+}
+
+/// Case-insensitive match of text against a pattern where `*` matches
+/// any run of characters.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // position of the last '*' in the pattern, and where in the text it started matching
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == text[t] {
+            p += 1;
+            t += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            t = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// A set of predicates that must all match.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Filter {
+    pub predicates: Vec<Predicate>,
+}
+
+/// Quick filter on pilots' flight rules, cycled through with a key.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RulesFilter {
+    #[default]
+    All,
+    Ifr,
+    /// VFR, including special and defense VFR.
+    Vfr,
+    /// Pilots without a flight plan.
+    NoPlan,
+}
+
+impl RulesFilter {
+    /// The next filter in the cycle.
+    pub fn next(self) -> Self {
+        match self {
+            RulesFilter::All => RulesFilter::Ifr,
+            RulesFilter::Ifr => RulesFilter::Vfr,
+            RulesFilter::Vfr => RulesFilter::NoPlan,
+            RulesFilter::NoPlan => RulesFilter::All,
+        }
+    }
+
+    /// Description for the table title.
+    pub fn label(self) -> &'static str {
+        match self {
+            RulesFilter::All => "All",
+            RulesFilter::Ifr => "IFR only",
+            RulesFilter::Vfr => "VFR only",
+            RulesFilter::NoPlan => "No plan",
+        }
+    }
+
+    /// Whether a pilot matches the filter.
+    pub fn matches(self, pilot: &Pilot) -> bool {
+        let rules = pilot
+            .flight_plan
+            .as_ref()
+            .map(|fp| fp.flight_rules.as_str());
+        match self {
+            RulesFilter::All => true,
+            RulesFilter::Ifr => rules == Some("I"),
+            RulesFilter::Vfr => matches!(rules, Some("V" | "S" | "D")),
+            RulesFilter::NoPlan => rules.is_none(),
+        }
+    }
+}
+
+/// Only pilots flying for some airlines, by ICAO airline code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AirlineFilter {
+    codes: Vec<String>,
+}
+
+impl AirlineFilter {
+    /// Parse comma-separated airline codes, like "DLH,BAW".
+    pub fn parse(value: &str) -> Result<Self> {
+        let codes: Vec<String> = value
+            .split(',')
+            .map(|code| code.trim().to_uppercase())
+            .filter(|code| !code.is_empty())
+            .collect();
+        if codes.is_empty() {
+            bail!("no airline codes given");
+        }
+        if let Some(code) = codes
+            .iter()
+            .find(|code| code.len() != 3 || !code.chars().all(|c| c.is_ascii_alphabetic()))
+        {
+            bail!("'{}' is not a three-letter ICAO airline code", code);
+        }
+        Ok(Self { codes })
+    }
+
+    /// Whether a pilot's callsign is a flight of one of the airlines.
+    pub fn matches(&self, pilot: &Pilot) -> bool {
+        callsign::airline(&pilot.callsign).is_some_and(|code| self.codes.contains(&code))
+    }
+
+    /// Description for the table title.
+    pub fn label(&self) -> String {
+        format!("Airlines: {}", self.codes.join(", "))
+    }
+}
+
+/// Whether a controller is working a tower, approach, or center position
+/// without having published an ATIS.
+pub fn missing_atis(controller: &Controller) -> bool {
+    controller.text_atis.is_none()
+        && matches!(
+            controller.facility_enum(),
+            Some(Facility::Tower | Facility::Approach | Facility::Center)
+        )
+}
+
+/// How long before the data was generated a client's record was last
+/// updated, if both times parse.
+pub fn record_age(
+    last_update: Option<DateTime<Utc>>,
+    data_updated: Option<DateTime<Utc>>,
+) -> Option<Duration> {
+    Some(data_updated? - last_update?)
+}
+
+/// Whether a record's age is past the threshold; unknown ages aren't stale.
+pub fn is_stale(age: Option<Duration>, threshold: Option<Duration>) -> bool {
+    matches!((age, threshold), (Some(age), Some(threshold)) if age > threshold)
+}
+
+/// Pilots within a controller's visual range.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RangeFilter {
+    pub callsign: String,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub range_nm: f64,
+}
+
+impl RangeFilter {
+    /// Filter to a controller's visual range, if they can be placed on the map.
+    pub fn around(controller: &Controller) -> Result<Self> {
+        if controller.visual_range <= 0 {
+            bail!("{} has no visual range", controller.callsign);
+        }
+        let airport = callsign::controller_airport(&controller.callsign).ok_or_else(|| {
+            anyhow!(
+                "Can't tell where {} is, so pilots in range can't be shown",
+                controller.callsign
+            )
+        })?;
+        #[allow(clippy::cast_precision_loss)]
+        let range_nm = controller.visual_range as f64;
+        Ok(Self {
+            callsign: controller.callsign.clone(),
+            latitude: airport.latitude,
+            longitude: airport.longitude,
+            range_nm,
+        })
+    }
+
+    /// Distance from the controller to a pilot.
+    pub fn distance_nm(&self, pilot: &Pilot) -> f64 {
+        geo::distance_nm(
+            self.latitude,
+            self.longitude,
+            pilot.latitude,
+            pilot.longitude,
+        )
+    }
+
+    /// Whether a pilot is within range.
+    pub fn matches(&self, pilot: &Pilot) -> bool {
+        self.distance_nm(pilot) <= self.range_nm
+    }
+
+    /// Description for the table title.
+    pub fn label(&self) -> String {
+        format!("Within {} nm of {}", self.range_nm, self.callsign)
+    }
+}
+
+/// Which part of a predicate row is being edited in the filter builder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuilderFocus {
+    Field,
+    Operator,
+    Value,
+}
+
+/// Editing state of the filter builder popup.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilterBuilder {
+    pub rows: Vec<Predicate>,
+    pub selected: usize,
+    pub focus: BuilderFocus,
+    pub error: Option<String>,
+    /// The selected row's value as it's being typed, with the cursor.
+    pub value: TextInput,
+}
+
+impl FilterBuilder {
+    /// Start editing from an existing filter, with an empty row if there are none.
+    pub fn new(filter: &Filter) -> Self {
+        let mut builder = Self {
+            rows: filter.predicates.clone(),
+            selected: 0,
+            focus: BuilderFocus::Value,
+            error: None,
+            value: TextInput::default(),
+        };
+        if builder.rows.is_empty() {
+            builder.add_row();
+        }
+        builder.load_value();
+        builder
+    }
+
+    /// Add a new row after the existing ones and select it.
+    pub fn add_row(&mut self) {
+        self.rows.push(Predicate {
+            field: Field::Callsign,
+            operator: Operator::Equal,
+            value: String::new(),
+        });
+        self.selected = self.rows.len() - 1;
+        self.focus = BuilderFocus::Field;
+        self.load_value();
+    }
+
+    /// Remove the selected row.
+    pub fn remove_row(&mut self) {
+        if self.selected < self.rows.len() {
+            let _ = self.rows.remove(self.selected);
+        }
+        self.selected = self.selected.min(self.rows.len().saturating_sub(1));
+        self.load_value();
+    }
+
+    /// Select the previous row.
+    pub fn up(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+        self.load_value();
+    }
+
+    /// Select the next row.
+    pub fn down(&mut self) {
+        if self.selected + 1 < self.rows.len() {
+            self.selected += 1;
+        }
+        self.load_value();
+    }
+
+    /// Start editing the selected row's value, with the cursor at the end.
+    fn load_value(&mut self) {
+        let value = self
+            .rows
+            .get(self.selected)
+            .map_or("", |row| row.value.as_str());
+        self.value.set(value);
+    }
+
+    /// Change the selected row's value, focusing it.
+    fn change_value(&mut self, change: impl FnOnce(&mut TextInput)) {
+        if let Some(row) = self.rows.get_mut(self.selected) {
+            self.focus = BuilderFocus::Value;
+            change(&mut self.value);
+            self.value.text().clone_into(&mut row.value);
+        }
+    }
+
+    /// Move focus to the next part of the row.
+    pub fn next_focus(&mut self) {
+        self.focus = match self.focus {
+            BuilderFocus::Field => BuilderFocus::Operator,
+            BuilderFocus::Operator => BuilderFocus::Value,
+            BuilderFocus::Value => BuilderFocus::Field,
+        };
+    }
+
+    /// Change the focused field or operator to the next or previous choice.
+    fn cycle(&mut self, forward: bool) {
+        let Some(row) = self.rows.get_mut(self.selected) else {
+            return;
+        };
+        match self.focus {
+            BuilderFocus::Field => {
+                row.field = cycle_item(&Field::ALL, row.field, forward);
+            }
+            BuilderFocus::Operator => {
+                row.operator = cycle_item(&Operator::ALL, row.operator, forward);
+            }
+            BuilderFocus::Value => {}
+        }
+    }
+
+    /// Type a character into the selected row's value.
+    pub fn input(&mut self, c: char) {
+        self.change_value(|value| value.insert(c));
+    }
+
+    /// Paste text into the selected row's value.
+    pub fn paste(&mut self, text: &str) {
+        self.change_value(|value| value.paste(text));
+    }
+
+    /// Apply an editing key to the selected row's value.
+    ///
+    /// Left and right change the field or operator instead when one of
+    /// them is focused.
+    pub fn edit(&mut self, edit: Edit) {
+        match (self.focus, edit) {
+            (BuilderFocus::Field | BuilderFocus::Operator, Edit::Left) => self.cycle(false),
+            (BuilderFocus::Field | BuilderFocus::Operator, Edit::Right) => self.cycle(true),
+            _ => self.change_value(|value| value.edit(edit)),
+        }
+    }
+
+    /// Build a filter from the rows, skipping rows with empty values.
+    ///
+    /// The error is also stored to show in the popup.
+    pub fn build(&mut self) -> Result<Filter> {
+        let predicates: Vec<Predicate> = self
+            .rows
+            .iter()
+            .filter(|row| !row.value.is_empty())
+            .cloned()
+            .collect();
+        for predicate in &predicates {
+            if let Err(e) = predicate.validate() {
+                self.error = Some(e.to_string());
+                return Err(e);
+            }
+        }
+        self.error = None;
+        Ok(Filter { predicates })
+    }
+}
+
+/// Get the item after (or before) `current` in `items`, wrapping around.
+fn cycle_item<T: Copy + PartialEq>(items: &[T], current: T, forward: bool) -> T {
+    let index = items.iter().position(|&i| i == current).unwrap_or(0);
+    let next = if forward {
+        (index + 1) % items.len()
+    } else {
+        (index + items.len() - 1) % items.len()
+    };
+    items[next]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        models::{self, FlightPlan},
+        query::Query,
+        testing,
+    };
+
+    fn predicate(field: Field, operator: Operator, value: &str) -> Predicate {
+        Predicate {
+            field,
+            operator,
+            value: value.to_owned(),
+        }
+    }
+
+    #[test]
+    fn validate_checks_value_and_operator() {
+        assert!(predicate(Field::Altitude, Operator::Greater, "30000")
+            .validate()
+            .is_ok());
+        assert!(predicate(Field::Altitude, Operator::Equal, "high")
+            .validate()
+            .is_err());
+        assert!(predicate(Field::Callsign, Operator::Less, "BAW")
+            .validate()
+            .is_err());
+        assert!(predicate(Field::Callsign, Operator::NotEqual, "BAW*")
+            .validate()
+            .is_ok());
+    }
+
+    #[test]
+    fn matches_pilot_fields() {
+        let mut pilot = testing::flying("BAW123", "EGLL", "KJFK");
+        pilot.altitude = 35_000;
+        assert!(predicate(Field::Callsign, Operator::Equal, "baw*").matches_pilot(&pilot));
+        assert!(predicate(Field::Departure, Operator::Equal, "EG**").matches_pilot(&pilot));
+        assert!(predicate(Field::Arrival, Operator::NotEqual, "EGLL").matches_pilot(&pilot));
+        assert!(predicate(Field::Aircraft, Operator::Equal, "B738*").matches_pilot(&pilot));
+        assert!(
+            predicate(Field::Altitude, Operator::GreaterOrEqual, "35000").matches_pilot(&pilot)
+        );
+        assert!(!predicate(Field::Altitude, Operator::Less, "35000").matches_pilot(&pilot));
+        assert!(predicate(Field::FiledAltitude, Operator::Equal, "35000").matches_pilot(&pilot));
+    }
+
+    #[test]
+    fn filed_altitude_is_parsed_before_comparing() {
+        let mut pilot = testing::flying("BAW123", "EGLL", "KJFK");
+        let above = predicate(Field::FiledAltitude, Operator::Greater, "30000");
+        for (filed, matches) in [
+            ("FL350", true),
+            ("350", true),
+            ("FL290", false),
+            ("VFR", false),
+        ] {
+            if let Some(plan) = &mut pilot.flight_plan {
+                plan.altitude = filed.to_owned();
+            }
+            assert_eq!(above.matches_pilot(&pilot), matches, "{}", filed);
+        }
+    }
+
+    #[test]
+    fn missing_values_never_match() {
+        let pilot = testing::pilot("N123AB");
+        assert!(!predicate(Field::Departure, Operator::Equal, "*").matches_pilot(&pilot));
+        assert!(!predicate(Field::Departure, Operator::NotEqual, "EGLL").matches_pilot(&pilot));
+        assert!(!predicate(Field::Frequency, Operator::Greater, "0").matches_pilot(&pilot));
+        let controller = testing::controller("EGLL_TWR", 4);
+        assert!(!predicate(Field::Altitude, Operator::Less, "1").matches_controller(&controller));
+    }
+
+    #[test]
+    fn matches_controller_fields() {
+        let controller = testing::controller("EGLL_TWR", 4);
+        assert!(
+            predicate(Field::Frequency, Operator::Equal, "118.5").matches_controller(&controller)
+        );
+        assert!(predicate(Field::Facility, Operator::Equal, "twr").matches_controller(&controller));
+        assert!(predicate(Field::Rating, Operator::Greater, "4").matches_controller(&controller));
+        assert!(!predicate(Field::Cid, Operator::Equal, "1").matches_controller(&controller));
+    }
+
+    #[test]
+    fn matches_controller_regions() {
+        let region = |value: &str, callsign: &str| {
+            predicate(Field::Region, Operator::Equal, value)
+                .matches_controller(&testing::controller(callsign, 4))
+        };
+        assert!(region("UK", "EGLL_TWR"));
+        assert!(region("uk", "EGTT_CTR"));
+        assert!(region("Other", "LON_S_CTR"));
+        assert!(region("South*", "FAOR_TWR"));
+        assert!(!region("UK", "UKBB_TWR"));
+        assert!(
+            !predicate(Field::Region, Operator::Equal, "*").matches_pilot(&testing::pilot("BAW1"))
+        );
+    }
+
+    #[test]
+    fn glob_edge_cases() {
+        assert!(glob_match("", ""));
+        assert!(!glob_match("", "A"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("**", "anything"));
+        assert!(glob_match("a*b", "aXbXb"));
+        assert!(!glob_match("a*b", "aXbX"));
+        assert!(glob_match("*LL", "egll"));
+        assert!(glob_match("E*G*L", "EGLL"));
+        assert!(!glob_match("EGL", "EGLL"));
+        assert!(glob_match("Ö*", "östersund"));
+    }
+
+    #[test]
+    fn display_quotes_values_that_need_it() {
+        let text = |value| predicate(Field::Name, Operator::Equal, value).to_string();
+        assert_eq!(text("Smith"), "name=Smith");
+        assert_eq!(text(""), "name=\"\"");
+        assert_eq!(text("A Smith"), "name=\"A Smith\"");
+        assert_eq!(text("A(B)"), "name=\"A(B)\"");
+        assert_eq!(text("x=y"), "name=\"x=y\"");
+        assert_eq!(text("say \"hi\""), r#"name="say \"hi\"""#);
+        assert_eq!(text("a\\b"), "name=a\\b");
+        assert_eq!(text("a\\b c"), r#"name="a\\b c""#);
+    }
+
+    #[test]
+    fn predicate_round_trips() {
+        for value in [
+            "Smith", "", " ", "A Smith", "(x)", "a=b", "a!b", "<>", "\"", "\\", "\\\"", "end\\",
+            "and", "o'r", "Jürgen",
+        ] {
+            let predicate = predicate(Field::Name, Operator::NotEqual, value);
+            let text = predicate.to_string();
+            assert_eq!(
+                Query::parse(&text),
+                Ok(Query::Predicate(predicate)),
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn filter_round_trips() {
+        let filter = Filter {
+            predicates: vec![
+                predicate(Field::Departure, Operator::Equal, "EG**"),
+                predicate(Field::Altitude, Operator::Greater, "30000"),
+                predicate(Field::Name, Operator::Equal, "\"Ace\" (retired)"),
+            ],
+        };
+        let text = Query::from(filter.clone()).to_string();
+        assert_eq!(Query::parse(&text).unwrap().as_filter(), Some(filter));
+    }
+
+    #[test]
+    fn builder_skips_empty_rows_and_keeps_errors() {
+        let mut builder = FilterBuilder::new(&Filter::default());
+        assert_eq!(builder.rows.len(), 1);
+        for c in "BAW*".chars() {
+            builder.input(c);
+        }
+        builder.add_row();
+        assert_eq!(
+            builder.build().unwrap().predicates,
+            [predicate(Field::Callsign, Operator::Equal, "BAW*")]
+        );
+        builder.rows[1] = predicate(Field::Altitude, Operator::Equal, "high");
+        assert!(builder.build().is_err());
+        assert!(builder.error.is_some());
+        builder.remove_row();
+        assert!(builder.build().is_ok());
+        assert_eq!(builder.error, None);
+    }
+
+    fn with_rules(rules: &str) -> Pilot {
+        Pilot {
+            flight_plan: Some(FlightPlan {
+                flight_rules: rules.to_owned(),
+                ..testing::flight_plan("EGLL", "EGKK")
+            }),
+            ..testing::pilot("GABCD")
+        }
+    }
+
+    #[test]
+    fn rules_filter_cycles_back_to_all() {
+        let mut filter = RulesFilter::default();
+        let mut labels = Vec::new();
+        for _ in 0..4 {
+            filter = filter.next();
+            labels.push(filter.label());
+        }
+        assert_eq!(labels, ["IFR only", "VFR only", "No plan", "All"]);
+    }
+
+    #[test]
+    fn rules_filter_matches() {
+        let ifr = with_rules("I");
+        let no_plan = testing::pilot("N123AB");
+        assert!(RulesFilter::All.matches(&ifr) && RulesFilter::All.matches(&no_plan));
+        assert!(RulesFilter::Ifr.matches(&ifr));
+        for vfr in ["V", "S", "D"] {
+            assert!(RulesFilter::Vfr.matches(&with_rules(vfr)), "{}", vfr);
+            assert!(!RulesFilter::Ifr.matches(&with_rules(vfr)), "{}", vfr);
+        }
+        assert!(!RulesFilter::Vfr.matches(&with_rules("Y")));
+        assert!(!RulesFilter::Ifr.matches(&no_plan));
+        assert!(RulesFilter::NoPlan.matches(&no_plan));
+        assert!(!RulesFilter::NoPlan.matches(&ifr));
+    }
+
+    #[test]
+    fn airline_filter_parses_codes() {
+        let airlines = AirlineFilter::parse(" dlh, BAW ,").unwrap();
+        assert_eq!(airlines.label(), "Airlines: DLH, BAW");
+        assert!(airlines.matches(&testing::pilot("DLH4AB")));
+        assert!(airlines.matches(&testing::pilot("baw12")));
+        assert!(!airlines.matches(&testing::pilot("BA123")));
+        assert!(!airlines.matches(&testing::pilot("AAL1")));
+        assert!(AirlineFilter::parse(" , ").is_err());
+        let error = AirlineFilter::parse("DLH,BA").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "'BA' is not a three-letter ICAO airline code"
+        );
+    }
+
+    #[test]
+    fn missing_atis_only_for_tower_approach_and_center() {
+        for (facility, expected) in [
+            (0, false),
+            (1, false),
+            (2, false),
+            (3, false),
+            (4, true),
+            (5, true),
+            (6, true),
+            (42, false),
+        ] {
+            let controller = testing::controller("EGLL_X", facility);
+            assert_eq!(missing_atis(&controller), expected, "facility {}", facility);
+        }
+        let with_atis = Controller {
+            text_atis: Some(vec!["Heathrow Tower".to_owned()]),
+            ..testing::controller("EGLL_TWR", 4)
+        };
+        assert!(!missing_atis(&with_atis));
+        // an empty ATIS was still published
+        let blank = Controller {
+            text_atis: Some(Vec::new()),
+            ..testing::controller("EGLL_TWR", 4)
+        };
+        assert!(!missing_atis(&blank));
+    }
+
+    #[test]
+    fn record_age_and_staleness() {
+        let data = models::parse_time("2022-08-01T12:30:00.1234567Z");
+        let updated = models::parse_time("2022-08-01T12:20:00Z");
+        let age = record_age(updated, data).unwrap();
+        assert_eq!(age.num_seconds(), 600);
+        assert_eq!(record_age(None, data), None);
+        assert_eq!(record_age(updated, None), None);
+
+        let five = Some(Duration::minutes(5));
+        assert!(is_stale(Some(age), five));
+        assert!(!is_stale(Some(Duration::minutes(5)), five));
+        assert!(!is_stale(Some(age), Some(Duration::minutes(15))));
+        // unknown ages and no threshold are never stale
+        assert!(!is_stale(None, five));
+        assert!(!is_stale(Some(age), None));
+    }
+
+    #[test]
+    fn range_around_a_controller_at_an_airport() {
+        let range = RangeFilter::around(&testing::controller("LHR_TWR", 4)).unwrap();
+        assert_eq!((range.latitude, range.longitude), (51.47, -0.45));
+        assert!((range.range_nm - 50.0).abs() < f64::EPSILON);
+        assert_eq!(range.label(), "Within 50 nm of LHR_TWR");
+
+        let mut pilot = testing::pilot("BAW123");
+        (pilot.latitude, pilot.longitude) = (51.47, -0.45);
+        assert!(range.distance_nm(&pilot) < 0.01);
+        assert!(range.matches(&pilot));
+        // about 49 and 51 nm north
+        pilot.latitude = 51.47 + 49.0 / 60.0;
+        assert!(range.matches(&pilot));
+        pilot.latitude = 51.47 + 51.0 / 60.0;
+        assert!(!range.matches(&pilot));
+    }
+
+    #[test]
+    fn no_range_without_a_position_or_range() {
+        let error = RangeFilter::around(&testing::controller("LON_S_CTR", 6)).unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("Can't tell where LON_S_CTR is"));
+        let blind = Controller {
+            visual_range: 0,
+            ..testing::controller("EGLL_TWR", 4)
+        };
+        assert!(RangeFilter::around(&blind).is_err());
+    }
+}
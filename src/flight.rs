@@ -0,0 +1,965 @@
+//! Estimates about a pilot's flight.
+
+use crate::{
+    airports, format, geo,
+    models::{FlightPlan, Pilot},
+};
+use chrono::{DateTime, Duration, NaiveTime, TimeZone, Timelike, Utc};
+use std::fmt;
+
+/// Pilots below this altitude aren't considered to be cruising.
+pub const MIN_CRUISE_ALTITUDE_FT: i64 = 10_000;
+/// Feet descended per nautical mile on a 3° descent path.
+const DESCENT_FT_PER_NM: f64 = 300.0;
+
+/// Where a cruising pilot is relative to their estimated top of descent.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TopOfDescent {
+    /// Distance in nautical miles until the descent should start.
+    Ahead(f64),
+    Passed,
+}
+
+impl TopOfDescent {
+    /// Short form for the table, like "84 nm".
+    #[must_use]
+    pub fn short(self) -> String {
+        match self {
+            TopOfDescent::Ahead(nm) => format!("{} nm", format::whole(nm)),
+            TopOfDescent::Passed => "past".to_owned(),
+        }
+    }
+}
+
+impl fmt::Display for TopOfDescent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TopOfDescent::Ahead(nm) => write!(f, "TOD in {} nm", format::whole(*nm)),
+            TopOfDescent::Passed => write!(f, "past TOD"),
+        }
+    }
+}
+
+/// Feet in a meter.
+const FEET_PER_METER: f64 = 3.280_84;
+/// Filed altitudes at or above this are nonsense.
+const MAX_FILED_ALTITUDE_FT: i64 = 100_000;
+
+/// Parse a flight plan's free-text cruise altitude into feet.
+///
+/// Handles flight levels ("FL350", "F350", "FL 350"), altitudes in
+/// hundreds of feet ("A045", bare "350"), feet ("35000", "10500ft"), and
+/// metric levels in tens of meters ("M0850", "S1130") or meters ("8500m").
+/// Anything else, like "VFR", is `None`.
+#[must_use]
+pub fn parse_filed_altitude(altitude: &str) -> Option<i64> {
+    let altitude: String = altitude
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_uppercase();
+    let number = |digits: &str| -> Option<i64> {
+        if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        digits.parse().ok()
+    };
+    #[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+    let meters = |meters: i64| (meters as f64 * FEET_PER_METER).round() as i64;
+    let feet = if let Some(hundreds) = altitude
+        .strip_prefix("FL")
+        .or_else(|| altitude.strip_prefix('F'))
+        .or_else(|| altitude.strip_prefix('A'))
+    {
+        number(hundreds)? * 100
+    } else if let Some(tens) = altitude
+        .strip_prefix('M')
+        .or_else(|| altitude.strip_prefix('S'))
+    {
+        meters(number(tens)? * 10)
+    } else if let Some(feet) = altitude.strip_suffix("FT") {
+        number(feet)?
+    } else if let Some(value) = altitude.strip_suffix('M') {
+        meters(number(value)?)
+    } else {
+        let value = number(&altitude)?;
+        // short numbers are hundreds of feet, like a flight level
+        if altitude.len() <= 3 {
+            value * 100
+        } else {
+            value
+        }
+    };
+    (feet > 0 && feet < MAX_FILED_ALTITUDE_FT).then_some(feet)
+}
+
+/// Altitudes at or above this are shown as flight levels.
+const FLIGHT_LEVEL_FROM_FT: i64 = 18_000;
+/// Altitude differences from the filed cruise past this are pointed out.
+const NOTABLE_ALTITUDE_DELTA_FT: i64 = 1_000;
+/// Speed differences from the filed TAS past this are pointed out.
+const NOTABLE_SPEED_DELTA_KT: i64 = 50;
+
+/// A filed value next to the current one, like
+/// "Filed FL350 / currently FL347 (\u{2212}300 ft)".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FiledComparison {
+    /// The filed and current values.
+    pub text: String,
+    /// How far the current value is from the filed one, like
+    /// "(\u{2212}300 ft)", if the filed value could be read.
+    pub delta: Option<String>,
+    /// Whether the difference is large enough to point out.
+    pub notable: bool,
+}
+
+impl FiledComparison {
+    /// Compare a current value to a filed one that may be missing, each
+    /// given as the number and how it's shown.
+    fn new(filed: Option<(i64, String)>, current: (i64, &str), unit: &str, threshold: i64) -> Self {
+        let Some((filed, filed_text)) = filed else {
+            return Self {
+                text: format!("filed: \u{2014} / {}", current.1),
+                delta: None,
+                notable: false,
+            };
+        };
+        let delta = current.0 - filed;
+        let sign = match delta.signum() {
+            1 => "+",
+            -1 => "\u{2212}",
+            _ => "",
+        };
+        Self {
+            text: format!("Filed {} / {}", filed_text, current.1),
+            delta: Some(format!(
+                "({}{} {})",
+                sign,
+                format::thousands(usize::try_from(delta.unsigned_abs()).unwrap_or_default()),
+                unit
+            )),
+            notable: delta.abs() > threshold,
+        }
+    }
+}
+
+/// An altitude like "FL347", or "9,500 ft" below the flight levels.
+///
+/// Altitudes below sea level, like at the Dead Sea, are "\u{2212}1,300 ft".
+fn altitude_text(feet: i64) -> String {
+    if feet >= FLIGHT_LEVEL_FROM_FT {
+        format!("FL{:03}", (feet + 50) / 100)
+    } else {
+        format!(
+            "{}{} ft",
+            if feet < 0 { "\u{2212}" } else { "" },
+            format::thousands(usize::try_from(feet.unsigned_abs()).unwrap_or_default())
+        )
+    }
+}
+
+/// Parse a flight plan's cruise true airspeed, like "450" or "N0450", in knots.
+fn parse_filed_tas(tas: &str) -> Option<i64> {
+    let tas = tas.trim();
+    let knots: i64 = tas.strip_prefix('N').unwrap_or(tas).parse().ok()?;
+    (knots > 0).then_some(knots)
+}
+
+/// Compare a pilot's altitude to their filed cruise altitude, like
+/// "Filed FL350 / currently FL347 (\u{2212}300 ft)".
+#[must_use]
+pub fn altitude_comparison(pilot: &Pilot) -> FiledComparison {
+    let filed = pilot
+        .flight_plan
+        .as_ref()
+        .and_then(|plan| parse_filed_altitude(&plan.altitude));
+    FiledComparison::new(
+        filed.map(|feet| (feet, altitude_text(feet))),
+        (
+            pilot.altitude,
+            &format!("currently {}", altitude_text(pilot.altitude)),
+        ),
+        "ft",
+        NOTABLE_ALTITUDE_DELTA_FT,
+    )
+}
+
+/// Compare a pilot's ground speed to their filed cruise true airspeed,
+/// like "Filed 450 kt TAS / GS 437 kt (\u{2212}13 kt)".
+#[must_use]
+pub fn speed_comparison(pilot: &Pilot) -> FiledComparison {
+    let filed = pilot
+        .flight_plan
+        .as_ref()
+        .and_then(|plan| parse_filed_tas(&plan.cruise_tas));
+    FiledComparison::new(
+        filed.map(|knots| (knots, format!("{} kt TAS", knots))),
+        (pilot.groundspeed, &format!("GS {} kt", pilot.groundspeed)),
+        "kt",
+        NOTABLE_SPEED_DELTA_KT,
+    )
+}
+
+/// Minutes in a day.
+const MINUTES_PER_DAY: i64 = 24 * 60;
+
+/// Minutes of fuel a flight should have left on landing, as a reserve.
+pub const FUEL_RESERVE_MINUTES: i64 = 45;
+
+/// Parse an "HHMM" flight plan value into hours and minutes.
+///
+/// "0000" is what's filed when nothing is given, so it's `None` along with
+/// anything that isn't four digits with the minutes under 60.
+fn parse_hhmm(value: &str) -> Option<(u32, u32)> {
+    let value = value.trim();
+    if value.len() != 4 || !value.chars().all(|c| c.is_ascii_digit()) || value == "0000" {
+        return None;
+    }
+    let minutes = value[2..].parse().ok()?;
+    (minutes < 60).then_some((value[..2].parse().ok()?, minutes))
+}
+
+/// Parse a flight plan's zulu departure time, like "1350".
+#[must_use]
+pub fn parse_deptime(deptime: &str) -> Option<NaiveTime> {
+    let (hours, minutes) = parse_hhmm(deptime)?;
+    NaiveTime::from_hms_opt(hours, minutes, 0)
+}
+
+/// Parse a flight plan's enroute or fuel time, like "0215".
+#[must_use]
+pub fn parse_filed_duration(value: &str) -> Option<Duration> {
+    let (hours, minutes) = parse_hhmm(value)?;
+    Some(Duration::minutes(i64::from(hours * 60 + minutes)))
+}
+
+/// How little fuel a flight filed for, compared to its enroute time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FuelWarning {
+    /// Less fuel than the enroute time.
+    Short,
+    /// Enough fuel for the flight, but less than the reserve on top.
+    Tight,
+}
+
+impl FuelWarning {
+    /// The warning for a margin of fuel over the enroute time, if it's
+    /// under the reserve.
+    #[must_use]
+    pub fn from_margin(margin: Duration) -> Option<Self> {
+        if margin < Duration::zero() {
+            Some(FuelWarning::Short)
+        } else if margin < Duration::minutes(FUEL_RESERVE_MINUTES) {
+            Some(FuelWarning::Tight)
+        } else {
+            None
+        }
+    }
+
+    /// Badge for the table.
+    #[must_use]
+    pub fn badge(self) -> &'static str {
+        match self {
+            FuelWarning::Short => "SHORT",
+            FuelWarning::Tight => "TIGHT",
+        }
+    }
+}
+
+/// Filed fuel time less the enroute time, if both were filed.
+#[must_use]
+pub fn fuel_margin(plan: &FlightPlan) -> Option<Duration> {
+    Some(parse_filed_duration(&plan.fuel_time)? - parse_filed_duration(&plan.enroute_time)?)
+}
+
+/// The warning for a flight plan's fuel, if it filed too little.
+pub fn fuel_warning(plan: &FlightPlan) -> Option<FuelWarning> {
+    fuel_margin(plan).and_then(FuelWarning::from_margin)
+}
+
+/// Minutes from `now` until a departure time, negative if it's passed.
+///
+/// Times are taken to be within 12 hours either way, so a departure at
+/// 2350 is 20 minutes ago at 0010, not 23 hours and 40 minutes from now.
+#[must_use]
+pub fn minutes_until(deptime: NaiveTime, now: NaiveTime) -> i64 {
+    let minutes = |time: NaiveTime| i64::from(time.hour() * 60 + time.minute());
+    let difference = (minutes(deptime) - minutes(now)).rem_euclid(MINUTES_PER_DAY);
+    if difference >= MINUTES_PER_DAY / 2 {
+        difference - MINUTES_PER_DAY
+    } else {
+        difference
+    }
+}
+
+/// When a departure time falls, taking it to be within 12 hours of `now`.
+#[must_use]
+pub fn departure_time(deptime: NaiveTime, now: DateTime<Utc>) -> DateTime<Utc> {
+    let day = (now + Duration::minutes(minutes_until(deptime, now.time())))
+        .naive_utc()
+        .date();
+    Utc.from_utc_datetime(&day.and_time(deptime))
+}
+
+/// Minutes until a pilot's filed departure time, if they filed one.
+#[must_use]
+pub fn minutes_until_departure(pilot: &Pilot, now: DateTime<Utc>) -> Option<i64> {
+    let plan = pilot.flight_plan.as_ref()?;
+    let deptime = parse_deptime(&plan.deptime)?;
+    Some(minutes_until(deptime, now.time()))
+}
+
+/// Estimate where a pilot is relative to their top of descent, using a
+/// 3° path from the filed cruise altitude to the arrival airport.
+///
+/// Pilots without a flight plan, a known arrival airport, or a cruise
+/// altitude, and pilots who aren't moving or are below
+/// [`MIN_CRUISE_ALTITUDE_FT`], have no estimate.
+#[must_use]
+pub fn top_of_descent(pilot: &Pilot) -> Option<TopOfDescent> {
+    if pilot.altitude < MIN_CRUISE_ALTITUDE_FT || pilot.groundspeed <= 0 {
+        return None;
+    }
+    let plan = pilot.flight_plan.as_ref()?;
+    let cruise = parse_filed_altitude(&plan.altitude)?;
+    let remaining = distance_to_arrival(pilot)?;
+    #[allow(clippy::cast_precision_loss)]
+    let descent = cruise as f64 / DESCENT_FT_PER_NM;
+    // anything that would round to "0 nm" counts as passed
+    let until = remaining - descent;
+    if until < 0.5 {
+        Some(TopOfDescent::Passed)
+    } else {
+        Some(TopOfDescent::Ahead(until))
+    }
+}
+
+/// Pilots slower than this are on the ground.
+const GROUND_SPEED_KTS: i64 = 50;
+
+/// Whether a pilot is on the ground, going by their speed.
+#[must_use]
+pub fn on_ground(pilot: &Pilot) -> bool {
+    pilot.groundspeed < GROUND_SPEED_KTS
+}
+
+/// Distance in nautical miles from a pilot to their filed arrival airport,
+/// if it's known.
+#[must_use]
+pub fn distance_to_arrival(pilot: &Pilot) -> Option<f64> {
+    let plan = pilot.flight_plan.as_ref()?;
+    let arrival = airports::by_icao(&plan.arrival)?;
+    Some(geo::distance_nm(
+        pilot.latitude,
+        pilot.longitude,
+        arrival.latitude,
+        arrival.longitude,
+    ))
+}
+
+/// When an airborne pilot would reach their arrival airport at their
+/// current ground speed, straight there.
+#[must_use]
+pub fn estimated_arrival(pilot: &Pilot, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    if on_ground(pilot) {
+        return None;
+    }
+    #[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+    let minutes = (distance_to_arrival(pilot)? / pilot.groundspeed as f64 * 60.0).round() as i64;
+    Some(now + Duration::minutes(minutes))
+}
+
+/// Slow pilots below this altitude are on the ground. Without field
+/// elevations, it's above the highest airports.
+const GROUND_BELOW_FT: i64 = 15_000;
+/// Vertical speed beyond which a pilot is climbing or descending.
+const LEVEL_TOLERANCE_FPM: f64 = 300.0;
+/// Pilots descending below this altitude, or level below their cruise
+/// altitude near the arrival, are on approach.
+const APPROACH_BELOW_FT: i64 = 10_000;
+/// Distance from the arrival within which a level pilot is on approach
+/// rather than leveled off on the climb.
+const APPROACH_WITHIN_NM: f64 = 40.0;
+/// Level pilots within this distance below their cruise altitude are cruising.
+const CRUISE_TOLERANCE_FT: i64 = 2_000;
+
+/// Phase of a pilot's flight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Boarding or taxiing.
+    Ground,
+    Climb,
+    Cruise,
+    Descent,
+    Approach,
+    /// Not enough information to tell.
+    Unknown,
+}
+
+impl Phase {
+    /// Phases that can be filtered on, in the order they're cycled through.
+    pub const FILTERABLE: [Phase; 5] = [
+        Phase::Ground,
+        Phase::Climb,
+        Phase::Cruise,
+        Phase::Descent,
+        Phase::Approach,
+    ];
+
+    /// Compact label for the table.
+    #[must_use]
+    pub fn short(self) -> &'static str {
+        match self {
+            Phase::Ground => "Gnd",
+            Phase::Climb => "Clb",
+            Phase::Cruise => "Crz",
+            Phase::Descent => "Des",
+            Phase::Approach => "App",
+            Phase::Unknown => "?",
+        }
+    }
+
+    /// Full name, for the table title.
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Phase::Ground => "Boarding/Taxi",
+            Phase::Climb => "Climb",
+            Phase::Cruise => "Cruise",
+            Phase::Descent => "Descent",
+            Phase::Approach => "Approach",
+            Phase::Unknown => "Unknown",
+        }
+    }
+}
+
+/// What's known about how a pilot is moving.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PilotKinematics {
+    pub groundspeed: i64,
+    pub altitude: i64,
+    /// Whether the pilot filed a flight plan.
+    pub has_flight_plan: bool,
+    /// Filed cruise altitude in feet, if it's a fixed level.
+    pub cruise_altitude: Option<i64>,
+    /// Vertical speed in feet per minute since the previous snapshot, if
+    /// the pilot was in it.
+    pub vertical_rate: Option<f64>,
+    /// Distance to the arrival airport, if it's known.
+    pub distance_to_arrival_nm: Option<f64>,
+}
+
+impl PilotKinematics {
+    /// Gather a pilot's kinematics, with the vertical rate from snapshots.
+    #[must_use]
+    pub fn new(pilot: &Pilot, vertical_rate: Option<f64>) -> Self {
+        let plan = pilot.flight_plan.as_ref();
+        Self {
+            groundspeed: pilot.groundspeed,
+            altitude: pilot.altitude,
+            has_flight_plan: plan.is_some(),
+            cruise_altitude: plan.and_then(|fp| parse_filed_altitude(&fp.altitude)),
+            vertical_rate,
+            distance_to_arrival_nm: distance_to_arrival(pilot),
+        }
+    }
+}
+
+/// Classify a pilot's phase of flight.
+///
+/// Without a flight plan or a previous snapshot to estimate the vertical
+/// rate from, the phase is [`Phase::Unknown`].
+///
+/// A level pilot below the filed altitude is taken to have leveled off on
+/// the climb, unless they're low and near the arrival.
+#[must_use]
+pub fn phase_of_flight(kinematics: &PilotKinematics) -> Phase {
+    let Some(vertical_rate) = kinematics.vertical_rate else {
+        return Phase::Unknown;
+    };
+    if !kinematics.has_flight_plan {
+        return Phase::Unknown;
+    }
+    if kinematics.groundspeed < GROUND_SPEED_KTS && kinematics.altitude < GROUND_BELOW_FT {
+        return Phase::Ground;
+    }
+    if vertical_rate > LEVEL_TOLERANCE_FPM {
+        return Phase::Climb;
+    }
+    let low = kinematics.altitude < APPROACH_BELOW_FT;
+    let distance = kinematics.distance_to_arrival_nm;
+    if vertical_rate < -LEVEL_TOLERANCE_FPM {
+        // descending low is approach, unless it's still far from the arrival
+        return if low && distance.is_none_or(|nm| nm <= APPROACH_WITHIN_NM) {
+            Phase::Approach
+        } else {
+            Phase::Descent
+        };
+    }
+    let below_cruise = kinematics
+        .cruise_altitude
+        .map(|cruise| kinematics.altitude < cruise - CRUISE_TOLERANCE_FT);
+    let near_arrival = distance.is_some_and(|nm| nm <= APPROACH_WITHIN_NM);
+    match below_cruise {
+        // level at the filed altitude, however low, like a VFR flight
+        Some(false) => Phase::Cruise,
+        _ if low && near_arrival => Phase::Approach,
+        // level well below the filed altitude, like a temporary restriction on the climb
+        Some(true) => Phase::Climb,
+        None => Phase::Cruise,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    /// KSFO's position in the airport database.
+    const KSFO: (f64, f64) = (37.62, -122.38);
+
+    /// A pilot bound for KSFO, `nm` due north of it at an altitude.
+    fn inbound(nm: f64, altitude: i64, filed: &str) -> Pilot {
+        let mut pilot = testing::flying("UAL1", "KSEA", "KSFO");
+        pilot.latitude = KSFO.0 + nm / 60.0;
+        pilot.longitude = KSFO.1;
+        pilot.altitude = altitude;
+        pilot.groundspeed = 450;
+        if let Some(plan) = &mut pilot.flight_plan {
+            plan.altitude = filed.to_owned();
+        }
+        pilot
+    }
+
+    fn ahead(tod: Option<TopOfDescent>) -> f64 {
+        match tod {
+            Some(TopOfDescent::Ahead(nm)) => nm,
+            other => panic!("expected a distance to TOD, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn top_of_descent_on_a_three_degree_path() {
+        // 35,000 ft takes about 117 nm to lose
+        let nm = ahead(top_of_descent(&inbound(200.0, 35_000, "FL350")));
+        assert!((nm - 83.3).abs() < 1.0, "{}", nm);
+        let nm = ahead(top_of_descent(&inbound(200.0, 35_000, "FL250")));
+        assert!((nm - 116.7).abs() < 1.0, "{}", nm);
+        assert_eq!(
+            top_of_descent(&inbound(100.0, 35_000, "FL350")),
+            Some(TopOfDescent::Passed)
+        );
+    }
+
+    #[test]
+    fn no_top_of_descent_without_what_it_needs() {
+        assert_eq!(top_of_descent(&inbound(200.0, 9_000, "FL350")), None);
+        assert_eq!(top_of_descent(&inbound(200.0, 35_000, "VFR")), None);
+        let mut parked = inbound(200.0, 35_000, "FL350");
+        parked.groundspeed = 0;
+        assert_eq!(top_of_descent(&parked), None);
+        let mut unknown = inbound(200.0, 35_000, "FL350");
+        if let Some(plan) = &mut unknown.flight_plan {
+            plan.arrival = "ZZZZ".to_owned();
+        }
+        assert_eq!(top_of_descent(&unknown), None);
+        let mut no_plan = inbound(200.0, 35_000, "FL350");
+        no_plan.flight_plan = None;
+        assert_eq!(top_of_descent(&no_plan), None);
+    }
+
+    #[test]
+    fn top_of_descent_to_the_nearest_mile() {
+        assert_eq!(TopOfDescent::Ahead(84.4).to_string(), "TOD in 84 nm");
+        assert_eq!(TopOfDescent::Ahead(83.6).short(), "84 nm");
+        assert_eq!(TopOfDescent::Passed.to_string(), "past TOD");
+        assert_eq!(TopOfDescent::Passed.short(), "past");
+        // less than half a mile to go would show as "0 nm"
+        let almost = 35_000.0 / DESCENT_FT_PER_NM + 0.4;
+        assert_eq!(
+            top_of_descent(&inbound(almost, 35_000, "FL350")),
+            Some(TopOfDescent::Passed)
+        );
+    }
+
+    #[test]
+    fn filed_altitudes_in_every_form() {
+        for (filed, feet) in [
+            ("FL350", Some(35_000)),
+            ("fl350", Some(35_000)),
+            ("F350", Some(35_000)),
+            ("FL 350", Some(35_000)),
+            ("35000", Some(35_000)),
+            ("350", Some(35_000)),
+            ("A045", Some(4_500)),
+            ("10500ft", Some(10_500)),
+            ("10500 FT", Some(10_500)),
+            ("M0850", Some(27_887)),
+            ("S1130", Some(37_073)),
+            ("8500m", Some(27_887)),
+            ("VFR", None),
+            ("", None),
+            ("0", None),
+            ("FL", None),
+            ("FL35O", None),
+            ("999999", None),
+        ] {
+            assert_eq!(parse_filed_altitude(filed), feet, "{:?}", filed);
+        }
+    }
+
+    #[test]
+    fn filed_altitude_corpus() {
+        // values seen in the data feed
+        for (filed, feet) in [
+            ("FL380", Some(38_000)),
+            ("FL050", Some(5_000)),
+            ("F080", Some(8_000)),
+            ("37000", Some(37_000)),
+            ("6000", Some(6_000)),
+            ("045", Some(4_500)),
+            ("90", Some(9_000)),
+            (" 24000 ", Some(24_000)),
+            ("FL 410", Some(41_000)),
+            ("A085", Some(8_500)),
+            ("a100", Some(10_000)),
+            ("1500FT", Some(1_500)),
+            ("M1010", Some(33_136)),
+            ("S0920", Some(30_184)),
+            ("VFR", None),
+            ("vfr", None),
+            ("OTP", None),
+            ("DCT", None),
+            ("FL3S0", None),
+            ("35,000", None),
+            ("-1000", None),
+            ("FL000", None),
+            ("FL1000", None),
+            ("M", None),
+            ("??", None),
+        ] {
+            assert_eq!(parse_filed_altitude(filed), feet, "{:?}", filed);
+        }
+    }
+
+    fn kinematics(groundspeed: i64, altitude: i64, vertical_rate: f64) -> PilotKinematics {
+        PilotKinematics {
+            groundspeed,
+            altitude,
+            has_flight_plan: true,
+            cruise_altitude: Some(35_000),
+            vertical_rate: Some(vertical_rate),
+            distance_to_arrival_nm: None,
+        }
+    }
+
+    #[test]
+    fn phases_of_a_flight() {
+        for (groundspeed, altitude, rate, distance, phase) in [
+            (0, 400, 0.0, Some(3_000.0), Phase::Ground),
+            (20, 400, 0.0, Some(3_000.0), Phase::Ground),
+            (160, 2_000, 2_000.0, Some(2_990.0), Phase::Climb),
+            // level off on departure, far from the arrival
+            (220, 3_000, 0.0, Some(2_980.0), Phase::Climb),
+            (450, 24_000, 1_500.0, Some(2_900.0), Phase::Climb),
+            (460, 35_000, 0.0, Some(1_500.0), Phase::Cruise),
+            (460, 34_000, -200.0, Some(1_500.0), Phase::Cruise),
+            (440, 30_000, -2_000.0, Some(100.0), Phase::Descent),
+            (300, 9_000, -800.0, Some(80.0), Phase::Descent),
+            (250, 9_000, -800.0, Some(30.0), Phase::Approach),
+            (180, 3_000, 0.0, Some(10.0), Phase::Approach),
+            (0, 20, 0.0, Some(0.5), Phase::Ground),
+            // too high to be on the ground, however slow
+            (40, 20_000, 0.0, Some(500.0), Phase::Climb),
+            // without a distance, only descending low is approach
+            (250, 9_000, -800.0, None, Phase::Approach),
+            (180, 3_000, 0.0, None, Phase::Climb),
+        ] {
+            let kinematics = PilotKinematics {
+                distance_to_arrival_nm: distance,
+                ..kinematics(groundspeed, altitude, rate)
+            };
+            assert_eq!(
+                phase_of_flight(&kinematics),
+                phase,
+                "{} kts at {} ft, {} fpm, {:?} nm out",
+                groundspeed,
+                altitude,
+                rate,
+                distance
+            );
+        }
+    }
+
+    #[test]
+    fn low_cruise_is_not_approach() {
+        for distance in [None, Some(200.0), Some(20.0)] {
+            let vfr = PilotKinematics {
+                cruise_altitude: Some(5_500),
+                distance_to_arrival_nm: distance,
+                ..kinematics(110, 5_500, 0.0)
+            };
+            assert_eq!(
+                phase_of_flight(&vfr),
+                Phase::Cruise,
+                "{:?} nm out",
+                distance
+            );
+        }
+        // below the filed altitude near the arrival
+        let descended = PilotKinematics {
+            cruise_altitude: Some(5_500),
+            distance_to_arrival_nm: Some(10.0),
+            ..kinematics(100, 2_500, 0.0)
+        };
+        assert_eq!(phase_of_flight(&descended), Phase::Approach);
+    }
+
+    #[test]
+    fn level_below_the_filed_altitude_is_still_climbing() {
+        assert_eq!(phase_of_flight(&kinematics(400, 24_000, 0.0)), Phase::Climb);
+        let unknown_cruise = PilotKinematics {
+            cruise_altitude: None,
+            ..kinematics(400, 24_000, 0.0)
+        };
+        assert_eq!(phase_of_flight(&unknown_cruise), Phase::Cruise);
+    }
+
+    #[test]
+    fn vertical_rate_thresholds() {
+        let at = |rate| phase_of_flight(&kinematics(450, 30_000, rate));
+        assert_eq!(at(LEVEL_TOLERANCE_FPM), Phase::Climb);
+        assert_eq!(at(LEVEL_TOLERANCE_FPM + 1.0), Phase::Climb);
+        assert_eq!(at(-LEVEL_TOLERANCE_FPM), Phase::Climb);
+        assert_eq!(at(-LEVEL_TOLERANCE_FPM - 1.0), Phase::Descent);
+    }
+
+    #[test]
+    fn unknown_without_a_flight_plan_or_previous_snapshot() {
+        let no_rate = PilotKinematics {
+            vertical_rate: None,
+            ..kinematics(460, 35_000, 0.0)
+        };
+        assert_eq!(phase_of_flight(&no_rate), Phase::Unknown);
+        let no_plan = PilotKinematics {
+            has_flight_plan: false,
+            cruise_altitude: None,
+            ..kinematics(460, 35_000, 0.0)
+        };
+        assert_eq!(phase_of_flight(&no_plan), Phase::Unknown);
+        assert_eq!(Phase::Unknown.short(), "?");
+    }
+
+    #[test]
+    fn kinematics_from_a_pilot() {
+        let mut pilot = testing::flying("BAW123", "EGLL", "KJFK");
+        pilot.groundspeed = 480;
+        pilot.altitude = 35_000;
+        assert_eq!(
+            PilotKinematics::new(&pilot, Some(-50.0)),
+            PilotKinematics {
+                vertical_rate: Some(-50.0),
+                distance_to_arrival_nm: distance_to_arrival(&pilot),
+                ..kinematics(480, 35_000, 0.0)
+            }
+        );
+
+        pilot.flight_plan = None;
+        let kinematics = PilotKinematics::new(&pilot, Some(-50.0));
+        assert!(!kinematics.has_flight_plan);
+        assert_eq!(kinematics.cruise_altitude, None);
+    }
+
+    /// A flight plan with filed fuel and enroute times.
+    fn filed(fuel_time: &str, enroute_time: &str) -> FlightPlan {
+        FlightPlan {
+            enroute_time: enroute_time.to_owned(),
+            fuel_time: fuel_time.to_owned(),
+            ..testing::flight_plan("EGLL", "KJFK")
+        }
+    }
+
+    #[test]
+    fn filed_durations() {
+        let minutes = |value| parse_filed_duration(value).map(|d| d.num_minutes());
+        assert_eq!(minutes("0215"), Some(135));
+        assert_eq!(minutes(" 0045 "), Some(45));
+        assert_eq!(minutes("0001"), Some(1));
+        // longer than a day is fine for a duration, not a departure time
+        assert_eq!(minutes("2630"), Some(26 * 60 + 30));
+        assert_eq!(parse_deptime("2630"), None);
+        for garbage in [
+            "0000", "", "215", "02:15", "0260", "02h15", "abcd", "-015", "00150",
+        ] {
+            assert_eq!(minutes(garbage), None, "{:?}", garbage);
+            assert_eq!(parse_deptime(garbage), None, "{:?}", garbage);
+        }
+    }
+
+    #[test]
+    fn fuel_warning_thresholds() {
+        let warning = |minutes| FuelWarning::from_margin(Duration::minutes(minutes));
+        assert_eq!(warning(120), None);
+        assert_eq!(warning(45), None);
+        assert_eq!(warning(44), Some(FuelWarning::Tight));
+        assert_eq!(warning(0), Some(FuelWarning::Tight));
+        assert_eq!(warning(-1), Some(FuelWarning::Short));
+        assert_eq!(FuelWarning::Tight.badge(), "TIGHT");
+        assert_eq!(FuelWarning::Short.badge(), "SHORT");
+    }
+
+    #[test]
+    fn fuel_margins_of_flight_plans() {
+        let margin = |fuel, enroute| fuel_margin(&filed(fuel, enroute)).map(|d| d.num_minutes());
+        assert_eq!(margin("0830", "0700"), Some(90));
+        assert_eq!(margin("0700", "0730"), Some(-30));
+        assert_eq!(margin("0000", "0700"), None);
+        assert_eq!(margin("0830", "garbage"), None);
+
+        assert_eq!(fuel_warning(&filed("0830", "0700")), None);
+        assert_eq!(
+            fuel_warning(&filed("0740", "0700")),
+            Some(FuelWarning::Tight)
+        );
+        assert_eq!(
+            fuel_warning(&filed("0700", "0730")),
+            Some(FuelWarning::Short)
+        );
+        assert_eq!(fuel_warning(&filed("", "0730")), None);
+    }
+
+    fn hhmm(hours: u32, minutes: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hours, minutes, 0).unwrap()
+    }
+
+    #[test]
+    fn deptimes_parse_as_zulu_times() {
+        assert_eq!(parse_deptime("1350"), Some(hhmm(13, 50)));
+        assert_eq!(parse_deptime(" 0005 "), Some(hhmm(0, 5)));
+        assert_eq!(parse_deptime("2359"), Some(hhmm(23, 59)));
+        for garbage in [
+            "0000", "", "135", "13500", "13:50", "1360", "2400", "12a0", "-130",
+        ] {
+            assert_eq!(parse_deptime(garbage), None, "{:?}", garbage);
+        }
+    }
+
+    #[test]
+    fn minutes_until_wraps_at_midnight() {
+        assert_eq!(minutes_until(hhmm(2, 0), hhmm(1, 0)), 60);
+        assert_eq!(minutes_until(hhmm(1, 0), hhmm(2, 0)), -60);
+        assert_eq!(minutes_until(hhmm(23, 50), hhmm(0, 10)), -20);
+        assert_eq!(minutes_until(hhmm(0, 10), hhmm(23, 50)), 20);
+        assert_eq!(minutes_until(hhmm(11, 59), hhmm(0, 0)), 719);
+        assert_eq!(minutes_until(hhmm(12, 0), hhmm(0, 0)), -720);
+        assert_eq!(minutes_until(hhmm(9, 0), hhmm(9, 0)), 0);
+    }
+
+    #[test]
+    fn departure_time_lands_on_the_right_day() {
+        let now = Utc.ymd(2022, 8, 1).and_hms(0, 10, 0);
+        assert_eq!(
+            departure_time(hhmm(23, 50), now),
+            Utc.ymd(2022, 7, 31).and_hms(23, 50, 0)
+        );
+        let now = Utc.ymd(2022, 8, 1).and_hms(23, 50, 0);
+        assert_eq!(
+            departure_time(hhmm(0, 10), now),
+            Utc.ymd(2022, 8, 2).and_hms(0, 10, 0)
+        );
+        assert_eq!(
+            departure_time(hhmm(20, 0), now),
+            Utc.ymd(2022, 8, 1).and_hms(20, 0, 0)
+        );
+    }
+
+    #[test]
+    fn minutes_until_a_pilots_departure() {
+        let now = Utc.ymd(2022, 8, 1).and_hms(12, 0, 0);
+        let mut pilot = testing::flying("BAW123", "EGLL", "KJFK");
+        // the test flight plan departs at 1300
+        assert_eq!(minutes_until_departure(&pilot, now), Some(60));
+        if let Some(plan) = &mut pilot.flight_plan {
+            plan.deptime = "0000".to_owned();
+        }
+        assert_eq!(minutes_until_departure(&pilot, now), None);
+        pilot.flight_plan = None;
+        assert_eq!(minutes_until_departure(&pilot, now), None);
+    }
+
+    /// A pilot at an altitude and ground speed, with a filed cruise
+    /// altitude and TAS.
+    fn cruising(altitude: i64, groundspeed: i64, filed_altitude: &str, filed_tas: &str) -> Pilot {
+        let mut pilot = testing::flying("UAL1", "KSEA", "KSFO");
+        pilot.altitude = altitude;
+        pilot.groundspeed = groundspeed;
+        if let Some(plan) = &mut pilot.flight_plan {
+            plan.altitude = filed_altitude.to_owned();
+            plan.cruise_tas = filed_tas.to_owned();
+        }
+        pilot
+    }
+
+    /// A comparison as one line, with "!" after a notable difference.
+    fn line(comparison: FiledComparison) -> String {
+        let mut line = comparison.text;
+        if let Some(delta) = comparison.delta {
+            line = format!("{} {}", line, delta);
+        }
+        if comparison.notable {
+            line.push_str(" !");
+        }
+        line
+    }
+
+    #[test]
+    fn altitude_compared_to_the_filed_cruise() {
+        let altitude = |pilot: &Pilot| line(altitude_comparison(pilot));
+        assert_eq!(
+            altitude(&cruising(34_720, 450, "FL350", "450")),
+            "Filed FL350 / currently FL347 (\u{2212}280 ft)"
+        );
+        assert_eq!(
+            altitude(&cruising(9_500, 250, "A080", "250")),
+            "Filed 8,000 ft / currently 9,500 ft (+1,500 ft) !"
+        );
+        // exactly at the threshold isn't notable
+        assert!(!altitude_comparison(&cruising(36_000, 450, "35000", "450")).notable);
+        assert!(altitude_comparison(&cruising(36_001, 450, "35000", "450")).notable);
+        assert_eq!(
+            altitude_comparison(&cruising(35_000, 450, "FL350", "450")).delta,
+            Some("(0 ft)".to_owned())
+        );
+    }
+
+    #[test]
+    fn missing_or_unreadable_filed_values() {
+        let vfr = cruising(-120, 0, "VFR", "");
+        assert_eq!(
+            line(altitude_comparison(&vfr)),
+            "filed: \u{2014} / currently \u{2212}120 ft"
+        );
+        assert_eq!(line(speed_comparison(&vfr)), "filed: \u{2014} / GS 0 kt");
+        let mut no_plan = testing::pilot("N123");
+        no_plan.groundspeed = 95;
+        assert_eq!(
+            speed_comparison(&no_plan).text,
+            "filed: \u{2014} / GS 95 kt"
+        );
+        assert_eq!(altitude_comparison(&no_plan).delta, None);
+    }
+
+    #[test]
+    fn speed_compared_to_the_filed_tas() {
+        assert_eq!(
+            line(speed_comparison(&cruising(35_000, 437, "FL350", "450"))),
+            "Filed 450 kt TAS / GS 437 kt (\u{2212}13 kt)"
+        );
+        assert_eq!(
+            line(speed_comparison(&cruising(35_000, 520, "FL350", "N0460"))),
+            "Filed 460 kt TAS / GS 520 kt (+60 kt) !"
+        );
+        assert!(!speed_comparison(&cruising(35_000, 500, "FL350", "450")).notable);
+        assert_eq!(speed_comparison(&cruising(0, 0, "FL350", "0")).delta, None);
+    }
+}
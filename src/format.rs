@@ -0,0 +1,637 @@
+//! Formatting helpers for values shown in the interface.
+
+use anyhow::{bail, Result};
+use chrono::{DateTime, FixedOffset, Local, Utc};
+use chrono_tz::Tz;
+use once_cell::sync::OnceCell;
+use std::fmt;
+
+/// Locale numbers and dates are formatted for, set once at startup.
+static LOCALE: OnceCell<Locale> = OnceCell::new();
+
+/// Time zone that times are shown in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DisplayZone {
+    /// The system's local time zone.
+    #[default]
+    Local,
+    /// Zulu time, marked with a "z".
+    Utc,
+    /// A fixed offset from UTC.
+    Offset(FixedOffset),
+    /// A zone from the IANA database, like "Europe/London", with its
+    /// daylight saving time.
+    Named(Tz),
+}
+
+impl DisplayZone {
+    /// Parse "local", "utc", an offset like "+05:30", "-0300", or "UTC+2",
+    /// or an IANA zone name like "Europe/London" in any case.
+    pub fn parse(value: &str) -> Result<Self> {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("local") {
+            return Ok(DisplayZone::Local);
+        }
+        if value.eq_ignore_ascii_case("utc") || value.eq_ignore_ascii_case("z") {
+            return Ok(DisplayZone::Utc);
+        }
+        if let Some(offset) = parse_offset(value) {
+            return Ok(DisplayZone::Offset(offset));
+        }
+        match Tz::from_str_insensitive(value) {
+            Ok(zone) => Ok(DisplayZone::Named(zone)),
+            Err(_) => bail!(
+                "Unknown time zone '{}', expected local, utc, an offset like +05:30, -0300, or UTC+2, or a zone like Europe/London",
+                value
+            ),
+        }
+    }
+}
+
+impl fmt::Display for DisplayZone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DisplayZone::Local => write!(f, "local"),
+            DisplayZone::Utc => write!(f, "UTC"),
+            DisplayZone::Offset(offset) => write!(f, "UTC{}", offset),
+            DisplayZone::Named(zone) => write!(f, "{}", zone.name()),
+        }
+    }
+}
+
+/// Parse a UTC offset like "+05:30", "-0300", or "UTC+2".
+fn parse_offset(value: &str) -> Option<FixedOffset> {
+    let value = value
+        .get(..3)
+        .filter(|prefix| prefix.eq_ignore_ascii_case("utc") || prefix.eq_ignore_ascii_case("gmt"))
+        .map_or(value, |_| &value[3..]);
+    let (sign, rest) = match value.as_bytes().first()? {
+        b'+' => (1, &value[1..]),
+        b'-' => (-1, &value[1..]),
+        _ => return None,
+    };
+    if rest.is_empty() || !rest.chars().all(|c| c.is_ascii_digit() || c == ':') {
+        return None;
+    }
+    let (hours, minutes) = match rest.split_once(':') {
+        Some((hours, minutes)) => (hours, minutes),
+        None if rest.len() > 2 => rest.split_at(rest.len() - 2),
+        None => (rest, "0"),
+    };
+    let hours: i32 = hours.parse().ok()?;
+    let minutes: i32 = minutes.parse().ok()?;
+    if hours > 14 || minutes >= 60 {
+        return None;
+    }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+/// Whether dates put the day or the month first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateOrder {
+    /// Like "16 Oct".
+    DayMonth,
+    /// Like "Oct 16".
+    MonthDay,
+}
+
+/// How numbers and dates are written: separators and date order only.
+///
+/// The default is how they were written before locales could be chosen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Locale {
+    /// Between groups of three digits, like the comma in "1,234".
+    pub thousands: char,
+    /// Before the fraction, like the point in "29.92".
+    pub decimal: char,
+    pub date_order: DateOrder,
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Self {
+            thousands: ',',
+            decimal: '.',
+            date_order: DateOrder::DayMonth,
+        }
+    }
+}
+
+impl Locale {
+    /// Parse a language tag like "de-DE" or "en_US.UTF-8", or "auto" to
+    /// use the environment's locale.
+    pub fn parse(value: &str) -> Result<Self> {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("auto") {
+            return Ok(Self::from_env());
+        }
+        let Some(locale) = Self::from_tag(value) else {
+            bail!(
+                "Unknown locale '{}', expected auto or a language tag like en-US or de-DE",
+                value
+            );
+        };
+        Ok(locale)
+    }
+
+    /// The locale for a language tag, if it looks like one.
+    ///
+    /// Languages that aren't known here get the default.
+    fn from_tag(tag: &str) -> Option<Self> {
+        let tag = tag.split(['.', '@']).next().unwrap_or_default();
+        if tag.eq_ignore_ascii_case("C") || tag.eq_ignore_ascii_case("POSIX") {
+            return Some(Self::default());
+        }
+        let mut parts = tag.split(['-', '_']);
+        let language = parts.next()?.to_lowercase();
+        let region = parts.next().unwrap_or_default().to_uppercase();
+        if !(2..=3).contains(&language.len()) || !language.chars().all(|c| c.is_ascii_alphabetic())
+        {
+            return None;
+        }
+        let (thousands, decimal) = match language.as_str() {
+            "de" | "nl" | "es" | "it" | "pt" | "da" | "id" | "tr" | "el" => ('.', ','),
+            "fr" | "ru" | "pl" | "sv" | "nb" | "no" | "fi" | "cs" | "sk" | "uk" | "hu" => {
+                (' ', ',')
+            }
+            _ => (',', '.'),
+        };
+        let date_order = if language == "en" && region == "US" {
+            DateOrder::MonthDay
+        } else {
+            DateOrder::DayMonth
+        };
+        Some(Self {
+            thousands,
+            decimal,
+            date_order,
+        })
+    }
+
+    /// A count with this locale's thousands separators.
+    fn group(self, n: usize) -> String {
+        let digits = n.to_string();
+        let mut formatted = String::with_capacity(digits.len() + digits.len() / 3);
+        for (i, c) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i) % 3 == 0 {
+                formatted.push(self.thousands);
+            }
+            formatted.push(c);
+        }
+        formatted
+    }
+
+    /// A number to some decimal places with this locale's decimal point.
+    fn point(self, value: f64, places: usize) -> String {
+        let formatted = format!("{:.*}", places, value);
+        match self.decimal {
+            '.' => formatted,
+            point => formatted.replace('.', &point.to_string()),
+        }
+    }
+
+    /// The `strftime` pattern for a day and time in this locale's order.
+    fn day_pattern(self) -> &'static str {
+        match self.date_order {
+            DateOrder::DayMonth => "%d %b %H:%M",
+            DateOrder::MonthDay => "%b %d %H:%M",
+        }
+    }
+
+    /// The locale from `LC_ALL`, `LC_NUMERIC`, or `LANG`, whichever is set
+    /// first, or the default.
+    #[must_use]
+    pub fn from_env() -> Self {
+        ["LC_ALL", "LC_NUMERIC", "LANG"]
+            .into_iter()
+            .filter_map(|name| std::env::var(name).ok())
+            .find(|value| !value.is_empty())
+            .and_then(|value| Self::from_tag(&value))
+            .unwrap_or_default()
+    }
+}
+
+/// Use a locale for all formatting from now on. Only the first call has
+/// an effect.
+pub fn set_locale(locale: Locale) {
+    let _ = LOCALE.set(locale);
+}
+
+/// The locale in use.
+fn locale() -> Locale {
+    LOCALE.get().copied().unwrap_or_default()
+}
+
+/// How much of a time to show.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeStyle {
+    /// Hours and minutes, like "12:05".
+    Clock,
+    /// Hours and minutes without a separator, like "1205".
+    Compact,
+    /// Day, month, hours, and minutes, like "16 Oct 12:05", in the
+    /// locale's date order.
+    Day,
+}
+
+/// Format a time in a zone. Times in UTC are marked with a "z", like "12:05z".
+#[must_use]
+pub fn format_time(time: DateTime<Utc>, zone: DisplayZone, style: TimeStyle) -> String {
+    let pattern = match style {
+        TimeStyle::Clock => "%H:%M",
+        TimeStyle::Compact => "%H%M",
+        TimeStyle::Day => locale().day_pattern(),
+    };
+    match zone {
+        DisplayZone::Local => time.with_timezone(&Local).format(pattern).to_string(),
+        DisplayZone::Utc => format!("{}z", time.format(pattern)),
+        DisplayZone::Offset(offset) => time.with_timezone(&offset).format(pattern).to_string(),
+        DisplayZone::Named(zone) => time.with_timezone(&zone).format(pattern).to_string(),
+    }
+}
+
+/// Format a time parsed from the API, or pass the raw text through if it
+/// didn't parse.
+#[must_use]
+pub fn time_or_raw(
+    time: Option<DateTime<Utc>>,
+    raw: &str,
+    zone: DisplayZone,
+    style: TimeStyle,
+) -> String {
+    time.map_or_else(|| raw.to_owned(), |time| format_time(time, zone, style))
+}
+
+/// Format a count with the locale's thousands separators, like "1,234".
+#[must_use]
+pub fn thousands(n: usize) -> String {
+    locale().group(n)
+}
+
+/// Format a number rounded to a whole one with the locale's thousands
+/// separators, like "2,991".
+#[must_use]
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+pub fn whole(value: f64) -> String {
+    let rounded = value.round();
+    let digits = thousands(rounded.abs() as usize);
+    if rounded < 0.0 {
+        format!("\u{2212}{}", digits)
+    } else {
+        digits
+    }
+}
+
+/// Format a number to some decimal places with the locale's decimal
+/// point, like "29.92".
+#[must_use]
+pub fn decimal(value: f64, places: usize) -> String {
+    locale().point(value, places)
+}
+
+/// Format a tab label with its row count.
+///
+/// When some rows are hidden, both counts are shown: "Pilots (12/1,234)".
+#[must_use]
+pub fn tab_label(name: &str, shown: usize, total: usize) -> String {
+    if shown == total {
+        format!("{} ({})", name, thousands(total))
+    } else {
+        format!("{} ({}/{})", name, thousands(shown), thousands(total))
+    }
+}
+
+/// Describe clients connecting and disconnecting, like "+12 / \u{2212}7 pilots".
+///
+/// Returns `None` if nobody connected or disconnected.
+#[must_use]
+pub fn connection_delta(joined: usize, left: usize, noun: &str) -> Option<String> {
+    if joined == 0 && left == 0 {
+        return None;
+    }
+    let plural = if joined.max(left) == 1 { "" } else { "s" };
+    Some(format!(
+        "+{} / \u{2212}{} {}{}",
+        thousands(joined),
+        thousands(left),
+        noun,
+        plural
+    ))
+}
+
+/// Spell out a flight plan's flight rules code, like "IFR" for "I".
+///
+/// Unexpected codes are passed through as-is.
+#[must_use]
+pub fn flight_rules(code: &str) -> &str {
+    match code {
+        "I" => "IFR",
+        "V" => "VFR",
+        "S" => "SVFR",
+        "D" => "DVFR",
+        other => other,
+    }
+}
+
+/// Format how long something has been going on, like "2h 05m".
+#[must_use]
+pub fn duration(duration: chrono::Duration) -> String {
+    let minutes = duration.num_minutes().max(0);
+    format!("{}h {:02}m", minutes / 60, minutes % 60)
+}
+
+/// Format how old something is, like "45s", "3m 10s", or "2h 05m".
+#[must_use]
+pub fn age(age: chrono::Duration) -> String {
+    let seconds = age.num_seconds().max(0);
+    if seconds < 60 {
+        format!("{}s", seconds)
+    } else if seconds < 60 * 60 {
+        format!("{}m {:02}s", seconds / 60, seconds % 60)
+    } else {
+        duration(age)
+    }
+}
+
+/// Cut text down to `width` characters, ending in an ellipsis if anything
+/// was cut.
+#[must_use]
+pub fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_owned();
+    }
+    let mut cut: String = text.chars().take(width.saturating_sub(1)).collect();
+    if width > 0 {
+        cut.push('\u{2026}');
+    }
+    cut
+}
+
+/// Wrap text at word boundaries so no line is longer than `width`.
+///
+/// Words longer than the width get a line to themselves.
+#[must_use]
+pub fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn thousands_separators() {
+        assert_eq!(thousands(0), "0");
+        assert_eq!(thousands(999), "999");
+        assert_eq!(thousands(1_000), "1,000");
+        assert_eq!(thousands(1_234_567), "1,234,567");
+    }
+
+    #[test]
+    fn whole_numbers_are_rounded_and_grouped() {
+        assert_eq!(whole(0.4), "0");
+        assert_eq!(whole(94.5), "95");
+        assert_eq!(whole(2_990.6), "2,991");
+        assert_eq!(whole(-1_234.4), "\u{2212}1,234");
+        assert_eq!(whole(-0.2), "0");
+    }
+
+    fn locale_for(tag: &str) -> Locale {
+        Locale::parse(tag).unwrap()
+    }
+
+    #[test]
+    fn locales_from_language_tags() {
+        assert_eq!(locale_for("en-GB"), Locale::default());
+        assert_eq!(locale_for("C"), Locale::default());
+        assert_eq!(locale_for("posix"), Locale::default());
+        assert_eq!(locale_for("xx"), Locale::default());
+        assert_eq!(
+            locale_for("en_US.UTF-8"),
+            Locale {
+                date_order: DateOrder::MonthDay,
+                ..Locale::default()
+            }
+        );
+        assert_eq!(
+            locale_for(" de-DE "),
+            Locale {
+                thousands: '.',
+                decimal: ',',
+                date_order: DateOrder::DayMonth,
+            }
+        );
+        assert_eq!(locale_for("fr_FR@euro"), locale_for("fr"));
+        assert_eq!(locale_for("fr").thousands, ' ');
+        for bad in ["", "12-34", "english", "e"] {
+            let error = Locale::parse(bad).unwrap_err().to_string();
+            assert!(error.starts_with("Unknown locale"), "{:?}: {}", bad, error);
+        }
+    }
+
+    #[test]
+    fn numbers_and_dates_in_each_locale() {
+        let time = crate::models::parse_time("2022-08-01T23:45:10Z").unwrap();
+        let cases = [
+            ("en-US", "1,234,567", "29.92", "Aug 01 23:45"),
+            ("en-GB", "1,234,567", "29.92", "01 Aug 23:45"),
+            ("de-DE", "1.234.567", "29,92", "01 Aug 23:45"),
+            ("fr-FR", "1 234 567", "29,92", "01 Aug 23:45"),
+        ];
+        for (tag, count, inches, day) in cases {
+            let locale = locale_for(tag);
+            assert_eq!(locale.group(1_234_567), count, "{}", tag);
+            assert_eq!(locale.group(999), "999", "{}", tag);
+            assert_eq!(locale.point(29.921, 2), inches, "{}", tag);
+            assert_eq!(
+                time.format(locale.day_pattern()).to_string(),
+                day,
+                "{}",
+                tag
+            );
+        }
+    }
+
+    #[test]
+    fn connection_deltas_are_pluralized() {
+        assert_eq!(connection_delta(0, 0, "pilot"), None);
+        assert_eq!(
+            connection_delta(1, 0, "pilot").as_deref(),
+            Some("+1 / \u{2212}0 pilot")
+        );
+        assert_eq!(
+            connection_delta(0, 1, "controller").as_deref(),
+            Some("+0 / \u{2212}1 controller")
+        );
+        assert_eq!(
+            connection_delta(12, 7, "pilot").as_deref(),
+            Some("+12 / \u{2212}7 pilots")
+        );
+        assert_eq!(
+            connection_delta(1_500, 2, "pilot").as_deref(),
+            Some("+1,500 / \u{2212}2 pilots")
+        );
+    }
+
+    #[test]
+    fn flight_rules_codes() {
+        assert_eq!(flight_rules("I"), "IFR");
+        assert_eq!(flight_rules("V"), "VFR");
+        assert_eq!(flight_rules("S"), "SVFR");
+        assert_eq!(flight_rules("D"), "DVFR");
+        assert_eq!(flight_rules("Y"), "Y");
+        assert_eq!(flight_rules(""), "");
+    }
+
+    #[test]
+    fn tab_labels_show_filtered_counts() {
+        assert_eq!(tab_label("Pilots", 1_234, 1_234), "Pilots (1,234)");
+        assert_eq!(tab_label("Pilots", 12, 1_234), "Pilots (12/1,234)");
+        assert_eq!(tab_label("Controllers", 0, 0), "Controllers (0)");
+    }
+
+    fn offset(value: &str) -> DisplayZone {
+        DisplayZone::parse(value).unwrap()
+    }
+
+    #[test]
+    fn parses_zones() {
+        assert_eq!(offset(" Local "), DisplayZone::Local);
+        assert_eq!(offset("UTC"), DisplayZone::Utc);
+        assert_eq!(offset("z"), DisplayZone::Utc);
+        let east = |seconds| DisplayZone::Offset(FixedOffset::east_opt(seconds).unwrap());
+        assert_eq!(offset("+05:30"), east(5 * 3600 + 30 * 60));
+        assert_eq!(offset("-0300"), east(-3 * 3600));
+        assert_eq!(offset("UTC+2"), east(2 * 3600));
+        assert_eq!(offset("gmt-9:30"), east(-(9 * 3600 + 30 * 60)));
+        assert_eq!(offset("+14").to_string(), "UTC+14:00");
+        for bad in ["+15", "+05:60", "5", "+", "UTC+x", "+-3"] {
+            assert!(DisplayZone::parse(bad).is_err(), "{:?}", bad);
+        }
+        assert_eq!(
+            offset("Europe/London"),
+            DisplayZone::Named(Tz::Europe__London)
+        );
+        assert_eq!(offset(" america/new_york ").to_string(), "America/New_York");
+        let error = DisplayZone::parse("Europe/Atlantis")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.starts_with("Unknown time zone 'Europe/Atlantis'"),
+            "{}",
+            error
+        );
+        assert!(error.contains("+05:30, -0300, or UTC+2"), "{}", error);
+    }
+
+    #[test]
+    fn named_zones_follow_daylight_saving_time() {
+        let london = offset("Europe/London");
+        // the clocks go forward at 01:00 UTC on the last Sunday in March,
+        // and back at 01:00 UTC on the last Sunday in October
+        for (raw, shown) in [
+            ("2022-03-27T00:30:00Z", "00:30"),
+            ("2022-03-27T01:30:00Z", "02:30"),
+            ("2022-10-30T00:30:00Z", "01:30"),
+            ("2022-10-30T01:30:00Z", "01:30"),
+        ] {
+            let time = crate::models::parse_time(raw).unwrap();
+            assert_eq!(
+                format_time(time, london, TimeStyle::Clock),
+                shown,
+                "{}",
+                raw
+            );
+        }
+        // and across the date line the other way round
+        let sydney = offset("Australia/Sydney");
+        let time = crate::models::parse_time("2022-04-02T15:30:00Z").unwrap();
+        assert_eq!(format_time(time, sydney, TimeStyle::Day), "03 Apr 02:30");
+        let time = crate::models::parse_time("2022-04-02T16:30:00Z").unwrap();
+        assert_eq!(format_time(time, sydney, TimeStyle::Day), "03 Apr 02:30");
+    }
+
+    #[test]
+    fn formats_api_times_in_a_zone() {
+        let time = crate::models::parse_time("2022-08-01T23:45:10.1234567Z").unwrap();
+        assert_eq!(
+            format_time(time, DisplayZone::Utc, TimeStyle::Clock),
+            "23:45z"
+        );
+        assert_eq!(
+            format_time(time, DisplayZone::Utc, TimeStyle::Compact),
+            "2345z"
+        );
+        assert_eq!(
+            format_time(time, DisplayZone::Utc, TimeStyle::Day),
+            "01 Aug 23:45z"
+        );
+        // offsets move the date across midnight in both directions
+        assert_eq!(
+            format_time(time, offset("+05:30"), TimeStyle::Day),
+            "02 Aug 05:15"
+        );
+        let early = crate::models::parse_time("2022-08-01T01:00:00Z").unwrap();
+        assert_eq!(
+            format_time(early, offset("UTC-3"), TimeStyle::Day),
+            "31 Jul 22:00"
+        );
+    }
+
+    #[test]
+    fn local_times_follow_the_system_offset() {
+        // the days either side of a DST change have different offsets in
+        // zones that observe it; each is formatted with its own
+        for raw in [
+            "2022-03-27T00:30:00Z",
+            "2022-03-27T01:30:00Z",
+            "2022-10-30T00:30:00Z",
+        ] {
+            let time = crate::models::parse_time(raw).unwrap();
+            assert_eq!(
+                format_time(time, DisplayZone::Local, TimeStyle::Clock),
+                time.with_timezone(&Local).format("%H:%M").to_string()
+            );
+        }
+    }
+
+    #[test]
+    fn unparsed_times_pass_through() {
+        assert_eq!(
+            time_or_raw(None, "garbled", DisplayZone::Utc, TimeStyle::Clock),
+            "garbled"
+        );
+    }
+
+    #[test]
+    fn truncating_to_a_width() {
+        assert_eq!(truncate("timed out", 20), "timed out");
+        assert_eq!(truncate("timed out", 9), "timed out");
+        assert_eq!(truncate("timed out", 8), "timed o\u{2026}");
+        assert_eq!(truncate("\u{e9}\u{e9}\u{e9}", 2), "\u{e9}\u{2026}");
+        assert_eq!(truncate("timed out", 1), "\u{2026}");
+        assert_eq!(truncate("timed out", 0), "");
+    }
+
+    #[test]
+    fn wrapping_narrow() {
+        assert_eq!(wrap("a bb ccc", 4), ["a bb", "ccc"]);
+        assert_eq!(wrap("abcdefgh ij", 4), ["abcdefgh", "ij"]);
+        assert_eq!(wrap("a b", 0), ["a", "b"]);
+        assert_eq!(wrap("  ", 10), Vec::<String>::new());
+    }
+}
@@ -0,0 +1,193 @@
+//! Radio frequency parsing and channel matching.
+
+use std::ops::RangeInclusive;
+
+/// Lowest frequency accepted, in kHz.
+const MIN_KHZ: u32 = 100_000;
+/// Highest frequency accepted, in kHz. VATSIM uses 199.998 for observers.
+const MAX_KHZ: u32 = 199_999;
+/// Channels closer than half the 8.33 kHz spacing are the same channel, in Hz.
+const SAME_CHANNEL_HZ: u32 = 4_167;
+/// The VHF airband controllers are expected on, in kHz.
+const AIRBAND_KHZ: RangeInclusive<u32> = 118_000..=136_975;
+
+/// Parse a frequency into kHz.
+///
+/// Accepts MHz with any number of decimals up to three ("124.35",
+/// "124.350"), and the digits without the decimal point ("12435",
+/// "124350").
+pub fn parse_khz(text: &str) -> Option<u32> {
+    let text = text.trim();
+    let all_digits = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_digit());
+    let khz = match text.split_once('.') {
+        Some((mhz, fraction)) => {
+            if mhz.len() != 3 || fraction.len() > 3 || !all_digits(mhz) || !all_digits(fraction) {
+                return None;
+            }
+            let fraction = format!("{:0<3}", fraction);
+            mhz.parse::<u32>().ok()? * 1000 + fraction.parse::<u32>().ok()?
+        }
+        None if all_digits(text) => {
+            let digits: u32 = text.parse().ok()?;
+            match text.len() {
+                3 => digits * 1000,
+                4 => digits * 100,
+                5 => digits * 10,
+                6 => digits,
+                _ => return None,
+            }
+        }
+        None => return None,
+    };
+    (MIN_KHZ..=MAX_KHZ).contains(&khz).then_some(khz)
+}
+
+/// Parse a frequency from the feed into kHz.
+///
+/// Besides what [`parse_khz`] accepts, the feed has had too few digits
+/// ("99.998"), too few decimals ("122.80"), and scientific notation
+/// ("1.99998e2"), so any positive number of MHz is rounded to the kHz.
+pub fn parse_feed(text: &str) -> Option<u32> {
+    let text = text.trim();
+    if let Some(khz) = parse_khz(text) {
+        return Some(khz);
+    }
+    let mhz: f64 = text.parse().ok()?;
+    if !mhz.is_finite() || mhz <= 0.0 || mhz >= 1_000.0 {
+        return None;
+    }
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let khz = (mhz * 1000.0).round() as u32;
+    (khz > 0).then_some(khz)
+}
+
+/// Whether a frequency in kHz is in the VHF airband.
+pub fn in_airband(khz: u32) -> bool {
+    AIRBAND_KHZ.contains(&khz)
+}
+
+/// A frequency from the feed, formatted like "122.800" if it parses, or as
+/// it was otherwise.
+pub fn display(text: &str) -> String {
+    parse_feed(text).map_or_else(|| text.trim().to_owned(), format_khz)
+}
+
+/// Whether a frequency from the feed is one to dim: unparsable or outside
+/// the airband, like the 199.998 placeholder observers log on with.
+pub fn is_unusual(text: &str) -> bool {
+    !parse_feed(text).is_some_and(in_airband)
+}
+
+/// The frequency tuned for a channel name, in Hz.
+///
+/// With 8.33 kHz spacing, channel names don't match the frequency:
+/// "118.005" is 118.000 MHz, "118.010" is 118.00833 MHz, and "118.015"
+/// is 118.01667 MHz. Names on the 25 kHz raster are the frequency.
+pub fn channel_hz(khz: u32) -> u32 {
+    let block = khz - khz % 25;
+    let offset_hz = match khz % 25 {
+        0 | 5 => 0,
+        10 => 8_333,
+        15 => 16_667,
+        other => other * 1000,
+    };
+    block * 1000 + offset_hz
+}
+
+/// Whether two channel names, in kHz, tune the same frequency.
+pub fn same_channel(a_khz: u32, b_khz: u32) -> bool {
+    channel_hz(a_khz).abs_diff(channel_hz(b_khz)) < SAME_CHANNEL_HZ
+}
+
+/// Format a frequency in kHz as MHz, like "124.350".
+pub fn format_khz(khz: u32) -> String {
+    format!("{}.{:03}", khz / 1000, khz % 1000)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn typed_frequencies_in_every_form() {
+        for text in ["124.35", "124.350", " 124.350 ", "12435", "124350"] {
+            assert_eq!(parse_khz(text), Some(124_350), "{:?}", text);
+        }
+        assert_eq!(parse_khz("124"), Some(124_000));
+        assert_eq!(parse_khz("1243"), Some(124_300));
+        assert_eq!(parse_khz("124.3"), Some(124_300));
+    }
+
+    #[test]
+    fn typed_garbage_is_rejected() {
+        for text in [
+            "", "124.", ".350", "124.3500", "24.350", "1243500", "abc", "124,35", "99.998",
+            "200.000", "-124.35",
+        ] {
+            assert_eq!(parse_khz(text), None, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn feed_frequencies_are_lenient() {
+        assert_eq!(parse_feed("122.800"), Some(122_800));
+        assert_eq!(parse_feed("122.80"), Some(122_800));
+        assert_eq!(parse_feed("99.998"), Some(99_998));
+        assert_eq!(parse_feed("1.99998e2"), Some(199_998));
+        assert_eq!(parse_feed("+122.8"), Some(122_800));
+        assert_eq!(parse_feed("122.8000"), Some(122_800));
+        assert_eq!(parse_feed("121.7999999"), Some(121_800));
+        assert_eq!(parse_feed(" 199.998\n"), Some(199_998));
+        assert_eq!(parse_feed("1e3"), None);
+        assert_eq!(parse_feed("-122.8"), None);
+        assert_eq!(parse_feed("122,800"), None);
+        assert_eq!(parse_feed("122.800 MHz"), None);
+        assert_eq!(parse_feed("0.0001"), None);
+        assert_eq!(parse_feed("0"), None);
+        assert_eq!(parse_feed("NaN"), None);
+        assert_eq!(parse_feed("inf"), None);
+        assert_eq!(parse_feed(""), None);
+    }
+
+    #[test]
+    fn eight_point_three_three_channel_names() {
+        assert_eq!(channel_hz(118_000), 118_000_000);
+        assert_eq!(channel_hz(118_005), 118_000_000);
+        assert_eq!(channel_hz(118_010), 118_008_333);
+        assert_eq!(channel_hz(118_015), 118_016_667);
+        assert_eq!(channel_hz(118_025), 118_025_000);
+        assert_eq!(channel_hz(118_030), 118_025_000);
+        assert_eq!(channel_hz(118_035), 118_033_333);
+        assert_eq!(channel_hz(118_040), 118_041_667);
+    }
+
+    #[test]
+    fn same_channel_across_spacings() {
+        // a 25 kHz name and the 8.33 kHz name for the same frequency
+        assert!(same_channel(124_350, 124_355));
+        assert!(same_channel(118_025, 118_030));
+        assert!(same_channel(124_350, 124_350));
+        // neighboring 8.33 kHz channels
+        assert!(!same_channel(118_005, 118_010));
+        assert!(!same_channel(118_010, 118_015));
+        assert!(!same_channel(124_350, 124_375));
+    }
+
+    #[test]
+    fn display_and_airband() {
+        assert_eq!(format_khz(124_350), "124.350");
+        assert_eq!(display("122.8"), "122.800");
+        assert_eq!(display(" n/a "), "n/a");
+        assert!(!is_unusual("118.500"));
+        assert!(is_unusual("199.998"));
+        assert!(is_unusual("117.950"));
+        assert!(is_unusual("n/a"));
+        assert!(is_unusual("99.998"));
+        assert!(is_unusual("1.99998e2"));
+        assert!(!is_unusual("122.80"));
+        assert_eq!(display("1.99998e2"), "199.998");
+        assert_eq!(display("99.998"), "99.998");
+        assert!(in_airband(136_975));
+        assert!(!in_airband(137_000));
+    }
+}
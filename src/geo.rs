@@ -0,0 +1,227 @@
+//! Geographic calculations.
+
+use crate::models::Pilot;
+use std::collections::HashMap;
+
+/// Mean radius of the Earth in nautical miles.
+const EARTH_RADIUS_NM: f64 = 3440.065;
+
+/// Great-circle distance in nautical miles between two points in degrees.
+#[must_use]
+pub fn distance_nm(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let (lat1, lat2) = (lat1.to_radians(), lat2.to_radians());
+    let d_lat = lat2 - lat1;
+    let d_lon = (lon2 - lon1).to_radians();
+    let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_NM * a.sqrt().asin()
+}
+
+/// Points bucketed into one-degree cells, for finding the ones near a
+/// position without measuring the distance to every one.
+#[derive(Debug, Clone, Default)]
+pub struct GridIndex {
+    /// Each point's ID, latitude, and longitude.
+    points: Vec<(usize, f64, f64)>,
+    /// Positions in `points` of the points in each cell.
+    cells: HashMap<(i32, i32), Vec<usize>>,
+}
+
+/// The cell a point is in: degrees north of the south pole, and east of
+/// the antimeridian.
+#[allow(clippy::cast_possible_truncation)]
+fn cell(latitude: f64, longitude: f64) -> (i32, i32) {
+    let row = ((latitude + 90.0).floor() as i32).clamp(0, 179);
+    let column = ((longitude + 180.0).floor() as i32).rem_euclid(360);
+    (row, column)
+}
+
+impl GridIndex {
+    /// Index points given as an ID, latitude, and longitude.
+    pub fn new(points: impl IntoIterator<Item = (usize, f64, f64)>) -> Self {
+        let points: Vec<(usize, f64, f64)> = points.into_iter().collect();
+        let mut cells: HashMap<(i32, i32), Vec<usize>> = HashMap::new();
+        for (position, &(_, latitude, longitude)) in points.iter().enumerate() {
+            cells
+                .entry(cell(latitude, longitude))
+                .or_default()
+                .push(position);
+        }
+        Self { points, cells }
+    }
+
+    /// IDs of the points within `radius_nm` of a position, in ascending order.
+    #[must_use]
+    pub fn within(&self, latitude: f64, longitude: f64, radius_nm: f64) -> Vec<usize> {
+        let mut ids: Vec<usize> = self
+            .candidates(latitude, longitude, radius_nm)
+            .into_iter()
+            .map(|position| self.points[position])
+            .filter(|&(_, lat, lon)| distance_nm(latitude, longitude, lat, lon) <= radius_nm)
+            .map(|(id, _, _)| id)
+            .collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    /// Positions in `points` of the points that might be within `radius_nm`
+    /// of a position: every point in a cell the radius reaches into.
+    #[allow(clippy::cast_possible_truncation)]
+    fn candidates(&self, latitude: f64, longitude: f64, radius_nm: f64) -> Vec<usize> {
+        // a degree of latitude is 60 nm; degrees of longitude shrink toward the poles
+        let lat_span = radius_nm / 60.0;
+        let (south, _) = cell((latitude - lat_span).max(-90.0), longitude);
+        let (north, _) = cell((latitude + lat_span).min(90.0), longitude);
+        let widest = latitude.abs() + lat_span;
+        let lon_span = if widest >= 89.0 {
+            360.0
+        } else {
+            lat_span / widest.to_radians().cos()
+        };
+        let (_, west) = cell(latitude, longitude - lon_span);
+        let columns: Vec<i32> = if lon_span >= 180.0 {
+            (0..360).collect()
+        } else {
+            let count = ((lon_span * 2.0).ceil() as i32 + 1).min(359);
+            (0..=count)
+                .map(|offset| (west + offset).rem_euclid(360))
+                .collect()
+        };
+        let mut found = Vec::new();
+        for row in south..=north {
+            for &column in &columns {
+                if let Some(positions) = self.cells.get(&(row, column)) {
+                    found.extend_from_slice(positions);
+                }
+            }
+        }
+        found
+    }
+}
+
+/// Index pilots by their position in a list.
+#[must_use]
+pub fn pilot_index(pilots: &[Pilot]) -> GridIndex {
+    GridIndex::new(
+        pilots
+            .iter()
+            .enumerate()
+            .map(|(i, p)| (i, p.latitude, p.longitude)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// IDs of the points within `radius_nm`, measuring every one.
+    fn brute_force(points: &[(usize, f64, f64)], lat: f64, lon: f64, radius_nm: f64) -> Vec<usize> {
+        points
+            .iter()
+            .filter(|&&(_, plat, plon)| distance_nm(lat, lon, plat, plon) <= radius_nm)
+            .map(|&(id, _, _)| id)
+            .collect()
+    }
+
+    #[test]
+    fn distances_between_airports() {
+        // KSFO to KLAX, and EGLL to KJFK
+        let sfo_lax = distance_nm(37.62, -122.38, 33.94, -118.41);
+        assert!((sfo_lax - 293.0).abs() < 2.0, "{}", sfo_lax);
+        let lhr_jfk = distance_nm(51.47, -0.45, 40.64, -73.78);
+        assert!((lhr_jfk - 2_991.0).abs() < 5.0, "{}", lhr_jfk);
+        assert!(distance_nm(10.0, 179.9, 10.0, -179.9) < 15.0);
+        assert!(distance_nm(51.0, 0.0, 51.0, 0.0).abs() < f64::EPSILON);
+    }
+
+    /// A fixed spread of points over the whole globe, denser near the poles
+    /// and the antimeridian where the cells wrap and narrow.
+    fn spread(count: usize) -> Vec<(usize, f64, f64)> {
+        let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = || {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            #[allow(clippy::cast_precision_loss)]
+            let unit = (seed % 1_000_000) as f64 / 1_000_000.0;
+            unit
+        };
+        (0..count)
+            .map(|id| {
+                let (lat, lon) = match id % 3 {
+                    0 => (next() * 180.0 - 90.0, next() * 360.0 - 180.0),
+                    1 => (85.0 + next() * 5.0, next() * 360.0 - 180.0),
+                    _ => (next() * 40.0 - 20.0, 178.0 + next() * 4.0),
+                };
+                let lon = if lon > 180.0 { lon - 360.0 } else { lon };
+                (id, lat, lon)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn grid_finds_what_measuring_every_point_finds() {
+        let points = spread(3_000);
+        let index = GridIndex::new(points.clone());
+        let searches = [
+            (37.62, -122.38, 40.0),
+            (51.47, -0.45, 250.0),
+            (0.0, 179.9, 120.0),
+            (-10.0, -179.5, 300.0),
+            (88.5, 10.0, 200.0),
+            (89.9, -170.0, 30.0),
+            (-89.0, 45.0, 150.0),
+            (60.0, 0.0, 1_500.0),
+            (0.0, 0.0, 0.0),
+        ];
+        for (lat, lon, radius) in searches {
+            assert_eq!(
+                index.within(lat, lon, radius),
+                brute_force(&points, lat, lon, radius),
+                "{} {} {}",
+                lat,
+                lon,
+                radius
+            );
+        }
+    }
+
+    #[test]
+    fn grid_handles_points_on_the_edges() {
+        let points = vec![(7, 90.0, 180.0), (3, -90.0, -180.0), (5, 0.0, 180.0)];
+        let index = GridIndex::new(points);
+        assert_eq!(index.within(89.9, 0.0, 10.0), [7]);
+        assert_eq!(index.within(-89.9, 100.0, 10.0), [3]);
+        assert_eq!(index.within(0.0, -179.95, 10.0), [5]);
+        assert!(GridIndex::default().within(0.0, 0.0, 10_000.0).is_empty());
+    }
+
+    /// How much faster the grid is than measuring every point, for 300
+    /// controllers looking for traffic among 1,500 pilots. Run it with
+    /// `--release`; hashing cells is slow in debug builds.
+    #[test]
+    #[ignore = "benchmark"]
+    fn bench_within() {
+        use std::time::Instant;
+
+        let pilots = spread(1_500);
+        let controllers: Vec<(f64, f64)> = spread(300)
+            .into_iter()
+            .map(|(_, lat, lon)| (lat, lon))
+            .collect();
+        let start = Instant::now();
+        let index = GridIndex::new(pilots.clone());
+        let mut found = 0;
+        for &(lat, lon) in &controllers {
+            found += index.within(lat, lon, 150.0).len();
+        }
+        let grid = start.elapsed();
+        let start = Instant::now();
+        let mut measured = 0;
+        for &(lat, lon) in &controllers {
+            measured += brute_force(&pilots, lat, lon, 150.0).len();
+        }
+        let every = start.elapsed();
+        assert_eq!(found, measured);
+        println!("grid: {:?}, every point: {:?}", grid, every);
+    }
+}
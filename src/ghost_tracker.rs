@@ -0,0 +1,122 @@
+//! Leaving the probable ghost connections out of counts and alerts, unless
+//! they're asked for.
+
+use crate::{
+    action::Action,
+    ghosts::Ghosts,
+    models::{Controller, Pilot, V3ResponseData},
+};
+use std::borrow::Cow;
+
+/// The ghosts in the data on screen, and whether they're counted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GhostTracker {
+    found: Ghosts,
+    /// Include the ghosts in counts and alerts.
+    counted: bool,
+}
+
+impl GhostTracker {
+    /// Keep the ghosts found in new data.
+    pub fn refresh(&mut self, ghosts: Ghosts) {
+        self.found = ghosts;
+    }
+
+    /// Apply an action, returning the status to show if it changed
+    /// whether ghosts are counted.
+    pub fn update(&mut self, action: Action) -> Option<&'static str> {
+        if action != Action::ToggleCountGhosts {
+            return None;
+        }
+        self.counted = !self.counted;
+        Some(if self.counted {
+            "Counting ghost connections"
+        } else {
+            "Not counting ghost connections"
+        })
+    }
+
+    pub fn has_pilot(&self, pilot: &Pilot) -> bool {
+        self.found.has_pilot(pilot)
+    }
+
+    pub fn has_controller(&self, controller: &Controller) -> bool {
+        self.found.has_controller(controller)
+    }
+
+    /// Whether counts include a pilot.
+    pub fn counts_pilot(&self, pilot: &Pilot) -> bool {
+        self.counted || !self.found.has_pilot(pilot)
+    }
+
+    /// Whether counts include a controller.
+    pub fn counts_controller(&self, controller: &Controller) -> bool {
+        self.counted || !self.found.has_controller(controller)
+    }
+
+    /// Numbers of pilots and controllers left out of counts.
+    pub fn uncounted(&self) -> (usize, usize) {
+        if self.counted {
+            (0, 0)
+        } else {
+            self.found.counts()
+        }
+    }
+
+    /// The data on screen and new data with the ghosts found in it, as
+    /// alerts compare them: as they are if ghosts are counted, or else each
+    /// without its own ghosts.
+    pub fn comparable<'a>(
+        &self,
+        old: &'a V3ResponseData,
+        new: &'a V3ResponseData,
+        new_ghosts: &Ghosts,
+    ) -> (Cow<'a, V3ResponseData>, Cow<'a, V3ResponseData>) {
+        if self.counted {
+            (Cow::Borrowed(old), Cow::Borrowed(new))
+        } else {
+            (self.found.without(old), new_ghosts.without(new))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    /// The same pilot connected twice, the second time more recently.
+    fn data() -> V3ResponseData {
+        let mut ghost = testing::pilot("BAW1");
+        ghost.last_updated = "2022-01-01T10:00:00Z".to_owned();
+        let mut primary = testing::pilot("BAW1A");
+        primary.last_updated = "2022-01-01T11:00:00Z".to_owned();
+        V3ResponseData {
+            pilots: vec![ghost, primary],
+            ..V3ResponseData::default()
+        }
+    }
+
+    #[test]
+    fn ghosts_count_only_when_asked() {
+        let data = data();
+        let mut tracker = GhostTracker::default();
+        tracker.refresh(Ghosts::find(&data));
+        assert!(tracker.has_pilot(&data.pilots[0]));
+        assert!(!tracker.counts_pilot(&data.pilots[0]));
+        assert!(tracker.counts_pilot(&data.pilots[1]));
+        assert_eq!(tracker.uncounted(), (1, 0));
+        let (old, new) = tracker.comparable(&data, &data, &Ghosts::default());
+        assert_eq!((old.pilots.len(), new.pilots.len()), (1, 2));
+
+        assert_eq!(tracker.update(Action::Quit), None);
+        assert_eq!(
+            tracker.update(Action::ToggleCountGhosts),
+            Some("Counting ghost connections")
+        );
+        assert!(tracker.counts_pilot(&data.pilots[0]));
+        assert_eq!(tracker.uncounted(), (0, 0));
+        let (old, _) = tracker.comparable(&data, &data, &Ghosts::default());
+        assert_eq!(old.pilots.len(), 2);
+    }
+}
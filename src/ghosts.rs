@@ -0,0 +1,277 @@
+//! Connections that share a CID with another, like one left behind after a crash.
+
+use crate::models::{Controller, Pilot, V3ResponseData};
+use chrono::{DateTime, Utc};
+use log::debug;
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+};
+
+/// The connections that share a CID with a more recently updated one, and
+/// so are probably ghosts.
+///
+/// Connections are told apart by callsign and update time, since a ghost
+/// left behind by a crash often has the same callsign as the reconnection.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Ghosts {
+    pilots: HashSet<(String, String)>,
+    controllers: HashSet<(String, String)>,
+}
+
+/// A record's CID, callsign, raw update time, and parsed update time.
+type Record<'a> = (i64, &'a str, &'a str, Option<DateTime<Utc>>);
+
+/// Callsigns and update times of all but the most recently updated record
+/// for each CID.
+///
+/// Records without a readable update time count as the oldest; ties go to
+/// the one listed first. Records that can't be told apart from the primary
+/// aren't marked, as that would mark the primary too.
+fn duplicates<'a>(
+    kind: &str,
+    records: impl Iterator<Item = Record<'a>>,
+) -> HashSet<(String, String)> {
+    let mut primaries: HashMap<i64, Record<'a>> = HashMap::new();
+    let mut ghosts = HashSet::new();
+    for record in records {
+        let Some(primary) = primaries.get_mut(&record.0) else {
+            let _ = primaries.insert(record.0, record);
+            continue;
+        };
+        let ghost = if record.3 > primary.3 {
+            std::mem::replace(primary, record)
+        } else {
+            record
+        };
+        if (ghost.1, ghost.2) == (primary.1, primary.2) {
+            continue;
+        }
+        debug!(
+            "CID {} is connected as more than one {}, treating {} as a ghost",
+            ghost.0, kind, ghost.1
+        );
+        let _ = ghosts.insert((ghost.1.to_owned(), ghost.2.to_owned()));
+    }
+    ghosts
+}
+
+/// Whether a record is in a set of ghosts.
+fn contains(ghosts: &HashSet<(String, String)>, callsign: &str, last_updated: &str) -> bool {
+    // no allocation for the common case of no ghosts
+    !ghosts.is_empty() && ghosts.contains(&(callsign.to_owned(), last_updated.to_owned()))
+}
+
+impl Ghosts {
+    /// Find the duplicate connections in the data.
+    #[must_use]
+    pub fn find(data: &V3ResponseData) -> Self {
+        Self {
+            pilots: duplicates(
+                "pilot",
+                data.pilots.iter().map(|p| {
+                    (
+                        p.cid,
+                        p.callsign.as_str(),
+                        p.last_updated.as_str(),
+                        p.last_update(),
+                    )
+                }),
+            ),
+            controllers: duplicates(
+                "controller",
+                data.controllers.iter().map(|c| {
+                    (
+                        c.cid,
+                        c.callsign.as_str(),
+                        c.last_updated.as_str(),
+                        c.last_update(),
+                    )
+                }),
+            ),
+        }
+    }
+
+    /// Whether a pilot is probably a ghost.
+    #[must_use]
+    pub fn has_pilot(&self, pilot: &Pilot) -> bool {
+        contains(&self.pilots, &pilot.callsign, &pilot.last_updated)
+    }
+
+    /// Whether a controller is probably a ghost.
+    #[must_use]
+    pub fn has_controller(&self, controller: &Controller) -> bool {
+        contains(
+            &self.controllers,
+            &controller.callsign,
+            &controller.last_updated,
+        )
+    }
+
+    /// Numbers of ghost pilots and controllers.
+    #[must_use]
+    pub fn counts(&self) -> (usize, usize) {
+        (self.pilots.len(), self.controllers.len())
+    }
+
+    /// The data without the ghosts, borrowed if there aren't any.
+    #[must_use]
+    pub fn without<'a>(&self, data: &'a V3ResponseData) -> Cow<'a, V3ResponseData> {
+        if self.pilots.is_empty() && self.controllers.is_empty() {
+            return Cow::Borrowed(data);
+        }
+        Cow::Owned(V3ResponseData {
+            pilots: data
+                .pilots
+                .iter()
+                .filter(|p| !self.has_pilot(p))
+                .cloned()
+                .collect(),
+            controllers: data
+                .controllers
+                .iter()
+                .filter(|c| !self.has_controller(c))
+                .cloned()
+                .collect(),
+            general: data.general.clone(),
+            atis: data.atis.clone(),
+            facilities: data.facilities.clone(),
+            ratings: data.ratings.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn pilot(cid: i64, callsign: &str, last_updated: &str) -> Pilot {
+        Pilot {
+            cid,
+            last_updated: last_updated.to_owned(),
+            ..testing::pilot(callsign)
+        }
+    }
+
+    fn controller(cid: i64, callsign: &str, last_updated: &str) -> Controller {
+        Controller {
+            cid,
+            last_updated: last_updated.to_owned(),
+            ..testing::controller(callsign, 4)
+        }
+    }
+
+    /// CID 1 is connected twice as a pilot and twice as a controller, and
+    /// CID 2 reconnected under the same callsign.
+    fn fixture() -> V3ResponseData {
+        V3ResponseData {
+            pilots: vec![
+                pilot(1, "BAW1", "2022-08-01T12:30:00Z"),
+                pilot(1, "BAW1X", "2022-08-01T12:10:00Z"),
+                pilot(2, "UAL2", "2022-08-01T12:00:00Z"),
+                pilot(2, "UAL2", "2022-08-01T12:31:00Z"),
+                pilot(3, "DAL3", "2022-08-01T12:30:00Z"),
+            ],
+            controllers: vec![
+                controller(1, "EGLL_TWR", "not a time"),
+                controller(1, "EGLL_1_TWR", "2022-08-01T12:00:00Z"),
+                controller(4, "KJFK_TWR", "2022-08-01T12:00:00Z"),
+            ],
+            ..V3ResponseData::default()
+        }
+    }
+
+    fn ghost_pilots(ghosts: &Ghosts, data: &V3ResponseData) -> Vec<(String, String)> {
+        data.pilots
+            .iter()
+            .filter(|p| ghosts.has_pilot(p))
+            .map(|p| (p.callsign.clone(), p.last_updated.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn the_newest_record_for_a_cid_is_the_primary() {
+        let data = fixture();
+        let ghosts = Ghosts::find(&data);
+        assert_eq!(
+            ghost_pilots(&ghosts, &data),
+            [
+                ("BAW1X".to_owned(), "2022-08-01T12:10:00Z".to_owned()),
+                ("UAL2".to_owned(), "2022-08-01T12:00:00Z".to_owned()),
+            ]
+        );
+        // an unreadable update time counts as the oldest
+        let controllers: Vec<&str> = data
+            .controllers
+            .iter()
+            .filter(|c| ghosts.has_controller(c))
+            .map(|c| c.callsign.as_str())
+            .collect();
+        assert_eq!(controllers, ["EGLL_TWR"]);
+        assert_eq!(ghosts.counts(), (2, 1));
+    }
+
+    #[test]
+    fn ties_keep_the_first_listed() {
+        let data = V3ResponseData {
+            pilots: vec![
+                pilot(1, "BAW1", "2022-08-01T12:30:00Z"),
+                pilot(1, "BAW2", "2022-08-01T12:30:00Z"),
+            ],
+            ..V3ResponseData::default()
+        };
+        let ghosts = Ghosts::find(&data);
+        assert!(!ghosts.has_pilot(&data.pilots[0]));
+        assert!(ghosts.has_pilot(&data.pilots[1]));
+    }
+
+    #[test]
+    fn identical_records_are_not_marked() {
+        let data = V3ResponseData {
+            pilots: vec![pilot(1, "BAW1", "2022-08-01T12:30:00Z"); 2],
+            ..V3ResponseData::default()
+        };
+        assert_eq!(Ghosts::find(&data), Ghosts::default());
+    }
+
+    #[test]
+    fn a_pilot_can_also_be_a_controller() {
+        let data = V3ResponseData {
+            pilots: vec![pilot(1, "BAW1", "2022-08-01T12:30:00Z")],
+            controllers: vec![controller(1, "EGLL_TWR", "2022-08-01T12:30:00Z")],
+            ..V3ResponseData::default()
+        };
+        assert_eq!(Ghosts::find(&data), Ghosts::default());
+    }
+
+    #[test]
+    fn without_drops_only_the_ghosts() {
+        let data = fixture();
+        let ghosts = Ghosts::find(&data);
+        let without = ghosts.without(&data);
+        let pilots: Vec<(&str, &str)> = without
+            .pilots
+            .iter()
+            .map(|p| (p.callsign.as_str(), p.last_updated.as_str()))
+            .collect();
+        assert_eq!(
+            pilots,
+            [
+                ("BAW1", "2022-08-01T12:30:00Z"),
+                ("UAL2", "2022-08-01T12:31:00Z"),
+                ("DAL3", "2022-08-01T12:30:00Z"),
+            ]
+        );
+        assert_eq!(without.controllers.len(), 2);
+
+        let clean = V3ResponseData {
+            pilots: vec![pilot(3, "DAL3", "2022-08-01T12:30:00Z")],
+            ..V3ResponseData::default()
+        };
+        assert!(matches!(
+            Ghosts::find(&clean).without(&clean),
+            Cow::Borrowed(_)
+        ));
+    }
+}
@@ -0,0 +1,254 @@
+//! Where the traffic is, as a text heat map of pilots in a coarse grid.
+
+use crate::{
+    models::Pilot,
+    report::{fetch_data, DataSource, Reporter},
+};
+use anyhow::Result;
+
+/// Size of a grid cell in degrees of latitude and longitude.
+const CELL_DEGREES: f64 = 10.0;
+/// Grid rows, from the north pole to the south.
+const ROWS: usize = 18;
+/// Grid columns, eastward from the antimeridian.
+const COLUMNS: usize = 36;
+/// Shades for busier and busier cells, after an empty one.
+const SHADES: [char; 4] = ['\u{2591}', '\u{2592}', '\u{2593}', '\u{2588}'];
+
+/// Counts of pilots in each grid cell.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Grid {
+    /// Rows from north to south, each with columns from west to east.
+    counts: Vec<Vec<usize>>,
+}
+
+/// The grid cell, as (row, column), for a position.
+///
+/// The poles belong to the cells next to them, and longitudes wrap, so
+/// 180° lands in the same column as -180°. Positions that aren't numbers
+/// have no cell.
+pub fn cell(latitude: f64, longitude: f64) -> Option<(usize, usize)> {
+    if !latitude.is_finite() || !longitude.is_finite() {
+        return None;
+    }
+    let from_north = (90.0 - latitude.clamp(-90.0, 90.0)) / CELL_DEGREES;
+    let from_antimeridian = (longitude + 180.0).rem_euclid(360.0) / CELL_DEGREES;
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let (row, column) = (from_north as usize, from_antimeridian as usize);
+    Some((row.min(ROWS - 1), column.min(COLUMNS - 1)))
+}
+
+impl Grid {
+    /// Count the pilots in each cell.
+    pub fn from_pilots(pilots: &[Pilot]) -> Self {
+        let mut counts = vec![vec![0; COLUMNS]; ROWS];
+        for pilot in pilots {
+            if let Some((row, column)) = cell(pilot.latitude, pilot.longitude) {
+                counts[row][column] += 1;
+            }
+        }
+        Self { counts }
+    }
+
+    /// Pilots in the busiest cell.
+    pub fn max(&self) -> usize {
+        self.counts
+            .iter()
+            .flatten()
+            .copied()
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Draw the map with latitude labels and a longitude ruler.
+    ///
+    /// Cells are shaded blocks, or in `ascii` mode digits from 1 to 9
+    /// scaled to the busiest cell, with '.' for empty cells.
+    pub fn render(&self, ascii: bool) -> String {
+        let max = self.max();
+        let mut lines = Vec::with_capacity(ROWS + 2);
+        for (row, counts) in self.counts.iter().enumerate() {
+            let cells: String = counts
+                .iter()
+                .map(|&count| {
+                    let c = if ascii {
+                        match level(count, max, 9) {
+                            0 => '.',
+                            #[allow(clippy::cast_possible_truncation)]
+                            level => char::from(b'0' + level as u8),
+                        }
+                    } else {
+                        match level(count, max, SHADES.len()) {
+                            0 => ' ',
+                            level => SHADES[level - 1],
+                        }
+                    };
+                    [c, c]
+                })
+                .flat_map(IntoIterator::into_iter)
+                .collect();
+            lines.push(format!("{:>4} |{}|", latitude_label(row), cells));
+        }
+        let ruler: Vec<String> = (0..COLUMNS)
+            .step_by(6)
+            .map(|column| format!("{:<12}", longitude_label(column)))
+            .collect();
+        lines.push(format!("     +{}+", "-".repeat(COLUMNS * 2)));
+        lines.push(format!("      {}", ruler.concat().trim_end()));
+        lines.join("\n")
+    }
+}
+
+/// How busy a cell is relative to the busiest, from 0 for empty to
+/// `levels` for the busiest.
+fn level(count: usize, max: usize, levels: usize) -> usize {
+    match max {
+        0 => 0,
+        max => (count * levels).div_ceil(max),
+    }
+}
+
+/// Latitude of the north edge of a grid row, like "80N".
+fn latitude_label(row: usize) -> String {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+    let latitude = 90 - (row as i64) * CELL_DEGREES as i64;
+    match latitude {
+        0 => "0".to_owned(),
+        l if l > 0 => format!("{}N", l),
+        l => format!("{}S", -l),
+    }
+}
+
+/// Longitude of the west edge of a grid column, like "120W".
+fn longitude_label(column: usize) -> String {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+    let longitude = (column as i64) * CELL_DEGREES as i64 - 180;
+    match longitude {
+        0 => "0".to_owned(),
+        l if l > 0 => format!("{}E", l),
+        l => format!("{}W", -l),
+    }
+}
+
+/// Fetch the current data and print the heat map.
+///
+/// The line saying what the map shows is a note, left out when quiet.
+pub fn run(ascii: bool, source: &DataSource, reporter: &mut Reporter) -> Result<()> {
+    let data = fetch_data(source, reporter)?;
+    let grid = Grid::from_pilots(&data.pilots);
+    reporter.note(format!(
+        "{} pilots by {}\u{b0} cell, busiest cell has {}",
+        data.pilots.len(),
+        CELL_DEGREES,
+        grid.max()
+    ));
+    reporter.data(grid.render(ascii))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn at(latitude: f64, longitude: f64) -> Pilot {
+        Pilot {
+            latitude,
+            longitude,
+            ..testing::pilot("N123AB")
+        }
+    }
+
+    #[test]
+    fn cells_from_the_north_pole_and_antimeridian() {
+        assert_eq!(cell(0.0, 0.0), Some((9, 18)));
+        assert_eq!(cell(51.47, -0.45), Some((3, 17)));
+        assert_eq!(cell(-33.9, 151.2), Some((12, 33)));
+        assert_eq!(cell(f64::NAN, 0.0), None);
+        assert_eq!(cell(0.0, f64::INFINITY), None);
+    }
+
+    #[test]
+    fn poles_belong_to_the_cells_next_to_them() {
+        assert_eq!(cell(90.0, 0.0), Some((0, 18)));
+        assert_eq!(cell(89.9, 0.0), Some((0, 18)));
+        assert_eq!(cell(-90.0, 0.0), Some((ROWS - 1, 18)));
+        // out of range latitudes are clamped
+        assert_eq!(cell(95.0, 0.0), Some((0, 18)));
+        assert_eq!(cell(-100.0, 0.0), Some((ROWS - 1, 18)));
+    }
+
+    #[test]
+    fn longitudes_wrap_at_the_antimeridian() {
+        assert_eq!(cell(0.0, -180.0), Some((9, 0)));
+        assert_eq!(cell(0.0, 180.0), Some((9, 0)));
+        assert_eq!(cell(0.0, 179.99), Some((9, COLUMNS - 1)));
+        assert_eq!(cell(0.0, 190.0), Some((9, 1)));
+        assert_eq!(cell(0.0, -190.0), Some((9, COLUMNS - 1)));
+        assert_eq!(cell(0.0, 540.0), Some((9, 0)));
+    }
+
+    #[test]
+    fn counts_pilots_per_cell() {
+        let grid = Grid::from_pilots(&[
+            at(51.5, -0.4),
+            at(52.0, -1.0),
+            at(40.6, -73.8),
+            at(f64::NAN, 0.0),
+        ]);
+        assert_eq!(grid.counts[3][17], 2);
+        assert_eq!(grid.counts[4][10], 1);
+        assert_eq!(grid.counts.iter().flatten().sum::<usize>(), 3);
+        assert_eq!(grid.max(), 2);
+        assert_eq!(Grid::from_pilots(&[]).max(), 0);
+    }
+
+    #[test]
+    fn levels_scale_to_the_busiest() {
+        assert_eq!(level(0, 0, 9), 0);
+        assert_eq!(level(0, 10, 9), 0);
+        // anything is at least the first level
+        assert_eq!(level(1, 100, 9), 1);
+        assert_eq!(level(50, 100, 4), 2);
+        assert_eq!(level(51, 100, 4), 3);
+        assert_eq!(level(100, 100, 4), 4);
+    }
+
+    #[test]
+    fn labels() {
+        assert_eq!(latitude_label(0), "90N");
+        assert_eq!(latitude_label(9), "0");
+        assert_eq!(latitude_label(17), "80S");
+        assert_eq!(longitude_label(0), "180W");
+        assert_eq!(longitude_label(18), "0");
+        assert_eq!(longitude_label(30), "120E");
+    }
+
+    #[test]
+    fn renders_shades_or_digits() {
+        let mut pilots = vec![at(51.5, -0.4); 4];
+        pilots.push(at(40.6, -73.8));
+        let grid = Grid::from_pilots(&pilots);
+
+        let ascii = grid.render(true);
+        let lines: Vec<&str> = ascii.lines().collect();
+        assert_eq!(lines.len(), ROWS + 2);
+        assert!(lines.iter().all(|line| line.is_ascii()));
+        assert_eq!(
+            lines[3],
+            format!(" 60N |{}99{}|", "..".repeat(17), "..".repeat(18))
+        );
+        assert_eq!(
+            lines[4],
+            format!(" 50N |{}33{}|", "..".repeat(10), "..".repeat(25))
+        );
+        assert_eq!(lines[ROWS], format!("     +{}+", "-".repeat(72)));
+        assert!(lines[ROWS + 1].starts_with("      180W        120W"));
+
+        let shaded = grid.render(false);
+        let lines: Vec<&str> = shaded.lines().collect();
+        assert_eq!(lines[3].chars().nth(6 + 34), Some('\u{2588}'));
+        assert_eq!(lines[4].chars().nth(6 + 20), Some('\u{2591}'));
+        assert_eq!(lines[0], format!(" 90N |{}|", " ".repeat(72)));
+    }
+}
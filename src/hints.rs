@@ -0,0 +1,81 @@
+//! Spotting controllers who say they're about to go offline.
+//!
+//! This is a heuristic keyword scan of the controller info text; add
+//! phrases to [`CLOSING_PHRASES`] as new wordings turn up.
+
+/// Phrases in controller info that suggest the position is closing soon.
+///
+/// Matched case-insensitively anywhere in a line.
+pub const CLOSING_PHRASES: &[&str] = &[
+    "closing at",
+    "closing in",
+    "closing soon",
+    "closing shortly",
+    "last session",
+    "last few minutes",
+    "offline at",
+    "offline in",
+    "going offline",
+    "logging off",
+    "signing off",
+    "until approx",
+];
+
+/// The first closing phrase found in controller info lines, if any.
+pub fn closing_hint(lines: &[String]) -> Option<&'static str> {
+    lines.iter().find_map(|line| {
+        let line = line.to_lowercase();
+        CLOSING_PHRASES
+            .iter()
+            .find(|phrase| line.contains(*phrase))
+            .copied()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &[&str]) -> Vec<String> {
+        text.iter().map(|&line| line.to_owned()).collect()
+    }
+
+    #[test]
+    fn finds_phrases_in_any_case() {
+        assert_eq!(
+            closing_hint(&lines(&["London Control", "Closing at 2200z"])),
+            Some("closing at")
+        );
+        assert_eq!(
+            closing_hint(&lines(&["LAST SESSION before exams, thanks all"])),
+            Some("last session")
+        );
+    }
+
+    #[test]
+    fn no_hint_without_a_phrase() {
+        assert_eq!(closing_hint(&[]), None);
+        assert_eq!(
+            closing_hint(&lines(&[
+                "Charts at chartfox.org",
+                "Open until further notice"
+            ])),
+            None
+        );
+    }
+
+    #[test]
+    fn every_phrase_is_found() {
+        for phrase in CLOSING_PHRASES {
+            let line = format!("Tower {} 21z", phrase.to_uppercase());
+            assert_eq!(closing_hint(&lines(&[&line])), Some(*phrase));
+        }
+    }
+
+    #[test]
+    fn phrases_are_lowercase() {
+        for phrase in CLOSING_PHRASES {
+            assert_eq!(*phrase, phrase.to_lowercase());
+        }
+    }
+}
@@ -0,0 +1,215 @@
+//! Recently-entered queries, for recalling in the search prompt.
+
+use crate::bounded::MruList;
+
+/// How many entries are kept.
+pub const MAX_ENTRIES: usize = 20;
+
+/// Most-recently-used list of entered queries.
+///
+/// Entering a query that's already in the list moves it to the most
+/// recent position instead of adding a duplicate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct History {
+    entries: MruList<String>,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            entries: MruList::new(MAX_ENTRIES),
+        }
+    }
+}
+
+impl History {
+    /// Build a history from saved entries, most recent first.
+    pub fn from_entries(entries: &[String]) -> Self {
+        let mut history = Self::default();
+        for entry in entries.iter().rev() {
+            history.push(entry);
+        }
+        history
+    }
+
+    /// Entries, most recent first.
+    pub fn entries(&self) -> &[String] {
+        self.entries.as_slice()
+    }
+
+    /// Record an entered query. Blank queries aren't recorded.
+    pub fn push(&mut self, entry: &str) {
+        let entry = entry.trim();
+        if entry.is_empty() {
+            return;
+        }
+        self.entries.push(entry.to_owned());
+    }
+
+    /// The entry at a position, 0 being the most recent.
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.entries.as_slice().get(index).map(String::as_str)
+    }
+
+    /// Find the most recent entry containing `needle`, starting at `from`.
+    pub fn find(&self, needle: &str, from: usize) -> Option<usize> {
+        let needle = needle.to_lowercase();
+        self.entries
+            .as_slice()
+            .iter()
+            .enumerate()
+            .skip(from)
+            .find(|(_, entry)| entry.to_lowercase().contains(&needle))
+            .map(|(index, _)| index)
+    }
+}
+
+/// Position of a text input while browsing through a history.
+///
+/// The text typed before browsing is stashed so it comes back after
+/// moving past the most recent entry.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HistoryCursor {
+    /// Entry being shown, or `None` when showing the typed text.
+    index: Option<usize>,
+    stash: Option<String>,
+}
+
+impl HistoryCursor {
+    /// Move to an older entry, returning the text to show.
+    pub fn older(&mut self, history: &History, current: &str) -> Option<String> {
+        let index = self.index.map_or(0, |i| i + 1);
+        self.show(history, index, current)
+    }
+
+    /// Move to a newer entry, returning the text to show.
+    ///
+    /// Moving past the most recent entry brings back the stashed text.
+    pub fn newer(&mut self, history: &History) -> Option<String> {
+        match self.index? {
+            0 => {
+                self.index = None;
+                Some(self.stash.take().unwrap_or_default())
+            }
+            index => {
+                self.index = Some(index - 1);
+                history.get(index - 1).map(str::to_owned)
+            }
+        }
+    }
+
+    /// Jump to the next older entry containing `needle`, returning the text to show.
+    ///
+    /// If `again` is set, the search continues past the current entry.
+    pub fn search(
+        &mut self,
+        history: &History,
+        needle: &str,
+        again: bool,
+        current: &str,
+    ) -> Option<String> {
+        let from = match self.index {
+            Some(index) if again => index + 1,
+            Some(index) => index,
+            None => 0,
+        };
+        let index = history.find(needle, from)?;
+        self.show(history, index, current)
+    }
+
+    /// Go back to the stashed text, returning it if browsing.
+    pub fn restore(&mut self) -> Option<String> {
+        let _ = self.index.take()?;
+        Some(self.stash.take().unwrap_or_default())
+    }
+
+    /// Stop browsing, keeping the shown text as if it were typed.
+    pub fn reset(&mut self) {
+        self.index = None;
+        self.stash = None;
+    }
+
+    /// Show an entry, stashing the typed text if this starts browsing.
+    fn show(&mut self, history: &History, index: usize, current: &str) -> Option<String> {
+        let entry = history.get(index)?;
+        if self.index.is_none() {
+            self.stash = Some(current.to_owned());
+        }
+        self.index = Some(index);
+        Some(entry.to_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(entries: &[&str]) -> History {
+        let mut history = History::default();
+        for entry in entries {
+            history.push(entry);
+        }
+        history
+    }
+
+    #[test]
+    fn most_recent_first_without_duplicates() {
+        let history = history(&["EG*", "K*", " EG* ", "", "   "]);
+        assert_eq!(history.entries(), ["EG*", "K*"]);
+    }
+
+    #[test]
+    fn keeps_the_last_entries() {
+        let mut history = History::default();
+        for i in 0..MAX_ENTRIES + 5 {
+            history.push(&i.to_string());
+        }
+        assert_eq!(history.entries().len(), MAX_ENTRIES);
+        assert_eq!(history.get(0), Some("24"));
+        assert_eq!(history.get(MAX_ENTRIES - 1), Some("5"));
+    }
+
+    #[test]
+    fn saved_entries_round_trip() {
+        let history = history(&["a", "b", "c"]);
+        let entries = history.entries().to_vec();
+        assert_eq!(History::from_entries(&entries), history);
+    }
+
+    #[test]
+    fn browsing_keeps_the_typed_text() {
+        let history = history(&["older", "newer"]);
+        let mut cursor = HistoryCursor::default();
+        assert_eq!(cursor.newer(&history), None);
+        assert_eq!(cursor.older(&history, "typed").as_deref(), Some("newer"));
+        assert_eq!(cursor.older(&history, "newer").as_deref(), Some("older"));
+        assert_eq!(cursor.older(&history, "older"), None);
+        assert_eq!(cursor.newer(&history).as_deref(), Some("newer"));
+        assert_eq!(cursor.newer(&history).as_deref(), Some("typed"));
+        assert_eq!(cursor.newer(&history), None);
+    }
+
+    #[test]
+    fn restore_brings_back_the_typed_text() {
+        let history = history(&["EG*"]);
+        let mut cursor = HistoryCursor::default();
+        assert_eq!(cursor.restore(), None);
+        let _ = cursor.older(&history, "KS");
+        assert_eq!(cursor.restore().as_deref(), Some("KS"));
+        assert_eq!(cursor.restore(), None);
+    }
+
+    #[test]
+    fn reverse_search() {
+        let history = history(&["callsign=BAW*", "K*", "callsign=DLH*"]);
+        let mut cursor = HistoryCursor::default();
+        let search =
+            |cursor: &mut HistoryCursor, again| cursor.search(&history, "CALLSIGN", again, "typed");
+        assert_eq!(search(&mut cursor, false).as_deref(), Some("callsign=DLH*"));
+        assert_eq!(search(&mut cursor, false).as_deref(), Some("callsign=DLH*"));
+        assert_eq!(search(&mut cursor, true).as_deref(), Some("callsign=BAW*"));
+        assert_eq!(search(&mut cursor, true), None);
+        assert_eq!(cursor.restore().as_deref(), Some("typed"));
+        assert_eq!(history.find("zzz", 0), None);
+    }
+}
@@ -0,0 +1,71 @@
+//! Where clickable parts of the interface were drawn, for mouse input.
+
+use tui::layout::Rect;
+
+/// A clickable part of the interface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    /// A "tab"'s label in the header, by tab index.
+    Tab(usize),
+    /// A table column's header cell, by column index.
+    Column(usize),
+}
+
+/// Regions drawn in the last frame and where.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HitMap {
+    regions: Vec<(Rect, Region)>,
+}
+
+impl HitMap {
+    /// Forget the last frame's regions.
+    pub fn clear(&mut self) {
+        self.regions.clear();
+    }
+
+    /// Record where a region was drawn.
+    pub fn add(&mut self, area: Rect, region: Region) {
+        if area.width > 0 && area.height > 0 {
+            self.regions.push((area, region));
+        }
+    }
+
+    /// The region at a terminal cell, if any.
+    pub fn hit(&self, x: u16, y: u16) -> Option<Region> {
+        self.regions
+            .iter()
+            .find(|(area, _)| {
+                (area.x..area.right()).contains(&x) && (area.y..area.bottom()).contains(&y)
+            })
+            .map(|&(_, region)| region)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hits_inside_regions_only() {
+        let mut map = HitMap::default();
+        map.add(Rect::new(2, 1, 6, 1), Region::Tab(0));
+        map.add(Rect::new(8, 1, 4, 1), Region::Tab(1));
+        map.add(Rect::new(20, 4, 0, 1), Region::Column(9));
+        assert_eq!(map.hit(2, 1), Some(Region::Tab(0)));
+        assert_eq!(map.hit(7, 1), Some(Region::Tab(0)));
+        assert_eq!(map.hit(8, 1), Some(Region::Tab(1)));
+        assert_eq!(map.hit(12, 1), None);
+        assert_eq!(map.hit(1, 1), None);
+        assert_eq!(map.hit(3, 2), None);
+        assert_eq!(map.hit(20, 4), None);
+    }
+
+    #[test]
+    fn clearing_forgets_the_last_frame() {
+        let mut map = HitMap::default();
+        map.add(Rect::new(0, 0, 10, 1), Region::Column(0));
+        map.clear();
+        assert_eq!(map.hit(0, 0), None);
+        assert_eq!(map, HitMap::default());
+    }
+}
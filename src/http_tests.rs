@@ -0,0 +1,714 @@
+//! Tests against a mock server, run the same way for the blocking and
+//! async clients so they can't behave differently.
+
+use crate::{
+    api::{DataResponse, RawResponse, Vatsim},
+    dump::DumpSettings,
+    mirrors::{MirrorChoice, MirrorSelection},
+    models::{Event, V3ResponseData},
+    testing,
+};
+use anyhow::Result;
+use mockito::{mock, Matcher};
+use reqwest::header::HeaderValue;
+use std::{
+    sync::{Mutex, MutexGuard, PoisonError},
+    time::Duration,
+};
+
+/// The status document and events list are at fixed paths on the one
+/// mock server, so the tests take turns.
+static SERVER: Mutex<()> = Mutex::new(());
+
+/// What the tests need from a client.
+trait Client: Sized {
+    fn connect_as(
+        selection: &MirrorSelection,
+        low_bandwidth: bool,
+        user_agent: &HeaderValue,
+    ) -> Result<Self>;
+    fn connect(selection: &MirrorSelection, low_bandwidth: bool) -> Result<Self> {
+        Self::connect_as(selection, low_bandwidth, &user_agent())
+    }
+    fn url(&self) -> &str;
+    fn data(&mut self) -> Result<DataResponse>;
+    fn metar(&self, icao: &str) -> Result<String>;
+    fn events(&self) -> Result<Vec<Event>>;
+    fn take_stale_switch(&mut self) -> bool;
+    fn fallbacks(&self) -> &[String];
+    fn failed_mirrors(&self) -> &[String];
+}
+
+impl Client for Vatsim {
+    fn connect_as(
+        selection: &MirrorSelection,
+        low_bandwidth: bool,
+        user_agent: &HeaderValue,
+    ) -> Result<Self> {
+        Vatsim::with_mirror(false, low_bandwidth, user_agent, selection)
+    }
+
+    fn url(&self) -> &str {
+        self.mirror_url()
+    }
+
+    fn data(&mut self) -> Result<DataResponse> {
+        self.get_data()
+    }
+
+    fn metar(&self, icao: &str) -> Result<String> {
+        self.get_metar(icao)
+    }
+
+    fn events(&self) -> Result<Vec<Event>> {
+        self.get_events(false)
+    }
+
+    fn take_stale_switch(&mut self) -> bool {
+        Vatsim::take_stale_switch(self)
+    }
+
+    fn fallbacks(&self) -> &[String] {
+        self.last_fallbacks()
+    }
+
+    fn failed_mirrors(&self) -> &[String] {
+        self.last_failed_mirrors()
+    }
+}
+
+/// The async client, with a runtime to wait on it.
+#[cfg(feature = "async")]
+struct Async {
+    runtime: tokio::runtime::Runtime,
+    client: crate::async_api::AsyncVatsim,
+}
+
+#[cfg(feature = "async")]
+impl Client for Async {
+    fn connect_as(
+        selection: &MirrorSelection,
+        low_bandwidth: bool,
+        user_agent: &HeaderValue,
+    ) -> Result<Self> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let client = runtime.block_on(crate::async_api::AsyncVatsim::with_mirror(
+            false,
+            low_bandwidth,
+            user_agent,
+            selection,
+        ))?;
+        Ok(Self { runtime, client })
+    }
+
+    fn url(&self) -> &str {
+        self.client.mirror_url()
+    }
+
+    fn data(&mut self) -> Result<DataResponse> {
+        self.runtime.block_on(self.client.get_data())
+    }
+
+    fn metar(&self, icao: &str) -> Result<String> {
+        self.runtime.block_on(self.client.get_metar(icao))
+    }
+
+    fn events(&self) -> Result<Vec<Event>> {
+        self.runtime.block_on(self.client.get_events(false))
+    }
+
+    fn take_stale_switch(&mut self) -> bool {
+        self.client.take_stale_switch()
+    }
+
+    fn fallbacks(&self) -> &[String] {
+        self.client.last_fallbacks()
+    }
+
+    fn failed_mirrors(&self) -> &[String] {
+        self.client.last_failed_mirrors()
+    }
+}
+
+fn user_agent() -> HeaderValue {
+    HeaderValue::from_static("vatsim_online tests")
+}
+
+fn take_server() -> MutexGuard<'static, ()> {
+    SERVER.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// A mirror at `path` on the mock server, used without a status document.
+fn only(path: &str) -> MirrorSelection {
+    MirrorSelection {
+        choice: MirrorChoice::Url(format!("{}{}", mockito::server_url(), path)),
+        ..MirrorSelection::default()
+    }
+}
+
+/// URL of a path on the mock server, or the path itself if it's already a URL.
+fn server_url(path: &str) -> String {
+    if path.starts_with("http") {
+        path.to_owned()
+    } else {
+        format!("{}{}", mockito::server_url(), path)
+    }
+}
+
+/// A status document listing mirrors and a METAR endpoint at these paths.
+fn status(v3: &[&str], metar: &str) -> String {
+    let urls: Vec<_> = v3.iter().map(|path| server_url(path)).collect();
+    serde_json::json!({
+        "data": {
+            "v3": urls,
+            "transceivers": [],
+            "servers": [],
+            "servers_sweatbox": [],
+            "servers_all": [],
+        },
+        "user": [],
+        "metar": [format!("{}{}", mockito::server_url(), metar)],
+    })
+    .to_string()
+}
+
+/// A V3 body with pilots and controllers out of callsign order.
+fn v3_body() -> String {
+    let mut data = V3ResponseData {
+        pilots: vec![
+            testing::flying("UAL1", "KSFO", "KLAX"),
+            testing::pilot("AAL1"),
+        ],
+        controllers: vec![
+            testing::controller("KSFO_TWR", 4),
+            testing::controller("KLAX_TWR", 4),
+        ],
+        ..V3ResponseData::default()
+    };
+    data.general.update_timestamp = "2022-08-01T12:30:00Z".to_owned();
+    serde_json::to_string(&data).unwrap()
+}
+
+fn changed(response: DataResponse) -> V3ResponseData {
+    match response {
+        DataResponse::Changed(data) => data,
+        other => panic!("expected new data, got {:?}", other),
+    }
+}
+
+fn gets_data<C: Client>(prefix: &str) {
+    let _server = take_server();
+    let path = format!("{}/data", prefix);
+    let data = mock("GET", path.as_str())
+        .with_header("content-type", "application/json")
+        .with_body(v3_body())
+        .create();
+    let mut client = C::connect(&only(&path), false).unwrap();
+    let parsed = changed(client.data().unwrap());
+    data.assert();
+    let callsigns: Vec<_> = parsed.pilots.iter().map(|p| p.callsign.as_str()).collect();
+    assert_eq!(callsigns, ["AAL1", "UAL1"]);
+    assert_eq!(parsed.controllers[0].callsign, "KLAX_TWR");
+}
+
+fn reports_error_statuses<C: Client>(prefix: &str) {
+    let _server = take_server();
+    let path = format!("{}/data", prefix);
+    let _data = mock("GET", path.as_str()).with_status(503).create();
+    let mut client = C::connect(&only(&path), false).unwrap();
+    let error = client.data().unwrap_err().to_string();
+    assert!(error.contains("503"), "{}", error);
+    assert_eq!(client.failed_mirrors(), [client.url()]);
+}
+
+/// Check that the mirror at `bad`, a path on the mock server or a URL, is
+/// failed over from.
+fn fails_over_from<C: Client>(prefix: &str, bad: &str) {
+    let good = format!("{}/good", prefix);
+    let _status = mock("GET", "/status.json")
+        .with_body(status(&[bad, &good], "/metar"))
+        .create();
+    let good_mirror = mock("GET", good.as_str())
+        .with_header("content-type", "application/json")
+        .with_body(v3_body())
+        .expect(2)
+        .create();
+    let selection = MirrorSelection {
+        choice: MirrorChoice::Url(server_url(bad)),
+        allow_failover: true,
+        ..MirrorSelection::default()
+    };
+    let mut client = C::connect(&selection, false).unwrap();
+    let _ = changed(client.data().unwrap());
+    assert!(client.url().ends_with(&good));
+    let fallbacks = client.fallbacks();
+    assert_eq!(fallbacks.len(), 1);
+    assert!(
+        fallbacks[0].ends_with(", trying next mirror"),
+        "{:?}",
+        fallbacks
+    );
+    assert_eq!(client.failed_mirrors(), [server_url(bad)]);
+    // only the last fetch's fallbacks are kept
+    let _ = client.data().unwrap();
+    assert!(client.fallbacks().is_empty());
+    assert!(client.failed_mirrors().is_empty());
+    good_mirror.assert();
+}
+
+/// Check that a mirror answering 200 with a bad body is failed over from.
+fn fails_over_from_bad_body<C: Client>(prefix: &str, content_type: &str, body: &str) {
+    let _server = take_server();
+    let bad = format!("{}/bad", prefix);
+    let bad_mirror = mock("GET", bad.as_str())
+        .with_header("content-type", content_type)
+        .with_body(body)
+        .create();
+    fails_over_from::<C>(prefix, &bad);
+    bad_mirror.assert();
+}
+
+fn fails_over_from_html<C: Client>(prefix: &str) {
+    fails_over_from_bad_body::<C>(prefix, "text/html", "<html>down for maintenance</html>");
+}
+
+fn fails_over_from_empty_body<C: Client>(prefix: &str) {
+    fails_over_from_bad_body::<C>(prefix, "application/json", "  \n");
+}
+
+fn fails_over_from_truncated_json<C: Client>(prefix: &str) {
+    let body = v3_body();
+    fails_over_from_bad_body::<C>(prefix, "application/json", &body[..body.len() / 2]);
+}
+
+fn fails_over_from_server_errors<C: Client>(prefix: &str) {
+    for status in [500, 502, 503] {
+        let _server = take_server();
+        let bad = format!("{}/{}", prefix, status);
+        let bad_mirror = mock("GET", bad.as_str()).with_status(status).create();
+        fails_over_from::<C>(prefix, &bad);
+        bad_mirror.assert();
+    }
+}
+
+fn fails_over_from_unreachable_mirrors<C: Client>(prefix: &str) {
+    let _server = take_server();
+    fails_over_from::<C>(prefix, &testing::refused_url());
+}
+
+/// A client error would be the same from every mirror.
+fn does_not_fail_over_from_client_errors<C: Client>(prefix: &str) {
+    let _server = take_server();
+    let (bad, good) = (format!("{}/forbidden", prefix), format!("{}/good", prefix));
+    let _status = mock("GET", "/status.json")
+        .with_body(status(&[&bad, &good], "/metar"))
+        .create();
+    let _bad = mock("GET", bad.as_str()).with_status(403).create();
+    let good_mirror = mock("GET", good.as_str()).expect(0).create();
+    let selection = MirrorSelection {
+        allow_failover: true,
+        ..only(&bad)
+    };
+    let mut client = C::connect(&selection, false).unwrap();
+    let error = client.data().unwrap_err().to_string();
+    assert!(error.contains("403"), "{}", error);
+    assert!(client.fallbacks().is_empty());
+    good_mirror.assert();
+}
+
+fn names_the_content_type_of_html<C: Client>(prefix: &str) {
+    let _server = take_server();
+    let path = format!("{}/html", prefix);
+    let _html = mock("GET", path.as_str())
+        .with_header("content-type", "text/html")
+        .with_body("\n<!DOCTYPE html>")
+        .create();
+    let mut client = C::connect(&only(&path), false).unwrap();
+    let error = client.data().unwrap_err().to_string();
+    assert!(error.contains("non-JSON response (text/html)"), "{}", error);
+}
+
+fn races_for_the_fastest_mirror<C: Client>(prefix: &str) {
+    let _server = take_server();
+    let (down, up) = (format!("{}/down", prefix), format!("{}/up", prefix));
+    let _status = mock("GET", "/status.json")
+        .with_body(status(&[&down, &up], "/metar"))
+        .create();
+    let _down = mock("HEAD", down.as_str()).with_status(503).create();
+    let _up = mock("HEAD", up.as_str()).create();
+    for seed in 0..4 {
+        let selection = MirrorSelection {
+            choice: MirrorChoice::Fastest,
+            seed: Some(seed),
+            ..MirrorSelection::default()
+        };
+        let client = C::connect(&selection, false).unwrap();
+        assert!(client.url().ends_with(&up), "seed {}", seed);
+    }
+}
+
+fn not_modified_in_low_bandwidth<C: Client>(prefix: &str) {
+    let _server = take_server();
+    let path = format!("{}/data", prefix);
+    let _first = mock("GET", path.as_str())
+        .match_header("if-none-match", Matcher::Missing)
+        .with_header("content-type", "application/json")
+        .with_header("etag", "\"v1\"")
+        .with_body(v3_body())
+        .create();
+    let again = mock("GET", path.as_str())
+        .match_header("if-none-match", "\"v1\"")
+        .with_status(304)
+        .create();
+    let mut client = C::connect(&only(&path), true).unwrap();
+    let _ = changed(client.data().unwrap());
+    assert!(matches!(client.data().unwrap(), DataResponse::NotModified));
+    again.assert();
+}
+
+fn skips_identical_bodies_in_low_bandwidth<C: Client>(prefix: &str) {
+    let _server = take_server();
+    let path = format!("{}/data", prefix);
+    let data = mock("GET", path.as_str())
+        .with_header("content-type", "application/json")
+        .with_body(v3_body())
+        .expect(2)
+        .create();
+    let mut client = C::connect(&only(&path), true).unwrap();
+    let _ = changed(client.data().unwrap());
+    assert!(matches!(
+        client.data().unwrap(),
+        DataResponse::IdenticalBody
+    ));
+    drop(data);
+
+    let _changed = mock("GET", path.as_str())
+        .with_header("content-type", "application/json")
+        .with_body(v3_body().replace("AAL1", "AAL2"))
+        .create();
+    let data = changed(client.data().unwrap());
+    assert_eq!(data.pilots[0].callsign, "AAL2");
+}
+
+fn requests_compression_in_low_bandwidth<C: Client>(prefix: &str) {
+    let _server = take_server();
+    let path = format!("{}/data", prefix);
+    let data = mock("GET", path.as_str())
+        .match_header("accept-encoding", Matcher::Regex("gzip".to_owned()))
+        .with_header("content-type", "application/json")
+        .with_body(v3_body())
+        .create();
+    let mut client = C::connect(&only(&path), true).unwrap();
+    let _ = changed(client.data().unwrap());
+    data.assert();
+}
+
+/// Check that every request carries a user agent.
+fn sends_user_agent<C: Client>(prefix: &str, user_agent: &HeaderValue) {
+    let _server = take_server();
+    let path = format!("{}/data", prefix);
+    let agent = user_agent.to_str().unwrap();
+    let status_request = mock("GET", "/status.json")
+        .match_header("user-agent", agent)
+        .with_body(status(&[&path], "/metar"))
+        .create();
+    let data = mock("GET", path.as_str())
+        .match_header("user-agent", agent)
+        .with_header("content-type", "application/json")
+        .with_body(v3_body())
+        .create();
+    let mut client = C::connect_as(&MirrorSelection::default(), false, user_agent).unwrap();
+    let _ = changed(client.data().unwrap());
+    status_request.assert();
+    data.assert();
+}
+
+fn sends_the_default_user_agent<C: Client>(prefix: &str) {
+    let user_agent = HeaderValue::from_static(crate::api::DEFAULT_USER_AGENT);
+    assert!(user_agent
+        .to_str()
+        .unwrap()
+        .ends_with(env!("CARGO_PKG_VERSION")));
+    sends_user_agent::<C>(prefix, &user_agent);
+}
+
+fn sends_an_overridden_user_agent<C: Client>(prefix: &str) {
+    let user_agent = crate::api::parse_user_agent(" my-tracker/2.1 (ops@example.com) ").unwrap();
+    sends_user_agent::<C>(prefix, &user_agent);
+}
+
+fn gets_metar<C: Client>(prefix: &str) {
+    let _server = take_server();
+    let (data, metar) = (format!("{}/data", prefix), format!("{}/metar", prefix));
+    let _status = mock("GET", "/status.json")
+        .with_body(status(&[&data], &metar))
+        .create();
+    let _metar = mock("GET", metar.as_str())
+        .match_query(Matcher::UrlEncoded("id".to_owned(), "EGLL".to_owned()))
+        .with_body("EGLL 011220Z 24010KT 9999 FEW030 18/09 Q1015\n")
+        .create();
+    let _empty = mock("GET", metar.as_str())
+        .match_query(Matcher::UrlEncoded("id".to_owned(), "XXXX".to_owned()))
+        .with_body("\n")
+        .create();
+    let client = C::connect(&MirrorSelection::default(), false).unwrap();
+    assert_eq!(
+        client.metar("EGLL").unwrap(),
+        "EGLL 011220Z 24010KT 9999 FEW030 18/09 Q1015"
+    );
+    assert!(client.metar("XXXX").is_err());
+}
+
+fn gets_events<C: Client>(prefix: &str) {
+    let _server = take_server();
+    let path = format!("{}/data", prefix);
+    let _events = mock("GET", "/events")
+        .with_body(
+            serde_json::json!({"data": [{
+                "id": 1,
+                "name": "Friday Night Ops",
+                "link": "https://example.com",
+                "start_time": "2022-08-05T23:00:00.000000Z",
+                "end_time": "2022-08-06T02:00:00.000000Z",
+                "airports": [{"icao": "KSFO"}],
+            }]})
+            .to_string(),
+        )
+        .create();
+    let client = C::connect(&only(&path), false).unwrap();
+    let events = client.events().unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].airports[0].icao, "KSFO");
+}
+
+fn chosen_url_skips_the_status_document_and_fails_alone<C: Client>(prefix: &str) {
+    let _server = take_server();
+    let (bad, good) = (format!("{}/bad", prefix), format!("{}/good", prefix));
+    let status_document = mock("GET", "/status.json")
+        .with_body(status(&[&bad, &good], "/metar"))
+        .expect(0)
+        .create();
+    let _bad = mock("GET", bad.as_str())
+        .with_header("content-type", "text/html")
+        .with_body("<html>down</html>")
+        .create();
+    let good_mirror = mock("GET", good.as_str()).expect(0).create();
+    let mut client = C::connect(&only(&bad), false).unwrap();
+    assert!(client.data().is_err());
+    assert!(client.url().ends_with(&bad));
+    status_document.assert();
+    good_mirror.assert();
+}
+
+fn chosen_index_uses_the_listed_mirror<C: Client>(prefix: &str) {
+    let _server = take_server();
+    let mirrors: Vec<String> = (0..3).map(|i| format!("{}/mirror{}", prefix, i)).collect();
+    let paths: Vec<&str> = mirrors.iter().map(String::as_str).collect();
+    let _status = mock("GET", "/status.json")
+        .with_body(status(&paths, "/metar"))
+        .create();
+    let chosen = mock("GET", paths[1])
+        .with_header("content-type", "application/json")
+        .with_body(v3_body())
+        .create();
+    let selection = MirrorSelection {
+        choice: MirrorChoice::Index(1),
+        ..MirrorSelection::default()
+    };
+    let mut client = C::connect(&selection, false).unwrap();
+    let _ = changed(client.data().unwrap());
+    assert!(client.url().ends_with(paths[1]));
+    chosen.assert();
+
+    let selection = MirrorSelection {
+        choice: MirrorChoice::Index(3),
+        ..MirrorSelection::default()
+    };
+    let error = C::connect(&selection, false).err().unwrap().to_string();
+    assert!(error.contains("lists 3 mirrors (0 to 2)"), "{}", error);
+}
+
+fn switches_from_a_frozen_mirror<C: Client>(prefix: &str) {
+    let _server = take_server();
+    let (frozen, fresh) = (format!("{}/frozen", prefix), format!("{}/fresh", prefix));
+    let _status = mock("GET", "/status.json")
+        .with_body(status(&[&frozen, &fresh], "/metar"))
+        .create();
+    let frozen_mirror = mock("GET", frozen.as_str())
+        .with_header("content-type", "application/json")
+        .with_body(v3_body())
+        .expect(4)
+        .create();
+    let fresh_mirror = mock("GET", fresh.as_str())
+        .with_header("content-type", "application/json")
+        .with_body(v3_body())
+        .expect(1)
+        .create();
+    let selection = MirrorSelection {
+        allow_failover: true,
+        ..only(&frozen)
+    };
+    let mut client = C::connect(&selection, false).unwrap();
+    // the first fetch sets the timestamp, and the next three don't advance it
+    for _ in 0..3 {
+        let _ = changed(client.data().unwrap());
+        assert!(client.url().ends_with(&frozen));
+        assert!(!client.take_stale_switch());
+    }
+    let _ = changed(client.data().unwrap());
+    assert!(client.url().ends_with(&fresh));
+    assert!(client.take_stale_switch());
+    assert!(!client.take_stale_switch());
+    let _ = changed(client.data().unwrap());
+    frozen_mirror.assert();
+    fresh_mirror.assert();
+}
+
+fn stays_on_a_frozen_mirror_without_failover<C: Client>(prefix: &str) {
+    let _server = take_server();
+    let path = format!("{}/frozen", prefix);
+    let frozen = mock("GET", path.as_str())
+        .with_header("content-type", "application/json")
+        .with_body(v3_body())
+        .expect(6)
+        .create();
+    let mut client = C::connect(&only(&path), false).unwrap();
+    for _ in 0..6 {
+        let _ = changed(client.data().unwrap());
+        assert!(!client.take_stale_switch());
+    }
+    assert!(client.url().ends_with(&path));
+    frozen.assert();
+}
+
+/// Run the suite against a client, with mocks under their own path.
+macro_rules! client_tests {
+    ($module:ident, $client:ty) => {
+        mod $module {
+            use super::*;
+
+            client_tests!(
+                @tests $client,
+                concat!("/", stringify!($module)),
+                gets_data,
+                reports_error_statuses,
+                fails_over_from_html,
+                fails_over_from_empty_body,
+                fails_over_from_truncated_json,
+                fails_over_from_server_errors,
+                fails_over_from_unreachable_mirrors,
+                does_not_fail_over_from_client_errors,
+                names_the_content_type_of_html,
+                races_for_the_fastest_mirror,
+                not_modified_in_low_bandwidth,
+                skips_identical_bodies_in_low_bandwidth,
+                requests_compression_in_low_bandwidth,
+                sends_the_default_user_agent,
+                sends_an_overridden_user_agent,
+                gets_metar,
+                gets_events,
+                chosen_url_skips_the_status_document_and_fails_alone,
+                chosen_index_uses_the_listed_mirror,
+                switches_from_a_frozen_mirror,
+                stays_on_a_frozen_mirror_without_failover
+            );
+        }
+    };
+    (@tests $client:ty, $prefix:expr, $($test:ident),*) => {
+        $(
+            #[test]
+            fn $test() {
+                super::$test::<$client>($prefix);
+            }
+        )*
+    };
+}
+
+client_tests!(blocking, Vatsim);
+#[cfg(feature = "async")]
+client_tests!(non_blocking, Async);
+
+/// The steps of a blocking fetch can be run apart, and are timed apart.
+#[test]
+fn fetches_and_parses_as_separate_steps() {
+    let _server = take_server();
+    let path = "/split/data";
+    let _data = mock("GET", path)
+        .with_header("content-type", "application/json")
+        .with_body(v3_body())
+        .create();
+    let mut client = Vatsim::connect(&only(path), false).unwrap();
+    let raw = client.fetch_raw().unwrap();
+    let RawResponse::Body { content_type, body } = &raw else {
+        panic!("expected a body, got {:?}", raw);
+    };
+    assert_eq!(content_type.as_deref(), Some("application/json"));
+    assert_eq!(body, v3_body().as_bytes());
+    let parsed = changed(client.parse_raw(&raw).unwrap());
+    assert_eq!(parsed.pilots[0].callsign, "AAL1");
+
+    let _ = changed(client.get_data().unwrap());
+    let timings = client.last_timings();
+    assert!(timings.fetch > Duration::ZERO && timings.parse > Duration::ZERO);
+}
+
+/// A body that downloads fine can still fail to parse.
+#[test]
+fn fetching_does_not_check_the_body() {
+    let _server = take_server();
+    let path = "/split/truncated";
+    let _data = mock("GET", path)
+        .with_header("content-type", "application/json")
+        .with_body(&v3_body()[..20])
+        .create();
+    let mut client = Vatsim::connect(&only(path), false).unwrap();
+    let raw = client.fetch_raw().unwrap();
+    let error = client.parse_raw(&raw).unwrap_err().to_string();
+    assert!(error.contains("truncated"), "{}", error);
+    assert!(matches!(
+        client.parse_raw(&RawResponse::NotModified).unwrap(),
+        DataResponse::NotModified
+    ));
+}
+
+/// Bodies that fail to parse are saved with `--debug-dump`, and every
+/// body with `--debug-dump-all`.
+#[test]
+fn saves_raw_bodies_for_debugging() {
+    let _server = take_server();
+    let dir = tempfile::tempdir().unwrap();
+    let (bad, good) = ("/dump/bad", "/dump/good");
+    let _bad = mock("GET", bad)
+        .with_header("content-type", "application/json")
+        .with_body(&v3_body()[..20])
+        .create();
+    let _good = mock("GET", good)
+        .with_header("content-type", "application/json")
+        .with_body(v3_body())
+        .create();
+    let settings = |all| {
+        Some(DumpSettings {
+            dir: dir.path().to_owned(),
+            all,
+        })
+    };
+
+    let mut client = Vatsim::connect(&only(bad), false).unwrap();
+    client.set_dumps(settings(false));
+    assert!(client.get_data().is_err());
+    let saved = client.take_dump().unwrap();
+    assert_eq!(saved.parent(), Some(dir.path()));
+    assert_eq!(std::fs::read(&saved).unwrap(), &v3_body().as_bytes()[..20]);
+    assert_eq!(client.take_dump(), None);
+
+    let mut client = Vatsim::connect(&only(good), false).unwrap();
+    client.set_dumps(settings(false));
+    let _ = changed(client.get_data().unwrap());
+    assert_eq!(client.take_dump(), None);
+    client.set_dumps(settings(true));
+    let _ = changed(client.get_data().unwrap());
+    let saved = client.take_dump().unwrap();
+    assert_eq!(std::fs::read(saved).unwrap(), v3_body().as_bytes());
+}
@@ -1,169 +1,1049 @@
 use crate::{
-    models::V3ResponseData,
-    state::{App, SelectedRow},
+    action::{self, Effect},
+    alerts::{AlertSettings, AlertSound, Alerter},
+    atis::DiffLine,
+    callsign, columns,
+    custom::CustomColumns,
+    dump::{self, DumpSettings},
+    enrich::EnrichedPilot,
+    error_panel::RefreshError,
+    events,
+    filter::{AirlineFilter, BuilderFocus, FilterBuilder},
+    flight::{self, FiledComparison, FuelWarning},
+    format::{self, DisplayZone, TimeStyle},
+    frequency,
+    hitmap::Region,
+    lock,
+    logbuffer::LogView,
+    mirrors::MirrorSelection,
+    models::{parse_time, FlightPlan},
+    nearby::NearbyController,
+    polling::{self, AutoBounds, PollInterval, Poller},
+    privacy::Privacy,
+    query::Query,
+    route::{self, RouteProgress},
+    sanity::Limits,
+    session,
+    shared::{self, Sharing},
+    sort::DefaultSort,
+    split::{Pane, SplitPanes},
+    state::{App, Density, LoadState, SearchPrompt, SelectedRow, ViewData},
+    summary,
+    terminal::{self, Mode, Plain},
+    theme::Theme,
+    tour,
+    trail::{self, Trail},
+    tune::TunePrompt,
+    widgets::CheckList,
+    worker::{Abandoned, FetchStats, Fetched, Message, Worker, WorkerOptions},
 };
 use anyhow::Result;
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
+    event::{self, DisableBracketedPaste, DisableMouseCapture, Event, KeyCode},
     execute,
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+    terminal::{disable_raw_mode, LeaveAlternateScreen},
 };
-use log::{debug, error};
+use log::{debug, error, warn, Level};
 use once_cell::sync::Lazy;
+use reqwest::header::HeaderValue;
+use std::{
+    fmt::Write,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
 use tui::{
-    backend::CrosstermBackend,
-    layout::{Constraint, Direction, Layout, Rect},
+    backend::{Backend, CrosstermBackend},
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
-    text::{Spans, Text},
-    widgets::{Block, Borders, Cell, Clear, Paragraph, Row, Table, Wrap},
-    Terminal,
+    text::{Span, Spans, Text},
+    widgets::{Block, Borders, Cell, Clear, Paragraph, Row, Table, TableState, Wrap},
+    Frame, Terminal,
 };
 
 /// Text shown in the top right.
 const HELP_TEXT: &str =
-    "   Tab to switch sources. Up and down to navigate. Enter to examine; Esc to close. O to view online stats. Q to exit.";
+    "   Tab to switch sources. Up and down to navigate. Left and right to scroll; < and > to resize the sorted column. S to sort; 0 to reset the sort. I for flight rules; N for no flight plan; P for phase; Shift+R for pilots in a controller's range; E for departing soon; M for military and special flights. Shift+P to pin favorites to the top. Shift+A for no ATIS; X to hide stale records; B to hide bad data; G to count ghost connections; Shift+L for the log with --debug; C for coverage; T to tune a frequency; Shift+T for pilots near a controller squawking the wrong code; Shift+V to choose facilities; W to split an airport's pilots into inbound and outbound, then Tab to switch sides. Shift+F to filter; / to search; Ctrl+L to clear. Z for row density. Enter to examine, then S to copy or G to copy a pilot's trail; Esc to close. O to view online stats or the event page. Q to exit.";
+/// Percentage of the title row beside the tab selector given to the status
+/// while there is one.
+const STATUS_SHARE: u16 = 60;
+/// Text shown while waiting on the VATSIM API.
+const LOADING_TEXT: &str = "Loading data from VATSIM...";
+/// Frames of the spinner on the loading screen, one per input poll.
+const SPINNER: [char; 4] = ['|', '/', '-', '\\'];
+/// Text shown while refreshing is paused for inactivity.
+const IDLE_TEXT: &str = "Idle \u{2014} refreshing is paused.\n\nPress any key to resume.";
+/// How long to wait for input before checking on the background fetch.
+const EVENT_POLL_TIMEOUT: Duration = Duration::from_millis(250);
+/// How often to refresh the data.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+/// How often to refresh the data in low-bandwidth mode.
+const LOW_BANDWIDTH_REFRESH_INTERVAL: Duration = Duration::from_secs(120);
+/// How often to refresh the events list.
+const EVENTS_REFRESH_INTERVAL: Duration = Duration::from_secs(30 * 60);
+/// Trail readings shown in the popup, newest last.
+const TRAIL_READINGS: usize = 6;
+/// Longest to wait on quitting for a fetch in progress to finish.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(2);
+/// Narrowest terminal the interface is drawn in.
+const MIN_WIDTH: u16 = 60;
+/// Shortest terminal the interface is drawn in: the title row, the table
+/// borders and header, and a few rows.
+const MIN_HEIGHT: u16 = 10;
+/// Lines of the error shown in the refresh error panel.
+const REFRESH_ERROR_LINES: usize = 2;
+/// Keys shown in the refresh error panel.
+const REFRESH_ERROR_HELP_TEXT: &str = "r: retry now  M: switch mirror";
+/// Marker drawn before the selected table row.
+const HIGHLIGHT_SYMBOL: &str = ">> ";
+/// Width in the table area not available for columns: the borders
+/// and the selected row's highlight symbol.
+const TABLE_CHROME_WIDTH: u16 = 2 + 3;
+/// Height in the table area not available for rows: the borders and the
+/// header row.
+const TABLE_CHROME_HEIGHT: u16 = 2 + 1;
 /// Style applied to the table header row.
 static NORMAL_STYLE: Lazy<Style> = Lazy::new(|| Style::default().bg(Color::Blue));
-/// Style applied to non-header table rows.
-static SELECTED_STYLE: Lazy<Style> =
-    Lazy::new(|| Style::default().add_modifier(Modifier::REVERSED));
+/// Style applied to the second line of rows in the comfortable density.
+static DETAIL_STYLE: Lazy<Style> = Lazy::new(|| Style::default().add_modifier(Modifier::DIM));
+/// Style applied to the selected table row.
+///
+/// The colors are reset so that a row's tint doesn't show through.
+static SELECTED_STYLE: Lazy<Style> = Lazy::new(|| {
+    Style::default()
+        .fg(Color::Reset)
+        .bg(Color::Reset)
+        .add_modifier(Modifier::REVERSED)
+});
+
+/// Keys shown at the bottom of the filter builder popup.
+const FILTER_HELP_TEXT: &str = "Tab: next part  Left/Right: change or move  Up/Down: rows  Ctrl+N: add row  Ctrl+D: remove row  Enter: apply  Esc: cancel";
+
+/// Keys shown at the bottom of the search prompt.
+const SEARCH_HELP_TEXT: &str = "Conditions like callsign=BAW* joined by 'and'/'or', with parentheses.  Up/Down: history  Tab: complete  Ctrl+R: search history  Enter: apply  Esc: cancel";
+
+/// Keys shown at the bottom of the tune prompt.
+const TUNE_HELP_TEXT: &str =
+    "A frequency like 124.35, 124.350, or 12435. Empty to clear.  Enter: apply  Esc: cancel";
+
+/// Keys shown at the bottom of the facility picker.
+const FACILITY_HELP_TEXT: &str = "Up/Down: move  Space: show or hide  Enter: apply  Esc: cancel";
+
+/// Options for running the interface, from the command line.
+#[derive(Debug, Clone)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct Options {
+    /// Restore the tab and selection from the last run once data arrives.
+    pub restore: bool,
+    /// Use the on-disk status cache.
+    pub use_cache: bool,
+    /// Save bandwidth at the cost of less frequent updates.
+    pub low_bandwidth: bool,
+    /// User agent to send with requests.
+    pub user_agent: HeaderValue,
+    /// Pause refreshing after this long without input.
+    pub idle_timeout: Option<Duration>,
+    /// Directory to save exported summaries to.
+    pub summary_dir: Option<PathBuf>,
+    /// Filter to start with.
+    pub query: Option<Query>,
+    /// Only show pilots flying for these airlines.
+    pub airlines: Option<AirlineFilter>,
+    /// Sorts to start with and reset to.
+    pub sorts: Vec<DefaultSort>,
+    /// Room each table row gets to start with.
+    pub density: Density,
+    /// Records not updated for longer than this are dimmed.
+    pub stale_after: Option<chrono::Duration>,
+    /// Past these values a pilot's record is taken to be broken.
+    pub sanity_limits: Limits,
+    /// How much of each client's name is shown.
+    pub privacy: Privacy,
+    /// Show the data a running instance fetches instead of fetching it.
+    pub attach: bool,
+    /// Extra columns from the config file.
+    pub custom_columns: CustomColumns,
+    /// Which alerts ring the bell.
+    pub alerts: AlertSettings,
+    /// What alerts sound like.
+    pub alert_sound: AlertSound,
+    /// V3 mirror to use.
+    pub mirror: MirrorSelection,
+    /// Time zone to show times in.
+    pub zone: DisplayZone,
+    /// Give up on the first data after this long, if ever.
+    pub startup_timeout: Option<Duration>,
+    /// Where to save raw V3 bodies, if anywhere.
+    pub dumps: Option<DumpSettings>,
+    /// Save the session when quitting.
+    pub save_on_exit: bool,
+    /// How often to poll, if not the default.
+    pub poll_interval: Option<PollInterval>,
+    /// Bounds for the adaptive poll interval.
+    pub auto_bounds: AutoBounds,
+    /// Config profile in use, if any.
+    pub profile: Option<String>,
+}
 
 /// Run the terminal interface.
+///
+/// The terminal is set up immediately, and data from the VATSIM API
+/// is fetched on a background thread so that a loading screen can be
+/// shown while the network requests are in progress.
+///
+/// Returns whether the user quit before the first data came in.
 #[allow(clippy::too_many_lines)]
-pub fn run(data: V3ResponseData) -> Result<()> {
-    debug!(
-        "interface::run, {} pilots, {} controllers",
-        data.pilots.len(),
-        data.controllers.len()
-    );
+pub fn run(options: Options) -> Result<bool> {
+    debug!("interface::run, {:?}", options);
 
     // configure terminal
     let mut stdout = std::io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    let mode = terminal::set_up(&mut stdout, true)?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
-    enable_raw_mode()?;
     terminal.hide_cursor()?;
-    let mut app = App::new(data);
-
-    loop {
-        let view_data = app.get_view_data();
-        let _ = terminal.draw(|f| {
-            // general layout
-            let chunks = Layout::default()
-                .direction(Direction::Vertical)
-                .horizontal_margin(1)
-                .constraints([Constraint::Length(3), Constraint::Min(0)].as_ref())
-                .split(f.size());
-
-            // "title row" layout
-            let title_chunks = Layout::default()
-                .direction(Direction::Horizontal)
-                .constraints([
-                    Constraint::Length(32),
-                    Constraint::Min(1),
-                    Constraint::Length((HELP_TEXT.len() + 5).try_into().unwrap()),
-                ])
-                .split(chunks[0]);
-
-            // data sources switcher and help text
-            let tab_header = Paragraph::new(vec![Spans::from(app.tab_header())])
-                .block(Block::default().borders(Borders::ALL).title("Data sources"));
-            f.render_widget(tab_header, title_chunks[0]);
-            f.render_widget(
-                Paragraph::new(Text::from(HELP_TEXT))
-                    .block(Block::default().borders(Borders::ALL).title("Help")),
-                title_chunks[2],
+    let mut app = App::new();
+    if mode == Mode::Plain {
+        app.set_theme(Theme::mono());
+    }
+    app.set_idle_timeout(options.idle_timeout);
+    app.set_default_sorts(&options.sorts);
+    app.set_density(options.density);
+    app.set_stale_after(options.stale_after);
+    app.set_sanity_limits(options.sanity_limits);
+    app.set_privacy(options.privacy);
+    app.set_custom_columns(options.custom_columns);
+    app.set_zone(options.zone);
+    app.set_profile(options.profile.clone());
+    app.set_startup_timeout(options.startup_timeout);
+    if let Some(filter) = options.query {
+        app.set_filter(filter);
+    }
+    app.set_airline_filter(options.airlines);
+    let loaded = session::load();
+    if let Some(warning) = loaded.warning {
+        app.set_status(warning);
+    }
+    let session = loaded.value;
+    if let Some(session) = &session {
+        app.set_column_widths(session.column_widths.clone());
+        app.set_hidden_facilities(&session.hidden_facilities);
+    }
+    if session::needs_tour(session.as_ref()) {
+        app.show_tour();
+    }
+    if let (true, Some(session)) = (options.restore, session) {
+        app.restore_session(session);
+    }
+    // without the lock, another instance is running and saves the session,
+    // and shares what it fetches with instances attached to it
+    let (instance_lock, save_session, sharing) = match (lock::acquire(), shared::path()) {
+        (Ok(Some(lock)), path) => {
+            if options.attach {
+                app.set_status("No other instance is running to attach to".to_owned());
+            }
+            (
+                Some(lock),
+                true,
+                path.map_or(Sharing::Off, Sharing::Publish),
+            )
+        }
+        (Ok(None), Some(path)) if options.attach => {
+            app.set_status(
+                "Attached to the running instance; showing the data it fetches".to_owned(),
             );
+            (None, false, Sharing::Attach(path))
+        }
+        (Ok(None), _) => {
+            app.set_status(
+                "Another instance is running; this one won't save its session".to_owned(),
+            );
+            (None, false, Sharing::Off)
+        }
+        (Err(e), _) => {
+            warn!("Could not take instance lock: {}", e);
+            (None, true, Sharing::Off)
+        }
+    };
+    let dump_dir = options.dumps.as_ref().map(|dumps| dumps.dir.clone());
+    let worker = Worker::spawn(WorkerOptions {
+        use_cache: options.use_cache,
+        low_bandwidth: options.low_bandwidth,
+        user_agent: options.user_agent,
+        mirror: options.mirror,
+        dumps: options.dumps,
+        sharing,
+    });
+    let mut poller = Poller::new(
+        options.poll_interval,
+        default_refresh(options.low_bandwidth),
+        options.auto_bounds,
+    );
+    if poller.is_auto() {
+        app.set_poll_interval(Some(poller.interval()));
+    }
+    let mut alerter = Alerter::new(options.alerts, options.alert_sound);
+    worker.request();
+    worker.request_events();
+    let mut last_request = Instant::now();
+    let mut last_events_request = Instant::now();
+    let mut waiting = true;
+    let mut abandoned = Abandoned::default();
 
-            // table
-            let header_cells = view_data.headers.iter().map(|&h| Cell::from(h));
-            let header = Row::new(header_cells).style(*NORMAL_STYLE).height(1);
-            let rows = view_data
-                .data
-                .iter()
-                .map(|items| Row::new(items.iter().map(|c| Cell::from(c.clone()))));
-            let table = Table::new(rows)
-                .header(header)
-                .block(
-                    Block::default()
-                        .borders(Borders::ALL)
-                        .title(view_data.title),
-                )
-                .widths(&[
-                    Constraint::Percentage(25),
-                    Constraint::Percentage(25),
-                    Constraint::Percentage(25),
-                    Constraint::Percentage(25),
-                ])
-                .highlight_style(*SELECTED_STYLE)
-                .highlight_symbol(">> ");
-            f.render_stateful_widget(table, chunks[1], app.current_table_state());
-
-            // popup
-            if view_data.show_popup {
-                let area = centered_rect(70, 50, f.size());
-                f.render_widget(Clear, area);
-                f.render_widget(popup_text(&view_data.selected_row_data), area);
+    // quitting while loading cancels
+    let cancelled = loop {
+        match worker.try_recv() {
+            Some(Message::Data(result, _)) if !abandoned.keep(&result) => {
+                if let Err(e) = result {
+                    debug!("Ignoring failure of an abandoned fetch: {}", e);
+                }
             }
-        })?;
-
-        // key press handlers
-        if let Event::Key(key) = event::read()? {
-            match key.code {
-                KeyCode::Char('q') => break,
-                KeyCode::Down => {
-                    if !view_data.show_popup {
-                        app.down();
-                    }
+            Some(Message::Data(result, stats)) => {
+                waiting = false;
+                app.set_fetch_stats(stats);
+                app.reload_favorites();
+                if result.is_ok() {
+                    app.refresh_succeeded();
                 }
-                KeyCode::Up => {
-                    if !view_data.show_popup {
-                        app.up();
+                match result {
+                    Ok(Fetched::Data(data)) => {
+                        debug!(
+                            "Got data, {} pilots, {} controllers",
+                            data.pilots.len(),
+                            data.controllers.len()
+                        );
+                        if let Some(rate) = app.change_rate(&data) {
+                            observe(&mut app, &mut poller, rate, polling::reload_time(&data));
+                        }
+                        app.set_data(data);
+                        alerter.alert(&app.take_alerts(), Instant::now());
                     }
-                }
-                KeyCode::Tab => {
-                    if !view_data.show_popup {
-                        app.tab_over();
+                    Ok(Fetched::Unchanged) => {
+                        debug!("Data unchanged");
+                        // nothing changed, so the network is quiet
+                        if app.is_loaded() {
+                            observe(&mut app, &mut poller, 0.0, None);
+                        }
                     }
-                }
-                KeyCode::PageDown => {
-                    if !view_data.show_popup {
-                        app.page_down();
+                    Err(e) if app.is_loaded() => {
+                        // keep showing the old data; the next refresh tries again
+                        warn!("Could not refresh VATSIM data: {}", e);
+                        app.refresh_failed(&e);
                     }
-                }
-                KeyCode::PageUp => {
-                    if !view_data.show_popup {
-                        app.page_up();
+                    Err(e) => {
+                        error!("Could not get VATSIM data: {}", e);
+                        app.set_error(&e);
                     }
                 }
-                KeyCode::Enter => app.toggle_popup(true),
-                KeyCode::Esc => app.toggle_popup(false),
-                KeyCode::Char('o') => {
-                    let cid = match view_data.selected_row_data {
-                        SelectedRow::Pilot(p) => p.cid,
-                        SelectedRow::Controller(c) => c.cid,
-                    };
-                    if let Err(e) =
-                        webbrowser::open(&format!("https://stats.vatsim.net/stats/{}", cid))
-                    {
-                        error!("Could not open web browser: {}", e);
+            }
+            Some(Message::Events(Ok(events))) => {
+                debug!("Got {} events", events.len());
+                app.set_events(events);
+            }
+            // keep showing the old events; the next refresh tries again
+            Some(Message::Events(Err(e))) => warn!("Could not get VATSIM events: {}", e),
+            None => {}
+        }
+        if app.check_startup_deadline(Instant::now()) {
+            warn!("Timed out waiting for VATSIM data");
+            abandoned.add();
+            waiting = false;
+        }
+        app.check_idle(Instant::now());
+        if !app.is_idle() && last_events_request.elapsed() >= EVENTS_REFRESH_INTERVAL {
+            worker.request_events();
+            last_events_request = Instant::now();
+        }
+        if !waiting
+            && app.is_loaded()
+            && !app.is_idle()
+            && last_request.elapsed() >= poller.interval()
+        {
+            worker.request();
+            last_request = Instant::now();
+            waiting = true;
+        }
+
+        let view_data = app.get_view_data();
+        let mut too_small = false;
+        let _ = terminal.draw(|f| {
+            // redrawn every time round, so it follows the resizing and the
+            // interface appears as soon as it fits
+            too_small = !draw_screen(f, &mut app, &view_data);
+            if mode == Mode::Plain {
+                f.render_widget(Plain, f.size());
+            }
+        })?;
+
+        if !event::poll(EVENT_POLL_TIMEOUT)? {
+            continue;
+        }
+
+        let event = event::read()?;
+        if too_small {
+            // only quitting works until there's room; data keeps coming in
+            // underneath, and the filters and tab are as they were
+            if quits_while_too_small(&event) {
+                break view_data.load_state == LoadState::Loading;
+            }
+            continue;
+        }
+        if matches!(event, Event::Key(_) | Event::Mouse(_) | Event::Paste(_)) {
+            app.record_input(Instant::now());
+        }
+        if matches!(event, Event::Key(_)) && app.dismiss_tour() {
+            // the key only closes the tour, which isn't shown again
+            if let Err(e) = session::mark_onboarded() {
+                warn!("Could not save that the tour was seen: {}", e);
+            }
+            continue;
+        }
+        if app.take_resume() {
+            // the input only wakes the interface up
+            if !waiting {
+                worker.request();
+                last_request = Instant::now();
+                waiting = true;
+            }
+            continue;
+        }
+
+        if let Event::Paste(text) = &event {
+            app.paste(text);
+            continue;
+        }
+        let Some(action) = action::map_event(&app, &event) else {
+            continue;
+        };
+        match app.update(action) {
+            Some(Effect::Quit) => break view_data.load_state == LoadState::Loading,
+            Some(Effect::Fetch) => {
+                worker.request();
+                last_request = Instant::now();
+                waiting = true;
+            }
+            Some(Effect::NextMirror) => {
+                worker.request_next_mirror();
+                last_request = Instant::now();
+                waiting = true;
+            }
+            Some(Effect::ExportSummary) => {
+                export_summary(&mut app, &view_data, options.summary_dir.as_deref());
+            }
+            Some(Effect::ExportTrail) => {
+                export_trail(&mut app, &view_data, options.summary_dir.as_deref());
+            }
+            Some(Effect::OpenStats) => {
+                let url = match view_data.selected_row_data {
+                    Some(SelectedRow::Pilot(p)) => {
+                        format!("https://stats.vatsim.net/stats/{}", p.cid)
                     }
+                    Some(SelectedRow::Controller(c)) => {
+                        format!("https://stats.vatsim.net/stats/{}", c.cid)
+                    }
+                    Some(SelectedRow::Event(e)) => e.link,
+                    None => continue,
+                };
+                if let Err(e) = webbrowser::open(&url) {
+                    error!("Could not open web browser: {}", e);
                 }
-                _ => {}
             }
+            None => {}
+        }
+    };
+
+    // the terminal stays up until everything is saved
+    stop_worker(worker, dump_dir.as_deref(), SHUTDOWN_TIMEOUT);
+    if app.is_loaded() && save_session && options.save_on_exit {
+        if let Err(e) = session::save(&app.session()) {
+            warn!("Could not save session: {}", e);
         }
     }
+    drop(instance_lock);
 
     // exit, restore terminal
     disable_raw_mode()?;
     execute!(
         terminal.backend_mut(),
         LeaveAlternateScreen,
-        DisableMouseCapture
+        DisableMouseCapture,
+        DisableBracketedPaste
     )?;
     terminal.show_cursor()?;
-    Ok(())
+    Ok(cancelled)
+}
+
+/// Adjust an adaptive poll interval to a change rate, showing the new
+/// interval in the title.
+fn observe(app: &mut App, poller: &mut Poller, rate: f64, reload: Option<Duration>) {
+    poller.observe(rate, reload);
+    if poller.is_auto() {
+        debug!(
+            "Change rate {:.3}, polling every {:?}",
+            rate,
+            poller.interval()
+        );
+        app.set_poll_interval(Some(poller.interval()));
+    }
+}
+
+/// Stop the worker, waiting up to `timeout` for a fetch in progress, then
+/// remove any body it was stopped partway through saving.
+fn stop_worker(worker: Worker, dump_dir: Option<&Path>, timeout: Duration) {
+    if !worker.shutdown(timeout) {
+        warn!("Worker thread didn't stop in time");
+    }
+    if let Some(dir) = dump_dir {
+        match dump::discard_partial(dir) {
+            Ok(0) => {}
+            Ok(count) => debug!("Discarded {} partly-saved bodies", count),
+            Err(e) => warn!("Could not discard partly-saved bodies: {}", e),
+        }
+    }
+}
+
+/// Copy a summary of the client in the popup to the clipboard, and save
+/// it if a summary directory is set, reporting how it went in the status.
+fn export_summary(app: &mut App, view_data: &ViewData, summary_dir: Option<&Path>) {
+    let (callsign, text) = match &view_data.selected_row_data {
+        Some(SelectedRow::Pilot(p)) => (&p.callsign, summary::pilot(p, view_data.zone)),
+        Some(SelectedRow::Controller(c)) => (&c.callsign, summary::controller(c, view_data.zone)),
+        Some(SelectedRow::Event(e)) => (&e.name, summary::event(e, view_data.zone)),
+        None => return,
+    };
+    let mut messages = Vec::new();
+    match summary::copy_to_clipboard(&text) {
+        Ok(()) => messages.push(format!("Copied summary of {}", callsign)),
+        Err(e) => warn!("Could not copy summary: {}", e),
+    }
+    if let Some(dir) = summary_dir {
+        match summary::save(dir, callsign, &text) {
+            Ok(path) => messages.push(format!("saved to {}", path.display())),
+            Err(e) => messages.push(format!("{:#}", e)),
+        }
+    }
+    app.set_status(messages.join("; "));
+}
+
+/// Copy the trail of the pilot in the popup to the clipboard as GeoJSON,
+/// and save it if a summary directory is set, reporting how it went in
+/// the status.
+fn export_trail(app: &mut App, view_data: &ViewData, summary_dir: Option<&Path>) {
+    let feature = match (&view_data.selected_row_data, &view_data.trail) {
+        (Some(SelectedRow::Pilot(pilot)), Some(trail)) => {
+            trail::geojson(&pilot.callsign, trail).map(|feature| (pilot, feature))
+        }
+        _ => None,
+    };
+    let Some((pilot, feature)) = feature else {
+        app.set_status("No trail for this row yet".to_owned());
+        return;
+    };
+    let text = serde_json::to_string_pretty(&feature).unwrap_or_default();
+    let mut messages = Vec::new();
+    match summary::copy_to_clipboard(&text) {
+        Ok(()) => messages.push(format!("Copied trail of {}", pilot.callsign)),
+        Err(e) => warn!("Could not copy trail: {}", e),
+    }
+    if let Some(dir) = summary_dir {
+        match summary::save_as(dir, &pilot.callsign, "geojson", &text) {
+            Ok(path) => messages.push(format!("saved to {}", path.display())),
+            Err(e) => messages.push(format!("{:#}", e)),
+        }
+    }
+    app.set_status(messages.join("; "));
+}
+
+/// Draw a line summarizing the filters at the top of the area, if there
+/// are any, and return the rest of the area.
+fn draw_filter_summary<B: Backend>(f: &mut Frame<B>, area: Rect, summary: Option<&str>) -> Rect {
+    let Some(summary) = summary else {
+        return area;
+    };
+    let areas = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(0)])
+        .split(area);
+    f.render_widget(
+        Paragraph::new(summary).style(Style::default().fg(Color::Gray)),
+        areas[0],
+    );
+    areas[1]
+}
+
+/// Draw the interface for a single frame.
+fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App, view_data: &ViewData) {
+    app.hit_map_mut().clear();
+    // general layout, with a taller header for the refresh error panel
+    let header_height = if view_data.refresh_error.is_some() {
+        u16::try_from(REFRESH_ERROR_LINES).unwrap_or(u16::MAX) + 4
+    } else {
+        3
+    };
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .horizontal_margin(1)
+        .constraints([Constraint::Length(header_height), Constraint::Min(0)].as_ref())
+        .split(f.size());
+    if let Some(error) = &view_data.refresh_error {
+        f.render_widget(refresh_error_text(error, chunks[0].width), chunks[0]);
+    } else {
+        draw_title_row(f, chunks[0], app, view_data);
+    }
+
+    // loading and error screens take the place of the table
+    match &view_data.load_state {
+        LoadState::Loading => {
+            let frame = view_data.loading_for.as_millis() / EVENT_POLL_TIMEOUT.as_millis();
+            #[allow(clippy::cast_possible_truncation)]
+            let spinner = SPINNER[frame as usize % SPINNER.len()];
+            let text = format!(
+                "{} {}\n\n{}s elapsed. Press Esc or Q to cancel.",
+                spinner,
+                LOADING_TEXT,
+                view_data.loading_for.as_secs()
+            );
+            f.render_widget(status_screen("Loading", &text), chunks[1]);
+            return;
+        }
+        LoadState::Failed(message) => {
+            let text = format!(
+                "Could not get data from VATSIM:\n\n{}\n\nPress R to retry, or Q to exit.",
+                message
+            );
+            f.render_widget(status_screen("Error", &text), chunks[1]);
+            return;
+        }
+        LoadState::Loaded => {}
+    }
+
+    let table_area = draw_filter_summary(f, chunks[1], view_data.filter_summary.as_deref());
+    if let Some(split) = &view_data.split {
+        draw_split(f, table_area, split);
+    } else {
+        draw_table(f, table_area, app, view_data);
+    }
+
+    // popup
+    if view_data.show_popup {
+        if let Some(selected) = &view_data.selected_row_data {
+            let area = centered_rect(70, 50, f.size());
+            f.render_widget(Clear, area);
+            f.render_widget(popup_text(selected, view_data), area);
+        }
+    }
+    if let Some(builder) = &view_data.filter_builder {
+        let area = centered_rect(70, 50, f.size());
+        f.render_widget(Clear, area);
+        f.render_widget(filter_builder_text(builder), area);
+    }
+    if let Some(stats) = &view_data.debug_stats {
+        let height = 12 + u16::try_from(stats.mirror_latencies.len()).unwrap_or(u16::MAX);
+        let area = Rect {
+            x: f.size().width.saturating_sub(34),
+            y: f.size().height.saturating_sub(height + 1),
+            width: 34.min(f.size().width),
+            height: height.min(f.size().height),
+        };
+        f.render_widget(Clear, area);
+        f.render_widget(
+            debug_text(
+                stats,
+                view_data.retained_entries,
+                view_data.profile.as_deref(),
+            ),
+            area,
+        );
+    }
+    if let Some(log_view) = &view_data.log_view {
+        draw_log_panel(f, log_view);
+    }
+    if let Some(search) = &view_data.search {
+        let area = centered_rect(70, 30, f.size());
+        f.render_widget(Clear, area);
+        f.render_widget(search_text(search), area);
+    }
+    if let Some(tune) = &view_data.tune {
+        let area = centered_rect(50, 30, f.size());
+        f.render_widget(Clear, area);
+        f.render_widget(tune_text(tune), area);
+    }
+    if let Some(picker) = &view_data.facility_picker {
+        let area = centered_rect(40, 60, f.size());
+        f.render_widget(Clear, area);
+        f.render_widget(facility_picker_text(picker), area);
+    }
+    if view_data.idle {
+        draw_idle(f);
+    }
+}
+
+/// Draw the interface, with the tour over it, if it fits in the frame,
+/// and otherwise ask for a bigger terminal. Whether the interface fit.
+fn draw_screen<B: Backend>(f: &mut Frame<B>, app: &mut App, view_data: &ViewData) -> bool {
+    if !fits(f.size()) {
+        draw_too_small(f);
+        return false;
+    }
+    draw(f, app, view_data);
+    // over the loading and error screens too
+    if view_data.tour {
+        draw_tour(f);
+    }
+    true
+}
+
+/// Whether an event quits while the terminal is too small; nothing else
+/// does anything until the interface fits.
+fn quits_while_too_small(event: &Event) -> bool {
+    matches!(
+        event,
+        Event::Key(key) if matches!(key.code, KeyCode::Char('q' | 'Q') | KeyCode::Esc)
+    )
+}
+
+/// Whether the interface fits in an area.
+fn fits(area: Rect) -> bool {
+    area.width >= MIN_WIDTH && area.height >= MIN_HEIGHT
+}
+
+/// Ask for a bigger terminal in place of the interface, with its size
+/// now and the size needed.
+fn draw_too_small<B: Backend>(f: &mut Frame<B>) {
+    let size = f.size();
+    // the sizes first and short, so they show in even the tiniest terminal
+    let text = format!(
+        "{}x{}, needs {}x{}\n\nThe terminal is too small. Make the window bigger, or press Q to exit.",
+        size.width, size.height, MIN_WIDTH, MIN_HEIGHT,
+    );
+    // no borders, so as much as possible fits in a tiny terminal
+    f.render_widget(
+        Paragraph::new(text)
+            .alignment(Alignment::Center)
+            .wrap(Wrap { trim: true }),
+        size,
+    );
+}
+
+/// Draw the tab selector, status, and help text across the top.
+fn draw_title_row<B: Backend>(f: &mut Frame<B>, area: Rect, app: &mut App, view_data: &ViewData) {
+    // "title row" layout, with the tab selector sized to its labels
+    let tab_spans = Spans::from(app.tab_header());
+    let tab_width = u16::try_from(tab_spans.width() + 3).unwrap_or(u16::MAX);
+    let title_chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Length(tab_width.max(32)), Constraint::Min(1)])
+        .split(area);
+    // the status gets a share of what's left while there is one, and the
+    // help text the rest
+    let status_width = if view_data.status.is_some() {
+        Constraint::Percentage(STATUS_SHARE)
+    } else {
+        Constraint::Length(0)
+    };
+    let text_chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([status_width, Constraint::Min(1)])
+        .split(title_chunks[1]);
+
+    // data sources switcher and help text
+    let tab_regions = tab_regions(&tab_spans, title_chunks[0]);
+    let sources_title = view_data.profile.as_ref().map_or_else(
+        || "Data sources".to_owned(),
+        |profile| format!("Data sources ({})", profile),
+    );
+    let tab_header = Paragraph::new(vec![tab_spans])
+        .block(Block::default().borders(Borders::ALL).title(sources_title));
+    f.render_widget(tab_header, title_chunks[0]);
+    for (area, region) in tab_regions {
+        app.hit_map_mut().add(area, region);
+    }
+    if let Some(status) = &view_data.status {
+        f.render_widget(
+            Paragraph::new(Text::from(status.as_str()))
+                .block(Block::default().borders(Borders::ALL).title("Status")),
+            text_chunks[0],
+        );
+    }
+    f.render_widget(
+        Paragraph::new(Text::from(HELP_TEXT))
+            .block(Block::default().borders(Borders::ALL).title("Help"))
+            .wrap(Wrap { trim: true }),
+        text_chunks[1],
+    );
+}
+
+/// Construct the panel shown in place of the title row while refreshes
+/// keep failing, cut to fit the width so it never spills over.
+fn refresh_error_text(error: &RefreshError, width: u16) -> Paragraph<'static> {
+    let width = usize::from(width.saturating_sub(2));
+    let mut lines: Vec<String> = format::wrap(&error.message, width);
+    if lines.len() > REFRESH_ERROR_LINES {
+        lines.truncate(REFRESH_ERROR_LINES);
+        if let Some(last) = lines.last_mut() {
+            last.push_str(" \u{2026}");
+        }
+    }
+    if !error.mirrors_tried.is_empty() {
+        let hosts: Vec<&str> = error
+            .mirrors_tried
+            .iter()
+            .map(|url| mirror_host(url))
+            .collect();
+        lines.push(format!("Mirrors tried: {}", hosts.join(", ")));
+    }
+    lines.push(REFRESH_ERROR_HELP_TEXT.to_owned());
+    let text: Vec<Spans> = lines
+        .iter()
+        .map(|line| Spans::from(format::truncate(line, width)))
+        .collect();
+    let title = format!("Refresh failed {} times in a row", error.failures);
+    Paragraph::new(text).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Red))
+            .title(format::truncate(&title, width)),
+    )
+}
+
+/// Show the first-run tour over everything else.
+fn draw_tour<B: Backend>(f: &mut Frame<B>) {
+    let area = centered_rect(70, 70, f.size());
+    let text = format!("{}\nPress any key to start.", tour::text());
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(text)
+            .block(Block::default().borders(Borders::ALL).title("Welcome"))
+            .wrap(Wrap { trim: false }),
+        area,
+    );
+}
+
+/// Show the recent log lines, the newest at the bottom.
+fn draw_log_panel<B: Backend>(f: &mut Frame<B>, view: &LogView) {
+    let area = centered_rect(90, 80, f.size());
+    let title = format!(
+        "Log: {} and above{}  Up/Down/PgUp/PgDn: scroll  End: follow  V: level  Esc: close",
+        view.min_level,
+        if view.following { ", following" } else { "" }
+    );
+    let height = usize::from(area.height.saturating_sub(2));
+    let start = view.lines.len().saturating_sub(height);
+    let lines: Vec<Spans> = view.lines[start..]
+        .iter()
+        .map(|line| {
+            Spans::from(Span::styled(
+                line.text.as_str(),
+                log_level_style(line.level),
+            ))
+        })
+        .collect();
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(title)),
+        area,
+    );
+}
+
+/// Style of a log line at a level.
+fn log_level_style(level: Level) -> Style {
+    match level {
+        Level::Error => Style::default().fg(Color::Red),
+        Level::Warn => Style::default().fg(Color::Yellow),
+        Level::Info => Style::default(),
+        Level::Debug => Style::default().fg(Color::Gray),
+        Level::Trace => Style::default().fg(Color::DarkGray),
+    }
+}
+
+/// Dim the screen and show that refreshing is paused.
+fn draw_idle<B: Backend>(f: &mut Frame<B>) {
+    f.render_widget(
+        Block::default().style(Style::default().add_modifier(Modifier::DIM)),
+        f.size(),
+    );
+    // big enough for all three lines of text at the smallest size
+    let area = centered_rect(50, 50, f.size());
+    f.render_widget(Clear, area);
+    f.render_widget(status_screen("Idle", IDLE_TEXT), area);
+}
+
+/// Draw the data table, with as many columns as fit in the area.
+fn draw_table<B: Backend>(f: &mut Frame<B>, area: Rect, app: &mut App, view_data: &ViewData) {
+    let widths = &view_data.widths;
+    let available = area.width.saturating_sub(TABLE_CHROME_WIDTH);
+    app.set_max_column_offset(columns::max_offset(widths, available));
+    let visible = columns::visible_columns(widths, view_data.column_offset, available);
+    let all_visible = visible.len() == widths.len();
+    let title = if all_visible {
+        view_data.title.clone()
+    } else {
+        format!(
+            "{} (cols {}\u{2013}{} of {})",
+            view_data.title,
+            visible.get(1).map_or(1, |&i| i + 1),
+            visible.last().map_or(1, |&i| i + 1),
+            widths.len()
+        )
+    };
+    let drawn = columns::drawn_widths(widths, &visible, available);
+    let constraints: Vec<Constraint> = drawn.iter().map(|&w| Constraint::Length(w)).collect();
+    let header_cells = visible.iter().map(|&i| {
+        let header = view_data.columns[i].header;
+        if i == view_data.sort.column {
+            let arrow = if view_data.sort.descending {
+                "\u{25bc}"
+            } else {
+                "\u{25b2}"
+            };
+            Cell::from(format!("{} {}", header, arrow))
+        } else {
+            Cell::from(header)
+        }
+    });
+    let header = Row::new(header_cells).style(*NORMAL_STYLE).height(1);
+    let has_selection = app.current_table_state().selected().is_some();
+    let header_areas = column_header_areas(area, &drawn, has_selection);
+    for (&column, &header_area) in visible.iter().zip(&header_areas) {
+        app.hit_map_mut().add(header_area, Region::Column(column));
+    }
+    // only the rows on screen were built, for as many as fit last frame
+    let fit = area.height.saturating_sub(TABLE_CHROME_HEIGHT) / view_data.row_height.max(1);
+    app.set_table_fit(usize::from(fit));
+    let mut state = TableState::default();
+    state.select(
+        app.current_table_state()
+            .selected()
+            .map(|selected| selected.saturating_sub(view_data.first_row)),
+    );
+    let rows = view_data.data.iter().map(|row| {
+        Row::new(visible.iter().map(|&i| {
+            let cell = row.cells.get(i).cloned().unwrap_or_default();
+            let cell = match &row.detail {
+                Some((column, detail)) if *column == i => Cell::from(Text::from(vec![
+                    Spans::from(cell),
+                    Spans::from(Span::styled(detail.clone(), *DETAIL_STYLE)),
+                ])),
+                _ => Cell::from(cell),
+            };
+            match row.cell_styles.iter().find(|(column, _)| *column == i) {
+                Some(&(_, style)) => cell.style(style),
+                None => cell,
+            }
+        }))
+        .style(row.style)
+        .height(view_data.row_height)
+    });
+    let table = Table::new(rows)
+        .header(header)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .widths(&constraints)
+        .highlight_style(*SELECTED_STYLE)
+        .highlight_symbol(HIGHLIGHT_SYMBOL);
+    f.render_stateful_widget(table, area, &mut state);
+}
+
+/// Draw the airport split: inbound flights on the left, outbound on the
+/// right, with the pane keys act on highlighted.
+fn draw_split<B: Backend>(f: &mut Frame<B>, area: Rect, split: &SplitPanes) {
+    let halves = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+        .split(area);
+    let panes = [
+        (Pane::Inbound, columns::INBOUND_COLUMNS),
+        (Pane::Outbound, columns::OUTBOUND_COLUMNS),
+    ];
+    for ((pane, columns), &half) in panes.into_iter().zip(halves.iter()) {
+        let rows = &split.rows[pane.index()];
+        let focused = pane == split.focus;
+        let title = format!("{} {} ({})", pane.title(), split.airport, rows.len());
+        let border_style = if focused {
+            *NORMAL_STYLE
+        } else {
+            Style::default().fg(Color::DarkGray)
+        };
+        let constraints: Vec<Constraint> = columns
+            .iter()
+            .map(|column| Constraint::Length(column.width))
+            .collect();
+        let header = Row::new(columns.iter().map(|column| Cell::from(column.header)))
+            .style(*NORMAL_STYLE)
+            .height(1);
+        let mut state = TableState::default();
+        if focused && !rows.is_empty() {
+            state.select(Some(split.selected[pane.index()]));
+        }
+        let table = Table::new(rows.iter().map(|cells| Row::new(cells.clone())))
+            .header(header)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(border_style)
+                    .title(title),
+            )
+            .widths(&constraints)
+            .highlight_style(*SELECTED_STYLE)
+            .highlight_symbol(HIGHLIGHT_SYMBOL);
+        f.render_stateful_widget(table, half, &mut state);
+    }
+}
+
+/// Where each tab's label is in the bordered tab header.
+///
+/// The labels are every other span after the leading padding, with
+/// separators between them.
+fn tab_regions(spans: &Spans, area: Rect) -> Vec<(Rect, Region)> {
+    let mut regions = Vec::new();
+    let mut x = area.x.saturating_add(1);
+    let right = area.right().saturating_sub(1);
+    for (i, span) in spans.0.iter().enumerate() {
+        let width = u16::try_from(span.width()).unwrap_or(u16::MAX);
+        if i % 2 == 1 {
+            let shown = width.min(right.saturating_sub(x));
+            regions.push((Rect::new(x, area.y + 1, shown, 1), Region::Tab(i / 2)));
+        }
+        x = x.saturating_add(width);
+    }
+    regions
+}
+
+/// Where the table draws each visible column's header cell, placed one
+/// after another inside its borders the same way the table widget does.
+fn column_header_areas(area: Rect, widths: &[u16], has_selection: bool) -> Vec<Rect> {
+    let inner = Rect::new(
+        area.x.saturating_add(1),
+        area.y.saturating_add(1),
+        area.width.saturating_sub(2),
+        area.height.saturating_sub(2).min(1),
+    );
+    let mut x = inner.x;
+    if has_selection {
+        let highlight: u16 = HIGHLIGHT_SYMBOL.len().try_into().unwrap_or(0);
+        x = x.saturating_add(highlight.min(inner.width));
+    }
+    widths
+        .iter()
+        .map(|&width| {
+            let cell = Rect::new(
+                x,
+                inner.y,
+                width.min(inner.right().saturating_sub(x)),
+                inner.height,
+            );
+            x = x
+                .saturating_add(width)
+                .saturating_add(columns::COLUMN_SPACING);
+            cell
+        })
+        .collect()
+}
+
+/// Construct a bordered block of centered text, used in place of the table.
+fn status_screen<'a>(title: &'a str, text: &'a str) -> Paragraph<'a> {
+    Paragraph::new(Text::from(text))
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .alignment(Alignment::Center)
+        .wrap(Wrap { trim: false })
 }
 
 /// Helper function to create a centered rect using up certain percentage of the available rect `r`.
@@ -194,23 +1074,191 @@ fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
         .split(popup_layout[1])[1]
 }
 
+/// A popup line comparing a filed value to the current one, with the
+/// difference colored if it's large.
+fn filed_comparison_line(label: &str, comparison: FiledComparison) -> Spans<'static> {
+    let mut spans = vec![Span::raw(format!("{}: {}", label, comparison.text))];
+    if let Some(delta) = comparison.delta {
+        let style = if comparison.notable {
+            Style::default().fg(Color::Yellow)
+        } else {
+            Style::default()
+        };
+        spans.push(Span::raw(" "));
+        spans.push(Span::styled(delta, style));
+    }
+    Spans::from(spans)
+}
+
+/// Popup lines with a flight plan's enroute and fuel times, warning if the
+/// fuel leaves less than the reserve.
+fn filed_times_lines(plan: &FlightPlan) -> Vec<Spans<'static>> {
+    let time = |value: &str| {
+        flight::parse_filed_duration(value).map_or_else(|| "\u{2014}".to_owned(), format::duration)
+    };
+    let mut fuel = vec![Span::raw(format!("Fuel: {}", time(&plan.fuel_time)))];
+    if let (Some(margin), Some(warning)) = (flight::fuel_margin(plan), flight::fuel_warning(plan)) {
+        let text = match warning {
+            FuelWarning::Short => {
+                format!("{} short of the enroute time", format::duration(-margin))
+            }
+            FuelWarning::Tight => format!(
+                "{} spare, under the {} min reserve",
+                format::duration(margin),
+                flight::FUEL_RESERVE_MINUTES
+            ),
+        };
+        fuel.push(Span::raw(" "));
+        fuel.push(Span::styled(
+            format!("{} ({})", warning.badge(), text),
+            Style::default().fg(Color::Yellow),
+        ));
+    }
+    vec![
+        Spans::from(format!("Enroute: {}", time(&plan.enroute_time))),
+        Spans::from(fuel),
+    ]
+}
+
+/// A pilot's phase, distance and ETA to the arrival airport, how long
+/// they've been online, and why their record looks broken if it does.
+fn enriched_text(enriched: &EnrichedPilot, zone: DisplayZone) -> String {
+    let remaining = match (enriched.distance_remaining_nm, enriched.eta) {
+        (Some(nm), Some(eta)) => format!(
+            "{} nm, ETA {}",
+            format::whole(nm),
+            format::format_time(eta, zone, TimeStyle::Clock)
+        ),
+        (Some(nm), None) => format!("{} nm", format::whole(nm)),
+        _ => "\u{2014}".to_owned(),
+    };
+    let bad_data = enriched
+        .bad_data
+        .map_or_else(String::new, |bad_data| format!("\nBad data: {}", bad_data));
+    format!(
+        "Phase: {}\nRemaining: {}\nOnline for: {}{}",
+        enriched.phase.name(),
+        remaining,
+        enriched
+            .online_for
+            .map_or_else(|| "\u{2014}".to_owned(), format::duration),
+        bad_data
+    )
+}
+
+/// A pilot's latest altitude and groundspeed readings, and how far and
+/// how fast they've gone this session.
+fn trail_text(trail: &Trail) -> String {
+    let readings: Vec<String> = trail
+        .points()
+        .map(|point| format!("{}/{}", point.altitude, point.groundspeed))
+        .collect();
+    let recent = &readings[readings.len().saturating_sub(TRAIL_READINGS)..];
+    format!(
+        "Trail (ft/kt): {}\nThis session: {} nm, average {}",
+        recent.join(" "),
+        format::whole(trail.distance_nm()),
+        trail
+            .average_groundspeed()
+            .map_or_else(|| "\u{2014}".to_owned(), |knots| format!("{:.0} kt", knots))
+    )
+}
+
+/// List the controllers near a pilot, one per line.
+fn nearby_atc_text(nearby_atc: &[NearbyController]) -> String {
+    if nearby_atc.is_empty() {
+        return "no nearby ATC resolved".to_owned();
+    }
+    nearby_atc
+        .iter()
+        .map(|n| {
+            format!(
+                "{}: {} ({} nm)",
+                n.class,
+                n.callsign,
+                format::whole(n.distance_nm)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// List the controllers covering a pilot, most specific first.
+fn covering_atc_text(covering_atc: &[NearbyController]) -> String {
+    if covering_atc.is_empty() {
+        return "UNICOM".to_owned();
+    }
+    nearby_atc_text(covering_atc)
+}
+
 /// Construct the text to be shown in the popup window.
-fn popup_text(data: &SelectedRow) -> Paragraph {
+fn popup_text<'a>(data: &'a SelectedRow, view_data: &ViewData) -> Paragraph<'a> {
     let text = match data {
         SelectedRow::Pilot(p) => {
-            format!(
-                "CID: {}\nServer: {}\nGround speed: {}\nTransponder: {}\nHeading: {}\nLogon time: {}",
-                p.cid, p.server, p.groundspeed, p.transponder, p.heading, p.logon_time
-            )
+            let mut text = Text::from(format!("CID: {}\nServer: {}", p.cid, p.server));
+            text.lines.push(filed_comparison_line(
+                "Altitude",
+                flight::altitude_comparison(p),
+            ));
+            text.lines
+                .push(filed_comparison_line("Speed", flight::speed_comparison(p)));
+            if let Some(plan) = &p.flight_plan {
+                text.lines.extend(filed_times_lines(plan));
+            }
+            if let Some(enriched) = &view_data.enriched_pilot {
+                text.extend(Text::from(enriched_text(enriched, view_data.zone)));
+            }
+            if let Some(route) = route::pilot(p) {
+                text.lines.push(route_line(&route));
+            }
+            if let Some(trail) = &view_data.trail {
+                text.extend(Text::from(trail_text(trail)));
+            }
+            text.extend(Text::from(format!(
+                "Transponder: {}\nHeading: {}\nDescent: {}\nLogon time: {}\nLast updated: {}\n\nNearby ATC:\n{}\n\nCovering ATC:\n{}",
+                p.transponder,
+                p.heading,
+                flight::top_of_descent(p).map_or_else(|| "\u{2014}".to_owned(), |tod| tod.to_string()),
+                format::time_or_raw(p.logon(), &p.logon_time, view_data.zone, TimeStyle::Day),
+                last_updated_text(&p.last_updated, view_data),
+                nearby_atc_text(&view_data.nearby_atc),
+                covering_atc_text(&view_data.covering_atc)
+            )));
+            text
         }
         SelectedRow::Controller(c) => {
-            format!(
-                "CID: {}\nServer: {}\nFrequency: {}\nVisual range: {}\nLogon time: {}",
-                c.cid, c.server, c.frequency, c.visual_range, c.logon_time
-            )
+            let mut text = Text::from(format!(
+                "CID: {}\nPosition: {}\nFacility: {}\nServer: {}\nFrequency: {}\nVisual range: {}\nLogon time: {}\nLast updated: {}\n\nPlausible traffic:\n{}",
+                c.cid,
+                callsign::position_name(&c.callsign),
+                view_data
+                    .facility_name
+                    .clone()
+                    .unwrap_or_else(|| c.facility.to_string()),
+                c.server,
+                frequency::display(&c.frequency),
+                c.visual_range,
+                format::time_or_raw(c.logon(), &c.logon_time, view_data.zone, TimeStyle::Day),
+                last_updated_text(&c.last_updated, view_data),
+                plausible_traffic_text(view_data.plausible_traffic.as_deref())
+            ));
+            if let Some(diff) = &view_data.atis_diff {
+                text.lines.push(Spans::from(""));
+                text.lines.push(Spans::from("ATIS changes:"));
+                text.lines.extend(diff.iter().map(atis_diff_line));
+            }
+            text
         }
+        SelectedRow::Event(e) => Text::from(format!(
+            "Starts: {}\nEnds: {}\nAirports: {}\nOrganisers: {}\nLink: {}",
+            format::time_or_raw(e.start(), &e.start_time, view_data.zone, TimeStyle::Day),
+            format::time_or_raw(e.end(), &e.end_time, view_data.zone, TimeStyle::Day),
+            events::airports_text(e),
+            events::organisers_text(e),
+            e.link
+        )),
     };
-    Paragraph::new(Text::from(text))
+    Paragraph::new(text)
         .block(
             Block::default()
                 .title("Additional information")
@@ -218,3 +1266,861 @@ fn popup_text(data: &SelectedRow) -> Paragraph {
         )
         .wrap(Wrap { trim: false })
 }
+
+/// A pilot's route waypoints, marking the one they're probably near.
+///
+/// The marker is a rough guess, so it's shown as approximate.
+fn route_line(route: &RouteProgress) -> Spans<'static> {
+    let mut spans = vec![Span::raw("Route:")];
+    for (index, waypoint) in route.waypoints.iter().enumerate() {
+        spans.push(Span::raw(" "));
+        if route.near == Some(index) {
+            spans.push(Span::styled(
+                format!("\u{2248}{}", waypoint),
+                Style::default().add_modifier(Modifier::BOLD),
+            ));
+        } else {
+            spans.push(Span::raw(waypoint.clone()));
+        }
+    }
+    Spans::from(spans)
+}
+
+/// A line of a controller's ATIS changes, with removed lines struck
+/// through and added lines highlighted.
+fn atis_diff_line(line: &DiffLine) -> Spans<'static> {
+    match line {
+        DiffLine::Same(text) => Spans::from(format!("  {}", text)),
+        DiffLine::Removed(text) => Spans::from(Span::styled(
+            format!("- {}", text),
+            Style::default().add_modifier(Modifier::DIM | Modifier::CROSSED_OUT),
+        )),
+        DiffLine::Added(text) => Spans::from(Span::styled(
+            format!("+ {}", text),
+            Style::default().fg(Color::Green),
+        )),
+    }
+}
+
+/// Pilots a controller is plausibly working, for the popup.
+fn plausible_traffic_text(callsigns: Option<&[String]>) -> String {
+    match callsigns {
+        None => "  Unknown; the position can't be placed on the map".to_owned(),
+        Some([]) => "  None nearby".to_owned(),
+        Some(callsigns) => format!("  {}: {}", callsigns.len(), callsigns.join(", ")),
+    }
+}
+
+/// When a record was last updated, with how old it was when the data was generated.
+fn last_updated_text(raw: &str, view_data: &ViewData) -> String {
+    let time = format::time_or_raw(parse_time(raw), raw, view_data.zone, TimeStyle::Day);
+    match view_data.record_age {
+        Some(age) => format!("{} ({} old)", time, format::age(age)),
+        None => time,
+    }
+}
+
+/// Construct the filter builder popup, highlighting the part being edited.
+fn filter_builder_text(builder: &FilterBuilder) -> Paragraph {
+    let mut lines: Vec<Spans> = Vec::new();
+    for (index, row) in builder.rows.iter().enumerate() {
+        let selected = index == builder.selected;
+        let style = |focus: BuilderFocus| {
+            if selected && builder.focus == focus {
+                *SELECTED_STYLE
+            } else {
+                Style::default()
+            }
+        };
+        let joiner = if index == 0 { "      " } else { "  and " };
+        let marker = if selected { ">> " } else { "   " };
+        let mut spans = vec![
+            Span::raw(marker),
+            Span::raw(joiner),
+            Span::styled(
+                format!("{:<12}", row.field.name()),
+                style(BuilderFocus::Field),
+            ),
+            Span::raw(" "),
+            Span::styled(
+                format!("{:<2}", row.operator.symbol()),
+                style(BuilderFocus::Operator),
+            ),
+            Span::raw(" "),
+        ];
+        if selected && builder.focus == BuilderFocus::Value {
+            spans.extend(builder.value.spans(*SELECTED_STYLE));
+        } else {
+            spans.push(Span::raw(row.value.as_str()));
+        }
+        lines.push(Spans::from(spans));
+    }
+    lines.push(Spans::from(""));
+    if let Some(error) = &builder.error {
+        lines.push(Spans::from(Span::styled(
+            error.clone(),
+            Style::default().fg(Color::Red),
+        )));
+    }
+    lines.push(Spans::from(FILTER_HELP_TEXT));
+    Paragraph::new(lines)
+        .block(Block::default().title("Filter").borders(Borders::ALL))
+        .wrap(Wrap { trim: false })
+}
+
+/// Construct the search prompt popup, with the parse error if there is one.
+fn search_text(search: &SearchPrompt) -> Paragraph {
+    let prompt = if let Some(needle) = &search.reverse_search {
+        Spans::from(format!(
+            "(reverse-i-search)'{}': {}",
+            needle,
+            search.input.text()
+        ))
+    } else {
+        let mut spans = vec![Span::raw("> ")];
+        spans.extend(search.input.spans(Style::default()));
+        Spans::from(spans)
+    };
+    let mut lines = vec![prompt, Spans::from("")];
+    if let Some(completion) = &search.completion {
+        let mut spans = Vec::new();
+        for (i, candidate) in completion.candidates.iter().enumerate() {
+            let style = if i == completion.index {
+                *SELECTED_STYLE
+            } else {
+                Style::default()
+            };
+            spans.push(Span::styled(candidate.clone(), style));
+            spans.push(Span::raw(" "));
+        }
+        lines.push(Spans::from(spans));
+        lines.push(Spans::from(""));
+    }
+    if let Some(error) = &search.error {
+        for line in error.lines() {
+            lines.push(Spans::from(Span::styled(
+                line.to_owned(),
+                Style::default().fg(Color::Red),
+            )));
+        }
+        lines.push(Spans::from(""));
+    }
+    lines.push(Spans::from(SEARCH_HELP_TEXT));
+    Paragraph::new(lines)
+        .block(Block::default().title("Search").borders(Borders::ALL))
+        .wrap(Wrap { trim: false })
+}
+
+/// Construct the tune prompt popup.
+fn tune_text(tune: &TunePrompt) -> Paragraph {
+    let mut prompt = vec![Span::raw("> ")];
+    prompt.extend(tune.input.spans(Style::default()));
+    let mut lines = vec![Spans::from(prompt), Spans::from("")];
+    if let Some(error) = &tune.error {
+        lines.push(Spans::from(Span::styled(
+            error.clone(),
+            Style::default().fg(Color::Red),
+        )));
+        lines.push(Spans::from(""));
+    }
+    lines.push(Spans::from(TUNE_HELP_TEXT));
+    Paragraph::new(lines)
+        .block(Block::default().title("Tune").borders(Borders::ALL))
+        .wrap(Wrap { trim: false })
+}
+
+/// Construct the facility picker, a box per kind of position.
+fn facility_picker_text(picker: &CheckList) -> Paragraph {
+    let mut lines = picker.lines(Style::default());
+    lines.push(Spans::from(""));
+    lines.push(Spans::from(FACILITY_HELP_TEXT));
+    Paragraph::new(lines)
+        .block(Block::default().title("Facilities").borders(Borders::ALL))
+        .wrap(Wrap { trim: false })
+}
+
+/// How often to refresh the data when no poll interval is given.
+fn default_refresh(low_bandwidth: bool) -> Duration {
+    if low_bandwidth {
+        LOW_BANDWIDTH_REFRESH_INTERVAL
+    } else {
+        REFRESH_INTERVAL
+    }
+}
+
+/// Host of a mirror URL, which is all that fits in the debug overlay.
+fn mirror_host(url: &str) -> &str {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    rest.split('/').next().unwrap_or(rest)
+}
+
+/// Construct the debug overlay with the fetch counters.
+fn debug_text<'a>(
+    stats: &FetchStats,
+    retained_entries: usize,
+    profile: Option<&str>,
+) -> Paragraph<'a> {
+    let mut text = format!(
+        "Profile: {}\nFetches: {}\nFailures: {}\nNot modified: {}\nSkipped identical body: {}\nStale mirror switches: {}\nMirror: {}\nFetch: {} ms\nParse: {} ms\nRetained entries: {}",
+        profile.unwrap_or("\u{2014}"),
+        stats.fetches,
+        stats.failures,
+        stats.not_modified,
+        stats.skipped_identical,
+        stats.stale_switches,
+        stats.mirror.as_deref().map_or("\u{2014}", mirror_host),
+        stats.timings.fetch.as_millis(),
+        stats.timings.parse.as_millis(),
+        retained_entries
+    );
+    for latency in &stats.mirror_latencies {
+        let _ = match latency.latency {
+            Some(time) => write!(
+                text,
+                "\n  {}: {} ms",
+                mirror_host(&latency.url),
+                time.as_millis()
+            ),
+            None => write!(text, "\n  {}: failed", mirror_host(&latency.url)),
+        };
+    }
+    Paragraph::new(Text::from(text)).block(Block::default().title("Debug").borders(Borders::ALL))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        mirrors::MirrorChoice,
+        models::{Pilot, V3ResponseData},
+        nearby::FacilityClass,
+        testing,
+    };
+    use tui::{backend::TestBackend, buffer::Buffer};
+
+    /// Draw a frame of the interface, as lines of text.
+    fn render(app: &mut App, width: u16, height: u16) -> Vec<String> {
+        render_buffer(app, width, height)
+            .content
+            .chunks(usize::from(width))
+            .map(|row| row.iter().map(|cell| cell.symbol.as_str()).collect())
+            .collect()
+    }
+
+    /// Draw a frame of the interface.
+    fn render_buffer(app: &mut App, width: u16, height: u16) -> Buffer {
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        let view_data = app.get_view_data();
+        let _ = terminal
+            .draw(|f| {
+                let _ = draw_screen(f, app, &view_data);
+            })
+            .unwrap();
+        terminal.backend().buffer().clone()
+    }
+
+    /// Style of the first cell showing some text.
+    fn style_of(buffer: &Buffer, text: &str) -> Style {
+        let width = usize::from(buffer.area.width);
+        for (y, row) in buffer.content.chunks(width).enumerate() {
+            let line: String = row.iter().map(|cell| cell.symbol.as_str()).collect();
+            if let Some(x) = line.find(text) {
+                return buffer.content[y * width + x].style();
+            }
+        }
+        panic!("{} not shown", text);
+    }
+
+    fn shows(lines: &[String], text: &str) -> bool {
+        lines.iter().any(|line| line.contains(text))
+    }
+
+    #[test]
+    fn filed_times_in_the_popup() {
+        let text = |fuel: &str, enroute: &str| {
+            let plan = FlightPlan {
+                fuel_time: fuel.to_owned(),
+                enroute_time: enroute.to_owned(),
+                ..testing::flight_plan("EGLL", "KJFK")
+            };
+            filed_times_lines(&plan)
+                .into_iter()
+                .map(|spans| spans.0.iter().map(|span| span.content.as_ref()).collect())
+                .collect::<Vec<String>>()
+        };
+        assert_eq!(text("0830", "0700"), ["Enroute: 7h 00m", "Fuel: 8h 30m"]);
+        assert_eq!(
+            text("0740", "0700"),
+            [
+                "Enroute: 7h 00m",
+                "Fuel: 7h 40m TIGHT (0h 40m spare, under the 45 min reserve)"
+            ]
+        );
+        assert_eq!(
+            text("0700", "0730"),
+            [
+                "Enroute: 7h 30m",
+                "Fuel: 7h 00m SHORT (0h 30m short of the enroute time)"
+            ]
+        );
+        assert_eq!(
+            text("0000", "garbage"),
+            ["Enroute: \u{2014}", "Fuel: \u{2014}"]
+        );
+    }
+
+    #[test]
+    fn tour_overlay_shows_the_tour_text() {
+        let mut terminal = Terminal::new(TestBackend::new(120, 40)).unwrap();
+        let _ = terminal.draw(draw_tour).unwrap();
+        let lines: Vec<String> = terminal
+            .backend()
+            .buffer()
+            .content
+            .chunks(120)
+            .map(|row| row.iter().map(|cell| cell.symbol.as_str()).collect())
+            .collect();
+        assert!(shows(&lines, "Welcome"));
+        assert!(shows(&lines, "Press any key to start."));
+        for line in tour::text().lines().filter(|line| line.starts_with("  ")) {
+            assert!(shows(&lines, line.trim()), "{:?} missing", line);
+        }
+    }
+
+    /// Where some text was drawn: the first cell's column and row.
+    fn position_of(lines: &[String], text: &str) -> Option<(u16, u16)> {
+        lines.iter().enumerate().find_map(|(y, line)| {
+            let x = line.find(text)?;
+            let x = line[..x].chars().count();
+            Some((u16::try_from(x).ok()?, u16::try_from(y).ok()?))
+        })
+    }
+
+    #[test]
+    fn clicks_hit_the_headers_where_they_were_drawn() {
+        for (width, scrolls) in [(220, 0), (90, 0), (90, 2)] {
+            let mut app = loaded_app();
+            for _ in 0..scrolls {
+                let _ = app.update(action::Action::ScrollColumns(1));
+            }
+            let lines = render(&mut app, width, 20);
+            let columns = app.get_view_data().columns;
+            let (_, header_row) = position_of(&lines, "Callsign").unwrap();
+            let mut hits = 0;
+            for (i, column) in columns.iter().enumerate() {
+                let header_line = std::slice::from_ref(&lines[usize::from(header_row)]);
+                let Some((x, y)) = position_of(header_line, column.header) else {
+                    continue;
+                };
+                assert_eq!(
+                    app.hit(x, header_row + y),
+                    Some(Region::Column(i)),
+                    "{} at width {} after {} scrolls",
+                    column.header,
+                    width,
+                    scrolls
+                );
+                hits += 1;
+            }
+            assert!(hits > 2, "only {} headers at width {}", hits, width);
+            assert_eq!(app.hit(0, header_row + 1), None);
+        }
+    }
+
+    #[test]
+    fn clicks_hit_the_tab_labels() {
+        let mut app = loaded_app();
+        let lines = render(&mut app, 120, 20);
+        let (x, y) = position_of(&lines, "Controllers").unwrap();
+        assert_eq!(app.hit(x, y), Some(Region::Tab(1)));
+        assert_eq!(app.hit(x + 5, y), Some(Region::Tab(1)));
+        let (x, y) = position_of(&lines, "Pilots").unwrap();
+        assert_eq!(app.hit(x, y), Some(Region::Tab(0)));
+    }
+
+    fn loaded_app() -> App {
+        let mut app = App::new();
+        app.set_data(V3ResponseData {
+            pilots: vec![testing::flying("BAW123", "EGLL", "KJFK")],
+            controllers: vec![testing::controller("EGLL_TWR", 4)],
+            ..V3ResponseData::default()
+        });
+        app
+    }
+
+    #[test]
+    fn status_shows_beside_the_help() {
+        for width in [120, 200] {
+            let mut app = loaded_app();
+            let lines = render(&mut app, width, 20);
+            assert!(shows(&lines, "Help"));
+            assert!(!shows(&lines, "Status"));
+
+            app.set_status("Copied BAW123".to_owned());
+            let lines = render(&mut app, width, 20);
+            assert!(shows(&lines, "Status"), "no status at {}", width);
+            assert!(shows(&lines, "Copied BAW123"), "no status at {}", width);
+            assert!(shows(&lines, "Help"), "no help at {}", width);
+        }
+    }
+
+    #[test]
+    fn loading_screen_until_data_arrives() {
+        let mut app = App::new();
+        let lines = render(&mut app, 100, 20);
+        assert!(shows(&lines, LOADING_TEXT));
+        assert!(!shows(&lines, "BAW123"));
+
+        let mut app = loaded_app();
+        let lines = render(&mut app, 100, 20);
+        assert!(!shows(&lines, LOADING_TEXT));
+        assert!(shows(&lines, "BAW123"));
+    }
+
+    #[test]
+    fn error_screen_offers_a_retry() {
+        let mut app = App::new();
+        app.set_error(&anyhow::anyhow!("connection refused"));
+        let lines = render(&mut app, 100, 20);
+        assert!(shows(&lines, "Could not get data from VATSIM"));
+        assert!(shows(&lines, "connection refused"));
+        assert!(shows(&lines, "Press R to retry"));
+
+        assert_eq!(app.update(action::Action::Retry), Some(Effect::Fetch));
+        assert!(shows(&render(&mut app, 100, 20), LOADING_TEXT));
+    }
+
+    /// The line of the pilots table with the column headers.
+    fn header(lines: &[String]) -> &str {
+        lines.iter().find(|line| line.contains("Callsign")).unwrap()
+    }
+
+    #[test]
+    fn narrow_tables_scroll_by_column() {
+        let mut app = loaded_app();
+        let lines = render(&mut app, 60, 20);
+        assert!(shows(&lines, "(cols 2\u{2013}"), "{:#?}", lines);
+        assert!(header(&lines).contains("Name"));
+        assert!(!header(&lines).contains("ATC"));
+
+        let _ = app.update(action::Action::ScrollColumns(1));
+        let lines = render(&mut app, 60, 20);
+        assert!(shows(&lines, "(cols 3\u{2013}"), "{:#?}", lines);
+        let columns = header(&lines);
+        assert!(columns.find("Callsign") < columns.find("Aircraft"));
+        assert!(!columns.contains("Name"));
+
+        for _ in 0..20 {
+            let _ = app.update(action::Action::ScrollColumns(1));
+        }
+        let lines = render(&mut app, 60, 20);
+        assert!(header(&lines).contains("Callsign"));
+        assert!(header(&lines).contains("ATC"));
+    }
+
+    #[test]
+    fn wider_tables_show_more_columns() {
+        let mut app = loaded_app();
+        let narrow = render(&mut app, 60, 20);
+        let wide = render(&mut app, 100, 20);
+        let count = |lines: &[String]| {
+            columns::PILOT_COLUMNS
+                .iter()
+                .filter(|column| header(lines).contains(column.header))
+                .count()
+        };
+        assert!(count(&wide) > count(&narrow));
+        assert!(header(&wide).contains("Aircraft"));
+        assert!(!shows(&render(&mut app, 200, 20), "(cols"));
+    }
+
+    #[test]
+    fn tab_counts_follow_the_filter() {
+        let mut app = App::new();
+        app.set_data(V3ResponseData {
+            pilots: vec![
+                testing::flying("BAW123", "EGLL", "KJFK"),
+                Pilot {
+                    cid: 1_000_001,
+                    ..testing::pilot("DLH4AB")
+                },
+            ],
+            controllers: vec![testing::controller("EGLL_TWR", 4)],
+            ..V3ResponseData::default()
+        });
+        let lines = render(&mut app, 100, 20);
+        assert!(shows(&lines, "Pilots (2)"), "{:#?}", lines);
+        assert!(shows(&lines, "Controllers (1)"));
+
+        app.set_filter(Query::parse("callsign=BAW*").unwrap());
+        let lines = render(&mut app, 100, 20);
+        assert!(shows(&lines, "Pilots (1/2)"), "{:#?}", lines);
+        assert!(shows(&lines, "Controllers (1)"));
+    }
+
+    #[test]
+    fn retry_only_after_a_failure() {
+        let mut app = App::new();
+        assert_eq!(app.update(action::Action::Retry), None);
+        assert_eq!(app.update(action::Action::Quit), Some(Effect::Quit));
+    }
+
+    #[test]
+    fn controllers_are_tinted_unless_selected() {
+        let mut app = App::new();
+        app.set_data(V3ResponseData {
+            controllers: vec![
+                testing::controller("EGLL_TWR", 4),
+                testing::controller("EGTT_CTR", 6),
+                testing::controller("LFPG_ATIS", 4),
+            ],
+            ..V3ResponseData::default()
+        });
+        app.select_tab(1);
+        let buffer = render_buffer(&mut app, 100, 20);
+        let selected = style_of(&buffer, "EGLL_TWR");
+        assert_eq!(selected.fg, Some(Color::Reset));
+        assert!(selected.add_modifier.contains(Modifier::REVERSED));
+        assert_eq!(style_of(&buffer, "EGTT_CTR").fg, Some(Color::LightBlue));
+        assert_eq!(style_of(&buffer, "LFPG_ATIS").fg, Some(Color::DarkGray));
+
+        let _ = app.update(action::Action::MoveSelection(1));
+        let buffer = render_buffer(&mut app, 100, 20);
+        assert_eq!(style_of(&buffer, "EGLL_TWR").fg, Some(Color::LightGreen));
+    }
+
+    #[test]
+    fn interface_appears_once_the_terminal_fits() {
+        let mut app = loaded_app();
+        app.set_data(V3ResponseData {
+            controllers: vec![
+                testing::controller("EGLL_TWR", 4),
+                testing::controller("LFPG_TWR", 4),
+            ],
+            ..V3ResponseData::default()
+        });
+        // as given on the command line
+        app.select_tab(1);
+        app.set_filter(Query::parse("callsign=EGLL*").unwrap());
+        // one terminal, resized between frames like the real one
+        let mut terminal = Terminal::new(TestBackend::new(20, 5)).unwrap();
+        let sizes = [(20, 5), (59, 10), (60, 9), (60, 10), (120, 40)];
+        for (width, height) in sizes {
+            terminal.backend_mut().resize(width, height);
+            let view_data = app.get_view_data();
+            let mut fit = false;
+            let _ = terminal
+                .draw(|f| fit = draw_screen(f, &mut app, &view_data))
+                .unwrap();
+            let buffer = terminal.backend().buffer();
+            assert_eq!(buffer.area, Rect::new(0, 0, width, height));
+            let lines: Vec<String> = buffer
+                .content
+                .chunks(usize::from(width))
+                .map(|row| row.iter().map(|cell| cell.symbol.as_str()).collect())
+                .collect();
+            let size = format!("{}x{}", width, height);
+            assert_eq!(fit, width >= MIN_WIDTH && height >= MIN_HEIGHT, "{}", size);
+            if fit {
+                // on the tab and with the filter given before it fit
+                assert!(shows(&lines, "EGLL_TWR"), "{}", size);
+                assert!(!shows(&lines, "LFPG_TWR"), "{}", size);
+                assert!(!shows(&lines, "too small"), "{}", size);
+            } else {
+                let sizes = format!("{}x{}, needs {}x{}", width, height, MIN_WIDTH, MIN_HEIGHT);
+                assert!(shows(&lines, &sizes), "{} {:?}", size, lines);
+                assert!(!shows(&lines, "EGLL_TWR"), "{}", size);
+            }
+        }
+    }
+
+    #[test]
+    fn refresh_error_panel_fits_narrow_terminals() {
+        let mut app = loaded_app();
+        let error = anyhow::anyhow!(
+            "error sending request for url (https://data.vatsim.net/v3/vatsim-data.json): \
+             operation timed out after waiting thirty seconds for the mirror to answer"
+        );
+        for _ in 0..3 {
+            app.refresh_failed(&error);
+        }
+        app.set_fetch_stats(FetchStats {
+            mirrors_tried: vec![
+                "https://data.vatsim.net/v3/vatsim-data.json".to_owned(),
+                "https://mirror.example.org/v3/vatsim-data.json".to_owned(),
+            ],
+            ..FetchStats::default()
+        });
+        for width in [MIN_WIDTH, 80, 120, 300] {
+            let lines = render(&mut app, width, 20);
+            let size = format!("{} wide", width);
+            assert!(
+                lines[0].contains("Refresh failed 3 times in a row"),
+                "{}",
+                size
+            );
+            let panel = &lines[..6];
+            assert!(
+                shows(panel, "Mirrors tried: data.vatsim.net, mirror"),
+                "{}",
+                size
+            );
+            assert!(shows(panel, REFRESH_ERROR_HELP_TEXT), "{}", size);
+            // the header is replaced, and the table is still below
+            assert!(!shows(&lines, "Data sources"), "{}", size);
+            assert!(shows(&lines[6..], "BAW123"), "{}", size);
+            // the error gets two lines, cut short if it needs more
+            assert!(lines[1].contains("error sending request"), "{}", size);
+            let cut = lines[2].contains('\u{2026}');
+            assert_eq!(cut, width < 80, "{} {:?}", size, &lines[1..3]);
+            // and the border is left standing
+            for line in &lines[1..5] {
+                let border = line.chars().nth(usize::from(width) - 2);
+                assert_eq!(border, Some('\u{2502}'), "{} {:?}", size, line);
+            }
+        }
+    }
+
+    #[test]
+    fn only_quitting_while_too_small() {
+        use crossterm::event::{KeyEvent, KeyModifiers};
+        let key = |code| Event::Key(KeyEvent::new(code, KeyModifiers::NONE));
+        assert!(quits_while_too_small(&key(KeyCode::Char('q'))));
+        assert!(quits_while_too_small(&key(KeyCode::Char('Q'))));
+        assert!(quits_while_too_small(&key(KeyCode::Esc)));
+        assert!(!quits_while_too_small(&key(KeyCode::Char('x'))));
+        assert!(!quits_while_too_small(&key(KeyCode::Enter)));
+        assert!(!quits_while_too_small(&Event::Resize(80, 24)));
+    }
+
+    #[test]
+    fn idle_overlay_until_input() {
+        let mut app = loaded_app();
+        let start = Instant::now();
+        app.set_idle_timeout(Some(Duration::from_secs(60)));
+        app.record_input(start);
+        app.check_idle(start + Duration::from_secs(60));
+        for (width, height) in [(100, 20), (80, 24), (MIN_WIDTH, MIN_HEIGHT)] {
+            let lines = render(&mut app, width, height);
+            assert!(
+                shows(&lines, "Press any key to resume."),
+                "{}x{}",
+                width,
+                height
+            );
+        }
+        let buffer = render_buffer(&mut app, 100, 20);
+        assert!(style_of(&buffer, "BAW123")
+            .add_modifier
+            .contains(Modifier::DIM));
+
+        app.record_input(start + Duration::from_secs(61));
+        let lines = render(&mut app, 100, 20);
+        assert!(!shows(&lines, "Press any key to resume."));
+        let buffer = render_buffer(&mut app, 100, 20);
+        assert!(!style_of(&buffer, "BAW123")
+            .add_modifier
+            .contains(Modifier::DIM));
+    }
+
+    #[test]
+    fn connection_deltas_show_then_expire() {
+        let mut app = loaded_app();
+        app.set_data(V3ResponseData {
+            pilots: vec![
+                testing::flying("BAW123", "EGLL", "KJFK"),
+                Pilot {
+                    cid: 1_000_001,
+                    ..testing::flying("DLH4", "EDDF", "EGLL")
+                },
+            ],
+            controllers: vec![testing::controller("EGLL_TWR", 4)],
+            ..V3ResponseData::default()
+        });
+        let text = "+1 / \u{2212}0 pilot";
+        assert!(shows(&render(&mut app, 100, 20), text));
+
+        app.age_deltas(Duration::from_secs(5));
+        assert!(!shows(&render(&mut app, 100, 20), text));
+    }
+
+    /// An app with pilots flying a long route, given CIDs 1 and up.
+    fn routed_pilots(count: i64) -> App {
+        let pilots = (1..=count)
+            .map(|cid| {
+                let mut pilot = Pilot {
+                    cid,
+                    ..testing::flying(&format!("TST{:02}", cid), "EGLL", "KJFK")
+                };
+                if let Some(fp) = pilot.flight_plan.as_mut() {
+                    fp.route = "CPT3F CPT UL9 KENET".to_owned();
+                }
+                pilot
+            })
+            .collect();
+        let mut app = App::new();
+        app.set_data(V3ResponseData {
+            pilots,
+            ..V3ResponseData::default()
+        });
+        app
+    }
+
+    #[test]
+    fn comfortable_rows_have_a_detail_line() {
+        let mut app = routed_pilots(3);
+        let lines = render(&mut app, 120, 20);
+        assert!(!shows(&lines, "CPT3F CPT UL9 KENET"));
+
+        let _ = app.update(action::Action::ToggleDensity);
+        let lines = render(&mut app, 120, 20);
+        let row = lines
+            .iter()
+            .position(|line| line.contains("TST01"))
+            .unwrap();
+        assert!(
+            lines[row + 1].contains("B738 CPT3F CPT UL9 KENET"),
+            "{}",
+            lines[row + 1]
+        );
+        assert!(lines[row + 2].contains("TST02"));
+        let buffer = render_buffer(&mut app, 120, 20);
+        assert!(style_of(&buffer, "B738 CPT3F")
+            .add_modifier
+            .contains(Modifier::DIM));
+    }
+
+    #[test]
+    fn comfortable_rows_fit_half_as_many() {
+        let mut app = routed_pilots(30);
+        let height = 20;
+        let count = |lines: &[String]| lines.iter().filter(|line| line.contains("TST")).count();
+        let compact = count(&render(&mut app, 120, height));
+        let _ = app.update(action::Action::ToggleDensity);
+        let comfortable = count(&render(&mut app, 120, height));
+        // the table gets 17 of the 20 lines, less its borders and header
+        assert_eq!(compact, 14);
+        assert_eq!(comfortable, compact / 2);
+    }
+
+    #[test]
+    fn comfortable_rows_move_as_one() {
+        let mut app = routed_pilots(30);
+        let _ = app.update(action::Action::ToggleDensity);
+        let _ = render(&mut app, 120, 20);
+        let _ = app.update(action::Action::MoveSelection(1));
+        assert_eq!(app.current_table_state().selected(), Some(1));
+        let _ = app.update(action::Action::MovePage(1));
+        assert_eq!(app.current_table_state().selected(), Some(6));
+
+        // the selected row stays on screen with two lines per row
+        for _ in 0..10 {
+            let _ = app.update(action::Action::MoveSelection(1));
+        }
+        let lines = render(&mut app, 120, 20);
+        assert!(shows(&lines, &format!("{}TST17", HIGHLIGHT_SYMBOL)));
+    }
+
+    #[test]
+    fn nearby_atc_lines() {
+        assert_eq!(nearby_atc_text(&[]), "no nearby ATC resolved");
+        assert_eq!(covering_atc_text(&[]), "UNICOM");
+        let nearby = [
+            NearbyController {
+                class: FacilityClass::Approach,
+                callsign: "SFO_APP".to_owned(),
+                distance_nm: 12.4,
+            },
+            NearbyController {
+                class: FacilityClass::Tower,
+                callsign: "SFO_TWR".to_owned(),
+                distance_nm: 0.6,
+            },
+        ];
+        assert_eq!(
+            nearby_atc_text(&nearby),
+            "APP: SFO_APP (12 nm)\nTWR: SFO_TWR (1 nm)"
+        );
+    }
+
+    #[test]
+    fn low_bandwidth_polls_less_often() {
+        assert!(default_refresh(true) > default_refresh(false));
+        assert_eq!(default_refresh(false), REFRESH_INTERVAL);
+    }
+
+    #[test]
+    fn debug_overlay_counts_skipped_bodies() {
+        let stats = FetchStats {
+            fetches: 5,
+            not_modified: 1,
+            skipped_identical: 3,
+            mirror: Some("https://data.vatsim.net/v3/vatsim-data.json".to_owned()),
+            ..FetchStats::default()
+        };
+        let area = Rect::new(0, 0, 40, 14);
+        let mut buffer = Buffer::empty(area);
+        tui::widgets::Widget::render(debug_text(&stats, 0, None), area, &mut buffer);
+        let lines: Vec<String> = buffer
+            .content
+            .chunks(40)
+            .map(|row| row.iter().map(|cell| cell.symbol.as_str()).collect())
+            .collect();
+        assert!(shows(&lines, "Fetches: 5"));
+        assert!(shows(&lines, "Not modified: 1"));
+        assert!(shows(&lines, "Skipped identical body: 3"));
+        assert!(shows(&lines, "Mirror: data.vatsim.net"));
+    }
+
+    #[test]
+    fn active_profile_in_the_header_and_debug_overlay() {
+        let mut app = loaded_app();
+        let lines = render(&mut app, 120, 40);
+        assert!(shows(&lines, "Data sources"));
+        assert!(!shows(&lines, "Data sources ("));
+
+        app.set_profile(Some("events".to_owned()));
+        let lines = render(&mut app, 120, 40);
+        assert!(shows(&lines, "Data sources (events)"));
+        assert!(!shows(&lines, "Profile: events"));
+        let _ = app.update(action::Action::ToggleDebug);
+        let lines = render(&mut app, 120, 40);
+        assert!(shows(&lines, "Profile: events"));
+    }
+
+    #[test]
+    fn quitting_mid_fetch_leaves_no_partial_bodies() {
+        let dir = tempfile::tempdir().unwrap();
+        let worker = Worker::spawn(WorkerOptions {
+            use_cache: false,
+            low_bandwidth: false,
+            user_agent: HeaderValue::from_static("vatsim_online tests"),
+            mirror: MirrorSelection {
+                choice: MirrorChoice::Url(testing::slow_server(200, Duration::from_secs(30))),
+                ..MirrorSelection::default()
+            },
+            dumps: Some(DumpSettings {
+                dir: dir.path().to_owned(),
+                all: true,
+            }),
+            sharing: Sharing::Off,
+        });
+        worker.request();
+        // a body saved before, and one the worker was partway through
+        std::fs::write(dir.path().join("v3-20221015T120000.000Z.json"), "{}").unwrap();
+        std::fs::write(
+            dir.path().join(".v3-20221015T120030.000Z.json.tmp"),
+            "{\"gen",
+        )
+        .unwrap();
+
+        let start = Instant::now();
+        stop_worker(worker, Some(dir.path()), Duration::from_millis(100));
+        assert!(start.elapsed() < Duration::from_secs(2));
+        let left: Vec<_> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(left, ["v3-20221015T120000.000Z.json"]);
+    }
+}
@@ -0,0 +1,54 @@
+//! Fetching who's online on VATSIM, for embedding in other applications.
+//!
+//! `api::Vatsim` is a blocking client; with the `async` feature,
+//! `async_api::AsyncVatsim` is the same client for async applications.
+//! `enrich::enrich` derives each pilot's phase of flight, ETA, and the
+//! other values the `vatsim_online` binary shows.
+//! The other modules are shared with the `vatsim_online` binary.
+
+#![deny(
+    clippy::all,
+    clippy::pedantic,
+    missing_debug_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unused_extern_crates,
+    unused_import_braces,
+    unused_qualifications,
+    unused_results
+)]
+
+#[doc(hidden)]
+pub mod airports;
+pub mod api;
+#[cfg(feature = "async")]
+pub mod async_api;
+#[doc(hidden)]
+pub mod cache;
+#[doc(hidden)]
+pub mod columns;
+#[doc(hidden)]
+pub mod dump;
+pub mod enrich;
+pub mod flight;
+#[doc(hidden)]
+pub mod format;
+#[doc(hidden)]
+pub mod geo;
+pub mod ghosts;
+#[cfg(test)]
+mod http_tests;
+pub mod mirrors;
+pub mod models;
+#[doc(hidden)]
+pub mod persist;
+#[doc(hidden)]
+pub mod privacy;
+#[doc(hidden)]
+pub mod reference;
+pub mod sanity;
+#[doc(hidden)]
+pub mod sort;
+#[cfg(test)]
+mod testing;
@@ -0,0 +1,154 @@
+//! Print the pilots or controllers table without the interface.
+
+use crate::{
+    filter::AirlineFilter,
+    format::DisplayZone,
+    models::V3ResponseData,
+    output::{OutputFormat, Table},
+    query::Query,
+    report::{fetch_data, DataSource, Reporter},
+    sort::DefaultSort,
+    state::App,
+};
+use anyhow::Result;
+
+/// What to list and how.
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// List controllers instead of pilots.
+    pub controllers: bool,
+    pub format: OutputFormat,
+    /// Only list the clients matching this filter.
+    pub query: Option<Query>,
+    /// Only list pilots flying for these airlines.
+    pub airlines: Option<AirlineFilter>,
+    pub sorts: Vec<DefaultSort>,
+    /// Time zone to show times in.
+    pub zone: DisplayZone,
+}
+
+/// Fetch the current data and print the table, filtered and sorted the same
+/// way as in the interface.
+pub fn run(options: Options, source: &DataSource, reporter: &mut Reporter) -> Result<()> {
+    let data = fetch_data(source, reporter)?;
+    print(options, data, reporter)
+}
+
+/// Print the table of clients in the data.
+fn print(options: Options, data: V3ResponseData, reporter: &mut Reporter) -> Result<()> {
+    let formatter = options.format.formatter();
+    reporter.data(formatter.format(&table(options, data)))?;
+    Ok(())
+}
+
+/// The table of clients in the data, as it's printed.
+pub fn table(options: Options, data: V3ResponseData) -> Table {
+    let mut app = App::new();
+    app.set_default_sorts(&options.sorts);
+    if options.controllers {
+        app.tab_over();
+    }
+    if let Some(query) = options.query {
+        app.set_filter(query);
+    }
+    app.set_airline_filter(options.airlines);
+    app.set_zone(options.zone);
+    app.set_data(data);
+    let view_data = app.get_view_data();
+    Table {
+        title: view_data.title,
+        headers: view_data.columns.iter().map(|c| c.header).collect(),
+        rows: view_data.data.into_iter().map(|row| row.cells).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{models::Pilot, privacy::Privacy, report::Verbosity, testing};
+
+    fn options(controllers: bool, query: Option<&str>) -> Options {
+        Options {
+            controllers,
+            format: OutputFormat::Csv,
+            query: query.map(|q| Query::parse(q).unwrap()),
+            airlines: None,
+            sorts: Vec::new(),
+            zone: DisplayZone::Utc,
+        }
+    }
+
+    fn data() -> V3ResponseData {
+        V3ResponseData {
+            pilots: [
+                testing::flying("BAW1", "EGLL", "KJFK"),
+                testing::flying("DLH1", "EDDF", "KJFK"),
+                testing::pilot("N123AB"),
+            ]
+            .into_iter()
+            .zip(1..)
+            .map(|(pilot, cid)| Pilot { cid, ..pilot })
+            .collect(),
+            controllers: vec![testing::controller("KJFK_TWR", 4)],
+            ..V3ResponseData::default()
+        }
+    }
+
+    /// The lines printed to stdout, and everything printed to stderr.
+    fn printed(options: Options, verbosity: Verbosity) -> (Vec<String>, String) {
+        let (mut reporter, out, err) = Reporter::captured(verbosity);
+        print(options, data(), &mut reporter).unwrap();
+        (out.text().lines().map(str::to_owned).collect(), err.text())
+    }
+
+    #[test]
+    fn prints_only_the_table_on_stdout() {
+        for verbosity in [Verbosity::Normal, Verbosity::Quiet, Verbosity::Silent] {
+            let (lines, err) = printed(options(false, Some("arrival=KJFK")), verbosity);
+            assert_eq!(err, "");
+            assert_eq!(lines.len(), 3, "{:?}", lines);
+            assert!(lines[0].starts_with("Callsign,"), "{:?}", lines[0]);
+            assert!(lines.iter().any(|line| line.starts_with("BAW1,")));
+            assert!(lines.iter().any(|line| line.starts_with("DLH1,")));
+        }
+    }
+
+    #[test]
+    fn private_names_stay_private_in_every_format() {
+        let mut data = data();
+        for pilot in &mut data.pilots {
+            pilot.name = "John Smith".to_owned();
+        }
+        data.controllers[0].name = "Jane Doe".to_owned();
+        Privacy::Initials.apply(&mut data);
+        for controllers in [false, true] {
+            for format in [
+                OutputFormat::Text,
+                OutputFormat::Csv,
+                OutputFormat::Json,
+                OutputFormat::Markdown,
+                OutputFormat::Html,
+            ] {
+                let options = Options {
+                    format,
+                    ..options(controllers, None)
+                };
+                let (mut reporter, out, _) = Reporter::captured(Verbosity::Silent);
+                print(options, data.clone(), &mut reporter).unwrap();
+                let text = out.text();
+                let shown = if controllers { "Jane D." } else { "John S." };
+                assert!(text.contains(shown), "{:?}: {}", format, text);
+                assert!(!text.contains("Smith"), "{:?}: {}", format, text);
+                assert!(!text.contains("Doe"), "{:?}: {}", format, text);
+            }
+        }
+    }
+
+    #[test]
+    fn lists_controllers() {
+        let (lines, err) = printed(options(true, None), Verbosity::Silent);
+        assert_eq!(err, "");
+        assert_eq!(lines.len(), 2, "{:?}", lines);
+        assert!(lines[1].starts_with("KJFK_TWR,"), "{:?}", lines[1]);
+    }
+}
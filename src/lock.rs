@@ -0,0 +1,139 @@
+//! An advisory lock so that only one running instance saves state.
+
+use anyhow::{anyhow, Result};
+use log::{debug, warn};
+use std::{
+    fs::{self, OpenOptions},
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+};
+
+/// Name of the lock file in the data directory.
+const LOCK_FILE_NAME: &str = "instance.lock";
+
+/// Held while this instance is the one saving state; the lock file is
+/// removed when it's dropped.
+#[derive(Debug)]
+pub struct InstanceLock {
+    path: PathBuf,
+}
+
+impl Drop for InstanceLock {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            warn!("Could not remove lock file {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+/// Path to the lock file, if the platform has a data directory.
+fn lock_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("vatsim_online").join(LOCK_FILE_NAME))
+}
+
+/// Whether a process is still running.
+///
+/// Only Linux can be checked; elsewhere every process is assumed to be.
+fn is_running(pid: u32) -> bool {
+    if cfg!(target_os = "linux") {
+        Path::new("/proc").join(pid.to_string()).exists()
+    } else {
+        true
+    }
+}
+
+/// Take the lock in the data directory.
+///
+/// Returns `None` if another running instance holds it. A lock left
+/// behind by an instance that's no longer running is taken over.
+pub fn acquire() -> Result<Option<InstanceLock>> {
+    let path = lock_path().ok_or_else(|| anyhow!("No data directory on this platform"))?;
+    acquire_at(&path)
+}
+
+/// Take the lock at a path.
+fn acquire_at(path: &Path) -> Result<Option<InstanceLock>> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    for _ in 0..2 {
+        match OpenOptions::new().write(true).create_new(true).open(path) {
+            Ok(mut file) => {
+                write!(file, "{}", std::process::id())?;
+                debug!("Took instance lock {}", path.display());
+                return Ok(Some(InstanceLock {
+                    path: path.to_owned(),
+                }));
+            }
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {}
+            Err(e) => return Err(e.into()),
+        }
+        let holder = fs::read_to_string(path)
+            .ok()
+            .and_then(|content| content.trim().parse().ok());
+        match holder {
+            Some(pid) if is_running(pid) => {
+                debug!("Instance lock {} is held by PID {}", path.display(), pid);
+                return Ok(None);
+            }
+            _ => {
+                warn!("Removing stale instance lock {}", path.display());
+                match fs::remove_file(path) {
+                    Ok(()) => {}
+                    Err(e) if e.kind() == ErrorKind::NotFound => {}
+                    Err(e) => return Err(e.into()),
+                }
+            }
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A PID no process has, as it's past the kernel's limit.
+    const DEAD_PID: u32 = 4_194_305;
+
+    #[test]
+    fn taken_once_and_released_on_drop() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data").join(LOCK_FILE_NAME);
+        let lock = acquire_at(&path).unwrap().unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            std::process::id().to_string()
+        );
+        // this process is running, so the lock is held
+        assert!(acquire_at(&path).unwrap().is_none());
+        drop(lock);
+        assert!(!path.exists());
+        assert!(acquire_at(&path).unwrap().is_some());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn stale_locks_are_taken_over() {
+        assert!(!is_running(DEAD_PID));
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(LOCK_FILE_NAME);
+        for content in [DEAD_PID.to_string(), "not a pid".to_owned(), String::new()] {
+            fs::write(&path, &content).unwrap();
+            let lock = acquire_at(&path).unwrap();
+            assert!(lock.is_some(), "{:?}", content);
+            assert_eq!(
+                fs::read_to_string(&path).unwrap(),
+                std::process::id().to_string()
+            );
+        }
+    }
+
+    #[test]
+    fn unwritable_locations_are_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("file");
+        fs::write(&file, "").unwrap();
+        assert!(acquire_at(&file.join(LOCK_FILE_NAME)).is_err());
+    }
+}
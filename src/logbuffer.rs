@@ -0,0 +1,294 @@
+//! Recent log lines kept in memory, for the log panel shown with `--debug`.
+
+use crate::bounded::RingBuffer;
+use log::Level;
+use once_cell::sync::Lazy;
+use std::sync::Mutex;
+
+/// Log lines kept for the panel.
+const CAPACITY: usize = 2_000;
+
+/// A formatted log line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogLine {
+    /// Position among all the lines logged, to keep a place as old lines
+    /// are dropped.
+    pub seq: u64,
+    pub level: Level,
+    pub text: String,
+}
+
+/// The most recent lines.
+struct Kept {
+    lines: RingBuffer<LogLine>,
+    /// Lines logged so far, including those dropped.
+    count: u64,
+}
+
+/// The kept lines; `None` until logging to memory is turned on.
+///
+/// The lock is only held to push or copy lines, never while logging, so
+/// the panel can't deadlock with the logger.
+static BUFFER: Lazy<Mutex<Option<Kept>>> = Lazy::new(|| Mutex::new(None));
+
+/// Turn on keeping log lines in memory, returning an output for the
+/// logger that keeps them.
+pub fn output() -> fern::Output {
+    if let Ok(mut buffer) = BUFFER.lock() {
+        *buffer = Some(Kept {
+            lines: RingBuffer::new(CAPACITY),
+            count: 0,
+        });
+    }
+    fern::Output::call(|record| {
+        // formatting may run code that logs or reads the log, so it's done
+        // before taking the lock
+        let text = record.args().to_string();
+        // a poisoned lock means a panic elsewhere; the line isn't worth another
+        let Ok(mut buffer) = BUFFER.lock() else {
+            return;
+        };
+        if let Some(kept) = buffer.as_mut() {
+            kept.lines.push(LogLine {
+                seq: kept.count,
+                level: record.level(),
+                text,
+            });
+            kept.count += 1;
+        }
+    })
+}
+
+/// Whether log lines are being kept in memory.
+pub fn is_enabled() -> bool {
+    BUFFER.lock().is_ok_and(|buffer| buffer.is_some())
+}
+
+/// Copies of the kept lines at `min_level` or more severe, oldest first.
+pub fn lines(min_level: Level) -> Vec<LogLine> {
+    let Ok(buffer) = BUFFER.lock() else {
+        return Vec::new();
+    };
+    buffer.as_ref().map_or_else(Vec::new, |kept| {
+        kept.lines
+            .iter()
+            .filter(|line| line.level <= min_level)
+            .cloned()
+            .collect()
+    })
+}
+
+/// Where the log panel is scrolled to, and which lines it shows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogPanel {
+    /// Least severe level shown.
+    pub min_level: Level,
+    /// `seq` of the bottom line shown, or `None` to follow new lines.
+    anchor: Option<u64>,
+}
+
+impl Default for LogPanel {
+    fn default() -> Self {
+        Self {
+            min_level: Level::Debug,
+            anchor: None,
+        }
+    }
+}
+
+/// What the log panel shows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogView {
+    /// Lines up to the bottom one shown, oldest first; as many from the end
+    /// as fit are drawn.
+    pub lines: Vec<LogLine>,
+    pub following: bool,
+    pub min_level: Level,
+}
+
+impl LogPanel {
+    /// Index in `lines` of the bottom line shown.
+    ///
+    /// If the anchored line was dropped or filtered out, the closest older
+    /// line is used.
+    fn bottom(&self, lines: &[LogLine]) -> Option<usize> {
+        let last = lines.len().checked_sub(1)?;
+        match self.anchor {
+            None => Some(last),
+            Some(seq) => Some(lines.iter().rposition(|line| line.seq <= seq).unwrap_or(0)),
+        }
+    }
+
+    /// Scroll by some lines, negative for older ones. Scrolling to the
+    /// newest line follows new lines again.
+    pub fn scroll(&mut self, lines: &[LogLine], delta: i32) {
+        let Some(bottom) = self.bottom(lines) else {
+            return;
+        };
+        let last = lines.len() - 1;
+        let target = bottom
+            .saturating_add_signed(isize::try_from(delta).unwrap_or_default())
+            .min(last);
+        self.anchor = (target < last).then(|| lines[target].seq);
+    }
+
+    /// Stick to the newest line as new ones arrive.
+    pub fn follow(&mut self) {
+        self.anchor = None;
+    }
+
+    /// Show fewer levels, wrapping around to all of them.
+    pub fn cycle_level(&mut self) {
+        self.min_level = match self.min_level {
+            Level::Trace | Level::Debug => Level::Info,
+            Level::Info => Level::Warn,
+            Level::Warn => Level::Error,
+            Level::Error => Level::Debug,
+        };
+    }
+
+    /// The lines to show from the kept ones.
+    pub fn view(&self, lines: Vec<LogLine>) -> LogView {
+        let mut lines = lines;
+        lines.truncate(self.bottom(&lines).map_or(0, |bottom| bottom + 1));
+        LogView {
+            lines,
+            following: self.anchor.is_none(),
+            min_level: self.min_level,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fmt;
+
+    fn log(logger: &dyn log::Log, level: Level, text: impl fmt::Display) {
+        logger.log(
+            &log::Record::builder()
+                .level(level)
+                .args(format_args!("{}", text))
+                .build(),
+        );
+    }
+
+    fn texts(lines: &[LogLine]) -> Vec<&str> {
+        lines.iter().map(|line| line.text.as_str()).collect()
+    }
+
+    /// Formats as the number of lines kept, reading the log while it's
+    /// being logged.
+    struct CountKept;
+
+    impl fmt::Display for CountKept {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "kept {}", lines(Level::Trace).len())
+        }
+    }
+
+    /// The buffer is shared by the whole process, so everything that logs
+    /// to it is in this one test.
+    #[test]
+    fn keeps_the_newest_lines_by_level() {
+        let (_, logger) = fern::Dispatch::new()
+            .level(log::LevelFilter::Trace)
+            .chain(output())
+            .into_log();
+        assert!(is_enabled());
+        log(&*logger, Level::Info, "starting");
+        log(&*logger, Level::Debug, "fetching");
+        log(&*logger, Level::Warn, "slow mirror");
+        log(&*logger, Level::Error, "fetch failed");
+        assert_eq!(
+            texts(&lines(Level::Debug)),
+            ["starting", "fetching", "slow mirror", "fetch failed"]
+        );
+        assert_eq!(texts(&lines(Level::Warn)), ["slow mirror", "fetch failed"]);
+        assert_eq!(texts(&lines(Level::Error)), ["fetch failed"]);
+
+        log(&*logger, Level::Info, CountKept);
+        assert_eq!(lines(Level::Trace).last().unwrap().text, "kept 4");
+
+        for i in 0..CAPACITY {
+            log(&*logger, Level::Info, i);
+        }
+        let kept = lines(Level::Trace);
+        assert_eq!(kept.len(), CAPACITY);
+        assert_eq!(kept[0].text, "0");
+        assert_eq!(kept[0].seq, 5);
+        assert_eq!(kept.last().unwrap().seq, 5 + CAPACITY as u64 - 1);
+    }
+
+    fn numbered(count: u64) -> Vec<LogLine> {
+        (0..count)
+            .map(|seq| LogLine {
+                seq,
+                level: Level::Info,
+                text: seq.to_string(),
+            })
+            .collect()
+    }
+
+    fn bottom(panel: &LogPanel, lines: &[LogLine]) -> Option<String> {
+        panel
+            .view(lines.to_vec())
+            .lines
+            .last()
+            .map(|line| line.text.clone())
+    }
+
+    #[test]
+    fn follows_new_lines_until_scrolled() {
+        let mut panel = LogPanel::default();
+        let lines = numbered(10);
+        assert_eq!(bottom(&panel, &lines).as_deref(), Some("9"));
+        assert!(panel.view(lines.clone()).following);
+
+        panel.scroll(&lines, -3);
+        assert_eq!(bottom(&panel, &lines).as_deref(), Some("6"));
+        // new lines arrive without moving the view
+        let more = numbered(15);
+        assert_eq!(bottom(&panel, &more).as_deref(), Some("6"));
+        assert!(!panel.view(more.clone()).following);
+
+        // scrolling back to the newest line follows again
+        panel.scroll(&more, 100);
+        assert!(panel.view(more.clone()).following);
+        panel.scroll(&more, -100);
+        assert_eq!(bottom(&panel, &more).as_deref(), Some("0"));
+        panel.follow();
+        assert_eq!(bottom(&panel, &more).as_deref(), Some("14"));
+    }
+
+    #[test]
+    fn keeps_its_place_when_lines_are_dropped_or_hidden() {
+        let mut panel = LogPanel::default();
+        let lines = numbered(10);
+        panel.scroll(&lines, -5);
+        // lines 0 to 5 were dropped from the buffer
+        let dropped: Vec<LogLine> = numbered(20).into_iter().skip(6).collect();
+        assert_eq!(bottom(&panel, &dropped).as_deref(), Some("6"));
+        // the anchored line is filtered out, so the one before it is shown
+        let filtered: Vec<LogLine> = lines.iter().filter(|l| l.seq != 4).cloned().collect();
+        assert_eq!(bottom(&panel, &filtered).as_deref(), Some("3"));
+
+        let mut empty = LogPanel::default();
+        empty.scroll(&[], -1);
+        assert!(empty.view(Vec::new()).lines.is_empty());
+    }
+
+    #[test]
+    fn cycling_levels_wraps_around() {
+        let mut panel = LogPanel::default();
+        let mut levels = Vec::new();
+        for _ in 0..4 {
+            panel.cycle_level();
+            levels.push(panel.min_level);
+        }
+        assert_eq!(
+            levels,
+            [Level::Info, Level::Warn, Level::Error, Level::Debug]
+        );
+    }
+}
@@ -0,0 +1,275 @@
+//! Looking up many clients at once from a file, for the `lookup` subcommand.
+
+use crate::{
+    callsign, format,
+    models::{Controller, Pilot, V3ResponseData},
+    reference::ReferenceNames,
+    report::{fetch_data, DataSource, Reporter},
+};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::{fs, path::Path};
+
+/// A client to look for, by CID or callsign.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    Cid(i64),
+    Callsign(String),
+}
+
+impl Target {
+    /// Interpret a line: all digits is a CID, anything else a callsign.
+    pub fn parse(line: &str) -> Self {
+        let line = line.trim();
+        if !line.is_empty() && line.chars().all(|c| c.is_ascii_digit()) {
+            if let Ok(cid) = line.parse() {
+                return Target::Cid(cid);
+            }
+        }
+        Target::Callsign(line.to_uppercase())
+    }
+
+    /// Whether a client with this CID and callsign is the target.
+    pub fn matches(&self, cid: i64, client_callsign: &str) -> bool {
+        match self {
+            Target::Cid(target) => *target == cid,
+            Target::Callsign(target) => client_callsign.eq_ignore_ascii_case(target),
+        }
+    }
+}
+
+/// Read the targets from lookup file content, one per line.
+///
+/// Blank lines and lines starting with `#` are skipped, as is anything
+/// after a `#` on a line. Returns each target with the line it came from.
+pub fn parse_targets(content: &str) -> Vec<(String, Target)> {
+    content
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| (line.to_owned(), Target::parse(line)))
+        .collect()
+}
+
+/// Result of looking up one target.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LookupResult {
+    /// The line from the input file.
+    pub input: String,
+    pub online: bool,
+    /// "pilot" or "controller", if online.
+    pub kind: Option<&'static str>,
+    pub callsign: Option<String>,
+    pub cid: Option<i64>,
+    /// One-line description of the client, if online.
+    pub summary: Option<String>,
+}
+
+/// One-line description of a pilot.
+fn pilot_summary(pilot: &Pilot) -> String {
+    let route = pilot.flight_plan.as_ref().map_or_else(
+        || "no flight plan".to_owned(),
+        |fp| {
+            format!(
+                "{} -> {} ({})",
+                fp.departure,
+                fp.arrival,
+                format::flight_rules(&fp.flight_rules)
+            )
+        },
+    );
+    format!(
+        "{} ({}) flying {}, {} at {} ft, {} kts",
+        pilot.callsign,
+        pilot.name,
+        pilot.aircraft().unwrap_or("???"),
+        route,
+        pilot.altitude,
+        pilot.groundspeed
+    )
+}
+
+/// One-line description of a controller.
+fn controller_summary(controller: &Controller, names: &ReferenceNames) -> String {
+    format!(
+        "{} ({}) controlling {} on {}, rated {}",
+        controller.callsign,
+        controller.name,
+        callsign::position_name(&controller.callsign),
+        controller.frequency,
+        names.rating(controller.rating_enum())
+    )
+}
+
+/// Find each target in the data, in input order.
+pub fn resolve(targets: &[(String, Target)], data: &V3ResponseData) -> Vec<LookupResult> {
+    let names = ReferenceNames::new(data);
+    targets
+        .iter()
+        .map(|(input, target)| {
+            let offline = LookupResult {
+                input: input.clone(),
+                online: false,
+                kind: None,
+                callsign: None,
+                cid: None,
+                summary: None,
+            };
+            if let Some(pilot) = data
+                .pilots
+                .iter()
+                .find(|p| target.matches(p.cid, &p.callsign))
+            {
+                return LookupResult {
+                    online: true,
+                    kind: Some("pilot"),
+                    callsign: Some(pilot.callsign.clone()),
+                    cid: Some(pilot.cid),
+                    summary: Some(pilot_summary(pilot)),
+                    ..offline
+                };
+            }
+            if let Some(controller) = data
+                .controllers
+                .iter()
+                .find(|c| target.matches(c.cid, &c.callsign))
+            {
+                return LookupResult {
+                    online: true,
+                    kind: Some("controller"),
+                    callsign: Some(controller.callsign.clone()),
+                    cid: Some(controller.cid),
+                    summary: Some(controller_summary(controller, &names)),
+                    ..offline
+                };
+            }
+            offline
+        })
+        .collect()
+}
+
+/// Look up the clients listed in a file and print the results.
+///
+/// Returns whether every client was online.
+pub fn run(file: &Path, json: bool, source: &DataSource, reporter: &mut Reporter) -> Result<bool> {
+    let content =
+        fs::read_to_string(file).with_context(|| format!("Could not read {}", file.display()))?;
+    let targets = parse_targets(&content);
+    let data = fetch_data(source, reporter)?;
+    report(&resolve(&targets, &data), json, reporter)
+}
+
+/// Print lookup results, as JSON or one line each.
+///
+/// Returns whether every client was online.
+fn report(results: &[LookupResult], json: bool, reporter: &mut Reporter) -> Result<bool> {
+    if json {
+        reporter.data(serde_json::to_string_pretty(results)?)?;
+    } else {
+        for result in results {
+            match &result.summary {
+                Some(summary) => reporter.data(format!("{}: {}", result.input, summary))?,
+                None => reporter.data(format!("{}: offline", result.input))?,
+            }
+        }
+    }
+    Ok(results.iter().all(|result| result.online))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{report::Verbosity, testing};
+
+    fn data() -> V3ResponseData {
+        V3ResponseData {
+            pilots: vec![Pilot {
+                cid: 1_234_567,
+                ..testing::flying("BAW123", "EGLL", "KJFK")
+            }],
+            controllers: vec![testing::controller("EGLL_TWR", 4)],
+            ..V3ResponseData::default()
+        }
+    }
+
+    #[test]
+    fn digits_are_cids_and_the_rest_callsigns() {
+        assert_eq!(Target::parse("1234567"), Target::Cid(1_234_567));
+        assert_eq!(Target::parse(" 1234567 "), Target::Cid(1_234_567));
+        assert_eq!(
+            Target::parse("baw123"),
+            Target::Callsign("BAW123".to_owned())
+        );
+        assert_eq!(Target::parse("12A"), Target::Callsign("12A".to_owned()));
+        assert_eq!(Target::parse("-5"), Target::Callsign("-5".to_owned()));
+    }
+
+    #[test]
+    fn comments_and_blank_lines_are_skipped() {
+        let targets =
+            parse_targets("# friends\n\n1234567\n  baw123  # my flight\n   \n#EGLL_TWR\n");
+        assert_eq!(
+            targets,
+            vec![
+                ("1234567".to_owned(), Target::Cid(1_234_567)),
+                ("baw123".to_owned(), Target::Callsign("BAW123".to_owned())),
+            ]
+        );
+    }
+
+    #[test]
+    fn results_keep_input_order() {
+        let targets = parse_targets("EGLL_TWR\nDLH1\n1234567\n");
+        let results = resolve(&targets, &data());
+        let inputs: Vec<_> = results.iter().map(|r| r.input.as_str()).collect();
+        assert_eq!(inputs, ["EGLL_TWR", "DLH1", "1234567"]);
+
+        assert_eq!(results[0].kind, Some("controller"));
+        assert_eq!(results[0].cid, Some(2_000_000));
+        assert!(!results[1].online);
+        assert_eq!(results[1].summary, None);
+        assert_eq!(results[2].kind, Some("pilot"));
+        assert_eq!(results[2].callsign.as_deref(), Some("BAW123"));
+        assert!(results[2]
+            .summary
+            .as_ref()
+            .unwrap()
+            .contains("EGLL -> KJFK"));
+    }
+
+    #[test]
+    fn text_report_marks_offline_clients() {
+        let results = resolve(&parse_targets("BAW123\nDLH1\n"), &data());
+        let (mut reporter, out, _) = Reporter::captured(Verbosity::Normal);
+        assert!(!report(&results, false, &mut reporter).unwrap());
+        let text = out.text();
+        let lines: Vec<_> = text.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("BAW123: BAW123 (Test Pilot) flying B738"));
+        assert_eq!(lines[1], "DLH1: offline");
+    }
+
+    #[test]
+    fn json_report() {
+        let results = resolve(&parse_targets("BAW123\nDLH1\n"), &data());
+        let (mut reporter, out, _) = Reporter::captured(Verbosity::Silent);
+        assert!(!report(&results, true, &mut reporter).unwrap());
+        let json: serde_json::Value = serde_json::from_str(&out.text()).unwrap();
+        assert_eq!(json[0]["online"], true);
+        assert_eq!(json[0]["kind"], "pilot");
+        assert_eq!(json[0]["cid"], 1_234_567);
+        assert_eq!(json[1]["input"], "DLH1");
+        assert_eq!(json[1]["online"], false);
+        assert!(json[1]["summary"].is_null());
+    }
+
+    #[test]
+    fn all_online_only_when_none_missing() {
+        let (mut reporter, _, _) = Reporter::captured(Verbosity::Normal);
+        let online = resolve(&parse_targets("1234567\negll_twr\n"), &data());
+        assert!(report(&online, false, &mut reporter).unwrap());
+        let missing = resolve(&parse_targets("1234567\n7654321\n"), &data());
+        assert!(!report(&missing, false, &mut reporter).unwrap());
+        assert!(report(&[], false, &mut reporter).unwrap());
+    }
+}
@@ -11,28 +11,129 @@
     unused_results
 )]
 
-mod api;
+mod action;
+mod alerts;
+mod atc;
+mod atis;
+mod board;
+mod bounded;
+mod callsign;
+mod check;
+mod cli;
+mod compare;
+mod complete;
+mod config;
+mod count;
+mod custom;
+mod diff;
+mod error_panel;
+mod events;
+mod facilities;
+mod favorites;
+mod filter;
+mod frequency;
+mod ghost_tracker;
+mod heatmap;
+mod hints;
+mod history;
+mod hitmap;
 mod interface;
-mod models;
+mod list;
+mod lock;
+mod logbuffer;
+mod lookup;
+mod metar;
+mod nearby;
+mod output;
+mod polling;
+mod query;
+mod regions;
+mod report;
+mod route;
+mod rows;
+mod schema;
+mod serve;
+mod session;
+mod shared;
+mod snapshots;
+mod special;
+mod split;
+mod squawk;
 mod state;
+mod stats;
+mod summary;
+mod terminal;
+#[cfg(test)]
+mod testing;
+mod theme;
+mod tour;
+mod traffic;
+mod trail;
+mod transitions;
+mod tune;
+mod watch;
+mod widgets;
+mod worker;
 
+use alerts::{AlertSettings, AlertSound};
 use anyhow::Result;
-use api::Vatsim;
 use clap::Parser;
+use cli::{Args, CacheAction, Command};
+use dump::DumpSettings;
+use mirrors::{MirrorChoice, MirrorSelection};
+use polling::AutoBounds;
+use report::{DataSource, Reporter, Verbosity};
+use reqwest::header::HeaderValue;
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use vatsim_online::{
+    airports, api, cache, columns, dump, enrich, flight, format, geo, ghosts, mirrors, models,
+    persist, privacy, reference, sanity, sort,
+};
 
 const LOG_FILE_NAME: &str = "vatsim_online.log";
+/// Exit code when the terminal can't show a full-screen view.
+const EXIT_UNSUPPORTED_TERMINAL: i32 = 3;
 
-#[derive(Debug, Parser)]
-#[clap(author, version, about, long_about = None)]
-struct Args {
-    /// Enable debug logging to a 'vatsim_online.log' file
-    #[clap(short, long)]
-    debug: bool,
+/// Places to try writing the debug log, in order: the working directory,
+/// then the platform's data directory.
+fn log_file_candidates() -> Vec<PathBuf> {
+    let mut paths = vec![PathBuf::from(LOG_FILE_NAME)];
+    if let Some(dir) = dirs::data_dir() {
+        paths.push(dir.join("vatsim_online").join(LOG_FILE_NAME));
+    }
+    paths
+}
+
+/// Open the first of the log files that can be written, appending so that
+/// several instances can share it.
+fn open_log_file(
+    candidates: Vec<PathBuf>,
+    reporter: &mut Reporter,
+) -> Option<(PathBuf, std::fs::File)> {
+    for path in candidates {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            if let Err(e) = std::fs::create_dir_all(parent) {
+                reporter.warn(format!("Could not create {}: {}", parent.display(), e));
+                continue;
+            }
+        }
+        match fern::log_file(&path) {
+            Ok(file) => return Some((path, file)),
+            Err(e) => reporter.warn(format!("Could not open log file {}: {}", path.display(), e)),
+        }
+    }
+    None
 }
 
-/// Configure the debug file logger.
-fn setup_logger() -> Result<(), fern::InitError> {
-    fern::Dispatch::new()
+/// Configure the debug logger.
+///
+/// Logs go to the first log file that can be opened, or to stderr if none
+/// can. Logging problems are reported but never stop the app from running.
+fn setup_logger(reporter: &mut Reporter) {
+    let dispatch = fern::Dispatch::new()
         .format(|out, message, record| {
             out.finish(format_args!(
                 "{} [{}] {}",
@@ -42,19 +143,364 @@ fn setup_logger() -> Result<(), fern::InitError> {
             ));
         })
         .level(log::LevelFilter::Info)
-        .level_for("vatsim_online", log::LevelFilter::Debug)
-        .chain(fern::log_file(LOG_FILE_NAME)?)
-        .apply()?;
-    Ok(())
+        .level_for("vatsim_online", log::LevelFilter::Debug);
+    // also kept in memory for the log panel
+    let dispatch = dispatch.chain(logbuffer::output());
+    let dispatch = if let Some((path, file)) = open_log_file(log_file_candidates(), reporter) {
+        if path != Path::new(LOG_FILE_NAME) {
+            reporter.warn(format!("Logging to {}", path.display()));
+        }
+        dispatch.chain(file)
+    } else {
+        reporter.warn("Logging to stderr, as no log file could be opened");
+        dispatch.chain(std::io::stderr())
+    };
+    if let Err(e) = dispatch.apply() {
+        reporter.warn(format!("Could not set up logging: {}", e));
+    }
+}
+
+/// The value of a full-screen view's result, or on an error, report it
+/// and exit: with advice and [`EXIT_UNSUPPORTED_TERMINAL`] if the terminal
+/// couldn't show the view, otherwise after `context` with 1.
+fn check_view<T>(result: Result<T>, context: &str, reporter: &mut Reporter) -> T {
+    match result {
+        Ok(value) => value,
+        Err(e) => {
+            if let Some(unsupported) = e.downcast_ref::<terminal::Unsupported>() {
+                reporter.error(unsupported);
+                std::process::exit(EXIT_UNSUPPORTED_TERMINAL);
+            }
+            or_exit(Err(e), context, reporter)
+        }
+    }
+}
+
+/// The value of a command-line path's result, or on an error, report it
+/// after `context` and exit with 1.
+fn or_exit<T>(result: Result<T>, context: &str, reporter: &mut Reporter) -> T {
+    match result {
+        Ok(value) => value,
+        Err(e) => {
+            reporter.error(format!("{}: {:#}", context, e));
+            std::process::exit(1);
+        }
+    }
 }
 
 /// Entry point.
+#[allow(clippy::too_many_lines)]
 fn main() {
     let args = Args::parse();
-    if args.debug {
-        setup_logger().expect("Could not configure logger");
+    let resolved =
+        config::load().and_then(|config| config.resolve(args.profile.as_deref(), args.given()));
+    let settings = match resolved {
+        Ok(settings) => settings,
+        Err(e) => {
+            Reporter::stdio(Verbosity::from_quiet(args.quiet)).error(format!("{:#}", e));
+            std::process::exit(2);
+        }
+    };
+    let mut reporter = Reporter::stdio(Verbosity::from_quiet(settings.quiet.unwrap_or_default()));
+    if settings.debug.unwrap_or_default() {
+        setup_logger(&mut reporter);
+    }
+    if let Some(locale) = settings.locale {
+        format::set_locale(locale);
+    }
+    let zone = settings.timezone.unwrap_or_default();
+    let use_cache = !settings.no_cache.unwrap_or_default();
+    let user_agent = settings
+        .user_agent
+        .unwrap_or_else(|| HeaderValue::from_static(api::DEFAULT_USER_AGENT));
+    let mirror = MirrorSelection {
+        choice: if settings.fastest_mirror.unwrap_or_default() {
+            MirrorChoice::Fastest
+        } else {
+            settings.mirror.unwrap_or_default()
+        },
+        allow_failover: settings.allow_failover.unwrap_or_default(),
+        seed: settings.seed,
+    };
+    let privacy = settings.privacy.unwrap_or_default();
+    let source = DataSource {
+        use_cache,
+        mirror: &mirror,
+        user_agent: &user_agent,
+        privacy,
+    };
+    if args.schema {
+        let _ = reporter.data(serde_json::to_string_pretty(&schema::schema()).unwrap_or_default());
+        return;
+    }
+    if args.summary {
+        let result = stats::run(
+            settings.snapshot_dir.as_deref(),
+            settings.sanity_limits.unwrap_or_default(),
+            &source,
+            &mut reporter,
+        );
+        or_exit(result, "Could not get network statistics", &mut reporter);
+        return;
+    }
+    if let Some(expr) = &args.count {
+        let result = count::print(expr, args.input.as_deref(), &source, &mut reporter);
+        if let Err(e) = result {
+            reporter.error(format!("Could not count clients: {:#}", e));
+            std::process::exit(1);
+        }
+        return;
+    }
+    match args.command {
+        Some(Command::Airport {
+            icao,
+            tui,
+            interval,
+        }) => {
+            let interval = interval.map(Duration::from_secs);
+            let result = if tui {
+                board::run_tui(&icao, interval, zone, use_cache, &mirror, &user_agent)
+            } else {
+                board::run(
+                    &icao,
+                    interval,
+                    zone,
+                    use_cache,
+                    &mirror,
+                    &user_agent,
+                    &mut reporter,
+                )
+            };
+            check_view(result, "Could not show the airport board", &mut reporter);
+        }
+        Some(Command::Atc {
+            icao,
+            positions,
+            json,
+        }) => {
+            let result = atc::run(
+                &icao,
+                &positions.unwrap_or_default(),
+                json,
+                &source,
+                &mut reporter,
+            );
+            if let Err(e) = &result {
+                reporter.error(format!("Could not check ATC: {:#}", e));
+            }
+            match atc::exit_code(&result) {
+                0 => {}
+                code => std::process::exit(code),
+            }
+        }
+        Some(Command::Cache {
+            action: CacheAction::Clear,
+        }) => {
+            if or_exit(cache::clear(), "Could not clear cache", &mut reporter) {
+                reporter.note("Cleared cache");
+            } else {
+                reporter.note("Nothing cached to clear");
+            }
+        }
+        Some(Command::Check) => {
+            // the check is of the status endpoint and the mirrors it lists
+            if mirror != MirrorSelection::default() {
+                reporter.error(
+                    "check tries the mirrors the status endpoint lists, so it takes no --mirror, --fastest-mirror, or --seed",
+                );
+                std::process::exit(2);
+            }
+            if !check::run(&user_agent, &mut reporter) {
+                std::process::exit(1);
+            }
+        }
+        Some(Command::CompareDay { dir }) => {
+            let result = compare::run(&dir, zone, &source, &mut reporter);
+            or_exit(result, "Could not compare with yesterday", &mut reporter);
+        }
+        Some(Command::Density { ascii }) => {
+            let result = heatmap::run(ascii, &source, &mut reporter);
+            or_exit(result, "Could not map pilots", &mut reporter);
+        }
+        Some(Command::Lookup {
+            file,
+            json,
+            fail_if_missing,
+        }) => {
+            let result = lookup::run(&file, json, &source, &mut reporter);
+            let all_online = or_exit(result, "Could not look up clients", &mut reporter);
+            if fail_if_missing && !all_online {
+                std::process::exit(1);
+            }
+        }
+        Some(Command::Metar { icao, decode }) => {
+            if let Err(e) = metar::run(&icao, decode, use_cache, &user_agent, &mut reporter) {
+                reporter.error(format!("Could not get METAR: {:#}", e));
+                std::process::exit(1);
+            }
+        }
+        Some(Command::List {
+            controllers,
+            format,
+        }) => {
+            let options = list::Options {
+                controllers,
+                format,
+                query: settings.query,
+                airlines: settings.airline,
+                sorts: settings.sort.unwrap_or_default(),
+                zone,
+            };
+            if let Err(e) = list::run(options, &source, &mut reporter) {
+                reporter.error(format!("Could not list clients: {:#}", e));
+                std::process::exit(1);
+            }
+        }
+        Some(Command::Favorites { action }) => {
+            let result = favorites::run(action, &mut reporter);
+            or_exit(result, "Could not update favorites", &mut reporter);
+        }
+        Some(Command::Serve {
+            address,
+            keep,
+            interval,
+        }) => {
+            let interval = Duration::from_secs(interval.max(1));
+            let result = serve::run(address, keep, interval, &source, &mut reporter);
+            or_exit(result, "Could not serve events", &mut reporter);
+        }
+        Some(Command::Tour) => {
+            let _ = reporter.data(tour::text().trim_end());
+        }
+        Some(Command::Watch { json, interval }) => {
+            let interval = Duration::from_secs(interval.max(1));
+            let result = watch::run(interval, json, zone, &source, &mut reporter);
+            or_exit(result, "Could not watch the network", &mut reporter);
+        }
+        None => {
+            let idle_minutes = settings.idle_minutes.unwrap_or(cli::DEFAULT_IDLE_MINUTES);
+            let stale_minutes = settings.stale_minutes.unwrap_or(cli::DEFAULT_STALE_MINUTES);
+            let startup_timeout = settings
+                .startup_timeout
+                .unwrap_or(cli::DEFAULT_STARTUP_TIMEOUT_SECS);
+            let result = interface::run(interface::Options {
+                restore: settings.restore.unwrap_or_default(),
+                use_cache,
+                low_bandwidth: settings.low_bandwidth.unwrap_or_default(),
+                user_agent,
+                summary_dir: settings.summary_dir,
+                idle_timeout: (idle_minutes > 0).then(|| Duration::from_secs(idle_minutes * 60)),
+                query: settings.query,
+                airlines: settings.airline,
+                sorts: settings.sort.unwrap_or_default(),
+                density: if settings.comfortable.unwrap_or_default() {
+                    state::Density::Comfortable
+                } else {
+                    state::Density::Compact
+                },
+                stale_after: (stale_minutes > 0).then(|| chrono::Duration::minutes(stale_minutes)),
+                sanity_limits: settings.sanity_limits.unwrap_or_default(),
+                privacy,
+                attach: args.attach,
+                custom_columns: or_exit(
+                    custom::load(),
+                    "Could not load custom columns",
+                    &mut reporter,
+                ),
+                alerts: AlertSettings {
+                    enabled: settings.alert.unwrap_or_default(),
+                    min_interval: Duration::from_secs(
+                        settings
+                            .alert_interval
+                            .unwrap_or(cli::DEFAULT_ALERT_INTERVAL_SECS),
+                    ),
+                },
+                alert_sound: settings
+                    .alert_sound
+                    .map_or(AlertSound::Bell, AlertSound::Command),
+                mirror,
+                zone,
+                startup_timeout: (startup_timeout > 0)
+                    .then(|| Duration::from_secs(startup_timeout)),
+                dumps: settings.debug_dump.map(|dir| DumpSettings {
+                    dir,
+                    all: settings.debug_dump_all.unwrap_or_default(),
+                }),
+                save_on_exit: !settings.no_save_on_exit.unwrap_or_default(),
+                poll_interval: settings.interval,
+                auto_bounds: AutoBounds {
+                    min: Duration::from_secs(
+                        settings
+                            .min_interval
+                            .unwrap_or(cli::DEFAULT_MIN_INTERVAL_SECS)
+                            .max(1),
+                    ),
+                    max: Duration::from_secs(
+                        settings
+                            .max_interval
+                            .unwrap_or(cli::DEFAULT_MAX_INTERVAL_SECS),
+                    ),
+                },
+                profile: args.profile,
+            });
+            if check_view(result, "Could not set up interface", &mut reporter) {
+                reporter.note("Cancelled");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, io::Write};
+
+    #[test]
+    fn log_file_falls_back_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        // a file where the first candidate's directory should be
+        let blocked = dir.path().join("blocked");
+        fs::write(&blocked, "").unwrap();
+        // and a directory where the second's file should be
+        let taken = dir.path().join("taken.log");
+        fs::create_dir(&taken).unwrap();
+        let fallback = dir.path().join("data").join(LOG_FILE_NAME);
+        let (mut reporter, _, err) = Reporter::captured(Verbosity::Normal);
+
+        let (path, _) = open_log_file(
+            vec![blocked.join(LOG_FILE_NAME), taken.clone(), fallback.clone()],
+            &mut reporter,
+        )
+        .unwrap();
+        assert_eq!(path, fallback);
+        let warnings = err.text();
+        assert_eq!(warnings.lines().count(), 2, "{}", warnings);
+        assert!(warnings.contains(&format!("Could not create {}", blocked.display())));
+        assert!(warnings.contains(&format!("Could not open log file {}", taken.display())));
+    }
+
+    #[test]
+    fn no_log_file_when_none_can_be_opened() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut reporter, _, _) = Reporter::captured(Verbosity::Normal);
+        assert!(open_log_file(vec![dir.path().to_owned()], &mut reporter).is_none());
+        assert!(open_log_file(Vec::new(), &mut reporter).is_none());
+    }
+
+    #[test]
+    fn instances_append_to_a_shared_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(LOG_FILE_NAME);
+        fs::write(&path, "earlier\n").unwrap();
+        let (mut reporter, _, _) = Reporter::captured(Verbosity::Normal);
+        let (_, mut first) = open_log_file(vec![path.clone()], &mut reporter).unwrap();
+        let (_, mut second) = open_log_file(vec![path.clone()], &mut reporter).unwrap();
+        first.write_all(b"first\n").unwrap();
+        second.write_all(b"second\n").unwrap();
+        first.write_all(b"first again\n").unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "earlier\nfirst\nsecond\nfirst again\n"
+        );
     }
-    let vatsim = Vatsim::new().expect("Could not set up access to VATSIM API");
-    let data = vatsim.get_data().expect("Could not get VATSIM data");
-    interface::run(data).expect("Could not set up interface");
 }
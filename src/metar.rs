@@ -0,0 +1,743 @@
+//! Decoding METARs into a readable summary, for the `metar` command.
+
+use crate::{api::Vatsim, format, report::Reporter};
+use anyhow::Result;
+use crossterm::style::{Color, Stylize};
+use reqwest::header::HeaderValue;
+use std::{
+    fmt,
+    io::{stdout, IsTerminal},
+};
+
+/// Statute miles in a meter.
+const MILES_PER_METER: f64 = 1.0 / 1609.344;
+
+/// Which kind of weather pilots can fly in, by ceiling and visibility.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlightCategory {
+    Vfr,
+    MarginalVfr,
+    Ifr,
+    LowIfr,
+}
+
+impl FlightCategory {
+    /// Work out the category from the ceiling in feet and the visibility in
+    /// statute miles, either of which might not be known.
+    fn from_conditions(ceiling_ft: Option<u32>, visibility_sm: Option<f64>) -> Self {
+        let ceiling = ceiling_ft.unwrap_or(u32::MAX);
+        let visibility = visibility_sm.unwrap_or(f64::MAX);
+        if ceiling < 500 || visibility < 1.0 {
+            FlightCategory::LowIfr
+        } else if ceiling < 1000 || visibility < 3.0 {
+            FlightCategory::Ifr
+        } else if ceiling <= 3000 || visibility <= 5.0 {
+            FlightCategory::MarginalVfr
+        } else {
+            FlightCategory::Vfr
+        }
+    }
+
+    /// Color the category is usually shown in.
+    fn color(self) -> Color {
+        match self {
+            FlightCategory::Vfr => Color::Green,
+            FlightCategory::MarginalVfr => Color::Blue,
+            FlightCategory::Ifr => Color::Red,
+            FlightCategory::LowIfr => Color::Magenta,
+        }
+    }
+}
+
+impl fmt::Display for FlightCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            FlightCategory::Vfr => "VFR",
+            FlightCategory::MarginalVfr => "MVFR",
+            FlightCategory::Ifr => "IFR",
+            FlightCategory::LowIfr => "LIFR",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Surface wind.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Wind {
+    Calm,
+    Blowing {
+        /// Direction in degrees, or `None` if variable.
+        direction: Option<u16>,
+        speed: u16,
+        gust: Option<u16>,
+        unit: &'static str,
+        /// Range the direction is varying between.
+        varying: Option<(u16, u16)>,
+    },
+}
+
+impl fmt::Display for Wind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Wind::Blowing {
+            direction,
+            speed,
+            gust,
+            unit,
+            varying,
+        } = self
+        else {
+            return write!(f, "calm");
+        };
+        match direction {
+            Some(direction) => write!(f, "{:03}\u{b0} at {} {}", direction, speed, unit)?,
+            None => write!(f, "variable at {} {}", speed, unit)?,
+        }
+        if let Some(gust) = gust {
+            write!(f, ", gusting {} {}", gust, unit)?;
+        }
+        if let Some((from, to)) = varying {
+            write!(f, ", varying {:03}\u{b0}\u{2013}{:03}\u{b0}", from, to)?;
+        }
+        Ok(())
+    }
+}
+
+/// Prevailing visibility, as reported and in statute miles.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Visibility {
+    pub text: String,
+    pub statute_miles: f64,
+}
+
+/// A layer of cloud, or the vertical visibility into an obscured sky.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CloudLayer {
+    /// "FEW", "SCT", "BKN", "OVC", or "VV".
+    pub cover: String,
+    pub base_ft: u32,
+    /// "CB" or "TCU", if reported.
+    pub kind: Option<String>,
+}
+
+impl CloudLayer {
+    /// Whether the layer counts as a ceiling.
+    fn is_ceiling(&self) -> bool {
+        matches!(self.cover.as_str(), "BKN" | "OVC" | "VV")
+    }
+}
+
+impl fmt::Display for CloudLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let cover = match self.cover.as_str() {
+            "FEW" => "few",
+            "SCT" => "scattered",
+            "BKN" => "broken",
+            "OVC" => "overcast",
+            _ => "vertical visibility",
+        };
+        write!(
+            f,
+            "{} {} ft",
+            cover,
+            format::thousands(self.base_ft as usize)
+        )?;
+        if let Some(kind) = &self.kind {
+            write!(f, " ({})", kind)?;
+        }
+        Ok(())
+    }
+}
+
+/// Altimeter setting.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pressure {
+    Hectopascals(u16),
+    InchesOfMercury(f64),
+}
+
+impl fmt::Display for Pressure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Pressure::Hectopascals(hpa) => write!(f, "{} hPa", hpa),
+            Pressure::InchesOfMercury(inches) => {
+                write!(f, "{} inHg", format::decimal(*inches, 2))
+            }
+        }
+    }
+}
+
+/// The parts of a METAR this decodes.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Metar {
+    pub station: String,
+    pub wind: Option<Wind>,
+    pub visibility: Option<Visibility>,
+    /// "Ceiling and visibility OK": 10 km or more and no cloud below 5,000 ft.
+    pub cavok: bool,
+    pub clouds: Vec<CloudLayer>,
+    /// Temperature and dewpoint in degrees Celsius.
+    pub temperature: Option<(i32, Option<i32>)>,
+    pub pressure: Option<Pressure>,
+}
+
+impl Metar {
+    /// Parse a raw METAR, like "EGLL 161250Z 24012G22KT 9999 BKN025 12/08 Q1013".
+    ///
+    /// Returns `None` if it doesn't look like a METAR: no station, or none of
+    /// the wind, visibility, and temperature.
+    pub fn parse(raw: &str) -> Option<Self> {
+        let mut tokens = raw
+            .split_whitespace()
+            .filter(|token| !matches!(*token, "METAR" | "SPECI" | "AUTO" | "COR"));
+        let station = tokens.next()?;
+        if station.len() != 4 || !station.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return None;
+        }
+        let mut metar = Metar {
+            station: station.to_owned(),
+            ..Metar::default()
+        };
+        let mut whole_miles: Option<f64> = None;
+        for token in tokens {
+            if matches!(token, "RMK" | "TEMPO" | "BECMG" | "NOSIG") {
+                break;
+            }
+            if let Some(miles) = whole_miles.take() {
+                if let Some(fraction) = parse_statute_miles(token) {
+                    metar.visibility = Some(Visibility {
+                        text: format!("{} {}", miles, token.replace("SM", " SM")),
+                        statute_miles: miles + fraction,
+                    });
+                    continue;
+                }
+            }
+            if token == "CAVOK" {
+                metar.cavok = true;
+            } else if let Some(wind) = parse_wind(token) {
+                metar.wind = Some(wind);
+            } else if let Some(range) = parse_varying(token) {
+                if let Some(Wind::Blowing { varying, .. }) = &mut metar.wind {
+                    *varying = Some(range);
+                }
+            } else if let Some(visibility) = parse_visibility(token) {
+                metar.visibility = Some(visibility);
+            } else if token.len() == 1 && token.as_bytes()[0].is_ascii_digit() {
+                // the whole part of a visibility like "1 1/2SM"
+                whole_miles = token.parse().ok();
+            } else if let Some(layer) = parse_cloud(token) {
+                metar.clouds.push(layer);
+            } else if let Some(temperature) = parse_temperature(token) {
+                metar.temperature = Some(temperature);
+            } else if let Some(pressure) = parse_pressure(token) {
+                metar.pressure = Some(pressure);
+            }
+        }
+        if metar.wind.is_none()
+            && metar.visibility.is_none()
+            && !metar.cavok
+            && metar.temperature.is_none()
+        {
+            return None;
+        }
+        Some(metar)
+    }
+
+    /// Height of the lowest broken, overcast, or obscured layer.
+    pub fn ceiling_ft(&self) -> Option<u32> {
+        self.clouds
+            .iter()
+            .filter(|layer| layer.is_ceiling())
+            .map(|layer| layer.base_ft)
+            .min()
+    }
+
+    /// Visibility in statute miles; CAVOK is 10 km or more.
+    fn visibility_sm(&self) -> Option<f64> {
+        if self.cavok {
+            Some(10_000.0 * MILES_PER_METER)
+        } else {
+            self.visibility.as_ref().map(|v| v.statute_miles)
+        }
+    }
+
+    pub fn category(&self) -> FlightCategory {
+        FlightCategory::from_conditions(self.ceiling_ft(), self.visibility_sm())
+    }
+
+    /// The decoded METAR as lines of text, with the flight category colored
+    /// if `color` is set.
+    pub fn summary(&self, color: bool) -> String {
+        let category = self.category();
+        let category = if color {
+            category
+                .to_string()
+                .with(category.color())
+                .bold()
+                .to_string()
+        } else {
+            category.to_string()
+        };
+        let unknown = || "not reported".to_owned();
+        let visibility = if self.cavok {
+            "10 km or more (CAVOK)".to_owned()
+        } else {
+            self.visibility
+                .as_ref()
+                .map_or_else(unknown, |v| v.text.clone())
+        };
+        let ceiling = self
+            .clouds
+            .iter()
+            .find(|layer| layer.is_ceiling())
+            .map_or_else(
+                || "none".to_owned(),
+                |layer| format!("{} ft", format::thousands(layer.base_ft as usize)),
+            );
+        let clouds = if self.clouds.is_empty() {
+            if self.cavok {
+                "none below 5,000 ft".to_owned()
+            } else {
+                "none reported".to_owned()
+            }
+        } else {
+            self.clouds
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let temperature = self
+            .temperature
+            .map_or_else(unknown, |(temperature, dewpoint)| match dewpoint {
+                Some(dewpoint) => format!("{}\u{b0}C, dewpoint {}\u{b0}C", temperature, dewpoint),
+                None => format!("{}\u{b0}C", temperature),
+            });
+        [
+            format!("{}: {}", self.station, category),
+            format!(
+                "Wind: {}",
+                self.wind.as_ref().map_or_else(unknown, ToString::to_string)
+            ),
+            format!("Visibility: {}", visibility),
+            format!("Ceiling: {}", ceiling),
+            format!("Clouds: {}", clouds),
+            format!("Temperature: {}", temperature),
+            format!(
+                "Pressure: {}",
+                self.pressure.map_or_else(unknown, |p| p.to_string())
+            ),
+        ]
+        .join("\n")
+    }
+}
+
+/// Parse a wind group like "24012KT", "VRB03KT", "27015G25KT", or "00000KT".
+fn parse_wind(token: &str) -> Option<Wind> {
+    let (body, unit) = if let Some(body) = token.strip_suffix("KT") {
+        (body, "kt")
+    } else if let Some(body) = token.strip_suffix("MPS") {
+        (body, "m/s")
+    } else {
+        return None;
+    };
+    if body.len() < 5 {
+        return None;
+    }
+    let (direction, rest) = body.split_at(3);
+    let direction = if direction == "VRB" {
+        None
+    } else {
+        Some(parse_digits(direction)?)
+    };
+    let (speed, gust) = match rest.split_once('G') {
+        Some((speed, gust)) => (parse_digits(speed)?, Some(parse_digits(gust)?)),
+        None => (parse_digits(rest)?, None),
+    };
+    if direction == Some(0) && speed == 0 {
+        return Some(Wind::Calm);
+    }
+    Some(Wind::Blowing {
+        direction,
+        speed,
+        gust,
+        unit,
+        varying: None,
+    })
+}
+
+/// Parse a variable wind direction group like "210V270".
+fn parse_varying(token: &str) -> Option<(u16, u16)> {
+    let (from, to) = token.split_once('V')?;
+    if from.len() != 3 || to.len() != 3 {
+        return None;
+    }
+    Some((parse_digits(from)?, parse_digits(to)?))
+}
+
+/// Parse a visibility in meters like "9999" or "0800", or in statute miles
+/// like "10SM", "1/2SM", "M1/4SM", or "P6SM".
+fn parse_visibility(token: &str) -> Option<Visibility> {
+    if token.len() == 4 && token.chars().all(|c| c.is_ascii_digit()) {
+        let meters: u16 = token.parse().ok()?;
+        let text = if meters == 9999 {
+            "10 km or more".to_owned()
+        } else if meters >= 5000 {
+            format!("{} km", meters / 1000)
+        } else {
+            format!("{} m", meters)
+        };
+        return Some(Visibility {
+            text,
+            statute_miles: f64::from(meters) * MILES_PER_METER,
+        });
+    }
+    let statute_miles = parse_statute_miles(token)?;
+    let text = match token.as_bytes().first() {
+        Some(b'P') => format!("more than {}", &token[1..]),
+        Some(b'M') => format!("less than {}", &token[1..]),
+        _ => token.to_owned(),
+    };
+    Some(Visibility {
+        text: text.replace("SM", " SM"),
+        statute_miles,
+    })
+}
+
+/// Parse statute miles like "10SM", "1/2SM", "M1/4SM", or "P6SM".
+fn parse_statute_miles(token: &str) -> Option<f64> {
+    let miles = token.strip_suffix("SM")?;
+    let miles = miles
+        .strip_prefix('P')
+        .or_else(|| miles.strip_prefix('M'))
+        .unwrap_or(miles);
+    match miles.split_once('/') {
+        Some((numerator, denominator)) => {
+            let numerator: f64 = numerator.parse().ok()?;
+            let denominator: f64 = denominator.parse().ok()?;
+            (denominator > 0.0).then(|| numerator / denominator)
+        }
+        None => miles.parse().ok(),
+    }
+}
+
+/// Parse a cloud group like "BKN025", "FEW012CB", or "VV002".
+fn parse_cloud(token: &str) -> Option<CloudLayer> {
+    let cover = ["FEW", "SCT", "BKN", "OVC", "VV"]
+        .into_iter()
+        .find(|cover| token.starts_with(cover))?;
+    let rest = &token[cover.len()..];
+    if rest.len() < 3 {
+        return None;
+    }
+    let (height, kind) = rest.split_at(3);
+    let hundreds: u32 = parse_digits(height)?.into();
+    Some(CloudLayer {
+        cover: cover.to_owned(),
+        base_ft: hundreds * 100,
+        // automated stations send "///" when they can't tell the kind
+        kind: matches!(kind, "CB" | "TCU").then(|| kind.to_owned()),
+    })
+}
+
+/// Parse a temperature group like "12/08", "M05/M07", or "12/".
+fn parse_temperature(token: &str) -> Option<(i32, Option<i32>)> {
+    let (temperature, dewpoint) = token.split_once('/')?;
+    let degrees = |text: &str| -> Option<i32> {
+        let (sign, digits) = match text.strip_prefix('M') {
+            Some(digits) => (-1, digits),
+            None => (1, text),
+        };
+        if digits.len() != 2 {
+            return None;
+        }
+        Some(sign * i32::from(parse_digits(digits)?))
+    };
+    let dewpoint = if dewpoint.is_empty() {
+        None
+    } else {
+        Some(degrees(dewpoint)?)
+    };
+    Some((degrees(temperature)?, dewpoint))
+}
+
+/// Parse an altimeter setting like "Q1013" or "A2992".
+fn parse_pressure(token: &str) -> Option<Pressure> {
+    if token.len() != 5 {
+        return None;
+    }
+    let value = parse_digits(&token[1..])?;
+    match token.as_bytes()[0] {
+        b'Q' => Some(Pressure::Hectopascals(value)),
+        b'A' => Some(Pressure::InchesOfMercury(f64::from(value) / 100.0)),
+        _ => None,
+    }
+}
+
+/// Parse a group of ASCII digits.
+fn parse_digits(text: &str) -> Option<u16> {
+    if text.is_empty() || !text.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    text.parse().ok()
+}
+
+/// Print a METAR raw, or decoded with the raw text kept below.
+///
+/// A METAR that can't be decoded is printed raw, with a warning.
+fn print(raw: &str, decode: bool, color: bool, reporter: &mut Reporter) -> Result<()> {
+    if !decode {
+        reporter.data(raw)?;
+        return Ok(());
+    }
+    if let Some(metar) = Metar::parse(raw) {
+        reporter.data(format!("{}\n\n{}", metar.summary(color), raw))?;
+    } else {
+        reporter.data(raw)?;
+        reporter.warn("Could not decode this METAR");
+    }
+    Ok(())
+}
+
+/// Fetch and print the METAR for an airport.
+pub fn run(
+    icao: &str,
+    decode: bool,
+    use_cache: bool,
+    user_agent: &HeaderValue,
+    reporter: &mut Reporter,
+) -> Result<()> {
+    let raw = Vatsim::new(use_cache, false, user_agent)?.get_metar(&icao.to_uppercase())?;
+    print(&raw, decode, stdout().is_terminal(), reporter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::Verbosity;
+
+    fn parse(raw: &str) -> Metar {
+        Metar::parse(raw).unwrap_or_else(|| panic!("could not parse {:?}", raw))
+    }
+
+    fn blowing(direction: Option<u16>, speed: u16, gust: Option<u16>) -> Wind {
+        Wind::Blowing {
+            direction,
+            speed,
+            gust,
+            unit: "kt",
+            varying: None,
+        }
+    }
+
+    fn layer(cover: &str, base_ft: u32, kind: Option<&str>) -> CloudLayer {
+        CloudLayer {
+            cover: cover.to_owned(),
+            base_ft,
+            kind: kind.map(str::to_owned),
+        }
+    }
+
+    /// Real METARs and the flight category each decodes to.
+    const CORPUS: [(&str, FlightCategory); 10] = [
+        (
+            "EGLL 161250Z 24012G22KT 210V270 9999 FEW025 BKN040 12/08 Q1013 NOSIG",
+            FlightCategory::Vfr,
+        ),
+        (
+            "KSFO 161256Z 29015KT 10SM FEW008 SCT200 17/12 A2992 RMK AO2 SLP132",
+            FlightCategory::Vfr,
+        ),
+        (
+            "METAR KJFK 161251Z 03008KT 2 1/2SM BR OVC007 14/13 A3001",
+            FlightCategory::Ifr,
+        ),
+        (
+            "LFPG 161300Z AUTO VRB02KT CAVOK 22/11 Q1021",
+            FlightCategory::Vfr,
+        ),
+        (
+            "KORD 161251Z 00000KT M1/4SM FG VV002 08/08 A3012",
+            FlightCategory::LowIfr,
+        ),
+        (
+            "EDDF 161250Z 27005MPS 4000 -RA BKN012 OVC025 M02/M04 Q0998",
+            FlightCategory::Ifr,
+        ),
+        (
+            "KDEN 161253Z 36021G35KT P6SM FEW060CB BKN090 24/M03 A3021",
+            FlightCategory::Vfr,
+        ),
+        (
+            "SPECI EGPH 161320Z 09010KT 0800 R24/1000N FG OVC001 09/09 Q1025",
+            FlightCategory::LowIfr,
+        ),
+        (
+            "YSSY 161300Z 16008KT 9999 BKN030/// 15/ Q1018 TEMPO 3000 SHRA",
+            FlightCategory::MarginalVfr,
+        ),
+        (
+            "KBOS 161254Z 05012KT 4SM -SN BR SCT015 BKN025 OVC040 M01/M03 A2988",
+            FlightCategory::MarginalVfr,
+        ),
+    ];
+
+    #[test]
+    fn decodes_the_corpus() {
+        for (raw, category) in CORPUS {
+            assert_eq!(parse(raw).category(), category, "{}", raw);
+        }
+    }
+
+    #[test]
+    fn wind_groups() {
+        assert_eq!(parse("KJFK 161251Z 00000KT 10SM").wind, Some(Wind::Calm));
+        assert_eq!(
+            parse("KJFK 161251Z VRB03KT 10SM").wind,
+            Some(blowing(None, 3, None))
+        );
+        assert_eq!(
+            parse("KDEN 161253Z 36021G35KT P6SM").wind,
+            Some(blowing(Some(360), 21, Some(35)))
+        );
+        let wind = parse(CORPUS[0].0).wind.unwrap();
+        assert_eq!(
+            wind.to_string(),
+            "240\u{b0} at 12 kt, gusting 22 kt, varying 210\u{b0}\u{2013}270\u{b0}"
+        );
+        assert_eq!(
+            parse(CORPUS[5].0).wind.unwrap().to_string(),
+            "270\u{b0} at 5 m/s"
+        );
+        assert_eq!(parse("KJFK 161251Z /////KT 10SM").wind, None);
+    }
+
+    #[test]
+    fn visibility_formats() {
+        let visibility = |raw| parse(raw).visibility.unwrap();
+        assert_eq!(visibility("EGLL 161250Z 9999").text, "10 km or more");
+        assert_eq!(visibility("EDDF 161250Z 6000").text, "6 km");
+        assert_eq!(visibility("EGPH 161320Z 0800").text, "800 m");
+        let fraction = visibility(CORPUS[2].0);
+        assert_eq!(
+            (fraction.text.as_str(), fraction.statute_miles),
+            ("2 1/2 SM", 2.5)
+        );
+        assert_eq!(visibility("KORD 161251Z M1/4SM").text, "less than 1/4 SM");
+        assert_eq!(visibility("KDEN 161253Z P6SM").text, "more than 6 SM");
+        assert_eq!(visibility("KSFO 161256Z 10SM").text, "10 SM");
+        // a lone digit that isn't followed by a fraction isn't visibility
+        assert_eq!(parse("KJFK 161251Z 03008KT 1 BKN010").visibility, None);
+        assert!(parse(CORPUS[3].0).cavok);
+    }
+
+    #[test]
+    fn cloud_layers_and_ceiling() {
+        let metar = parse(CORPUS[9].0);
+        assert_eq!(
+            metar.clouds,
+            [
+                layer("SCT", 1_500, None),
+                layer("BKN", 2_500, None),
+                layer("OVC", 4_000, None)
+            ]
+        );
+        assert_eq!(metar.ceiling_ft(), Some(2_500));
+        assert_eq!(
+            parse(CORPUS[6].0).clouds[0],
+            layer("FEW", 6_000, Some("CB"))
+        );
+        assert_eq!(parse(CORPUS[8].0).clouds, [layer("BKN", 3_000, None)]);
+        assert_eq!(parse(CORPUS[4].0).ceiling_ft(), Some(200));
+        assert_eq!(parse(CORPUS[1].0).ceiling_ft(), None);
+    }
+
+    #[test]
+    fn temperature_and_pressure() {
+        let metar = parse(CORPUS[5].0);
+        assert_eq!(metar.temperature, Some((-2, Some(-4))));
+        assert_eq!(metar.pressure, Some(Pressure::Hectopascals(998)));
+        assert_eq!(parse(CORPUS[8].0).temperature, Some((15, None)));
+        let pressure = parse(CORPUS[1].0).pressure.unwrap();
+        assert_eq!(pressure, Pressure::InchesOfMercury(29.92));
+        assert_eq!(pressure.to_string(), "29.92 inHg");
+    }
+
+    #[test]
+    fn remarks_and_trends_are_skipped() {
+        // "TEMPO 3000" would otherwise replace the visibility
+        assert_eq!(parse(CORPUS[8].0).visibility.unwrap().text, "10 km or more");
+        assert_eq!(parse(CORPUS[1].0).temperature, Some((17, Some(12))));
+    }
+
+    #[test]
+    fn category_boundaries() {
+        let category = FlightCategory::from_conditions;
+        assert_eq!(category(Some(3_100), Some(5.5)), FlightCategory::Vfr);
+        assert_eq!(category(Some(3_000), None), FlightCategory::MarginalVfr);
+        assert_eq!(category(None, Some(5.0)), FlightCategory::MarginalVfr);
+        assert_eq!(category(Some(900), Some(10.0)), FlightCategory::Ifr);
+        assert_eq!(category(None, Some(2.9)), FlightCategory::Ifr);
+        assert_eq!(category(Some(400), None), FlightCategory::LowIfr);
+        assert_eq!(category(None, Some(0.5)), FlightCategory::LowIfr);
+        assert_eq!(category(None, None), FlightCategory::Vfr);
+    }
+
+    #[test]
+    fn summary_lines() {
+        assert_eq!(
+            parse(CORPUS[3].0).summary(false),
+            "LFPG: VFR\nWind: variable at 2 kt\nVisibility: 10 km or more (CAVOK)\nCeiling: none\nClouds: none below 5,000 ft\nTemperature: 22\u{b0}C, dewpoint 11\u{b0}C\nPressure: 1021 hPa"
+        );
+        let colored = parse(CORPUS[4].0).summary(true);
+        assert!(colored.starts_with("KORD: \u{1b}["), "{:?}", colored);
+    }
+
+    #[test]
+    fn undecodable_metars_are_shown_raw() {
+        for raw in ["", "NIL", "KJFK", "KJFK 161251Z NIL", "not a metar at all"] {
+            assert_eq!(Metar::parse(raw), None, "{:?}", raw);
+        }
+        assert_eq!(
+            printed("KJFK 161251Z NIL", true, Verbosity::Normal),
+            (
+                "KJFK 161251Z NIL\n".to_owned(),
+                "Could not decode this METAR\n".to_owned()
+            )
+        );
+    }
+
+    /// What printing a METAR writes to (stdout, stderr).
+    fn printed(raw: &str, decode: bool, verbosity: Verbosity) -> (String, String) {
+        let (mut reporter, out, err) = Reporter::captured(verbosity);
+        print(raw, decode, false, &mut reporter).unwrap();
+        (out.text(), err.text())
+    }
+
+    #[test]
+    fn prints_the_metar_alone_on_stdout() {
+        let raw = CORPUS[3].0;
+        for verbosity in [Verbosity::Normal, Verbosity::Quiet, Verbosity::Silent] {
+            assert_eq!(
+                printed(raw, false, verbosity),
+                (format!("{}\n", raw), String::new())
+            );
+            let (out, err) = printed(raw, true, verbosity);
+            assert!(out.starts_with("LFPG: VFR\n"), "{:?}", out);
+            assert!(out.ends_with(&format!("\n\n{}\n", raw)), "{:?}", out);
+            assert_eq!(err, "");
+        }
+        // only the warning is silenced
+        assert_eq!(
+            printed("NIL", true, Verbosity::Quiet).1,
+            "Could not decode this METAR\n"
+        );
+        assert_eq!(
+            printed("NIL", true, Verbosity::Silent),
+            ("NIL\n".to_owned(), String::new())
+        );
+        assert_eq!(
+            printed("NIL", false, Verbosity::Normal),
+            ("NIL\n".to_owned(), String::new())
+        );
+    }
+}
@@ -0,0 +1,960 @@
+//! The parts of talking to the VATSIM API that don't depend on how the
+//! requests are made, shared by the blocking and async clients.
+
+use crate::{
+    api::DataResponse,
+    cache,
+    models::{parse_time, Event, Status, V3ResponseData},
+    sort::{sort_rows, SortKey},
+};
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, Utc};
+use log::{debug, warn};
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use reqwest::{
+    header::{HeaderMap, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+    StatusCode,
+};
+use serde_json::error::Category;
+use std::{
+    collections::hash_map::DefaultHasher,
+    error::Error,
+    fmt,
+    hash::{Hash, Hasher},
+    time::Duration,
+};
+
+/// Initial VATSIM API requests are made to this endpoint.
+pub const STATUS_URL: &str = "https://status.vatsim.net/status.json";
+
+/// Consecutive fetches without newer data before a mirror is treated as stale.
+const STALE_FETCH_LIMIT: usize = 3;
+
+/// Current and upcoming events are listed at this endpoint.
+pub const EVENTS_URL: &str = "https://my.vatsim.net/api/v2/events/latest";
+
+/// How long to wait for each mirror to answer when racing them.
+pub const RACE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Where the status document is fetched from.
+#[cfg(not(test))]
+#[must_use]
+pub fn status_url() -> String {
+    STATUS_URL.to_owned()
+}
+
+/// The mock server's status document, in tests.
+#[cfg(test)]
+#[must_use]
+pub fn status_url() -> String {
+    format!("{}/status.json", mockito::server_url())
+}
+
+/// Where the events list is fetched from.
+#[cfg(not(test))]
+#[must_use]
+pub fn events_url() -> String {
+    EVENTS_URL.to_owned()
+}
+
+/// The mock server's events list, in tests.
+#[cfg(test)]
+#[must_use]
+pub fn events_url() -> String {
+    format!("{}/events", mockito::server_url())
+}
+
+/// A mirror sent a body that isn't usable JSON, so another mirror should be tried.
+#[derive(Debug)]
+struct BadBody(String);
+
+impl fmt::Display for BadBody {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for BadBody {}
+
+/// An endpoint answered with a status other than a success.
+#[derive(Debug)]
+struct BadStatus(StatusCode, String);
+
+impl fmt::Display for BadStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Got status {} from {} endpoint", self.0.as_u16(), self.1)
+    }
+}
+
+impl Error for BadStatus {}
+
+/// Whether a failed request is down to the mirror rather than the request.
+fn mirror_at_fault(error: &anyhow::Error) -> bool {
+    if error.is::<BadBody>() {
+        return true;
+    }
+    if let Some(BadStatus(status, _)) = error.downcast_ref() {
+        return status.is_server_error();
+    }
+    error.chain().any(|cause| {
+        cause
+            .downcast_ref::<reqwest::Error>()
+            .is_some_and(|e| e.is_connect() || e.is_timeout() || e.is_request() || e.is_body())
+    })
+}
+
+/// Check that a V3 response body looks like JSON before parsing it.
+///
+/// Misbehaving mirrors sometimes send HTML error pages with a 200 status.
+fn check_body(content_type: Option<&str>, body: &[u8]) -> Result<(), BadBody> {
+    match body.iter().find(|b| !b.is_ascii_whitespace()) {
+        Some(b'{') => Ok(()),
+        Some(_) => Err(BadBody(format!(
+            "mirror returned non-JSON response ({})",
+            content_type.unwrap_or("no content type")
+        ))),
+        None => Err(BadBody("mirror returned an empty response".to_owned())),
+    }
+}
+
+/// Fail if a response's status isn't a success.
+///
+/// # Errors
+///
+/// If the status isn't a success, naming the endpoint.
+pub fn check_status(status: StatusCode, endpoint: &str) -> Result<()> {
+    if status.is_success() {
+        Ok(())
+    } else {
+        Err(BadStatus(status, endpoint.to_owned()).into())
+    }
+}
+
+/// The cached status document, if `use_cache` is set and it's fresh.
+#[must_use]
+pub fn cached_status(use_cache: bool) -> Option<Status> {
+    if use_cache {
+        cache::load_status(cache::status_max_age())
+    } else {
+        None
+    }
+}
+
+/// Cache a status document that was just fetched.
+pub fn status_fetched(status: &Status) {
+    if let Err(e) = cache::save_status(status) {
+        warn!("Could not cache status: {}", e);
+    }
+}
+
+/// The cached events list, if `use_cache` is set and it's fresh.
+#[must_use]
+pub fn cached_events(use_cache: bool) -> Option<Vec<Event>> {
+    if use_cache {
+        cache::load_events(cache::events_max_age())
+    } else {
+        None
+    }
+}
+
+/// Cache an events list that was just fetched.
+pub fn events_fetched(events: &[Event]) {
+    if let Err(e) = cache::save_events(events) {
+        warn!("Could not cache events: {}", e);
+    }
+}
+
+/// Which V3 mirror to use.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum MirrorChoice {
+    /// A random mirror from the status document.
+    #[default]
+    Random,
+    /// The mirror at this index in the status document's list.
+    Index(usize),
+    /// This URL, without asking the status endpoint for mirrors.
+    Url(String),
+    /// Whichever mirror answers a HEAD request fastest, with the others
+    /// after it from fastest to slowest.
+    Fastest,
+}
+
+impl MirrorChoice {
+    /// Parse a mirror from the command line: an index or a URL.
+    ///
+    /// # Errors
+    ///
+    /// If the value is neither an index nor an http(s) URL.
+    pub fn parse(value: &str) -> Result<Self> {
+        let value = value.trim();
+        if let Ok(index) = value.parse() {
+            Ok(Self::Index(index))
+        } else if value.starts_with("http://") || value.starts_with("https://") {
+            Ok(Self::Url(value.to_owned()))
+        } else {
+            bail!(
+                "mirror {:?} must be an index into the status document's V3 list or an http(s) URL",
+                value
+            )
+        }
+    }
+}
+
+/// The mirror to use, and whether others can be used if it misbehaves.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MirrorSelection {
+    pub choice: MirrorChoice,
+    /// Fail over from a chosen mirror to the others. A random mirror
+    /// always can.
+    pub allow_failover: bool,
+    /// Seed for shuffling the mirrors, to make the order reproducible.
+    pub seed: Option<u64>,
+}
+
+impl MirrorSelection {
+    /// Whether the status document is needed to find the mirrors.
+    #[must_use]
+    pub fn needs_status(&self) -> bool {
+        !matches!(self.choice, MirrorChoice::Url(_)) || self.allow_failover
+    }
+}
+
+/// How quickly a mirror answered when the mirrors were raced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MirrorLatency {
+    pub url: String,
+    /// Time to answer, or `None` if it failed, timed out, or sent an error
+    /// status.
+    pub latency: Option<Duration>,
+}
+
+impl MirrorLatency {
+    /// Record how a mirror answered a HEAD request, after `elapsed`.
+    #[must_use]
+    pub fn measured(url: &str, elapsed: Duration, status: reqwest::Result<StatusCode>) -> Self {
+        let latency = match status {
+            Ok(status) if status.is_success() => Some(elapsed),
+            Ok(status) => {
+                debug!("Mirror {} answered the race with {}", url, status);
+                None
+            }
+            Err(e) => {
+                debug!("Mirror {} failed the race: {}", url, e);
+                None
+            }
+        };
+        Self {
+            url: url.to_owned(),
+            latency,
+        }
+    }
+}
+
+/// Put the mirrors that answered first, fastest to slowest, and the rest
+/// after them in the order they were in.
+pub fn order_by_latency(urls: &mut [String], latencies: &[MirrorLatency]) {
+    urls.sort_by_key(|url| {
+        latencies
+            .iter()
+            .find(|latency| &latency.url == url)
+            .and_then(|latency| latency.latency)
+            .map_or((true, Duration::ZERO), |latency| (false, latency))
+    });
+}
+
+/// What the headers of a V3 response say about its body.
+#[derive(Debug)]
+pub enum Head {
+    /// The server said nothing changed, so there's no body to read.
+    NotModified,
+    /// There's a body to read, with its content type.
+    Body(Option<String>),
+}
+
+/// The V3 endpoint mirrors from the status document, which one is in use,
+/// and what it last sent.
+#[derive(Debug)]
+pub struct Mirrors {
+    /// V3 endpoint mirrors, in the order they're tried.
+    v3_urls: Vec<String>,
+    /// Index of the mirror in use.
+    mirror: usize,
+    /// METAR endpoints from the status document.
+    metar_urls: Vec<String>,
+    /// Use conditional requests and skip parsing unchanged responses.
+    low_bandwidth: bool,
+    /// Validators from the last V3 response, for conditional requests.
+    etag: Option<String>,
+    last_modified: Option<String>,
+    /// Hash of the last V3 response body.
+    body_hash: Option<u64>,
+    /// Update timestamp of the newest V3 data from the mirror in use.
+    last_update: Option<DateTime<Utc>>,
+    /// Consecutive fetches where the update timestamp didn't advance.
+    stale_fetches: usize,
+    /// A stale mirror was switched away from since this was last checked.
+    switched_stale: bool,
+    /// How quickly each mirror answered, if they were raced.
+    latencies: Vec<MirrorLatency>,
+}
+
+impl Mirrors {
+    /// Mirrors from a status document, in a random order after the chosen
+    /// one, if any.
+    ///
+    /// Without `allow_failover`, a chosen mirror is the only one. The status
+    /// document is only optional when the choice is a URL. The order is the
+    /// same every time for the same seed and status document.
+    ///
+    /// To use the fastest mirror, `latencies` has how quickly each answered
+    /// a race; mirrors that didn't answer go last, in the random order.
+    ///
+    /// # Errors
+    ///
+    /// If the chosen index isn't in the status document's list, or
+    /// there are no mirrors at all.
+    pub fn new(
+        status: Option<Status>,
+        low_bandwidth: bool,
+        selection: &MirrorSelection,
+        latencies: Vec<MirrorLatency>,
+    ) -> Result<Self> {
+        let (mut urls, metar_urls) = status.map_or_else(Default::default, |s| (s.data.v3, s.metar));
+        let chosen = match &selection.choice {
+            MirrorChoice::Random | MirrorChoice::Fastest => None,
+            MirrorChoice::Index(_) if urls.is_empty() => None,
+            MirrorChoice::Index(index) => Some(urls.get(*index).cloned().ok_or_else(|| {
+                anyhow!(
+                    "Mirror index {} is out of range; the status document lists {} mirrors (0 to {})",
+                    index,
+                    urls.len(),
+                    urls.len() - 1
+                )
+            })?),
+            MirrorChoice::Url(url) => Some(url.clone()),
+        };
+        let listed = urls.clone();
+        let mut rng = selection
+            .seed
+            .map_or_else(StdRng::from_entropy, StdRng::seed_from_u64);
+        urls.shuffle(&mut rng);
+        if selection.choice == MirrorChoice::Fastest {
+            order_by_latency(&mut urls, &latencies);
+            match latencies.iter().filter_map(|l| l.latency).min() {
+                Some(fastest) => debug!("Fastest V3 mirror answered in {:?}", fastest),
+                None => warn!("No V3 mirror answered the race, using a random one"),
+            }
+        }
+        if let Some(chosen) = chosen {
+            debug!("Using chosen V3 mirror {}", chosen);
+            if selection.allow_failover {
+                urls.retain(|url| url != &chosen);
+                urls.insert(0, chosen);
+            } else {
+                urls = vec![chosen];
+            }
+        }
+        if urls.is_empty() {
+            return Err(anyhow!("No V3 URLs returned"));
+        }
+        debug!("V3 URLs: {:?}", urls);
+        match listed.iter().position(|url| url == &urls[0]) {
+            Some(index) => debug!(
+                "Starting with V3 mirror {} of the status document's list",
+                index
+            ),
+            None => debug!("Starting with a V3 mirror not in the status document's list"),
+        }
+        Ok(Self {
+            v3_urls: urls,
+            mirror: 0,
+            metar_urls,
+            low_bandwidth,
+            etag: None,
+            last_modified: None,
+            body_hash: None,
+            last_update: None,
+            stale_fetches: 0,
+            switched_stale: false,
+            latencies,
+        })
+    }
+
+    /// V3 mirrors from a status document to race, if the fastest is wanted.
+    #[must_use]
+    pub fn to_race(status: Option<&Status>, selection: &MirrorSelection) -> Vec<String> {
+        match status {
+            Some(status) if selection.choice == MirrorChoice::Fastest => status.data.v3.clone(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// How quickly each mirror answered, if they were raced.
+    #[must_use]
+    pub fn latencies(&self) -> &[MirrorLatency] {
+        &self.latencies
+    }
+
+    /// Number of V3 mirrors.
+    #[must_use]
+    pub fn count(&self) -> usize {
+        self.v3_urls.len()
+    }
+
+    /// URL of the mirror in use.
+    #[must_use]
+    pub fn url(&self) -> &str {
+        &self.v3_urls[self.mirror]
+    }
+
+    /// URL of the METAR endpoint, if the status document listed one.
+    pub fn metar_url(&self) -> Option<&str> {
+        self.metar_urls.first().map(String::as_str)
+    }
+
+    /// Headers to make the next V3 request conditional on the data having
+    /// changed, in low-bandwidth mode.
+    pub fn conditional_headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        if !self.low_bandwidth {
+            return headers;
+        }
+        if let Some(etag) = self.etag.as_ref().and_then(|v| v.parse().ok()) {
+            let _ = headers.insert(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = self.last_modified.as_ref().and_then(|v| v.parse().ok()) {
+            let _ = headers.insert(IF_MODIFIED_SINCE, last_modified);
+        }
+        headers
+    }
+
+    /// Whether a failed V3 request should be tried again on the next mirror,
+    /// after `attempts` mirrors have been tried.
+    ///
+    /// That's when the mirror is at fault: it sent an unusable body or a
+    /// server error, or couldn't be reached in time. A client error, like
+    /// a rejected user agent, would be the same from every mirror.
+    #[must_use]
+    pub fn should_fail_over(&self, error: &anyhow::Error, attempts: usize) -> bool {
+        attempts < self.v3_urls.len() && mirror_at_fault(error)
+    }
+
+    /// Switch to the next mirror, forgetting what the last one sent.
+    pub fn next(&mut self) {
+        self.mirror = (self.mirror + 1) % self.v3_urls.len();
+        self.etag = None;
+        self.last_modified = None;
+        self.body_hash = None;
+        self.last_update = None;
+        self.stale_fetches = 0;
+    }
+
+    /// Switch to the first mirror from this one on that isn't in `tried`,
+    /// staying on this one if they all are.
+    ///
+    /// This goes by URL rather than by position, since the order is
+    /// shuffled again each time the mirrors are set up.
+    pub fn skip_tried(&mut self, tried: &[String]) {
+        for _ in 0..self.v3_urls.len() {
+            if !tried.contains(&self.v3_urls[self.mirror]) {
+                return;
+            }
+            self.next();
+        }
+    }
+
+    /// Record the update time of a fetch, or `None` if nothing new
+    /// was received.
+    ///
+    /// Some mirrors keep serving an old snapshot without any errors, so if
+    /// the timestamp hasn't advanced in several fetches in a row, the next
+    /// mirror is used.
+    fn record_update(&mut self, update: Option<DateTime<Utc>>) {
+        if update > self.last_update {
+            self.last_update = update;
+            self.stale_fetches = 0;
+            return;
+        }
+        self.stale_fetches += 1;
+        if self.stale_fetches < STALE_FETCH_LIMIT {
+            return;
+        }
+        if self.v3_urls.len() > 1 {
+            warn!("Mirror {} is serving stale data, switching", self.url());
+            self.next();
+            self.switched_stale = true;
+        } else {
+            warn!(
+                "Mirror {} is serving stale data, but there's no other",
+                self.url()
+            );
+            self.stale_fetches = 0;
+        }
+    }
+
+    /// Whether a stale mirror was switched away from since the last call.
+    pub fn take_stale_switch(&mut self) -> bool {
+        std::mem::take(&mut self.switched_stale)
+    }
+
+    /// Look at a V3 response's status and headers, remembering its validators.
+    ///
+    /// # Errors
+    ///
+    /// If the status isn't a success.
+    pub fn read_head(&mut self, status: StatusCode, headers: &HeaderMap) -> Result<Head> {
+        if status == StatusCode::NOT_MODIFIED {
+            debug!("V3 data not modified");
+            self.record_update(None);
+            return Ok(Head::NotModified);
+        }
+        check_status(status, "V3")?;
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_owned)
+        };
+        self.etag = header(ETAG);
+        self.last_modified = header(LAST_MODIFIED);
+        Ok(Head::Body(header(CONTENT_TYPE)))
+    }
+
+    /// Parse a V3 response body, with the clients sorted by callsign.
+    ///
+    /// In low-bandwidth mode, a body identical to the last one isn't parsed.
+    ///
+    /// # Errors
+    ///
+    /// If the body is empty, isn't JSON, or isn't V3 data.
+    pub fn read_body(&mut self, content_type: Option<&str>, body: &[u8]) -> Result<DataResponse> {
+        check_body(content_type, body)?;
+        if self.low_bandwidth {
+            let mut hasher = DefaultHasher::new();
+            body.hash(&mut hasher);
+            let hash = hasher.finish();
+            if self.body_hash == Some(hash) {
+                debug!("V3 data identical to last response");
+                self.record_update(None);
+                return Ok(DataResponse::IdenticalBody);
+            }
+            self.body_hash = Some(hash);
+        }
+        let data = parse_data(body)?;
+        // an unreadable timestamp says nothing new came in
+        self.record_update(parse_time(&data.general.update_timestamp));
+        Ok(DataResponse::Changed(data))
+    }
+}
+
+/// Parse a V3 response body, with the clients sorted by callsign.
+///
+/// # Errors
+///
+/// If the body is truncated or isn't V3 data.
+pub fn parse_data(body: &[u8]) -> Result<V3ResponseData> {
+    let mut data: V3ResponseData = match serde_json::from_slice(body) {
+        Ok(data) => data,
+        Err(e) if e.classify() == Category::Eof => {
+            return Err(BadBody(format!("mirror returned truncated JSON: {}", e)).into());
+        }
+        Err(e) => return Err(e.into()),
+    };
+    sort_rows(
+        &mut data.pilots,
+        false,
+        |p| SortKey::text(&p.callsign),
+        |p| p.cid,
+    );
+    sort_rows(
+        &mut data.controllers,
+        false,
+        |c| SortKey::text(&c.callsign),
+        |c| c.cid,
+    );
+    Ok(data)
+}
+
+/// Check that a METAR response had one in it.
+///
+/// # Errors
+///
+/// If the body is blank.
+pub fn read_metar(icao: &str, body: &str) -> Result<String> {
+    let metar = body.trim();
+    if metar.is_empty() {
+        return Err(anyhow!("No METAR returned for {}", icao));
+    }
+    Ok(metar.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{models::StatusData, testing};
+
+    /// A status document listing these V3 mirrors.
+    fn status(urls: &[&str]) -> Status {
+        Status {
+            data: StatusData {
+                v3: urls.iter().map(|url| (*url).to_owned()).collect(),
+                transceivers: Vec::new(),
+                servers: Vec::new(),
+                servers_sweatbox: Vec::new(),
+                servers_all: Vec::new(),
+            },
+            user: Vec::new(),
+            metar: Vec::new(),
+        }
+    }
+
+    /// Mirrors with these URLs, used in the order given.
+    fn mirrors(urls: &[&str]) -> Mirrors {
+        let selection = MirrorSelection {
+            choice: MirrorChoice::Index(0),
+            allow_failover: true,
+            seed: Some(0),
+        };
+        let mut mirrors = Mirrors::new(Some(status(urls)), false, &selection, Vec::new()).unwrap();
+        // keep the test's order after the chosen first one
+        mirrors.v3_urls = urls.iter().map(|url| (*url).to_owned()).collect();
+        mirrors
+    }
+
+    /// The order mirrors would be used in for a selection.
+    fn order(urls: &[&str], choice: MirrorChoice, allow_failover: bool) -> Result<Vec<String>> {
+        let selection = MirrorSelection {
+            choice,
+            allow_failover,
+            seed: Some(7),
+        };
+        let mirrors = Mirrors::new(Some(status(urls)), false, &selection, Vec::new())?;
+        Ok(mirrors.v3_urls)
+    }
+
+    #[test]
+    fn parses_a_mirror_choice() {
+        assert_eq!(MirrorChoice::parse(" 2 ").unwrap(), MirrorChoice::Index(2));
+        assert_eq!(
+            MirrorChoice::parse("https://data.example.com/v3").unwrap(),
+            MirrorChoice::Url("https://data.example.com/v3".to_owned())
+        );
+        assert!(MirrorChoice::parse("-1").is_err());
+        assert!(MirrorChoice::parse("data.example.com").is_err());
+    }
+
+    #[test]
+    fn only_a_url_without_failover_skips_the_status_document() {
+        let url = MirrorChoice::Url("https://a".to_owned());
+        let selection = |choice, allow_failover| MirrorSelection {
+            choice,
+            allow_failover,
+            seed: None,
+        };
+        assert!(!selection(url.clone(), false).needs_status());
+        assert!(selection(url, true).needs_status());
+        assert!(selection(MirrorChoice::Index(0), false).needs_status());
+        assert!(selection(MirrorChoice::Random, false).needs_status());
+    }
+
+    #[test]
+    fn chosen_mirrors_are_the_only_one_without_failover() {
+        let urls = ["a", "b", "c", "d"];
+        assert_eq!(order(&urls, MirrorChoice::Index(2), false).unwrap(), ["c"]);
+        let url = MirrorChoice::Url("https://e".to_owned());
+        assert_eq!(order(&urls, url.clone(), false).unwrap(), ["https://e"]);
+        let selection = MirrorSelection {
+            choice: url,
+            ..MirrorSelection::default()
+        };
+        let mirrors = Mirrors::new(None, false, &selection, Vec::new()).unwrap();
+        assert_eq!(mirrors.count(), 1);
+        assert!(!mirrors.should_fail_over(&BadBody("bad".to_owned()).into(), 1));
+    }
+
+    #[test]
+    fn fails_over_when_the_mirror_is_at_fault() {
+        let mirrors = mirrors(&["a", "b"]);
+        let status = |code| check_status(StatusCode::from_u16(code).unwrap(), "V3").unwrap_err();
+        assert!(mirrors.should_fail_over(&BadBody("bad".to_owned()).into(), 1));
+        assert!(mirrors.should_fail_over(&status(500), 1));
+        assert!(mirrors.should_fail_over(&status(503), 1));
+        // the same from any mirror
+        assert!(!mirrors.should_fail_over(&status(403), 1));
+        assert!(!mirrors.should_fail_over(&status(404), 1));
+        assert!(!mirrors.should_fail_over(&anyhow!("no such field"), 1));
+
+        let client = reqwest::blocking::Client::new();
+        let refused = client.get(testing::refused_url()).send().unwrap_err();
+        assert!(mirrors.should_fail_over(&refused.into(), 1));
+        let slow = testing::slow_server(200, Duration::from_secs(2));
+        let timed_out = client
+            .get(slow)
+            .timeout(Duration::from_millis(100))
+            .send()
+            .unwrap_err();
+        let timed_out = anyhow::Error::from(timed_out).context("fetching V3 data");
+        assert!(mirrors.should_fail_over(&timed_out, 1));
+        // every mirror has been tried
+        assert!(!mirrors.should_fail_over(&status(503), 2));
+    }
+
+    #[test]
+    fn skipping_tried_mirrors_survives_a_reshuffle() {
+        let urls = ["a", "b", "c", "d"];
+        let tried = ["a".to_owned(), "c".to_owned()];
+        // set up again each time, as after a failure, in a new random order
+        for _ in 0..50 {
+            let mut mirrors = Mirrors::new(
+                Some(status(&urls)),
+                false,
+                &MirrorSelection::default(),
+                Vec::new(),
+            )
+            .unwrap();
+            mirrors.skip_tried(&tried);
+            assert!(
+                !tried.contains(&mirrors.url().to_owned()),
+                "{}",
+                mirrors.url()
+            );
+        }
+        // with every mirror tried, it stays put
+        let mut mirrors = mirrors(&urls);
+        let all: Vec<String> = urls.iter().map(|url| (*url).to_owned()).collect();
+        mirrors.skip_tried(&all);
+        assert_eq!(mirrors.url(), "a");
+    }
+
+    #[test]
+    fn chosen_mirrors_go_first_with_failover() {
+        let urls = ["a", "b", "c", "d"];
+        let order = order(&urls, MirrorChoice::Index(2), true).unwrap();
+        assert_eq!(order[0], "c");
+        let mut rest = order[1..].to_vec();
+        rest.sort();
+        assert_eq!(rest, ["a", "b", "d"]);
+
+        let order = self::order(&urls, MirrorChoice::Url("https://e".to_owned()), true).unwrap();
+        assert_eq!(order.len(), 5);
+        assert_eq!(order[0], "https://e");
+        // a listed URL isn't tried twice
+        let order = self::order(&urls, MirrorChoice::Url("b".to_owned()), true).unwrap();
+        assert_eq!(order.len(), 4);
+        assert_eq!(order[0], "b");
+    }
+
+    #[test]
+    fn out_of_range_indexes_say_how_many_there_are() {
+        let error = order(&["a", "b", "c"], MirrorChoice::Index(3), false).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Mirror index 3 is out of range; the status document lists 3 mirrors (0 to 2)"
+        );
+    }
+
+    #[test]
+    fn random_order_is_reproducible_with_a_seed() {
+        let urls = ["a", "b", "c", "d", "e", "f"];
+        let first = order(&urls, MirrorChoice::Random, false).unwrap();
+        assert_eq!(first, order(&urls, MirrorChoice::Random, false).unwrap());
+        let mut sorted = first.clone();
+        sorted.sort();
+        assert_eq!(sorted, urls);
+    }
+
+    #[test]
+    fn a_seed_picks_the_same_mirror_every_time() {
+        let urls = ["a", "b", "c", "d", "e", "f"];
+        // the order for a seed is fixed, so a report with --seed reproduces
+        assert_eq!(
+            order(&urls, MirrorChoice::Random, true).unwrap(),
+            ["d", "f", "e", "b", "a", "c"]
+        );
+        let first = |seed| {
+            let selection = MirrorSelection {
+                seed: Some(seed),
+                ..MirrorSelection::default()
+            };
+            Mirrors::new(Some(status(&urls)), false, &selection, Vec::new())
+                .unwrap()
+                .v3_urls[0]
+                .clone()
+        };
+        let firsts: Vec<String> = (0..8).map(first).collect();
+        // different seeds spread over the mirrors
+        assert_eq!(firsts, ["d", "a", "d", "b", "f", "e", "f", "d"]);
+    }
+
+    #[test]
+    fn fastest_mirrors_first() {
+        let latency = |url: &str, millis: Option<u64>| MirrorLatency {
+            url: url.to_owned(),
+            latency: millis.map(Duration::from_millis),
+        };
+        let mut urls: Vec<String> = ["a", "b", "c", "d"].map(str::to_owned).to_vec();
+        order_by_latency(
+            &mut urls,
+            &[
+                latency("a", None),
+                latency("b", Some(90)),
+                latency("c", Some(30)),
+            ],
+        );
+        assert_eq!(urls, ["c", "b", "a", "d"]);
+    }
+
+    /// An update time on the day of the tests.
+    fn at(time: &str) -> Option<DateTime<Utc>> {
+        parse_time(&format!("2022-08-01T{}:00Z", time))
+    }
+
+    #[test]
+    fn timestamps_are_compared_as_instants() {
+        let mut mirrors = mirrors(&["a", "b"]);
+        mirrors.record_update(at("12:00"));
+        // later as text, but the same instant or an earlier one
+        for _ in 0..STALE_FETCH_LIMIT {
+            mirrors.record_update(parse_time("2022-08-01T13:00:00+01:00"));
+        }
+        assert_eq!(mirrors.url(), "b");
+        mirrors.record_update(parse_time("2022-08-01T12:00:00.1234567Z"));
+        // earlier as text, but later
+        mirrors.record_update(parse_time("2022-08-01T11:00:01-01:00"));
+        assert_eq!(mirrors.stale_fetches, 0);
+    }
+
+    #[test]
+    fn switches_after_the_timestamp_stops_advancing() {
+        let mut mirrors = mirrors(&["a", "b"]);
+        mirrors.record_update(at("12:00"));
+        for _ in 1..STALE_FETCH_LIMIT {
+            mirrors.record_update(at("12:00"));
+            assert_eq!(mirrors.url(), "a");
+            assert!(!mirrors.take_stale_switch());
+        }
+        mirrors.record_update(at("12:00"));
+        assert_eq!(mirrors.url(), "b");
+        assert!(mirrors.take_stale_switch());
+        assert!(!mirrors.take_stale_switch());
+    }
+
+    #[test]
+    fn advancing_resets_the_count() {
+        let mut mirrors = mirrors(&["a", "b"]);
+        for minute in 0..10 {
+            mirrors.record_update(at(&format!("12:{:02}", minute)));
+            mirrors.record_update(None);
+        }
+        assert_eq!(mirrors.url(), "a");
+        // an older snapshot than the newest seen is no better than the same one
+        for _ in 0..STALE_FETCH_LIMIT {
+            mirrors.record_update(at("11:00"));
+        }
+        assert_eq!(mirrors.url(), "b");
+    }
+
+    #[test]
+    fn not_modified_counts_as_stale() {
+        let mut mirrors = mirrors(&["a", "b"]);
+        mirrors.record_update(at("12:00"));
+        for _ in 0..STALE_FETCH_LIMIT {
+            mirrors.record_update(None);
+        }
+        assert_eq!(mirrors.url(), "b");
+    }
+
+    #[test]
+    fn rotates_without_going_back_to_the_stale_mirror() {
+        let mut mirrors = mirrors(&["a", "b", "c"]);
+        let mut used = vec![mirrors.url().to_owned()];
+        for _ in 0..3 {
+            // the first fetch from a mirror sets its timestamp
+            for _ in 0..=STALE_FETCH_LIMIT {
+                mirrors.record_update(at("12:00"));
+            }
+            used.push(mirrors.url().to_owned());
+        }
+        assert_eq!(used, ["a", "b", "c", "a"]);
+    }
+
+    #[test]
+    fn stays_on_the_only_mirror() {
+        let mut mirrors = mirrors(&["a"]);
+        for _ in 0..STALE_FETCH_LIMIT * 2 {
+            mirrors.record_update(at("12:00"));
+        }
+        assert_eq!(mirrors.url(), "a");
+        assert!(!mirrors.take_stale_switch());
+    }
+
+    #[test]
+    fn failing_over_starts_a_new_window() {
+        let mut mirrors = mirrors(&["a", "b", "c"]);
+        mirrors.record_update(at("12:00"));
+        mirrors.record_update(at("12:00"));
+        // a bad body from "a" fails over to "b", which gets its own window
+        mirrors.next();
+        for _ in 1..STALE_FETCH_LIMIT {
+            mirrors.record_update(at("12:00"));
+        }
+        assert_eq!(mirrors.url(), "b");
+        assert!(!mirrors.take_stale_switch());
+    }
+
+    /// A V3 body with pilots and controllers out of callsign order.
+    fn body(pilots: usize) -> Vec<u8> {
+        let data = V3ResponseData {
+            pilots: (0..pilots)
+                .rev()
+                .map(|i| testing::flying(&format!("BAW{}", i), "EGLL", "KJFK"))
+                .collect(),
+            controllers: vec![
+                testing::controller("LON_S_CTR", 6),
+                testing::controller("EGLL_TWR", 4),
+            ],
+            ..V3ResponseData::default()
+        };
+        serde_json::to_vec(&data).unwrap()
+    }
+
+    #[test]
+    fn parsed_data_is_sorted_by_callsign() {
+        let data = parse_data(&body(3)).unwrap();
+        let pilots: Vec<_> = data.pilots.iter().map(|p| p.callsign.as_str()).collect();
+        assert_eq!(pilots, ["BAW0", "BAW1", "BAW2"]);
+        assert_eq!(data.controllers[0].callsign, "EGLL_TWR");
+    }
+
+    #[test]
+    fn truncated_bodies_fail_over_and_others_do_not() {
+        let body = body(3);
+        let error = parse_data(&body[..body.len() / 2]).unwrap_err();
+        assert!(error.is::<BadBody>(), "{:#}", error);
+        assert!(error
+            .to_string()
+            .starts_with("mirror returned truncated JSON"));
+        let error = parse_data(br#"{"pilots": 5}"#).unwrap_err();
+        assert!(!error.is::<BadBody>(), "{:#}", error);
+    }
+
+    /// Run with `cargo test -- --ignored --nocapture` to time parsing a
+    /// large body, and how much of that is serde.
+    #[test]
+    #[ignore = "benchmark"]
+    fn bench_parse_data() {
+        use std::time::Instant;
+
+        let body = body(5000);
+        let start = Instant::now();
+        for _ in 0..10 {
+            let _: V3ResponseData = serde_json::from_slice(&body).unwrap();
+        }
+        println!("serde only: {:?} per body", start.elapsed() / 10);
+        let start = Instant::now();
+        for _ in 0..10 {
+            let _ = parse_data(&body).unwrap();
+        }
+        println!("parse_data: {:?} per body", start.elapsed() / 10);
+    }
+}
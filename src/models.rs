@@ -1,7 +1,24 @@
 //! JSON API models.
 
+use crate::reference::{ControllerRating, Facility};
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
 use serde::{Deserialize, Serialize};
 
+/// Parse a timestamp from the API.
+///
+/// The feed sends up to seven fractional digits, and some records leave
+/// off the time zone; those are taken to be UTC.
+#[must_use]
+pub fn parse_time(raw: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(raw)
+        .map(|time| time.with_timezone(&Utc))
+        .or_else(|_| {
+            NaiveDateTime::parse_from_str(raw, "%Y-%m-%dT%H:%M:%S%.f")
+                .map(|time| Utc.from_utc_datetime(&time))
+        })
+        .ok()
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct StatusData {
     pub v3: Vec<String>,
@@ -58,6 +75,35 @@ pub struct Pilot {
     pub last_updated: String,
 }
 
+impl Pilot {
+    /// When the pilot's position was last updated, if the timestamp parses.
+    #[must_use]
+    pub fn last_update(&self) -> Option<DateTime<Utc>> {
+        parse_time(&self.last_updated)
+    }
+
+    /// When the pilot logged on, if the timestamp parses.
+    #[must_use]
+    pub fn logon(&self) -> Option<DateTime<Utc>> {
+        parse_time(&self.logon_time)
+    }
+
+    /// Aircraft type from the flight plan, if one has been filed.
+    ///
+    /// Prefers the FAA-format type, falling back to the short ICAO type.
+    #[must_use]
+    pub fn aircraft(&self) -> Option<&str> {
+        let fp = self.flight_plan.as_ref()?;
+        if !fp.aircraft_faa.is_empty() {
+            Some(&fp.aircraft_faa)
+        } else if !fp.aircraft_short.is_empty() {
+            Some(&fp.aircraft_short)
+        } else {
+            None
+        }
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Controller {
     pub cid: i64,
@@ -73,7 +119,39 @@ pub struct Controller {
     pub logon_time: String,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+impl Controller {
+    /// When the controller's record was last updated, if the timestamp parses.
+    #[must_use]
+    pub fn last_update(&self) -> Option<DateTime<Utc>> {
+        parse_time(&self.last_updated)
+    }
+
+    /// When the controller logged on, if the timestamp parses.
+    #[must_use]
+    pub fn logon(&self) -> Option<DateTime<Utc>> {
+        parse_time(&self.logon_time)
+    }
+
+    /// The controller's rating.
+    #[must_use]
+    pub fn rating_enum(&self) -> ControllerRating {
+        ControllerRating::from(self.rating)
+    }
+
+    /// The kind of position the controller is working, if the id is in range.
+    #[must_use]
+    pub fn facility_enum(&self) -> Option<Facility> {
+        Facility::try_from(self.facility).ok()
+    }
+
+    /// Controller info lines, or nothing if none were sent.
+    #[must_use]
+    pub fn info_lines(&self) -> &[String] {
+        self.text_atis.as_deref().unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct GeneralData {
     pub version: i64,
     pub reload: i64,
@@ -83,6 +161,14 @@ pub struct GeneralData {
     pub unique_users: i64,
 }
 
+impl GeneralData {
+    /// When the data was generated, if the timestamp parses.
+    #[must_use]
+    pub fn updated(&self) -> Option<DateTime<Utc>> {
+        parse_time(&self.update_timestamp)
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ReferenceItem {
     pub id: i8,
@@ -90,14 +176,96 @@ pub struct ReferenceItem {
     pub long: String,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct V3ResponseData {
     pub general: GeneralData,
     pub pilots: Vec<Pilot>,
     pub controllers: Vec<Controller>,
-    // atis: Vec<?>,
+    /// ATIS stations, which have the same fields as controllers.
+    #[serde(default)]
+    pub atis: Vec<Controller>,
     // servers: Vec<?>,
     pub facilities: Vec<ReferenceItem>,
     pub ratings: Vec<ReferenceItem>,
     // pilot_ratings: Vec<?>,
 }
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct EventAirport {
+    pub icao: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct EventOrganiser {
+    pub region: Option<String>,
+    pub division: Option<String>,
+    pub subdivision: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Event {
+    pub id: i64,
+    pub name: String,
+    pub link: String,
+    pub start_time: String,
+    pub end_time: String,
+    #[serde(default)]
+    pub airports: Vec<EventAirport>,
+    #[serde(default)]
+    pub organisers: Vec<EventOrganiser>,
+}
+
+impl Event {
+    /// When the event starts, if the timestamp parses.
+    #[must_use]
+    pub fn start(&self) -> Option<DateTime<Utc>> {
+        parse_time(&self.start_time)
+    }
+
+    /// When the event ends, if the timestamp parses.
+    #[must_use]
+    pub fn end(&self) -> Option<DateTime<Utc>> {
+        parse_time(&self.end_time)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct EventsResponse {
+    pub data: Vec<Event>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(text: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(text)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn parses_the_feeds_timestamps() {
+        let half_past = utc("2022-08-01T12:30:00Z");
+        // whole seconds, and the feed's seven digits of fractions
+        assert_eq!(parse_time("2022-08-01T12:30:00Z"), Some(half_past));
+        assert_eq!(
+            parse_time("2022-08-01T12:30:00.1234567Z"),
+            Some(utc("2022-08-01T12:30:00.1234567Z"))
+        );
+        assert_eq!(parse_time("2022-08-01T14:30:00+02:00"), Some(half_past));
+        // times without a zone are UTC
+        assert_eq!(parse_time("2022-08-01T12:30:00"), Some(half_past));
+        assert_eq!(
+            parse_time("2022-08-01T12:30:00.5"),
+            Some(utc("2022-08-01T12:30:00.5Z"))
+        );
+    }
+
+    #[test]
+    fn unparseable_timestamps_are_none() {
+        for raw in ["", "TBA", "2022-08-01", "12:30:00", "2022-13-01T12:30:00Z"] {
+            assert_eq!(parse_time(raw), None, "{:?}", raw);
+        }
+    }
+}
@@ -0,0 +1,387 @@
+//! Finding the controllers a pilot is probably talking to.
+
+use crate::{
+    callsign,
+    geo::{self, GridIndex},
+    models::{Controller, Pilot},
+    reference::Facility,
+    traffic::{self, Position},
+};
+use std::{cmp::Ordering, collections::HashMap, fmt};
+
+/// Kinds of controller a pilot might be working, outermost first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FacilityClass {
+    Center,
+    Approach,
+    Tower,
+}
+
+impl FacilityClass {
+    /// Class of a facility, if it's one a pilot would talk to.
+    ///
+    /// Delivery and ground are grouped with tower.
+    pub fn of(facility: Facility) -> Option<Self> {
+        match facility {
+            Facility::Center | Facility::FlightService => Some(FacilityClass::Center),
+            Facility::Approach => Some(FacilityClass::Approach),
+            Facility::Tower | Facility::Ground | Facility::Delivery => Some(FacilityClass::Tower),
+            Facility::Observer | Facility::Unknown(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for FacilityClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            FacilityClass::Center => "CTR",
+            FacilityClass::Approach => "APP",
+            FacilityClass::Tower => "TWR",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A controller near a pilot.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NearbyController {
+    pub class: FacilityClass,
+    pub callsign: String,
+    pub distance_nm: f64,
+}
+
+/// Where the controllers a pilot might be talking to are, indexed by their
+/// position in the controllers list.
+#[derive(Debug, Clone, Default)]
+pub struct ControllerPositions {
+    index: GridIndex,
+    /// Longest visual range of the indexed controllers.
+    max_range_nm: f64,
+}
+
+impl ControllerPositions {
+    /// Index the controllers that can be placed on the map and that a pilot
+    /// would talk to.
+    pub fn new(controllers: &[Controller]) -> Self {
+        let mut max_range_nm = 0.0_f64;
+        let index = GridIndex::new(controllers.iter().enumerate().filter_map(|(i, c)| {
+            let airport = c
+                .facility_enum()
+                .and_then(FacilityClass::of)
+                .and(callsign::controller_airport(&c.callsign))?;
+            #[allow(clippy::cast_precision_loss)]
+            let range = c.visual_range as f64;
+            max_range_nm = max_range_nm.max(range);
+            Some((i, airport.latitude, airport.longitude))
+        }));
+        Self {
+            index,
+            max_range_nm,
+        }
+    }
+}
+
+/// The closest controller of each class whose visual range covers the pilot.
+///
+/// Controllers that can't be placed on the map are skipped. The result is
+/// ordered outermost class first.
+pub fn nearest_by_class(
+    pilot: &Pilot,
+    controllers: &[Controller],
+    positions: &ControllerPositions,
+) -> Vec<NearbyController> {
+    let mut nearest: Vec<NearbyController> = Vec::new();
+    let near = positions
+        .index
+        .within(pilot.latitude, pilot.longitude, positions.max_range_nm);
+    for controller in near.into_iter().filter_map(|i| controllers.get(i)) {
+        let Some(class) = controller.facility_enum().and_then(FacilityClass::of) else {
+            continue;
+        };
+        let Some(airport) = callsign::controller_airport(&controller.callsign) else {
+            continue;
+        };
+        let distance_nm = geo::distance_nm(
+            airport.latitude,
+            airport.longitude,
+            pilot.latitude,
+            pilot.longitude,
+        );
+        #[allow(clippy::cast_precision_loss)]
+        let visual_range = controller.visual_range as f64;
+        if distance_nm > visual_range {
+            continue;
+        }
+        match nearest.iter_mut().find(|n| n.class == class) {
+            Some(existing) if existing.distance_nm <= distance_nm => {}
+            Some(existing) => {
+                existing.callsign.clone_from(&controller.callsign);
+                existing.distance_nm = distance_nm;
+            }
+            None => nearest.push(NearbyController {
+                class,
+                callsign: controller.callsign.clone(),
+                distance_nm,
+            }),
+        }
+    }
+    nearest.sort_by_key(|n| n.class);
+    nearest
+}
+
+/// Order controllers covering a pilot most specific first: tower before
+/// approach before center, and the closest first within a class.
+fn by_specificity(a: &NearbyController, b: &NearbyController) -> Ordering {
+    b.class
+        .cmp(&a.class)
+        .then(a.distance_nm.total_cmp(&b.distance_nm))
+        .then_with(|| a.callsign.cmp(&b.callsign))
+}
+
+/// The controllers whose plausible traffic includes each pilot, by
+/// callsign, most specific first. Pilots no one covers are left out.
+///
+/// Each controller's traffic is looked up in the index of the pilots'
+/// positions, so this is cheap enough to redo on every refresh.
+pub fn covering<'a>(
+    controllers: impl IntoIterator<Item = &'a Controller>,
+    pilots: &[Pilot],
+    index: &GridIndex,
+) -> HashMap<String, Vec<NearbyController>> {
+    let mut covering: HashMap<String, Vec<NearbyController>> = HashMap::new();
+    for controller in controllers {
+        let Some(facility) = controller.facility_enum() else {
+            continue;
+        };
+        let (Some(class), Some(position)) =
+            (FacilityClass::of(facility), Position::resolve(controller))
+        else {
+            continue;
+        };
+        for pilot in traffic::plausible_traffic(pilots, index, facility, &position) {
+            covering
+                .entry(pilot.callsign.clone())
+                .or_default()
+                .push(NearbyController {
+                    class,
+                    callsign: controller.callsign.clone(),
+                    distance_nm: geo::distance_nm(
+                        position.latitude,
+                        position.longitude,
+                        pilot.latitude,
+                        pilot.longitude,
+                    ),
+                });
+        }
+    }
+    for controllers in covering.values_mut() {
+        controllers.sort_by(by_specificity);
+    }
+    covering
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn controller(callsign: &str, facility: i64, visual_range: i64) -> Controller {
+        Controller {
+            visual_range,
+            ..testing::controller(callsign, facility)
+        }
+    }
+
+    /// A pilot about a mile east of San Francisco.
+    fn pilot() -> Pilot {
+        Pilot {
+            latitude: 37.62,
+            longitude: -122.36,
+            ..testing::pilot("UAL1")
+        }
+    }
+
+    fn summary(nearby: &[NearbyController]) -> Vec<(FacilityClass, &str, i64)> {
+        #[allow(clippy::cast_possible_truncation)]
+        nearby
+            .iter()
+            .map(|n| (n.class, n.callsign.as_str(), n.distance_nm.round() as i64))
+            .collect()
+    }
+
+    #[test]
+    fn closest_controller_of_each_class() {
+        let controllers = vec![
+            controller("OAK_TWR", 4, 50),
+            controller("SFO_TWR", 4, 50),
+            controller("SJC_APP", 5, 50),
+            controller("SFO_APP", 5, 50),
+            controller("OAK_CTR", 6, 300),
+            controller("ZOA_CTR", 6, 600),
+            controller("LAX_TWR", 4, 50),
+            controller("SFO_OBS", 0, 300),
+        ];
+        let positions = ControllerPositions::new(&controllers);
+        let nearest = nearest_by_class(&pilot(), &controllers, &positions);
+        assert_eq!(
+            summary(&nearest),
+            [
+                (FacilityClass::Center, "OAK_CTR", 9),
+                (FacilityClass::Approach, "SFO_APP", 1),
+                (FacilityClass::Tower, "SFO_TWR", 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn only_controllers_whose_range_covers_the_pilot() {
+        let controllers = vec![controller("OAK_TWR", 4, 5), controller("SJC_APP", 5, 50)];
+        let positions = ControllerPositions::new(&controllers);
+        let nearest = nearest_by_class(&pilot(), &controllers, &positions);
+        assert_eq!(
+            summary(&nearest),
+            [(FacilityClass::Approach, "SJC_APP", 26)]
+        );
+    }
+
+    #[test]
+    fn nothing_when_no_controller_can_be_placed() {
+        let controllers = vec![controller("ZOA_CTR", 6, 600), controller("NCT_APP", 5, 150)];
+        let positions = ControllerPositions::new(&controllers);
+        assert!(nearest_by_class(&pilot(), &controllers, &positions).is_empty());
+        assert!(nearest_by_class(&pilot(), &[], &ControllerPositions::default()).is_empty());
+    }
+
+    #[test]
+    fn facility_classes() {
+        assert_eq!(
+            FacilityClass::of(Facility::Delivery),
+            Some(FacilityClass::Tower)
+        );
+        assert_eq!(
+            FacilityClass::of(Facility::Ground),
+            Some(FacilityClass::Tower)
+        );
+        assert_eq!(
+            FacilityClass::of(Facility::FlightService),
+            Some(FacilityClass::Center)
+        );
+        assert_eq!(FacilityClass::of(Facility::Observer), None);
+        assert_eq!(FacilityClass::of(Facility::Unknown(9)), None);
+        assert!(FacilityClass::Center < FacilityClass::Tower);
+    }
+
+    #[test]
+    fn covering_lists_the_most_specific_first() {
+        let pilots = vec![
+            pilot(),
+            Pilot {
+                latitude: 37.62 + 100.0 / 60.0,
+                longitude: -122.38,
+                altitude: 35_000,
+                ..testing::pilot("FAR")
+            },
+        ];
+        let controllers = vec![
+            controller("ZOA_CTR", 6, 600),
+            controller("OAK_CTR", 6, 300),
+            controller("SFO_APP", 5, 50),
+            controller("OAK_TWR", 4, 50),
+            controller("SFO_TWR", 4, 50),
+        ];
+        let index = geo::pilot_index(&pilots);
+        let covering = covering(&controllers, &pilots, &index);
+        assert_eq!(
+            summary(&covering["UAL1"]),
+            [
+                (FacilityClass::Tower, "SFO_TWR", 1),
+                (FacilityClass::Approach, "SFO_APP", 1),
+                (FacilityClass::Center, "OAK_CTR", 9),
+            ]
+        );
+        assert_eq!(
+            summary(&covering["FAR"]),
+            [(FacilityClass::Center, "OAK_CTR", 94)]
+        );
+    }
+
+    fn near(class: FacilityClass, callsign: &str, distance_nm: f64) -> NearbyController {
+        NearbyController {
+            class,
+            callsign: callsign.to_owned(),
+            distance_nm,
+        }
+    }
+
+    #[test]
+    fn specificity_beats_distance() {
+        let mut covering = vec![
+            near(FacilityClass::Center, "C_CTR", 0.0),
+            near(FacilityClass::Approach, "A_APP", 12.0),
+            near(FacilityClass::Tower, "FAR_TWR", 4.0),
+            near(FacilityClass::Approach, "B_APP", 0.5),
+            near(FacilityClass::Tower, "NEAR_GND", 1.0),
+            near(FacilityClass::Tower, "NEAR_TWR", 1.0),
+        ];
+        covering.sort_by(by_specificity);
+        assert_eq!(
+            summary(&covering),
+            [
+                (FacilityClass::Tower, "NEAR_GND", 1),
+                (FacilityClass::Tower, "NEAR_TWR", 1),
+                (FacilityClass::Tower, "FAR_TWR", 4),
+                (FacilityClass::Approach, "B_APP", 1),
+                (FacilityClass::Approach, "A_APP", 12),
+                (FacilityClass::Center, "C_CTR", 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn covering_overlapping_positions_at_one_airport() {
+        let pilots = vec![
+            // on the ground
+            Pilot {
+                altitude: 0,
+                ..pilot()
+            },
+            // above the tower's ceiling
+            Pilot {
+                altitude: 12_000,
+                ..Pilot {
+                    callsign: "HIGH".to_owned(),
+                    ..pilot()
+                }
+            },
+            // beyond everyone's reach
+            Pilot {
+                latitude: 30.0,
+                ..testing::pilot("AWAY")
+            },
+        ];
+        let controllers = vec![
+            controller("SFO_CTR", 6, 300),
+            controller("SFO_APP", 5, 50),
+            controller("SFO_TWR", 4, 50),
+            controller("SFO_GND", 3, 50),
+            controller("SFO_DEL", 2, 50),
+            controller("SFO_OBS", 0, 300),
+            controller("SFO_X_SUP", 11, 300),
+        ];
+        let index = geo::pilot_index(&pilots);
+        let covering = covering(&controllers, &pilots, &index);
+        let callsigns = |pilot: &str| -> Vec<&str> {
+            covering[pilot]
+                .iter()
+                .map(|n| n.callsign.as_str())
+                .collect()
+        };
+        assert_eq!(
+            callsigns("UAL1"),
+            ["SFO_DEL", "SFO_GND", "SFO_TWR", "SFO_APP", "SFO_CTR"]
+        );
+        assert_eq!(callsigns("HIGH"), ["SFO_APP", "SFO_CTR"]);
+        assert!(!covering.contains_key("AWAY"));
+        assert_eq!(covering.len(), 2);
+    }
+}
@@ -0,0 +1,356 @@
+//! Printing a table outside the interface, in several formats.
+
+use anyhow::{bail, Result};
+use serde_json::{Map, Value};
+
+/// A table to print: a title, the column headers, and the rows' cells.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Table {
+    pub title: String,
+    pub headers: Vec<&'static str>,
+    pub rows: Vec<Vec<String>>,
+}
+
+/// Turns a table into text in some format.
+pub trait Formatter {
+    fn format(&self, table: &Table) -> String;
+}
+
+/// The formats a table can be printed in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    Csv,
+    Json,
+    Markdown,
+    Html,
+}
+
+impl OutputFormat {
+    /// Names accepted on the command line, in the order of the variants.
+    const NAMES: [&'static str; 5] = ["text", "csv", "json", "markdown", "html"];
+
+    /// Parse a format name from the command line.
+    pub fn parse(name: &str) -> Result<Self> {
+        let format = match name.to_lowercase().as_str() {
+            "text" => Self::Text,
+            "csv" => Self::Csv,
+            "json" => Self::Json,
+            "markdown" | "md" => Self::Markdown,
+            "html" => Self::Html,
+            _ => bail!(
+                "Unknown format '{}', expected one of: {}",
+                name,
+                Self::NAMES.join(", ")
+            ),
+        };
+        Ok(format)
+    }
+
+    /// The formatter for this format.
+    pub fn formatter(self) -> Box<dyn Formatter> {
+        match self {
+            Self::Text => Box::new(TextFormatter),
+            Self::Csv => Box::new(CsvFormatter),
+            Self::Json => Box::new(JsonFormatter),
+            Self::Markdown => Box::new(MarkdownFormatter),
+            Self::Html => Box::new(HtmlFormatter),
+        }
+    }
+}
+
+/// Columns padded with spaces to line up.
+#[derive(Debug, Clone, Copy)]
+pub struct TextFormatter;
+
+impl Formatter for TextFormatter {
+    fn format(&self, table: &Table) -> String {
+        let mut widths: Vec<usize> = table.headers.iter().map(|h| h.chars().count()).collect();
+        for row in &table.rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+        let line = |cells: Vec<&str>| {
+            cells
+                .iter()
+                .zip(&widths)
+                .map(|(cell, &width)| format!("{:<width$}", cell, width = width))
+                .collect::<Vec<_>>()
+                .join("  ")
+                .trim_end()
+                .to_owned()
+        };
+        let mut lines = vec![line(table.headers.clone())];
+        lines.extend(
+            table
+                .rows
+                .iter()
+                .map(|row| line(row.iter().map(String::as_str).collect())),
+        );
+        lines.join("\n")
+    }
+}
+
+/// Comma-separated values with a header line.
+#[derive(Debug, Clone, Copy)]
+pub struct CsvFormatter;
+
+/// Quote a CSV field if it has a comma, quote, or line break in it.
+fn csv_escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+impl Formatter for CsvFormatter {
+    fn format(&self, table: &Table) -> String {
+        let mut lines = vec![table
+            .headers
+            .iter()
+            .map(|header| csv_escape(header))
+            .collect::<Vec<_>>()
+            .join(",")];
+        lines.extend(table.rows.iter().map(|row| {
+            row.iter()
+                .map(|cell| csv_escape(cell))
+                .collect::<Vec<_>>()
+                .join(",")
+        }));
+        lines.join("\n")
+    }
+}
+
+/// An array of objects keyed by the column headers.
+#[derive(Debug, Clone, Copy)]
+pub struct JsonFormatter;
+
+impl Formatter for JsonFormatter {
+    fn format(&self, table: &Table) -> String {
+        let rows: Vec<Value> = table
+            .rows
+            .iter()
+            .map(|row| {
+                Value::Object(
+                    table
+                        .headers
+                        .iter()
+                        .zip(row)
+                        .map(|(header, cell)| ((*header).to_owned(), Value::String(cell.clone())))
+                        .collect::<Map<_, _>>(),
+                )
+            })
+            .collect();
+        serde_json::to_string_pretty(&rows).unwrap_or_default()
+    }
+}
+
+/// A GitHub-flavored Markdown pipe table.
+#[derive(Debug, Clone, Copy)]
+pub struct MarkdownFormatter;
+
+/// Escape a Markdown table cell, which has to stay on one line and can't
+/// contain a bare pipe.
+fn markdown_escape(cell: &str) -> String {
+    cell.replace('\\', "\\\\")
+        .replace('|', "\\|")
+        .replace(['\r', '\n'], " ")
+}
+
+impl Formatter for MarkdownFormatter {
+    fn format(&self, table: &Table) -> String {
+        let line = |cells: Vec<String>| format!("| {} |", cells.join(" | "));
+        let mut lines = vec![
+            line(table.headers.iter().map(|h| markdown_escape(h)).collect()),
+            line(table.headers.iter().map(|_| "---".to_owned()).collect()),
+        ];
+        lines.extend(
+            table
+                .rows
+                .iter()
+                .map(|row| line(row.iter().map(|cell| markdown_escape(cell)).collect())),
+        );
+        lines.join("\n")
+    }
+}
+
+/// A standalone page with a table that sorts when a header is clicked.
+#[derive(Debug, Clone, Copy)]
+pub struct HtmlFormatter;
+
+/// Script that sorts the table by a clicked header, numerically when both
+/// cells are numbers.
+const HTML_SORT_SCRIPT: &str = r#"document.querySelectorAll("th").forEach((th, column) => {
+  th.addEventListener("click", () => {
+    const body = th.closest("table").tBodies[0];
+    const ascending = th.dataset.order !== "asc";
+    th.closest("tr").querySelectorAll("th").forEach((other) => delete other.dataset.order);
+    th.dataset.order = ascending ? "asc" : "desc";
+    const value = (row) => row.cells[column].textContent;
+    const rows = Array.from(body.rows).sort((a, b) => {
+      const x = value(a), y = value(b);
+      const order = x !== "" && y !== "" && !isNaN(x) && !isNaN(y)
+        ? x - y
+        : x.localeCompare(y);
+      return ascending ? order : -order;
+    });
+    rows.forEach((row) => body.appendChild(row));
+  });
+});"#;
+
+/// Escape text for HTML element content and attribute values.
+fn html_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl Formatter for HtmlFormatter {
+    fn format(&self, table: &Table) -> String {
+        let title = html_escape(&table.title);
+        let headers: Vec<String> = table
+            .headers
+            .iter()
+            .map(|header| format!("<th>{}</th>", html_escape(header)))
+            .collect();
+        let rows: Vec<String> = table
+            .rows
+            .iter()
+            .map(|row| {
+                let cells: Vec<String> = row
+                    .iter()
+                    .map(|cell| format!("<td>{}</td>", html_escape(cell)))
+                    .collect();
+                format!("<tr>{}</tr>", cells.concat())
+            })
+            .collect();
+        format!(
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n<style>\nbody {{ font-family: sans-serif; }}\ntable {{ border-collapse: collapse; }}\nth, td {{ border: 1px solid #ccc; padding: 2px 6px; text-align: left; }}\nth {{ cursor: pointer; background: #eee; }}\n</style>\n</head>\n<body>\n<h1>{title}</h1>\n<table>\n<thead><tr>{headers}</tr></thead>\n<tbody>\n{rows}\n</tbody>\n</table>\n<script>\n{script}\n</script>\n</body>\n</html>",
+            title = title,
+            headers = headers.concat(),
+            rows = rows.join("\n"),
+            script = HTML_SORT_SCRIPT
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table() -> Table {
+        Table {
+            title: "Pilots <EGLL>".to_owned(),
+            headers: vec!["Callsign", "Name", "Alt"],
+            rows: vec![
+                vec!["BAW1".to_owned(), "A | B".to_owned(), "35000".to_owned()],
+                vec![
+                    "DLH2".to_owned(),
+                    "<b>\"Jo\" & 'Al'</b>".to_owned(),
+                    String::new(),
+                ],
+                vec![
+                    "AFR3".to_owned(),
+                    "Line\nbreak, too".to_owned(),
+                    "800".to_owned(),
+                ],
+            ],
+        }
+    }
+
+    #[test]
+    fn parses_format_names() {
+        assert_eq!(OutputFormat::parse("MD").unwrap(), OutputFormat::Markdown);
+        assert_eq!(OutputFormat::parse("html").unwrap(), OutputFormat::Html);
+        assert_eq!(OutputFormat::parse("Csv").unwrap(), OutputFormat::Csv);
+        assert_eq!(
+            OutputFormat::parse("xml").unwrap_err().to_string(),
+            "Unknown format 'xml', expected one of: text, csv, json, markdown, html"
+        );
+    }
+
+    #[test]
+    fn text_lines_up_columns() {
+        let table = Table {
+            rows: vec![vec!["BAW1".to_owned(), "Jo".to_owned(), String::new()]],
+            ..table()
+        };
+        assert_eq!(
+            TextFormatter.format(&table),
+            "Callsign  Name  Alt\nBAW1      Jo"
+        );
+    }
+
+    #[test]
+    fn csv_quotes_what_it_has_to() {
+        assert_eq!(
+            CsvFormatter.format(&table()),
+            "Callsign,Name,Alt\n\
+             BAW1,A | B,35000\n\
+             DLH2,\"<b>\"\"Jo\"\" & 'Al'</b>\",\n\
+             AFR3,\"Line\nbreak, too\",800"
+        );
+    }
+
+    #[test]
+    fn json_keys_cells_by_header() {
+        let rows: Vec<Map<String, Value>> =
+            serde_json::from_str(&JsonFormatter.format(&table())).unwrap();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[1]["Name"], "<b>\"Jo\" & 'Al'</b>");
+        assert_eq!(rows[2]["Alt"], "800");
+    }
+
+    #[test]
+    fn markdown_escapes_pipes_and_line_breaks() {
+        assert_eq!(markdown_escape(r"a|b\c"), r"a\|b\\c");
+        assert_eq!(
+            MarkdownFormatter.format(&table()),
+            "| Callsign | Name | Alt |\n\
+             | --- | --- | --- |\n\
+             | BAW1 | A \\| B | 35000 |\n\
+             | DLH2 | <b>\"Jo\" & 'Al'</b> |  |\n\
+             | AFR3 | Line break, too | 800 |"
+        );
+    }
+
+    #[test]
+    fn html_escapes_markup() {
+        assert_eq!(
+            html_escape("<b>\"Jo\" & 'Al'</b>"),
+            "&lt;b&gt;&quot;Jo&quot; &amp; &#39;Al&#39;&lt;/b&gt;"
+        );
+        let page = HtmlFormatter.format(&table());
+        assert!(page.starts_with("<!DOCTYPE html>\n<html lang=\"en\">"));
+        assert!(page.ends_with("</html>"));
+        assert!(page.contains("<title>Pilots &lt;EGLL&gt;</title>"));
+        assert!(page.contains("<th>Callsign</th><th>Name</th><th>Alt</th>"));
+        assert!(page.contains(
+            "<tr><td>DLH2</td><td>&lt;b&gt;&quot;Jo&quot; &amp; &#39;Al&#39;&lt;/b&gt;</td><td></td></tr>"
+        ));
+        assert!(!page.contains("<b>"));
+        // every element opened is closed
+        for tag in [
+            "html", "head", "body", "table", "thead", "tbody", "script", "style",
+        ] {
+            assert_eq!(
+                page.matches(&format!("<{}", tag)).count(),
+                page.matches(&format!("</{}>", tag)).count(),
+                "{}",
+                tag
+            );
+        }
+        assert_eq!(page.matches("<tr>").count(), 4);
+    }
+}
@@ -0,0 +1,402 @@
+//! Saving and loading the files kept between runs, so a crash mid-write
+//! can't leave a file that breaks the next startup.
+
+use anyhow::{anyhow, bail, Context, Result};
+use log::{debug, warn};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use std::{
+    ffi::OsString,
+    fs::{self, File},
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+};
+
+/// A file format with a `version` field, which files written by older
+/// versions of the program can be migrated from.
+pub trait Versioned: Serialize + DeserializeOwned {
+    /// Version files are written with.
+    const VERSION: u32;
+
+    /// Bring a file from an older version up to this one, as JSON.
+    ///
+    /// Formats that have only had one version can't migrate anything.
+    fn migrate(value: Value, version: u32) -> Result<Value> {
+        let _ = value;
+        bail!("version {} can't be migrated to {}", version, Self::VERSION)
+    }
+}
+
+/// A path with text added around the file name, like ".bak" after it.
+fn with_affixes(path: &Path, prefix: &str, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(prefix);
+    name.push(path.file_name().unwrap_or_default());
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// Write a file so that it has either its old or its new contents, even
+/// if the program dies partway: the contents go to a temporary file in
+/// the same directory, which is then renamed over the file.
+///
+/// The temporary file is hidden, so one left behind isn't mistaken for a
+/// real file by anything listing the directory.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Could not create {}", parent.display()))?;
+    }
+    let temp = with_affixes(path, ".", ".tmp");
+    let result = File::create(&temp)
+        .and_then(|mut file| {
+            file.write_all(contents)?;
+            file.sync_all()
+        })
+        .and_then(|()| fs::rename(&temp, path));
+    if let Err(e) = result {
+        let _ = fs::remove_file(&temp);
+        return Err(e).with_context(|| format!("Could not write {}", path.display()));
+    }
+    Ok(())
+}
+
+/// Remove the temporary files [`write_atomic`] left in a directory when
+/// the program died partway, for files whose names `matches` accepts,
+/// returning how many were removed.
+pub fn remove_partial(dir: &Path, matches: impl Fn(&str) -> bool) -> Result<usize> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e).with_context(|| format!("Could not read {}", dir.display())),
+    };
+    let mut removed = 0;
+    for entry in entries.filter_map(Result::ok) {
+        let name = entry.file_name();
+        let partial = name
+            .to_str()
+            .and_then(|name| name.strip_prefix('.')?.strip_suffix(".tmp"))
+            .is_some_and(&matches);
+        if partial && fs::remove_file(entry.path()).is_ok() {
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+/// Save a versioned file atomically, as pretty JSON.
+pub fn save<T: Versioned>(path: &Path, value: &T) -> Result<()> {
+    write_atomic(path, serde_json::to_string_pretty(value)?.as_bytes())?;
+    debug!("Saved {}", path.display());
+    Ok(())
+}
+
+/// The version a file says it has.
+fn file_version(value: &Value) -> Result<u32> {
+    let version = value
+        .get("version")
+        .and_then(Value::as_u64)
+        .ok_or_else(|| anyhow!("no version number"))?;
+    Ok(u32::try_from(version)?)
+}
+
+/// Parse a versioned file, migrating it if it's from an older version.
+pub fn parse<T: Versioned>(content: &str) -> Result<T> {
+    let mut value: Value = serde_json::from_str(content)?;
+    let version = file_version(&value)?;
+    if version > T::VERSION {
+        bail!(
+            "version {} is newer than this program reads ({})",
+            version,
+            T::VERSION
+        );
+    }
+    if version < T::VERSION {
+        value = T::migrate(value, version)?;
+        if let Some(fields) = value.as_object_mut() {
+            let _ = fields.insert("version".to_owned(), T::VERSION.into());
+        }
+        debug!("Migrated a file from version {} to {}", version, T::VERSION);
+    }
+    Ok(serde_json::from_value(value)?)
+}
+
+/// What came of loading a versioned file.
+#[derive(Debug)]
+pub struct Loaded<T> {
+    /// The contents, if the file exists and could be read.
+    pub value: Option<T>,
+    /// Why an existing file wasn't used, to show in the status bar.
+    pub warning: Option<String>,
+}
+
+impl<T> Default for Loaded<T> {
+    fn default() -> Self {
+        Self {
+            value: None,
+            warning: None,
+        }
+    }
+}
+
+impl<T> Loaded<T> {
+    /// Nothing loaded, with a warning.
+    fn warn(warning: String) -> Self {
+        warn!("{}", warning);
+        Self {
+            value: None,
+            warning: Some(warning),
+        }
+    }
+}
+
+/// Load a versioned file, migrating older versions.
+///
+/// A missing file loads nothing. A corrupt one is moved aside to a
+/// ".bak" file, replacing any earlier one, so the next save starts over;
+/// one from a newer version of the program is left alone. Either way
+/// nothing is loaded, with a warning.
+#[must_use]
+pub fn load<T: Versioned>(path: &Path) -> Loaded<T> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Loaded::default(),
+        Err(e) => return Loaded::warn(format!("Could not read {}: {}", path.display(), e)),
+    };
+    let error = match parse(&content) {
+        Ok(value) => {
+            return Loaded {
+                value: Some(value),
+                warning: None,
+            }
+        }
+        Err(e) => e,
+    };
+    let newer = serde_json::from_str(&content)
+        .ok()
+        .and_then(|value| file_version(&value).ok())
+        .is_some_and(|version| version > T::VERSION);
+    if newer {
+        return Loaded::warn(format!("Ignoring {}: {}", path.display(), error));
+    }
+    let backup = with_affixes(path, "", ".bak");
+    match fs::rename(path, &backup) {
+        Ok(()) => Loaded::warn(format!(
+            "{} was unreadable ({}), so it was moved to {}",
+            path.display(),
+            error,
+            backup.display()
+        )),
+        Err(e) => Loaded::warn(format!(
+            "{} was unreadable ({}) and couldn't be moved aside: {}",
+            path.display(),
+            error,
+            e
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use serde_json::json;
+
+    /// A format on its third version: version 1 called the name "title",
+    /// and version 2 added the count.
+    #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+    struct Record {
+        version: u32,
+        name: String,
+        count: u32,
+    }
+
+    impl Versioned for Record {
+        const VERSION: u32 = 3;
+
+        fn migrate(mut value: Value, version: u32) -> Result<Value> {
+            let fields = value
+                .as_object_mut()
+                .ok_or_else(|| anyhow!("not an object"))?;
+            if version < 2 {
+                let title = fields.remove("title").ok_or_else(|| anyhow!("no title"))?;
+                let _ = fields.insert("name".to_owned(), title);
+            }
+            let _ = fields.entry("count").or_insert_with(|| 0.into());
+            Ok(value)
+        }
+    }
+
+    fn record(name: &str, count: u32) -> Record {
+        Record {
+            version: Record::VERSION,
+            name: name.to_owned(),
+            count,
+        }
+    }
+
+    /// A format that has only had one version.
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Plain {
+        version: u32,
+    }
+
+    impl Versioned for Plain {
+        const VERSION: u32 = 1;
+    }
+
+    fn names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<_> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn atomic_writes_replace_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested").join("state.json");
+        write_atomic(&path, b"first").unwrap();
+        write_atomic(&path, b"second").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"second");
+        // nothing is left behind
+        assert_eq!(names(&dir.path().join("nested")), ["state.json"]);
+    }
+
+    #[test]
+    fn a_failed_write_keeps_the_old_contents() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+        write_atomic(&path, b"old").unwrap();
+        // a directory where the temporary file goes makes the write fail
+        fs::create_dir(dir.path().join(".state.json.tmp")).unwrap();
+        let error = format!("{:#}", write_atomic(&path, b"new").unwrap_err());
+        assert!(error.starts_with("Could not write"), "{}", error);
+        assert_eq!(fs::read(&path).unwrap(), b"old");
+    }
+
+    #[test]
+    fn an_interrupted_write_leaves_the_file_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("record.json");
+        save(&path, &record("saved", 2)).unwrap();
+        // the program died while writing the next version
+        fs::write(dir.path().join(".record.json.tmp"), r#"{"version": 3, "na"#).unwrap();
+        fs::write(dir.path().join(".other.json.tmp"), "").unwrap();
+        assert_eq!(load::<Record>(&path).value, Some(record("saved", 2)));
+
+        assert_eq!(
+            remove_partial(dir.path(), |name| name.starts_with("record")).unwrap(),
+            1
+        );
+        assert_eq!(names(dir.path()), [".other.json.tmp", "record.json"]);
+        assert_eq!(
+            remove_partial(&dir.path().join("missing"), |_| true).unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn older_versions_are_migrated() {
+        assert_eq!(
+            parse::<Record>(r#"{"version": 1, "title": "old"}"#).unwrap(),
+            record("old", 0)
+        );
+        assert_eq!(
+            parse::<Record>(r#"{"version": 2, "name": "newer", "count": 4}"#).unwrap(),
+            record("newer", 4)
+        );
+        assert_eq!(
+            parse::<Record>(&json!(record("current", 7)).to_string()).unwrap(),
+            record("current", 7)
+        );
+        let error = parse::<Record>(r#"{"version": 1, "name": "x"}"#).unwrap_err();
+        assert_eq!(error.to_string(), "no title");
+        let error = parse::<Plain>(r#"{"version": 0}"#).unwrap_err();
+        assert_eq!(error.to_string(), "version 0 can't be migrated to 1");
+    }
+
+    #[test]
+    fn unusable_contents_do_not_parse() {
+        let error = parse::<Record>(r#"{"version": 4, "name": "x", "count": 1}"#).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "version 4 is newer than this program reads (3)"
+        );
+        assert_eq!(
+            parse::<Record>(r#"{"name": "x"}"#).unwrap_err().to_string(),
+            "no version number"
+        );
+        assert!(parse::<Record>(r#"{"version": -1}"#).is_err());
+        assert!(parse::<Record>(r#"{"version": 3, "name": 5, "count": 1}"#).is_err());
+        assert!(parse::<Record>("").is_err());
+    }
+
+    #[test]
+    fn loading_a_missing_file_loads_nothing_quietly() {
+        let dir = tempfile::tempdir().unwrap();
+        let loaded = load::<Record>(&dir.path().join("record.json"));
+        assert_eq!(loaded.value, None);
+        assert_eq!(loaded.warning, None);
+    }
+
+    #[test]
+    fn loading_migrates_without_rewriting() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("record.json");
+        fs::write(&path, r#"{"version": 1, "title": "old"}"#).unwrap();
+        let loaded = load::<Record>(&path);
+        assert_eq!(loaded.value, Some(record("old", 0)));
+        assert_eq!(loaded.warning, None);
+        assert_eq!(names(dir.path()), ["record.json"]);
+    }
+
+    #[test]
+    fn corrupt_files_are_moved_aside() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("record.json");
+        fs::write(dir.path().join("record.json.bak"), "older backup").unwrap();
+        fs::write(&path, r#"{"version": 3, "na"#).unwrap();
+        let loaded = load::<Record>(&path);
+        assert_eq!(loaded.value, None);
+        let warning = loaded.warning.unwrap();
+        assert!(warning.contains("was unreadable"), "{}", warning);
+        assert!(warning.ends_with("record.json.bak"), "{}", warning);
+        assert_eq!(names(dir.path()), ["record.json.bak"]);
+        assert_eq!(
+            fs::read_to_string(dir.path().join("record.json.bak")).unwrap(),
+            r#"{"version": 3, "na"#
+        );
+        // the next save starts over
+        save(&path, &record("fresh", 1)).unwrap();
+        assert_eq!(load::<Record>(&path).value, Some(record("fresh", 1)));
+    }
+
+    #[test]
+    fn files_from_newer_versions_are_left_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("record.json");
+        let newer = r#"{"version": 9, "name": "from the future"}"#;
+        fs::write(&path, newer).unwrap();
+        let loaded = load::<Record>(&path);
+        assert_eq!(loaded.value, None);
+        let warning = loaded.warning.unwrap();
+        assert!(warning.starts_with("Ignoring"), "{}", warning);
+        assert!(
+            warning.ends_with("newer than this program reads (3)"),
+            "{}",
+            warning
+        );
+        assert_eq!(fs::read_to_string(&path).unwrap(), newer);
+        assert_eq!(names(dir.path()), ["record.json"]);
+    }
+
+    #[test]
+    fn unreadable_files_are_warned_about() {
+        let dir = tempfile::tempdir().unwrap();
+        let loaded = load::<Record>(dir.path());
+        assert_eq!(loaded.value, None);
+        assert!(loaded.warning.unwrap().starts_with("Could not read"));
+    }
+}
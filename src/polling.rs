@@ -0,0 +1,351 @@
+//! How often the interface polls for new data, including an adaptive
+//! mode that polls faster while the network is changing quickly.
+
+use crate::models::V3ResponseData;
+use anyhow::{bail, Result};
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Duration,
+};
+
+/// Change rates averaged when adjusting the interval.
+const RECENT_RATES: usize = 3;
+/// Above this fraction of CIDs changing per poll, poll faster.
+const FAST_RATE: f64 = 0.05;
+/// Below this fraction of CIDs changing per poll, poll slower. Rates
+/// between this and [`FAST_RATE`] leave the interval alone, so it doesn't
+/// flap around the edge.
+const SLOW_RATE: f64 = 0.01;
+/// Most the interval shrinks in one step.
+const MAX_STEP: f64 = 2.0;
+/// How much the interval grows per step while the network is quiet.
+const SLOW_STEP: f64 = 1.25;
+
+/// Poll interval from the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PollInterval {
+    /// Poll this often.
+    Fixed(Duration),
+    /// Adjust the interval to how fast the network is changing.
+    Auto,
+}
+
+impl PollInterval {
+    /// Parse a number of seconds, or "auto".
+    pub fn parse(value: &str) -> Result<Self> {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("auto") {
+            return Ok(PollInterval::Auto);
+        }
+        match value.parse::<u64>() {
+            Ok(seconds) if seconds > 0 => Ok(PollInterval::Fixed(Duration::from_secs(seconds))),
+            _ => bail!(
+                "'{}' isn't a poll interval; use a number of seconds or 'auto'",
+                value
+            ),
+        }
+    }
+}
+
+/// Fraction of the CIDs in either of two snapshots that changed between
+/// them: connected, disconnected, changed callsign, refiled their flight
+/// plan, or changed frequency.
+///
+/// Positions aren't compared, since every moving pilot's position changes
+/// on every poll whether anything interesting happened or not.
+pub fn change_rate(old: &V3ResponseData, new: &V3ResponseData) -> f64 {
+    /// What's compared for each client, keyed by whether they're a pilot
+    /// and their CID.
+    fn signatures(data: &V3ResponseData) -> HashMap<(bool, i64), (&str, String)> {
+        let pilots = data.pilots.iter().map(|p| {
+            let revision = p
+                .flight_plan
+                .as_ref()
+                .map_or_else(String::new, |fp| fp.revision_id.to_string());
+            ((true, p.cid), (p.callsign.as_str(), revision))
+        });
+        let controllers = data
+            .controllers
+            .iter()
+            .map(|c| ((false, c.cid), (c.callsign.as_str(), c.frequency.clone())));
+        pilots.chain(controllers).collect()
+    }
+    let old = signatures(old);
+    let new = signatures(new);
+    let removed = old.keys().filter(|key| !new.contains_key(key)).count();
+    let changed = new
+        .iter()
+        .filter(|(key, signature)| old.get(key) != Some(signature))
+        .count();
+    let total = new.len() + removed;
+    if total == 0 {
+        return 0.0;
+    }
+    #[allow(clippy::cast_precision_loss)]
+    let rate = (changed + removed) as f64 / total as f64;
+    rate
+}
+
+/// The next poll interval, from the current one and the recent change
+/// rates.
+///
+/// While the average rate is above [`FAST_RATE`], the interval shrinks in
+/// proportion to how far above it is; while it's below [`SLOW_RATE`], it
+/// grows by [`SLOW_STEP`]. It shrinks at most [`MAX_STEP`] times per step,
+/// stays between `min` and `max`, and is rounded to whole seconds.
+pub fn next_interval(current: Duration, rates: &[f64], min: Duration, max: Duration) -> Duration {
+    if rates.is_empty() {
+        return current.clamp(min, max.max(min));
+    }
+    #[allow(clippy::cast_precision_loss)]
+    let rate = rates.iter().sum::<f64>() / rates.len() as f64;
+    let factor = if rate > FAST_RATE {
+        (FAST_RATE / rate).max(1.0 / MAX_STEP)
+    } else if rate < SLOW_RATE {
+        SLOW_STEP
+    } else {
+        1.0
+    };
+    let seconds = (current.as_secs_f64() * factor).round();
+    Duration::from_secs_f64(seconds).clamp(min, max.max(min))
+}
+
+/// Bounds for the adaptive interval.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AutoBounds {
+    pub min: Duration,
+    pub max: Duration,
+}
+
+/// The interval in use, adjusted as data arrives in adaptive mode.
+#[derive(Debug, Clone)]
+pub struct Poller {
+    interval: Duration,
+    /// Bounds, if the interval is adaptive.
+    auto: Option<AutoBounds>,
+    /// The latest change rates, oldest first.
+    rates: VecDeque<f64>,
+    /// The API's last advertised reload time.
+    reload: Option<Duration>,
+}
+
+impl Poller {
+    /// A poller for a setting, falling back to `default` if there's none.
+    ///
+    /// The adaptive interval starts at `default`, within its bounds.
+    pub fn new(setting: Option<PollInterval>, default: Duration, bounds: AutoBounds) -> Self {
+        let (interval, auto) = match setting {
+            Some(PollInterval::Fixed(interval)) => (interval, None),
+            Some(PollInterval::Auto) => (
+                default.clamp(bounds.min, bounds.max.max(bounds.min)),
+                Some(bounds),
+            ),
+            None => (default, None),
+        };
+        Self {
+            interval,
+            auto,
+            rates: VecDeque::with_capacity(RECENT_RATES),
+            reload: None,
+        }
+    }
+
+    /// The interval to wait before the next poll.
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Whether the interval adapts to the change rate.
+    pub fn is_auto(&self) -> bool {
+        self.auto.is_some()
+    }
+
+    /// Take a new change rate into account, never going below the
+    /// API's advertised reload time, or the last one advertised if
+    /// `reload` is `None`.
+    pub fn observe(&mut self, rate: f64, reload: Option<Duration>) {
+        let Some(bounds) = self.auto else {
+            return;
+        };
+        self.reload = reload.or(self.reload);
+        if self.rates.len() == RECENT_RATES {
+            let _ = self.rates.pop_front();
+        }
+        self.rates.push_back(rate);
+        let min = self
+            .reload
+            .map_or(bounds.min, |reload| bounds.min.max(reload));
+        self.interval = next_interval(self.interval, self.rates.make_contiguous(), min, bounds.max);
+    }
+}
+
+/// The API's advertised reload time, if it gives one; it's in minutes.
+pub fn reload_time(data: &V3ResponseData) -> Option<Duration> {
+    u64::try_from(data.general.reload)
+        .ok()
+        .filter(|&minutes| minutes > 0)
+        .map(|minutes| Duration::from_secs(minutes * 60))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        models::{Controller, Pilot},
+        testing,
+    };
+
+    const BOUNDS: AutoBounds = AutoBounds {
+        min: Duration::from_secs(15),
+        max: Duration::from_secs(300),
+    };
+
+    fn secs(seconds: u64) -> Duration {
+        Duration::from_secs(seconds)
+    }
+
+    fn next(current: u64, rates: &[f64]) -> u64 {
+        next_interval(secs(current), rates, BOUNDS.min, BOUNDS.max).as_secs()
+    }
+
+    /// Data with 100 pilots, CIDs 1 to 100.
+    fn hundred() -> V3ResponseData {
+        V3ResponseData {
+            pilots: (1..=100)
+                .map(|cid| Pilot {
+                    cid,
+                    ..testing::flying(&format!("TST{}", cid), "EGLL", "KJFK")
+                })
+                .collect(),
+            ..V3ResponseData::default()
+        }
+    }
+
+    #[test]
+    fn parses_seconds_or_auto() {
+        assert_eq!(
+            PollInterval::parse("60").unwrap(),
+            PollInterval::Fixed(secs(60))
+        );
+        assert_eq!(PollInterval::parse(" Auto ").unwrap(), PollInterval::Auto);
+        for value in ["0", "-5", "1.5", "fast", ""] {
+            assert!(PollInterval::parse(value).is_err(), "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn change_rates() {
+        let old = hundred();
+        assert!(change_rate(&old, &old).abs() < f64::EPSILON);
+        assert!(
+            change_rate(&V3ResponseData::default(), &V3ResponseData::default()).abs()
+                < f64::EPSILON
+        );
+
+        let mut new = old.clone();
+        // moving isn't a change
+        for pilot in &mut new.pilots {
+            pilot.latitude += 1.0;
+            pilot.altitude += 1_000;
+        }
+        assert!(change_rate(&old, &new).abs() < f64::EPSILON);
+        // a disconnect, a new connection, a new callsign, and a refile
+        let _ = new.pilots.remove(0);
+        new.pilots.push(Pilot {
+            cid: 101,
+            ..testing::pilot("NEW1")
+        });
+        new.pilots[0].callsign = "RENAMED".to_owned();
+        new.pilots[1].flight_plan.as_mut().unwrap().revision_id += 1;
+        // 4 of the 101 CIDs in either
+        assert!((change_rate(&old, &new) - 4.0 / 101.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn controllers_changing_frequency_count() {
+        let controller = |frequency: &str| V3ResponseData {
+            controllers: vec![Controller {
+                frequency: frequency.to_owned(),
+                ..testing::controller("EGLL_TWR", 4)
+            }],
+            ..V3ResponseData::default()
+        };
+        assert!((change_rate(&controller("118.500"), &controller("118.700")) - 1.0).abs() < 1e-9);
+        assert!(change_rate(&controller("118.500"), &controller("118.500")).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn busy_networks_poll_faster_in_proportion() {
+        assert_eq!(next(60, &[0.10]), 30);
+        assert_eq!(next(60, &[0.08]), 38);
+        // at most halving per step
+        assert_eq!(next(60, &[0.90]), 30);
+        assert_eq!(next(20, &[0.90]), 15);
+    }
+
+    #[test]
+    fn quiet_networks_poll_slower() {
+        assert_eq!(next(60, &[0.0]), 75);
+        assert_eq!(next(60, &[0.005, 0.0, 0.009]), 75);
+        assert_eq!(next(280, &[0.0]), 300);
+    }
+
+    #[test]
+    fn steady_networks_hold_the_interval() {
+        for rate in [0.01, 0.03, 0.05] {
+            assert_eq!(next(60, &[rate]), 60, "{}", rate);
+        }
+        // one busy poll among quiet ones is averaged away
+        assert_eq!(next(60, &[0.0, 0.12, 0.0]), 60);
+        assert_eq!(next(60, &[]), 60);
+        assert_eq!(next(5, &[]), 15);
+    }
+
+    #[test]
+    fn fixed_intervals_never_change() {
+        let mut poller = Poller::new(Some(PollInterval::Fixed(secs(60))), secs(30), BOUNDS);
+        assert!(!poller.is_auto());
+        poller.observe(0.5, None);
+        assert_eq!(poller.interval(), secs(60));
+        let poller = Poller::new(None, secs(30), BOUNDS);
+        assert_eq!(poller.interval(), secs(30));
+    }
+
+    #[test]
+    fn auto_intervals_follow_the_recent_rates() {
+        let mut poller = Poller::new(Some(PollInterval::Auto), secs(30), BOUNDS);
+        assert!(poller.is_auto());
+        assert_eq!(poller.interval(), secs(30));
+        let mut intervals = Vec::new();
+        for rate in [0.2, 0.2, 0.2, 0.0, 0.0, 0.0, 0.0] {
+            poller.observe(rate, None);
+            intervals.push(poller.interval().as_secs());
+        }
+        // the busy rates take a while to be averaged away
+        assert_eq!(intervals, [15, 15, 15, 15, 15, 19, 24]);
+    }
+
+    #[test]
+    fn never_faster_than_the_api_reload() {
+        let mut poller = Poller::new(Some(PollInterval::Auto), secs(60), BOUNDS);
+        poller.observe(0.5, Some(secs(60)));
+        assert_eq!(poller.interval(), secs(60));
+        // the last advertised reload still holds
+        poller.observe(0.5, None);
+        assert_eq!(poller.interval(), secs(60));
+        // even above the maximum
+        poller.observe(0.0, Some(secs(600)));
+        assert_eq!(poller.interval(), secs(600));
+    }
+
+    #[test]
+    fn reload_times_are_minutes() {
+        let mut data = V3ResponseData::default();
+        data.general.reload = 1;
+        assert_eq!(reload_time(&data), Some(secs(60)));
+        data.general.reload = 0;
+        assert_eq!(reload_time(&data), None);
+        data.general.reload = -1;
+        assert_eq!(reload_time(&data), None);
+    }
+}
@@ -0,0 +1,196 @@
+//! Hiding the real names that come with the data, for streaming or
+//! sharing screenshots.
+//!
+//! Parsing leaves names as sent. The binary applies its level to each
+//! refresh before deriving anything from it, so from the table to exports
+//! and the clipboard nothing downstream ever sees them.
+
+use crate::models::V3ResponseData;
+use anyhow::{bail, Result};
+
+/// How much of each client's name is shown.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Privacy {
+    /// The name as sent.
+    #[default]
+    Full,
+    /// The first name and the last name's initial, like "John S.".
+    Initials,
+    /// The CID in place of the name.
+    Cid,
+}
+
+impl Privacy {
+    /// Parse "full", "initials", or "cid".
+    pub fn parse(value: &str) -> Result<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "full" => Ok(Privacy::Full),
+            "initials" => Ok(Privacy::Initials),
+            "cid" => Ok(Privacy::Cid),
+            _ => bail!(
+                "Unknown privacy level '{}', expected full, initials, or cid",
+                value
+            ),
+        }
+    }
+
+    /// A name as shown at this level.
+    ///
+    /// Names with nothing to abbreviate, like an empty one or a single
+    /// word, fall back to the CID under initials, since a lone word may
+    /// well be a surname.
+    #[must_use]
+    pub fn redact(self, name: &str, cid: i64) -> String {
+        match self {
+            Privacy::Full => name.to_owned(),
+            Privacy::Initials => {
+                let words: Vec<&str> = name.split_whitespace().collect();
+                match (words.first(), words.last().and_then(|w| w.chars().next())) {
+                    (Some(first), Some(initial)) if words.len() > 1 => {
+                        format!("{} {}.", first, initial.to_uppercase())
+                    }
+                    _ => cid.to_string(),
+                }
+            }
+            Privacy::Cid => cid.to_string(),
+        }
+    }
+
+    /// Replace the names in parsed data as this level calls for, along
+    /// with any mention of them in controller info lines and flight plan
+    /// remarks, where controllers and pilots often sign their names.
+    pub fn apply(self, data: &mut V3ResponseData) {
+        if self == Privacy::Full {
+            return;
+        }
+        for pilot in &mut data.pilots {
+            let name = self.redact(&pilot.name, pilot.cid);
+            if let Some(plan) = &mut pilot.flight_plan {
+                plan.remarks = mentions(&plan.remarks, &pilot.name, &name);
+            }
+            pilot.name = name;
+        }
+        for controller in data.controllers.iter_mut().chain(&mut data.atis) {
+            let name = self.redact(&controller.name, controller.cid);
+            for line in controller.text_atis.iter_mut().flatten() {
+                *line = mentions(line, &controller.name, &name);
+            }
+            controller.name = name;
+        }
+    }
+}
+
+/// Text with each mention of a name replaced.
+fn mentions(text: &str, name: &str, replacement: &str) -> String {
+    let name = name.trim();
+    if name.is_empty() {
+        text.to_owned()
+    } else {
+        text.replace(name, replacement)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{models::Controller, testing};
+
+    #[test]
+    fn levels_parse() {
+        assert_eq!(Privacy::parse("full").unwrap(), Privacy::Full);
+        assert_eq!(Privacy::parse(" Initials ").unwrap(), Privacy::Initials);
+        assert_eq!(Privacy::parse("CID").unwrap(), Privacy::Cid);
+        let error = Privacy::parse("none").unwrap_err().to_string();
+        assert!(error.contains("'none'"), "{}", error);
+    }
+
+    #[test]
+    fn names_at_each_level() {
+        assert_eq!(Privacy::Full.redact("John Smith", 1), "John Smith");
+        assert_eq!(Privacy::Cid.redact("John Smith", 1_234_567), "1234567");
+        assert_eq!(Privacy::Initials.redact("John Smith", 1), "John S.");
+        assert_eq!(Privacy::Initials.redact("  Mary Ann  smith ", 1), "Mary S.");
+        assert_eq!(
+            Privacy::Initials.redact("Jos\u{e9} \u{c9}lan", 1),
+            "Jos\u{e9} \u{c9}."
+        );
+        // nothing to abbreviate
+        assert_eq!(Privacy::Initials.redact("Smith", 7), "7");
+        assert_eq!(Privacy::Initials.redact("  ", 7), "7");
+        assert_eq!(Privacy::Cid.redact("", 7), "7");
+    }
+
+    /// Data full of real names, in the name fields and the free text.
+    fn named() -> V3ResponseData {
+        let mut pilot = testing::flying("BAW1", "EGLL", "KJFK");
+        pilot.cid = 1_111_111;
+        pilot.name = "John Smith".to_owned();
+        if let Some(plan) = &mut pilot.flight_plan {
+            plan.remarks = "/V/ PILOT John Smith, NEW MEMBER".to_owned();
+        }
+        let controller = |callsign: &str, cid: i64| Controller {
+            cid,
+            name: "Jane Doe".to_owned(),
+            text_atis: Some(vec![
+                "London Control, Jane Doe".to_owned(),
+                "Feedback: Jane Doe at vatsim-uk".to_owned(),
+            ]),
+            ..testing::controller(callsign, 6)
+        };
+        V3ResponseData {
+            pilots: vec![pilot],
+            controllers: vec![controller("LON_S_CTR", 2_222_222)],
+            atis: vec![controller("EGLL_ATIS", 3_333_333)],
+            ..V3ResponseData::default()
+        }
+    }
+
+    /// The data as JSON, as saved or exported.
+    fn json(data: &V3ResponseData) -> String {
+        serde_json::to_string(data).unwrap()
+    }
+
+    #[test]
+    fn full_leaves_the_data_alone() {
+        let mut data = named();
+        Privacy::Full.apply(&mut data);
+        assert_eq!(json(&data), json(&named()));
+    }
+
+    #[test]
+    fn no_raw_names_are_left() {
+        for level in [Privacy::Initials, Privacy::Cid] {
+            let mut data = named();
+            level.apply(&mut data);
+            let text = json(&data);
+            for raw in ["John Smith", "Jane Doe", "Smith", "Doe"] {
+                assert!(!text.contains(raw), "{:?} {} in {}", level, raw, text);
+            }
+        }
+    }
+
+    #[test]
+    fn names_and_mentions_are_replaced() {
+        let mut data = named();
+        Privacy::Initials.apply(&mut data);
+        assert_eq!(data.pilots[0].name, "John S.");
+        assert_eq!(
+            data.pilots[0].flight_plan.as_ref().unwrap().remarks,
+            "/V/ PILOT John S., NEW MEMBER"
+        );
+        assert_eq!(data.controllers[0].name, "Jane D.");
+        assert_eq!(
+            data.atis[0].info_lines(),
+            ["London Control, Jane D.", "Feedback: Jane D. at vatsim-uk"]
+        );
+
+        let mut data = named();
+        Privacy::Cid.apply(&mut data);
+        assert_eq!(data.pilots[0].name, "1111111");
+        assert_eq!(
+            data.controllers[0].info_lines()[0],
+            "London Control, 2222222"
+        );
+        assert_eq!(data.atis[0].name, "3333333");
+    }
+}
@@ -0,0 +1,663 @@
+//! Text query language for filtering table rows.
+//!
+//! Queries are `field op value` conditions joined by `and` and `or`,
+//! with parentheses for grouping; `and` binds tighter than `or`:
+//!
+//! ```text
+//! departure=EG* and (altitude>30000 or groundspeed<100)
+//! ```
+//!
+//! Values with spaces or any of `()"=!<>` go in double quotes, where a
+//! backslash escapes a quote or another backslash: `name="A \"B\" C"`.
+
+use crate::{
+    filter::{Field, Filter, Operator, Predicate, DELIMITERS},
+    models::{Controller, Pilot},
+};
+use std::{collections::BTreeSet, error::Error, fmt};
+
+/// A parsed query.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Query {
+    /// The empty query, which matches everything.
+    #[default]
+    All,
+    Predicate(Predicate),
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+}
+
+impl Query {
+    /// Parse a query string. An empty string is the query matching everything.
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let tokens = tokenize(text)?;
+        if tokens.is_empty() {
+            return Ok(Query::All);
+        }
+        let mut parser = Parser {
+            text,
+            tokens,
+            position: 0,
+        };
+        let query = parser.or_expression()?;
+        if let Some(token) = parser.peek() {
+            let message = if token.kind == TokenKind::CloseParen {
+                "Unmatched ')'".to_owned()
+            } else {
+                format!("Expected 'and' or 'or', got '{}'", token.text(text))
+            };
+            return Err(parser.error_at(token, message));
+        }
+        Ok(query)
+    }
+
+    /// Whether the query matches everything.
+    pub fn is_empty(&self) -> bool {
+        *self == Query::All
+    }
+
+    /// Whether a pilot matches the query.
+    pub fn matches_pilot(&self, pilot: &Pilot) -> bool {
+        match self {
+            Query::All => true,
+            Query::Predicate(predicate) => predicate.matches_pilot(pilot),
+            Query::And(a, b) => a.matches_pilot(pilot) && b.matches_pilot(pilot),
+            Query::Or(a, b) => a.matches_pilot(pilot) || b.matches_pilot(pilot),
+        }
+    }
+
+    /// Whether a controller matches the query.
+    pub fn matches_controller(&self, controller: &Controller) -> bool {
+        match self {
+            Query::All => true,
+            Query::Predicate(predicate) => predicate.matches_controller(controller),
+            Query::And(a, b) => {
+                a.matches_controller(controller) && b.matches_controller(controller)
+            }
+            Query::Or(a, b) => a.matches_controller(controller) || b.matches_controller(controller),
+        }
+    }
+
+    /// The query as a list of predicates that must all match, if it can be
+    /// written that way, for editing in the filter builder.
+    pub fn as_filter(&self) -> Option<Filter> {
+        let mut predicates = Vec::new();
+        self.collect_conjunction(&mut predicates)?;
+        Some(Filter { predicates })
+    }
+
+    /// The one airport the query picks flights to or from, like EGLL in
+    /// `arrival=EGLL or departure=EGLL`, if it names exactly one.
+    pub fn airport(&self) -> Option<String> {
+        let mut airports = BTreeSet::new();
+        self.collect_airports(&mut airports);
+        let mut airports = airports.into_iter();
+        match (airports.next(), airports.next()) {
+            (Some(airport), None) => Some(airport),
+            _ => None,
+        }
+    }
+
+    /// Add the airports named exactly by departure and arrival conditions
+    /// anywhere in the query to a set.
+    fn collect_airports(&self, airports: &mut BTreeSet<String>) {
+        match self {
+            Query::All => {}
+            Query::Predicate(predicate) => {
+                let value = predicate.value.trim();
+                if matches!(predicate.field, Field::Departure | Field::Arrival)
+                    && predicate.operator == Operator::Equal
+                    && !value.is_empty()
+                    && !value.contains('*')
+                {
+                    let _ = airports.insert(value.to_uppercase());
+                }
+            }
+            Query::And(a, b) | Query::Or(a, b) => {
+                a.collect_airports(airports);
+                b.collect_airports(airports);
+            }
+        }
+    }
+
+    /// Add the predicates of an `and`-only query to a list.
+    fn collect_conjunction(&self, predicates: &mut Vec<Predicate>) -> Option<()> {
+        match self {
+            Query::All => Some(()),
+            Query::Predicate(predicate) => {
+                predicates.push(predicate.clone());
+                Some(())
+            }
+            Query::And(a, b) => {
+                a.collect_conjunction(predicates)?;
+                b.collect_conjunction(predicates)
+            }
+            Query::Or(_, _) => None,
+        }
+    }
+}
+
+impl From<Filter> for Query {
+    fn from(filter: Filter) -> Self {
+        filter
+            .predicates
+            .into_iter()
+            .map(Query::Predicate)
+            .reduce(|a, b| Query::And(Box::new(a), Box::new(b)))
+            .unwrap_or_default()
+    }
+}
+
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Query::All => Ok(()),
+            Query::Predicate(predicate) => write!(f, "{}", predicate),
+            Query::And(a, b) => {
+                write_and_operand(f, a)?;
+                write!(f, " and ")?;
+                write_and_operand(f, b)
+            }
+            Query::Or(a, b) => write!(f, "{} or {}", a, b),
+        }
+    }
+}
+
+/// Write one side of an `and`, in parentheses if it's an `or`.
+fn write_and_operand(f: &mut fmt::Formatter<'_>, query: &Query) -> fmt::Result {
+    if matches!(query, Query::Or(_, _)) {
+        write!(f, "({})", query)
+    } else {
+        write!(f, "{}", query)
+    }
+}
+
+/// An error in a query string, with where it happened.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub message: String,
+    text: String,
+    /// Byte offset of the offending token.
+    start: usize,
+    /// Byte length of the offending token.
+    length: usize,
+}
+
+impl ParseError {
+    /// The query with a line of carets under the offending token.
+    pub fn pointer(&self) -> String {
+        let indent = self.text[..self.start].chars().count();
+        let width = self.text[self.start..self.start + self.length]
+            .chars()
+            .count()
+            .max(1);
+        format!("{}\n{}{}", self.text, " ".repeat(indent), "^".repeat(width))
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}\n{}", self.message, self.pointer())
+    }
+}
+
+impl Error for ParseError {}
+
+/// Kinds of tokens in a query string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TokenKind {
+    OpenParen,
+    CloseParen,
+    Operator(Operator),
+    /// A bare word: a field name, value, or `and`/`or`.
+    Word(String),
+    /// A quoted value, which is never a keyword.
+    Quoted(String),
+}
+
+/// A token and where it is in the query string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Token {
+    kind: TokenKind,
+    start: usize,
+    end: usize,
+}
+
+impl Token {
+    /// The token as written in the query string.
+    fn text<'a>(&self, text: &'a str) -> &'a str {
+        &text[self.start..self.end]
+    }
+
+    /// Whether the token is the keyword `and` or `or`.
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(&self.kind, TokenKind::Word(word) if word.eq_ignore_ascii_case(keyword))
+    }
+}
+
+/// Characters that start an operator.
+const OPERATOR_CHARS: [char; 4] = ['=', '!', '<', '>'];
+
+/// Split a query string into tokens.
+fn tokenize(text: &str) -> Result<Vec<Token>, ParseError> {
+    let error = |start: usize, length: usize, message: &str| ParseError {
+        message: message.to_owned(),
+        text: text.to_owned(),
+        start,
+        length,
+    };
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            let _ = chars.next();
+            continue;
+        }
+        let kind = match c {
+            '(' | ')' => {
+                let _ = chars.next();
+                if c == '(' {
+                    TokenKind::OpenParen
+                } else {
+                    TokenKind::CloseParen
+                }
+            }
+            '"' => {
+                let _ = chars.next();
+                let mut value = String::new();
+                let unterminated = || error(start, text.len() - start, "Unterminated quote");
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, '\\')) => value.push(chars.next().ok_or_else(unterminated)?.1),
+                        Some((_, c)) => value.push(c),
+                        None => return Err(unterminated()),
+                    }
+                }
+                TokenKind::Quoted(value)
+            }
+            c if OPERATOR_CHARS.contains(&c) => {
+                let mut symbol = String::new();
+                while let Some(&(_, c)) = chars.peek() {
+                    if !OPERATOR_CHARS.contains(&c) {
+                        break;
+                    }
+                    symbol.push(c);
+                    let _ = chars.next();
+                }
+                match Operator::ALL.into_iter().find(|op| op.symbol() == symbol) {
+                    Some(operator) => TokenKind::Operator(operator),
+                    None => {
+                        return Err(error(
+                            start,
+                            symbol.len(),
+                            &format!("Unknown comparison '{}'", symbol),
+                        ))
+                    }
+                }
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&(_, c)) = chars.peek() {
+                    if c.is_whitespace() || DELIMITERS.contains(&c) {
+                        break;
+                    }
+                    word.push(c);
+                    let _ = chars.next();
+                }
+                TokenKind::Word(word)
+            }
+        };
+        let end = chars.peek().map_or(text.len(), |&(i, _)| i);
+        tokens.push(Token { kind, start, end });
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent parser over a query's tokens.
+struct Parser<'a> {
+    text: &'a str,
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser<'_> {
+    /// The next token, without consuming it.
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    /// Consume and return the next token.
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    /// An error pointing at a token.
+    fn error_at(&self, token: &Token, message: String) -> ParseError {
+        ParseError {
+            message,
+            text: self.text.to_owned(),
+            start: token.start,
+            length: token.end - token.start,
+        }
+    }
+
+    /// An error pointing just past the end of the query.
+    fn error_at_end(&self, message: &str) -> ParseError {
+        ParseError {
+            message: message.to_owned(),
+            text: self.text.to_owned(),
+            start: self.text.len(),
+            length: 0,
+        }
+    }
+
+    /// `or_expression := and_expression ("or" and_expression)*`
+    fn or_expression(&mut self) -> Result<Query, ParseError> {
+        let mut query = self.and_expression()?;
+        while self.peek().is_some_and(|t| t.is_keyword("or")) {
+            let _ = self.next();
+            let right = self.and_expression()?;
+            query = Query::Or(Box::new(query), Box::new(right));
+        }
+        Ok(query)
+    }
+
+    /// `and_expression := primary ("and" primary)*`
+    fn and_expression(&mut self) -> Result<Query, ParseError> {
+        let mut query = self.primary()?;
+        while self.peek().is_some_and(|t| t.is_keyword("and")) {
+            let _ = self.next();
+            let right = self.primary()?;
+            query = Query::And(Box::new(query), Box::new(right));
+        }
+        Ok(query)
+    }
+
+    /// `primary := "(" or_expression ")" | predicate`
+    fn primary(&mut self) -> Result<Query, ParseError> {
+        let Some(token) = self.peek().cloned() else {
+            return Err(self.error_at_end("Expected a condition"));
+        };
+        if token.kind != TokenKind::OpenParen {
+            return self.predicate().map(Query::Predicate);
+        }
+        let _ = self.next();
+        let query = self.or_expression()?;
+        match self.next() {
+            Some(close) if close.kind == TokenKind::CloseParen => Ok(query),
+            Some(other) => Err(self.error_at(
+                &other,
+                format!("Expected ')', got '{}'", other.text(self.text)),
+            )),
+            None => Err(self.error_at(&token, "Unmatched '('".to_owned())),
+        }
+    }
+
+    /// `predicate := field operator value`
+    fn predicate(&mut self) -> Result<Predicate, ParseError> {
+        let field_token = self.next().expect("primary checked for a token");
+        let field = match &field_token.kind {
+            TokenKind::Word(name)
+                if !field_token.is_keyword("and") && !field_token.is_keyword("or") =>
+            {
+                Field::from_name(name).ok_or_else(|| {
+                    self.error_at(&field_token, format!("Unknown field '{}'", name))
+                })?
+            }
+            _ => {
+                return Err(self.error_at(
+                    &field_token,
+                    format!(
+                        "Expected a field name, got '{}'",
+                        field_token.text(self.text)
+                    ),
+                ))
+            }
+        };
+        let operator_token = self
+            .next()
+            .ok_or_else(|| self.error_at_end("Expected a comparison like '=' or '>'"))?;
+        let TokenKind::Operator(operator) = operator_token.kind else {
+            return Err(self.error_at(
+                &operator_token,
+                format!(
+                    "Expected a comparison like '=' or '>', got '{}'",
+                    operator_token.text(self.text)
+                ),
+            ));
+        };
+        let value_token = self
+            .next()
+            .ok_or_else(|| self.error_at_end("Expected a value"))?;
+        let value = match &value_token.kind {
+            TokenKind::Word(value) | TokenKind::Quoted(value) => value.clone(),
+            _ => {
+                return Err(self.error_at(
+                    &value_token,
+                    format!("Expected a value, got '{}'", value_token.text(self.text)),
+                ))
+            }
+        };
+        let predicate = Predicate {
+            field,
+            operator,
+            value,
+        };
+        if let Err(e) = predicate.validate() {
+            // ordering a text field is the operator's fault, anything else the value's
+            let culprit = if field.is_numeric() {
+                &value_token
+            } else {
+                &operator_token
+            };
+            return Err(self.error_at(culprit, e.to_string()));
+        }
+        Ok(predicate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn predicate(field: Field, operator: Operator, value: &str) -> Query {
+        Query::Predicate(Predicate {
+            field,
+            operator,
+            value: value.to_owned(),
+        })
+    }
+
+    fn and(a: Query, b: Query) -> Query {
+        Query::And(Box::new(a), Box::new(b))
+    }
+
+    fn or(a: Query, b: Query) -> Query {
+        Query::Or(Box::new(a), Box::new(b))
+    }
+
+    /// The caret line of a parse error.
+    fn caret(text: &str) -> String {
+        let error = Query::parse(text).unwrap_err();
+        error
+            .pointer()
+            .lines()
+            .nth(1)
+            .unwrap_or_default()
+            .to_owned()
+    }
+
+    #[test]
+    fn empty_query_matches_everything() {
+        assert_eq!(Query::parse("  "), Ok(Query::All));
+        assert!(Query::All.matches_pilot(&testing::pilot("N1")));
+        assert!(Query::All.to_string().is_empty());
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let a = predicate(Field::Callsign, Operator::Equal, "A");
+        let b = predicate(Field::Callsign, Operator::Equal, "B");
+        let c = predicate(Field::Callsign, Operator::Equal, "C");
+        assert_eq!(
+            Query::parse("callsign=A or callsign=B and callsign=C"),
+            Ok(or(a.clone(), and(b.clone(), c.clone())))
+        );
+        assert_eq!(
+            Query::parse("callsign=A AND callsign=B Or callsign=C"),
+            Ok(or(and(a.clone(), b.clone()), c.clone()))
+        );
+        assert_eq!(
+            Query::parse("(callsign=A or callsign=B) and callsign=C"),
+            Ok(and(or(a, b), c))
+        );
+    }
+
+    #[test]
+    fn precedence_when_evaluating() {
+        let mut pilot = testing::flying("BAW1", "EGLL", "KJFK");
+        pilot.altitude = 1000;
+        let query = Query::parse("altitude>30000 and callsign=X or departure=EGLL").unwrap();
+        assert!(query.matches_pilot(&pilot));
+        let query = Query::parse("altitude>30000 and (callsign=X or departure=EGLL)").unwrap();
+        assert!(!query.matches_pilot(&pilot));
+    }
+
+    #[test]
+    fn display_keeps_grouping() {
+        for text in [
+            "callsign=A or callsign=B and callsign=C",
+            "(callsign=A or callsign=B) and callsign=C",
+            "callsign=A and (callsign=B or callsign=C) and altitude>=100",
+            "name=\"A (B)\" or name=\"say \\\"hi\\\"\"",
+        ] {
+            let query = Query::parse(text).unwrap();
+            assert_eq!(query.to_string(), text);
+            assert_eq!(Query::parse(&query.to_string()), Ok(query));
+        }
+    }
+
+    #[test]
+    fn glob_edge_cases() {
+        let pilot = testing::flying("DLH4AB", "EDDF", "EDDM");
+        let matches = |text: &str| Query::parse(text).unwrap().matches_pilot(&pilot);
+        assert!(matches("callsign=*"));
+        assert!(matches("callsign=dlh*"));
+        assert!(matches("callsign=*4*"));
+        assert!(matches("callsign=D*H*B"));
+        assert!(!matches("callsign=DLH"));
+        assert!(!matches("callsign=*X*"));
+        assert!(matches("callsign!=DLH"));
+        assert!(matches("arrival=\"ED*\""));
+        // a glob never matches a flight without a plan
+        assert!(!Query::parse("departure=*")
+            .unwrap()
+            .matches_pilot(&testing::pilot("N1")));
+    }
+
+    #[test]
+    fn errors_point_at_the_token() {
+        assert_eq!(caret("callsing=BAW"), "^^^^^^^^");
+        assert_eq!(caret("callsign=BAW and"), "                ^");
+        assert_eq!(caret("callsign BAW"), "         ^^^");
+        assert_eq!(caret("callsign=BAW)"), "            ^");
+        assert_eq!(caret("(callsign=BAW"), "^");
+        assert_eq!(caret("callsign=BAW altitude>1"), "             ^^^^^^^^");
+        assert_eq!(caret("callsign=>BAW"), "        ^^");
+        assert_eq!(caret("name=\"abc"), "     ^^^^");
+        assert_eq!(caret("name=\"abc\\\""), "     ^^^^^^");
+        assert_eq!(caret("name="), "     ^");
+        assert_eq!(caret("ä=1"), "^");
+    }
+
+    #[test]
+    fn error_messages() {
+        let message = |text: &str| Query::parse(text).unwrap_err().message;
+        assert_eq!(message("callsing=BAW"), "Unknown field 'callsing'");
+        assert_eq!(message("callsign=BAW)"), "Unmatched ')'");
+        assert_eq!(message("(callsign=BAW"), "Unmatched '('");
+        assert_eq!(
+            message("callsign=BAW altitude>1"),
+            "Expected 'and' or 'or', got 'altitude'"
+        );
+        assert_eq!(message("callsign=>BAW"), "Unknown comparison '=>'");
+        assert_eq!(message("name=\"abc"), "Unterminated quote");
+        assert_eq!(message("and=1"), "Expected a field name, got 'and'");
+    }
+
+    #[test]
+    fn type_mismatches_are_errors() {
+        let error = Query::parse("callsign>BAW").unwrap_err();
+        assert_eq!(
+            error.message,
+            "'callsign' is text and can't be compared with '>'"
+        );
+        assert_eq!(error.pointer().lines().nth(1), Some("        ^"));
+
+        let error = Query::parse("altitude=high").unwrap_err();
+        assert_eq!(error.message, "'altitude' needs a number, got 'high'");
+        assert_eq!(error.pointer().lines().nth(1), Some("         ^^^^"));
+
+        assert!(Query::parse("rating<=\"5\"").is_ok());
+        assert!(Query::parse("groundspeed=*").is_err());
+    }
+
+    #[test]
+    fn as_filter_only_for_conjunctions() {
+        let query = Query::parse("callsign=A and altitude>1 and name=B").unwrap();
+        let filter = query.as_filter().unwrap();
+        assert_eq!(filter.predicates.len(), 3);
+        assert_eq!(Query::from(filter), query);
+        assert!(Query::parse("callsign=A or callsign=B")
+            .unwrap()
+            .as_filter()
+            .is_none());
+        assert_eq!(Query::All.as_filter().unwrap().predicates, []);
+    }
+
+    #[test]
+    fn airport_needs_exactly_one() {
+        let airport = |text: &str| Query::parse(text).unwrap().airport();
+        assert_eq!(
+            airport("arrival=egll or departure=EGLL"),
+            Some("EGLL".to_owned())
+        );
+        assert_eq!(
+            airport("arrival=EGLL and altitude<1000"),
+            Some("EGLL".to_owned())
+        );
+        assert_eq!(airport("arrival=EGLL or departure=EGKK"), None);
+        assert_eq!(airport("arrival=EG*"), None);
+        assert_eq!(airport("arrival!=EGLL"), None);
+        assert_eq!(airport("callsign=EGLL"), None);
+    }
+
+    #[test]
+    fn the_one_airport_a_query_names() {
+        let airport = |text: &str| Query::parse(text).unwrap().airport();
+        assert_eq!(airport("arrival=EGLL"), Some("EGLL".to_owned()));
+        assert_eq!(
+            airport("arrival=egll or departure=EGLL"),
+            Some("EGLL".to_owned())
+        );
+        assert_eq!(
+            airport("departure=EGLL and callsign=BAW*"),
+            Some("EGLL".to_owned())
+        );
+        // none, or more than one
+        assert_eq!(airport("callsign=BAW*"), None);
+        assert_eq!(airport("arrival=EGLL or departure=EGKK"), None);
+        // only exact matches name an airport
+        assert_eq!(airport("arrival=EG*"), None);
+        assert_eq!(airport("arrival!=EGLL"), None);
+        assert_eq!(Query::All.airport(), None);
+        assert_eq!(
+            predicate(Field::Arrival, Operator::Equal, " ").airport(),
+            None
+        );
+    }
+}
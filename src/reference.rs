@@ -0,0 +1,334 @@
+//! Typed controller ratings and facilities, for the ids in the API data.
+
+use crate::models::{ReferenceItem, V3ResponseData};
+use anyhow::{anyhow, Error};
+use log::warn;
+use std::{collections::HashMap, fmt, sync::Once};
+
+/// A controller's rating.
+///
+/// Ids the table doesn't know about yet are kept as `Unknown`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ControllerRating {
+    Inactive,
+    Suspended,
+    Observer,
+    Student1,
+    Student2,
+    Student3,
+    Controller1,
+    Controller2,
+    Controller3,
+    Instructor1,
+    Instructor2,
+    Instructor3,
+    Supervisor,
+    Administrator,
+    Unknown(i8),
+}
+
+/// Ratings by id, with their official short names.
+const RATINGS: &[(i8, ControllerRating, &str)] = &[
+    (-1, ControllerRating::Inactive, "INA"),
+    (0, ControllerRating::Suspended, "SUS"),
+    (1, ControllerRating::Observer, "OBS"),
+    (2, ControllerRating::Student1, "S1"),
+    (3, ControllerRating::Student2, "S2"),
+    (4, ControllerRating::Student3, "S3"),
+    (5, ControllerRating::Controller1, "C1"),
+    (6, ControllerRating::Controller2, "C2"),
+    (7, ControllerRating::Controller3, "C3"),
+    (8, ControllerRating::Instructor1, "I1"),
+    (9, ControllerRating::Instructor2, "I2"),
+    (10, ControllerRating::Instructor3, "I3"),
+    (11, ControllerRating::Supervisor, "SUP"),
+    (12, ControllerRating::Administrator, "ADM"),
+];
+
+impl ControllerRating {
+    /// Id used in the API data.
+    #[must_use]
+    pub fn id(self) -> i8 {
+        match self {
+            ControllerRating::Unknown(id) => id,
+            rating => RATINGS
+                .iter()
+                .find(|(_, r, _)| *r == rating)
+                .map_or(0, |(id, _, _)| *id),
+        }
+    }
+}
+
+impl From<i8> for ControllerRating {
+    fn from(id: i8) -> Self {
+        RATINGS
+            .iter()
+            .find(|(i, _, _)| *i == id)
+            .map_or(ControllerRating::Unknown(id), |(_, rating, _)| *rating)
+    }
+}
+
+impl fmt::Display for ControllerRating {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match RATINGS.iter().find(|(_, rating, _)| rating == self) {
+            Some((_, _, short)) => write!(f, "{}", short),
+            None => write!(f, "{}", self.id()),
+        }
+    }
+}
+
+/// The kind of position a controller is working.
+///
+/// Ids the table doesn't know about yet are kept as `Unknown`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Facility {
+    Observer,
+    FlightService,
+    Delivery,
+    Ground,
+    Tower,
+    Approach,
+    Center,
+    Unknown(i8),
+}
+
+/// Facilities by id, with their official short names.
+const FACILITIES: &[(i8, Facility, &str)] = &[
+    (0, Facility::Observer, "OBS"),
+    (1, Facility::FlightService, "FSS"),
+    (2, Facility::Delivery, "DEL"),
+    (3, Facility::Ground, "GND"),
+    (4, Facility::Tower, "TWR"),
+    (5, Facility::Approach, "APP"),
+    (6, Facility::Center, "CTR"),
+];
+
+impl Facility {
+    /// Look up a facility by its short name, like "TWR".
+    #[must_use]
+    pub fn from_short_name(name: &str) -> Option<Self> {
+        FACILITIES
+            .iter()
+            .find(|(_, _, short)| short.eq_ignore_ascii_case(name))
+            .map(|(_, facility, _)| *facility)
+    }
+
+    /// Id used in the API data.
+    #[must_use]
+    pub fn id(self) -> i8 {
+        match self {
+            Facility::Unknown(id) => id,
+            facility => FACILITIES
+                .iter()
+                .find(|(_, f, _)| *f == facility)
+                .map_or(0, |(id, _, _)| *id),
+        }
+    }
+}
+
+impl From<i8> for Facility {
+    fn from(id: i8) -> Self {
+        FACILITIES
+            .iter()
+            .find(|(i, _, _)| *i == id)
+            .map_or(Facility::Unknown(id), |(_, facility, _)| *facility)
+    }
+}
+
+impl TryFrom<i64> for Facility {
+    type Error = Error;
+
+    fn try_from(id: i64) -> Result<Self, Self::Error> {
+        i8::try_from(id)
+            .map(Facility::from)
+            .map_err(|_| anyhow!("Facility id {} is out of range", id))
+    }
+}
+
+impl fmt::Display for Facility {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match FACILITIES.iter().find(|(_, facility, _)| facility == self) {
+            Some((_, _, short)) => write!(f, "{}", short),
+            None => write!(f, "{}", self.id()),
+        }
+    }
+}
+
+/// Logs that the built-in ratings were needed, the first time only.
+static RATINGS_FALLBACK: Once = Once::new();
+/// Logs that the built-in facilities were needed, the first time only.
+static FACILITIES_FALLBACK: Once = Once::new();
+
+/// Short names of ratings and facilities by id.
+///
+/// The data's own lists are the source of truth, but they've been sent
+/// empty during incidents, so the built-in tables fill any gaps.
+#[derive(Debug, Clone, Default)]
+pub struct ReferenceNames {
+    ratings: HashMap<i8, String>,
+    facilities: HashMap<i8, String>,
+}
+
+impl ReferenceNames {
+    /// Build the lookups for a set of data.
+    #[must_use]
+    pub fn new(data: &V3ResponseData) -> Self {
+        Self {
+            ratings: merge(
+                &data.ratings,
+                RATINGS.iter().map(|(id, _, short)| (*id, *short)),
+                &RATINGS_FALLBACK,
+                "ratings",
+            ),
+            facilities: merge(
+                &data.facilities,
+                FACILITIES.iter().map(|(id, _, short)| (*id, *short)),
+                &FACILITIES_FALLBACK,
+                "facilities",
+            ),
+        }
+    }
+
+    /// Short name for a controller rating.
+    pub fn rating(&self, rating: ControllerRating) -> String {
+        self.ratings
+            .get(&rating.id())
+            .map_or_else(|| rating.to_string(), String::clone)
+    }
+
+    /// Short name for a facility.
+    pub fn facility(&self, facility: Facility) -> String {
+        self.facilities
+            .get(&facility.id())
+            .map_or_else(|| facility.to_string(), String::clone)
+    }
+}
+
+/// Names from the data's list, with the built-in names for ids it's missing.
+fn merge(
+    live: &[ReferenceItem],
+    built_in: impl Iterator<Item = (i8, &'static str)>,
+    fallback_logged: &Once,
+    kind: &str,
+) -> HashMap<i8, String> {
+    let mut names: HashMap<i8, String> =
+        built_in.map(|(id, short)| (id, short.to_owned())).collect();
+    let filled = names
+        .keys()
+        .filter(|id| !live.iter().any(|item| item.id == **id))
+        .count();
+    if filled > 0 {
+        fallback_logged.call_once(|| {
+            warn!(
+                "VATSIM data is missing {} of the {}, using built-in names",
+                filled, kind
+            );
+        });
+    }
+    for item in live {
+        let _ = names.insert(item.id, item.short.clone());
+    }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn item(id: i8, short: &str) -> ReferenceItem {
+        ReferenceItem {
+            id,
+            short: short.to_owned(),
+            long: format!("{} (long)", short),
+        }
+    }
+
+    #[test]
+    fn ratings_round_trip() {
+        for &(id, rating, short) in RATINGS {
+            assert_eq!(ControllerRating::from(id), rating);
+            assert_eq!(rating.id(), id);
+            assert_eq!(rating.to_string(), short);
+        }
+        assert_eq!(ControllerRating::from(13), ControllerRating::Unknown(13));
+        assert_eq!(ControllerRating::Unknown(13).id(), 13);
+        assert_eq!(ControllerRating::Unknown(13).to_string(), "13");
+    }
+
+    #[test]
+    fn facilities_round_trip() {
+        for &(id, facility, short) in FACILITIES {
+            assert_eq!(Facility::from(id), facility);
+            assert_eq!(Facility::try_from(i64::from(id)).unwrap(), facility);
+            assert_eq!(facility.id(), id);
+            assert_eq!(facility.to_string(), short);
+            assert_eq!(
+                Facility::from_short_name(&short.to_lowercase()),
+                Some(facility)
+            );
+        }
+        assert_eq!(Facility::from(7), Facility::Unknown(7));
+        assert_eq!(Facility::Unknown(7).to_string(), "7");
+        assert_eq!(Facility::from_short_name("RMP"), None);
+        assert!(Facility::try_from(1_000_i64).is_err());
+        assert!(Facility::try_from(-129_i64).is_err());
+    }
+
+    #[test]
+    fn controller_accessors() {
+        let controller = testing::controller("EGLL_TWR", 4);
+        assert_eq!(controller.facility_enum(), Some(Facility::Tower));
+        assert_eq!(controller.rating_enum(), ControllerRating::Controller1);
+        let odd = crate::models::Controller {
+            facility: 300,
+            rating: 42,
+            ..controller
+        };
+        assert_eq!(odd.facility_enum(), None);
+        assert_eq!(odd.rating_enum(), ControllerRating::Unknown(42));
+    }
+
+    #[test]
+    fn live_names_win_over_built_in_ones() {
+        let data = V3ResponseData {
+            ratings: vec![item(5, "TWR"), item(13, "NEW")],
+            facilities: vec![item(4, "ADC")],
+            ..V3ResponseData::default()
+        };
+        let names = ReferenceNames::new(&data);
+        assert_eq!(names.rating(ControllerRating::Controller1), "TWR");
+        assert_eq!(names.rating(ControllerRating::Unknown(13)), "NEW");
+        assert_eq!(names.facility(Facility::Tower), "ADC");
+        // built-in names fill the gaps
+        assert_eq!(names.rating(ControllerRating::Controller3), "C3");
+        assert_eq!(names.facility(Facility::Center), "CTR");
+        assert_eq!(names.facility(Facility::Unknown(9)), "9");
+    }
+
+    #[test]
+    fn fallback_is_logged_only_when_names_are_missing() {
+        let built_in = || FACILITIES.iter().map(|(id, _, short)| (*id, *short));
+        let complete: Vec<_> = FACILITIES
+            .iter()
+            .map(|&(id, _, short)| item(id, short))
+            .collect();
+        let logged = Once::new();
+        let names = merge(&complete, built_in(), &logged, "facilities");
+        assert!(!logged.is_completed());
+        assert_eq!(names.len(), FACILITIES.len());
+
+        let partial = &complete[..3];
+        let names = merge(partial, built_in(), &logged, "facilities");
+        assert!(logged.is_completed());
+        assert_eq!(names.len(), FACILITIES.len());
+        assert_eq!(names[&6], "CTR");
+    }
+
+    #[test]
+    fn built_in_names_without_live_lists() {
+        let names = ReferenceNames::new(&V3ResponseData::default());
+        assert_eq!(names.rating(ControllerRating::Student2), "S2");
+        assert_eq!(names.facility(Facility::Approach), "APP");
+    }
+}
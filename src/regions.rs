@@ -0,0 +1,215 @@
+//! Grouping controllers by the country or region of their position, from
+//! the ICAO code of the airport their callsign names.
+
+use crate::{
+    callsign::{self, ControllerCallsign},
+    models::Controller,
+};
+use std::collections::BTreeMap;
+
+/// Region of controllers whose position can't be placed.
+pub const OTHER: &str = "Other";
+
+/// ICAO code prefixes and the country or region they're in.
+///
+/// Longer prefixes are more specific, so "EG" is checked before "E".
+static PREFIXES: &[(&str, &str)] = &[
+    ("K", "USA"),
+    ("PA", "USA"),
+    ("PH", "USA"),
+    ("C", "Canada"),
+    ("MM", "Mexico"),
+    ("TJ", "Puerto Rico"),
+    ("SB", "Brazil"),
+    ("SA", "Argentina"),
+    ("SC", "Chile"),
+    ("SK", "Colombia"),
+    ("SP", "Peru"),
+    ("EG", "UK"),
+    ("EI", "Ireland"),
+    ("LF", "France"),
+    ("ED", "Germany"),
+    ("ET", "Germany"),
+    ("EH", "Netherlands"),
+    ("EB", "Belgium"),
+    ("EL", "Luxembourg"),
+    ("LS", "Switzerland"),
+    ("LO", "Austria"),
+    ("LE", "Spain"),
+    ("GC", "Spain"),
+    ("LP", "Portugal"),
+    ("LI", "Italy"),
+    ("LG", "Greece"),
+    ("LT", "Turkey"),
+    ("EK", "Denmark"),
+    ("EN", "Norway"),
+    ("ES", "Sweden"),
+    ("EF", "Finland"),
+    ("BI", "Iceland"),
+    ("EP", "Poland"),
+    ("LK", "Czechia"),
+    ("LH", "Hungary"),
+    ("LR", "Romania"),
+    ("LY", "Serbia"),
+    ("LD", "Croatia"),
+    ("EV", "Latvia"),
+    ("EY", "Lithuania"),
+    ("EE", "Estonia"),
+    ("UK", "Ukraine"),
+    ("U", "Russia"),
+    ("LL", "Israel"),
+    ("OM", "UAE"),
+    ("OE", "Saudi Arabia"),
+    ("OT", "Qatar"),
+    ("HE", "Egypt"),
+    ("FA", "South Africa"),
+    ("DN", "Nigeria"),
+    ("HK", "Kenya"),
+    ("GM", "Morocco"),
+    ("V", "South Asia"),
+    ("WS", "Singapore"),
+    ("WM", "Malaysia"),
+    ("WI", "Indonesia"),
+    ("WA", "Indonesia"),
+    ("VT", "Thailand"),
+    ("RP", "Philippines"),
+    ("RJ", "Japan"),
+    ("RO", "Japan"),
+    ("RK", "South Korea"),
+    ("RC", "Taiwan"),
+    ("VH", "Hong Kong"),
+    ("Z", "China"),
+    ("Y", "Australia"),
+    ("NZ", "New Zealand"),
+];
+
+/// The country or region an ICAO code is in, by the longest matching
+/// prefix in the table.
+pub fn of_icao(icao: &str) -> Option<&'static str> {
+    let icao = icao.to_ascii_uppercase();
+    PREFIXES
+        .iter()
+        .filter(|(prefix, _)| icao.starts_with(prefix))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|&(_, region)| region)
+}
+
+/// The ICAO code a controller's callsign names: the known airport's, or
+/// else a prefix that looks like one, like the "EGTT" of "EGTT_CTR".
+fn controller_icao(callsign: &str) -> Option<&str> {
+    if let Some(airport) = callsign::controller_airport(callsign) {
+        return Some(airport.icao);
+    }
+    let prefix = ControllerCallsign::parse(callsign)?.prefix;
+    (prefix.len() == 4 && prefix.chars().all(|c| c.is_ascii_alphabetic())).then_some(prefix)
+}
+
+/// The region of a controller's position, or [`OTHER`] if their callsign
+/// doesn't name an ICAO code in a listed region.
+pub fn of_controller(controller: &Controller) -> &'static str {
+    controller_icao(&controller.callsign)
+        .and_then(of_icao)
+        .unwrap_or(OTHER)
+}
+
+/// Number of controllers in each region, most first, with ties in
+/// alphabetical order.
+pub fn count(controllers: &[Controller]) -> Vec<(&'static str, usize)> {
+    let mut counts: BTreeMap<&'static str, usize> = BTreeMap::new();
+    for controller in controllers {
+        *counts.entry(of_controller(controller)).or_default() += 1;
+    }
+    let mut counts: Vec<(&'static str, usize)> = counts.into_iter().collect();
+    // stable, so ties stay alphabetical
+    counts.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn regions(callsigns: &[&str]) -> Vec<&'static str> {
+        callsigns
+            .iter()
+            .map(|callsign| of_controller(&testing::controller(callsign, 5)))
+            .collect()
+    }
+
+    #[test]
+    fn icao_prefixes() {
+        assert_eq!(of_icao("KJFK"), Some("USA"));
+        assert_eq!(of_icao("PHNL"), Some("USA"));
+        assert_eq!(of_icao("EGLL"), Some("UK"));
+        assert_eq!(of_icao("lfpg"), Some("France"));
+        assert_eq!(of_icao("EDDF"), Some("Germany"));
+        assert_eq!(of_icao("ETNG"), Some("Germany"));
+        assert_eq!(of_icao("YSSY"), Some("Australia"));
+        assert_eq!(of_icao("EZZZ"), None);
+        assert_eq!(of_icao("QQQQ"), None);
+        assert_eq!(of_icao(""), None);
+    }
+
+    #[test]
+    fn longest_prefix_wins() {
+        // in any order in the table
+        assert_eq!(of_icao("UKBB"), Some("Ukraine"));
+        assert_eq!(of_icao("UUEE"), Some("Russia"));
+        assert_eq!(of_icao("VHHH"), Some("Hong Kong"));
+        assert_eq!(of_icao("VTBS"), Some("Thailand"));
+        assert_eq!(of_icao("VIDP"), Some("South Asia"));
+        for (prefix, _) in PREFIXES {
+            let longer = PREFIXES
+                .iter()
+                .filter(|(other, _)| other.len() > prefix.len() && other.starts_with(prefix));
+            for (other, region) in longer {
+                assert_eq!(
+                    of_icao(&format!("{:X<4}", other)),
+                    Some(*region),
+                    "{}",
+                    other
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn controllers_by_their_callsign() {
+        assert_eq!(
+            regions(&["EGLL_TWR", "SFO_APP", "EGTT_CTR", "UKBV_CTR", "LFPG_DEL"]),
+            ["UK", "USA", "UK", "Ukraine", "France"]
+        );
+        // no airport or ICAO code to go on
+        assert_eq!(
+            regions(&[
+                "LON_S_CTR",
+                "ZOA_CTR",
+                "EZZZ_CTR",
+                "E2GL_TWR",
+                "NOTACALLSIGN"
+            ]),
+            [OTHER; 5]
+        );
+    }
+
+    #[test]
+    fn counts_are_ranked() {
+        let controllers: Vec<Controller> = [
+            "EGLL_TWR",
+            "LON_S_CTR",
+            "KJFK_TWR",
+            "EGKK_APP",
+            "LFPG_TWR",
+            "KBOS_APP",
+        ]
+        .iter()
+        .map(|callsign| testing::controller(callsign, 4))
+        .collect();
+        assert_eq!(
+            count(&controllers),
+            [("UK", 2), ("USA", 2), ("France", 1), (OTHER, 1)]
+        );
+        assert!(count(&[]).is_empty());
+    }
+}
@@ -0,0 +1,207 @@
+//! Where the command-line paths print: the requested data to stdout, and
+//! everything else as quiet as `-q` asks.
+
+use crate::{
+    api::{DataResponse, Vatsim},
+    mirrors::MirrorSelection,
+    models::V3ResponseData,
+    privacy::Privacy,
+};
+use anyhow::{bail, Result};
+use reqwest::header::HeaderValue;
+use std::{
+    fmt::Display,
+    io::{self, Write},
+};
+
+/// How much besides the requested data is printed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// Notes like "Cleared cache" on stdout, and warnings on stderr.
+    #[default]
+    Normal,
+    /// Only warnings, on stderr.
+    Quiet,
+    /// No warnings either; errors and exit codes still tell what happened.
+    Silent,
+}
+
+impl Verbosity {
+    /// The verbosity for the number of times `-q` was given.
+    pub fn from_quiet(count: u8) -> Self {
+        match count {
+            0 => Verbosity::Normal,
+            1 => Verbosity::Quiet,
+            _ => Verbosity::Silent,
+        }
+    }
+}
+
+/// Prints for a subcommand, sorting lines onto stdout and stderr.
+pub struct Reporter {
+    verbosity: Verbosity,
+    out: Box<dyn Write>,
+    err: Box<dyn Write>,
+}
+
+impl Reporter {
+    /// A reporter printing to this process's stdout and stderr.
+    pub fn stdio(verbosity: Verbosity) -> Self {
+        Self::new(verbosity, Box::new(io::stdout()), Box::new(io::stderr()))
+    }
+
+    /// A reporter printing to other streams.
+    pub fn new(verbosity: Verbosity, out: Box<dyn Write>, err: Box<dyn Write>) -> Self {
+        Self {
+            verbosity,
+            out,
+            err,
+        }
+    }
+
+    /// Print the data the command was run for, which is never silenced.
+    pub fn data(&mut self, text: impl Display) -> io::Result<()> {
+        writeln!(self.out, "{}", text)
+    }
+
+    /// Print a note on what was done, unless quiet.
+    pub fn note(&mut self, text: impl Display) {
+        if self.verbosity == Verbosity::Normal {
+            let _ = writeln!(self.out, "{}", text);
+        }
+    }
+
+    /// Print a warning to stderr, unless silent.
+    pub fn warn(&mut self, text: impl Display) {
+        if self.verbosity < Verbosity::Silent {
+            let _ = writeln!(self.err, "{}", text);
+        }
+    }
+
+    /// Print an error to stderr, which is never silenced.
+    pub fn error(&mut self, text: impl Display) {
+        let _ = writeln!(self.err, "{}", text);
+    }
+}
+
+/// Where a command-line path gets its data, and how the names in it are
+/// shown.
+#[derive(Debug, Clone, Copy)]
+pub struct DataSource<'a> {
+    pub use_cache: bool,
+    pub mirror: &'a MirrorSelection,
+    pub user_agent: &'a HeaderValue,
+    pub privacy: Privacy,
+}
+
+/// Fetch the current data for a command-line path, warning about each
+/// mirror that had to be skipped, with names hidden as asked.
+pub fn fetch_data(source: &DataSource, reporter: &mut Reporter) -> Result<V3ResponseData> {
+    let mut api = Vatsim::with_mirror(source.use_cache, false, source.user_agent, source.mirror)?;
+    let response = api.get_data();
+    for fallback in api.last_fallbacks() {
+        reporter.warn(fallback);
+    }
+    let DataResponse::Changed(mut data) = response? else {
+        bail!("No data returned from VATSIM");
+    };
+    source.privacy.apply(&mut data);
+    Ok(data)
+}
+
+/// A stream a test can read back after a reporter has written to it.
+#[cfg(test)]
+#[derive(Debug, Clone, Default)]
+pub struct Captured(std::rc::Rc<std::cell::RefCell<Vec<u8>>>);
+
+#[cfg(test)]
+impl Captured {
+    /// Everything written so far.
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.0.borrow()).into_owned()
+    }
+}
+
+#[cfg(test)]
+impl Write for Captured {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl Reporter {
+    /// A reporter writing into buffers, returned as (reporter, stdout, stderr).
+    pub fn captured(verbosity: Verbosity) -> (Self, Captured, Captured) {
+        let out = Captured::default();
+        let err = Captured::default();
+        let reporter = Self::new(verbosity, Box::new(out.clone()), Box::new(err.clone()));
+        (reporter, out, err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{mirrors::MirrorChoice, testing};
+
+    #[test]
+    fn quiet_counts() {
+        assert_eq!(Verbosity::from_quiet(0), Verbosity::Normal);
+        assert_eq!(Verbosity::from_quiet(1), Verbosity::Quiet);
+        assert_eq!(Verbosity::from_quiet(2), Verbosity::Silent);
+        assert_eq!(Verbosity::from_quiet(5), Verbosity::Silent);
+    }
+
+    #[test]
+    fn verbosity_silences_notes_then_warnings() {
+        for (verbosity, notes, warnings) in [
+            (Verbosity::Normal, true, true),
+            (Verbosity::Quiet, false, true),
+            (Verbosity::Silent, false, false),
+        ] {
+            let (mut reporter, out, err) = Reporter::captured(verbosity);
+            reporter.data("data").unwrap();
+            reporter.note("note");
+            reporter.warn("warning");
+            reporter.error("error");
+            assert!(out.text().contains("data"));
+            assert_eq!(out.text().contains("note"), notes);
+            assert_eq!(err.text().contains("warning"), warnings);
+            assert!(err.text().contains("error"));
+            assert!(!out.text().contains("error"));
+        }
+    }
+
+    #[test]
+    fn fetches_from_the_chosen_mirror_with_names_hidden() {
+        let data = V3ResponseData {
+            pilots: vec![testing::pilot("BAW1")],
+            ..V3ResponseData::default()
+        };
+        let chosen = mockito::mock("GET", "/chosen")
+            .with_header("content-type", "application/json")
+            .with_body(serde_json::to_string(&data).unwrap())
+            .create();
+        let mirror = MirrorSelection {
+            choice: MirrorChoice::Url(format!("{}/chosen", mockito::server_url())),
+            ..MirrorSelection::default()
+        };
+        let (mut reporter, _, _) = Reporter::captured(Verbosity::Normal);
+        let user_agent = HeaderValue::from_static("vatsim_online tests");
+        let source = DataSource {
+            use_cache: false,
+            mirror: &mirror,
+            user_agent: &user_agent,
+            privacy: Privacy::Cid,
+        };
+        let fetched = fetch_data(&source, &mut reporter).unwrap();
+        assert_eq!(fetched.pilots[0].callsign, "BAW1");
+        assert_eq!(fetched.pilots[0].name, "1000000");
+        chosen.assert();
+    }
+}
@@ -0,0 +1,239 @@
+//! Waypoints in a filed route, and a guess at which one a pilot is near.
+//!
+//! There's no navigation database, so waypoints are picked out by their
+//! shape and the pilot's place among them by distance flown.
+
+use crate::{airports, geo, models::Pilot};
+
+/// Route words that aren't places.
+const KEYWORDS: [&str; 5] = ["DCT", "SID", "STAR", "IFR", "VFR"];
+
+/// A pilot's route waypoints, and which one they're probably near.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteProgress {
+    pub waypoints: Vec<String>,
+    /// Index of the waypoint nearest the pilot's progress, if it could be
+    /// estimated.
+    pub near: Option<usize>,
+}
+
+/// The words of a route in order, uppercased, with any speed and level
+/// change after a slash removed.
+pub fn tokens(route: &str) -> Vec<String> {
+    route
+        .split_whitespace()
+        .filter_map(|word| word.split('/').next())
+        .filter(|word| !word.is_empty())
+        .map(str::to_uppercase)
+        .collect()
+}
+
+/// Whether a route word is an airway, like "UL9" or "J80": letters and
+/// then only digits. Speed and level groups like "F350" look the same.
+fn is_airway(token: &str) -> bool {
+    let digits = token.trim_start_matches(|c: char| c.is_ascii_alphabetic());
+    digits.len() < token.len() && !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Whether a route word is a departure or arrival procedure, like "BPK7G":
+/// letters, one digit, and one letter.
+fn is_procedure(token: &str) -> bool {
+    let bytes = token.as_bytes();
+    bytes.len() >= 4
+        && bytes[bytes.len() - 1].is_ascii_alphabetic()
+        && bytes[bytes.len() - 2].is_ascii_digit()
+        && bytes[..bytes.len() - 2].iter().all(u8::is_ascii_alphabetic)
+}
+
+/// Whether a route word plausibly names a waypoint or navaid: two to five
+/// letters and digits that isn't a keyword, airway, or procedure.
+pub fn is_waypoint(token: &str) -> bool {
+    (2..=5).contains(&token.len())
+        && token
+            .bytes()
+            .all(|b| b.is_ascii_uppercase() || b.is_ascii_digit())
+        && !KEYWORDS.contains(&token)
+        && !is_airway(token)
+        && !is_procedure(token)
+}
+
+/// The plausible waypoints in a route, in order, without repeats in a row.
+pub fn waypoints(route: &str) -> Vec<String> {
+    let mut waypoints: Vec<String> = tokens(route)
+        .into_iter()
+        .filter(|token| is_waypoint(token))
+        .collect();
+    waypoints.dedup();
+    waypoints
+}
+
+/// How far a pilot is from their departure to their arrival airport, from
+/// 0 to 1, if both are in the airport database.
+///
+/// This is the share of the distance flown out of the distance flown plus
+/// the distance left, both in straight lines.
+pub fn progress(pilot: &Pilot) -> Option<f64> {
+    let plan = pilot.flight_plan.as_ref()?;
+    let departure = airports::by_icao(&plan.departure)?;
+    let arrival = airports::by_icao(&plan.arrival)?;
+    let flown = geo::distance_nm(
+        departure.latitude,
+        departure.longitude,
+        pilot.latitude,
+        pilot.longitude,
+    );
+    let left = geo::distance_nm(
+        pilot.latitude,
+        pilot.longitude,
+        arrival.latitude,
+        arrival.longitude,
+    );
+    let total = flown + left;
+    Some(if total > 0.0 { flown / total } else { 0.0 })
+}
+
+/// Index of the waypoint at some progress along a route of `count`
+/// waypoints, treating them as evenly spaced.
+pub fn index_at(count: usize, progress: f64) -> Option<usize> {
+    let last = count.checked_sub(1)?;
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    let index = (progress.clamp(0.0, 1.0) * last as f64).round() as usize;
+    Some(index.min(last))
+}
+
+/// A pilot's route waypoints and the one they're probably near.
+pub fn pilot(pilot: &Pilot) -> Option<RouteProgress> {
+    let plan = pilot.flight_plan.as_ref()?;
+    let waypoints = waypoints(&plan.route);
+    if waypoints.is_empty() {
+        return None;
+    }
+    let near = progress(pilot).and_then(|progress| index_at(waypoints.len(), progress));
+    Some(RouteProgress { waypoints, near })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn tokens_drop_speed_and_level_changes() {
+        assert_eq!(
+            tokens("  n0450f350 lam  DCT BPK/N0460F370 UL9\tKENET "),
+            ["N0450F350", "LAM", "DCT", "BPK", "UL9", "KENET"]
+        );
+        assert!(tokens("").is_empty());
+        assert!(tokens(" / ").is_empty());
+    }
+
+    #[test]
+    fn waypoint_shapes() {
+        for waypoint in ["LAM", "BPK", "KENET", "OAL", "5020N", "NATC", "EGLL"] {
+            assert!(is_waypoint(waypoint), "{}", waypoint);
+        }
+        for other in [
+            "DCT",
+            "SID",
+            "STAR",
+            "IFR",
+            "VFR",
+            "UL9",
+            "J80",
+            "Q120",
+            "T9",
+            "F350",
+            "A045",
+            "BPK7G",
+            "CPT3F",
+            "N0450F350",
+            "D",
+            "TOOLONG",
+            "lam",
+            "BP-K",
+        ] {
+            assert!(!is_waypoint(other), "{}", other);
+        }
+    }
+
+    #[test]
+    fn waypoints_in_real_routes() {
+        assert_eq!(
+            waypoints("CPT3F CPT L9 KENET DCT NIGIT L18 VENUS DCT MALOT/M083F350 NATC PORTI/N0478F350 DCT SUPRY"),
+            ["CPT", "KENET", "NIGIT", "VENUS", "MALOT", "NATC", "PORTI", "SUPRY"]
+        );
+        assert_eq!(
+            waypoints("SSTIK4 SSTIK J110 BERYL J80 OAL DCT MVA Q120 LEGGS"),
+            ["SSTIK", "BERYL", "OAL", "MVA", "LEGGS"]
+        );
+        assert_eq!(
+            waypoints("DCT LAM DCT DCT LAM L10 BPK BPK7G"),
+            ["LAM", "BPK"]
+        );
+        assert!(waypoints("DCT DCT DCT").is_empty());
+        assert!(waypoints("").is_empty());
+    }
+
+    #[test]
+    fn progress_maps_to_evenly_spaced_waypoints() {
+        assert_eq!(index_at(0, 0.5), None);
+        assert_eq!(index_at(1, 0.7), Some(0));
+        assert_eq!(index_at(5, 0.0), Some(0));
+        assert_eq!(index_at(5, 0.49), Some(2));
+        assert_eq!(index_at(5, 0.9), Some(4));
+        assert_eq!(index_at(5, 1.0), Some(4));
+        // out of range progress is clamped
+        assert_eq!(index_at(5, -0.3), Some(0));
+        assert_eq!(index_at(5, 1.7), Some(4));
+    }
+
+    /// A pilot between Heathrow and Kennedy, `share` of the way along the
+    /// meridian-ish line between them.
+    fn between(share: f64, route: &str) -> Pilot {
+        let (lhr, jfk) = ((51.47, -0.45), (40.64, -73.78));
+        let mut pilot = testing::flying("BAW1", "EGLL", "KJFK");
+        pilot.latitude = lhr.0 + (jfk.0 - lhr.0) * share;
+        pilot.longitude = lhr.1 + (jfk.1 - lhr.1) * share;
+        if let Some(plan) = &mut pilot.flight_plan {
+            plan.route = route.to_owned();
+        }
+        pilot
+    }
+
+    #[test]
+    fn progress_between_the_airports() {
+        let at_departure = progress(&between(0.0, "")).unwrap();
+        assert!(at_departure < 0.01, "{}", at_departure);
+        let at_arrival = progress(&between(1.0, "")).unwrap();
+        assert!(at_arrival > 0.99, "{}", at_arrival);
+        let halfway = progress(&between(0.5, "")).unwrap();
+        assert!((0.4..0.6).contains(&halfway), "{}", halfway);
+
+        let mut unknown = between(0.5, "");
+        if let Some(plan) = &mut unknown.flight_plan {
+            plan.arrival = "KZZZ".to_owned();
+        }
+        assert_eq!(progress(&unknown), None);
+    }
+
+    #[test]
+    fn pilot_near_a_waypoint() {
+        let route = "CPT L9 KENET DCT NIGIT NATC PORTI DCT SUPRY";
+        assert_eq!(
+            pilot(&between(0.0, route)),
+            Some(RouteProgress {
+                waypoints: ["CPT", "KENET", "NIGIT", "NATC", "PORTI", "SUPRY"]
+                    .map(str::to_owned)
+                    .to_vec(),
+                near: Some(0),
+            })
+        );
+        assert_eq!(pilot(&between(1.0, route)).unwrap().near, Some(5));
+        assert_eq!(pilot(&between(0.5, "DCT")), None);
+        assert_eq!(pilot(&testing::pilot("N123")), None);
+    }
+}
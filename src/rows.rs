@@ -0,0 +1,94 @@
+//! The math for fitting table rows on screen and moving through them.
+
+use std::ops::Range;
+
+/// The rows to show out of `len`, when `fit` rows fit in the table.
+///
+/// Starting at `offset`, the first row shown last time, the window scrolls
+/// as little as it takes to keep the selected row in view. This is how the
+/// table widget places its rows, so building only these rows and drawing
+/// them as a table of their own looks the same as drawing them all.
+pub fn visible_rows(len: usize, selected: usize, offset: usize, fit: usize) -> Range<usize> {
+    if len == 0 || fit == 0 {
+        return 0..0;
+    }
+    let selected = selected.min(len - 1);
+    let mut start = offset.min(len - 1);
+    if selected >= start + fit {
+        start = selected + 1 - fit;
+    } else if selected < start {
+        start = selected;
+    }
+    start..(start + fit).min(len)
+}
+
+/// The row selected after moving `rows` from `selected` out of `len`, or
+/// `None` if there are no rows.
+///
+/// Single steps wrap around; bigger jumps stop at the ends.
+pub fn moved(len: usize, selected: usize, rows: i32) -> Option<usize> {
+    let last = len.checked_sub(1)?;
+    let step = usize::try_from(rows.unsigned_abs()).unwrap_or(usize::MAX);
+    Some(match rows {
+        1 if selected >= last => 0,
+        1 => selected + 1,
+        -1 if selected == 0 => last,
+        -1 => selected - 1,
+        rows if rows > 0 => selected.saturating_add(step).min(last),
+        _ => selected.saturating_sub(step),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{moved, visible_rows};
+
+    const LEN: usize = 5000;
+    const FIT: usize = 40;
+
+    #[test]
+    fn top_of_a_large_table() {
+        assert_eq!(visible_rows(LEN, 0, 0, FIT), 0..FIT);
+        assert_eq!(visible_rows(LEN, FIT - 1, 0, FIT), 0..FIT);
+    }
+
+    #[test]
+    fn scrolls_as_little_as_it_takes() {
+        assert_eq!(visible_rows(LEN, FIT, 0, FIT), 1..FIT + 1);
+        assert_eq!(visible_rows(LEN, 2500, 0, FIT), 2461..2501);
+        assert_eq!(visible_rows(LEN, 2470, 2461, FIT), 2461..2501);
+        assert_eq!(visible_rows(LEN, 2400, 2461, FIT), 2400..2440);
+    }
+
+    #[test]
+    fn bottom_of_a_large_table() {
+        assert_eq!(visible_rows(LEN, LEN - 1, 0, FIT), LEN - FIT..LEN);
+        assert_eq!(visible_rows(LEN, LEN + 10, 0, FIT), LEN - FIT..LEN);
+        assert_eq!(visible_rows(LEN, LEN - 1, LEN - 1, FIT), LEN - 1..LEN);
+    }
+
+    #[test]
+    fn offset_past_the_end_is_clamped() {
+        assert_eq!(visible_rows(10, 9, 50, FIT), 9..10);
+    }
+
+    #[test]
+    fn nothing_to_show() {
+        assert_eq!(visible_rows(0, 0, 0, FIT), 0..0);
+        assert_eq!(visible_rows(LEN, 10, 0, 0), 0..0);
+    }
+
+    #[test]
+    fn short_tables_show_every_row() {
+        assert_eq!(visible_rows(5, 3, 0, FIT), 0..5);
+    }
+
+    #[test]
+    fn single_steps_wrap_and_jumps_stop() {
+        assert_eq!(moved(5, 4, 1), Some(0));
+        assert_eq!(moved(5, 0, -1), Some(4));
+        assert_eq!(moved(5, 1, 10), Some(4));
+        assert_eq!(moved(5, 3, -10), Some(0));
+        assert_eq!(moved(0, 0, 1), None);
+    }
+}
@@ -0,0 +1,328 @@
+//! Spotting pilot records with impossible values, from broken clients.
+//!
+//! Some connections report groundspeeds of tens of thousands of knots,
+//! altitudes far underground, or positions that jump across the globe
+//! between refreshes. Those records are flagged so they can be left out of
+//! distances, ETAs, and counts.
+
+use crate::{format, geo, models::Pilot};
+use anyhow::{bail, Context, Result};
+use std::fmt;
+
+/// The values past which a record is taken to be broken.
+///
+/// The defaults leave room for the fastest and highest aircraft people
+/// fly on the network, like Concorde and the SR-71.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limits {
+    pub max_groundspeed_kt: i64,
+    /// Lowest altitude in feet, below the lowest airports.
+    pub min_altitude_ft: i64,
+    pub max_altitude_ft: i64,
+    /// Farthest a pilot can move between two records, in nautical miles,
+    /// unless the time between them allows it at the maximum groundspeed.
+    pub max_jump_nm: f64,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_groundspeed_kt: 2_500,
+            min_altitude_ft: -1_500,
+            max_altitude_ft: 100_000,
+            max_jump_nm: 600.0,
+        }
+    }
+}
+
+impl Limits {
+    /// Parse limits from the command line, like "speed=3000,jump=800".
+    ///
+    /// The keys are "speed", "min-altitude", "max-altitude", and "jump";
+    /// any left out keep their defaults.
+    ///
+    /// # Errors
+    ///
+    /// If a key is unknown, a value isn't a number, or the minimum altitude
+    /// isn't below the maximum.
+    pub fn parse(value: &str) -> Result<Self> {
+        let mut limits = Self::default();
+        for part in value.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let Some((key, number)) = part.split_once('=') else {
+                bail!("Expected key=value, got '{}'", part);
+            };
+            let number = number.trim();
+            let invalid = || format!("Invalid number '{}' for {}", number, key.trim());
+            match key.trim() {
+                "speed" => limits.max_groundspeed_kt = number.parse().with_context(invalid)?,
+                "min-altitude" => limits.min_altitude_ft = number.parse().with_context(invalid)?,
+                "max-altitude" => limits.max_altitude_ft = number.parse().with_context(invalid)?,
+                "jump" => limits.max_jump_nm = number.parse().with_context(invalid)?,
+                other => bail!(
+                    "Unknown limit '{}', expected speed, min-altitude, max-altitude, or jump",
+                    other
+                ),
+            }
+        }
+        if limits.min_altitude_ft >= limits.max_altitude_ft {
+            bail!("The minimum altitude has to be below the maximum");
+        }
+        Ok(limits)
+    }
+}
+
+/// Why a record was flagged.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BadData {
+    /// Groundspeed in knots past the limit, or negative.
+    Groundspeed(i64),
+    /// Altitude in feet outside the limits.
+    Altitude(i64),
+    /// Latitude or longitude off the globe.
+    Position,
+    /// Distance in nautical miles moved since the previous record.
+    Jump(f64),
+}
+
+impl fmt::Display for BadData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BadData::Groundspeed(kt) => write!(f, "groundspeed of {} kt", kt),
+            BadData::Altitude(ft) => write!(f, "altitude of {} ft", ft),
+            BadData::Position => write!(f, "position off the map"),
+            BadData::Jump(nm) => {
+                write!(f, "jumped {} nm since the last refresh", format::whole(*nm))
+            }
+        }
+    }
+}
+
+/// Whether a record's position is on the globe.
+fn on_globe(pilot: &Pilot) -> bool {
+    (-90.0..=90.0).contains(&pilot.latitude) && (-180.0..=180.0).contains(&pilot.longitude)
+}
+
+/// How far a pilot moved from the previous record, if it's farther than
+/// the limits allow.
+///
+/// A move longer than the jump limit is still allowed if the records are
+/// far enough apart in time to cover it at the maximum groundspeed, like
+/// after refreshing was paused. A pilot who reconnected, with a new logon
+/// time, can be anywhere.
+#[allow(clippy::cast_precision_loss)]
+fn jump(current: &Pilot, previous: &Pilot, limits: &Limits) -> Option<f64> {
+    if !on_globe(previous)
+        || previous.cid != current.cid
+        || previous.logon_time != current.logon_time
+    {
+        return None;
+    }
+    let distance = geo::distance_nm(
+        previous.latitude,
+        previous.longitude,
+        current.latitude,
+        current.longitude,
+    );
+    if distance <= limits.max_jump_nm {
+        return None;
+    }
+    let reachable = match (current.last_update(), previous.last_update()) {
+        (Some(now), Some(then)) if now > then => {
+            let hours = (now - then).num_seconds() as f64 / 3_600.0;
+            distance <= limits.max_groundspeed_kt as f64 * hours
+        }
+        _ => false,
+    };
+    (!reachable).then_some(distance)
+}
+
+/// The first problem with a pilot's record, if it has one, checking the
+/// move from their previous record if there is one.
+pub fn check(current: &Pilot, previous: Option<&Pilot>, limits: &Limits) -> Option<BadData> {
+    if !(0..=limits.max_groundspeed_kt).contains(&current.groundspeed) {
+        return Some(BadData::Groundspeed(current.groundspeed));
+    }
+    if !(limits.min_altitude_ft..=limits.max_altitude_ft).contains(&current.altitude) {
+        return Some(BadData::Altitude(current.altitude));
+    }
+    if !on_globe(current) {
+        return Some(BadData::Position);
+    }
+    previous
+        .and_then(|previous| jump(current, previous, limits))
+        .map(BadData::Jump)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    /// A pilot at a place and time, flying along.
+    fn at(latitude: f64, longitude: f64, last_updated: &str) -> Pilot {
+        Pilot {
+            latitude,
+            longitude,
+            altitude: 35_000,
+            groundspeed: 450,
+            last_updated: last_updated.to_owned(),
+            ..testing::flying("BAW1", "EGLL", "KJFK")
+        }
+    }
+
+    fn checked(pilot: &Pilot) -> Option<BadData> {
+        check(pilot, None, &Limits::default())
+    }
+
+    #[test]
+    fn ordinary_and_fast_flights_pass() {
+        assert_eq!(checked(&at(51.5, -0.5, "2022-08-01T12:30:00Z")), None);
+        // Concorde, and the SR-71 groups
+        for (groundspeed, altitude) in [(1_180, 60_000), (2_000, 85_000), (2_500, 100_000)] {
+            let fast = Pilot {
+                altitude,
+                groundspeed,
+                ..at(50.0, -30.0, "2022-08-01T12:30:00Z")
+            };
+            assert_eq!(checked(&fast), None, "{} kt", groundspeed);
+        }
+        // the Dead Sea, and a parked aircraft
+        let low = Pilot {
+            altitude: -1_400,
+            groundspeed: 0,
+            ..at(31.5, 35.5, "2022-08-01T12:30:00Z")
+        };
+        assert_eq!(checked(&low), None);
+    }
+
+    #[test]
+    fn groundspeed_out_of_range() {
+        for groundspeed in [40_000, 2_501, -1] {
+            let pilot = Pilot {
+                groundspeed,
+                ..at(51.5, -0.5, "2022-08-01T12:30:00Z")
+            };
+            assert_eq!(checked(&pilot), Some(BadData::Groundspeed(groundspeed)));
+        }
+    }
+
+    #[test]
+    fn altitude_out_of_range() {
+        for altitude in [-5_000, -1_501, 100_001] {
+            let pilot = Pilot {
+                altitude,
+                ..at(51.5, -0.5, "2022-08-01T12:30:00Z")
+            };
+            assert_eq!(checked(&pilot), Some(BadData::Altitude(altitude)));
+        }
+    }
+
+    #[test]
+    fn positions_off_the_globe() {
+        for (latitude, longitude) in [(91.0, 0.0), (-90.5, 0.0), (0.0, 180.5), (f64::NAN, 0.0)] {
+            assert_eq!(
+                checked(&at(latitude, longitude, "2022-08-01T12:30:00Z")),
+                Some(BadData::Position)
+            );
+        }
+        assert_eq!(checked(&at(-90.0, 180.0, "2022-08-01T12:30:00Z")), None);
+    }
+
+    #[test]
+    fn jumps_between_refreshes() {
+        let limits = Limits::default();
+        let london = at(51.5, -0.5, "2022-08-01T12:30:00Z");
+        // New York, 15 seconds later
+        let new_york = at(40.6, -73.8, "2022-08-01T12:30:15Z");
+        let Some(BadData::Jump(nm)) = check(&new_york, Some(&london), &limits) else {
+            panic!("expected a jump");
+        };
+        assert_eq!(format::whole(nm), "2,992");
+        // a short hop is fine
+        let nearby = at(51.6, -1.0, "2022-08-01T12:30:15Z");
+        assert_eq!(check(&nearby, Some(&london), &limits), None);
+        // two hours apart covers it at the maximum groundspeed
+        let later = at(40.6, -73.8, "2022-08-01T14:30:00Z");
+        assert_eq!(check(&later, Some(&london), &limits), None);
+        // the same update time doesn't
+        let same = at(40.6, -73.8, "2022-08-01T12:30:00Z");
+        assert!(check(&same, Some(&london), &limits).is_some());
+        // a larger limit allows it
+        let generous = Limits {
+            max_jump_nm: 3_000.0,
+            ..limits
+        };
+        assert_eq!(check(&new_york, Some(&london), &generous), None);
+        // a broken previous record says nothing about this one
+        let broken = at(95.0, 0.0, "2022-08-01T12:30:00Z");
+        assert_eq!(check(&new_york, Some(&broken), &limits), None);
+    }
+
+    #[test]
+    fn reconnecting_elsewhere_is_not_a_jump() {
+        let london = at(51.5, -0.5, "2022-08-01T12:30:00Z");
+        let reconnected = Pilot {
+            logon_time: "2022-08-01T12:30:10Z".to_owned(),
+            ..at(40.6, -73.8, "2022-08-01T12:30:15Z")
+        };
+        assert_eq!(check(&reconnected, Some(&london), &Limits::default()), None);
+        let someone_else = Pilot {
+            cid: 1_234_567,
+            ..at(40.6, -73.8, "2022-08-01T12:30:15Z")
+        };
+        assert_eq!(
+            check(&someone_else, Some(&london), &Limits::default()),
+            None
+        );
+    }
+
+    #[test]
+    fn reasons() {
+        assert_eq!(
+            BadData::Groundspeed(40_000).to_string(),
+            "groundspeed of 40000 kt"
+        );
+        assert_eq!(
+            BadData::Altitude(-5_000).to_string(),
+            "altitude of -5000 ft"
+        );
+        assert_eq!(BadData::Position.to_string(), "position off the map");
+        assert_eq!(
+            BadData::Jump(2_984.4).to_string(),
+            "jumped 2,984 nm since the last refresh"
+        );
+    }
+
+    #[test]
+    fn limits_from_the_command_line() {
+        assert_eq!(Limits::parse("").unwrap(), Limits::default());
+        assert_eq!(
+            Limits::parse(" speed = 3000, jump=800 ,").unwrap(),
+            Limits {
+                max_groundspeed_kt: 3_000,
+                max_jump_nm: 800.0,
+                ..Limits::default()
+            }
+        );
+        assert_eq!(
+            Limits::parse("min-altitude=-500,max-altitude=60000").unwrap(),
+            Limits {
+                min_altitude_ft: -500,
+                max_altitude_ft: 60_000,
+                ..Limits::default()
+            }
+        );
+        for (bad, error) in [
+            ("speed", "Expected key=value, got 'speed'"),
+            ("speed=fast", "Invalid number 'fast' for speed"),
+            ("height=3", "Unknown limit 'height'"),
+            (
+                "min-altitude=5000,max-altitude=4000",
+                "The minimum altitude has to be below the maximum",
+            ),
+        ] {
+            let message = Limits::parse(bad).unwrap_err().to_string();
+            assert!(message.starts_with(error), "{}: {}", bad, message);
+        }
+    }
+}
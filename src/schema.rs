@@ -0,0 +1,440 @@
+//! A JSON Schema of the JSON the commands print, so scripts built on it
+//! have something to pin against.
+//!
+//! The schema is kept by hand next to the types it describes; the list
+//! rows are built from the column tables so they can't drift.
+
+use crate::columns::{self, Column};
+use serde_json::{json, Map, Value};
+
+/// Version of the output formats, raised when a field is renamed,
+/// removed, or changes type.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Schema of a value of some type, or null.
+fn optional(kind: &str) -> Value {
+    json!({ "type": [kind, "null"] })
+}
+
+/// Schema of a `list --format json` row: an object with a string for
+/// each column header.
+fn list_rows(columns: &[Column]) -> Value {
+    let properties: Map<String, Value> = columns
+        .iter()
+        .map(|column| (column.header.to_owned(), json!({ "type": "string" })))
+        .collect();
+    let headers: Vec<&str> = columns.iter().map(|column| column.header).collect();
+    json!({
+        "type": "array",
+        "items": {
+            "type": "object",
+            "properties": properties,
+            "required": headers,
+            "additionalProperties": false,
+        },
+    })
+}
+
+/// The fields of each kind of change event besides `event`, and their types.
+const CHANGE_EVENTS: &[(&str, &[(&str, &str)])] = &[
+    (
+        "pilot_connected",
+        &[("cid", "integer"), ("callsign", "string")],
+    ),
+    (
+        "pilot_disconnected",
+        &[("cid", "integer"), ("callsign", "string")],
+    ),
+    (
+        "flight_plan_filed",
+        &[
+            ("cid", "integer"),
+            ("callsign", "string"),
+            ("departure", "string"),
+            ("arrival", "string"),
+        ],
+    ),
+    (
+        "altitude_band_changed",
+        &[
+            ("cid", "integer"),
+            ("callsign", "string"),
+            ("from", "integer"),
+            ("to", "integer"),
+        ],
+    ),
+    (
+        "controller_online",
+        &[("cid", "integer"), ("callsign", "string")],
+    ),
+    (
+        "controller_offline",
+        &[("cid", "integer"), ("callsign", "string")],
+    ),
+    (
+        "atis_updated",
+        &[("cid", "integer"), ("callsign", "string")],
+    ),
+    (
+        "callsign_changed",
+        &[("cid", "integer"), ("from", "string"), ("to", "string")],
+    ),
+];
+
+/// Schema of a change event as `watch --json` prints it, an object per
+/// kind of event with the time it was seen; `serve` numbers them too.
+fn change_event(numbered: bool) -> Value {
+    let kinds: Vec<Value> = CHANGE_EVENTS
+        .iter()
+        .map(|(kind, fields)| {
+            let mut properties = Map::new();
+            let _ = properties.insert("time".to_owned(), json!({ "type": "string" }));
+            let _ = properties.insert("event".to_owned(), json!({ "enum": [kind] }));
+            if numbered {
+                let _ = properties.insert("id".to_owned(), json!({ "type": "integer" }));
+            }
+            for (name, kind) in *fields {
+                let _ = properties.insert((*name).to_owned(), json!({ "type": kind }));
+            }
+            let required: Vec<&String> = properties.keys().collect();
+            json!({
+                "type": "object",
+                "properties": properties,
+                "required": required,
+                "additionalProperties": false,
+            })
+        })
+        .collect();
+    json!({ "anyOf": kinds })
+}
+
+/// The schema of every JSON output, each under `$defs`; a document
+/// printed by any command validates against the whole.
+pub fn schema() -> Value {
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "vatsim_online JSON output",
+        "version": SCHEMA_VERSION,
+        "anyOf": [
+            { "$ref": "#/$defs/list_pilots" },
+            { "$ref": "#/$defs/list_controllers" },
+            { "$ref": "#/$defs/atc" },
+            { "$ref": "#/$defs/lookup" },
+            { "$ref": "#/$defs/watch" },
+            { "$ref": "#/$defs/serve_events" },
+        ],
+        "$defs": {
+            "list_pilots": list_rows(columns::PILOT_COLUMNS),
+            "list_controllers": list_rows(columns::CONTROLLER_COLUMNS),
+            "atc": {
+                "type": "object",
+                "properties": {
+                    "airport": { "type": "string" },
+                    "online": { "type": "boolean" },
+                    "positions": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "callsign": { "type": "string" },
+                                "facility": { "type": "string" },
+                                "frequency": { "type": "string" },
+                                "name": { "type": "string" },
+                            },
+                            "required": ["callsign", "facility", "frequency", "name"],
+                            "additionalProperties": false,
+                        },
+                    },
+                },
+                "required": ["airport", "online", "positions"],
+                "additionalProperties": false,
+            },
+            "lookup": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "input": { "type": "string" },
+                        "online": { "type": "boolean" },
+                        "kind": { "enum": ["pilot", "controller", null] },
+                        "callsign": optional("string"),
+                        "cid": optional("integer"),
+                        "summary": optional("string"),
+                    },
+                    "required": ["input", "online", "kind", "callsign", "cid", "summary"],
+                    "additionalProperties": false,
+                },
+            },
+            "watch": change_event(false),
+            "serve_events": {
+                "type": "array",
+                "items": change_event(true),
+            },
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        atc,
+        diff::ChangeEvent,
+        format::DisplayZone,
+        list, lookup,
+        models::{Controller, Pilot, V3ResponseData},
+        output::{OutputFormat, Table},
+        serve::{self, EventLog},
+        testing,
+        watch::SeenEvent,
+    };
+    use chrono::Utc;
+    use std::collections::HashSet;
+
+    /// Why a value doesn't match a schema, checking the keywords this
+    /// schema uses, or nothing if it does.
+    fn errors(root: &Value, schema: &Value, value: &Value, at: &str) -> Vec<String> {
+        if let Some(reference) = schema["$ref"].as_str() {
+            let name = reference.strip_prefix("#/$defs/").unwrap();
+            return errors(root, &root["$defs"][name], value, at);
+        }
+        if let Some(any_of) = schema["anyOf"].as_array() {
+            if any_of
+                .iter()
+                .all(|s| !errors(root, s, value, at).is_empty())
+            {
+                return vec![format!("{}: matches none of anyOf", at)];
+            }
+        }
+        if let Some(allowed) = schema["enum"].as_array() {
+            if !allowed.contains(value) {
+                return vec![format!("{}: {} isn't allowed", at, value)];
+            }
+        }
+        let types: Vec<&str> = match &schema["type"] {
+            Value::String(kind) => vec![kind.as_str()],
+            Value::Array(kinds) => kinds.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        let type_of = match value {
+            Value::Null => "null",
+            Value::Bool(_) => "boolean",
+            Value::Number(n) if n.is_i64() || n.is_u64() => "integer",
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Array(_) => "array",
+            Value::Object(_) => "object",
+        };
+        if !types.is_empty() && !types.contains(&type_of) {
+            return vec![format!("{}: {} isn't {:?}", at, type_of, types)];
+        }
+        let mut found = Vec::new();
+        if let Value::Array(items) = value {
+            for (i, item) in items.iter().enumerate() {
+                found.extend(errors(
+                    root,
+                    &schema["items"],
+                    item,
+                    &format!("{}/{}", at, i),
+                ));
+            }
+        }
+        if let Value::Object(fields) = value {
+            for required in schema["required"].as_array().into_iter().flatten() {
+                if !fields.contains_key(required.as_str().unwrap()) {
+                    found.push(format!("{}: {} is missing", at, required));
+                }
+            }
+            for (name, field) in fields {
+                match schema["properties"].get(name) {
+                    Some(property) => {
+                        found.extend(errors(root, property, field, &format!("{}/{}", at, name)));
+                    }
+                    None if schema["additionalProperties"] == false => {
+                        found.push(format!("{}: {} isn't expected", at, name));
+                    }
+                    None => {}
+                }
+            }
+        }
+        found
+    }
+
+    /// Check a document against an output's entry; one that matches it
+    /// matches the whole schema too.
+    fn check(definition: &str, document: &Value) -> Vec<String> {
+        let root = schema();
+        let found = errors(&root, &root["$defs"][definition], document, "");
+        if found.is_empty() {
+            assert_eq!(errors(&root, &root, document, ""), Vec::<String>::new());
+        }
+        found
+    }
+
+    fn data() -> V3ResponseData {
+        V3ResponseData {
+            pilots: vec![
+                testing::flying("BAW1", "EGLL", "KJFK"),
+                Pilot {
+                    cid: 2,
+                    ..testing::pilot("N123AB")
+                },
+            ],
+            controllers: vec![
+                testing::controller("KJFK_TWR", 4),
+                Controller {
+                    cid: 3,
+                    ..testing::controller("KJFK_GND", 3)
+                },
+            ],
+            ..V3ResponseData::default()
+        }
+    }
+
+    /// What `list --format json` prints.
+    fn listed(controllers: bool) -> Value {
+        let table: Table = list::table(
+            list::Options {
+                controllers,
+                format: OutputFormat::Json,
+                query: None,
+                airlines: None,
+                sorts: Vec::new(),
+                zone: DisplayZone::Utc,
+            },
+            data(),
+        );
+        serde_json::from_str(&OutputFormat::Json.formatter().format(&table)).unwrap()
+    }
+
+    #[test]
+    fn versioned() {
+        assert_eq!(schema()["version"], SCHEMA_VERSION);
+        assert_eq!(
+            schema()["$schema"],
+            "https://json-schema.org/draft/2020-12/schema"
+        );
+    }
+
+    #[test]
+    fn list_outputs_validate() {
+        let pilots = listed(false);
+        assert_eq!(pilots.as_array().unwrap().len(), 2);
+        assert_eq!(check("list_pilots", &pilots), Vec::<String>::new());
+        let controllers = listed(true);
+        assert_eq!(controllers.as_array().unwrap().len(), 2);
+        assert_eq!(
+            check("list_controllers", &controllers),
+            Vec::<String>::new()
+        );
+        assert!(!check("list_controllers", &pilots).is_empty());
+    }
+
+    #[test]
+    fn atc_and_lookup_outputs_validate() {
+        let online = atc::check(&data(), "KJFK", &HashSet::new());
+        assert!(online.online);
+        let document = serde_json::to_value(&online).unwrap();
+        assert_eq!(check("atc", &document), Vec::<String>::new());
+        let offline = atc::check(&data(), "EGLL", &HashSet::new());
+        let document = serde_json::to_value(&offline).unwrap();
+        assert_eq!(check("atc", &document), Vec::<String>::new());
+
+        let targets = lookup::parse_targets("BAW1\nKJFK_TWR\n1000000\nNOBODY\n");
+        let results = lookup::resolve(&targets, &data());
+        assert!(results.iter().any(|result| !result.online));
+        let document = serde_json::to_value(&results).unwrap();
+        assert_eq!(check("lookup", &document), Vec::<String>::new());
+    }
+
+    /// One of each kind of change event.
+    fn change_events() -> Vec<ChangeEvent> {
+        let callsign = || "BAW1".to_owned();
+        vec![
+            ChangeEvent::PilotConnected {
+                cid: 1,
+                callsign: callsign(),
+            },
+            ChangeEvent::PilotDisconnected {
+                cid: 1,
+                callsign: callsign(),
+            },
+            ChangeEvent::FlightPlanFiled {
+                cid: 1,
+                callsign: callsign(),
+                departure: "EGLL".to_owned(),
+                arrival: "KJFK".to_owned(),
+            },
+            ChangeEvent::AltitudeBandChanged {
+                cid: 1,
+                callsign: callsign(),
+                from: 0,
+                to: 10_000,
+            },
+            ChangeEvent::ControllerOnline {
+                cid: 2,
+                callsign: "EGLL_TWR".to_owned(),
+            },
+            ChangeEvent::ControllerOffline {
+                cid: 2,
+                callsign: "EGLL_TWR".to_owned(),
+            },
+            ChangeEvent::AtisUpdated {
+                cid: 3,
+                callsign: "EGLL_ATIS".to_owned(),
+            },
+            ChangeEvent::CallsignChanged {
+                cid: 2,
+                from: "EGLL_GND".to_owned(),
+                to: "EGLL_TWR".to_owned(),
+            },
+        ]
+    }
+
+    #[test]
+    fn watch_and_serve_outputs_validate() {
+        let now = Utc::now();
+        assert_eq!(change_events().len(), CHANGE_EVENTS.len());
+        for event in change_events() {
+            let document = serde_json::to_value(SeenEvent::new(event, now)).unwrap();
+            assert_eq!(check("watch", &document), Vec::<String>::new());
+        }
+
+        let mut log = EventLog::new(20);
+        log.record(change_events(), now);
+        let (_, body) = serve::respond(&log, "/events");
+        let document: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(document.as_array().unwrap().len(), CHANGE_EVENTS.len());
+        assert_eq!(check("serve_events", &document), Vec::<String>::new());
+        // served events are numbered, which printed ones aren't
+        assert!(!check("watch", &document[0]).is_empty());
+    }
+
+    #[test]
+    fn drifted_fields_are_rejected() {
+        let mut renamed = listed(false);
+        let row = renamed[0].as_object_mut().unwrap();
+        let callsign = row.remove("Callsign").unwrap();
+        let _ = row.insert("callsign".to_owned(), callsign);
+        assert_eq!(
+            check("list_pilots", &renamed),
+            ["/0: \"Callsign\" is missing", "/0: callsign isn't expected"]
+        );
+
+        let mut retyped =
+            serde_json::to_value(atc::check(&data(), "KJFK", &HashSet::new())).unwrap();
+        retyped["online"] = json!("yes");
+        assert_eq!(
+            check("atc", &retyped),
+            ["/online: string isn't [\"boolean\"]"]
+        );
+
+        let mut unknown_kind =
+            serde_json::to_value(lookup::resolve(&lookup::parse_targets("BAW1"), &data())).unwrap();
+        unknown_kind[0]["kind"] = json!("observer");
+        assert_eq!(
+            check("lookup", &unknown_kind),
+            ["/0/kind: \"observer\" isn't allowed"]
+        );
+    }
+}
@@ -0,0 +1,229 @@
+//! `serve`: the change events `watch` prints, kept for tools to fetch over
+//! HTTP at `/events` instead of each polling VATSIM themselves.
+
+use crate::{
+    bounded::RingBuffer,
+    diff::ChangeEvent,
+    report::{DataSource, Reporter},
+    watch::{self, SeenEvent},
+};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use log::warn;
+use serde::Serialize;
+use serde_json::json;
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex, PoisonError},
+    thread,
+    time::Duration,
+};
+use tiny_http::{Header, Method, Response, Server};
+
+/// An event and the number it was logged under. The numbers only grow, so
+/// a client can ask for the events it hasn't seen.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct LoggedEvent {
+    id: u64,
+    #[serde(flatten)]
+    seen: SeenEvent,
+}
+
+/// The most recent events, up to a limit.
+#[derive(Debug)]
+pub struct EventLog {
+    events: RingBuffer<LoggedEvent>,
+    next_id: u64,
+}
+
+impl EventLog {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            events: RingBuffer::new(capacity),
+            next_id: 1,
+        }
+    }
+
+    /// Log a refresh's events, dropping the oldest past the limit.
+    pub fn record(&mut self, events: Vec<ChangeEvent>, time: DateTime<Utc>) {
+        for event in events {
+            self.events.push(LoggedEvent {
+                id: self.next_id,
+                seen: SeenEvent::new(event, time),
+            });
+            self.next_id += 1;
+        }
+    }
+
+    /// The events logged after the one with id `since`, or all that are
+    /// kept, oldest first.
+    fn after(&self, since: Option<u64>) -> Vec<&LoggedEvent> {
+        self.events
+            .iter()
+            .filter(|event| since.is_none_or(|since| event.id > since))
+            .collect()
+    }
+}
+
+/// The status and JSON body answering a GET of `url`.
+///
+/// `/events` gives the logged events as an array, and
+/// `/events?since=<id>` only those after that id.
+pub fn respond(log: &EventLog, url: &str) -> (u16, String) {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    if path != "/events" {
+        return (404, json!({ "error": "Events are at /events" }).to_string());
+    }
+    let since = match query
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("since="))
+        .map(str::parse)
+    {
+        None => None,
+        Some(Ok(since)) => Some(since),
+        Some(Err(_)) => {
+            return (
+                400,
+                json!({ "error": "since must be an event id" }).to_string(),
+            )
+        }
+    };
+    (
+        200,
+        serde_json::to_string(&log.after(since)).unwrap_or_default(),
+    )
+}
+
+/// Answer requests until the server is unblocked.
+fn answer(server: &Server, log: &Mutex<EventLog>) {
+    let content_type =
+        Header::from_bytes("Content-Type", "application/json").expect("valid header");
+    for request in server.incoming_requests() {
+        let (status, body) = if request.method() == &Method::Get {
+            let log = log.lock().unwrap_or_else(PoisonError::into_inner);
+            respond(&log, request.url())
+        } else {
+            (405, json!({ "error": "Only GET is served" }).to_string())
+        };
+        let response = Response::from_string(body)
+            .with_status_code(status)
+            .with_header(content_type.clone());
+        if let Err(e) = request.respond(response) {
+            warn!("Could not answer a request: {}", e);
+        }
+    }
+}
+
+/// Serve the last `keep` events at `address`, refreshing every
+/// `interval`, until interrupted.
+pub fn run(
+    address: SocketAddr,
+    keep: usize,
+    interval: Duration,
+    source: &DataSource,
+    reporter: &mut Reporter,
+) -> Result<()> {
+    let server =
+        Server::http(address).map_err(|e| anyhow!("Could not listen on {}: {}", address, e))?;
+    let log = Arc::new(Mutex::new(EventLog::new(keep)));
+    let shared = Arc::clone(&log);
+    let _ = thread::spawn(move || answer(&server, &shared));
+    reporter.note(format!(
+        "Serving change events at http://{}/events",
+        address
+    ));
+    watch::each_refresh(interval, source, reporter, |events, now, _| {
+        log.lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .record(events, now);
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use serde_json::Value;
+
+    fn connected(cid: i64) -> ChangeEvent {
+        ChangeEvent::PilotConnected {
+            cid,
+            callsign: format!("BAW{}", cid),
+        }
+    }
+
+    fn log() -> EventLog {
+        let mut log = EventLog::new(3);
+        let time = Utc.ymd(2022, 10, 16).and_hms(12, 5, 0);
+        log.record(vec![connected(1), connected(2)], time);
+        log.record(Vec::new(), time);
+        log.record(vec![connected(3), connected(4)], time);
+        log
+    }
+
+    fn ids(body: &str) -> Vec<u64> {
+        let events: Vec<Value> = serde_json::from_str(body).unwrap();
+        events.iter().map(|e| e["id"].as_u64().unwrap()).collect()
+    }
+
+    #[test]
+    fn keeps_the_latest_events() {
+        let (status, body) = respond(&log(), "/events");
+        assert_eq!(status, 200);
+        assert_eq!(ids(&body), [2, 3, 4]);
+        let events: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(
+            events[0],
+            json!({
+                "id": 2,
+                "time": "2022-10-16T12:05:00Z",
+                "event": "pilot_connected",
+                "cid": 2,
+                "callsign": "BAW2",
+            })
+        );
+    }
+
+    #[test]
+    fn gives_the_events_after_an_id() {
+        assert_eq!(ids(&respond(&log(), "/events?since=3").1), [4]);
+        assert!(ids(&respond(&log(), "/events?since=4").1).is_empty());
+        // dropped events are just gone
+        assert_eq!(ids(&respond(&log(), "/events?x=1&since=0").1), [2, 3, 4]);
+        assert_eq!(respond(&log(), "/events?since=two").0, 400);
+        assert_eq!(respond(&log(), "/").0, 404);
+    }
+
+    #[test]
+    fn answers_over_http() {
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let address = server.server_addr().to_ip().unwrap();
+        let log = Arc::new(Mutex::new(log()));
+        let shared = Arc::clone(&log);
+        let _ = thread::spawn(move || answer(&server, &shared));
+
+        let client = reqwest::blocking::Client::new();
+        let response = client
+            .get(format!("http://{}/events?since=2", address))
+            .send()
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.headers()["content-type"], "application/json");
+        assert_eq!(ids(&response.text().unwrap()), [3, 4]);
+
+        log.lock().unwrap().record(vec![connected(5)], Utc::now());
+        let body = client
+            .get(format!("http://{}/events?since=4", address))
+            .send()
+            .unwrap()
+            .text()
+            .unwrap();
+        assert_eq!(ids(&body), [5]);
+        let posted = client
+            .post(format!("http://{}/events", address))
+            .send()
+            .unwrap();
+        assert_eq!(posted.status(), 405);
+    }
+}
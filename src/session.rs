@@ -0,0 +1,191 @@
+//! Persisting interface state between runs.
+
+use crate::persist::{self, Loaded, Versioned};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+/// Version of the session file format.
+const SESSION_VERSION: u32 = 1;
+/// Name of the session file in the data directory.
+const SESSION_FILE_NAME: &str = "session.json";
+
+/// Interface state saved on exit.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Session {
+    pub version: u32,
+    pub tab_index: usize,
+    pub selected_callsign: Option<String>,
+    /// Recently-entered queries, most recent first.
+    #[serde(default)]
+    pub history: Vec<String>,
+    /// Column widths the user resized, by table.
+    #[serde(default)]
+    pub column_widths: BTreeMap<String, Vec<u16>>,
+    /// Whether the first-run tour was dismissed.
+    #[serde(default)]
+    pub onboarded: bool,
+    /// Kinds of position hidden from the controllers, like "OBS".
+    #[serde(default)]
+    pub hidden_facilities: Vec<String>,
+    /// How each "tab" was sorted, like "pilots:dep:desc".
+    #[serde(default)]
+    pub sorts: Vec<String>,
+    /// Queries filtering the pilots and controllers "tabs", in that order.
+    #[serde(default)]
+    pub filters: Vec<String>,
+    /// Whether favorites were pinned to the top of the tables.
+    #[serde(default)]
+    pub pin_favorites: bool,
+}
+
+impl Default for Session {
+    /// An empty session with the current file format version.
+    fn default() -> Self {
+        Self {
+            version: SESSION_VERSION,
+            tab_index: 0,
+            selected_callsign: None,
+            history: Vec::new(),
+            column_widths: BTreeMap::new(),
+            onboarded: false,
+            hidden_facilities: Vec::new(),
+            sorts: Vec::new(),
+            filters: Vec::new(),
+            pin_favorites: false,
+        }
+    }
+}
+
+/// Path to the session file, if the platform has a data directory.
+pub fn session_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("vatsim_online").join(SESSION_FILE_NAME))
+}
+
+impl Versioned for Session {
+    const VERSION: u32 = SESSION_VERSION;
+}
+
+/// Load the session saved by the last run.
+///
+/// Corrupt files are moved aside, and files from newer versions are
+/// ignored, with a warning.
+pub fn load() -> Loaded<Session> {
+    session_path().map_or_else(Loaded::default, |path| persist::load(&path))
+}
+
+/// Save the session for the next run.
+pub fn save(session: &Session) -> Result<()> {
+    let path = session_path().ok_or_else(|| anyhow!("No data directory on this platform"))?;
+    persist::save(&path, session)
+}
+
+/// Whether the first-run tour should show: nothing was saved yet, or the
+/// tour was never dismissed.
+pub fn needs_tour(session: Option<&Session>) -> bool {
+    !session.is_some_and(|session| session.onboarded)
+}
+
+/// Record that the first-run tour was dismissed, keeping the rest of the
+/// saved session.
+pub fn mark_onboarded() -> Result<()> {
+    let path = session_path().ok_or_else(|| anyhow!("No data directory on this platform"))?;
+    mark_onboarded_at(&path)
+}
+
+/// Record that the first-run tour was dismissed in the session file at a path.
+fn mark_onboarded_at(path: &Path) -> Result<()> {
+    let session = Session {
+        onboarded: true,
+        ..persist::load(path).value.unwrap_or_default()
+    };
+    persist::save(path, &session)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let session = Session {
+            tab_index: 1,
+            selected_callsign: Some("EGLL_TWR".to_owned()),
+            history: vec!["callsign=BAW*".to_owned()],
+            column_widths: BTreeMap::from([("pilots".to_owned(), vec![10, 8])]),
+            onboarded: true,
+            hidden_facilities: vec!["OBS".to_owned()],
+            sorts: vec!["pilots:dep:desc,callsign".to_owned()],
+            filters: vec!["departure=EGLL".to_owned(), String::new()],
+            pin_favorites: true,
+            ..Session::default()
+        };
+        let json = serde_json::to_string(&session).unwrap();
+        assert_eq!(persist::parse::<Session>(&json).unwrap(), session);
+    }
+
+    #[test]
+    fn fields_added_later_default() {
+        let session: Session =
+            persist::parse(r#"{"version": 1, "tab_index": 2, "selected_callsign": null}"#).unwrap();
+        assert_eq!(
+            session,
+            Session {
+                tab_index: 2,
+                ..Session::default()
+            }
+        );
+    }
+
+    #[test]
+    fn newer_and_corrupt_files_are_rejected() {
+        assert!(persist::parse::<Session>(r#"{"version": 2, "tab_index": 0}"#).is_err());
+        assert!(persist::parse::<Session>(r#"{"version": 1, "tab_index": "x"}"#).is_err());
+        assert!(persist::parse::<Session>("{").is_err());
+    }
+
+    #[test]
+    fn tour_shows_until_dismissed() {
+        assert!(needs_tour(None));
+        assert!(needs_tour(Some(&Session::default())));
+        let onboarded = Session {
+            onboarded: true,
+            ..Session::default()
+        };
+        assert!(!needs_tour(Some(&onboarded)));
+    }
+
+    #[test]
+    fn dismissing_the_tour_on_first_run_is_saved() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested").join(SESSION_FILE_NAME);
+        mark_onboarded_at(&path).unwrap();
+        let saved = persist::load::<Session>(&path).value;
+        assert!(!needs_tour(saved.as_ref()));
+        assert_eq!(saved.unwrap().history, Vec::<String>::new());
+    }
+
+    #[test]
+    fn dismissing_the_tour_keeps_the_session() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(SESSION_FILE_NAME);
+        let session = Session {
+            tab_index: 1,
+            history: vec!["callsign=BAW*".to_owned()],
+            ..Session::default()
+        };
+        persist::save(&path, &session).unwrap();
+        mark_onboarded_at(&path).unwrap();
+        mark_onboarded_at(&path).unwrap();
+        assert_eq!(
+            persist::load::<Session>(&path).value,
+            Some(Session {
+                onboarded: true,
+                ..session
+            })
+        );
+    }
+}
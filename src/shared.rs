@@ -0,0 +1,135 @@
+//! The data the instance holding the lock fetches, shared with instances
+//! started with `--attach` so that they don't poll the network too.
+
+use crate::{mirrors, models::V3ResponseData, persist};
+use anyhow::{Context, Result};
+use std::{
+    collections::hash_map::DefaultHasher,
+    fs,
+    hash::{Hash, Hasher},
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+/// Name of the shared data file, next to the lock file.
+const FILE_NAME: &str = "shared.json";
+
+/// Path to the shared data file, if the platform has a data directory.
+pub fn path() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("vatsim_online").join(FILE_NAME))
+}
+
+/// What the instance holding the lock does with the data, if anything.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Sharing {
+    /// Neither shares nor reads shared data.
+    Off,
+    /// Shares each new fetch at this path.
+    Publish(PathBuf),
+    /// Reads the data shared at this path instead of fetching it.
+    Attach(PathBuf),
+}
+
+/// Share new data at a path, replacing what was shared before.
+pub fn publish(path: &Path, data: &V3ResponseData) -> Result<()> {
+    let body = serde_json::to_vec(data)?;
+    persist::write_atomic(path, &body)
+        .with_context(|| format!("Could not share data at {}", path.display()))
+}
+
+/// Reads the shared data as it's replaced.
+#[derive(Debug)]
+pub struct Reader {
+    path: PathBuf,
+    /// Hash of the last body read, to tell when it's replaced.
+    last_hash: Option<u64>,
+}
+
+impl Reader {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            last_hash: None,
+        }
+    }
+
+    /// The shared data, or `None` if it hasn't been replaced since the
+    /// last read, or nothing has been shared yet.
+    pub fn read(&mut self) -> Result<Option<V3ResponseData>> {
+        let body = match fs::read(&self.path) {
+            Ok(body) => body,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Could not read {}", self.path.display()))
+            }
+        };
+        let mut hasher = DefaultHasher::new();
+        body.hash(&mut hasher);
+        let hash = hasher.finish();
+        if self.last_hash == Some(hash) {
+            return Ok(None);
+        }
+        let data = mirrors::parse_data(&body)
+            .with_context(|| format!("Could not parse {}", self.path.display()))?;
+        self.last_hash = Some(hash);
+        Ok(Some(data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn data(callsigns: &[&str]) -> V3ResponseData {
+        V3ResponseData {
+            pilots: callsigns.iter().map(|c| testing::pilot(c)).collect(),
+            ..V3ResponseData::default()
+        }
+    }
+
+    fn callsigns(data: &V3ResponseData) -> Vec<&str> {
+        data.pilots.iter().map(|p| p.callsign.as_str()).collect()
+    }
+
+    #[test]
+    fn reads_each_new_share_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(FILE_NAME);
+        let mut reader = Reader::new(path.clone());
+        // the other instance hasn't fetched anything yet
+        assert!(reader.read().unwrap().is_none());
+
+        publish(&path, &data(&["UAL1", "BAW1"])).unwrap();
+        let read = reader.read().unwrap().unwrap();
+        // sorted like fetched data
+        assert_eq!(callsigns(&read), ["BAW1", "UAL1"]);
+        assert!(reader.read().unwrap().is_none());
+
+        publish(&path, &data(&["DLH1"])).unwrap();
+        assert_eq!(callsigns(&reader.read().unwrap().unwrap()), ["DLH1"]);
+        // the same data shared again isn't new
+        publish(&path, &data(&["DLH1"])).unwrap();
+        assert!(reader.read().unwrap().is_none());
+    }
+
+    #[test]
+    fn broken_shares_are_errors_until_replaced() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(FILE_NAME);
+        let mut reader = Reader::new(path.clone());
+        fs::write(&path, r#"{"pilots": ["#).unwrap();
+        assert!(reader.read().is_err());
+        assert!(reader.read().is_err());
+        publish(&path, &data(&["BAW1"])).unwrap();
+        assert_eq!(callsigns(&reader.read().unwrap().unwrap()), ["BAW1"]);
+    }
+
+    #[test]
+    fn publishing_where_it_cannot_be_written_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("file");
+        fs::write(&file, "").unwrap();
+        assert!(publish(&file.join(FILE_NAME), &data(&[])).is_err());
+    }
+}
@@ -0,0 +1,276 @@
+//! A directory of counts recorded over time, for comparing with the past.
+//!
+//! Each file is named for when it was recorded, like
+//! "20261016T120000Z.txt", optionally after a prefix ending in '-' or '_'.
+//! It holds either `--summary` output or V3 data JSON. Other files are
+//! skipped, so a directory can be shared with anything else.
+
+use crate::{mirrors, models::V3ResponseData, persist, stats::NetworkStats};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, NaiveDateTime, TimeZone, Utc};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Format of the timestamp in file names.
+const TIMESTAMP_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// Numbers of clients connected at some time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Counts {
+    pub pilots: usize,
+    pub controllers: usize,
+}
+
+impl Counts {
+    /// Count the clients in the data.
+    pub fn from_data(data: &V3ResponseData) -> Self {
+        Self {
+            pilots: data.pilots.len(),
+            controllers: data.controllers.len(),
+        }
+    }
+}
+
+/// A recorded file and when it was recorded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub time: DateTime<Utc>,
+    pub path: PathBuf,
+}
+
+/// When a file was recorded, from its name, if it's named like a snapshot.
+pub fn parse_file_name(name: &str) -> Option<DateTime<Utc>> {
+    let stem = name.split_once('.').map_or(name, |(stem, _)| stem);
+    let timestamp = stem.rsplit(['-', '_']).next()?;
+    NaiveDateTime::parse_from_str(timestamp, TIMESTAMP_FORMAT)
+        .ok()
+        .map(|time| Utc.from_utc_datetime(&time))
+}
+
+/// The snapshots in a directory, oldest first; none if it doesn't exist.
+pub fn scan(dir: &Path) -> Result<Vec<Snapshot>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).with_context(|| format!("Could not read {}", dir.display())),
+    };
+    let mut snapshots: Vec<Snapshot> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let time = parse_file_name(entry.file_name().to_str()?)?;
+            Some(Snapshot {
+                time,
+                path: entry.path(),
+            })
+        })
+        .collect();
+    snapshots.sort_by_key(|snapshot| snapshot.time);
+    Ok(snapshots)
+}
+
+/// The snapshot closest to `target`, if any is within `tolerance` of it.
+///
+/// Ties go to the earlier snapshot.
+pub fn closest(
+    snapshots: &[Snapshot],
+    target: DateTime<Utc>,
+    tolerance: Duration,
+) -> Option<&Snapshot> {
+    let gap = |snapshot: &&Snapshot| (snapshot.time - target).num_seconds().abs();
+    snapshots
+        .iter()
+        .filter(|snapshot| gap(snapshot) <= tolerance.num_seconds())
+        .min_by_key(gap)
+}
+
+/// Read the counts from `--summary` output.
+fn parse_summary(text: &str) -> Option<Counts> {
+    let value = |key: &str| {
+        text.lines()
+            .filter_map(|line| line.split_once('='))
+            .find(|(name, _)| name.trim() == key)
+            .and_then(|(_, value)| value.trim().parse().ok())
+    };
+    Some(Counts {
+        pilots: value("pilots_total")?,
+        controllers: value("controllers_total")?,
+    })
+}
+
+/// Read the counts from a snapshot.
+pub fn load_counts(path: &Path) -> Result<Counts> {
+    let bytes = fs::read(path).with_context(|| format!("Could not read {}", path.display()))?;
+    if bytes.iter().find(|b| !b.is_ascii_whitespace()) == Some(&b'{') {
+        let data = mirrors::parse_data(&bytes)
+            .with_context(|| format!("Could not parse {}", path.display()))?;
+        return Ok(Counts::from_data(&data));
+    }
+    parse_summary(&String::from_utf8_lossy(&bytes))
+        .with_context(|| format!("No pilot and controller totals in {}", path.display()))
+}
+
+/// Record the network statistics as `--summary` output, named for `time`.
+pub fn save(dir: &Path, stats: &NetworkStats, time: DateTime<Utc>) -> Result<PathBuf> {
+    fs::create_dir_all(dir).with_context(|| format!("Could not create {}", dir.display()))?;
+    let path = dir.join(format!("{}.txt", time.format(TIMESTAMP_FORMAT)));
+    persist::write_atomic(&path, format!("{}\n", stats.to_lines()).as_bytes())?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{sanity::Limits, testing};
+
+    fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.ymd(2026, 10, 16).and_hms(hour, minute, 0)
+    }
+
+    fn snapshot(hour: u32, minute: u32) -> Snapshot {
+        Snapshot {
+            time: at(hour, minute),
+            path: PathBuf::from(format!("{}.txt", at(hour, minute).format(TIMESTAMP_FORMAT))),
+        }
+    }
+
+    #[test]
+    fn times_from_file_names() {
+        for name in [
+            "20261016T120500Z.txt",
+            "20261016T120500Z",
+            "vatsim-20261016T120500Z.json",
+            "cron_summary_20261016T120500Z.txt",
+            "20261016T120500Z.txt.bak",
+        ] {
+            assert_eq!(parse_file_name(name), Some(at(12, 5)), "{}", name);
+        }
+        for name in [
+            "notes.txt",
+            "20261016.txt",
+            "20261316T120500Z.txt",
+            "20261016T120500.txt",
+            "20261016T120500Z-copy.txt",
+            "",
+        ] {
+            assert_eq!(parse_file_name(name), None, "{}", name);
+        }
+    }
+
+    #[test]
+    fn scan_skips_other_files_and_sorts() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in [
+            "20261016T120000Z.txt",
+            "README.md",
+            "vatsim-20261015T120000Z.json",
+            "20261016T110000Z.txt",
+        ] {
+            fs::write(dir.path().join(name), "").unwrap();
+        }
+        let times: Vec<_> = scan(dir.path())
+            .unwrap()
+            .into_iter()
+            .map(|snapshot| snapshot.time)
+            .collect();
+        assert_eq!(times, [at(12, 0) - Duration::days(1), at(11, 0), at(12, 0)]);
+
+        assert!(scan(&dir.path().join("missing")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn closest_within_tolerance() {
+        let tolerance = Duration::minutes(15);
+        // hourly, with the 13:00 run missing
+        let snapshots = [snapshot(11, 0), snapshot(12, 0), snapshot(14, 0)];
+        let closest_time =
+            |hour, minute| closest(&snapshots, at(hour, minute), tolerance).map(|s| s.time);
+
+        assert_eq!(closest_time(12, 10), Some(at(12, 0)));
+        assert_eq!(closest_time(11, 50), Some(at(12, 0)));
+        assert_eq!(closest_time(12, 15), Some(at(12, 0)));
+        assert_eq!(closest_time(13, 50), Some(at(14, 0)));
+        assert_eq!(closest_time(12, 16), None);
+        assert_eq!(closest_time(13, 0), None);
+        assert_eq!(closest_time(9, 0), None);
+
+        // a tie goes to the earlier one
+        let close = [snapshot(12, 0), snapshot(12, 20)];
+        assert_eq!(
+            closest(&close, at(12, 10), tolerance).map(|s| s.time),
+            Some(at(12, 0))
+        );
+        assert_eq!(closest(&[], at(12, 0), tolerance), None);
+    }
+
+    #[test]
+    fn counts_from_summary_output_or_data() {
+        let dir = tempfile::tempdir().unwrap();
+        let summary = dir.path().join("summary.txt");
+        fs::write(
+            &summary,
+            "unique_users=900\npilots_total = 812\ncontrollers_total=95\n",
+        )
+        .unwrap();
+        assert_eq!(
+            load_counts(&summary).unwrap(),
+            Counts {
+                pilots: 812,
+                controllers: 95
+            }
+        );
+
+        let data = V3ResponseData {
+            pilots: vec![testing::pilot("BAW1"), testing::pilot("DAL2")],
+            controllers: vec![testing::controller("EGLL_TWR", 4)],
+            ..V3ResponseData::default()
+        };
+        let json = dir.path().join("data.json");
+        fs::write(
+            &json,
+            format!("\n  {}", serde_json::to_string(&data).unwrap()),
+        )
+        .unwrap();
+        assert_eq!(
+            load_counts(&json).unwrap(),
+            Counts {
+                pilots: 2,
+                controllers: 1
+            }
+        );
+
+        let partial = dir.path().join("partial.txt");
+        fs::write(&partial, "pilots_total=812\ncontrollers_total=many\n").unwrap();
+        assert!(load_counts(&partial).is_err());
+        assert!(load_counts(&dir.path().join("missing.txt")).is_err());
+    }
+
+    #[test]
+    fn saved_snapshots_are_found_again() {
+        let dir = tempfile::tempdir().unwrap();
+        let data = V3ResponseData {
+            pilots: vec![testing::pilot("BAW1")],
+            ..V3ResponseData::default()
+        };
+        let stats = NetworkStats::from_data(&data, &Limits::default());
+        let path = save(&dir.path().join("new"), &stats, at(12, 0)).unwrap();
+        assert_eq!(path.file_name().unwrap(), "20261016T120000Z.txt");
+
+        let snapshots = scan(&dir.path().join("new")).unwrap();
+        assert_eq!(
+            snapshots,
+            [Snapshot {
+                time: at(12, 0),
+                path
+            }]
+        );
+        assert_eq!(
+            load_counts(&snapshots[0].path).unwrap(),
+            Counts {
+                pilots: 1,
+                controllers: 0
+            }
+        );
+    }
+}
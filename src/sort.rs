@@ -0,0 +1,459 @@
+//! Sorting table rows.
+
+use crate::columns::{self, Column};
+use anyhow::{anyhow, bail, Result};
+use std::{cmp::Ordering, fmt};
+
+/// A value to sort table rows by.
+///
+/// Unlike `f64` and `partial_cmp`, this has a total ordering, so it can't
+/// panic on NaN or unparsable values. Numbers sort before text, and
+/// missing values always sort last.
+#[derive(Debug, Clone)]
+pub enum SortKey {
+    Number(f64),
+    Text(String),
+    Missing,
+}
+
+impl SortKey {
+    /// Key for a text value, compared case-insensitively.
+    ///
+    /// Empty strings are treated as missing.
+    #[must_use]
+    pub fn text(value: &str) -> Self {
+        if value.trim().is_empty() {
+            Self::Missing
+        } else {
+            Self::Text(value.to_lowercase())
+        }
+    }
+
+    /// Key for a numeric value stored as a string, like a frequency.
+    pub fn parsed(value: &str) -> Self {
+        value
+            .trim()
+            .parse::<f64>()
+            .map_or(Self::Missing, Self::from)
+    }
+
+    /// Relative position of the variants.
+    fn rank(&self) -> u8 {
+        match self {
+            Self::Number(_) => 0,
+            Self::Text(_) => 1,
+            Self::Missing => 2,
+        }
+    }
+}
+
+impl From<f64> for SortKey {
+    fn from(value: f64) -> Self {
+        if value.is_nan() {
+            Self::Missing
+        } else {
+            Self::Number(value)
+        }
+    }
+}
+
+impl From<i64> for SortKey {
+    #[allow(clippy::cast_precision_loss)]
+    fn from(value: i64) -> Self {
+        Self::Number(value as f64)
+    }
+}
+
+impl Ord for SortKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Self::Number(a), Self::Number(b)) => a.total_cmp(b),
+            (Self::Text(a), Self::Text(b)) => a.cmp(b),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+impl PartialOrd for SortKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for SortKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for SortKey {}
+
+/// Which column a table is sorted by.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SortSpec {
+    pub column: usize,
+    pub descending: bool,
+    /// Column to sort rows with the same value by, ascending.
+    pub then: Option<usize>,
+}
+
+/// Names of the "tabs" a default sort can be set for, by index.
+const TAB_NAMES: [&str; 3] = ["pilots", "controllers", "events"];
+
+/// Columns a "tab" can be sorted by.
+///
+/// Pilots can be sorted by distance, which only shows when filtered to a
+/// controller's range.
+fn tab_columns(tab_index: usize) -> &'static [Column] {
+    match tab_index {
+        0 => columns::PILOT_RANGE_COLUMNS,
+        1 => columns::CONTROLLER_COLUMNS,
+        _ => columns::EVENT_COLUMNS,
+    }
+}
+
+/// Identifier of a column, like "callsign".
+fn column_id(column: &Column) -> String {
+    column.header.to_lowercase()
+}
+
+/// A "tab"'s sort to start with and reset to, like "pilots:dep:desc"
+/// or "controllers:position,callsign".
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DefaultSort {
+    pub tab_index: usize,
+    pub spec: SortSpec,
+}
+
+impl DefaultSort {
+    /// A "tab"'s sort, if the columns it uses have names.
+    #[must_use]
+    pub fn new(tab_index: usize, spec: SortSpec) -> Option<Self> {
+        let named = spec.column.max(spec.then.unwrap_or(0)) < tab_columns(tab_index).len();
+        (tab_index < TAB_NAMES.len() && named).then_some(Self { tab_index, spec })
+    }
+
+    /// Parse a default sort, checking the columns exist in the "tab".
+    pub fn parse(value: &str) -> Result<Self> {
+        let (tab, rest) = value
+            .trim()
+            .split_once(':')
+            .ok_or_else(|| anyhow!("expected a tab and column, like \"pilots:callsign\""))?;
+        let tab_index = TAB_NAMES
+            .iter()
+            .position(|name| name.eq_ignore_ascii_case(tab.trim()))
+            .ok_or_else(|| {
+                anyhow!(
+                    "unknown tab \"{}\"; valid tabs are {}",
+                    tab.trim(),
+                    TAB_NAMES.join(", ")
+                )
+            })?;
+        let (primary, then) = match rest.split_once(',') {
+            Some((primary, then)) => (primary, Some(then)),
+            None => (rest, None),
+        };
+        let (column, descending) = match primary.split_once(':') {
+            Some((column, direction)) => match direction.trim().to_lowercase().as_str() {
+                "desc" => (column, true),
+                "asc" => (column, false),
+                _ => bail!(
+                    "unknown direction \"{}\"; valid directions are asc, desc",
+                    direction.trim()
+                ),
+            },
+            None => (primary, false),
+        };
+        let spec = SortSpec {
+            column: find_column(tab_index, column)?,
+            descending,
+            then: then.map(|then| find_column(tab_index, then)).transpose()?,
+        };
+        Ok(Self { tab_index, spec })
+    }
+}
+
+impl fmt::Display for DefaultSort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let columns = tab_columns(self.tab_index);
+        write!(
+            f,
+            "{}:{}",
+            TAB_NAMES[self.tab_index],
+            column_id(&columns[self.spec.column])
+        )?;
+        if self.spec.descending {
+            write!(f, ":desc")?;
+        }
+        if let Some(then) = self.spec.then {
+            write!(f, ",{}", column_id(&columns[then]))?;
+        }
+        Ok(())
+    }
+}
+
+/// Index of a column in a "tab" by its identifier.
+fn find_column(tab_index: usize, id: &str) -> Result<usize> {
+    let columns = tab_columns(tab_index);
+    let id = id.trim();
+    columns
+        .iter()
+        .position(|column| column_id(column).eq_ignore_ascii_case(id))
+        .ok_or_else(|| {
+            anyhow!(
+                "unknown {} column \"{}\"; valid columns are {}",
+                TAB_NAMES[tab_index],
+                id,
+                columns.iter().map(column_id).collect::<Vec<_>>().join(", ")
+            )
+        })
+}
+
+/// A sort key in a particular direction.
+#[derive(PartialEq, Eq)]
+struct Directed {
+    key: SortKey,
+    descending: bool,
+}
+
+impl Ord for Directed {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let ordering = self.key.cmp(&other.key);
+        if self.descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+}
+
+impl PartialOrd for Directed {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Stable sort of rows by a key, with a tie-breaker for equal keys.
+///
+/// Missing values stay at the end in either direction.
+pub fn sort_rows<T, K, B>(rows: &mut [T], descending: bool, key: K, tie_breaker: B)
+where
+    K: Fn(&T) -> SortKey,
+    B: Fn(&T) -> i64,
+{
+    sort_rows_then(rows, descending, key, |_| SortKey::Missing, tie_breaker);
+}
+
+/// Like [`sort_rows`], but rows with equal keys are sorted by a second
+/// key, ascending, before the tie-breaker.
+pub fn sort_rows_then<T, K, S, B>(rows: &mut [T], descending: bool, key: K, then: S, tie_breaker: B)
+where
+    K: Fn(&T) -> SortKey,
+    S: Fn(&T) -> SortKey,
+    B: Fn(&T) -> i64,
+{
+    rows.sort_by_cached_key(|row| {
+        let key = key(row);
+        let missing = matches!(key, SortKey::Missing);
+        (
+            missing,
+            Directed { key, descending },
+            then(row),
+            tie_breaker(row),
+        )
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    /// Values like the ones rows are sorted by: numbers including NaN and
+    /// infinities, numbers as text, blanks and duplicates.
+    fn value() -> impl Strategy<Value = String> {
+        prop_oneof![
+            any::<f64>().prop_map(|n| n.to_string()),
+            Just("NaN".to_owned()),
+            Just(String::new()),
+            Just("  ".to_owned()),
+            Just("inf".to_owned()),
+            "[A-Za-z]{1,3}",
+            (-5_i64..5).prop_map(|n| n.to_string()),
+        ]
+    }
+
+    fn keys(values: &[(String, bool)]) -> Vec<SortKey> {
+        values
+            .iter()
+            .map(|(v, numeric)| {
+                if *numeric {
+                    SortKey::parsed(v)
+                } else {
+                    SortKey::text(v)
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parsed_nan_is_missing() {
+        assert_eq!(SortKey::parsed("NaN"), SortKey::Missing);
+        assert_eq!(SortKey::parsed(" nan "), SortKey::Missing);
+        assert_eq!(SortKey::from(f64::NAN), SortKey::Missing);
+        assert_eq!(SortKey::parsed("1.5"), SortKey::Number(1.5));
+        assert_eq!(SortKey::parsed("x"), SortKey::Missing);
+    }
+
+    #[test]
+    fn numbers_before_text_before_missing() {
+        let mut rows = vec!["", "b", "2", "NaN", "a", "10"];
+        sort_rows(
+            &mut rows,
+            false,
+            |v| match SortKey::parsed(v) {
+                SortKey::Missing => SortKey::text(v),
+                key => key,
+            },
+            |_| 0,
+        );
+        assert_eq!(rows, ["2", "10", "a", "b", "NaN", ""]);
+    }
+
+    #[test]
+    fn missing_last_when_descending() {
+        let mut rows = vec!["1", "", "3", "NaN", "2"];
+        sort_rows(&mut rows, true, |v| SortKey::parsed(v), |_| 0);
+        assert_eq!(rows, ["3", "2", "1", "", "NaN"]);
+    }
+
+    #[test]
+    fn then_sorts_equal_keys() {
+        let mut rows = vec![("b", 1), ("a", 1), ("c", 0)];
+        sort_rows_then(
+            &mut rows,
+            false,
+            |(_, n)| SortKey::from(i64::from(*n)),
+            |(s, _)| SortKey::text(s),
+            |_| 0,
+        );
+        assert_eq!(rows, [("c", 0), ("a", 1), ("b", 1)]);
+    }
+
+    #[test]
+    fn new_needs_named_columns() {
+        let spec = |column, then| SortSpec {
+            column,
+            descending: false,
+            then,
+        };
+        assert!(DefaultSort::new(0, spec(0, Some(1))).is_some());
+        assert!(DefaultSort::new(0, spec(columns::PILOT_RANGE_COLUMNS.len(), None)).is_none());
+        assert!(DefaultSort::new(1, spec(0, Some(99))).is_none());
+        assert!(DefaultSort::new(3, spec(0, None)).is_none());
+    }
+
+    #[test]
+    fn default_sort_round_trip() {
+        for text in [
+            "pilots:callsign",
+            "pilots:dep:desc",
+            "controllers:position,callsign",
+        ] {
+            assert_eq!(DefaultSort::parse(text).unwrap().to_string(), text);
+        }
+        assert!(DefaultSort::parse("pilots").is_err());
+        assert!(DefaultSort::parse("planes:callsign").is_err());
+        assert!(DefaultSort::parse("pilots:callsign:up").is_err());
+        assert!(DefaultSort::parse("pilots:nothing").is_err());
+    }
+
+    #[test]
+    fn default_sort_errors_list_valid_names() {
+        let error = |text| DefaultSort::parse(text).unwrap_err().to_string();
+        assert_eq!(
+            error("planes:callsign"),
+            "unknown tab \"planes\"; valid tabs are pilots, controllers, events"
+        );
+        assert_eq!(
+            error("controllers:facility"),
+            "unknown controllers column \"facility\"; valid columns are callsign, position, \
+             name, frequency, rating, traffic, role, load"
+        );
+        assert!(error("pilots:callsign:up").contains("valid directions are asc, desc"));
+        assert!(error("pilots").contains("pilots:callsign"));
+    }
+
+    #[test]
+    fn default_sort_parse_is_lenient_about_case_and_space() {
+        let sort = DefaultSort::parse(" Pilots:DISTANCE:desc , Callsign ").unwrap();
+        assert_eq!(sort.tab_index, 0);
+        assert_eq!(
+            sort.spec,
+            SortSpec {
+                column: columns::PILOT_DISTANCE_COLUMN,
+                descending: true,
+                then: Some(0),
+            }
+        );
+        assert_eq!(sort.to_string(), "pilots:distance:desc,callsign");
+    }
+
+    proptest! {
+        #[test]
+        fn ordering_is_total(values in prop::collection::vec((value(), any::<bool>()), 0..40)) {
+            let keys = keys(&values);
+            for a in &keys {
+                prop_assert_eq!(a.cmp(a), Ordering::Equal);
+                for b in &keys {
+                    prop_assert_eq!(a.cmp(b), b.cmp(a).reverse());
+                    for c in &keys {
+                        if a <= b && b <= c {
+                            prop_assert!(a <= c);
+                        }
+                    }
+                }
+            }
+        }
+
+        #[test]
+        fn sorted_rows_are_ordered_and_kept(
+            values in prop::collection::vec((value(), any::<bool>()), 0..60),
+            descending in any::<bool>(),
+        ) {
+            let mut rows: Vec<(usize, SortKey)> = keys(&values).into_iter().enumerate().collect();
+            sort_rows(&mut rows, descending, |(_, key)| key.clone(), |_| 0);
+
+            let mut seen: Vec<usize> = rows.iter().map(|(i, _)| *i).collect();
+            seen.sort_unstable();
+            prop_assert_eq!(seen, (0..values.len()).collect::<Vec<_>>());
+
+            let missing = rows.iter().position(|(_, key)| matches!(key, SortKey::Missing));
+            if let Some(missing) = missing {
+                prop_assert!(rows[missing..].iter().all(|(_, key)| matches!(key, SortKey::Missing)));
+            }
+            for pair in rows.windows(2) {
+                let ((a_index, a), (b_index, b)) = (&pair[0], &pair[1]);
+                if matches!(b, SortKey::Missing) {
+                    continue;
+                }
+                let ordering = if descending { b.cmp(a) } else { a.cmp(b) };
+                prop_assert_ne!(ordering, Ordering::Greater);
+                // stable: equal keys keep the order they came in
+                if ordering == Ordering::Equal {
+                    prop_assert!(a_index < b_index);
+                }
+            }
+        }
+
+        #[test]
+        fn tie_breaker_orders_duplicates(
+            ids in prop::collection::vec(0_i64..1000, 0..40),
+            descending in any::<bool>(),
+        ) {
+            let mut rows: Vec<(&str, i64)> = ids.iter().map(|&id| ("KLAX", id)).collect();
+            sort_rows(&mut rows, descending, |(c, _)| SortKey::text(c), |(_, id)| *id);
+            prop_assert!(rows.windows(2).all(|pair| pair[0].1 <= pair[1].1));
+        }
+    }
+}
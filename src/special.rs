@@ -0,0 +1,237 @@
+//! A guess at which pilots are flying military or other special operations.
+//!
+//! VATSIM has no flag for these, so they're picked out by callsign and
+//! remarks. The tables below are the whole of what's recognised; add to
+//! them to recognise more.
+
+use crate::models::Pilot;
+
+/// Callsign prefixes of military and state operators, and the badge shown
+/// for each. The prefix has to be followed by a flight number.
+static OPERATORS: &[(&str, &str)] = &[
+    // US Air Force Air Mobility Command, filed or spoken
+    ("RCH", "USAF"),
+    ("REACH", "USAF"),
+    // US Air Force special air missions
+    ("SAM", "USAF"),
+    // US Army priority air transport
+    ("PAT", "ARMY"),
+    // US Navy
+    ("CNV", "NAVY"),
+    ("NAVY", "NAVY"),
+    // Royal Air Force, filed or spoken
+    ("RRR", "RAF"),
+    ("ASCOT", "RAF"),
+    ("RFR", "RAF"),
+    ("CFC", "RCAF"),
+    ("GAF", "GAF"),
+    ("FAF", "FAF"),
+    // French Air Force transport
+    ("CTM", "FAF"),
+    ("IAM", "IAM"),
+    ("BAF", "BAF"),
+    ("NAF", "RNLAF"),
+    ("NATO", "NATO"),
+];
+
+/// Airline radio callsigns that some pilots log on with, which would
+/// otherwise look tactical, like "SPEEDBIRD12".
+static AIRLINE_TELEPHONY: &[&str] = &[
+    "ALASKA",
+    "CACTUS",
+    "CATHAY",
+    "CLIPPER",
+    "CONDOR",
+    "DELTA",
+    "DYNASTY",
+    "EASY",
+    "ENVOY",
+    "ETIHAD",
+    "FINNAIR",
+    "JETBLUE",
+    "JETSTAR",
+    "QANTAS",
+    "QATARI",
+    "ROUGE",
+    "RYANAIR",
+    "SHAMROCK",
+    "SKYWEST",
+    "SPEEDBIRD",
+    "SPRINGBOK",
+    "SWISS",
+    "UNITED",
+    "VIRGIN",
+    "WESTJET",
+];
+
+/// Words in flight plan remarks that mark a military flight.
+static REMARK_WORDS: &[&str] = &["MARSA", "MILITARY"];
+
+/// Badge for callsigns and remarks that look military without naming an
+/// operator.
+const MILITARY_BADGE: &str = "MIL";
+
+/// Whether the rest of a callsign after a prefix is a flight number: a
+/// digit first, then digits and letters.
+fn is_flight_number(rest: &str) -> bool {
+    rest.starts_with(|c: char| c.is_ascii_digit())
+        && rest.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+/// Whether a callsign is shaped like a tactical one, like "VIPER11" or
+/// "DARK01A": four to seven letters, one to three digits, and at most one
+/// more letter.
+///
+/// Airline callsigns have three letters before the number, so they don't
+/// match.
+fn is_tactical(callsign: &str) -> bool {
+    let letters = callsign.bytes().take_while(u8::is_ascii_alphabetic).count();
+    let rest = &callsign[letters..];
+    let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+    let suffix = &rest[digits..];
+    (4..=7).contains(&letters)
+        && (1..=3).contains(&digits)
+        && suffix.len() <= 1
+        && suffix.bytes().all(|b| b.is_ascii_alphabetic())
+        && !AIRLINE_TELEPHONY.contains(&&callsign[..letters])
+}
+
+/// Badge for a callsign that looks like a special operation, if it does.
+pub fn classify_callsign(callsign: &str) -> Option<&'static str> {
+    let callsign = callsign.trim().to_uppercase();
+    OPERATORS
+        .iter()
+        .find(|(prefix, _)| callsign.strip_prefix(prefix).is_some_and(is_flight_number))
+        .map(|&(_, badge)| badge)
+        .or_else(|| is_tactical(&callsign).then_some(MILITARY_BADGE))
+}
+
+/// Badge for flight plan remarks that mark a military flight, if they do.
+///
+/// Only whole words count, so "PARAMILITARY" doesn't.
+pub fn classify_remarks(remarks: &str) -> Option<&'static str> {
+    remarks
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .any(|word| {
+            REMARK_WORDS
+                .iter()
+                .any(|marker| word.eq_ignore_ascii_case(marker))
+        })
+        .then_some(MILITARY_BADGE)
+}
+
+/// Badge for a pilot who looks to be flying a special operation, from
+/// their callsign or else their remarks.
+pub fn pilot(pilot: &Pilot) -> Option<&'static str> {
+    classify_callsign(&pilot.callsign).or_else(|| {
+        pilot
+            .flight_plan
+            .as_ref()
+            .and_then(|plan| classify_remarks(&plan.remarks))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn military_operators() {
+        for (callsign, badge) in [
+            ("RCH123", "USAF"),
+            ("REACH401", "USAF"),
+            ("SAM28000", "USAF"),
+            ("PAT627", "ARMY"),
+            ("CNV4521", "NAVY"),
+            ("RRR2910", "RAF"),
+            ("ASCOT4421", "RAF"),
+            ("rfr7002", "RAF"),
+            ("CFC4002", "RCAF"),
+            ("GAF683", "GAF"),
+            ("CTM1234", "FAF"),
+            ("IAM3121", "IAM"),
+            ("BAF01", "BAF"),
+            ("NAF12A", "RNLAF"),
+            ("NATO01", "NATO"),
+        ] {
+            assert_eq!(classify_callsign(callsign), Some(badge), "{}", callsign);
+        }
+    }
+
+    #[test]
+    fn tactical_callsigns() {
+        for callsign in [
+            "VIPER11",
+            "DARK01A",
+            "HAWK21",
+            "COBRA1",
+            " BONE123 ",
+            "TOPGUN7",
+        ] {
+            assert_eq!(classify_callsign(callsign), Some("MIL"), "{:?}", callsign);
+        }
+    }
+
+    #[test]
+    fn airlines_and_general_aviation_do_not_match() {
+        for callsign in [
+            "BAW123",
+            "DAL1",
+            "UAL456",
+            "SWA2024",
+            "AAL100",
+            "EZY12AB",
+            "RYR1AB",
+            "DLH4CK",
+            "SAS9",
+            "KLM1234",
+            "RCH",
+            "RCHX",
+            "NATOX",
+            "SPEEDBIRD12",
+            "SWISS12",
+            "DELTA45",
+            "UNITED1",
+            "JETBLUE6",
+            "EASY12",
+            "N123AB",
+            "GABCD",
+            "DEABC",
+            "C-FABC",
+            "VIPER",
+            "VIPER1234",
+            "HAWK21AB",
+            "",
+            "11VIPER",
+        ] {
+            assert_eq!(classify_callsign(callsign), None, "{:?}", callsign);
+        }
+    }
+
+    #[test]
+    fn remarks_with_military_words() {
+        assert_eq!(classify_remarks("MARSA WITH RCH12"), Some("MIL"));
+        assert_eq!(classify_remarks("/v/ Military exercise"), Some("MIL"));
+        assert_eq!(classify_remarks("RMK/MARSA"), Some("MIL"));
+        assert_eq!(classify_remarks("/V/ PARAMILITARY TRAINING"), None);
+        assert_eq!(classify_remarks("MARSALA WINE RUN"), None);
+        assert_eq!(classify_remarks("/V/ SIMBRIEF"), None);
+        assert_eq!(classify_remarks(""), None);
+    }
+
+    #[test]
+    fn pilots_by_callsign_then_remarks() {
+        assert_eq!(pilot(&testing::pilot("RCH123")), Some("USAF"));
+        assert_eq!(pilot(&testing::pilot("BAW123")), None);
+
+        let mut marsa = testing::flying("BAW123", "EGLL", "KJFK");
+        if let Some(plan) = &mut marsa.flight_plan {
+            plan.remarks = "/V/ MARSA".to_owned();
+        }
+        assert_eq!(pilot(&marsa), Some("MIL"));
+        // the callsign's badge wins
+        marsa.callsign = "ASCOT1".to_owned();
+        assert_eq!(pilot(&marsa), Some("RAF"));
+    }
+}
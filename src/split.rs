@@ -0,0 +1,380 @@
+//! Splitting the flights at one airport into inbound and outbound panes.
+
+use crate::{
+    action::Action,
+    flight::{self, Phase},
+    models::{FlightPlan, Pilot},
+    rows,
+};
+use chrono::{DateTime, Utc};
+
+/// One side of the split.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Pane {
+    /// Flights filed to arrive at the airport.
+    #[default]
+    Inbound,
+    /// Flights filed to depart from the airport.
+    Outbound,
+}
+
+impl Pane {
+    /// Position of the pane, left to right.
+    pub fn index(self) -> usize {
+        match self {
+            Pane::Inbound => 0,
+            Pane::Outbound => 1,
+        }
+    }
+
+    /// The pane on the other side.
+    pub fn other(self) -> Self {
+        match self {
+            Pane::Inbound => Pane::Outbound,
+            Pane::Outbound => Pane::Inbound,
+        }
+    }
+
+    /// Name of the pane in its border.
+    pub fn title(self) -> &'static str {
+        match self {
+            Pane::Inbound => "Inbound",
+            Pane::Outbound => "Outbound",
+        }
+    }
+}
+
+/// Flights at an airport, as indexes into the pilots, in the order each
+/// pane lists them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Split {
+    pub inbound: Vec<usize>,
+    pub outbound: Vec<usize>,
+}
+
+impl Split {
+    /// The rows of a pane.
+    pub fn rows(&self, pane: Pane) -> &[usize] {
+        match pane {
+            Pane::Inbound => &self.inbound,
+            Pane::Outbound => &self.outbound,
+        }
+    }
+}
+
+/// Both panes of the split, ready to draw.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SplitPanes {
+    pub airport: String,
+    /// Cells of each pane's rows, inbound first.
+    pub rows: [Vec<Vec<String>>; 2],
+    /// Selected row in each pane.
+    pub selected: [usize; 2],
+    /// The pane keys act on.
+    pub focus: Pane,
+}
+
+/// The pilots at one airport split into panes, and the selection in each.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SplitView {
+    airport: String,
+    focus: Pane,
+    panes: Split,
+    /// CID of the pilot selected in each pane. It's kept by CID as the
+    /// pilots are reordered by every sort and refresh.
+    selected: [Option<i64>; 2],
+}
+
+impl SplitView {
+    pub fn new(airport: String) -> Self {
+        Self {
+            airport,
+            ..Self::default()
+        }
+    }
+
+    pub fn airport(&self) -> &str {
+        &self.airport
+    }
+
+    /// Replace the panes, after a refresh or a change to the shown pilots.
+    pub fn set_panes(&mut self, panes: Split) {
+        self.panes = panes;
+    }
+
+    /// Rows of the pane keys act on.
+    pub fn focused_rows(&self) -> &[usize] {
+        self.panes.rows(self.focus)
+    }
+
+    /// Row of the selected pilot in a pane, or the first row if they're
+    /// not listed.
+    fn selected_row(&self, pane: Pane, pilots: &[Pilot]) -> usize {
+        self.selected[pane.index()]
+            .and_then(|cid| {
+                self.panes
+                    .rows(pane)
+                    .iter()
+                    .position(|&i| pilots.get(i).is_some_and(|p| p.cid == cid))
+            })
+            .unwrap_or(0)
+    }
+
+    /// Index into the pilots of the one selected in the focused pane.
+    pub fn selected(&self, pilots: &[Pilot]) -> Option<usize> {
+        let row = self.selected_row(self.focus, pilots);
+        self.focused_rows().get(row).copied()
+    }
+
+    /// Apply an action, returning whether the split took it: switching
+    /// "tabs" switches panes, and moving the selection moves it in the
+    /// focused pane.
+    pub fn update(&mut self, action: Action, pilots: &[Pilot]) -> bool {
+        match action {
+            Action::ToggleTab => self.focus = self.focus.other(),
+            Action::MoveSelection(rows) => self.move_selection(rows, pilots),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Move the selection in the focused pane by a number of rows.
+    pub fn move_selection(&mut self, rows: i32, pilots: &[Pilot]) {
+        let selected = self.selected_row(self.focus, pilots);
+        let pane = self.panes.rows(self.focus);
+        if let Some(next) = rows::moved(pane.len(), selected, rows) {
+            self.selected[self.focus.index()] =
+                pane.get(next).and_then(|&i| pilots.get(i)).map(|p| p.cid);
+        }
+    }
+
+    /// Both panes, with the cells `cells` gives each pilot.
+    pub fn view(
+        &self,
+        pilots: &[Pilot],
+        cells: impl Fn(&Pilot, Pane) -> Vec<String>,
+    ) -> SplitPanes {
+        let panes = [Pane::Inbound, Pane::Outbound];
+        SplitPanes {
+            airport: self.airport.clone(),
+            rows: panes.map(|pane| {
+                self.panes
+                    .rows(pane)
+                    .iter()
+                    .map(|&i| cells(&pilots[i], pane))
+                    .collect()
+            }),
+            selected: panes.map(|pane| self.selected_row(pane, pilots)),
+            focus: self.focus,
+        }
+    }
+}
+
+/// Whether a flight plan names an airport, by one of its fields.
+fn files(pilot: &Pilot, airport: &str, field: fn(&FlightPlan) -> &str) -> bool {
+    pilot
+        .flight_plan
+        .as_ref()
+        .is_some_and(|plan| field(plan).trim().eq_ignore_ascii_case(airport))
+}
+
+/// Whether a flight is from and to the same airport, like pattern work.
+pub fn is_pattern(pilot: &Pilot, airport: &str) -> bool {
+    files(pilot, airport, |plan| &plan.departure) && files(pilot, airport, |plan| &plan.arrival)
+}
+
+/// Filed departure time of a pilot, on the day nearest `now`.
+pub fn filed_departure(pilot: &Pilot, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let plan = pilot.flight_plan.as_ref()?;
+    flight::parse_deptime(&plan.deptime).map(|time| flight::departure_time(time, now))
+}
+
+/// Outbound flights still to leave come first, then by how far along
+/// they are.
+fn phase_rank(phase: Phase) -> usize {
+    Phase::FILTERABLE
+        .iter()
+        .position(|&p| p == phase)
+        .unwrap_or(Phase::FILTERABLE.len())
+}
+
+/// Sort `None` after any time.
+fn time_key(time: Option<DateTime<Utc>>) -> (bool, Option<DateTime<Utc>>) {
+    (time.is_none(), time)
+}
+
+/// Split the shown pilots into those filed to arrive at the airport,
+/// soonest ETA first, and those filed to depart from it, by phase and
+/// then filed departure time. Flights from and to the airport are in
+/// both.
+pub fn partition(
+    pilots: &[Pilot],
+    rows: &[usize],
+    airport: &str,
+    eta: impl Fn(&Pilot) -> Option<DateTime<Utc>>,
+    phase: impl Fn(&Pilot) -> Phase,
+    now: DateTime<Utc>,
+) -> Split {
+    let shown = || rows.iter().filter_map(|&i| Some((i, pilots.get(i)?)));
+    let mut inbound: Vec<(usize, &Pilot)> = shown()
+        .filter(|(_, p)| files(p, airport, |plan| &plan.arrival))
+        .collect();
+    inbound.sort_by_cached_key(|(_, p)| (time_key(eta(p)), p.callsign.clone()));
+    let mut outbound: Vec<(usize, &Pilot)> = shown()
+        .filter(|(_, p)| files(p, airport, |plan| &plan.departure))
+        .collect();
+    outbound.sort_by_cached_key(|(_, p)| {
+        (
+            phase_rank(phase(p)),
+            time_key(filed_departure(p, now)),
+            p.callsign.clone(),
+        )
+    });
+    Split {
+        inbound: inbound.into_iter().map(|(i, _)| i).collect(),
+        outbound: outbound.into_iter().map(|(i, _)| i).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use chrono::TimeZone;
+
+    fn now() -> DateTime<Utc> {
+        Utc.ymd(2022, 8, 1).and_hms(12, 0, 0)
+    }
+
+    /// A flight filed to depart at a time, with an ETA and a phase for
+    /// the partition to use.
+    fn flight(callsign: &str, dep: &str, arr: &str, deptime: &str) -> Pilot {
+        let mut pilot = testing::flying(callsign, dep, arr);
+        if let Some(plan) = &mut pilot.flight_plan {
+            plan.deptime = deptime.to_owned();
+        }
+        pilot
+    }
+
+    /// ETAs by callsign, minutes from now.
+    fn eta(pilot: &Pilot) -> Option<DateTime<Utc>> {
+        let minutes = match pilot.callsign.as_str() {
+            "INB1" => 30,
+            "INB2" => 10,
+            "PAT1" => 5,
+            _ => return None,
+        };
+        Some(now() + chrono::Duration::minutes(minutes))
+    }
+
+    /// Phases by callsign.
+    fn phase(pilot: &Pilot) -> Phase {
+        match pilot.callsign.as_str() {
+            "OUT1" => Phase::Climb,
+            "OUT2" | "OUT3" | "PAT1" => Phase::Ground,
+            _ => Phase::Unknown,
+        }
+    }
+
+    fn pilots() -> Vec<Pilot> {
+        vec![
+            flight("INB1", "KJFK", "EGLL", "0800"),
+            flight("OUT1", "EGLL", "EDDF", "1130"),
+            flight("INB2", "LFPG", "egll", "1100"),
+            flight("OUT2", "EGLL", "LFPG", "1330"),
+            flight("OUT3", "EGLL", "KJFK", "1230"),
+            flight("PAT1", "EGLL", "EGLL", "1200"),
+            flight("ELSE", "EDDF", "LFPG", "1200"),
+            flight("INB3", "EHAM", "EGLL", "1000"),
+            testing::pilot("NOPLAN"),
+        ]
+    }
+
+    /// Callsigns of a pane's rows.
+    fn callsigns(pilots: &[Pilot], rows: &[usize]) -> Vec<String> {
+        rows.iter().map(|&i| pilots[i].callsign.clone()).collect()
+    }
+
+    #[test]
+    fn inbound_by_eta_and_outbound_by_phase_then_departure() {
+        let pilots = pilots();
+        let rows: Vec<usize> = (0..pilots.len()).collect();
+        let split = partition(&pilots, &rows, "EGLL", eta, phase, now());
+        // soonest first, and no ETA last
+        assert_eq!(
+            callsigns(&pilots, &split.inbound),
+            ["PAT1", "INB2", "INB1", "INB3"]
+        );
+        // on the ground first, then by filed departure
+        assert_eq!(
+            callsigns(&pilots, &split.outbound),
+            ["PAT1", "OUT3", "OUT2", "OUT1"]
+        );
+        assert_eq!(split.rows(Pane::Inbound), split.inbound.as_slice());
+        assert_eq!(split.rows(Pane::Outbound), split.outbound.as_slice());
+    }
+
+    #[test]
+    fn only_shown_pilots_are_split() {
+        let pilots = pilots();
+        // filtered out, and a row that's gone since
+        let split = partition(&pilots, &[0, 1, 99], "egll", eta, phase, now());
+        assert_eq!(callsigns(&pilots, &split.inbound), ["INB1"]);
+        assert_eq!(callsigns(&pilots, &split.outbound), ["OUT1"]);
+        assert_eq!(
+            partition(&pilots, &[], "EGLL", eta, phase, now()),
+            Split::default()
+        );
+    }
+
+    #[test]
+    fn pattern_flights_are_from_and_to_the_airport() {
+        let pilots = pilots();
+        assert!(is_pattern(&pilots[5], "EGLL"));
+        assert!(is_pattern(&pilots[5], "egll"));
+        assert!(!is_pattern(&pilots[0], "EGLL"));
+        assert!(!is_pattern(&pilots[8], "EGLL"));
+    }
+
+    #[test]
+    fn filed_departures_on_the_nearest_day() {
+        let pilots = pilots();
+        let at = |h, m| Utc.ymd(2022, 8, 1).and_hms(h, m, 0);
+        assert_eq!(filed_departure(&pilots[3], now()), Some(at(13, 30)));
+        assert_eq!(filed_departure(&pilots[1], now()), Some(at(11, 30)));
+        assert_eq!(filed_departure(&pilots[8], now()), None);
+    }
+
+    #[test]
+    fn panes_switch_sides() {
+        assert_eq!(Pane::default(), Pane::Inbound);
+        assert_eq!(Pane::Inbound.other(), Pane::Outbound);
+        assert_eq!(Pane::Outbound.other().index(), 0);
+        assert_eq!(Pane::Outbound.title(), "Outbound");
+    }
+
+    #[test]
+    fn view_keeps_each_panes_selection_by_cid() {
+        let mut pilots = pilots();
+        for (cid, pilot) in (1..).zip(pilots.iter_mut()) {
+            pilot.cid = cid;
+        }
+        let rows: Vec<usize> = (0..pilots.len()).collect();
+        let mut view = SplitView::new("EGLL".to_owned());
+        view.set_panes(partition(&pilots, &rows, "EGLL", eta, phase, now()));
+        assert!(view.update(Action::MoveSelection(1), &pilots));
+        assert!(view.update(Action::ToggleTab, &pilots));
+        assert!(view.update(Action::MoveSelection(-1), &pilots));
+        assert!(!view.update(Action::CycleSort, &pilots));
+        assert_eq!(pilots[view.selected(&pilots).unwrap()].callsign, "OUT1");
+
+        // the selected pilots stay selected when the rows move
+        pilots.reverse();
+        let rows: Vec<usize> = (0..pilots.len()).collect();
+        view.set_panes(partition(&pilots, &rows, "EGLL", eta, phase, now()));
+        let panes = view.view(&pilots, |pilot, _| vec![pilot.callsign.clone()]);
+        assert_eq!(panes.selected, [1, 3]);
+        assert_eq!(panes.rows[0][1], ["INB2"]);
+        assert_eq!(panes.focus, Pane::Outbound);
+    }
+}
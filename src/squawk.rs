@@ -0,0 +1,160 @@
+//! Finding pilots squawking a different code from the one they were
+//! assigned, in a controller's airspace.
+
+use crate::{
+    models::{Controller, Pilot},
+    nearby::NearbyController,
+    regions,
+};
+
+/// Code squawked entering an area without an assigned code, everywhere.
+const NO_ASSIGNMENT_CODE: &str = "2000";
+/// Regions where VFR flights squawk 1200; elsewhere they squawk 7000.
+const VFR_1200_REGIONS: [&str; 6] = [
+    "USA",
+    "Canada",
+    "Mexico",
+    "Puerto Rico",
+    "Australia",
+    "New Zealand",
+];
+
+/// Codes squawked without an assignment in a region, which aren't taken
+/// for a mismatch. Where the region isn't known, any of them are allowed.
+pub fn standby_codes(region: &str) -> &'static [&'static str] {
+    if region == regions::OTHER {
+        &["1200", NO_ASSIGNMENT_CODE, "7000"]
+    } else if VFR_1200_REGIONS.contains(&region) {
+        &["1200", NO_ASSIGNMENT_CODE]
+    } else {
+        &[NO_ASSIGNMENT_CODE, "7000"]
+    }
+}
+
+/// Whether a squawk differs from the assigned code in a way worth a
+/// controller's attention.
+///
+/// Pilots without an assignment, which the data gives as empty or 0000,
+/// and pilots squawking one of the region's standby codes aren't.
+pub fn is_mismatch(squawk: &str, assigned: &str, region: &str) -> bool {
+    let (squawk, assigned) = (squawk.trim(), assigned.trim());
+    if assigned.is_empty() || assigned.chars().all(|c| c == '0') {
+        return false;
+    }
+    squawk != assigned && !standby_codes(region).contains(&squawk)
+}
+
+/// A pilot's assigned code, if their flight plan has one.
+pub fn assigned(pilot: &Pilot) -> Option<&str> {
+    pilot
+        .flight_plan
+        .as_ref()
+        .map(|plan| plan.assigned_transponder.trim())
+        .filter(|code| !code.is_empty())
+}
+
+/// Pilots in a controller's plausible traffic squawking other than their
+/// assigned code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SquawkFilter {
+    pub callsign: String,
+    /// Region of the controller's airspace, which decides the standby codes.
+    pub region: &'static str,
+}
+
+impl SquawkFilter {
+    /// Filter to a controller's plausible traffic.
+    pub fn around(controller: &Controller) -> Self {
+        Self {
+            callsign: controller.callsign.clone(),
+            region: regions::of_controller(controller),
+        }
+    }
+
+    /// Whether a pilot, with the controllers covering them, is in the
+    /// controller's traffic and squawking the wrong code.
+    pub fn matches(&self, pilot: &Pilot, covering: &[NearbyController]) -> bool {
+        covering.iter().any(|c| c.callsign == self.callsign)
+            && assigned(pilot)
+                .is_some_and(|code| is_mismatch(&pilot.transponder, code, self.region))
+    }
+
+    /// Description for the table title.
+    pub fn label(&self) -> String {
+        format!("Squawk mismatches: {}", self.callsign)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{nearby::FacilityClass, testing};
+
+    fn squawking(squawk: &str, assigned: &str) -> Pilot {
+        let mut pilot = testing::flying("BAW123", "EGLL", "KJFK");
+        pilot.transponder = squawk.to_owned();
+        pilot.flight_plan.as_mut().unwrap().assigned_transponder = assigned.to_owned();
+        pilot
+    }
+
+    fn covered_by(callsign: &str) -> Vec<NearbyController> {
+        vec![NearbyController {
+            class: FacilityClass::Approach,
+            callsign: callsign.to_owned(),
+            distance_nm: 10.0,
+        }]
+    }
+
+    #[test]
+    fn standby_codes_by_region() {
+        assert_eq!(standby_codes("USA"), ["1200", "2000"]);
+        assert_eq!(standby_codes("Australia"), ["1200", "2000"]);
+        assert_eq!(standby_codes("UK"), ["2000", "7000"]);
+        assert_eq!(standby_codes(regions::OTHER), ["1200", "2000", "7000"]);
+    }
+
+    #[test]
+    fn mismatches() {
+        assert!(is_mismatch("1234", "4321", "USA"));
+        assert!(!is_mismatch("4321", "4321", "USA"));
+        assert!(!is_mismatch(" 4321", "4321 ", "USA"));
+        // no assignment
+        assert!(!is_mismatch("1234", "", "USA"));
+        assert!(!is_mismatch("1234", "  ", "USA"));
+        assert!(!is_mismatch("1234", "0000", "USA"));
+        // standby codes, by region
+        assert!(!is_mismatch("1200", "4321", "USA"));
+        assert!(is_mismatch("7000", "4321", "USA"));
+        assert!(!is_mismatch("7000", "4321", "UK"));
+        assert!(is_mismatch("1200", "4321", "UK"));
+        assert!(!is_mismatch("2000", "4321", "UK"));
+        assert!(!is_mismatch("1200", "4321", regions::OTHER));
+        assert!(!is_mismatch("7000", "4321", regions::OTHER));
+        // an emergency code is still a mismatch
+        assert!(is_mismatch("7700", "4321", "USA"));
+    }
+
+    #[test]
+    fn assigned_codes() {
+        assert_eq!(assigned(&squawking("1234", " 4321 ")), Some("4321"));
+        assert_eq!(assigned(&squawking("1234", "")), None);
+        let mut pilot = squawking("1234", "4321");
+        pilot.flight_plan = None;
+        assert_eq!(assigned(&pilot), None);
+    }
+
+    #[test]
+    fn filter_needs_coverage_and_a_mismatch() {
+        let filter = SquawkFilter::around(&testing::controller("EGLL_APP", 5));
+        assert_eq!(filter.region, "UK");
+        assert_eq!(filter.label(), "Squawk mismatches: EGLL_APP");
+
+        let wrong = squawking("1234", "4321");
+        assert!(filter.matches(&wrong, &covered_by("EGLL_APP")));
+        assert!(!filter.matches(&wrong, &covered_by("LON_S_CTR")));
+        assert!(!filter.matches(&wrong, &[]));
+        assert!(!filter.matches(&squawking("4321", "4321"), &covered_by("EGLL_APP")));
+        assert!(!filter.matches(&squawking("7000", "4321"), &covered_by("EGLL_APP")));
+        assert!(!filter.matches(&squawking("1234", ""), &covered_by("EGLL_APP")));
+    }
+}
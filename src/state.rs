@@ -1,6 +1,55 @@
 use crate::{
-    api::Vatsim,
-    models::{Controller, Pilot, V3ResponseData},
+    action::{Action, Effect, InputContext},
+    alerts::{AlertKind, PendingAlerts},
+    atis::{AtisTracker, DiffLine},
+    callsign,
+    columns::{self, Column},
+    complete::{self, Completion, Target},
+    custom::{self, CustomColumn, CustomColumns},
+    diff::{self, ChangeEvent},
+    enrich::{self, ConnectionKey, EnrichOptions, EnrichedPilot},
+    error_panel::{self, ErrorPanel, RefreshError},
+    events::{self, Timing},
+    facilities::{self, FacilityFilter},
+    favorites::FavoritesFile,
+    filter::{self, AirlineFilter, Field, FilterBuilder, RangeFilter, RulesFilter},
+    flight::{self, Phase, TopOfDescent},
+    format::{self, DisplayZone, TimeStyle},
+    frequency,
+    geo::{self, GridIndex},
+    ghost_tracker::GhostTracker,
+    ghosts::Ghosts,
+    hints,
+    history::{History, HistoryCursor},
+    hitmap::{HitMap, Region},
+    logbuffer::{self, LogPanel, LogView},
+    models::{Controller, Event, FlightPlan, Pilot, V3ResponseData},
+    nearby::{self, ControllerPositions, FacilityClass, NearbyController},
+    polling,
+    privacy::Privacy,
+    query::Query,
+    reference::ReferenceNames,
+    rows,
+    sanity::Limits,
+    session::Session,
+    sort::{sort_rows_then, DefaultSort, SortKey, SortSpec},
+    special,
+    split::{self, Pane, SplitPanes, SplitView},
+    squawk::{self, SquawkFilter},
+    theme::{self, Theme},
+    traffic::{self, Load},
+    trail::{Trail, Trails},
+    transitions,
+    tune::{Tune, TunePrompt},
+    widgets::{self, CheckList, Edit, TextInput},
+    worker::FetchStats,
+};
+use chrono::{DateTime, Utc};
+use log::warn;
+use std::{
+    collections::{BTreeMap, HashMap},
+    ops::Range,
+    time::{Duration, Instant},
 };
 use tui::{
     style::{Color, Modifier, Style},
@@ -8,13 +57,333 @@ use tui::{
     widgets::TableState,
 };
 
+/// How far ahead the "departing soon" filter looks, in minutes.
+const DEPARTING_SOON_MINUTES: i64 = 60;
+
+/// Number of "tabs": pilots, controllers, and events.
+const TAB_COUNT: usize = 3;
+
+/// Sorts of each "tab" unless set on the command line.
+const DEFAULT_SORTS: [SortSpec; TAB_COUNT] = [
+    SortSpec {
+        column: 0,
+        descending: false,
+        then: None,
+    },
+    SortSpec {
+        column: 0,
+        descending: false,
+        then: None,
+    },
+    SortSpec {
+        column: columns::EVENT_START_COLUMN,
+        descending: false,
+        then: None,
+    },
+];
+
+/// Sort of the controller coverage view to start with.
+const DEFAULT_COVERAGE_SORT: SortSpec = SortSpec {
+    column: columns::COVERAGE_LOGON_COLUMN,
+    descending: false,
+    then: None,
+};
+
+/// How long the clients that connected and disconnected are shown after a refresh.
+const DELTA_DISPLAY_TIME: Duration = Duration::from_secs(5);
+
+/// Lines moved by paging up and down, whatever the row height.
+const PAGE_LINES: u16 = 10;
+
+/// Marker added to the callsigns of pilots flying to or from an active event.
+const EVENT_MARKER: &str = " \u{2605}";
+/// Appended to the callsigns of connections that are probably ghosts.
+const GHOST_MARKER: &str = " ghost?";
+/// Appended to the callsigns of controllers who recently changed their ATIS.
+const ATIS_MARKER: &str = " ATIS updated";
+/// Marker after the callsign of a pilot whose record looks broken.
+const BAD_DATA_MARKER: &str = " bad data";
+/// Marker after the callsign of a flight from and to the split airport,
+/// which is listed in both panes.
+const PATTERN_MARKER: &str = " \u{21ba}";
+/// ATC cell of a pilot no controller covers.
+const UNICOM: &str = "UNICOM";
+
 /// Information from the V3 API data for the current interface view.
 pub struct ViewData {
-    pub title: &'static str,
-    pub headers: Vec<&'static str>,
-    pub data: Vec<Vec<String>>,
+    pub title: String,
+    /// Each "tab"'s filter, if any are filtered.
+    pub filter_summary: Option<String>,
+    pub columns: Vec<Column>,
+    /// Width of each column, after any resizing.
+    pub widths: Vec<u16>,
+    pub column_offset: usize,
+    pub sort: SortSpec,
+    /// The rows on screen, which are the only ones built.
+    pub data: Vec<RowData>,
+    /// Position of the first row in `data` among all the "tab"'s rows.
+    pub first_row: usize,
+    /// Lines each row takes up.
+    pub row_height: u16,
     pub show_popup: bool,
-    pub selected_row_data: SelectedRow,
+    pub selected_row_data: Option<SelectedRow>,
+    /// Controllers near the selected pilot, when the popup is shown.
+    pub nearby_atc: Vec<NearbyController>,
+    /// Controllers whose plausible traffic includes the selected pilot,
+    /// most specific first, when the popup is shown.
+    pub covering_atc: Vec<NearbyController>,
+    /// The selected pilot's derived values, when the popup is shown.
+    pub enriched_pilot: Option<EnrichedPilot>,
+    /// Where the selected pilot has been this session, when the popup is
+    /// shown.
+    pub trail: Option<Trail>,
+    /// Facility name of the selected controller, when the popup is shown.
+    pub facility_name: Option<String>,
+    /// Callsigns of the pilots the selected controller is plausibly
+    /// working, or `None` if they can't be placed, when the popup is shown.
+    pub plausible_traffic: Option<Vec<String>>,
+    /// How the selected controller's ATIS changed, if it did recently, when
+    /// the popup is shown.
+    pub atis_diff: Option<Vec<DiffLine>>,
+    /// How long the selected client's record had gone without an update
+    /// when the data was generated, when the popup is shown.
+    pub record_age: Option<chrono::Duration>,
+    pub load_state: LoadState,
+    /// How long the current attempt at loading the data has taken.
+    pub loading_for: Duration,
+    pub filter_builder: Option<FilterBuilder>,
+    pub search: Option<SearchPrompt>,
+    pub tune: Option<TunePrompt>,
+    pub facility_picker: Option<CheckList>,
+    /// The pilots at the filtered airport, inbound and outbound, in place
+    /// of the table.
+    pub split: Option<SplitPanes>,
+    /// Fetch counters, when the debug overlay is shown.
+    pub debug_stats: Option<FetchStats>,
+    /// Recent log lines, when the log panel is open.
+    pub log_view: Option<LogView>,
+    /// Entries kept from one refresh to the next.
+    pub retained_entries: usize,
+    /// Time zone times are shown in.
+    pub zone: DisplayZone,
+    /// Message about the last action, like why it couldn't be done.
+    pub status: Option<String>,
+    /// Why refreshing keeps failing, once it has failed enough times in a row.
+    pub refresh_error: Option<RefreshError>,
+    /// Refreshing is paused for inactivity.
+    pub idle: bool,
+    /// The first-run tour is showing.
+    pub tour: bool,
+    /// Config profile the program was started with.
+    pub profile: Option<String>,
+}
+
+/// A table row's cells and style.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RowData {
+    pub cells: Vec<String>,
+    /// Text for a second line under one of the cells, by column.
+    pub detail: Option<(usize, String)>,
+    pub style: Style,
+    /// Styles for some of the cells, like a badge, by column.
+    pub cell_styles: Vec<(usize, Style)>,
+}
+
+/// How much room each table row gets.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Density {
+    /// One line per row.
+    #[default]
+    Compact,
+    /// Two lines per row, with details on the second.
+    Comfortable,
+}
+
+impl Density {
+    /// Lines each row takes up.
+    pub fn row_height(self) -> u16 {
+        match self {
+            Density::Compact => 1,
+            Density::Comfortable => 2,
+        }
+    }
+
+    /// The other density.
+    fn toggled(self) -> Self {
+        match self {
+            Density::Compact => Density::Comfortable,
+            Density::Comfortable => Density::Compact,
+        }
+    }
+}
+
+/// Text typed into the search prompt, with the last parse error.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchPrompt {
+    pub input: TextInput,
+    pub error: Option<String>,
+    /// Text being looked for in the history, when reverse searching.
+    pub reverse_search: Option<String>,
+    /// Completions of the last word, while Tab is being pressed.
+    pub completion: Option<Completion>,
+    cursor: HistoryCursor,
+}
+
+impl SearchPrompt {
+    /// Type a character, into the reverse search if there is one.
+    pub fn type_char(&mut self, c: char, history: &History) {
+        self.type_text(&c.to_string(), history);
+    }
+
+    /// Insert pasted text, into the reverse search if there is one.
+    pub fn paste(&mut self, text: &str, history: &History) {
+        self.type_text(&widgets::single_line(text), history);
+    }
+
+    /// Insert text at the cursor, or at the end of the reverse search.
+    fn type_text(&mut self, text: &str, history: &History) {
+        self.completion = None;
+        if let Some(needle) = self.reverse_search.as_mut() {
+            needle.push_str(text);
+            let needle = needle.clone();
+            self.search_history(history, &needle, false);
+        } else {
+            self.input.paste(text);
+            self.cursor.reset();
+        }
+    }
+
+    /// Apply an editing key.
+    ///
+    /// In a reverse search, Backspace edits the text being looked for, and
+    /// moving the cursor keeps the found entry to edit, like in a shell.
+    pub fn edit(&mut self, edit: Edit) {
+        self.completion = None;
+        if let Some(needle) = self.reverse_search.as_mut() {
+            if edit == Edit::Backspace {
+                let _ = needle.pop();
+                return;
+            }
+            if !edit.is_movement() {
+                return;
+            }
+            self.end_reverse_search(true);
+        }
+        self.input.edit(edit);
+        if !edit.is_movement() {
+            self.cursor.reset();
+        }
+    }
+
+    /// Show the next older history entry.
+    pub fn older(&mut self, history: &History) {
+        self.completion = None;
+        if let Some(entry) = self.cursor.older(history, self.input.text()) {
+            self.input.set(entry);
+        }
+    }
+
+    /// Show the next newer history entry, or the stashed text after the newest.
+    pub fn newer(&mut self, history: &History) {
+        self.completion = None;
+        if let Some(entry) = self.cursor.newer(history) {
+            self.input.set(entry);
+        }
+    }
+
+    /// Start a reverse search through the history, or find the next older match.
+    pub fn reverse_search(&mut self, history: &History) {
+        self.completion = None;
+        match self.reverse_search.clone() {
+            Some(needle) => self.search_history(history, &needle, true),
+            None => self.reverse_search = Some(String::new()),
+        }
+    }
+
+    /// Leave the reverse search.
+    ///
+    /// If `accept` is set, the found entry is kept to edit; otherwise the
+    /// text from before the search comes back.
+    pub fn end_reverse_search(&mut self, accept: bool) {
+        self.reverse_search = None;
+        if accept {
+            self.cursor.reset();
+        } else if let Some(stash) = self.cursor.restore() {
+            self.input.set(stash);
+        }
+    }
+
+    /// Show the next history entry containing `needle`.
+    fn search_history(&mut self, history: &History, needle: &str, again: bool) {
+        if let Some(entry) = self
+            .cursor
+            .search(history, needle, again, self.input.text())
+        {
+            self.input.set(entry);
+        }
+    }
+}
+
+/// Progress of getting data from the VATSIM API.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadState {
+    /// Waiting on the initial fetch.
+    Loading,
+    /// The fetch failed, with the error message to show.
+    Failed(String),
+    /// Data is available.
+    Loaded,
+}
+
+/// Whether someone is using the interface, to pause refreshing when not.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Activity {
+    Active,
+    /// No input for a while, so refreshing is paused.
+    Idle,
+    /// Input came in while idle; the data should be refreshed right away.
+    Resuming,
+}
+
+/// Numbers of clients that connected and disconnected between two refreshes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConnectionDeltas {
+    pub pilots_joined: usize,
+    pub pilots_left: usize,
+    pub controllers_joined: usize,
+    pub controllers_left: usize,
+}
+
+impl ConnectionDeltas {
+    /// Count the connections and disconnections in the changes between two
+    /// sets of data.
+    fn from_events(events: &[ChangeEvent]) -> Self {
+        let mut deltas = Self::default();
+        for event in events {
+            match event {
+                ChangeEvent::PilotConnected { .. } => deltas.pilots_joined += 1,
+                ChangeEvent::PilotDisconnected { .. } => deltas.pilots_left += 1,
+                ChangeEvent::ControllerOnline { .. } => deltas.controllers_joined += 1,
+                ChangeEvent::ControllerOffline { .. } => deltas.controllers_left += 1,
+                _ => {}
+            }
+        }
+        deltas
+    }
+
+    /// Describe the changes, like "+12 / \u{2212}7 pilots, +1 / \u{2212}2 controllers".
+    ///
+    /// Returns `None` if nothing changed.
+    pub fn text(&self) -> Option<String> {
+        let parts: Vec<String> = [
+            format::connection_delta(self.pilots_joined, self.pilots_left, "pilot"),
+            format::connection_delta(self.controllers_joined, self.controllers_left, "controller"),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        (!parts.is_empty()).then(|| parts.join(", "))
+    }
 }
 
 /// The data for a selected row in the interface.
@@ -23,203 +392,4467 @@ pub struct ViewData {
 pub enum SelectedRow {
     Pilot(Pilot),
     Controller(Controller),
+    Event(Event),
 }
 
 /// State of the interface.
+#[allow(clippy::struct_excessive_bools)]
 pub struct App {
     tab_index: usize,
-    table_states: [TableState; 2],
+    table_states: [TableState; TAB_COUNT],
+    /// First row shown in each "tab"'s table. It's kept here rather than
+    /// by the table widget so that only the rows on screen are built.
+    row_offsets: [usize; TAB_COUNT],
+    /// Rows that fit in the table as last drawn, or `None` until it's
+    /// drawn, when every row is built.
+    table_fit: Option<usize>,
+    column_offset: usize,
+    max_column_offset: usize,
+    /// Where the tab labels and column headers were last drawn.
+    hit_map: HitMap,
+    /// Time zone times are shown in.
+    zone: DisplayZone,
+    sorts: [SortSpec; TAB_COUNT],
+    /// Sorts to reset each "tab" to.
+    default_sorts: [SortSpec; TAB_COUNT],
+    /// Show the controllers "tab" as a coverage timeline.
+    coverage_view: bool,
+    coverage_sort: SortSpec,
     data: V3ResponseData,
+    /// Rating and facility names for the data.
+    names: ReferenceNames,
+    /// Clients that connected and disconnected in the last refresh, and
+    /// when it was received.
+    deltas: Option<(ConnectionDeltas, Instant)>,
+    /// Current and upcoming events, fetched separately from the data.
+    events: Vec<Event>,
+    /// Indexes into the data's pilots and controllers, and the events,
+    /// of the rows shown.
+    rows: [Vec<usize>; TAB_COUNT],
+    /// Queries filtering the pilots and controllers "tabs".
+    filters: [Query; 2],
+    rules_filter: RulesFilter,
+    /// Only show pilots in this phase of flight.
+    phase_filter: Option<Phase>,
+    /// Only show pilots within a controller's visual range.
+    range_filter: Option<RangeFilter>,
+    /// Only show pilots in a controller's traffic squawking the wrong code.
+    squawk_filter: Option<SquawkFilter>,
+    /// Only show pilots on the ground who filed to depart within the hour.
+    departing_soon: bool,
+    /// Only show pilots who look to be flying special operations.
+    special_only: bool,
+    /// Only show pilots flying for these airlines.
+    airline_filter: Option<AirlineFilter>,
+    /// Only show tower, approach, and center controllers without an ATIS.
+    no_atis: bool,
+    /// Kinds of position hidden from the controllers.
+    facility_filter: FacilityFilter,
+    /// Keep favorites at the top of the pilots and controllers.
+    pin_favorites: bool,
+    /// Records not updated for longer than this are stale.
+    stale_after: Option<chrono::Duration>,
+    /// Hide clients whose records are stale.
+    hide_stale: bool,
+    /// Altitude and update time of each pilot in the previous data, by CID.
+    previous_altitudes: HashMap<i64, (i64, DateTime<Utc>)>,
+    /// Vertical speed of each pilot in feet per minute, by CID.
+    vertical_rates: HashMap<i64, f64>,
+    /// Each pilot's derived values by connection, as of the last refresh.
+    enriched: HashMap<ConnectionKey, EnrichedPilot>,
+    /// Past these values a pilot's record is taken to be broken.
+    sanity_limits: Limits,
+    /// How much of each client's name is shown.
+    privacy: Privacy,
+    /// Hide pilots whose records look broken.
+    hide_bad_data: bool,
+    /// Extra columns from the config file.
+    custom_columns: CustomColumns,
+    /// Cells of the custom pilot and controller columns, by callsign, as
+    /// of the last refresh.
+    custom_cells: [HashMap<String, Vec<Option<String>>>; 2],
+    /// Number of pilots each controller is plausibly working, by CID, for
+    /// controllers that can be placed on the map.
+    traffic: HashMap<i64, usize>,
+    /// Where the pilots are, for finding those near a controller.
+    pilot_index: GridIndex,
+    /// Where the controllers are, for finding those near a pilot.
+    controller_positions: ControllerPositions,
+    /// The adaptive poll interval in use, shown in the title.
+    poll_interval: Option<Duration>,
+    /// Where each pilot has been this session, by CID.
+    trails: Trails,
+    /// Controllers whose plausible traffic includes each pilot, by
+    /// callsign, most specific first, as of the last refresh.
+    covering: HashMap<String, Vec<NearbyController>>,
+    filter_builder: Option<FilterBuilder>,
+    search: Option<SearchPrompt>,
+    /// The frequency the controllers are filtered to, and its prompt.
+    tune: Tune,
+    /// Which kinds of position to show, while they're being chosen.
+    facility_picker: Option<CheckList>,
+    /// The pilots at the filtered airport split into inbound and
+    /// outbound, if they're shown that way.
+    split: Option<SplitView>,
+    history: History,
     show_popup: bool,
+    load_state: LoadState,
+    /// When the current attempt at loading the data started.
+    loading_since: Instant,
+    /// How long loading the data can take before giving up, if ever.
+    startup_timeout: Option<Duration>,
+    pending_session: Option<Session>,
+    fetch_stats: FetchStats,
+    error_panel: ErrorPanel,
+    show_debug: bool,
+    /// The log panel, if it's open.
+    log_panel: Option<LogPanel>,
+    status: Option<String>,
+    activity: Activity,
+    /// Whether the first-run tour is showing.
+    tour: bool,
+    /// When the last key or mouse input came in.
+    last_input: Instant,
+    /// How long without input before going idle, if ever.
+    idle_timeout: Option<Duration>,
+    density: Density,
+    /// Column widths the user resized, by table name.
+    column_widths: BTreeMap<String, Vec<u16>>,
+    favorites: FavoritesFile,
+    /// What in the latest data is worth an alert.
+    alerts: PendingAlerts,
+    /// Connections that share a CID with a more recently updated one, and
+    /// whether they're counted.
+    ghosts: GhostTracker,
+    /// Controllers' ATIS from the last refresh, and recent changes to it.
+    atis: AtisTracker,
+    theme: Theme,
+    /// Config profile the program was started with.
+    profile: Option<String>,
+}
+
+impl Default for App {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl App {
-    /// Create a new interface state from the VATSIM V3 data.
-    pub fn new(data: V3ResponseData) -> Self {
+    /// Create a new interface state, waiting on data from the API.
+    pub fn new() -> Self {
         let mut state = TableState::default();
         state.select(Some(0));
         Self {
             tab_index: 0,
-            table_states: [state.clone(), state.clone()],
-            data,
+            table_states: [state.clone(), state.clone(), state.clone()],
+            row_offsets: [0; TAB_COUNT],
+            table_fit: None,
+            column_offset: 1,
+            max_column_offset: 1,
+            hit_map: HitMap::default(),
+            zone: DisplayZone::default(),
+            sorts: DEFAULT_SORTS,
+            default_sorts: DEFAULT_SORTS,
+            coverage_view: false,
+            coverage_sort: DEFAULT_COVERAGE_SORT,
+            data: V3ResponseData::default(),
+            names: ReferenceNames::default(),
+            deltas: None,
+            events: Vec::new(),
+            rows: [Vec::new(), Vec::new(), Vec::new()],
+            filters: [Query::default(), Query::default()],
+            rules_filter: RulesFilter::default(),
+            phase_filter: None,
+            range_filter: None,
+            squawk_filter: None,
+            departing_soon: false,
+            special_only: false,
+            airline_filter: None,
+            no_atis: false,
+            facility_filter: FacilityFilter::default(),
+            pin_favorites: false,
+            stale_after: None,
+            hide_stale: false,
+            previous_altitudes: HashMap::new(),
+            vertical_rates: HashMap::new(),
+            enriched: HashMap::new(),
+            sanity_limits: Limits::default(),
+            privacy: Privacy::default(),
+            hide_bad_data: false,
+            custom_columns: CustomColumns::default(),
+            custom_cells: [HashMap::new(), HashMap::new()],
+            traffic: HashMap::new(),
+            pilot_index: GridIndex::default(),
+            controller_positions: ControllerPositions::default(),
+            poll_interval: None,
+            trails: Trails::default(),
+            covering: HashMap::new(),
+            filter_builder: None,
+            search: None,
+            tune: Tune::default(),
+            facility_picker: None,
+            split: None,
+            history: History::default(),
             show_popup: false,
+            load_state: LoadState::Loading,
+            loading_since: Instant::now(),
+            startup_timeout: None,
+            pending_session: None,
+            fetch_stats: FetchStats::default(),
+            error_panel: ErrorPanel::default(),
+            show_debug: false,
+            log_panel: None,
+            status: None,
+            activity: Activity::Active,
+            tour: false,
+            last_input: Instant::now(),
+            idle_timeout: None,
+            density: Density::default(),
+            column_widths: BTreeMap::new(),
+            favorites: FavoritesFile::load(),
+            alerts: PendingAlerts::default(),
+            ghosts: GhostTracker::default(),
+            atis: AtisTracker::default(),
+            theme: Theme::default(),
+            profile: None,
         }
     }
 
-    /// Switch between the pilots and controllers data in the table.
+    /// Store VATSIM V3 data received from the API.
     ///
-    /// Effectively the "Tabs" component from tui, just manual.
-    pub fn tab_over(&mut self) {
-        self.tab_index = if self.tab_index == 0 { 1 } else { 0 };
-        self.table_states[0].select(Some(0));
-        self.table_states[1].select(Some(0));
+    /// On a refresh, the selected clients stay selected if they're still
+    /// online, and the numbers of clients that connected and disconnected
+    /// are shown for a few seconds. Clients who reconnected under another
+    /// callsign are counted as staying, and the change is shown in the
+    /// status. If a session is waiting to be restored, it's applied to the
+    /// new data. Names are hidden first, so nothing is derived from them.
+    pub fn set_data(&mut self, mut data: V3ResponseData) {
+        self.privacy.apply(&mut data);
+        let selected = [self.selected_cid(0), self.selected_cid(1)];
+        let ghosts = Ghosts::find(&data);
+        self.update_vertical_rates(&data, &ghosts);
+        self.trails
+            .update(data.pilots.iter().filter(|p| !ghosts.has_pilot(p)));
+        // a ghost may share its primary's callsign
+        let previous: HashMap<&str, &Pilot> = self
+            .data
+            .pilots
+            .iter()
+            .filter(|pilot| !self.ghosts.has_pilot(pilot))
+            .map(|pilot| (pilot.callsign.as_str(), pilot))
+            .collect();
+        let options = EnrichOptions {
+            now: Utc::now(),
+            vertical_rates: &self.vertical_rates,
+            previous: &previous,
+            limits: self.sanity_limits,
+        };
+        self.enriched = enrich::enrich_with(&data, &ghosts, &options)
+            .into_iter()
+            .map(|enriched| (enriched.key(), enriched))
+            .collect();
+        let events = diff::diff_snapshots(&self.data, &data);
+        self.deltas = self
+            .is_loaded()
+            .then(|| (ConnectionDeltas::from_events(&events), Instant::now()));
+        if self.is_loaded() {
+            let (old, new) = self.ghosts.comparable(&self.data, &data, &ghosts);
+            self.alerts.update(&old, &new, self.favorites.favorites());
+            let changes = transitions::callsign_changes(&events);
+            if let Some(text) = transitions::describe(&changes) {
+                self.status = Some(text);
+            }
+        }
+        self.custom_cells = [
+            custom::cells(&self.custom_columns.pilots, &data.pilots, |p| &p.callsign),
+            custom::cells(&self.custom_columns.controllers, &data.controllers, |c| {
+                &c.callsign
+            }),
+        ];
+        self.names = ReferenceNames::new(&data);
+        self.pilot_index = geo::pilot_index(&data.pilots);
+        self.controller_positions = ControllerPositions::new(&data.controllers);
+        self.ghosts.refresh(ghosts);
+        self.covering = nearby::covering(
+            data.controllers
+                .iter()
+                .filter(|c| !self.ghosts.has_controller(c)),
+            &data.pilots,
+            &self.pilot_index,
+        );
+        self.update_traffic(&data);
+        self.atis.update(
+            data.controllers
+                .iter()
+                .filter(|c| !self.ghosts.has_controller(c)),
+        );
+        self.data = data;
+        self.load_state = LoadState::Loaded;
+        self.rows[0].clear();
+        self.rows[1].clear();
+        self.apply_sort(0);
+        self.apply_sort(1);
+        self.select_cid(0, selected[0]);
+        self.select_cid(1, selected[1]);
+        if let Some(session) = self.pending_session.take() {
+            self.apply_session(&session);
+        }
     }
 
-    /// Scroll down the table. Wrap-around supported.
-    pub fn down(&mut self) {
-        let sel = self.table_states[self.tab_index].selected().unwrap_or(0);
-        let length = if self.tab_index == 0 {
-            self.data.pilots.len()
-        } else {
-            self.data.controllers.len()
-        };
-        let next = if sel >= length - 1 { 0 } else { sel + 1 };
-        self.table_states[self.tab_index].select(Some(next));
+    /// What in the latest data is worth an alert, if it hasn't been taken yet.
+    pub fn take_alerts(&mut self) -> Vec<AlertKind> {
+        self.alerts.take()
     }
 
-    /// Scroll up the table. Wrap-around supported.
-    pub fn up(&mut self) {
-        let sel = self.table_states[self.tab_index].selected().unwrap_or(0);
-        let length = if self.tab_index == 0 {
-            self.data.pilots.len()
-        } else {
-            self.data.controllers.len()
-        };
-        let next = if sel == 0 { length - 1 } else { sel - 1 };
-        self.table_states[self.tab_index].select(Some(next));
+    /// Store the events list received from the API.
+    ///
+    /// The selected event stays selected if it's still listed.
+    pub fn set_events(&mut self, events: Vec<Event>) {
+        let selected = self.selected_cid(2);
+        self.events = events;
+        self.rows[2].clear();
+        self.apply_sort(2);
+        self.select_cid(2, selected);
+    }
+
+    /// Queue a saved session to be restored once data is available.
+    ///
+    /// The query history, sorts, filters, and pinning don't depend on the
+    /// data, so they're restored now. Sorts and filters that no longer
+    /// parse are skipped with a warning.
+    pub fn restore_session(&mut self, session: Session) {
+        self.history = History::from_entries(&session.history);
+        for text in &session.sorts {
+            match DefaultSort::parse(text) {
+                Ok(sort) => self.sorts[sort.tab_index] = sort.spec,
+                Err(e) => warn!("Ignoring saved sort '{}': {}", text, e),
+            }
+        }
+        for (filter, text) in self.filters.iter_mut().zip(&session.filters) {
+            match Query::parse(text) {
+                Ok(query) => *filter = query,
+                Err(e) => warn!("Ignoring saved filter '{}': {}", text, e.message),
+            }
+        }
+        self.pin_favorites = session.pin_favorites;
+        self.pending_session = Some(session);
     }
 
-    /// Scroll down 10 to the button. No wrap-around.
-    pub fn page_down(&mut self) {
-        let sel = self.table_states[self.tab_index].selected().unwrap_or(0);
-        let length = if self.tab_index == 0 {
-            self.data.pilots.len()
+    /// Apply a saved session to the current data.
+    ///
+    /// If the previously-selected client is no longer online, the
+    /// selection stays at the top of the table.
+    fn apply_session(&mut self, session: &Session) {
+        self.tab_index = if session.tab_index < TAB_COUNT {
+            session.tab_index
         } else {
-            self.data.controllers.len()
+            0
         };
-        let next = if sel + 10 >= length {
-            length - 1
+        self.update_rows();
+        let cid = session
+            .selected_callsign
+            .as_ref()
+            .and_then(|callsign| match self.tab_index {
+                0 => self
+                    .data
+                    .pilots
+                    .iter()
+                    .find(|pilot| &pilot.callsign == callsign)
+                    .map(|pilot| pilot.cid),
+                1 => self
+                    .data
+                    .controllers
+                    .iter()
+                    .find(|controller| &controller.callsign == callsign)
+                    .map(|controller| controller.cid),
+                _ => self
+                    .events
+                    .iter()
+                    .find(|event| &event.name == callsign)
+                    .map(|event| event.id),
+            });
+        self.select_cid(self.tab_index, cid);
+    }
+
+    /// Capture the current state to save for the next run.
+    pub fn session(&self) -> Session {
+        let callsign = self.get_selected_row_data().map(|row| match row {
+            SelectedRow::Pilot(pilot) => pilot.callsign,
+            SelectedRow::Controller(controller) => controller.callsign,
+            SelectedRow::Event(event) => event.name,
+        });
+        Session {
+            tab_index: self.tab_index,
+            selected_callsign: callsign,
+            history: self.history.entries().to_vec(),
+            column_widths: self.column_widths.clone(),
+            onboarded: !self.tour,
+            hidden_facilities: self.facility_filter.hidden(),
+            sorts: self.saved_sorts(),
+            filters: self.filters.iter().map(Query::to_string).collect(),
+            pin_favorites: self.pin_favorites,
+            ..Session::default()
+        }
+    }
+
+    /// Each "tab"'s sort as text, to save.
+    ///
+    /// Sorts by custom columns, or by the pilot columns that only show
+    /// while filtered to a controller, aren't saved, since the columns
+    /// won't be there when the session is restored.
+    fn saved_sorts(&self) -> Vec<String> {
+        self.sorts
+            .iter()
+            .enumerate()
+            .filter(|&(tab_index, spec)| {
+                tab_index != 0
+                    || spec.column.max(spec.then.unwrap_or(0)) < columns::PILOT_COLUMNS.len()
+            })
+            .filter_map(|(tab_index, &spec)| DefaultSort::new(tab_index, spec))
+            .map(|sort| sort.to_string())
+            .collect()
+    }
+
+    /// Record that getting data from the API failed.
+    pub fn set_error(&mut self, error: &anyhow::Error) {
+        self.load_state = LoadState::Failed(format!("{:#}", error));
+        self.show_popup = false;
+    }
+
+    /// Record that refreshing the data failed, keeping the old data.
+    ///
+    /// The first few failures in a row are shown in the status; after
+    /// that the error panel shows until a refresh succeeds.
+    pub fn refresh_failed(&mut self, error: &anyhow::Error) {
+        if let Some(status) = self.error_panel.failed(error) {
+            self.status = Some(status);
+        }
+    }
+
+    /// Record that a refresh succeeded, clearing any error panel and the
+    /// status saying it failed.
+    pub fn refresh_succeeded(&mut self) {
+        self.error_panel.succeeded();
+        if self
+            .status
+            .as_ref()
+            .is_some_and(|status| status.starts_with(error_panel::FAILED_STATUS))
+        {
+            self.status = None;
+        }
+    }
+
+    /// Whether the error panel is showing, so keys can retry or switch
+    /// mirrors.
+    pub fn shows_refresh_error(&self) -> bool {
+        self.error_panel.is_showing()
+    }
+
+    /// Store the worker's latest fetch counters.
+    ///
+    /// If the worker switched away from a stale mirror, a status message
+    /// says so.
+    pub fn set_fetch_stats(&mut self, stats: FetchStats) {
+        if stats.stale_switches > self.fetch_stats.stale_switches {
+            self.status = Some("Mirror stale, switched to another".to_owned());
+        }
+        if stats.dumps > self.fetch_stats.dumps {
+            if let Some(path) = &stats.last_dump {
+                self.status = Some(format!("Saved the raw response to {}", path.display()));
+            }
+        }
+        self.fetch_stats = stats;
+    }
+
+    /// Show or hide the debug overlay.
+    pub fn toggle_debug(&mut self) {
+        self.show_debug = !self.show_debug;
+    }
+
+    /// Open or close the log panel, which needs the log kept in memory.
+    pub fn toggle_log_panel(&mut self) {
+        if self.log_panel.take().is_some() {
+            return;
+        }
+        if logbuffer::is_enabled() {
+            self.log_panel = Some(LogPanel::default());
         } else {
-            sel + 10
+            self.status = Some("Run with --debug to see the log".to_owned());
+        }
+    }
+
+    /// Set the colors of the table rows.
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+    }
+
+    /// Set how much room each table row gets.
+    pub fn set_density(&mut self, density: Density) {
+        self.density = density;
+    }
+
+    /// Set how long without input before refreshing is paused, or `None` to never pause.
+    pub fn set_idle_timeout(&mut self, timeout: Option<Duration>) {
+        self.idle_timeout = timeout;
+    }
+
+    /// Record key or mouse input, waking up if idle.
+    pub fn record_input(&mut self, now: Instant) {
+        self.last_input = now;
+        if self.activity == Activity::Idle {
+            self.activity = Activity::Resuming;
+        }
+    }
+
+    /// Go idle if there's been no input for long enough.
+    pub fn check_idle(&mut self, now: Instant) {
+        let Some(timeout) = self.idle_timeout else {
+            return;
         };
-        self.table_states[self.tab_index].select(Some(next));
+        if self.activity == Activity::Active && now.duration_since(self.last_input) >= timeout {
+            self.activity = Activity::Idle;
+        }
     }
 
-    /// Scroll up 10 to the top. No wrap-around.
-    pub fn page_up(&mut self) {
-        let sel = self.table_states[self.tab_index].selected().unwrap_or(0);
-        let next = if sel <= 10 { 0 } else { sel - 10 };
-        self.table_states[self.tab_index].select(Some(next));
+    /// Whether refreshing is paused for inactivity.
+    pub fn is_idle(&self) -> bool {
+        self.activity == Activity::Idle
     }
 
-    /// Toggle the inspection popup on a table row.
-    pub fn toggle_popup(&mut self, open: bool) {
-        self.show_popup = open;
+    /// Show the first-run tour until a key is pressed.
+    pub fn show_tour(&mut self) {
+        self.tour = true;
     }
 
-    /// Get data from the selected "tab" for the table.
-    fn get_tab_data(&self) -> Vec<Vec<String>> {
-        if self.tab_index == 0 {
-            self.data
-                .pilots
-                .iter()
-                .map(|pilot| {
-                    vec![
-                        pilot.callsign.clone(),
-                        pilot.name.clone(),
-                        pilot.flight_plan.as_ref().map_or_else(
-                            || String::from("???"),
-                            |fp| {
-                                if !fp.aircraft_faa.is_empty() {
-                                    fp.aircraft_faa.clone()
-                                } else if !fp.aircraft_short.is_empty() {
-                                    fp.aircraft_short.clone()
-                                } else {
-                                    String::from("???")
-                                }
-                            },
-                        ),
-                        pilot.latitude.to_string(),
-                        pilot.longitude.to_string(),
-                    ]
-                })
-                .collect()
+    /// Close the first-run tour, returning whether it was showing.
+    pub fn dismiss_tour(&mut self) -> bool {
+        std::mem::replace(&mut self.tour, false)
+    }
+
+    /// Finish waking up, returning whether the interface was resuming
+    /// from idle and so should refresh right away.
+    pub fn take_resume(&mut self) -> bool {
+        if self.activity == Activity::Resuming {
+            self.activity = Activity::Active;
+            true
         } else {
-            self.data
-                .controllers
-                .iter()
-                .map(|controller| {
-                    vec![
-                        controller.callsign.clone(),
-                        controller.name.clone(),
-                        controller.frequency.clone(),
-                        Vatsim::controller_rating_lookup(&self.data, controller.rating),
-                    ]
-                })
-                .collect()
+            false
         }
     }
 
-    /// Get table headers for the selected "tab".
-    fn get_headers(&self) -> Vec<&'static str> {
-        if self.tab_index == 0 {
-            vec!["Callsign", "Name", "Aircraft", "Lat", "Long"]
-        } else {
-            vec!["Callsign", "Name", "Frequency", "Rating"]
+    /// What's on screen, which decides what keys do.
+    pub fn input_context(&self) -> InputContext {
+        match self.load_state {
+            LoadState::Loading => InputContext::Loading { failed: false },
+            LoadState::Failed(_) => InputContext::Loading { failed: true },
+            LoadState::Loaded if self.filter_builder.is_some() => InputContext::FilterBuilder,
+            LoadState::Loaded if self.search.is_some() => InputContext::Search,
+            LoadState::Loaded if self.tune.prompt().is_some() => InputContext::Tune,
+            LoadState::Loaded if self.facility_picker.is_some() => InputContext::FacilityPicker,
+            LoadState::Loaded if self.log_panel.is_some() => InputContext::LogPanel,
+            LoadState::Loaded if self.show_popup => InputContext::Popup,
+            LoadState::Loaded => InputContext::Table,
         }
     }
 
-    /// Get the table border title for the selected "tab".
-    fn get_selected_title(&self) -> &'static str {
-        if self.tab_index == 0 {
-            "Pilots"
+    /// Apply an action, returning any work left for the interface loop.
+    ///
+    /// Actions that don't apply to what's on screen do nothing.
+    pub fn update(&mut self, action: Action) -> Option<Effect> {
+        match self.input_context() {
+            InputContext::Loading { failed } => match action {
+                Action::Quit => Some(Effect::Quit),
+                Action::Retry if failed => {
+                    self.set_loading();
+                    Some(Effect::Fetch)
+                }
+                _ => None,
+            },
+            InputContext::FilterBuilder => {
+                self.update_filter_builder(action);
+                None
+            }
+            InputContext::Search => {
+                self.update_search(action);
+                None
+            }
+            InputContext::Tune => {
+                self.update_tune(action);
+                None
+            }
+            InputContext::FacilityPicker => {
+                self.update_facility_picker(action);
+                None
+            }
+            InputContext::LogPanel => self.update_log_panel(action),
+            InputContext::Popup | InputContext::Table => self.update_table(action),
+        }
+    }
+
+    /// Apply an action to the table, or the popup if it's open.
+    fn update_table(&mut self, action: Action) -> Option<Effect> {
+        self.clear_status();
+        let popup = self.show_popup;
+        if !popup && self.update_split_view(action) {
+            return None;
+        }
+        match action {
+            Action::Quit => return Some(Effect::Quit),
+            Action::OpenStats => return Some(Effect::OpenStats),
+            Action::ExportSummary if popup => return Some(Effect::ExportSummary),
+            Action::ExportTrail if popup => return Some(Effect::ExportTrail),
+            Action::Retry | Action::NextMirror => return self.error_panel.update(action),
+            Action::ToggleDebug => self.toggle_debug(),
+            Action::ToggleLogPanel => self.toggle_log_panel(),
+            Action::OpenPopup => self.toggle_popup(true),
+            Action::ClosePopup => self.toggle_popup(false),
+            _ if popup => {}
+            Action::MoveSelection(rows) => self.move_selection(rows),
+            Action::MovePage(pages) => {
+                let page = PAGE_LINES / self.density.row_height();
+                self.move_selection(pages.saturating_mul(i32::from(page)));
+            }
+            Action::ToggleDensity => self.density = self.density.toggled(),
+            Action::ToggleHideStale => self.toggle_hide_stale(),
+            Action::ToggleCountGhosts => self.update_ghosts(action),
+            Action::ResizeColumn(delta) => self.resize_column(delta),
+            Action::ToggleTab => self.tab_over(),
+            Action::SelectTab(tab_index) => self.select_tab(tab_index),
+            Action::CycleSort => self.cycle_sort_column(),
+            Action::SortByColumn(column) => self.sort_by_column(column),
+            Action::ReverseSort => self.reverse_sort(),
+            Action::ResetSort => self.reset_sort(),
+            Action::ClearFilter => self.clear_filter(),
+            Action::ClearAllFilters => self.clear_all_filters(),
+            Action::ScrollColumns(columns) if columns > 0 => self.scroll_right(),
+            Action::ScrollColumns(_) => self.scroll_left(),
+            Action::CycleRulesFilter => self.cycle_rules_filter(),
+            Action::ToggleNoFlightPlan => self.toggle_no_flight_plan(),
+            Action::ToggleNoAtis => self.toggle_no_atis(),
+            Action::CyclePhaseFilter => self.cycle_phase_filter(),
+            Action::ToggleCoverage => self.toggle_coverage_view(),
+            Action::ToggleRangeFilter => self.toggle_range_filter(),
+            Action::ToggleSquawkMismatches => self.toggle_squawk_filter(),
+            Action::ToggleDepartingSoon => self.toggle_departing_soon(),
+            Action::ToggleSpecialOnly => self.toggle_special_only(),
+            Action::ToggleHideBadData => self.toggle_hide_bad_data(),
+            Action::ToggleSplit => self.toggle_split(),
+            Action::TogglePinFavorites => self.toggle_pin_favorites(),
+            Action::OpenTune => self.open_tune(),
+            Action::OpenFacilityPicker => self.open_facility_picker(),
+            Action::OpenFilterBuilder => self.open_filter_builder(),
+            Action::OpenSearch => self.open_search(),
+            _ => {}
+        }
+        None
+    }
+
+    /// Type pasted text into the open prompt, if there is one.
+    ///
+    /// Pastes go straight to the prompt rather than through the key
+    /// bindings, and are ignored anywhere else.
+    pub fn paste(&mut self, text: &str) {
+        match self.input_context() {
+            InputContext::FilterBuilder => {
+                if let Some(builder) = self.filter_builder.as_mut() {
+                    builder.paste(text);
+                }
+            }
+            InputContext::Search => {
+                if let Some(search) = self.search.as_mut() {
+                    search.paste(text, &self.history);
+                }
+            }
+            InputContext::Tune => self.tune.paste(text),
+            _ => {}
+        }
+    }
+
+    /// Apply an action to the filter builder popup.
+    fn update_filter_builder(&mut self, action: Action) {
+        match action {
+            Action::Submit => self.close_filter_builder(true),
+            Action::Cancel => self.close_filter_builder(false),
+            action => {
+                let Some(builder) = self.filter_builder.as_mut() else {
+                    return;
+                };
+                match action {
+                    Action::AddRow => builder.add_row(),
+                    Action::RemoveRow => builder.remove_row(),
+                    Action::NextFocus => builder.next_focus(),
+                    Action::MoveSelection(rows) if rows < 0 => builder.up(),
+                    Action::MoveSelection(_) => builder.down(),
+                    Action::Edit(edit) => builder.edit(edit),
+                    Action::InputChar(c) => builder.input(c),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// Apply an action to the search prompt, or its reverse search.
+    fn update_search(&mut self, action: Action) {
+        let Some(search) = self.search.as_mut() else {
+            return;
+        };
+        let reverse_searching = search.reverse_search.is_some();
+        match action {
+            Action::Submit if reverse_searching => search.end_reverse_search(true),
+            Action::Cancel if reverse_searching => search.end_reverse_search(false),
+            Action::Submit => self.close_search(true),
+            Action::Cancel => self.close_search(false),
+            Action::ReverseSearch => search.reverse_search(&self.history),
+            Action::MoveSelection(rows) if rows < 0 => search.older(&self.history),
+            Action::MoveSelection(_) => search.newer(&self.history),
+            Action::Edit(edit) => search.edit(edit),
+            Action::InputChar(c) => search.type_char(c, &self.history),
+            Action::Complete if !reverse_searching => self.complete_search(),
+            _ => {}
+        }
+    }
+
+    /// Complete the last word in the search prompt from the current data,
+    /// or show the next completion if Tab was just pressed.
+    fn complete_search(&mut self) {
+        let Some(search) = &self.search else {
+            return;
+        };
+        let started = if search.completion.is_some() {
+            None
         } else {
-            "Controllers"
+            let input = search.input.text();
+            let Some((start, target)) = complete::target(input) else {
+                return;
+            };
+            let candidates = complete::candidates(self.completion_values(target), &input[start..]);
+            Some((start, candidates))
+        };
+        let Some(search) = self.search.as_mut() else {
+            return;
+        };
+        match (search.completion.as_mut(), started) {
+            (Some(completion), _) => search.input.rewrite(|input| completion.next(input)),
+            (None, Some((start, candidates))) => {
+                let mut completion = None;
+                search.input.rewrite(|input| {
+                    completion = Completion::start(input, start, candidates);
+                });
+                search.completion = completion;
+            }
+            (None, None) => {}
         }
     }
 
-    /// Get data to render in the interface.
-    pub fn get_view_data(&self) -> ViewData {
-        ViewData {
-            title: self.get_selected_title(),
-            headers: self.get_headers(),
-            data: self.get_tab_data(),
-            show_popup: self.show_popup,
-            selected_row_data: self.get_selected_row_data(),
+    /// Values in the current data to complete a field name or value with.
+    fn completion_values(&self, target: Target) -> Vec<&str> {
+        let pilots = &self.data.pilots;
+        let controllers = &self.data.controllers;
+        match target {
+            Target::Field => Field::ALL.iter().map(|field| field.name()).collect(),
+            Target::Value(Field::Callsign) => pilots
+                .iter()
+                .map(|p| p.callsign.as_str())
+                .chain(controllers.iter().map(|c| c.callsign.as_str()))
+                .collect(),
+            Target::Value(Field::Departure | Field::Arrival) => pilots
+                .iter()
+                .filter_map(|p| p.flight_plan.as_ref())
+                .flat_map(|fp| [fp.departure.as_str(), fp.arrival.as_str()])
+                .collect(),
+            Target::Value(Field::Server) => pilots
+                .iter()
+                .map(|p| p.server.as_str())
+                .chain(controllers.iter().map(|c| c.server.as_str()))
+                .collect(),
+            Target::Value(Field::Aircraft) => pilots.iter().filter_map(Pilot::aircraft).collect(),
+            Target::Value(_) => Vec::new(),
         }
     }
 
-    /// Get the current "tab"'s `TableState` as a mutable reference.
-    pub fn current_table_state(&mut self) -> &mut TableState {
-        &mut self.table_states[self.tab_index]
+    /// Apply an action to the tune prompt, filtering the controllers if
+    /// a frequency was tuned.
+    fn update_tune(&mut self, action: Action) {
+        if self.tune.update(action) {
+            self.update_rows();
+        }
     }
 
-    /// Construct the "tab" selector.
-    pub fn tab_header(&self) -> Vec<Span> {
-        let active = Style::default()
-            .bg(Color::LightGreen)
-            .fg(Color::Black)
-            .add_modifier(Modifier::BOLD);
-        let inactive = Style::default();
-        vec![
-            Span::raw("   "),
-            Span::styled(
-                "Pilots",
-                if self.tab_index == 0 {
-                    active
-                } else {
-                    inactive
-                },
-            ),
-            Span::raw("  <->  "),
-            Span::styled(
-                "Controllers",
-                if self.tab_index == 1 {
-                    active
-                } else {
-                    inactive
-                },
+    /// Apply an action to the facility picker.
+    fn update_facility_picker(&mut self, action: Action) {
+        match action {
+            Action::Submit => self.close_facility_picker(true),
+            Action::Cancel => self.close_facility_picker(false),
+            Action::MoveSelection(rows) => {
+                if let Some(picker) = self.facility_picker.as_mut() {
+                    picker.move_selection(rows);
+                }
+            }
+            Action::ToggleItem => {
+                if let Some(picker) = self.facility_picker.as_mut() {
+                    picker.toggle();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Apply an action to the log panel.
+    fn update_log_panel(&mut self, action: Action) -> Option<Effect> {
+        if action == Action::Quit {
+            return Some(Effect::Quit);
+        }
+        if action == Action::ToggleLogPanel {
+            self.log_panel = None;
+            return None;
+        }
+        let panel = self.log_panel.as_mut()?;
+        match action {
+            Action::MoveSelection(lines) => {
+                panel.scroll(&logbuffer::lines(panel.min_level), lines);
+            }
+            Action::MovePage(pages) => panel.scroll(
+                &logbuffer::lines(panel.min_level),
+                pages.saturating_mul(i32::from(PAGE_LINES)),
             ),
-        ]
+            Action::FollowLog => panel.follow(),
+            Action::CycleLogLevel => panel.cycle_level(),
+            _ => {}
+        }
+        None
     }
 
-    /// Get the currently selected row's data.
-    fn get_selected_row_data(&self) -> SelectedRow {
-        let row = self.table_states[self.tab_index].selected().unwrap_or(0);
-        if self.tab_index == 0 {
-            SelectedRow::Pilot(self.data.pilots.get(row).unwrap().clone())
-        } else {
-            SelectedRow::Controller(self.data.controllers.get(row).unwrap().clone())
+    /// Record that a new fetch from the API has started.
+    pub fn set_loading(&mut self) {
+        self.load_state = LoadState::Loading;
+        self.loading_since = Instant::now();
+    }
+
+    /// Give up on loading the data after this long, if ever.
+    pub fn set_startup_timeout(&mut self, timeout: Option<Duration>) {
+        self.startup_timeout = timeout;
+    }
+
+    /// Show the error screen if loading has taken longer than the startup
+    /// timeout, returning whether it just timed out.
+    pub fn check_startup_deadline(&mut self, now: Instant) -> bool {
+        let Some(timeout) = self.startup_timeout else {
+            return false;
+        };
+        if self.load_state != LoadState::Loading || now.duration_since(self.loading_since) < timeout
+        {
+            return false;
+        }
+        self.load_state = LoadState::Failed(format!(
+            "Timed out after {}s waiting for data",
+            timeout.as_secs()
+        ));
+        true
+    }
+
+    /// Whether data from the API is available.
+    pub fn is_loaded(&self) -> bool {
+        self.load_state == LoadState::Loaded
+    }
+
+    /// Switch to the next of the pilots, controllers, and events data in the table.
+    ///
+    /// Effectively the "Tabs" component from tui, just manual.
+    pub fn tab_over(&mut self) {
+        self.select_tab((self.tab_index + 1) % TAB_COUNT);
+    }
+
+    /// Switch to a "tab", unless it's already selected.
+    pub fn select_tab(&mut self, tab_index: usize) {
+        if tab_index == self.tab_index || tab_index >= TAB_COUNT {
+            return;
+        }
+        self.tab_index = tab_index;
+        self.split = None;
+        self.update_rows();
+        for state in &mut self.table_states {
+            state.select(Some(0));
+        }
+        self.column_offset = 1;
+    }
+
+    /// Estimate each controller's traffic, without ghosts unless they're counted.
+    ///
+    /// Ghost controllers are skipped so they don't replace the estimate for
+    /// the connection that shares their CID.
+    fn update_traffic(&mut self, data: &V3ResponseData) {
+        self.traffic = data
+            .controllers
+            .iter()
+            .filter(|c| !self.ghosts.has_controller(c))
+            .filter_map(|c| {
+                traffic::estimate(c, &data.pilots, &self.pilot_index).map(|pilots| {
+                    let count = pilots
+                        .iter()
+                        .filter(|p| self.ghosts.counts_pilot(p))
+                        .filter(|p| !has_bad_data(p, &self.enriched))
+                        .count();
+                    (c.cid, count)
+                })
+            })
+            .collect();
+    }
+
+    /// Estimate pilots' vertical speeds from their altitude changes since
+    /// the previous data.
+    ///
+    /// Pilots whose position hasn't been updated keep their last estimate,
+    /// and ghosts are skipped so they don't mix with the pilot sharing
+    /// their CID.
+    fn update_vertical_rates(&mut self, data: &V3ResponseData, ghosts: &Ghosts) {
+        let mut rates = HashMap::new();
+        let mut altitudes = HashMap::new();
+        for pilot in data.pilots.iter().filter(|p| !ghosts.has_pilot(p)) {
+            let Some(updated) = pilot.last_update() else {
+                continue;
+            };
+            if let Some(&(previous, previous_updated)) = self.previous_altitudes.get(&pilot.cid) {
+                let seconds = (updated - previous_updated).num_seconds();
+                if seconds > 0 {
+                    #[allow(clippy::cast_precision_loss)]
+                    let rate = (pilot.altitude - previous) as f64 * 60.0 / seconds as f64;
+                    let _ = rates.insert(pilot.cid, rate);
+                } else if let Some(&rate) = self.vertical_rates.get(&pilot.cid) {
+                    let _ = rates.insert(pilot.cid, rate);
+                }
+            }
+            let _ = altitudes.insert(pilot.cid, (pilot.altitude, updated));
         }
+        self.vertical_rates = rates;
+        self.previous_altitudes = altitudes;
+    }
+
+    /// Set the time zone times are shown in.
+    pub fn set_zone(&mut self, zone: DisplayZone) {
+        self.zone = zone;
+    }
+
+    /// Set the config profile to show in the header and debug overlay.
+    pub fn set_profile(&mut self, profile: Option<String>) {
+        self.profile = profile;
+    }
+
+    /// Show the adaptive poll interval in use in the title, or nothing if
+    /// the interval is fixed.
+    pub fn set_poll_interval(&mut self, interval: Option<Duration>) {
+        self.poll_interval = interval;
+    }
+
+    /// How much of the network changed between the data shown and new
+    /// data, if any is shown yet.
+    pub fn change_rate(&self, data: &V3ResponseData) -> Option<f64> {
+        self.is_loaded()
+            .then(|| polling::change_rate(&self.data, data))
+    }
+
+    /// Use these limits to spot broken pilot records from the next refresh.
+    pub fn set_sanity_limits(&mut self, limits: Limits) {
+        self.sanity_limits = limits;
+    }
+
+    /// Hide names at this level from the next refresh.
+    pub fn set_privacy(&mut self, privacy: Privacy) {
+        self.privacy = privacy;
+    }
+
+    /// Show these extra columns from the next refresh.
+    pub fn set_custom_columns(&mut self, columns: CustomColumns) {
+        self.custom_columns = columns;
+    }
+
+    /// Set how long a record can go without updates before it's stale.
+    pub fn set_stale_after(&mut self, stale_after: Option<chrono::Duration>) {
+        self.stale_after = stale_after;
+    }
+
+    /// Apply an action to which ghost connections are counted,
+    /// recounting the traffic if it changed.
+    fn update_ghosts(&mut self, action: Action) {
+        let Some(status) = self.ghosts.update(action) else {
+            return;
+        };
+        let data = std::mem::take(&mut self.data);
+        self.update_traffic(&data);
+        self.data = data;
+        self.status = Some(status.to_owned());
+    }
+
+    /// Toggle hiding pilots and controllers whose records are stale.
+    pub fn toggle_hide_stale(&mut self) {
+        self.hide_stale = !self.hide_stale;
+        self.update_rows();
+    }
+
+    /// How long before the data was generated a record was last updated.
+    fn record_age(&self, last_update: Option<DateTime<Utc>>) -> Option<chrono::Duration> {
+        filter::record_age(last_update, self.data.general.updated())
+    }
+
+    /// Whether a record hasn't been updated for too long.
+    fn is_stale(&self, last_update: Option<DateTime<Utc>>) -> bool {
+        filter::is_stale(self.record_age(last_update), self.stale_after)
+    }
+
+    /// Move to the next phase of flight filter on the pilots "tab".
+    pub fn cycle_phase_filter(&mut self) {
+        self.phase_filter = match self.phase_filter {
+            None => Some(Phase::FILTERABLE[0]),
+            Some(phase) => Phase::FILTERABLE
+                .iter()
+                .position(|&p| p == phase)
+                .and_then(|i| Phase::FILTERABLE.get(i + 1))
+                .copied(),
+        };
+        self.update_rows();
+    }
+
+    /// Toggle showing only pilots on the ground who filed to depart within the hour.
+    pub fn toggle_departing_soon(&mut self) {
+        self.departing_soon = !self.departing_soon;
+        self.update_rows();
+    }
+
+    /// Toggle showing only pilots who look to be flying military or other
+    /// special operations.
+    pub fn toggle_special_only(&mut self) {
+        self.special_only = !self.special_only;
+        self.update_rows();
+    }
+
+    /// Toggle keeping favorites at the top of the pilots and controllers,
+    /// ahead of the sort.
+    pub fn toggle_pin_favorites(&mut self) {
+        self.pin_favorites = !self.pin_favorites;
+        self.apply_sort(0);
+        self.apply_sort(1);
+    }
+
+    /// Toggle hiding pilots whose records look broken.
+    pub fn toggle_hide_bad_data(&mut self) {
+        self.hide_bad_data = !self.hide_bad_data;
+        self.update_rows();
+    }
+
+    /// Split the pilots at the airport the pilots "tab" is filtered to into
+    /// inbound and outbound panes, or go back to the table.
+    ///
+    /// If the filter doesn't name one airport, a status message says how
+    /// to filter to one instead.
+    pub fn toggle_split(&mut self) {
+        if self.split.take().is_some() || self.tab_index != 0 {
+            return;
+        }
+        let Some(airport) = self.filters[0].airport() else {
+            self.set_status(
+                "Filter the pilots to one airport first, like arrival=EGLL or departure=EGLL"
+                    .to_owned(),
+            );
+            return;
+        };
+        self.split = Some(SplitView::new(airport));
+        self.update_split();
+    }
+
+    /// Apply an action to the airport split, if it's shown, returning
+    /// whether the split took it.
+    fn update_split_view(&mut self, action: Action) -> bool {
+        self.split
+            .as_mut()
+            .is_some_and(|split| split.update(action, &self.data.pilots))
+    }
+
+    /// Sort the shown pilots into the panes of the airport split.
+    ///
+    /// The split is closed if the filter no longer names one airport.
+    fn update_split(&mut self) {
+        let Some(split) = self.split.as_ref() else {
+            return;
+        };
+        if self.filters[0].airport().as_deref() != Some(split.airport()) {
+            self.split = None;
+            return;
+        }
+        let panes = split::partition(
+            &self.data.pilots,
+            &self.rows[0],
+            split.airport(),
+            |pilot| {
+                self.enriched
+                    .get(&ConnectionKey::of(pilot))
+                    .and_then(|enriched| enriched.eta)
+            },
+            |pilot| pilot_phase(pilot, &self.enriched),
+            Utc::now(),
+        );
+        if let Some(split) = self.split.as_mut() {
+            split.set_panes(panes);
+        }
+    }
+
+    /// Both panes of the airport split, if it's shown.
+    fn split_panes(&self) -> Option<SplitPanes> {
+        let split = self.split.as_ref()?;
+        Some(split.view(&self.data.pilots, |pilot, pane| {
+            self.split_cells(pilot, split.airport(), pane)
+        }))
+    }
+
+    /// A pilot's cells in a pane of the airport split: the callsign, the
+    /// aircraft, the other airport, the ETA or filed departure time, and
+    /// the phase.
+    fn split_cells(&self, pilot: &Pilot, airport: &str, pane: Pane) -> Vec<String> {
+        let mut callsign = pilot.callsign.clone();
+        if split::is_pattern(pilot, airport) {
+            callsign.push_str(PATTERN_MARKER);
+        }
+        let other = pilot.flight_plan.as_ref().map_or("", |fp| match pane {
+            Pane::Inbound => &fp.departure,
+            Pane::Outbound => &fp.arrival,
+        });
+        let time = match pane {
+            Pane::Inbound => self
+                .enriched
+                .get(&ConnectionKey::of(pilot))
+                .and_then(|enriched| enriched.eta),
+            Pane::Outbound => split::filed_departure(pilot, Utc::now()),
+        };
+        vec![
+            callsign,
+            pilot_aircraft(pilot, &self.enriched)
+                .unwrap_or("???")
+                .to_owned(),
+            other.to_owned(),
+            time.map_or_else(
+                || "\u{2014}".to_owned(),
+                |time| format::format_time(time, self.zone, TimeStyle::Clock),
+            ),
+            pilot_phase(pilot, &self.enriched).short().to_owned(),
+        ]
+    }
+
+    /// Filter the pilots "tab" to pilots within the selected controller's
+    /// visual range, sorted by distance, or clear the range filter.
+    ///
+    /// If the controller can't be placed on the map, a status message
+    /// says so instead.
+    pub fn toggle_range_filter(&mut self) {
+        if self.range_filter.take().is_some() {
+            if self.sorts[0].column == columns::PILOT_DISTANCE_COLUMN {
+                self.sorts[0] = DEFAULT_SORTS[0];
+            }
+            self.apply_sort(0);
+            return;
+        }
+        let Some(SelectedRow::Controller(controller)) = self.get_selected_row_data() else {
+            self.status = Some("Select a controller to show the pilots in their range".to_owned());
+            return;
+        };
+        match RangeFilter::around(&controller) {
+            Ok(range) => {
+                self.range_filter = Some(range);
+                self.squawk_filter = None;
+                self.sorts[0] = SortSpec {
+                    column: columns::PILOT_DISTANCE_COLUMN,
+                    descending: false,
+                    then: None,
+                };
+                self.select_tab(0);
+                self.apply_sort(0);
+            }
+            Err(e) => self.status = Some(e.to_string()),
+        }
+    }
+
+    /// Filter the pilots "tab" to the selected controller's plausible
+    /// traffic squawking other than their assigned code, or clear the
+    /// squawk filter.
+    ///
+    /// If the controller can't be placed on the map, a status message
+    /// says so instead.
+    pub fn toggle_squawk_filter(&mut self) {
+        if self.squawk_filter.take().is_some() {
+            if self.sorts[0].column >= columns::PILOT_SQUAWK_COLUMN {
+                self.sorts[0] = DEFAULT_SORTS[0];
+            }
+            self.apply_sort(0);
+            return;
+        }
+        let Some(SelectedRow::Controller(controller)) = self.get_selected_row_data() else {
+            self.status =
+                Some("Select a controller to show the pilots squawking the wrong code".to_owned());
+            return;
+        };
+        if controller
+            .facility_enum()
+            .is_some_and(|facility| FacilityClass::of(facility).is_none())
+        {
+            self.status = Some(format!(
+                "{} has no airspace, so no one is in their traffic",
+                controller.callsign
+            ));
+            return;
+        }
+        if traffic::estimate(&controller, &self.data.pilots, &self.pilot_index).is_none() {
+            self.status = Some(format!(
+                "Can't tell where {} is, so their traffic can't be shown",
+                controller.callsign
+            ));
+            return;
+        }
+        // the range filter's distance, or custom columns, would move under
+        // the squawk columns
+        self.range_filter = None;
+        if self.sorts[0].column >= columns::PILOT_SQUAWK_COLUMN {
+            self.sorts[0] = DEFAULT_SORTS[0];
+        }
+        self.squawk_filter = Some(SquawkFilter::around(&controller));
+        self.select_tab(0);
+        self.apply_sort(0);
+    }
+
+    /// Show a status message.
+    pub fn set_status(&mut self, status: String) {
+        self.status = Some(status);
+    }
+
+    /// Clear the status message.
+    pub fn clear_status(&mut self) {
+        self.status = None;
+    }
+
+    /// Only show pilots flying for some airlines.
+    pub fn set_airline_filter(&mut self, airlines: Option<AirlineFilter>) {
+        self.airline_filter = airlines;
+        self.update_rows();
+    }
+
+    /// Filter the selected "tab"'s rows, leaving the other "tab"'s filter as it is.
+    pub fn set_filter(&mut self, filter: Query) {
+        if let Some(current) = self.filters.get_mut(self.tab_index) {
+            *current = filter;
+            self.update_rows();
+        }
+    }
+
+    /// The selected "tab"'s filter, unless it's the events "tab".
+    fn current_filter(&self) -> Option<&Query> {
+        self.filters.get(self.tab_index)
+    }
+
+    /// Clear the selected "tab"'s filter.
+    fn clear_filter(&mut self) {
+        if self
+            .current_filter()
+            .is_some_and(|filter| !filter.is_empty())
+        {
+            self.set_filter(Query::default());
+            self.status = Some("Cleared this tab's filter".to_owned());
+        }
+    }
+
+    /// Clear every "tab"'s filter.
+    fn clear_all_filters(&mut self) {
+        if self.filters.iter().any(|filter| !filter.is_empty()) {
+            self.filters = [Query::default(), Query::default()];
+            self.update_rows();
+            self.status = Some("Cleared all filters".to_owned());
+        }
+    }
+
+    /// Move to the next flight rules filter on the pilots "tab".
+    pub fn cycle_rules_filter(&mut self) {
+        self.rules_filter = self.rules_filter.next();
+        self.update_rows();
+    }
+
+    /// Toggle showing only pilots without a flight plan, on the pilots "tab".
+    fn toggle_no_flight_plan(&mut self) {
+        if self.tab_index != 0 {
+            return;
+        }
+        self.rules_filter = if self.rules_filter == RulesFilter::NoPlan {
+            RulesFilter::All
+        } else {
+            RulesFilter::NoPlan
+        };
+        self.update_rows();
+    }
+
+    /// Toggle showing only tower, approach, and center controllers without
+    /// an ATIS, on the controllers "tab".
+    fn toggle_no_atis(&mut self) {
+        if self.tab_index != 1 {
+            return;
+        }
+        self.no_atis = !self.no_atis;
+        self.update_rows();
+    }
+
+    /// Open the filter builder popup, starting from the current filter.
+    ///
+    /// Filters using `or` can't be shown as builder rows, so the builder
+    /// starts empty for those.
+    pub fn open_filter_builder(&mut self) {
+        if self.tab_index == 2 {
+            self.status = Some("Events can't be filtered".to_owned());
+        } else if self.is_loaded() {
+            let filter = self.filters[self.tab_index].as_filter().unwrap_or_default();
+            self.filter_builder = Some(FilterBuilder::new(&filter));
+        }
+    }
+
+    /// Close the filter builder popup.
+    ///
+    /// If `apply` is set, the built filter is applied; if it's invalid,
+    /// the popup stays open showing the error.
+    pub fn close_filter_builder(&mut self, apply: bool) {
+        if !apply {
+            self.filter_builder = None;
+            return;
+        }
+        if let Some(builder) = self.filter_builder.as_mut() {
+            if let Ok(filter) = builder.build() {
+                self.filter_builder = None;
+                let query = Query::from(filter);
+                self.history.push(&query.to_string());
+                self.set_filter(query);
+            }
+        }
+    }
+
+    /// Open the search prompt, starting from the current filter's text.
+    pub fn open_search(&mut self) {
+        if self.tab_index == 2 {
+            self.status = Some("Events can't be filtered".to_owned());
+        } else if self.is_loaded() {
+            self.search = Some(SearchPrompt {
+                input: TextInput::new(self.filters[self.tab_index].to_string()),
+                ..SearchPrompt::default()
+            });
+        }
+    }
+
+    /// Close the search prompt.
+    ///
+    /// If `apply` is set, the typed query is parsed and applied; if it
+    /// doesn't parse, the prompt stays open showing where the error is.
+    pub fn close_search(&mut self, apply: bool) {
+        if !apply {
+            self.search = None;
+            return;
+        }
+        if let Some(search) = self.search.as_mut() {
+            match Query::parse(search.input.text()) {
+                Ok(query) => {
+                    self.search = None;
+                    self.history.push(&query.to_string());
+                    self.set_filter(query);
+                }
+                Err(e) => search.error = Some(e.to_string()),
+            }
+        }
+    }
+
+    /// Open the tune prompt, starting from the tuned frequency.
+    pub fn open_tune(&mut self) {
+        if self.is_loaded() {
+            self.tune.open();
+        }
+    }
+
+    /// Open the facility picker, ticking the kinds of position shown.
+    pub fn open_facility_picker(&mut self) {
+        if self.is_loaded() {
+            self.facility_picker =
+                Some(CheckList::new(facilities::KINDS.iter().map(|&kind| {
+                    (kind.to_owned(), self.facility_filter.shows(kind))
+                })));
+        }
+    }
+
+    /// Close the facility picker, showing only the ticked kinds of
+    /// position on the controllers "tab" if `apply` is set.
+    pub fn close_facility_picker(&mut self, apply: bool) {
+        let Some(picker) = self.facility_picker.take() else {
+            return;
+        };
+        if apply {
+            self.facility_filter = FacilityFilter::hiding(&picker.unticked());
+            self.update_rows();
+        }
+    }
+
+    /// Hide kinds of position chosen in an earlier run.
+    pub fn set_hidden_facilities(&mut self, names: &[String]) {
+        self.facility_filter = FacilityFilter::hiding(names);
+    }
+
+    /// Recompute which rows are shown, keeping the selected client selected.
+    ///
+    /// Each of the pilots and controllers "tabs" has its own query. The
+    /// flight rules, phase, range, departure, and airline filters also apply
+    /// to pilots, the tuned frequency and ATIS filters to controllers, and
+    /// hiding stale records to both. Events are listed if they're on now or
+    /// starting soon.
+    fn update_rows(&mut self) {
+        let selected = [
+            self.selected_cid(0),
+            self.selected_cid(1),
+            self.selected_cid(2),
+        ];
+        let now = Utc::now();
+        self.rows[0] = self
+            .data
+            .pilots
+            .iter()
+            .enumerate()
+            .filter(|(_, pilot)| {
+                self.rules_filter.matches(pilot)
+                    && self
+                        .phase_filter
+                        .is_none_or(|phase| pilot_phase(pilot, &self.enriched) == phase)
+                    && self.range_filter.as_ref().is_none_or(|range| {
+                        range.matches(pilot) && !has_bad_data(pilot, &self.enriched)
+                    })
+                    && self.squawk_filter.as_ref().is_none_or(|squawk| {
+                        squawk.matches(
+                            pilot,
+                            self.covering
+                                .get(&pilot.callsign)
+                                .map_or(&[], Vec::as_slice),
+                        )
+                    })
+                    && (!self.departing_soon || departing_soon(pilot, &self.enriched, now))
+                    && (!self.special_only || special::pilot(pilot).is_some())
+                    && !(self.hide_bad_data && has_bad_data(pilot, &self.enriched))
+                    && !(self.hide_stale && self.is_stale(pilot.last_update()))
+                    && self
+                        .airline_filter
+                        .as_ref()
+                        .is_none_or(|airlines| airlines.matches(pilot))
+                    && self.filters[0].matches_pilot(pilot)
+            })
+            .map(|(i, _)| i)
+            .collect();
+        self.rows[1] = self
+            .data
+            .controllers
+            .iter()
+            .enumerate()
+            .filter(|(_, controller)| {
+                self.tune.matches(controller)
+                    && (!self.no_atis || filter::missing_atis(controller))
+                    && self.facility_filter.matches(controller)
+                    && !(self.hide_stale && self.is_stale(controller.last_update()))
+                    && self.filters[1].matches_controller(controller)
+            })
+            .map(|(i, _)| i)
+            .collect();
+        self.rows[2] = events::listed(&self.events, now);
+        self.select_cid(0, selected[0]);
+        self.select_cid(1, selected[1]);
+        self.select_cid(2, selected[2]);
+        self.update_split();
+    }
+
+    /// CID of the client, or id of the event, in a "tab"'s selected row.
+    fn selected_cid(&self, tab_index: usize) -> Option<i64> {
+        let row = self.table_states[tab_index].selected().unwrap_or(0);
+        let index = *self.rows[tab_index].get(row)?;
+        match tab_index {
+            0 => self.data.pilots.get(index).map(|p| p.cid),
+            1 => self.data.controllers.get(index).map(|c| c.cid),
+            _ => self.events.get(index).map(|e| e.id),
+        }
+    }
+
+    /// Select the row with a client or event in a "tab", or the first row if they're not shown.
+    fn select_cid(&mut self, tab_index: usize, cid: Option<i64>) {
+        let row = cid.and_then(|cid| {
+            self.rows[tab_index]
+                .iter()
+                .position(|&index| match tab_index {
+                    0 => self.data.pilots[index].cid == cid,
+                    1 => self.data.controllers[index].cid == cid,
+                    _ => self.events[index].id == cid,
+                })
+        });
+        self.table_states[tab_index].select(Some(row.unwrap_or(0)));
+    }
+
+    /// Sort the selected "tab" by the next column.
+    pub fn cycle_sort_column(&mut self) {
+        let columns = self.get_columns().len();
+        let sort = self.sort_mut(self.tab_index);
+        sort.column = (sort.column + 1) % columns;
+        sort.descending = false;
+        sort.then = None;
+        self.apply_sort(self.tab_index);
+    }
+
+    /// Sort the selected "tab" by a column, reversing the sort if it's
+    /// already sorted by that column.
+    pub fn sort_by_column(&mut self, column: usize) {
+        if column >= self.get_columns().len() {
+            return;
+        }
+        let sort = self.sort_mut(self.tab_index);
+        if sort.column == column {
+            sort.descending = !sort.descending;
+        } else {
+            sort.column = column;
+            sort.descending = false;
+            sort.then = None;
+        }
+        self.apply_sort(self.tab_index);
+    }
+
+    /// Set the sorts to start with and reset to, replacing the current sorts.
+    pub fn set_default_sorts(&mut self, sorts: &[DefaultSort]) {
+        for sort in sorts {
+            self.default_sorts[sort.tab_index] = sort.spec;
+            self.sorts[sort.tab_index] = sort.spec;
+        }
+    }
+
+    /// Sort the selected "tab" by its default sort again.
+    pub fn reset_sort(&mut self) {
+        if self.tab_index == 1 && self.coverage_view {
+            self.coverage_sort = DEFAULT_COVERAGE_SORT;
+        } else {
+            self.sorts[self.tab_index] = self.default_sorts[self.tab_index];
+        }
+        self.apply_sort(self.tab_index);
+    }
+
+    /// Reverse the sort direction of the selected "tab".
+    pub fn reverse_sort(&mut self) {
+        let sort = self.sort_mut(self.tab_index);
+        sort.descending = !sort.descending;
+        self.apply_sort(self.tab_index);
+    }
+
+    /// Switch the controllers "tab" between the normal and coverage views.
+    pub fn toggle_coverage_view(&mut self) {
+        if self.tab_index != 1 {
+            return;
+        }
+        self.coverage_view = !self.coverage_view;
+        self.column_offset = 1;
+        self.apply_sort(1);
+    }
+
+    /// Current sort settings for a "tab".
+    fn sort(&self, tab_index: usize) -> SortSpec {
+        if tab_index == 1 && self.coverage_view {
+            self.coverage_sort
+        } else {
+            self.sorts[tab_index]
+        }
+    }
+
+    /// Sort settings for a "tab", to change them.
+    fn sort_mut(&mut self, tab_index: usize) -> &mut SortSpec {
+        if tab_index == 1 && self.coverage_view {
+            &mut self.coverage_sort
+        } else {
+            &mut self.sorts[tab_index]
+        }
+    }
+
+    /// Sort a "tab"'s data by its sort settings, keeping the selected row selected.
+    fn apply_sort(&mut self, tab_index: usize) {
+        let sort = self.sort(tab_index);
+        let coverage_view = self.coverage_view;
+        let selected = self.selected_cid(tab_index);
+        if tab_index == 0 {
+            let now = Utc::now();
+            let built_in = self.pilot_columns().len();
+            let custom = &self.custom_cells[0];
+            let key = |p: &Pilot, column: usize| match column.checked_sub(built_in) {
+                Some(index) => custom_sort_key(custom, &p.callsign, index),
+                None => pilot_sort_key(
+                    p,
+                    column,
+                    &self.enriched,
+                    &self.covering,
+                    self.range_filter.as_ref(),
+                    now,
+                ),
+            };
+            sort_rows_then(
+                &mut self.data.pilots,
+                sort.descending,
+                |p| key(p, sort.column),
+                |p| sort.then.map_or(SortKey::Missing, |then| key(p, then)),
+                |p| p.cid,
+            );
+            if self.pin_favorites {
+                let favorites = self.favorites.favorites();
+                // stable, so favorites and the rest each keep the sort
+                self.data
+                    .pilots
+                    .sort_by_key(|p| !favorites.contains(p.cid, &p.callsign));
+            }
+        } else if tab_index == 2 {
+            sort_rows_then(
+                &mut self.events,
+                sort.descending,
+                |e| event_sort_key(e, sort.column),
+                |e| {
+                    sort.then
+                        .map_or(SortKey::Missing, |then| event_sort_key(e, then))
+                },
+                |e| e.id,
+            );
+        } else {
+            let traffic = &self.traffic;
+            let custom = &self.custom_cells[1];
+            let key = |c: &Controller, column: usize| {
+                if coverage_view {
+                    coverage_sort_key(c, column)
+                } else if let Some(index) = column.checked_sub(columns::CONTROLLER_COLUMNS.len()) {
+                    custom_sort_key(custom, &c.callsign, index)
+                } else {
+                    controller_sort_key(c, column, traffic)
+                }
+            };
+            sort_rows_then(
+                &mut self.data.controllers,
+                sort.descending,
+                |c| key(c, sort.column),
+                |c| sort.then.map_or(SortKey::Missing, |then| key(c, then)),
+                |c| c.cid,
+            );
+            if self.pin_favorites {
+                let favorites = self.favorites.favorites();
+                self.data
+                    .controllers
+                    .sort_by_key(|c| !favorites.contains(c.cid, &c.callsign));
+            }
+        }
+        self.update_rows();
+        self.select_cid(tab_index, selected);
+    }
+
+    /// Scroll the table right by one column, keeping the first column in place.
+    fn scroll_right(&mut self) {
+        if self.column_offset < self.max_column_offset {
+            self.column_offset += 1;
+        }
+    }
+
+    /// Scroll the table left by one column.
+    fn scroll_left(&mut self) {
+        if self.column_offset > 1 {
+            self.column_offset -= 1;
+        }
+    }
+
+    /// The hit map to record this frame's clickable regions in.
+    pub fn hit_map_mut(&mut self) -> &mut HitMap {
+        &mut self.hit_map
+    }
+
+    /// The clickable region at a terminal cell, if any.
+    pub fn hit(&self, x: u16, y: u16) -> Option<Region> {
+        self.hit_map.hit(x, y)
+    }
+
+    /// Record how far the table can be scrolled right at the current terminal width.
+    pub fn set_max_column_offset(&mut self, max: usize) {
+        self.max_column_offset = max.max(1);
+        self.column_offset = self.column_offset.min(self.max_column_offset);
+    }
+
+    /// Number of rows in the selected "tab".
+    fn tab_length(&self) -> usize {
+        self.rows[self.tab_index].len()
+    }
+
+    /// Move the selection by a number of rows, in the focused pane if the
+    /// airport split is shown.
+    ///
+    /// Single steps wrap around; bigger jumps stop at the ends.
+    fn move_selection(&mut self, rows: i32) {
+        if let Some(split) = self.split.as_mut() {
+            split.move_selection(rows, &self.data.pilots);
+            return;
+        }
+        let state = &mut self.table_states[self.tab_index];
+        let selected = state.selected().unwrap_or(0);
+        if let Some(next) = rows::moved(self.rows[self.tab_index].len(), selected, rows) {
+            state.select(Some(next));
+        }
+    }
+
+    /// Toggle the inspection popup on a table row, or on a pilot in the
+    /// focused pane of the airport split.
+    pub fn toggle_popup(&mut self, open: bool) {
+        let length = match &self.split {
+            Some(split) => split.focused_rows().len(),
+            None => self.tab_length(),
+        };
+        self.show_popup = open && self.is_loaded() && length > 0;
+    }
+
+    /// Cells of some of the selected "tab"'s rows, by index into its data.
+    #[allow(clippy::too_many_lines)]
+    fn get_tab_data(&self, rows: &[usize]) -> Vec<Vec<String>> {
+        if self.tab_index == 0 {
+            let now = Utc::now();
+            let event_airports = events::active_airports(&self.events, now);
+            rows.iter()
+                .map(|&i| {
+                    let pilot = &self.data.pilots[i];
+                    let in_event = pilot.flight_plan.as_ref().is_some_and(|fp| {
+                        event_airports.contains(fp.departure.as_str())
+                            || event_airports.contains(fp.arrival.as_str())
+                    });
+                    let mut callsign = pilot.callsign.clone();
+                    if in_event {
+                        callsign.push_str(EVENT_MARKER);
+                    }
+                    if self.ghosts.has_pilot(pilot) {
+                        callsign.push_str(GHOST_MARKER);
+                    }
+                    if has_bad_data(pilot, &self.enriched) {
+                        callsign.push_str(BAD_DATA_MARKER);
+                    }
+                    let mut cells = vec![
+                        callsign,
+                        pilot.name.clone(),
+                        pilot_aircraft(pilot, &self.enriched)
+                            .unwrap_or("???")
+                            .to_owned(),
+                        pilot
+                            .flight_plan
+                            .as_ref()
+                            .map_or("", |fp| format::flight_rules(&fp.flight_rules))
+                            .to_owned(),
+                        pilot_phase(pilot, &self.enriched).short().to_owned(),
+                        pilot.latitude.to_string(),
+                        pilot.longitude.to_string(),
+                        flight::top_of_descent(pilot)
+                            .map_or_else(|| "\u{2014}".to_owned(), TopOfDescent::short),
+                        pilot
+                            .flight_plan
+                            .as_ref()
+                            .and_then(|fp| flight::parse_deptime(&fp.deptime))
+                            .map_or_else(String::new, |time| {
+                                let departs = flight::departure_time(time, now);
+                                format::format_time(departs, self.zone, TimeStyle::Compact)
+                            }),
+                        special::pilot(pilot).unwrap_or("").to_owned(),
+                        pilot
+                            .flight_plan
+                            .as_ref()
+                            .and_then(|fp| flight::parse_filed_duration(&fp.enroute_time))
+                            .map_or_else(String::new, format::duration),
+                        pilot
+                            .flight_plan
+                            .as_ref()
+                            .map_or_else(String::new, fuel_cell),
+                        covering_controller(pilot, &self.covering)
+                            .unwrap_or(UNICOM)
+                            .to_owned(),
+                    ];
+                    if let Some(range) = &self.range_filter {
+                        cells.push(format!("{} nm", format::whole(range.distance_nm(pilot))));
+                    }
+                    if self.squawk_filter.is_some() {
+                        cells.push(pilot.transponder.clone());
+                        cells.push(squawk::assigned(pilot).unwrap_or_default().to_owned());
+                    }
+                    cells.extend(self.custom_row(0, &pilot.callsign));
+                    cells
+                })
+                .collect()
+        } else if self.tab_index == 2 {
+            let now = Utc::now();
+            rows.iter()
+                .map(|&i| {
+                    let event = &self.events[i];
+                    vec![
+                        event.name.clone(),
+                        format::time_or_raw(
+                            event.start(),
+                            &event.start_time,
+                            self.zone,
+                            TimeStyle::Day,
+                        ),
+                        format::time_or_raw(
+                            event.end(),
+                            &event.end_time,
+                            self.zone,
+                            TimeStyle::Day,
+                        ),
+                        events::airports_text(event),
+                        events::timing(event, now).map_or_else(String::new, |t| t.to_string()),
+                    ]
+                })
+                .collect()
+        } else if self.coverage_view {
+            let now = Utc::now();
+            rows.iter()
+                .map(|&i| {
+                    let controller = &self.data.controllers[i];
+                    let logon = controller.logon();
+                    vec![
+                        controller.callsign.clone(),
+                        callsign::position_name(&controller.callsign),
+                        logon.map_or_else(|| "?".to_owned(), |logon| format::duration(now - logon)),
+                        logon.map_or_else(
+                            || "?".to_owned(),
+                            |logon| format::format_time(logon, self.zone, TimeStyle::Clock),
+                        ),
+                        hints::closing_hint(controller.info_lines())
+                            .map_or_else(String::new, |hint| format!("\u{26a0} {}", hint)),
+                    ]
+                })
+                .collect()
+        } else {
+            rows.iter()
+                .map(|&i| {
+                    let controller = &self.data.controllers[i];
+                    let mut callsign = controller.callsign.clone();
+                    if self.ghosts.has_controller(controller) {
+                        callsign.push_str(GHOST_MARKER);
+                    } else if self.atis.change(controller.cid).is_some() {
+                        callsign.push_str(ATIS_MARKER);
+                    }
+                    let mut cells = vec![
+                        callsign,
+                        callsign::position_name(&controller.callsign),
+                        controller.name.clone(),
+                        frequency::display(&controller.frequency),
+                        self.names.rating(controller.rating_enum()),
+                        self.traffic
+                            .get(&controller.cid)
+                            .map_or_else(|| "\u{2014}".to_owned(), |count| format!("~{}", count)),
+                        callsign::role_badge(&controller.callsign)
+                            .unwrap_or_default()
+                            .to_owned(),
+                        controller_load(controller, &self.traffic)
+                            .map_or_else(String::new, |load| load.to_string()),
+                    ];
+                    cells.extend(self.custom_row(1, &controller.callsign));
+                    cells
+                })
+                .collect()
+        }
+    }
+
+    /// Second lines for some of the selected "tab"'s rows, in the
+    /// comfortable density.
+    ///
+    /// Pilots get their aircraft and route under their name, controllers
+    /// the first line of their ATIS under their position, and events their
+    /// organisers under their name.
+    fn get_row_details(&self, rows: &[usize]) -> Vec<Option<(usize, String)>> {
+        if self.density == Density::Compact {
+            return vec![None; rows.len()];
+        }
+        rows.iter()
+            .map(|&i| match self.tab_index {
+                0 => {
+                    let pilot = &self.data.pilots[i];
+                    let detail = pilot.flight_plan.as_ref().map_or_else(
+                        || "No flight plan".to_owned(),
+                        |fp| {
+                            format!(
+                                "{} {}",
+                                pilot_aircraft(pilot, &self.enriched).unwrap_or("???"),
+                                fp.route
+                            )
+                        },
+                    );
+                    Some((1, detail))
+                }
+                1 => {
+                    let controller = &self.data.controllers[i];
+                    let first_line = controller.info_lines().first().cloned();
+                    Some((1, first_line.unwrap_or_default()))
+                }
+                _ => Some((0, events::organisers_text(&self.events[i]))),
+            })
+            .collect()
+    }
+
+    /// Name of the table shown, which column widths are saved under.
+    fn table_name(&self) -> &'static str {
+        match self.tab_index {
+            0 if self.range_filter.is_some() => "pilots_range",
+            0 if self.squawk_filter.is_some() => "pilots_squawk",
+            0 => "pilots",
+            1 if self.coverage_view => "coverage",
+            1 => "controllers",
+            _ => "events",
+        }
+    }
+
+    /// Width of each column in the table shown.
+    ///
+    /// Saved widths are only used if they're for the same columns, and
+    /// none of them is zero.
+    fn get_column_widths(&self) -> Vec<u16> {
+        let columns = self.get_columns();
+        match self.column_widths.get(self.table_name()) {
+            Some(widths) if widths.len() == columns.len() && !widths.contains(&0) => widths.clone(),
+            _ => columns.iter().map(|c| c.width).collect(),
+        }
+    }
+
+    /// Use column widths resized in an earlier run.
+    pub fn set_column_widths(&mut self, widths: BTreeMap<String, Vec<u16>>) {
+        self.column_widths = widths;
+    }
+
+    /// Widen the sorted column by `delta` cells, or narrow it, taking the
+    /// room from or giving it to the other columns.
+    fn resize_column(&mut self, delta: i16) {
+        let column = self.sort(self.tab_index).column;
+        let widths = columns::resize(&self.get_column_widths(), column, delta);
+        let _ = self
+            .column_widths
+            .insert(self.table_name().to_owned(), widths);
+    }
+
+    /// Get the table columns for the selected "tab", with any custom ones
+    /// at the end.
+    fn get_columns(&self) -> Vec<Column> {
+        let mut columns = self.get_built_in_columns().to_vec();
+        columns.extend(self.get_custom_columns().iter().map(|custom| custom.column));
+        columns
+    }
+
+    /// Custom columns for the selected "tab".
+    fn get_custom_columns(&self) -> &[CustomColumn] {
+        match self.tab_index {
+            0 => &self.custom_columns.pilots,
+            1 if !self.coverage_view => &self.custom_columns.controllers,
+            _ => &[],
+        }
+    }
+
+    /// Cells of a record's custom columns, with "—" for missing fields.
+    fn custom_row(&self, tab_index: usize, callsign: &str) -> Vec<String> {
+        let columns = if tab_index == 0 {
+            self.custom_columns.pilots.len()
+        } else {
+            self.custom_columns.controllers.len()
+        };
+        match self.custom_cells[tab_index].get(callsign) {
+            Some(cells) => cells
+                .iter()
+                .map(|cell| cell.clone().unwrap_or_else(|| "\u{2014}".to_owned()))
+                .collect(),
+            None => vec!["\u{2014}".to_owned(); columns],
+        }
+    }
+
+    /// The columns built into the pilots table, with any the controller
+    /// filters add.
+    fn pilot_columns(&self) -> &'static [Column] {
+        if self.range_filter.is_some() {
+            columns::PILOT_RANGE_COLUMNS
+        } else if self.squawk_filter.is_some() {
+            columns::PILOT_SQUAWK_COLUMNS
+        } else {
+            columns::PILOT_COLUMNS
+        }
+    }
+
+    /// Get the table columns built into the selected "tab".
+    fn get_built_in_columns(&self) -> &'static [Column] {
+        if self.tab_index == 0 {
+            self.pilot_columns()
+        } else if self.tab_index == 2 {
+            columns::EVENT_COLUMNS
+        } else if self.coverage_view {
+            columns::COVERAGE_COLUMNS
+        } else {
+            columns::CONTROLLER_COLUMNS
+        }
+    }
+
+    /// Styles for cells in some of the selected "tab"'s rows: in the
+    /// controllers table, dimmed frequencies outside the airband and the
+    /// colored load badge.
+    fn get_cell_styles(&self, rows: &[usize]) -> Vec<Vec<(usize, Style)>> {
+        if self.tab_index != 1 || self.coverage_view {
+            return vec![Vec::new(); rows.len()];
+        }
+        rows.iter()
+            .map(|&i| {
+                let controller = &self.data.controllers[i];
+                let mut styles = Vec::new();
+                if frequency::is_unusual(&controller.frequency) {
+                    styles.push((3, Style::default().add_modifier(Modifier::DIM)));
+                }
+                if let Some(load) = controller_load(controller, &self.traffic) {
+                    let color = match load {
+                        Load::Low => Color::Green,
+                        Load::Medium => Color::Yellow,
+                        Load::High => Color::Red,
+                    };
+                    styles.push((7, Style::default().fg(color)));
+                }
+                styles
+            })
+            .collect()
+    }
+
+    /// Style for some of the selected "tab"'s rows.
+    ///
+    /// Favorites are highlighted; otherwise pilots are tinted by flight
+    /// rules and controllers by facility. Stale records are dimmed, and
+    /// active events are bold.
+    fn get_row_styles(&self, rows: &[usize]) -> Vec<Style> {
+        let favorites = self.favorites.favorites();
+        if self.tab_index == 2 {
+            let now = Utc::now();
+            return rows
+                .iter()
+                .map(|&i| {
+                    if events::timing(&self.events[i], now) == Some(Timing::Active) {
+                        Style::default().add_modifier(Modifier::BOLD)
+                    } else {
+                        Style::default()
+                    }
+                })
+                .collect();
+        }
+        if self.tab_index != 0 {
+            return rows
+                .iter()
+                .map(|&i| {
+                    let controller = &self.data.controllers[i];
+                    let style = if favorites.contains(controller.cid, &controller.callsign) {
+                        self.theme.style("favorite")
+                    } else {
+                        self.theme.style(theme::facility_slot(
+                            controller.facility_enum(),
+                            &controller.callsign,
+                        ))
+                    };
+                    self.stale_style(style, controller.last_update())
+                })
+                .collect();
+        }
+        rows.iter()
+            .map(|&i| {
+                let pilot = &self.data.pilots[i];
+                let style = if favorites.contains(pilot.cid, &pilot.callsign) {
+                    self.theme.style("favorite")
+                } else if RulesFilter::Vfr.matches(pilot) {
+                    self.theme.style("pilot.vfr")
+                } else {
+                    Style::default()
+                };
+                self.stale_style(style, pilot.last_update())
+            })
+            .collect()
+    }
+
+    /// Dim a row's style if its record is stale.
+    fn stale_style(&self, style: Style, last_update: Option<DateTime<Utc>>) -> Style {
+        if self.is_stale(last_update) {
+            style.add_modifier(Modifier::DIM)
+        } else {
+            style
+        }
+    }
+
+    /// Reload the favorites if they were changed from the command line.
+    pub fn reload_favorites(&mut self) {
+        if self.favorites.reload_if_changed() && self.pin_favorites {
+            self.apply_sort(0);
+            self.apply_sort(1);
+        }
+        if let Some(warning) = self.favorites.take_warning() {
+            self.status = Some(warning);
+        }
+    }
+
+    /// Get the table border title for the selected "tab", with the filters if there are any.
+    fn get_selected_title(&self) -> String {
+        let mut parts = vec![if self.tab_index == 0 {
+            "Pilots".to_owned()
+        } else if self.tab_index == 2 {
+            "Events, active and within 24h".to_owned()
+        } else if self.coverage_view {
+            "Controller coverage".to_owned()
+        } else {
+            "Controllers".to_owned()
+        }];
+        if self.tab_index == 0 && self.rules_filter != RulesFilter::All {
+            parts.push(format!("[{}]", self.rules_filter.label()));
+        }
+        if let (0, Some(phase)) = (self.tab_index, self.phase_filter) {
+            parts.push(format!("[Phase: {}]", phase.name()));
+        }
+        if let (0, Some(range)) = (self.tab_index, &self.range_filter) {
+            parts.push(format!("[{}]", range.label()));
+        }
+        if let (0, Some(squawk)) = (self.tab_index, &self.squawk_filter) {
+            parts.push(format!("[{}]", squawk.label()));
+        }
+        if self.tab_index == 0 && self.departing_soon {
+            parts.push("[Departing within 1h]".to_owned());
+        }
+        if self.tab_index == 0 && self.special_only {
+            parts.push("[Special operations]".to_owned());
+        }
+        if self.tab_index == 0 && self.hide_bad_data {
+            parts.push("[Hiding bad data]".to_owned());
+        }
+        if let Some(split) = &self.split {
+            parts.push(format!("[Inbound/outbound {}]", split.airport()));
+        }
+        if self.tab_index != 2 && self.pin_favorites {
+            parts.push("[Favorites first]".to_owned());
+        }
+        if let (0, Some(airlines)) = (self.tab_index, &self.airline_filter) {
+            parts.push(format!("[{}]", airlines.label()));
+        }
+        if let (1, Some(label)) = (self.tab_index, self.tune.label()) {
+            parts.push(format!("[{}]", label));
+        }
+        if self.tab_index == 1 && self.no_atis {
+            parts.push("[No ATIS]".to_owned());
+        }
+        if let (1, Some(label)) = (self.tab_index, self.facility_filter.label()) {
+            parts.push(format!("[{}]", label));
+        }
+        if self.tab_index != 2 && self.hide_stale {
+            parts.push("[Hiding stale]".to_owned());
+        }
+        if let Some(filter) = self.current_filter().filter(|filter| !filter.is_empty()) {
+            parts.push(format!("[{}]", filter));
+        }
+        if let (true, Some(updated)) = (self.tab_index != 2, self.data.general.updated()) {
+            let time = format::format_time(updated, self.zone, TimeStyle::Clock);
+            parts.push(if self.zone == DisplayZone::Utc {
+                format!("[Updated {}]", time)
+            } else {
+                format!("[Updated {} {}]", time, self.zone)
+            });
+        }
+        if let Some(interval) = self.poll_interval {
+            parts.push(format!("[Polling every {}s]", interval.as_secs()));
+        }
+        parts.join(" ")
+    }
+
+    /// Each "tab"'s filter, if any are filtered, with how to clear them.
+    fn filter_summary(&self) -> Option<String> {
+        let parts: Vec<String> = ["Pilots", "Controllers"]
+            .iter()
+            .zip(&self.filters)
+            .filter(|(_, filter)| !filter.is_empty())
+            .map(|(name, filter)| format!("{}: {}", name, filter))
+            .collect();
+        if parts.is_empty() {
+            return None;
+        }
+        Some(format!(
+            "{}   (Ctrl+L clears this tab's, Ctrl+Shift+L all)",
+            parts.join("   ")
+        ))
+    }
+
+    /// Get data to render in the interface.
+    ///
+    /// Only the rows on screen are built, which matters during big events,
+    /// so this scrolls the table to keep the selection in view.
+    pub fn get_view_data(&mut self) -> ViewData {
+        let window = self.visible_window();
+        let rows = &self.rows[self.tab_index][window.clone()];
+        ViewData {
+            title: self.get_selected_title(),
+            filter_summary: self.filter_summary(),
+            columns: self.get_columns(),
+            widths: self.get_column_widths(),
+            column_offset: self.column_offset,
+            sort: self.sort(self.tab_index),
+            data: self
+                .get_tab_data(rows)
+                .into_iter()
+                .zip(self.get_row_details(rows))
+                .zip(self.get_row_styles(rows))
+                .zip(self.get_cell_styles(rows))
+                .map(|(((cells, detail), style), cell_styles)| RowData {
+                    cells,
+                    detail,
+                    style,
+                    cell_styles,
+                })
+                .collect(),
+            first_row: window.start,
+            row_height: self.density.row_height(),
+            show_popup: self.show_popup,
+            selected_row_data: self.get_selected_row_data(),
+            nearby_atc: self.nearby_atc(),
+            covering_atc: self.covering_atc(),
+            enriched_pilot: self.enriched_pilot(),
+            trail: self.selected_trail(),
+            facility_name: self.facility_name(),
+            record_age: self.selected_record_age(),
+            plausible_traffic: self.plausible_traffic(),
+            atis_diff: self.atis_diff(),
+            load_state: self.load_state.clone(),
+            loading_for: self.loading_since.elapsed(),
+            filter_builder: self.filter_builder.clone(),
+            search: self.search.clone(),
+            tune: self.tune.prompt().cloned(),
+            facility_picker: self.facility_picker.clone(),
+            split: self.split_panes(),
+            debug_stats: self.show_debug.then(|| self.fetch_stats.clone()),
+            log_view: self
+                .log_panel
+                .as_ref()
+                .map(|panel| panel.view(logbuffer::lines(panel.min_level))),
+            retained_entries: self.retained_entries(),
+            zone: self.zone,
+            status: self.status.clone(),
+            refresh_error: self.error_panel.view(&self.fetch_stats.mirrors_tried),
+            idle: self.is_idle(),
+            tour: self.tour,
+            profile: self.profile.clone(),
+        }
+    }
+
+    /// Entries kept from one refresh to the next.
+    ///
+    /// The per-client maps are rebuilt from each refresh, so they're
+    /// bounded by the clients online; the rest have fixed caps.
+    fn retained_entries(&self) -> usize {
+        self.previous_altitudes.len()
+            + self.vertical_rates.len()
+            + self.enriched.len()
+            + self.traffic.len()
+            + self.history.entries().len()
+            + self.column_widths.len()
+            + self.atis.entries()
+            + self.trails.retained()
+    }
+
+    /// Record how many rows fit in the table, to build only those next time.
+    pub fn set_table_fit(&mut self, fit: usize) {
+        self.table_fit = Some(fit);
+    }
+
+    /// The current "tab"'s rows that are on screen, scrolling to keep the
+    /// selection in view, or all of them if the table hasn't been drawn.
+    fn visible_window(&mut self) -> Range<usize> {
+        let len = self.rows[self.tab_index].len();
+        let Some(fit) = self.table_fit else {
+            return 0..len;
+        };
+        let selected = self.table_states[self.tab_index].selected().unwrap_or(0);
+        let offset = &mut self.row_offsets[self.tab_index];
+        let window = rows::visible_rows(len, selected, *offset, fit);
+        if !window.is_empty() {
+            *offset = window.start;
+        }
+        window
+    }
+
+    /// Get the current "tab"'s `TableState` as a mutable reference.
+    pub fn current_table_state(&mut self) -> &mut TableState {
+        &mut self.table_states[self.tab_index]
+    }
+
+    /// Number of rows shown and total for a "tab", without the ghosts
+    /// unless they're counted.
+    fn tab_counts(&self, tab_index: usize) -> (usize, usize) {
+        let rows = &self.rows[tab_index];
+        let (pilot_ghosts, controller_ghosts) = self.ghosts.uncounted();
+        match tab_index {
+            0 => (
+                rows.iter()
+                    .filter(|&&i| self.ghosts.counts_pilot(&self.data.pilots[i]))
+                    .count(),
+                self.data.pilots.len() - pilot_ghosts,
+            ),
+            1 => (
+                rows.iter()
+                    .filter(|&&i| self.ghosts.counts_controller(&self.data.controllers[i]))
+                    .count(),
+                self.data.controllers.len() - controller_ghosts,
+            ),
+            _ => (rows.len(), self.events.len()),
+        }
+    }
+
+    /// Label for a "tab" in the selector, with counts once data is loaded.
+    fn tab_label(&self, tab_index: usize) -> String {
+        let name = match tab_index {
+            0 => "Pilots",
+            1 => "Controllers",
+            _ => "Events",
+        };
+        if !self.is_loaded() {
+            return name.to_owned();
+        }
+        let (shown, total) = self.tab_counts(tab_index);
+        format::tab_label(name, shown, total)
+    }
+
+    /// Clients that connected and disconnected in the last refresh, if it
+    /// was recent enough to still show them.
+    fn recent_deltas(&self, now: Instant) -> Option<String> {
+        let (deltas, received) = self.deltas?;
+        if now.duration_since(received) >= DELTA_DISPLAY_TIME {
+            return None;
+        }
+        deltas.text()
+    }
+
+    /// Make the last refresh's deltas look older, to test them expiring.
+    #[cfg(test)]
+    pub fn age_deltas(&mut self, by: Duration) {
+        if let Some((_, received)) = &mut self.deltas {
+            *received -= by;
+        }
+    }
+
+    /// Construct the "tab" selector.
+    ///
+    /// After a refresh, the clients that connected and disconnected are
+    /// shown after the "tabs" for a few seconds.
+    pub fn tab_header(&self) -> Vec<Span> {
+        let active = Style::default()
+            .bg(Color::LightGreen)
+            .fg(Color::Black)
+            .add_modifier(Modifier::BOLD);
+        let inactive = Style::default();
+        let mut spans = vec![Span::raw("   ")];
+        for tab_index in 0..TAB_COUNT {
+            if tab_index > 0 {
+                spans.push(Span::raw("  <->  "));
+            }
+            spans.push(Span::styled(
+                self.tab_label(tab_index),
+                if self.tab_index == tab_index {
+                    active
+                } else {
+                    inactive
+                },
+            ));
+        }
+        if let Some(deltas) = self.recent_deltas(Instant::now()) {
+            spans.push(Span::styled(
+                format!("   {}", deltas),
+                Style::default().fg(Color::Gray),
+            ));
+        }
+        spans
+    }
+
+    /// Facility name of the selected controller, if the popup is showing one.
+    fn facility_name(&self) -> Option<String> {
+        match self.get_selected_row_data() {
+            Some(SelectedRow::Controller(controller)) if self.show_popup => controller
+                .facility_enum()
+                .map(|facility| self.names.facility(facility)),
+            _ => None,
+        }
+    }
+
+    /// Pilots the selected controller is plausibly working, if the popup is
+    /// showing a controller that can be placed on the map.
+    fn plausible_traffic(&self) -> Option<Vec<String>> {
+        match self.get_selected_row_data() {
+            Some(SelectedRow::Controller(controller)) if self.show_popup => {
+                traffic::estimate(&controller, &self.data.pilots, &self.pilot_index)
+                    .map(|pilots| pilots.iter().map(|p| p.callsign.clone()).collect())
+            }
+            _ => None,
+        }
+    }
+
+    /// How the selected controller's ATIS changed, if the popup is showing a
+    /// controller who changed it recently.
+    fn atis_diff(&self) -> Option<Vec<DiffLine>> {
+        match self.get_selected_row_data() {
+            Some(SelectedRow::Controller(controller)) if self.show_popup => {
+                self.atis.change(controller.cid).map(<[DiffLine]>::to_vec)
+            }
+            _ => None,
+        }
+    }
+
+    /// Age of the selected client's record, if the popup is showing one.
+    fn selected_record_age(&self) -> Option<chrono::Duration> {
+        match self.get_selected_row_data() {
+            Some(SelectedRow::Pilot(pilot)) if self.show_popup => {
+                self.record_age(pilot.last_update())
+            }
+            Some(SelectedRow::Controller(controller)) if self.show_popup => {
+                self.record_age(controller.last_update())
+            }
+            _ => None,
+        }
+    }
+
+    /// The selected pilot's derived values, if the popup is showing one.
+    fn enriched_pilot(&self) -> Option<EnrichedPilot> {
+        match self.get_selected_row_data() {
+            Some(SelectedRow::Pilot(pilot)) if self.show_popup => {
+                self.enriched.get(&ConnectionKey::of(&pilot)).cloned()
+            }
+            _ => None,
+        }
+    }
+
+    /// The selected pilot's trail, if the popup is showing one.
+    fn selected_trail(&self) -> Option<Trail> {
+        match self.get_selected_row_data() {
+            Some(SelectedRow::Pilot(pilot)) if self.show_popup => {
+                self.trails.get(pilot.cid).cloned()
+            }
+            _ => None,
+        }
+    }
+
+    /// Controllers near the selected pilot, if the popup is showing one.
+    fn nearby_atc(&self) -> Vec<NearbyController> {
+        match self.get_selected_row_data() {
+            Some(SelectedRow::Pilot(pilot)) if self.show_popup => {
+                nearby::nearest_by_class(&pilot, &self.data.controllers, &self.controller_positions)
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Controllers covering the selected pilot, if the popup is showing one.
+    fn covering_atc(&self) -> Vec<NearbyController> {
+        match self.get_selected_row_data() {
+            Some(SelectedRow::Pilot(pilot)) if self.show_popup => self
+                .covering
+                .get(&pilot.callsign)
+                .cloned()
+                .unwrap_or_default(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Get the currently selected row's data, if there is any.
+    fn get_selected_row_data(&self) -> Option<SelectedRow> {
+        if let Some(split) = &self.split {
+            let index = split.selected(&self.data.pilots)?;
+            return self.data.pilots.get(index).cloned().map(SelectedRow::Pilot);
+        }
+        let row = self.table_states[self.tab_index].selected().unwrap_or(0);
+        let index = *self.rows[self.tab_index].get(row)?;
+        match self.tab_index {
+            0 => self.data.pilots.get(index).cloned().map(SelectedRow::Pilot),
+            1 => self
+                .data
+                .controllers
+                .get(index)
+                .cloned()
+                .map(SelectedRow::Controller),
+            _ => self.events.get(index).cloned().map(SelectedRow::Event),
+        }
+    }
+}
+
+/// Phase of flight of a pilot, as of the last refresh.
+fn pilot_phase(pilot: &Pilot, enriched: &HashMap<ConnectionKey, EnrichedPilot>) -> Phase {
+    enriched
+        .get(&ConnectionKey::of(pilot))
+        .map_or(Phase::Unknown, |enriched| enriched.phase)
+}
+
+/// Whether a pilot's record looked broken as of the last refresh.
+fn has_bad_data(pilot: &Pilot, enriched: &HashMap<ConnectionKey, EnrichedPilot>) -> bool {
+    enriched
+        .get(&ConnectionKey::of(pilot))
+        .is_some_and(|enriched| enriched.bad_data.is_some())
+}
+
+/// Aircraft type of a pilot, as of the last refresh.
+fn pilot_aircraft<'a>(
+    pilot: &Pilot,
+    enriched: &'a HashMap<ConnectionKey, EnrichedPilot>,
+) -> Option<&'a str> {
+    enriched
+        .get(&ConnectionKey::of(pilot))?
+        .aircraft_type
+        .as_deref()
+}
+
+/// Whether a pilot is on the ground and filed to depart within the hour.
+fn departing_soon(
+    pilot: &Pilot,
+    enriched: &HashMap<ConnectionKey, EnrichedPilot>,
+    now: DateTime<Utc>,
+) -> bool {
+    flight::minutes_until_departure(pilot, now)
+        .is_some_and(|minutes| (0..=DEPARTING_SOON_MINUTES).contains(&minutes))
+        && pilot_phase(pilot, enriched) == Phase::Ground
+}
+
+/// Callsign of the most specific controller covering a pilot, if any.
+fn covering_controller<'a>(
+    pilot: &Pilot,
+    covering: &'a HashMap<String, Vec<NearbyController>>,
+) -> Option<&'a str> {
+    covering
+        .get(&pilot.callsign)?
+        .first()
+        .map(|controller| controller.callsign.as_str())
+}
+
+/// A flight plan's fuel time for the table, with a badge if it's tight.
+fn fuel_cell(plan: &FlightPlan) -> String {
+    let Some(fuel) = flight::parse_filed_duration(&plan.fuel_time) else {
+        return String::new();
+    };
+    match flight::fuel_warning(plan) {
+        Some(warning) => format!("{} {}", format::duration(fuel), warning.badge()),
+        None => format::duration(fuel),
+    }
+}
+
+/// Sort key for a record in a custom column, numeric if the value is a
+/// number.
+fn custom_sort_key(
+    cells: &HashMap<String, Vec<Option<String>>>,
+    callsign: &str,
+    index: usize,
+) -> SortKey {
+    let Some(cell) = cells
+        .get(callsign)
+        .and_then(|cells| cells.get(index)?.as_deref())
+    else {
+        return SortKey::Missing;
+    };
+    match SortKey::parsed(cell) {
+        SortKey::Missing => SortKey::text(cell),
+        number => number,
+    }
+}
+
+/// Sort key for a pilot in a pilots table column.
+fn pilot_sort_key(
+    pilot: &Pilot,
+    column: usize,
+    enriched: &HashMap<ConnectionKey, EnrichedPilot>,
+    covering: &HashMap<String, Vec<NearbyController>>,
+    range: Option<&RangeFilter>,
+    now: DateTime<Utc>,
+) -> SortKey {
+    match column {
+        1 => SortKey::text(&pilot.name),
+        2 => pilot_aircraft(pilot, enriched).map_or(SortKey::Missing, SortKey::text),
+        3 => pilot
+            .flight_plan
+            .as_ref()
+            .map_or(SortKey::Missing, |fp| SortKey::text(&fp.flight_rules)),
+        4 => match pilot_phase(pilot, enriched) {
+            Phase::Unknown => SortKey::Missing,
+            phase => SortKey::text(phase.name()),
+        },
+        5 => pilot.latitude.into(),
+        6 => pilot.longitude.into(),
+        7 => match flight::top_of_descent(pilot) {
+            Some(TopOfDescent::Ahead(nm)) => nm.round().into(),
+            Some(TopOfDescent::Passed) => 0.0.into(),
+            None => SortKey::Missing,
+        },
+        // soonest departures first, so past ones come before future ones
+        columns::PILOT_DEPARTURE_COLUMN => {
+            flight::minutes_until_departure(pilot, now).map_or(SortKey::Missing, SortKey::from)
+        }
+        columns::PILOT_SPECIAL_COLUMN => {
+            special::pilot(pilot).map_or(SortKey::Missing, SortKey::text)
+        }
+        columns::PILOT_ENROUTE_COLUMN => pilot
+            .flight_plan
+            .as_ref()
+            .and_then(|fp| flight::parse_filed_duration(&fp.enroute_time))
+            .map_or(SortKey::Missing, |time| time.num_minutes().into()),
+        // least fuel to spare first, so the flagged flights come together
+        columns::PILOT_FUEL_COLUMN => pilot
+            .flight_plan
+            .as_ref()
+            .and_then(flight::fuel_margin)
+            .map_or(SortKey::Missing, |margin| margin.num_minutes().into()),
+        columns::PILOT_ATC_COLUMN => {
+            covering_controller(pilot, covering).map_or(SortKey::Missing, SortKey::text)
+        }
+        columns::PILOT_DISTANCE_COLUMN if range.is_some() => {
+            range.map_or(SortKey::Missing, |range| range.distance_nm(pilot).into())
+        }
+        columns::PILOT_SQUAWK_COLUMN => SortKey::text(&pilot.transponder),
+        columns::PILOT_ASSIGNED_COLUMN => {
+            squawk::assigned(pilot).map_or(SortKey::Missing, SortKey::text)
+        }
+        _ => SortKey::text(&pilot.callsign),
+    }
+}
+
+/// How busy an approach or center controller plausibly is, from their
+/// traffic estimate.
+fn controller_load(controller: &Controller, traffic: &HashMap<i64, usize>) -> Option<Load> {
+    Load::from_traffic(controller.facility_enum()?, *traffic.get(&controller.cid)?)
+}
+
+/// Sort key for a controller in a controllers table column.
+///
+/// Controllers without a traffic estimate sort last.
+fn controller_sort_key(
+    controller: &Controller,
+    column: usize,
+    traffic: &HashMap<i64, usize>,
+) -> SortKey {
+    match column {
+        1 => SortKey::text(&callsign::position_name(&controller.callsign)),
+        2 => SortKey::text(&controller.name),
+        3 => frequency::parse_feed(&controller.frequency)
+            .map_or(SortKey::Missing, |khz| i64::from(khz).into()),
+        4 => i64::from(controller.rating).into(),
+        5 => traffic
+            .get(&controller.cid)
+            .map_or(SortKey::Missing, |&count| {
+                i64::try_from(count).unwrap_or(i64::MAX).into()
+            }),
+        6 => callsign::role_badge(&controller.callsign).map_or(SortKey::Missing, SortKey::text),
+        // by bucket, then by traffic within it
+        7 => controller_load(controller, traffic).map_or(SortKey::Missing, |load| {
+            let count = traffic.get(&controller.cid).copied().unwrap_or_default();
+            (load as i64 * 1_000_000 + i64::try_from(count).unwrap_or(999_999)).into()
+        }),
+        _ => SortKey::text(&controller.callsign),
+    }
+}
+
+/// Sort key for a controller in a coverage view column.
+///
+/// Relief, mentor, and instructor positions sort with the position they
+/// work alongside. Unparsable logon times sort last.
+fn coverage_sort_key(controller: &Controller, column: usize) -> SortKey {
+    match column {
+        1 => SortKey::text(&callsign::position_name(&controller.callsign)),
+        // online longest is logged on earliest
+        2 => controller
+            .logon()
+            .map_or(SortKey::Missing, |logon| (-logon.timestamp()).into()),
+        3 => controller
+            .logon()
+            .map_or(SortKey::Missing, |logon| logon.timestamp().into()),
+        4 => hints::closing_hint(controller.info_lines()).map_or(SortKey::Missing, SortKey::text),
+        _ => SortKey::text(&callsign::grouped_key(&controller.callsign)),
+    }
+}
+
+/// Sort key for an event in an events table column.
+///
+/// Unparsable times sort last.
+fn event_sort_key(event: &Event, column: usize) -> SortKey {
+    match column {
+        columns::EVENT_START_COLUMN => event
+            .start()
+            .map_or(SortKey::Missing, |start| start.timestamp().into()),
+        2 => event
+            .end()
+            .map_or(SortKey::Missing, |end| end.timestamp().into()),
+        3 => SortKey::text(&events::airports_text(event)),
+        4 => match events::timing(event, Utc::now()) {
+            Some(Timing::Active) => 0_i64.into(),
+            Some(Timing::Upcoming) => 1_i64.into(),
+            None => SortKey::Missing,
+        },
+        _ => SortKey::text(&event.name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{persist, testing};
+
+    /// Data with three controllers, CIDs 1 to 3.
+    fn controllers(callsigns: &[&str]) -> V3ResponseData {
+        let controllers = callsigns
+            .iter()
+            .zip(1..)
+            .map(|(callsign, cid)| Controller {
+                cid,
+                ..testing::controller(callsign, 4)
+            })
+            .collect();
+        V3ResponseData {
+            controllers,
+            ..V3ResponseData::default()
+        }
+    }
+
+    #[test]
+    fn session_round_trip() {
+        let data = controllers(&["EGLL_TWR", "EGKK_APP", "LON_S_CTR"]);
+        let mut app = App::new();
+        app.set_data(data.clone());
+        app.select_tab(1);
+        app.set_filter(Query::parse("callsign=EG* and name!=\"A (B)\"").unwrap());
+        app.sorts[0] = SortSpec {
+            column: columns::PILOT_DEPARTURE_COLUMN,
+            descending: true,
+            then: Some(0),
+        };
+        app.sorts[1].descending = true;
+        app.apply_sort(1);
+        app.toggle_pin_favorites();
+        app.select_cid(1, Some(2));
+
+        let session = app.session();
+        assert_eq!(session.selected_callsign.as_deref(), Some("EGKK_APP"));
+        let json = serde_json::to_string(&session).unwrap();
+        let loaded: Session = persist::parse(&json).unwrap();
+        assert_eq!(loaded, session);
+
+        let mut restored = App::new();
+        restored.restore_session(loaded);
+        restored.set_data(data);
+        assert_eq!(restored.tab_index, 1);
+        assert_eq!(restored.sorts, app.sorts);
+        assert_eq!(restored.filters, app.filters);
+        assert!(restored.pin_favorites);
+        assert_eq!(restored.selected_cid(1), Some(2));
+        assert_eq!(restored.session(), session);
+    }
+
+    #[test]
+    fn selected_callsign_no_longer_online() {
+        let mut app = App::new();
+        app.restore_session(Session {
+            tab_index: 1,
+            selected_callsign: Some("EGLL_TWR".to_owned()),
+            ..Session::default()
+        });
+        app.set_data(controllers(&["EGKK_APP", "LON_S_CTR"]));
+        assert_eq!(app.tab_index, 1);
+        assert_eq!(app.table_states[1].selected(), Some(0));
+        assert!(matches!(
+            app.get_selected_row_data(),
+            Some(SelectedRow::Controller(c)) if c.callsign == "EGKK_APP"
+        ));
+    }
+
+    #[test]
+    fn unreadable_sorts_and_filters_are_skipped() {
+        let mut app = App::new();
+        app.restore_session(Session {
+            sorts: vec![
+                "pilots:nothing".to_owned(),
+                "controllers:callsign:desc".to_owned(),
+            ],
+            filters: vec!["altitude=high".to_owned(), "callsign=EG*".to_owned()],
+            ..Session::default()
+        });
+        assert_eq!(app.sorts[0], DEFAULT_SORTS[0]);
+        assert!(app.sorts[1].descending);
+        assert!(app.filters[0].is_empty());
+        assert_eq!(app.filters[1].to_string(), "callsign=EG*");
+    }
+
+    /// An app with these pilots, given CIDs 1 and up.
+    fn with_pilots(pilots: Vec<Pilot>) -> App {
+        let pilots = pilots
+            .into_iter()
+            .zip(1..)
+            .map(|(pilot, cid)| Pilot { cid, ..pilot })
+            .collect();
+        let mut app = App::new();
+        app.set_data(V3ResponseData {
+            pilots,
+            ..V3ResponseData::default()
+        });
+        app
+    }
+
+    /// Callsigns in the rows of a "tab", in order.
+    fn shown(app: &App, tab_index: usize) -> Vec<&str> {
+        app.rows[tab_index]
+            .iter()
+            .map(|&i| match tab_index {
+                0 => app.data.pilots[i].callsign.as_str(),
+                _ => app.data.controllers[i].callsign.as_str(),
+            })
+            .collect()
+    }
+
+    /// A pilot flying under some flight rules.
+    fn rules(callsign: &str, rules: &str) -> Pilot {
+        let mut pilot = testing::flying(callsign, "EGLL", "KJFK");
+        if let Some(plan) = &mut pilot.flight_plan {
+            plan.flight_rules = rules.to_owned();
+        }
+        pilot
+    }
+
+    #[test]
+    fn airline_filter_composes_with_aircraft_and_airport_queries() {
+        let mut heavy = testing::flying("DLH400", "EDDF", "KJFK");
+        if let Some(plan) = &mut heavy.flight_plan {
+            plan.aircraft_faa = "B748".to_owned();
+        }
+        let mut app = with_pilots(vec![
+            testing::flying("BAW1", "EGLL", "KJFK"),
+            testing::flying("DLH1", "EDDF", "EGLL"),
+            testing::flying("DLH2", "EGLL", "KJFK"),
+            heavy,
+            testing::flying("N123AB", "EGLL", "KJFK"),
+        ]);
+        app.set_airline_filter(Some(AirlineFilter::parse("dlh, baw").unwrap()));
+        assert_eq!(shown(&app, 0), ["BAW1", "DLH1", "DLH2", "DLH400"]);
+        assert!(app.get_selected_title().contains("Airlines: DLH, BAW"));
+
+        app.set_filter(Query::parse("departure=EGLL").unwrap());
+        assert_eq!(shown(&app, 0), ["BAW1", "DLH2"]);
+        app.set_filter(Query::parse("aircraft=B748 and arrival=KJFK").unwrap());
+        assert_eq!(shown(&app, 0), ["DLH400"]);
+
+        app.set_airline_filter(Some(AirlineFilter::parse("BAW").unwrap()));
+        assert!(shown(&app, 0).is_empty());
+        app.set_airline_filter(None);
+        assert_eq!(shown(&app, 0), ["DLH400"]);
+    }
+
+    #[test]
+    fn dismissed_tour_is_saved_as_onboarded() {
+        let mut app = App::new();
+        assert!(app.session().onboarded);
+        app.show_tour();
+        assert!(app.get_view_data().tour);
+        assert!(!app.session().onboarded);
+        assert!(app.dismiss_tour());
+        assert!(!app.dismiss_tour());
+        assert!(!app.get_view_data().tour);
+        assert!(app.session().onboarded);
+    }
+
+    #[test]
+    fn retained_entries_stay_bounded_over_many_refreshes() {
+        let mut app = App::new();
+        let mut peak = 0;
+        for refresh in 0..2_000_i64 {
+            // a few pilots stay online while the rest come and go
+            let pilots = (0..10)
+                .map(|i| {
+                    let cid = if i < 3 { i } else { refresh * 10 + i };
+                    Pilot {
+                        cid,
+                        altitude: refresh % 7 * 1_000,
+                        last_updated: format!(
+                            "2022-08-01T{:02}:{:02}:{:02}Z",
+                            refresh / 3600,
+                            refresh / 60 % 60,
+                            refresh % 60
+                        ),
+                        ..testing::flying(&format!("BAW{}", cid), "EGLL", "KJFK")
+                    }
+                })
+                .collect();
+            let mut controller = testing::controller(&format!("EGLL_{}_TWR", refresh % 50), 4);
+            controller.cid = refresh;
+            controller.text_atis = Some(vec![format!("Information {}", refresh)]);
+            app.set_data(V3ResponseData {
+                pilots,
+                controllers: vec![controller],
+                ..V3ResponseData::default()
+            });
+            app.history.push(&format!("callsign=BAW{}", refresh));
+            peak = peak.max(app.retained_entries());
+        }
+        assert_eq!(app.history.entries().len(), crate::history::MAX_ENTRIES);
+        // a full trail for each pilot who stayed, and a point for the rest
+        let trails = 3 * crate::trail::TRAIL_LENGTH + 7;
+        assert_eq!(app.trails.retained(), trails);
+        assert!(
+            peak <= 10 * 4 + 1 + 2 + crate::history::MAX_ENTRIES + trails,
+            "{}",
+            peak
+        );
+    }
+
+    #[test]
+    fn title_shows_the_update_time_in_the_zone() {
+        let mut app = with_pilots(vec![testing::pilot("BAW1")]);
+        let mut data = app.data.clone();
+        data.general.update_timestamp = "2022-08-01T23:30:00.1234567Z".to_owned();
+        app.set_data(data);
+        app.set_zone(DisplayZone::Utc);
+        assert!(app.get_selected_title().contains("[Updated 23:30z]"));
+        app.set_zone(DisplayZone::parse("+02:00").unwrap());
+        assert!(app
+            .get_selected_title()
+            .contains("[Updated 01:30 UTC+02:00]"));
+        app.select_tab(2);
+        assert!(!app.get_selected_title().contains("Updated"));
+    }
+
+    #[test]
+    fn startup_deadline_fails_loading_once_per_attempt() {
+        let mut app = App::new();
+        app.set_loading();
+        let far = Instant::now() + Duration::from_secs(3_600);
+        assert!(!app.check_startup_deadline(far));
+
+        app.set_startup_timeout(Some(Duration::from_secs(30)));
+        app.set_loading();
+        let start = Instant::now();
+        assert!(!app.check_startup_deadline(start + Duration::from_secs(29)));
+        assert!(app.check_startup_deadline(start + Duration::from_secs(31)));
+        assert_eq!(
+            app.load_state,
+            LoadState::Failed("Timed out after 30s waiting for data".to_owned())
+        );
+        assert!(!app.check_startup_deadline(start + Duration::from_secs(60)));
+
+        // a retry gets a fresh deadline
+        app.set_loading();
+        let retried = Instant::now();
+        assert!(!app.check_startup_deadline(retried + Duration::from_secs(29)));
+        app.set_data(V3ResponseData::default());
+        assert!(!app.check_startup_deadline(retried + Duration::from_secs(60)));
+        assert!(app.is_loaded());
+    }
+
+    #[test]
+    fn load_column_sorts_by_bucket_then_traffic() {
+        let near_sfo = |nm: i32| Pilot {
+            cid: nm.into(),
+            latitude: 37.62 + f64::from(nm) / 60.0,
+            longitude: -122.38,
+            altitude: 4_000,
+            ..testing::pilot(&format!("N{}", nm))
+        };
+        let mut app = App::new();
+        app.set_data(V3ResponseData {
+            pilots: (1..=6).map(near_sfo).collect(),
+            controllers: [
+                ("SFO_TWR", 4),
+                ("SFO_APP", 5),
+                ("SFO_CTR", 6),
+                ("LAX_APP", 5),
+            ]
+            .into_iter()
+            .zip(1..)
+            .map(|((callsign, facility), cid)| Controller {
+                cid,
+                ..testing::controller(callsign, facility)
+            })
+            .collect(),
+            ..V3ResponseData::default()
+        });
+        app.select_tab(1);
+        app.sort_by_column(7);
+        // Low with none, Low with six, Medium with six, then no load
+        assert_eq!(shown(&app, 1), ["LAX_APP", "SFO_CTR", "SFO_APP", "SFO_TWR"]);
+        let view = app.get_view_data();
+        let badges: Vec<(&str, Vec<(usize, Style)>)> = view
+            .data
+            .iter()
+            .map(|row| (row.cells[7].as_str(), row.cell_styles.clone()))
+            .collect();
+        assert_eq!(
+            badges,
+            [
+                ("Low", vec![(7, Style::default().fg(Color::Green))]),
+                ("Low", vec![(7, Style::default().fg(Color::Green))]),
+                ("Medium", vec![(7, Style::default().fg(Color::Yellow))]),
+                ("", Vec::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn ghosts_are_marked_and_left_out_of_counts_and_rates() {
+        let refresh = |altitude: i64, updated: &str| V3ResponseData {
+            pilots: vec![
+                Pilot {
+                    cid: 5,
+                    altitude,
+                    groundspeed: 300,
+                    last_updated: updated.to_owned(),
+                    ..testing::flying("BAW1", "EGLL", "KJFK")
+                },
+                // left behind by a crash, and never updated again
+                Pilot {
+                    cid: 5,
+                    altitude: 0,
+                    last_updated: "2022-08-01T11:00:00Z".to_owned(),
+                    ..testing::pilot("BAW1")
+                },
+                Pilot {
+                    cid: 6,
+                    ..testing::pilot("UAL2")
+                },
+            ],
+            controllers: vec![
+                Controller {
+                    cid: 5,
+                    ..testing::controller("EGLL_TWR", 4)
+                },
+                Controller {
+                    cid: 7,
+                    ..testing::controller("EGLL_GND", 3)
+                },
+            ],
+            ..V3ResponseData::default()
+        };
+        let mut app = App::new();
+        app.set_data(refresh(10_000, "2022-08-01T12:00:00Z"));
+        app.set_data(refresh(11_000, "2022-08-01T12:01:00Z"));
+        let rate = app.vertical_rates[&5];
+        assert!((rate - 1_000.0).abs() < 1.0, "{}", rate);
+        // the ghost shares its primary's callsign, but has its own enrichment
+        let primary = &app.enriched[&ConnectionKey::of(&app.data.pilots[0])];
+        let ghost = &app.enriched[&ConnectionKey::of(&app.data.pilots[1])];
+        assert_eq!((primary.phase, primary.ghost), (Phase::Climb, false));
+        assert_eq!((ghost.phase, ghost.ghost), (Phase::Unknown, true));
+        assert_eq!(app.enriched.len(), 3);
+
+        let callsigns: Vec<String> = app
+            .get_view_data()
+            .data
+            .iter()
+            .map(|row| row.cells[0].clone())
+            .collect();
+        assert!(callsigns.contains(&"BAW1 ghost?".to_owned()));
+        assert!(callsigns.contains(&"BAW1".to_owned()));
+        // a pilot who is also controlling isn't a ghost
+        assert_eq!(app.tab_counts(0), (2, 2));
+        assert_eq!(app.tab_counts(1), (2, 2));
+
+        let _ = app.update(Action::ToggleCountGhosts);
+        assert_eq!(app.tab_counts(0), (3, 3));
+        let _ = app.update(Action::ToggleCountGhosts);
+        assert_eq!(app.tab_counts(0), (2, 2));
+    }
+
+    #[test]
+    fn ghosts_under_their_own_callsign_are_enriched() {
+        let mut app = App::new();
+        app.set_data(V3ResponseData {
+            pilots: vec![
+                Pilot {
+                    cid: 5,
+                    last_updated: "2022-08-01T12:00:00Z".to_owned(),
+                    ..testing::flying("BAW1", "EGLL", "KJFK")
+                },
+                // left behind under the callsign flown before reconnecting
+                Pilot {
+                    cid: 5,
+                    last_updated: "2022-08-01T11:00:00Z".to_owned(),
+                    ..testing::flying("BAW1X", "EGLL", "KJFK")
+                },
+            ],
+            ..V3ResponseData::default()
+        });
+        let ghost = app
+            .get_view_data()
+            .data
+            .into_iter()
+            .find(|row| row.cells[0] == "BAW1X ghost?")
+            .unwrap();
+        let primary = app
+            .get_view_data()
+            .data
+            .into_iter()
+            .find(|row| row.cells[0] == "BAW1")
+            .unwrap();
+        assert!(
+            !ghost.cells.contains(&"???".to_owned()),
+            "{:?}",
+            ghost.cells
+        );
+        assert_eq!(ghost.cells[1..], primary.cells[1..]);
+        let [primary, ghost] =
+            [0, 1].map(|i| &app.enriched[&ConnectionKey::of(&app.data.pilots[i])]);
+        assert_eq!(ghost.phase, primary.phase);
+        assert!(ghost.ghost && !primary.ghost);
+    }
+
+    #[test]
+    fn names_are_hidden_before_anything_sees_them() {
+        let app = |privacy: Privacy| {
+            let mut app = App::new();
+            app.set_privacy(privacy);
+            app.set_filter(Query::parse("name=\"John Smith\"").unwrap());
+            app.set_data(V3ResponseData {
+                pilots: vec![Pilot {
+                    name: "John Smith".to_owned(),
+                    ..testing::pilot("BAW1")
+                }],
+                ..V3ResponseData::default()
+            });
+            app
+        };
+        assert_eq!(app(Privacy::Full).get_view_data().data.len(), 1);
+        let mut private = app(Privacy::Cid);
+        assert_eq!(private.data.pilots[0].name, "1000000");
+        // the filter can't find a pilot by the hidden name either
+        assert!(private.get_view_data().data.is_empty());
+    }
+
+    #[test]
+    fn changed_atis_is_marked_in_the_callsign() {
+        let refresh = |letter: &str| V3ResponseData {
+            controllers: vec![Controller {
+                text_atis: Some(vec![format!("INFO {}", letter)]),
+                ..testing::controller("EGLL_TWR", 4)
+            }],
+            ..V3ResponseData::default()
+        };
+        let mut app = App::new();
+        app.select_tab(1);
+        let callsign = |app: &mut App| app.get_view_data().data[0].cells[0].clone();
+        app.set_data(refresh("A"));
+        assert_eq!(callsign(&mut app), "EGLL_TWR");
+        app.set_data(refresh("B"));
+        assert_eq!(callsign(&mut app), "EGLL_TWR ATIS updated");
+        app.set_data(refresh("B"));
+        app.set_data(refresh("B"));
+        assert_eq!(callsign(&mut app), "EGLL_TWR ATIS updated");
+        app.set_data(refresh("B"));
+        assert_eq!(callsign(&mut app), "EGLL_TWR");
+    }
+
+    #[test]
+    fn each_tab_keeps_its_own_filter() {
+        let mut data = controllers(&["LON_S_CTR", "EGLL_TWR", "LON_N_CTR"]);
+        data.pilots = vec![
+            testing::flying("BAW1", "KJFK", "EGLL"),
+            Pilot {
+                cid: 2,
+                ..testing::flying("UAL2", "EGLL", "KJFK")
+            },
+        ];
+        let mut app = App::new();
+        app.set_data(data.clone());
+        app.set_filter(Query::parse("arrival=EGLL").unwrap());
+        app.select_tab(1);
+        app.set_filter(Query::parse("callsign=LON_*").unwrap());
+        assert_eq!(shown(&app, 0), ["BAW1"]);
+        assert_eq!(shown(&app, 1), ["LON_N_CTR", "LON_S_CTR"]);
+        assert_eq!(
+            app.filter_summary().unwrap(),
+            "Pilots: arrival=EGLL   Controllers: callsign=LON_*   (Ctrl+L clears this tab's, Ctrl+Shift+L all)"
+        );
+
+        app.select_tab(0);
+        app.set_data(data.clone());
+        assert_eq!(shown(&app, 0), ["BAW1"]);
+        assert_eq!(shown(&app, 1), ["LON_N_CTR", "LON_S_CTR"]);
+
+        // clearing the pilots' filter leaves the controllers'
+        let _ = app.update(Action::ClearFilter);
+        assert_eq!(shown(&app, 0), ["BAW1", "UAL2"]);
+        assert_eq!(shown(&app, 1), ["LON_N_CTR", "LON_S_CTR"]);
+        assert_eq!(
+            app.filter_summary().unwrap(),
+            "Controllers: callsign=LON_*   (Ctrl+L clears this tab's, Ctrl+Shift+L all)"
+        );
+        // and clearing an already clear tab says nothing
+        app.status = None;
+        let _ = app.update(Action::ClearFilter);
+        assert_eq!(app.status, None);
+
+        app.set_filter(Query::parse("departure=EGLL").unwrap());
+        let _ = app.update(Action::ClearAllFilters);
+        assert_eq!(shown(&app, 0), ["BAW1", "UAL2"]);
+        assert_eq!(shown(&app, 1), ["EGLL_TWR", "LON_N_CTR", "LON_S_CTR"]);
+        assert_eq!(app.filter_summary(), None);
+        assert_eq!(app.status.as_deref(), Some("Cleared all filters"));
+    }
+
+    #[test]
+    fn special_only_shows_special_flights() {
+        let mut marsa = testing::flying("DAL1", "KSFO", "KJFK");
+        if let Some(plan) = &mut marsa.flight_plan {
+            plan.remarks = "MARSA".to_owned();
+        }
+        let mut app = with_pilots(vec![
+            testing::flying("ASCOT1", "EGLL", "KJFK"),
+            testing::flying("BAW1", "EGLL", "KJFK"),
+            marsa,
+            testing::flying("VIPER11", "KSFO", "KLAX"),
+        ]);
+        let _ = app.update(Action::ToggleSpecialOnly);
+        assert_eq!(shown(&app, 0), ["ASCOT1", "DAL1", "VIPER11"]);
+        assert!(app.get_selected_title().contains("[Special operations]"));
+        let _ = app.update(Action::ToggleSpecialOnly);
+        assert_eq!(shown(&app, 0), ["ASCOT1", "BAW1", "DAL1", "VIPER11"]);
+    }
+
+    #[test]
+    fn pastes_go_to_the_open_prompt_only() {
+        let mut app = with_pilots(vec![testing::pilot("BAW1"), testing::pilot("DAL2")]);
+        // outside a prompt a paste is ignored rather than run as keys
+        app.paste("q/t");
+        assert!(app.search.is_none() && app.tune.prompt().is_none());
+
+        let _ = app.update(Action::OpenSearch);
+        type_text(&mut app, "cs:");
+        app.paste("BAW1\n");
+        assert_eq!(search_text(&app), "cs:BAW1");
+        let _ = app.update(Action::Edit(Edit::Home));
+        app.paste("q ");
+        assert_eq!(search_text(&app), "q cs:BAW1");
+        let _ = app.update(Action::Cancel);
+
+        let _ = app.update(Action::OpenTune);
+        app.paste("121.5\r\n");
+        assert_eq!(app.tune.prompt().unwrap().input.text(), "121.5");
+    }
+
+    /// A flight with filed fuel and enroute times.
+    fn fueled(callsign: &str, fuel_time: &str, enroute_time: &str) -> Pilot {
+        let mut pilot = testing::flying(callsign, "EGLL", "KJFK");
+        if let Some(plan) = &mut pilot.flight_plan {
+            plan.fuel_time = fuel_time.to_owned();
+            plan.enroute_time = enroute_time.to_owned();
+        }
+        pilot
+    }
+
+    #[test]
+    fn fuel_cells_carry_the_badge() {
+        let cell =
+            |fuel, enroute| fuel_cell(fueled("BAW1", fuel, enroute).flight_plan.as_ref().unwrap());
+        assert_eq!(cell("0830", "0700"), "8h 30m");
+        assert_eq!(cell("0740", "0700"), "7h 40m TIGHT");
+        assert_eq!(cell("0700", "0730"), "7h 00m SHORT");
+        assert_eq!(cell("0830", "0000"), "8h 30m");
+        assert_eq!(cell("0000", "0700"), "");
+    }
+
+    #[test]
+    fn fuel_sorts_by_the_time_to_spare() {
+        let mut app = with_pilots(vec![
+            fueled("AAL1", "0830", "0700"),
+            fueled("BAW2", "0700", "0730"),
+            fueled("DAL3", "", ""),
+            fueled("UAL4", "0740", "0700"),
+        ]);
+        app.sort_by_column(columns::PILOT_FUEL_COLUMN);
+        assert_eq!(shown(&app, 0), ["BAW2", "UAL4", "AAL1", "DAL3"]);
+        app.sort_by_column(columns::PILOT_ENROUTE_COLUMN);
+        assert_eq!(shown(&app, 0), ["AAL1", "UAL4", "BAW2", "DAL3"]);
+    }
+
+    #[test]
+    fn custom_columns_show_cells_from_the_last_refresh() {
+        let mut app = App::new();
+        app.set_custom_columns(
+            custom::parse(r#"{"version": 1, "pilots": [{"header": "Server", "path": "server"}]}"#)
+                .unwrap(),
+        );
+        app.set_data(V3ResponseData {
+            pilots: vec![Pilot {
+                server: "UK-1".to_owned(),
+                ..testing::pilot("BAW1")
+            }],
+            ..V3ResponseData::default()
+        });
+        let view = app.get_view_data();
+        assert_eq!(view.columns.last().unwrap().header, "Server");
+        assert_eq!(view.data[0].cells.last().unwrap(), "UK-1");
+
+        // drawing uses the cells worked out when the data arrived
+        app.data.pilots[0].server = "USA-WEST".to_owned();
+        assert_eq!(app.get_view_data().data[0].cells.last().unwrap(), "UK-1");
+
+        // the next refresh works them out again
+        app.set_data(V3ResponseData {
+            pilots: vec![testing::pilot("BAW1")],
+            ..V3ResponseData::default()
+        });
+        assert_eq!(
+            app.get_view_data().data[0].cells.last().unwrap(),
+            "USA-EAST"
+        );
+        // and a record without cells shows a dash
+        app.custom_cells[0].clear();
+        assert_eq!(
+            app.get_view_data().data[0].cells.last().unwrap(),
+            "\u{2014}"
+        );
+    }
+
+    #[test]
+    fn malformed_frequencies_agree_everywhere() {
+        let mut data = controllers(&["EGLL_TWR", "EGKK_APP", "LON_S_CTR", "EGLL_OBS", "EGPH_TWR"]);
+        for (controller, frequency) in
+            data.controllers
+                .iter_mut()
+                .zip(["122.80", "1.99998e2", "99.998", "n/a", "118.5"])
+        {
+            controller.frequency = frequency.to_owned();
+        }
+        let mut app = App::new();
+        app.set_data(data);
+        app.select_tab(1);
+        app.sort_by_column(3);
+        let view = app.get_view_data();
+        let cells: Vec<(&str, bool)> = view
+            .data
+            .iter()
+            .map(|row| {
+                let dim = row.cell_styles.iter().any(|&(column, style)| {
+                    column == 3 && style.add_modifier.contains(Modifier::DIM)
+                });
+                (row.cells[3].as_str(), dim)
+            })
+            .collect();
+        assert_eq!(
+            cells,
+            [
+                ("99.998", true),
+                ("118.500", false),
+                ("122.800", false),
+                ("199.998", true),
+                ("n/a", true),
+            ]
+        );
+
+        tune(&mut app, "122.8");
+        assert_eq!(shown(&app, 1), ["EGLL_TWR"]);
+        tune(&mut app, "");
+        app.set_filter(Query::parse("frequency=199.998").unwrap());
+        assert_eq!(shown(&app, 1), ["EGKK_APP"]);
+    }
+
+    #[test]
+    fn atc_column_shows_the_most_specific_cover() {
+        let mut app = with_pilots(vec![
+            Pilot {
+                latitude: 30.0,
+                longitude: -122.36,
+                ..testing::pilot("AWAY")
+            },
+            Pilot {
+                latitude: 37.62,
+                longitude: -122.36,
+                ..testing::pilot("UAL1")
+            },
+        ]);
+        let mut data = app.data.clone();
+        data.controllers = [("SFO_APP", 5), ("SFO_TWR", 4)]
+            .into_iter()
+            .zip(1..)
+            .map(|((callsign, facility), cid)| Controller {
+                cid,
+                visual_range: 50,
+                ..testing::controller(callsign, facility)
+            })
+            .collect();
+        app.set_data(data);
+        app.sort_by_column(columns::PILOT_ATC_COLUMN);
+        let cells: Vec<(String, String)> = app
+            .get_view_data()
+            .data
+            .into_iter()
+            .map(|row| {
+                (
+                    row.cells[0].clone(),
+                    row.cells[columns::PILOT_ATC_COLUMN].clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            cells,
+            [
+                ("UAL1".to_owned(), "SFO_TWR".to_owned()),
+                ("AWAY".to_owned(), UNICOM.to_owned()),
+            ]
+        );
+
+        app.select_cid(0, Some(2));
+        assert!(app.covering_atc().is_empty());
+        app.toggle_popup(true);
+        let covering: Vec<String> = app.covering_atc().into_iter().map(|n| n.callsign).collect();
+        assert_eq!(covering, ["SFO_TWR", "SFO_APP"]);
+    }
+
+    #[test]
+    fn region_filter_drills_down_into_a_region() {
+        let mut app = App::new();
+        app.set_data(controllers(&[
+            "EGLL_TWR",
+            "KJFK_TWR",
+            "EGTT_CTR",
+            "LON_S_CTR",
+        ]));
+        app.select_tab(1);
+        app.set_filter(Query::parse("region=UK").unwrap());
+        assert_eq!(shown(&app, 1), ["EGLL_TWR", "EGTT_CTR"]);
+        app.set_filter(Query::parse("region=Other").unwrap());
+        assert_eq!(shown(&app, 1), ["LON_S_CTR"]);
+    }
+
+    #[test]
+    fn adaptive_interval_in_the_title() {
+        let mut app = with_pilots(vec![testing::pilot("BAW1")]);
+        assert!(!app.get_selected_title().contains("Polling"));
+        app.set_poll_interval(Some(Duration::from_secs(45)));
+        assert!(app.get_selected_title().ends_with(" [Polling every 45s]"));
+        app.select_tab(1);
+        assert!(app.get_selected_title().ends_with(" [Polling every 45s]"));
+        app.set_poll_interval(None);
+        assert!(!app.get_selected_title().contains("Polling"));
+    }
+
+    #[test]
+    fn facility_picker_filters_controllers_and_is_saved() {
+        let mut data = controllers(&[
+            "EGLL_DEL",
+            "EGLL_GND",
+            "EGLL_TWR",
+            "EGLL_ATIS",
+            "EGLL_APP",
+            "LON_CTR",
+            "EGLL_OBS",
+        ]);
+        for (controller, facility) in data.controllers.iter_mut().zip([2, 3, 4, 4, 5, 6, 0]) {
+            controller.facility = facility;
+        }
+        let mut app = App::new();
+        app.set_data(data);
+        app.select_tab(1);
+        assert!(!app.get_selected_title().contains("TWR+"));
+
+        // DEL, GND, TWR, APP, CTR, FSS, ATIS, OBS: untick DEL, GND, ATIS, OBS
+        let _ = app.update(Action::OpenFacilityPicker);
+        for rows in [0, 1, 5, 1] {
+            let _ = app.update(Action::MoveSelection(rows));
+            let _ = app.update(Action::ToggleItem);
+        }
+        // cancelling changes nothing
+        let _ = app.update(Action::Cancel);
+        assert_eq!(shown(&app, 1).len(), 7);
+
+        let _ = app.update(Action::OpenFacilityPicker);
+        for rows in [0, 1, 5, 1] {
+            let _ = app.update(Action::MoveSelection(rows));
+            let _ = app.update(Action::ToggleItem);
+        }
+        let _ = app.update(Action::Submit);
+        assert!(app.facility_picker.is_none());
+        assert_eq!(shown(&app, 1), ["EGLL_APP", "EGLL_TWR", "LON_CTR"]);
+        assert!(app.get_selected_title().contains("[TWR+APP+CTR+FSS]"));
+        // only on the controllers "tab"
+        app.select_tab(0);
+        assert!(!app.get_selected_title().contains("TWR+"));
+
+        // saved, and applied on the next run
+        let session = app.session();
+        assert_eq!(session.hidden_facilities, ["DEL", "GND", "ATIS", "OBS"]);
+        let json = serde_json::to_string(&session).unwrap();
+        let loaded: Session = persist::parse(&json).unwrap();
+        let mut next = App::new();
+        next.set_hidden_facilities(&loaded.hidden_facilities);
+        next.set_data(app.data.clone());
+        next.select_tab(1);
+        assert_eq!(shown(&next, 1), ["EGLL_APP", "EGLL_TWR", "LON_CTR"]);
+        assert!(next.get_selected_title().contains("[TWR+APP+CTR+FSS]"));
+
+        // reopening ticks what's shown
+        let _ = next.update(Action::OpenFacilityPicker);
+        assert_eq!(
+            next.facility_picker.as_ref().unwrap().unticked(),
+            ["DEL", "GND", "ATIS", "OBS"]
+        );
+    }
+
+    #[test]
+    fn failed_refreshes_escalate_to_the_panel_and_clear() {
+        let mut app = with_pilots(vec![testing::pilot("BAW1")]);
+        let fail = |app: &mut App, n: usize| {
+            app.refresh_failed(&anyhow::anyhow!("timed out {}", n).context("mirror down"));
+        };
+        fail(&mut app, 1);
+        assert_eq!(
+            app.get_view_data().status.as_deref(),
+            Some("Could not refresh: mirror down: timed out 1")
+        );
+        assert!(!app.shows_refresh_error());
+        assert_eq!(app.get_view_data().refresh_error, None);
+        assert_eq!(app.update(Action::NextMirror), None);
+        fail(&mut app, 2);
+        assert!(!app.shows_refresh_error());
+
+        fail(&mut app, 3);
+        app.set_fetch_stats(FetchStats {
+            mirrors_tried: vec!["https://a.example/v3".to_owned()],
+            ..FetchStats::default()
+        });
+        assert!(app.shows_refresh_error());
+        assert_eq!(
+            app.get_view_data().refresh_error,
+            Some(RefreshError {
+                message: "mirror down: timed out 3".to_owned(),
+                failures: 3,
+                mirrors_tried: vec!["https://a.example/v3".to_owned()],
+            })
+        );
+        // the data stays up underneath
+        assert_eq!(shown(&app, 0), ["BAW1"]);
+        assert_eq!(app.update(Action::Retry), Some(Effect::Fetch));
+        assert_eq!(app.update(Action::NextMirror), Some(Effect::NextMirror));
+        fail(&mut app, 4);
+        assert_eq!(app.get_view_data().refresh_error.unwrap().failures, 4);
+
+        // the first success clears everything, and counting starts over
+        app.refresh_succeeded();
+        assert!(!app.shows_refresh_error());
+        let view_data = app.get_view_data();
+        assert_eq!(view_data.refresh_error, None);
+        assert_eq!(view_data.status, None);
+        fail(&mut app, 5);
+        fail(&mut app, 6);
+        assert!(!app.shows_refresh_error());
+
+        // other messages outlive a recovery
+        app.set_status("Copied".to_owned());
+        app.refresh_succeeded();
+        assert_eq!(app.get_view_data().status.as_deref(), Some("Copied"));
+    }
+
+    #[test]
+    fn airport_split_panes_and_selection() {
+        let mut app = with_pilots(vec![
+            testing::flying("BAW1", "KJFK", "EGLL"),
+            testing::flying("DLH2", "EGLL", "EDDF"),
+            testing::flying("AFR3", "LFPG", "EGLL"),
+            testing::flying("G-ABCD", "EGLL", "EGLL"),
+            testing::flying("KLM5", "EHAM", "LFPG"),
+        ]);
+        // not without one airport
+        let _ = app.update(Action::ToggleSplit);
+        assert!(app.get_view_data().split.is_none());
+        assert!(app.get_view_data().status.unwrap().contains("arrival=EGLL"));
+
+        app.set_filter(Query::parse("arrival=EGLL or departure=EGLL").unwrap());
+        let _ = app.update(Action::ToggleSplit);
+        let split = app.get_view_data().split.unwrap();
+        assert_eq!(split.airport, "EGLL");
+        let callsigns = |split: &SplitPanes, pane: Pane| -> Vec<String> {
+            split.rows[pane.index()]
+                .iter()
+                .map(|cells| cells[0].clone())
+                .collect()
+        };
+        let mut inbound = callsigns(&split, Pane::Inbound);
+        inbound.sort();
+        // the pattern flight is in both, marked
+        let pattern = format!("G-ABCD{}", PATTERN_MARKER);
+        assert_eq!(inbound, ["AFR3", "BAW1", pattern.as_str()]);
+        let mut outbound = callsigns(&split, Pane::Outbound);
+        outbound.sort();
+        assert_eq!(outbound, ["DLH2", pattern.as_str()]);
+        assert_eq!(split.focus, Pane::Inbound);
+        assert_eq!(split.selected, [0, 0]);
+
+        // each pane keeps its own selection, by pilot
+        let _ = app.update(Action::MoveSelection(1));
+        let split = app.get_view_data().split.unwrap();
+        assert_eq!(split.selected, [1, 0]);
+        let chosen = callsigns(&split, Pane::Inbound)[1].clone();
+        let _ = app.update(Action::ToggleTab);
+        let _ = app.update(Action::MoveSelection(-1));
+        let split = app.get_view_data().split.unwrap();
+        assert_eq!(split.focus, Pane::Outbound);
+        assert_eq!(split.selected, [1, 1]);
+
+        // the popup opens on the focused pane's pilot
+        let _ = app.update(Action::OpenPopup);
+        let view_data = app.get_view_data();
+        assert!(view_data.show_popup);
+        let outbound_pick = callsigns(&split, Pane::Outbound)[1].clone();
+        match view_data.selected_row_data {
+            Some(SelectedRow::Pilot(pilot)) => {
+                assert!(outbound_pick.starts_with(&pilot.callsign));
+            }
+            _ => panic!("no pilot selected"),
+        }
+        app.toggle_popup(false);
+        let _ = app.update(Action::ToggleTab);
+        match app.get_view_data().selected_row_data {
+            Some(SelectedRow::Pilot(pilot)) => assert!(chosen.starts_with(&pilot.callsign)),
+            _ => panic!("no pilot selected"),
+        }
+
+        // a refresh reordering the pilots keeps the selection
+        let mut data = app.data.clone();
+        data.pilots.reverse();
+        app.set_data(data);
+        let split = app.get_view_data().split.unwrap();
+        assert_eq!(callsigns(&split, Pane::Inbound)[split.selected[0]], chosen);
+
+        // an empty pane has nothing to open
+        app.set_filter(Query::parse("arrival=EGLL").unwrap());
+        let split = app.get_view_data().split.unwrap();
+        assert_eq!(split.rows[Pane::Outbound.index()].len(), 1);
+        app.set_filter(Query::parse("arrival=EGLL and callsign=BAW*").unwrap());
+        let _ = app.update(Action::ToggleTab);
+        let split = app.get_view_data().split.unwrap();
+        assert_eq!(split.focus, Pane::Outbound);
+        assert!(split.rows[Pane::Outbound.index()].is_empty());
+        let _ = app.update(Action::OpenPopup);
+        assert!(!app.get_view_data().show_popup);
+
+        // closed once the filter names another airport, or none
+        app.set_filter(Query::parse("arrival=EGKK").unwrap());
+        assert!(app.get_view_data().split.is_none());
+    }
+
+    /// Type into the search prompt.
+    fn type_text(app: &mut App, text: &str) {
+        for c in text.chars() {
+            let _ = app.update(Action::InputChar(c));
+        }
+    }
+
+    /// Text in the search prompt.
+    fn search_text(app: &App) -> &str {
+        app.search.as_ref().unwrap().input.text()
+    }
+
+    #[test]
+    fn tab_completes_from_the_current_data() {
+        let mut app = with_pilots(vec![
+            testing::flying("BAW1", "EGLL", "KJFK"),
+            testing::flying("DLH1", "EDDF", "egkk"),
+        ]);
+        let _ = app.update(Action::OpenSearch);
+        type_text(&mut app, "dep");
+        let _ = app.update(Action::Complete);
+        assert_eq!(search_text(&app), "departure");
+
+        type_text(&mut app, "=eg");
+        let _ = app.update(Action::Complete);
+        assert_eq!(search_text(&app), "departure=EGLL");
+        let _ = app.update(Action::Complete);
+        assert_eq!(search_text(&app), "departure=egkk");
+        let _ = app.update(Action::Complete);
+        assert_eq!(search_text(&app), "departure=EGLL");
+
+        app.set_data(V3ResponseData {
+            pilots: vec![testing::flying("BAW1", "EGPH", "KJFK")],
+            ..V3ResponseData::default()
+        });
+        type_text(&mut app, " or callsign=");
+        let _ = app.update(Action::Complete);
+        assert_eq!(search_text(&app), "departure=EGLL or callsign=BAW1");
+        type_text(&mut app, " or arrival=eg");
+        let _ = app.update(Action::Complete);
+        assert_eq!(
+            search_text(&app),
+            "departure=EGLL or callsign=BAW1 or arrival=EGPH"
+        );
+    }
+
+    #[test]
+    fn rules_query_and_airline_filters_compose() {
+        let mut app = with_pilots(vec![
+            rules("BAW1", "I"),
+            rules("BAW2", "V"),
+            rules("DLH1", "I"),
+            testing::pilot("GABCD"),
+        ]);
+        app.cycle_rules_filter();
+        assert_eq!(shown(&app, 0), ["BAW1", "DLH1"]);
+        assert!(app.get_selected_title().contains("[IFR only]"));
+
+        app.set_filter(Query::parse("callsign=*1").unwrap());
+        app.set_airline_filter(Some(AirlineFilter::parse("DLH").unwrap()));
+        assert_eq!(shown(&app, 0), ["DLH1"]);
+
+        app.set_airline_filter(None);
+        app.cycle_rules_filter();
+        assert!(shown(&app, 0).is_empty());
+        app.set_filter(Query::default());
+        assert_eq!(shown(&app, 0), ["BAW2"]);
+        app.cycle_rules_filter();
+        assert_eq!(shown(&app, 0), ["GABCD"]);
+        app.cycle_rules_filter();
+        assert_eq!(shown(&app, 0).len(), 4);
+        assert!(!app.get_selected_title().contains('['));
+    }
+
+    /// An app with `count` pilots, CIDs 1 and up.
+    fn many_pilots(count: i64) -> App {
+        let pilots = (1..=count)
+            .map(|cid| Pilot {
+                cid,
+                ..testing::flying(&format!("TST{cid}"), "KSFO", "KLAX")
+            })
+            .collect();
+        let mut app = App::new();
+        app.set_data(V3ResponseData {
+            pilots,
+            ..V3ResponseData::default()
+        });
+        app
+    }
+
+    #[test]
+    fn every_row_is_built_before_the_table_is_drawn() {
+        let mut app = many_pilots(100);
+        let view = app.get_view_data();
+        assert_eq!(view.data.len(), 100);
+        assert_eq!(view.first_row, 0);
+    }
+
+    #[test]
+    fn only_the_visible_rows_are_built() {
+        let mut app = many_pilots(5000);
+        app.set_table_fit(40);
+        let view = app.get_view_data();
+        assert_eq!((view.first_row, view.data.len()), (0, 40));
+
+        app.current_table_state().select(Some(2500));
+        let view = app.get_view_data();
+        assert_eq!((view.first_row, view.data.len()), (2461, 40));
+        let selected = app.rows[0][2500];
+        assert_eq!(
+            view.data[2500 - 2461].cells[0],
+            app.get_tab_data(&[selected])[0][0]
+        );
+
+        app.current_table_state().select(Some(4999));
+        let view = app.get_view_data();
+        assert_eq!((view.first_row, view.data.len()), (4960, 40));
+    }
+
+    /// Run with `cargo test -- --ignored --nocapture` to compare frames.
+    #[test]
+    #[ignore = "benchmark"]
+    fn bench_view_data() {
+        use std::time::Instant;
+
+        let mut app = many_pilots(5000);
+        for fit in [None, Some(40)] {
+            app.table_fit = fit;
+            let start = Instant::now();
+            for _ in 0..20 {
+                let _ = app.get_view_data();
+            }
+            println!("{fit:?} rows: {:?} per frame", start.elapsed() / 20);
+        }
+    }
+
+    #[test]
+    fn sorts_by_filter_only_columns_are_not_saved() {
+        let mut app = App::new();
+        app.sorts[0].column = columns::PILOT_DISTANCE_COLUMN;
+        assert!(app.session().sorts.iter().all(|s| !s.starts_with("pilots")));
+    }
+
+    #[test]
+    fn coverage_sorts_longest_online_first_and_unknown_last() {
+        let mut data = controllers(&["EGLL_TWR", "EGKK_APP", "LON_S_CTR", "EGSS_GND"]);
+        for (controller, logon) in data.controllers.iter_mut().zip([
+            "2022-08-01T10:00:00Z",
+            "2022-08-01T09:00:00Z",
+            "yesterday",
+            "2022-08-01T11:00:00Z",
+        ]) {
+            controller.logon_time = logon.to_owned();
+        }
+        data.controllers[0].text_atis = Some(vec!["Closing at 2200z".to_owned()]);
+        let mut app = App::new();
+        app.set_zone(DisplayZone::Utc);
+        app.set_data(data);
+        app.select_tab(1);
+        app.toggle_coverage_view();
+        assert_eq!(
+            shown(&app, 1),
+            ["EGKK_APP", "EGLL_TWR", "EGSS_GND", "LON_S_CTR"]
+        );
+
+        let rows = app.rows[1].clone();
+        let table = app.get_tab_data(&rows);
+        assert_eq!(table[1][3], "10:00z");
+        assert_eq!(table[1][4], "\u{26a0} closing at");
+        assert_eq!(table[0][4], "");
+        assert_eq!(table[3][2], "?");
+        assert_eq!(table[3][3], "?");
+
+        app.reverse_sort();
+        assert_eq!(
+            shown(&app, 1),
+            ["EGSS_GND", "EGLL_TWR", "EGKK_APP", "LON_S_CTR"]
+        );
+    }
+
+    #[test]
+    fn coverage_view_only_on_the_controllers_tab() {
+        let mut app = App::new();
+        app.toggle_coverage_view();
+        assert!(!app.coverage_view);
+        app.select_tab(1);
+        app.toggle_coverage_view();
+        assert!(app.coverage_view);
+        assert_eq!(app.get_columns(), columns::COVERAGE_COLUMNS);
+        app.toggle_coverage_view();
+        assert_eq!(app.get_columns(), columns::CONTROLLER_COLUMNS);
+    }
+
+    /// A pilot at an altitude and time, flying at 250 kts.
+    fn at(cid: i64, callsign: &str, altitude: i64, updated: &str) -> Pilot {
+        Pilot {
+            cid,
+            altitude,
+            groundspeed: 250,
+            last_updated: updated.to_owned(),
+            ..testing::flying(callsign, "KSFO", "KLAX")
+        }
+    }
+
+    #[test]
+    fn phase_from_snapshot_deltas_and_its_filter() {
+        let mut app = App::new();
+        app.set_data(V3ResponseData {
+            pilots: vec![
+                at(1, "UP", 5_000, "2022-08-01T12:30:00Z"),
+                at(2, "LEVEL", 35_000, "2022-08-01T12:30:00Z"),
+            ],
+            ..V3ResponseData::default()
+        });
+        assert!(app
+            .data
+            .pilots
+            .iter()
+            .all(|p| pilot_phase(p, &app.enriched) == Phase::Unknown));
+
+        app.set_data(V3ResponseData {
+            pilots: vec![
+                at(1, "UP", 7_000, "2022-08-01T12:31:00Z"),
+                at(2, "LEVEL", 35_000, "2022-08-01T12:31:00Z"),
+                at(3, "NEW", 20_000, "2022-08-01T12:31:00Z"),
+            ],
+            ..V3ResponseData::default()
+        });
+        assert_eq!(app.vertical_rates.get(&1), Some(&2_000.0));
+        assert_eq!(app.vertical_rates.get(&3), None);
+
+        let mut seen = Vec::new();
+        for _ in 0..=Phase::FILTERABLE.len() {
+            app.cycle_phase_filter();
+            seen.push((app.phase_filter, shown(&app, 0).join(",")));
+        }
+        assert_eq!(
+            seen,
+            [
+                (Some(Phase::Ground), String::new()),
+                (Some(Phase::Climb), "UP".to_owned()),
+                (Some(Phase::Cruise), "LEVEL".to_owned()),
+                (Some(Phase::Descent), String::new()),
+                (Some(Phase::Approach), String::new()),
+                (None, "LEVEL,NEW,UP".to_owned()),
+            ]
+        );
+    }
+
+    /// A pilot some nautical miles north of Heathrow.
+    fn north_of_heathrow(cid: i64, callsign: &str, nm: f64) -> Pilot {
+        Pilot {
+            cid,
+            latitude: 51.47 + nm / 60.0,
+            longitude: -0.45,
+            ..testing::flying(callsign, "EGLL", "KJFK")
+        }
+    }
+
+    #[test]
+    fn range_filter_shows_pilots_by_distance() {
+        let mut app = App::new();
+        app.set_data(V3ResponseData {
+            pilots: vec![
+                north_of_heathrow(1, "FAR", 80.0),
+                north_of_heathrow(2, "MID", 30.0),
+                north_of_heathrow(3, "NEAR", 5.0),
+            ],
+            controllers: vec![testing::controller("EGLL_TWR", 4)],
+            ..V3ResponseData::default()
+        });
+        let unfiltered = shown(&app, 0).join(",");
+        app.select_tab(1);
+        app.toggle_range_filter();
+        assert_eq!(app.tab_index, 0);
+        assert_eq!(shown(&app, 0), ["NEAR", "MID"]);
+        assert_eq!(app.sorts[0].column, columns::PILOT_DISTANCE_COLUMN);
+
+        app.toggle_range_filter();
+        assert_eq!(shown(&app, 0).join(","), unfiltered);
+        assert_eq!(app.sorts[0], DEFAULT_SORTS[0]);
+    }
+
+    #[test]
+    fn range_filter_explains_unplaceable_controllers() {
+        let mut app = App::new();
+        app.set_data(controllers(&["LON_S_CTR"]));
+        app.toggle_range_filter();
+        assert!(app.range_filter.is_none());
+        assert!(app
+            .status
+            .as_deref()
+            .unwrap()
+            .starts_with("Select a controller"));
+
+        app.select_tab(1);
+        app.toggle_range_filter();
+        assert!(app.range_filter.is_none());
+        assert_eq!(app.tab_index, 1);
+        assert!(app
+            .status
+            .as_deref()
+            .unwrap()
+            .starts_with("Can't tell where LON_S_CTR is"));
+    }
+
+    /// A pilot near Heathrow squawking a code, with one assigned.
+    fn squawking(cid: i64, callsign: &str, nm: f64, squawk: &str, assigned: &str) -> Pilot {
+        let mut pilot = north_of_heathrow(cid, callsign, nm);
+        pilot.transponder = squawk.to_owned();
+        pilot.flight_plan.as_mut().unwrap().assigned_transponder = assigned.to_owned();
+        pilot
+    }
+
+    #[test]
+    fn squawk_mismatches_near_a_controller() {
+        let mut app = App::new();
+        app.set_data(V3ResponseData {
+            pilots: vec![
+                squawking(1, "WRONG", 5.0, "1234", "4321"),
+                squawking(2, "RIGHT", 10.0, "4321", "4321"),
+                squawking(3, "STANDBY", 15.0, "7000", "4321"),
+                squawking(4, "UNASSIGNED", 20.0, "1234", "0000"),
+                squawking(5, "ALSOWRONG", 25.0, "5555", "4444"),
+                squawking(6, "FAR", 100.0, "1234", "4321"),
+            ],
+            controllers: vec![testing::controller("EGLL_APP", 5)],
+            ..V3ResponseData::default()
+        });
+        let unfiltered = shown(&app, 0).join(",");
+        app.select_tab(1);
+        let _ = app.update(Action::ToggleSquawkMismatches);
+        assert_eq!(app.tab_index, 0);
+        assert_eq!(shown(&app, 0), ["ALSOWRONG", "WRONG"]);
+        assert!(app
+            .get_selected_title()
+            .contains("[Squawk mismatches: EGLL_APP]"));
+        let view = app.get_view_data();
+        let header: Vec<&str> = view.columns.iter().map(|column| column.header).collect();
+        assert_eq!(
+            header[columns::PILOT_SQUAWK_COLUMN..=columns::PILOT_ASSIGNED_COLUMN],
+            ["Squawk", "Assigned"]
+        );
+
+        // sorting by the codes
+        app.sorts[0] = SortSpec {
+            column: columns::PILOT_SQUAWK_COLUMN,
+            descending: false,
+            then: None,
+        };
+        app.apply_sort(0);
+        assert_eq!(shown(&app, 0), ["WRONG", "ALSOWRONG"]);
+        app.sorts[0].column = columns::PILOT_ASSIGNED_COLUMN;
+        app.apply_sort(0);
+        assert_eq!(shown(&app, 0), ["WRONG", "ALSOWRONG"]);
+
+        // the range filter replaces it, and pressing it again clears it
+        app.select_tab(1);
+        app.toggle_range_filter();
+        assert!(app.squawk_filter.is_none());
+        app.select_tab(1);
+        let _ = app.update(Action::ToggleSquawkMismatches);
+        assert!(app.range_filter.is_none());
+        assert_eq!(app.sorts[0], DEFAULT_SORTS[0]);
+        let _ = app.update(Action::ToggleSquawkMismatches);
+        assert!(app.squawk_filter.is_none());
+        assert_eq!(shown(&app, 0).join(","), unfiltered);
+    }
+
+    #[test]
+    fn squawk_mismatches_need_a_placeable_controller() {
+        let mut app = App::new();
+        app.set_data(controllers(&["LON_S_CTR"]));
+        let _ = app.update(Action::ToggleSquawkMismatches);
+        assert!(app
+            .status
+            .as_deref()
+            .unwrap()
+            .starts_with("Select a controller"));
+
+        app.select_tab(1);
+        let _ = app.update(Action::ToggleSquawkMismatches);
+        assert!(app.squawk_filter.is_none());
+        assert_eq!(app.tab_index, 1);
+        assert!(app
+            .status
+            .as_deref()
+            .unwrap()
+            .starts_with("Can't tell where LON_S_CTR is"));
+
+        app.set_data(V3ResponseData {
+            controllers: vec![testing::controller("EGLL_OBS", 0)],
+            ..V3ResponseData::default()
+        });
+        let _ = app.update(Action::ToggleSquawkMismatches);
+        assert!(app.squawk_filter.is_none());
+        assert_eq!(
+            app.status.as_deref(),
+            Some("EGLL_OBS has no airspace, so no one is in their traffic")
+        );
+    }
+
+    /// Type a frequency into the tune prompt and submit it.
+    fn tune(app: &mut App, text: &str) {
+        let _ = app.update(Action::OpenTune);
+        let _ = app.update(Action::Edit(Edit::DeleteToStart));
+        app.paste(text);
+        let _ = app.update(Action::Submit);
+    }
+
+    #[test]
+    fn tuning_filters_controllers_by_channel() {
+        let mut data = controllers(&["EGLL_TWR", "EGLL_N_APP", "EGKK_APP", "LON_S_CTR"]);
+        for (controller, frequency) in data
+            .controllers
+            .iter_mut()
+            .zip(["118.500", "119.725", "124.225", "129.425"])
+        {
+            controller.frequency = frequency.to_owned();
+        }
+        let mut app = App::new();
+        app.set_data(data);
+        app.select_tab(1);
+
+        tune(&mut app, "118.5");
+        assert_eq!(shown(&app, 1), ["EGLL_TWR"]);
+        // the 8.33 kHz name of the same channel
+        tune(&mut app, "119.730");
+        assert_eq!(shown(&app, 1), ["EGLL_N_APP"]);
+        tune(&mut app, "129425");
+        assert!(app.tune.prompt().is_none());
+        assert_eq!(shown(&app, 1), ["LON_S_CTR"]);
+
+        tune(&mut app, "12x");
+        assert!(app.tune.prompt().unwrap().error.is_some());
+        assert_eq!(shown(&app, 1), ["LON_S_CTR"]);
+        let _ = app.update(Action::Cancel);
+
+        tune(&mut app, "");
+        assert_eq!(shown(&app, 1).len(), 4);
+    }
+
+    #[test]
+    fn idle_after_the_timeout_and_resuming_on_input() {
+        let start = Instant::now();
+        let minutes = |m: u64| start + Duration::from_secs(m * 60);
+        let mut app = App::new();
+        app.set_idle_timeout(Some(Duration::from_secs(30 * 60)));
+        app.record_input(start);
+
+        app.check_idle(minutes(29));
+        assert_eq!(app.activity, Activity::Active);
+        app.record_input(minutes(29));
+        app.check_idle(minutes(58));
+        assert!(!app.is_idle());
+        app.check_idle(minutes(59));
+        assert!(app.is_idle());
+        assert!(!app.take_resume());
+
+        app.record_input(minutes(90));
+        assert_eq!(app.activity, Activity::Resuming);
+        assert!(!app.is_idle());
+        // not idle again before the resume is taken, however long it's been
+        app.check_idle(minutes(200));
+        assert_eq!(app.activity, Activity::Resuming);
+        assert!(app.take_resume());
+        assert!(!app.take_resume());
+        assert_eq!(app.activity, Activity::Active);
+    }
+
+    #[test]
+    fn never_idle_without_a_timeout() {
+        let start = Instant::now();
+        let mut app = App::new();
+        app.set_idle_timeout(None);
+        app.record_input(start);
+        app.check_idle(start + Duration::from_secs(24 * 60 * 60));
+        assert!(!app.is_idle());
+        app.record_input(start);
+        assert!(!app.take_resume());
+    }
+
+    /// A parked pilot filed to depart at a time, like "1350".
+    fn departing(cid: i64, callsign: &str, deptime: &str, updated: &str) -> Pilot {
+        let mut pilot = Pilot {
+            cid,
+            last_updated: updated.to_owned(),
+            ..testing::flying(callsign, "EGLL", "KJFK")
+        };
+        if let Some(plan) = &mut pilot.flight_plan {
+            plan.deptime = deptime.to_owned();
+        }
+        pilot
+    }
+
+    #[test]
+    fn departing_soon_and_sorted_by_deptime() {
+        let now = Utc::now();
+        let deptime = |minutes| {
+            let time = |minutes| {
+                (now + chrono::Duration::minutes(minutes))
+                    .format("%H%M")
+                    .to_string()
+            };
+            // "0000" is no time filed, so step past it
+            Some(time(minutes))
+                .filter(|time| time != "0000")
+                .unwrap_or_else(|| time(minutes + 1))
+        };
+        let pilots = |updated: &str| {
+            vec![
+                departing(1, "LATER", &deptime(90), updated),
+                departing(2, "SOON", &deptime(30), updated),
+                departing(3, "NONE", "0000", updated),
+                departing(4, "LATE", &deptime(-20), updated),
+                departing(5, "BLANK", "", updated),
+                departing(6, "NEXT", &deptime(5), updated),
+            ]
+        };
+        let mut app = App::new();
+        for updated in ["2022-08-01T12:30:00Z", "2022-08-01T12:31:00Z"] {
+            app.set_data(V3ResponseData {
+                pilots: pilots(updated),
+                ..V3ResponseData::default()
+            });
+        }
+        app.sorts[0] = SortSpec {
+            column: columns::PILOT_DEPARTURE_COLUMN,
+            descending: false,
+            then: None,
+        };
+        app.apply_sort(0);
+        let order = shown(&app, 0);
+        assert_eq!(order[..4], ["LATE", "NEXT", "SOON", "LATER"]);
+        assert_eq!(order.len(), 6);
+        app.reverse_sort();
+        let order = shown(&app, 0);
+        assert_eq!(order[..4], ["LATER", "SOON", "NEXT", "LATE"]);
+
+        app.toggle_departing_soon();
+        assert_eq!(shown(&app, 0), ["SOON", "NEXT"]);
+    }
+
+    #[test]
+    fn default_sort_at_startup_and_on_reset() {
+        let mut data = controllers(&["EGLL_TWR", "EGKK_APP", "EGLL_APP", "EGSS_TWR"]);
+        for (controller, rating) in data.controllers.iter_mut().zip([5, 3, 5, 3]) {
+            controller.rating = rating;
+        }
+        let mut app = App::new();
+        app.set_default_sorts(&[DefaultSort::parse("controllers:rating:desc,callsign").unwrap()]);
+        app.set_data(data);
+        app.select_tab(1);
+        let order = ["EGLL_APP", "EGLL_TWR", "EGKK_APP", "EGSS_TWR"];
+        assert_eq!(shown(&app, 1), order);
+
+        app.sort_by_column(0);
+        assert_eq!(
+            shown(&app, 1),
+            ["EGKK_APP", "EGLL_APP", "EGLL_TWR", "EGSS_TWR"]
+        );
+        app.sort_by_column(0);
+        assert_eq!(
+            shown(&app, 1),
+            ["EGSS_TWR", "EGLL_TWR", "EGLL_APP", "EGKK_APP"]
+        );
+        app.reset_sort();
+        assert_eq!(shown(&app, 1), order);
+        // the pilots "tab" keeps its own default
+        assert_eq!(app.sorts[0], DEFAULT_SORTS[0]);
+    }
+
+    #[test]
+    fn deltas_count_connections_and_skip_no_change() {
+        let mut app = with_pilots(vec![testing::pilot("BAW1"), testing::pilot("BAW2")]);
+        assert_eq!(app.recent_deltas(Instant::now()), None);
+
+        let mut data = app.data.clone();
+        let _ = data.pilots.remove(0);
+        data.pilots.push(Pilot {
+            cid: 3,
+            ..testing::pilot("BAW3")
+        });
+        data.pilots.push(Pilot {
+            cid: 4,
+            ..testing::pilot("BAW4")
+        });
+        data.controllers.push(testing::controller("EGLL_TWR", 4));
+        app.set_data(data.clone());
+        let (deltas, received) = app.deltas.unwrap();
+        assert_eq!(
+            deltas,
+            ConnectionDeltas {
+                pilots_joined: 2,
+                pilots_left: 1,
+                controllers_joined: 1,
+                controllers_left: 0,
+            }
+        );
+        assert_eq!(
+            app.recent_deltas(received).as_deref(),
+            Some("+2 / \u{2212}1 pilots, +1 / \u{2212}0 controller")
+        );
+        assert_eq!(app.recent_deltas(received + DELTA_DISPLAY_TIME), None);
+
+        // a refresh where nothing connected or disconnected shows nothing
+        app.set_data(data);
+        assert_eq!(app.recent_deltas(Instant::now()), None);
+    }
+
+    #[test]
+    fn no_atis_filter_composes_and_shows_in_the_title() {
+        let mut data = controllers(&["EGLL_TWR", "EGLL_GND", "EGKK_APP", "LON_S_CTR", "KJFK_TWR"]);
+        data.controllers[0].facility = 4;
+        data.controllers[1].facility = 3;
+        data.controllers[2].facility = 5;
+        data.controllers[3].facility = 6;
+        data.controllers[4].text_atis = Some(vec!["Kennedy Tower".to_owned()]);
+        let mut app = App::new();
+        app.set_data(data);
+        app.select_tab(1);
+
+        let _ = app.update(Action::ToggleNoAtis);
+        assert_eq!(shown(&app, 1), ["EGKK_APP", "EGLL_TWR", "LON_S_CTR"]);
+        assert_eq!(app.get_selected_title(), "Controllers [No ATIS]");
+
+        app.set_filter(Query::parse("callsign=EG*").unwrap());
+        assert_eq!(shown(&app, 1), ["EGKK_APP", "EGLL_TWR"]);
+        assert!(app
+            .get_selected_title()
+            .starts_with("Controllers [No ATIS] [callsign=EG*]"));
+
+        // the toggle only works on the controllers "tab"
+        app.select_tab(0);
+        let _ = app.update(Action::ToggleNoAtis);
+        app.select_tab(1);
+        assert_eq!(shown(&app, 1), ["EGKK_APP", "EGLL_TWR"]);
+
+        let _ = app.update(Action::ToggleNoAtis);
+        assert_eq!(shown(&app, 1), ["EGKK_APP", "EGLL_GND", "EGLL_TWR"]);
+        assert!(!app.get_selected_title().contains("No ATIS"));
+    }
+
+    #[test]
+    fn no_flight_plan_filter_composes_and_shows_in_the_title() {
+        let mut app = with_pilots(vec![
+            testing::flying("BAW1", "EGLL", "KJFK"),
+            testing::pilot("GABCD"),
+            testing::pilot("N123AB"),
+        ]);
+        let _ = app.update(Action::ToggleNoFlightPlan);
+        assert_eq!(shown(&app, 0), ["GABCD", "N123AB"]);
+        assert_eq!(app.get_selected_title(), "Pilots [No plan]");
+
+        app.set_filter(Query::parse("callsign=N*").unwrap());
+        assert_eq!(shown(&app, 0), ["N123AB"]);
+
+        let _ = app.update(Action::ToggleNoFlightPlan);
+        assert_eq!(shown(&app, 0), ["N123AB"]);
+        assert_eq!(app.get_selected_title(), "Pilots [callsign=N*]");
+    }
+
+    #[test]
+    fn ratings_are_named_without_the_live_list() {
+        let mut app = App::new();
+        app.set_data(controllers(&["EGLL_TWR"]));
+        app.select_tab(1);
+        let cells = app.get_tab_data(&[0]).remove(0);
+        assert_eq!(cells[4], "C1");
+    }
+
+    #[test]
+    fn resized_widths_are_saved_per_table() {
+        let mut app = App::new();
+        app.set_data(controllers(&["EGLL_TWR"]));
+        let defaults: Vec<u16> = columns::PILOT_COLUMNS.iter().map(|c| c.width).collect();
+        assert_eq!(app.get_column_widths(), defaults);
+
+        let _ = app.update(Action::ResizeColumn(2));
+        let widths = app.get_column_widths();
+        assert_eq!(widths[0], defaults[0] + 2);
+        assert_eq!(widths.iter().sum::<u16>(), defaults.iter().sum::<u16>());
+        app.select_tab(1);
+        let controller_defaults: Vec<u16> = columns::CONTROLLER_COLUMNS
+            .iter()
+            .map(|c| c.width)
+            .collect();
+        assert_eq!(app.get_column_widths(), controller_defaults);
+
+        let session = app.session();
+        assert_eq!(session.column_widths.keys().collect::<Vec<_>>(), ["pilots"]);
+        let mut restored = App::new();
+        restored.set_column_widths(session.column_widths);
+        assert_eq!(restored.get_column_widths(), widths);
+    }
+
+    #[test]
+    fn saved_widths_for_other_columns_are_ignored() {
+        let defaults: Vec<u16> = columns::PILOT_COLUMNS.iter().map(|c| c.width).collect();
+        let mut app = App::new();
+        // from before a column was added
+        app.set_column_widths(BTreeMap::from([(
+            "pilots".to_owned(),
+            defaults[1..].to_vec(),
+        )]));
+        assert_eq!(app.get_column_widths(), defaults);
+
+        let mut zero = defaults.clone();
+        zero[3] = 0;
+        app.set_column_widths(BTreeMap::from([("pilots".to_owned(), zero)]));
+        assert_eq!(app.get_column_widths(), defaults);
+
+        // a custom column makes the saved widths out of date too
+        let mut widened = defaults.clone();
+        widened[1] += 4;
+        app.set_column_widths(BTreeMap::from([("pilots".to_owned(), widened.clone())]));
+        assert_eq!(app.get_column_widths(), widened);
+        app.set_custom_columns(
+            custom::parse(
+                r#"{"version": 1, "pilots": [{"header": "Transponder", "path": "transponder"}]}"#,
+            )
+            .unwrap(),
+        );
+        let widths = app.get_column_widths();
+        assert_eq!(widths.len(), defaults.len() + 1);
+        assert_eq!(widths[..defaults.len()], defaults);
+    }
+
+    /// A pilot whose record was last updated at a time on the day of the
+    /// data.
+    fn updated_at(callsign: &str, time: &str) -> Pilot {
+        Pilot {
+            last_updated: format!("2022-08-01T{}Z", time),
+            ..testing::flying(callsign, "EGLL", "KJFK")
+        }
+    }
+
+    #[test]
+    fn stale_records_are_dimmed_and_can_be_hidden() {
+        let mut app = with_pilots(vec![
+            updated_at("BAW1", "12:29:58.1234567"),
+            updated_at("BAW2", "12:20:00"),
+            Pilot {
+                last_updated: "garbled".to_owned(),
+                ..testing::flying("BAW3", "EGLL", "KJFK")
+            },
+        ]);
+        let mut data = app.data.clone();
+        data.general.update_timestamp = "2022-08-01T12:30:00.7654321Z".to_owned();
+        app.set_data(data);
+        app.set_stale_after(Some(chrono::Duration::minutes(5)));
+
+        let dimmed: Vec<bool> = app
+            .get_row_styles(&app.rows[0])
+            .iter()
+            .map(|style| style.add_modifier.contains(Modifier::DIM))
+            .collect();
+        assert_eq!(dimmed, [false, true, false]);
+
+        app.toggle_hide_stale();
+        assert_eq!(shown(&app, 0), ["BAW1", "BAW3"]);
+        assert!(app.get_selected_title().contains("[Hiding stale]"));
+        app.toggle_hide_stale();
+        assert_eq!(shown(&app, 0), ["BAW1", "BAW2", "BAW3"]);
+
+        // without a threshold nothing is stale
+        app.set_stale_after(None);
+        assert!(app
+            .get_row_styles(&app.rows[0])
+            .iter()
+            .all(|style| !style.add_modifier.contains(Modifier::DIM)));
+    }
+
+    #[test]
+    fn record_age_in_the_popup() {
+        let mut app = with_pilots(vec![updated_at("BAW1", "12:20:00")]);
+        let mut data = app.data.clone();
+        data.general.update_timestamp = "2022-08-01T12:30:00.1234567Z".to_owned();
+        app.set_data(data);
+        assert_eq!(app.selected_record_age(), None);
+        app.toggle_popup(true);
+        let age = app.selected_record_age().unwrap();
+        assert_eq!(age.num_seconds(), 600);
+    }
+
+    /// An event at an airport, starting some hours from now and lasting
+    /// two hours.
+    fn event(id: i64, name: &str, airport: &str, starts_in: i64) -> Event {
+        let start = Utc::now() + chrono::Duration::hours(starts_in);
+        let end = start + chrono::Duration::hours(2);
+        Event {
+            id,
+            name: name.to_owned(),
+            link: String::new(),
+            start_time: start.to_rfc3339(),
+            end_time: end.to_rfc3339(),
+            airports: vec![crate::models::EventAirport {
+                icao: airport.to_owned(),
+            }],
+            organisers: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn events_tab_lists_by_start_and_marks_pilots() {
+        let mut app = with_pilots(vec![
+            testing::flying("UAL1", "KSFO", "KLAX"),
+            testing::flying("BAW2", "EGLL", "KJFK"),
+            testing::flying("DAL3", "KATL", "KSFO"),
+        ]);
+        app.set_events(vec![
+            event(1, "Later", "EGLL", 20),
+            event(2, "Too far off", "KATL", 30),
+            event(3, "Over", "KJFK", -3),
+            event(4, "Now", "KSFO", -1),
+            event(5, "Soon", "EGLL", 2),
+        ]);
+        let names: Vec<_> = app.rows[2]
+            .iter()
+            .map(|&i| app.events[i].name.as_str())
+            .collect();
+        assert_eq!(names, ["Now", "Soon", "Later"]);
+
+        let callsigns: Vec<_> = app
+            .get_tab_data(&app.rows[0])
+            .into_iter()
+            .map(|cells| cells[0].clone())
+            .collect();
+        assert_eq!(
+            callsigns,
+            [
+                "BAW2".to_owned(),
+                format!("DAL3{}", EVENT_MARKER),
+                format!("UAL1{}", EVENT_MARKER),
+            ]
+        );
     }
 }
@@ -0,0 +1,273 @@
+//! Network-wide statistics, for the `--summary` one-shot mode.
+
+use crate::{
+    callsign, compare,
+    filter::RangeFilter,
+    geo::GridIndex,
+    models::V3ResponseData,
+    regions,
+    report::{fetch_data, DataSource, Reporter},
+    sanity::{self, Limits},
+    snapshots::{self, Counts},
+};
+use anyhow::Result;
+use chrono::Utc;
+use std::{collections::BTreeMap, path::Path};
+
+/// Airlines listed in the statistics.
+const TOP_AIRLINES: usize = 5;
+
+/// Totals and busiest places on the network at one point in time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetworkStats {
+    pub pilots_total: usize,
+    pub controllers_total: usize,
+    pub atis_total: usize,
+    pub unique_users: i64,
+    pub update_timestamp: String,
+    /// Departure airport in the most flight plans.
+    pub top_departure_airport: Option<String>,
+    /// Controller with the most pilots in their visual range.
+    pub busiest_controller_callsign: Option<String>,
+    /// Airlines with the most flights online, and how many, busiest first.
+    pub top_airlines: Vec<(String, usize)>,
+    /// Controllers online in each country or region, most first; filter
+    /// the controllers with `region=NAME` to see them.
+    pub controllers_by_region: Vec<(String, usize)>,
+}
+
+impl NetworkStats {
+    /// Aggregate the statistics from the V3 data.
+    ///
+    /// Ties are broken alphabetically, so the same data always gives the
+    /// same result. Pilots whose records look broken aren't placed in any
+    /// controller's range.
+    pub fn from_data(data: &V3ResponseData, limits: &Limits) -> Self {
+        let mut departures: BTreeMap<&str, usize> = BTreeMap::new();
+        for plan in data.pilots.iter().filter_map(|p| p.flight_plan.as_ref()) {
+            if !plan.departure.is_empty() {
+                *departures.entry(&plan.departure).or_default() += 1;
+            }
+        }
+        let top_departure_airport = most_common(departures);
+
+        let mut in_range: BTreeMap<&str, usize> = BTreeMap::new();
+        let pilots = GridIndex::new(
+            data.pilots
+                .iter()
+                .enumerate()
+                .filter(|(_, p)| sanity::check(p, None, limits).is_none())
+                .map(|(i, p)| (i, p.latitude, p.longitude)),
+        );
+        for controller in &data.controllers {
+            let Ok(range) = RangeFilter::around(controller) else {
+                continue;
+            };
+            let count = pilots
+                .within(range.latitude, range.longitude, range.range_nm)
+                .len();
+            if count > 0 {
+                let _ = in_range.insert(&controller.callsign, count);
+            }
+        }
+        let busiest_controller_callsign = most_common(in_range);
+
+        let mut airlines: BTreeMap<String, usize> = BTreeMap::new();
+        for code in data
+            .pilots
+            .iter()
+            .filter_map(|p| callsign::airline(&p.callsign))
+        {
+            *airlines.entry(code).or_default() += 1;
+        }
+        let mut top_airlines: Vec<(String, usize)> = airlines.into_iter().collect();
+        // stable, so ties stay alphabetical
+        top_airlines.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+        top_airlines.truncate(TOP_AIRLINES);
+
+        Self {
+            pilots_total: data.pilots.len(),
+            controllers_total: data.controllers.len(),
+            atis_total: data.atis.len(),
+            unique_users: data.general.unique_users,
+            update_timestamp: data.general.update_timestamp.clone(),
+            top_departure_airport,
+            busiest_controller_callsign,
+            top_airlines,
+            controllers_by_region: regions::count(&data.controllers)
+                .into_iter()
+                .map(|(region, count)| (region.to_owned(), count))
+                .collect(),
+        }
+    }
+
+    /// The statistics as `key=value` lines, always in the same order.
+    ///
+    /// Missing values are left empty.
+    pub fn to_lines(&self) -> String {
+        [
+            ("pilots_total", self.pilots_total.to_string()),
+            ("controllers_total", self.controllers_total.to_string()),
+            ("atis_total", self.atis_total.to_string()),
+            ("unique_users", self.unique_users.to_string()),
+            ("update_timestamp", self.update_timestamp.clone()),
+            (
+                "top_departure_airport",
+                self.top_departure_airport.clone().unwrap_or_default(),
+            ),
+            (
+                "busiest_controller_callsign",
+                self.busiest_controller_callsign.clone().unwrap_or_default(),
+            ),
+            (
+                "top_airlines",
+                self.top_airlines
+                    .iter()
+                    .map(|(code, count)| format!("{}:{}", code, count))
+                    .collect::<Vec<_>>()
+                    .join(","),
+            ),
+            (
+                "controllers_by_region",
+                self.controllers_by_region
+                    .iter()
+                    .map(|(region, count)| format!("{}:{}", region, count))
+                    .collect::<Vec<_>>()
+                    .join(","),
+            ),
+        ]
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<_>>()
+        .join("\n")
+    }
+}
+
+/// The key with the highest count, the first alphabetically on ties.
+fn most_common(counts: BTreeMap<&str, usize>) -> Option<String> {
+    counts
+        .into_iter()
+        .fold(
+            None,
+            |best: Option<(&str, usize)>, (key, count)| match best {
+                Some((_, best_count)) if best_count >= count => best,
+                _ => Some((key, count)),
+            },
+        )
+        .map(|(key, _)| key.to_owned())
+}
+
+/// Fetch the current data and print the statistics.
+///
+/// With a snapshot directory, they're also compared with a day ago and
+/// recorded there for tomorrow.
+pub fn run(
+    snapshot_dir: Option<&Path>,
+    limits: Limits,
+    source: &DataSource,
+    reporter: &mut Reporter,
+) -> Result<()> {
+    let data = fetch_data(source, reporter)?;
+    let stats = NetworkStats::from_data(&data, &limits);
+    reporter.data(stats.to_lines())?;
+    if let Some(dir) = snapshot_dir {
+        let now = Utc::now();
+        let comparison = compare::yesterday(dir, now, Counts::from_data(&data))?;
+        reporter.data(compare::summary_line(comparison.as_ref()))?;
+        let _ = snapshots::save(dir, &stats, now)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        models::{Controller, Pilot},
+        testing,
+    };
+
+    fn at(callsign: &str, departure: &str, latitude: f64, longitude: f64) -> Pilot {
+        Pilot {
+            latitude,
+            longitude,
+            ..testing::flying(callsign, departure, "KJFK")
+        }
+    }
+
+    /// Three flights near Heathrow, one at Kennedy, and a pilot without a
+    /// flight plan, with a tower at each airport.
+    fn data() -> V3ResponseData {
+        let mut data = V3ResponseData {
+            pilots: vec![
+                at("BAW1", "EGLL", 51.6, -0.45),
+                at("BAW2", "EGLL", 51.8, -0.45),
+                at("VIR3", "EGLL", 51.5, -0.45),
+                at("DAL4", "KJFK", 40.7, -73.8),
+                testing::pilot("N123AB"),
+            ],
+            controllers: vec![
+                testing::controller("EGLL_TWR", 4),
+                testing::controller("KJFK_TWR", 4),
+            ],
+            atis: vec![Controller {
+                text_atis: Some(vec!["Heathrow information A".to_owned()]),
+                ..testing::controller("EGLL_ATIS", 4)
+            }],
+            ..V3ResponseData::default()
+        };
+        data.general.update_timestamp = "2022-08-01T12:30:00.1234567Z".to_owned();
+        data.general.unique_users = 7;
+        data
+    }
+
+    #[test]
+    fn summary_lines() {
+        let stats = NetworkStats::from_data(&data(), &Limits::default());
+        testing::golden("network_stats.txt", &stats.to_lines());
+    }
+
+    #[test]
+    fn empty_data_leaves_values_empty() {
+        let stats = NetworkStats::from_data(&V3ResponseData::default(), &Limits::default());
+        let lines = stats.to_lines();
+        let keys: Vec<_> = lines
+            .lines()
+            .map(|line| line.split_once('=').unwrap())
+            .collect();
+        assert_eq!(
+            keys,
+            [
+                ("pilots_total", "0"),
+                ("controllers_total", "0"),
+                ("atis_total", "0"),
+                ("unique_users", "0"),
+                ("update_timestamp", ""),
+                ("top_departure_airport", ""),
+                ("busiest_controller_callsign", ""),
+                ("top_airlines", ""),
+                ("controllers_by_region", ""),
+            ]
+        );
+    }
+
+    #[test]
+    fn ties_go_to_the_first_alphabetically() {
+        let counts = BTreeMap::from([("KSFO", 2), ("EGLL", 2), ("LFPG", 1)]);
+        assert_eq!(most_common(counts).as_deref(), Some("EGLL"));
+        assert_eq!(most_common(BTreeMap::new()), None);
+    }
+
+    #[test]
+    fn broken_records_are_not_in_range() {
+        let mut data = data();
+        for pilot in &mut data.pilots[..3] {
+            pilot.groundspeed = 5_000;
+        }
+        let stats = NetworkStats::from_data(&data, &Limits::default());
+        assert_eq!(
+            stats.busiest_controller_callsign.as_deref(),
+            Some("KJFK_TWR")
+        );
+    }
+}
@@ -0,0 +1,279 @@
+//! Plain-text summaries of clients, for pasting into chat.
+
+use crate::{
+    callsign, events, flight,
+    format::{self, DisplayZone, TimeStyle},
+    frequency,
+    models::{Controller, Event, Pilot},
+};
+use anyhow::{Context, Result};
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+/// Lines in a summary are wrapped to this width.
+const WIDTH: usize = 80;
+
+/// Indent for wrapped blocks like the route.
+const INDENT: &str = "  ";
+
+/// Wrap a block of text under a heading, indented.
+fn block(lines: &mut Vec<String>, heading: &str, text: &str) {
+    let wrapped = format::wrap(text, WIDTH - INDENT.len());
+    if wrapped.is_empty() {
+        return;
+    }
+    lines.push(format!("{}:", heading));
+    lines.extend(
+        wrapped
+            .into_iter()
+            .map(|line| format!("{}{}", INDENT, line)),
+    );
+}
+
+/// Summary of a pilot and their flight plan.
+pub fn pilot(pilot: &Pilot, zone: DisplayZone) -> String {
+    let mut lines = vec![format!(
+        "{} \u{2014} {} (CID {})",
+        pilot.callsign, pilot.name, pilot.cid
+    )];
+    if let Some(plan) = &pilot.flight_plan {
+        lines.push(format!(
+            "{} \u{2192} {}, {}, {} at {}",
+            plan.departure,
+            plan.arrival,
+            pilot.aircraft().unwrap_or("unknown aircraft"),
+            format::flight_rules(&plan.flight_rules),
+            plan.altitude
+        ));
+    } else {
+        lines.push("No flight plan filed".to_owned());
+    }
+    lines.push(format!(
+        "Altitude {} ft, ground speed {} kt",
+        format::thousands(usize::try_from(pilot.altitude).unwrap_or_default()),
+        pilot.groundspeed
+    ));
+    let mut times = vec![format!(
+        "Online since {}",
+        format::time_or_raw(pilot.logon(), &pilot.logon_time, zone, TimeStyle::Clock)
+    )];
+    if let Some(plan) = &pilot.flight_plan {
+        if let Some(deptime) = flight::parse_deptime(&plan.deptime) {
+            let departs = flight::departure_time(deptime, chrono::Utc::now());
+            times.push(format!(
+                "departs {}",
+                format::format_time(departs, zone, TimeStyle::Compact)
+            ));
+        }
+        if let Some(enroute) = flight::parse_filed_duration(&plan.enroute_time) {
+            times.push(format!("enroute {}", format::duration(enroute)));
+        }
+    }
+    lines.push(times.join(", "));
+    if let Some(plan) = &pilot.flight_plan {
+        block(&mut lines, "Route", &plan.route);
+    }
+    lines.join("\n")
+}
+
+/// Summary of a controller and their position.
+pub fn controller(controller: &Controller, zone: DisplayZone) -> String {
+    let mut lines = vec![
+        format!(
+            "{} \u{2014} {} (CID {})",
+            controller.callsign, controller.name, controller.cid
+        ),
+        format!(
+            "{} on {}",
+            callsign::position_name(&controller.callsign),
+            frequency::display(&controller.frequency)
+        ),
+        format!(
+            "Online since {}",
+            format::time_or_raw(
+                controller.logon(),
+                &controller.logon_time,
+                zone,
+                TimeStyle::Clock
+            )
+        ),
+    ];
+    block(&mut lines, "Info", &controller.info_lines().join(" "));
+    lines.join("\n")
+}
+
+/// Summary of an event.
+pub fn event(event: &Event, zone: DisplayZone) -> String {
+    let mut lines = vec![
+        event.name.clone(),
+        format!(
+            "{} to {}",
+            format::time_or_raw(event.start(), &event.start_time, zone, TimeStyle::Day),
+            format::time_or_raw(event.end(), &event.end_time, zone, TimeStyle::Day)
+        ),
+    ];
+    block(&mut lines, "Airports", &events::airports_text(event));
+    lines.push(event.link.clone());
+    lines.join("\n")
+}
+
+/// Copy text to the clipboard with an OSC 52 terminal escape sequence.
+///
+/// This works over SSH, but only in terminals that support it.
+pub fn copy_to_clipboard(text: &str) -> Result<()> {
+    let mut stdout = std::io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", base64::encode(text))?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// Write a summary to `<dir>/<callsign>.txt`, returning the path.
+pub fn save(dir: &Path, callsign: &str, text: &str) -> Result<PathBuf> {
+    save_as(dir, callsign, "txt", text)
+}
+
+/// Write an export to `<dir>/<callsign>.<extension>`, returning the path.
+pub fn save_as(dir: &Path, callsign: &str, extension: &str, text: &str) -> Result<PathBuf> {
+    fs::create_dir_all(dir).with_context(|| format!("Could not create {}", dir.display()))?;
+    let name: String = callsign
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let path = dir.join(format!("{}.{}", name, extension));
+    fs::write(&path, format!("{}\n", text))
+        .with_context(|| format!("Could not write {}", path.display()))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        models::{EventAirport, Pilot, V3ResponseData},
+        privacy::Privacy,
+        testing,
+    };
+
+    fn flying() -> Pilot {
+        let mut pilot = Pilot {
+            cid: 1_234_567,
+            altitude: 35_000,
+            groundspeed: 480,
+            ..testing::flying("BAW123", "EGLL", "KJFK")
+        };
+        if let Some(plan) = &mut pilot.flight_plan {
+            plan.altitude = "FL350".to_owned();
+            plan.route = "CPT3F CPT L9 KENET DCT NUMPO N14 PELIX DCT 50N020W 51N030W \
+                          51N040W 50N050W DCT PORTI N155A TOPPS DCT ENE PARCH3"
+                .to_owned();
+        }
+        pilot
+    }
+
+    #[test]
+    fn pilot_summary() {
+        testing::golden("pilot_summary.txt", &pilot(&flying(), DisplayZone::Utc));
+    }
+
+    #[test]
+    fn pilot_without_a_flight_plan() {
+        let pilot = Pilot {
+            flight_plan: None,
+            ..flying()
+        };
+        testing::golden(
+            "pilot_without_plan_summary.txt",
+            &super::pilot(&pilot, DisplayZone::Utc),
+        );
+    }
+
+    #[test]
+    fn private_names_stay_private() {
+        let mut controller = testing::controller("EGLL_N_TWR", 4);
+        controller.name = "Jane Doe".to_owned();
+        controller.text_atis = Some(vec!["Heathrow Tower, Jane Doe.".to_owned()]);
+        let mut data = V3ResponseData {
+            pilots: vec![Pilot {
+                name: "John Smith".to_owned(),
+                ..flying()
+            }],
+            controllers: vec![controller],
+            ..V3ResponseData::default()
+        };
+        Privacy::Cid.apply(&mut data);
+        let pilot = pilot(&data.pilots[0], DisplayZone::Utc);
+        assert!(
+            pilot.starts_with("BAW123 \u{2014} 1234567 (CID 1234567)"),
+            "{}",
+            pilot
+        );
+        assert!(!pilot.contains("Smith"), "{}", pilot);
+        let controller = super::controller(&data.controllers[0], DisplayZone::Utc);
+        assert!(!controller.contains("Doe"), "{}", controller);
+        assert!(
+            controller.contains("Heathrow Tower, 2000000."),
+            "{}",
+            controller
+        );
+    }
+
+    #[test]
+    fn controller_summary() {
+        let mut controller = testing::controller("EGLL_N_TWR", 4);
+        controller.text_atis = Some(vec![
+            "Heathrow Tower, north runway.".to_owned(),
+            "Charts and procedures at chartfox.org; please report ready for departure at the \
+             holding point and not before."
+                .to_owned(),
+        ]);
+        testing::golden(
+            "controller_summary.txt",
+            &super::controller(&controller, DisplayZone::Utc),
+        );
+    }
+
+    #[test]
+    fn event_summary() {
+        let event = Event {
+            id: 1,
+            name: "Friday Night Ops".to_owned(),
+            link: "https://my.vatsim.net/events/friday-night-ops".to_owned(),
+            start_time: "2022-08-05T23:00:00.000000Z".to_owned(),
+            end_time: "2022-08-06T02:00:00.000000Z".to_owned(),
+            airports: vec![
+                EventAirport {
+                    icao: "KSFO".to_owned(),
+                },
+                EventAirport {
+                    icao: "KLAX".to_owned(),
+                },
+            ],
+            organisers: Vec::new(),
+        };
+        testing::golden("event_summary.txt", &super::event(&event, DisplayZone::Utc));
+    }
+
+    #[test]
+    fn lines_fit_in_eighty_columns() {
+        let text = pilot(&flying(), DisplayZone::Utc);
+        assert!(text.lines().all(|line| line.chars().count() <= WIDTH));
+        assert!(text.lines().filter(|line| line.starts_with(INDENT)).count() > 1);
+    }
+
+    #[test]
+    fn saved_under_a_safe_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = save(&dir.path().join("summaries"), "EGLL_N/TWR", "text").unwrap();
+        assert_eq!(path.file_name().unwrap(), "EGLL_N_TWR.txt");
+        assert_eq!(fs::read_to_string(path).unwrap(), "text\n");
+    }
+}
@@ -0,0 +1,274 @@
+//! Checking the terminal can show the full-screen views, and how plainly.
+
+use crossterm::{
+    event::{DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use std::{
+    error::Error,
+    fmt,
+    io::{IsTerminal, Stdout},
+};
+use tui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Modifier},
+    widgets::Widget,
+};
+
+/// What the terminal was found to support.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct Capabilities {
+    /// Output goes to a terminal rather than a file or pipe.
+    pub is_terminal: bool,
+    /// `TERM` is "dumb", as in some IDE consoles.
+    pub dumb: bool,
+    /// Colors are welcome: `NO_COLOR` isn't set.
+    pub color: bool,
+    /// Raw mode could be turned on.
+    pub raw_mode: bool,
+    /// The alternate screen could be entered.
+    pub alternate_screen: bool,
+}
+
+/// How to draw on a terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Colors and Unicode glyphs.
+    Full,
+    /// No colors and only ASCII glyphs, for terminals that work but may
+    /// not show more.
+    Plain,
+    /// Nothing can be drawn, for the reason given.
+    Unsupported(&'static str),
+}
+
+/// Pick how to draw from what the terminal supports.
+pub fn choose_mode(capabilities: Capabilities) -> Mode {
+    if !capabilities.is_terminal {
+        Mode::Unsupported("output isn't going to a terminal")
+    } else if !capabilities.raw_mode {
+        Mode::Unsupported("it doesn't support raw mode")
+    } else if !capabilities.alternate_screen {
+        Mode::Unsupported("it doesn't support the alternate screen")
+    } else if capabilities.dumb || !capabilities.color {
+        Mode::Plain
+    } else {
+        Mode::Full
+    }
+}
+
+/// The terminal can't show a full-screen view.
+#[derive(Debug)]
+pub struct Unsupported(&'static str);
+
+impl fmt::Display for Unsupported {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "This terminal can't show the interface, as {}.\nTry `vatsim_online list` for a plain table, or `vatsim_online list --format json` for JSON.",
+            self.0
+        )
+    }
+}
+
+impl Error for Unsupported {}
+
+/// Set the terminal up for a full-screen view, returning how to draw.
+///
+/// What's supported is found by trying it, so on failure whatever did work
+/// is undone before returning the error. Nothing is tried when output isn't
+/// going to a terminal, to keep escape codes out of files.
+///
+/// Interactive views also get mouse clicks and bracketed paste, so pasted
+/// text arrives in one piece rather than as key presses.
+pub fn set_up(stdout: &mut Stdout, interactive: bool) -> Result<Mode, Unsupported> {
+    let is_terminal = stdout.is_terminal();
+    let alternate_screen = is_terminal
+        && if interactive {
+            execute!(
+                stdout,
+                EnterAlternateScreen,
+                EnableMouseCapture,
+                EnableBracketedPaste
+            )
+        } else {
+            execute!(stdout, EnterAlternateScreen)
+        }
+        .is_ok();
+    let raw_mode = is_terminal && enable_raw_mode().is_ok();
+    let capabilities = Capabilities {
+        is_terminal,
+        dumb: std::env::var("TERM").is_ok_and(|term| term == "dumb"),
+        color: std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty()),
+        raw_mode,
+        alternate_screen,
+    };
+    let mode = choose_mode(capabilities);
+    if let Mode::Unsupported(reason) = mode {
+        if raw_mode {
+            let _ = disable_raw_mode();
+        }
+        if alternate_screen {
+            let _ = execute!(
+                stdout,
+                LeaveAlternateScreen,
+                DisableMouseCapture,
+                DisableBracketedPaste
+            );
+        }
+        return Err(Unsupported(reason));
+    }
+    Ok(mode)
+}
+
+/// ASCII stand-in for a glyph the views draw, if it has one.
+fn ascii_glyph(symbol: &str) -> Option<&'static str> {
+    let ascii = match symbol {
+        "\u{2013}" | "\u{2014}" | "\u{2212}" => "-",
+        "\u{2192}" => ">",
+        "\u{2248}" => "~",
+        "\u{25b2}" => "^",
+        "\u{25bc}" => "v",
+        "\u{2605}" => "*",
+        "\u{26a0}" => "!",
+        "\u{2588}" => "#",
+        "\u{2593}" => "%",
+        "\u{2592}" => ":",
+        "\u{2591}" => ".",
+        _ => return None,
+    };
+    Some(ascii)
+}
+
+/// Rewrites what's been drawn for a plain terminal.
+///
+/// Colors are dropped, with reverse video where there was a background
+/// so headers still stand out, and glyphs are swapped for ASCII.
+pub struct Plain;
+
+impl Widget for Plain {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        for y in area.top()..area.bottom() {
+            for x in area.left()..area.right() {
+                let cell = buf.get_mut(x, y);
+                if cell.bg != Color::Reset {
+                    cell.modifier.insert(Modifier::REVERSED);
+                }
+                cell.fg = Color::Reset;
+                cell.bg = Color::Reset;
+                if let Some(ascii) = ascii_glyph(&cell.symbol) {
+                    let _ = cell.set_symbol(ascii);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tui::style::Style;
+
+    /// A terminal that supports everything.
+    const CAPABLE: Capabilities = Capabilities {
+        is_terminal: true,
+        dumb: false,
+        color: true,
+        raw_mode: true,
+        alternate_screen: true,
+    };
+
+    #[test]
+    fn capable_terminals_get_everything() {
+        assert_eq!(choose_mode(CAPABLE), Mode::Full);
+    }
+
+    #[test]
+    fn working_terminals_without_color_are_plain() {
+        let dumb = Capabilities {
+            dumb: true,
+            ..CAPABLE
+        };
+        assert_eq!(choose_mode(dumb), Mode::Plain);
+        let no_color = Capabilities {
+            color: false,
+            ..CAPABLE
+        };
+        assert_eq!(choose_mode(no_color), Mode::Plain);
+    }
+
+    #[test]
+    fn missing_features_are_unsupported() {
+        let redirected = Capabilities {
+            is_terminal: false,
+            raw_mode: false,
+            alternate_screen: false,
+            ..CAPABLE
+        };
+        assert_eq!(
+            choose_mode(redirected),
+            Mode::Unsupported("output isn't going to a terminal")
+        );
+        let no_raw_mode = Capabilities {
+            raw_mode: false,
+            dumb: true,
+            ..CAPABLE
+        };
+        assert_eq!(
+            choose_mode(no_raw_mode),
+            Mode::Unsupported("it doesn't support raw mode")
+        );
+        let no_alternate_screen = Capabilities {
+            alternate_screen: false,
+            ..CAPABLE
+        };
+        assert_eq!(
+            choose_mode(no_alternate_screen),
+            Mode::Unsupported("it doesn't support the alternate screen")
+        );
+    }
+
+    #[test]
+    fn unsupported_terminals_suggest_the_list_command() {
+        assert_eq!(
+            Unsupported("it doesn't support raw mode").to_string(),
+            "This terminal can't show the interface, as it doesn't support raw mode.\nTry `vatsim_online list` for a plain table, or `vatsim_online list --format json` for JSON."
+        );
+    }
+
+    #[test]
+    fn plain_drops_colors_and_unicode() {
+        let area = Rect::new(0, 0, 8, 1);
+        let mut buf = Buffer::empty(area);
+        buf.set_string(
+            0,
+            0,
+            "A\u{2014}\u{2192}\u{2588}\u{00e9}",
+            Style::default().fg(Color::Red),
+        );
+        buf.set_string(
+            5,
+            0,
+            "hdr",
+            Style::default().fg(Color::Black).bg(Color::LightGreen),
+        );
+        Plain.render(area, &mut buf);
+        let symbols: String = buf
+            .content
+            .iter()
+            .map(|cell| cell.symbol.as_str())
+            .collect();
+        // glyphs without an ASCII stand-in are left as they are
+        assert_eq!(symbols, "A->#\u{00e9}hdr");
+        assert!(buf
+            .content
+            .iter()
+            .all(|cell| cell.fg == Color::Reset && cell.bg == Color::Reset));
+        assert!(!buf.get(0, 0).modifier.contains(Modifier::REVERSED));
+        // what had a background stands out in reverse video
+        assert!(buf.get(5, 0).modifier.contains(Modifier::REVERSED));
+    }
+}
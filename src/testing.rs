@@ -0,0 +1,158 @@
+//! Pilots and controllers for tests.
+
+use crate::{
+    mirrors::{self, MirrorLatency},
+    models::{Controller, FlightPlan, Pilot},
+};
+use std::{
+    fs,
+    io::{Read, Write},
+    net::TcpListener,
+    path::Path,
+    thread,
+    time::Duration,
+};
+
+/// A pilot at 0,0 on the ground, without a flight plan.
+pub fn pilot(callsign: &str) -> Pilot {
+    Pilot {
+        cid: 1_000_000,
+        name: "Test Pilot".to_owned(),
+        callsign: callsign.to_owned(),
+        server: "USA-EAST".to_owned(),
+        pilot_rating: 0,
+        latitude: 0.0,
+        longitude: 0.0,
+        altitude: 0,
+        groundspeed: 0,
+        transponder: "2000".to_owned(),
+        heading: 0,
+        qnh_i_hg: 29.92,
+        qnh_mb: 1013,
+        flight_plan: None,
+        logon_time: "2022-08-01T12:00:00Z".to_owned(),
+        last_updated: "2022-08-01T12:30:00Z".to_owned(),
+    }
+}
+
+/// An IFR flight plan from one airport to another.
+pub fn flight_plan(departure: &str, arrival: &str) -> FlightPlan {
+    FlightPlan {
+        flight_rules: "I".to_owned(),
+        aircraft: "B738/M-SDE2E3FGHIJ2J3J4J5M1RWXY/LB1D1".to_owned(),
+        aircraft_faa: "B738/L".to_owned(),
+        aircraft_short: "B738".to_owned(),
+        departure: departure.to_owned(),
+        arrival: arrival.to_owned(),
+        alternate: String::new(),
+        cruise_tas: "450".to_owned(),
+        altitude: "35000".to_owned(),
+        deptime: "1300".to_owned(),
+        enroute_time: "0200".to_owned(),
+        fuel_time: "0400".to_owned(),
+        remarks: "/V/".to_owned(),
+        route: "DCT".to_owned(),
+        revision_id: 1,
+        assigned_transponder: "0000".to_owned(),
+    }
+}
+
+/// A pilot with a flight plan.
+pub fn flying(callsign: &str, departure: &str, arrival: &str) -> Pilot {
+    Pilot {
+        flight_plan: Some(flight_plan(departure, arrival)),
+        ..pilot(callsign)
+    }
+}
+
+/// A controller working a position, with a facility id like 4 for tower.
+pub fn controller(callsign: &str, facility: i64) -> Controller {
+    Controller {
+        cid: 2_000_000,
+        name: "Test Controller".to_owned(),
+        callsign: callsign.to_owned(),
+        frequency: "118.500".to_owned(),
+        facility,
+        rating: 5,
+        server: "UK-1".to_owned(),
+        visual_range: 50,
+        text_atis: None,
+        last_updated: "2022-08-01T12:30:00Z".to_owned(),
+        logon_time: "2022-08-01T12:00:00Z".to_owned(),
+    }
+}
+
+/// Compare output to its golden file in `src/testdata`, or rewrite the
+/// file if `UPDATE_GOLDEN` is set.
+#[allow(dead_code)] // only the binary's tests have golden files
+pub fn golden(name: &str, actual: &str) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("src/testdata")
+        .join(name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&path, format!("{}\n", actual)).unwrap();
+    }
+    let expected = fs::read_to_string(&path).unwrap();
+    assert_eq!(
+        actual,
+        expected.trim_end_matches('\n'),
+        "{}",
+        path.display()
+    );
+}
+
+/// Start a server on a local port that answers every request with
+/// `status` after `delay`, returning its URL.
+#[allow(dead_code)] // only the library's tests race mirrors
+pub fn slow_server(status: u16, delay: Duration) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/data", listener.local_addr().unwrap());
+    let _server = thread::spawn(move || {
+        for mut stream in listener.incoming().filter_map(Result::ok) {
+            let _answer = thread::spawn(move || {
+                let mut request = Vec::new();
+                let mut buffer = [0; 1024];
+                while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                    match stream.read(&mut buffer) {
+                        Ok(0) | Err(_) => return,
+                        Ok(n) => request.extend_from_slice(&buffer[..n]),
+                    }
+                }
+                thread::sleep(delay);
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {} Test\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    status
+                );
+            });
+        }
+    });
+    url
+}
+
+/// URL of a local port nothing is listening on.
+#[allow(dead_code)] // only the library's tests race mirrors
+pub fn refused_url() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    format!("http://{}/data", listener.local_addr().unwrap())
+}
+
+/// Check a race of the mirrors from [`slow_server`] with a delay, no
+/// delay, an error status, too long a delay, and from [`refused_url`], in
+/// that order: the two healthy ones answered, fastest first.
+#[allow(dead_code)] // only the library's tests race mirrors
+pub fn check_race(urls: &[String], latencies: &[MirrorLatency]) {
+    let answered: Vec<_> = latencies.iter().map(|l| l.latency.is_some()).collect();
+    assert_eq!(answered, [true, true, false, false, false]);
+    assert_eq!(
+        latencies.iter().map(|l| l.url.as_str()).collect::<Vec<_>>(),
+        urls
+    );
+    assert!(latencies[1].latency < latencies[0].latency);
+    assert!(latencies[0].latency >= Some(Duration::from_millis(300)));
+
+    let mut ordered = urls.to_vec();
+    mirrors::order_by_latency(&mut ordered, latencies);
+    let order = [1, 0, 2, 3, 4].map(|i| urls[i].clone());
+    assert_eq!(ordered, order);
+}
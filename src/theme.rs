@@ -0,0 +1,130 @@
+//! Colors for the interface, looked up by named slot.
+
+use crate::reference::Facility;
+use tui::style::{Color, Modifier, Style};
+
+/// A set of styles for named slots like "facility.ctr".
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    slots: Vec<(&'static str, Style)>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            slots: vec![
+                (
+                    "favorite",
+                    Style::default()
+                        .fg(Color::Yellow)
+                        .add_modifier(Modifier::BOLD),
+                ),
+                ("pilot.vfr", Style::default().fg(Color::LightCyan)),
+                ("facility.ctr", Style::default().fg(Color::LightBlue)),
+                ("facility.app", Style::default().fg(Color::LightMagenta)),
+                ("facility.twr", Style::default().fg(Color::LightGreen)),
+                ("facility.obs", Style::default().fg(Color::DarkGray)),
+            ],
+        }
+    }
+}
+
+impl Theme {
+    /// A theme without colors, for terminals that can't show them.
+    pub fn mono() -> Self {
+        Self {
+            slots: vec![
+                ("favorite", Style::default().add_modifier(Modifier::BOLD)),
+                ("pilot.vfr", Style::default().add_modifier(Modifier::ITALIC)),
+                ("facility.obs", Style::default().add_modifier(Modifier::DIM)),
+            ],
+        }
+    }
+
+    /// Style for a slot.
+    ///
+    /// A slot the theme doesn't set falls back to its parent ("facility.ctr"
+    /// to "facility"), and then to the terminal's default style.
+    pub fn style(&self, slot: &str) -> Style {
+        let mut slot = slot;
+        loop {
+            if let Some((_, style)) = self.slots.iter().find(|(name, _)| *name == slot) {
+                return *style;
+            }
+            match slot.rsplit_once('.') {
+                Some((parent, _)) => slot = parent,
+                None => return Style::default(),
+            }
+        }
+    }
+}
+
+/// Theme slot for a controller, by their facility.
+///
+/// ATIS stations are dimmed along with observers.
+pub fn facility_slot(facility: Option<Facility>, callsign: &str) -> &'static str {
+    if callsign.to_uppercase().ends_with("_ATIS") {
+        return "facility.obs";
+    }
+    match facility {
+        Some(Facility::FlightService | Facility::Center) => "facility.ctr",
+        Some(Facility::Approach) => "facility.app",
+        Some(Facility::Delivery | Facility::Ground | Facility::Tower) => "facility.twr",
+        _ => "facility.obs",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn facilities_map_to_slots() {
+        for (facility, slot) in [
+            (Facility::Center, "facility.ctr"),
+            (Facility::FlightService, "facility.ctr"),
+            (Facility::Approach, "facility.app"),
+            (Facility::Tower, "facility.twr"),
+            (Facility::Ground, "facility.twr"),
+            (Facility::Delivery, "facility.twr"),
+            (Facility::Observer, "facility.obs"),
+            (Facility::Unknown(9), "facility.obs"),
+        ] {
+            assert_eq!(facility_slot(Some(facility), "ABC_XYZ"), slot);
+        }
+        assert_eq!(facility_slot(None, "ABC_XYZ"), "facility.obs");
+    }
+
+    #[test]
+    fn atis_is_dimmed_whatever_its_facility() {
+        assert_eq!(
+            facility_slot(Some(Facility::Tower), "egll_atis"),
+            "facility.obs"
+        );
+    }
+
+    #[test]
+    fn slots_fall_back_to_their_parent_then_the_default() {
+        let theme = Theme {
+            slots: vec![
+                ("facility", Style::default().fg(Color::Red)),
+                ("facility.app", Style::default().fg(Color::Blue)),
+            ],
+        };
+        assert_eq!(theme.style("facility.app").fg, Some(Color::Blue));
+        assert_eq!(theme.style("facility.ctr").fg, Some(Color::Red));
+        assert_eq!(theme.style("favorite"), Style::default());
+    }
+
+    #[test]
+    fn mono_theme_has_no_colors() {
+        let theme = Theme::mono();
+        for slot in ["favorite", "pilot.vfr", "facility.ctr", "facility.obs"] {
+            assert_eq!(theme.style(slot).fg, None);
+        }
+        assert!(theme
+            .style("facility.obs")
+            .add_modifier
+            .contains(Modifier::DIM));
+    }
+}
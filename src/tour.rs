@@ -0,0 +1,65 @@
+//! The tour of the interface, shown on first run and by the `tour` command.
+
+use crate::{config, session};
+
+/// Keys to know, and what they do.
+const KEYS: [(&str, &str); 12] = [
+    ("Tab", "switch between pilots, controllers, and events"),
+    ("Up/Down", "move through the table"),
+    (
+        "Left/Right",
+        "scroll the columns; < and > resize the sorted one",
+    ),
+    ("S, 0", "sort by a column; reset the sort"),
+    (
+        "Shift+F, /",
+        "build a filter; search with a query like callsign=BAW*",
+    ),
+    ("T", "tune a frequency to see who's on it"),
+    ("Z", "switch the row density"),
+    ("Enter", "examine the selected client; S copies a summary"),
+    ("O", "open online stats or the event page"),
+    ("Esc", "close a popup or prompt"),
+    ("Shift+D", "show the debug overlay"),
+    ("Q", "exit"),
+];
+
+/// The tour: the keys to know and where settings and state live.
+pub fn text() -> String {
+    let width = KEYS.iter().map(|(key, _)| key.len()).max().unwrap_or(0);
+    let keys: Vec<String> = KEYS
+        .iter()
+        .map(|(key, action)| format!("  {:width$}  {}\n", key, action, width = width))
+        .collect();
+    let session = session::session_path().map_or_else(
+        || "aren't saved on this platform".to_owned(),
+        |path| format!("are saved to {}", path.display()),
+    );
+    let config = config::config_path().map_or_else(
+        || "in a config file".to_owned(),
+        |path| format!("in {}", path.display()),
+    );
+    format!(
+        "vatsim_online shows who's flying and controlling on VATSIM.\n\nKeys:\n{}\nSettings are command-line flags; run with --help to see them all.\nThey can also be kept {}, with named profiles chosen by --profile.\nYour tab, selection, search history, and column widths {}.\n",
+        keys.concat(),
+        config,
+        session
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_every_key_aligned() {
+        let text = text();
+        assert!(text.starts_with("vatsim_online shows"));
+        for (key, action) in KEYS {
+            let line = format!("  {:10}  {}", key, action);
+            assert!(text.lines().any(|l| l == line), "{:?} missing", line);
+        }
+        assert!(text.contains("--help"));
+        assert!(text.contains("--profile"));
+    }
+}
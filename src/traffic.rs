@@ -0,0 +1,251 @@
+//! Guessing which pilots a controller is working, without frequency data.
+
+use crate::{
+    callsign,
+    geo::GridIndex,
+    models::{Controller, Pilot},
+    reference::Facility,
+};
+use std::fmt;
+
+/// How far from a controller, and how high, their traffic plausibly is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Reach {
+    facility: Facility,
+    /// Radius in nm, or `None` for the controller's visual range.
+    radius_nm: Option<f64>,
+    /// Highest altitude in feet, or `None` for no ceiling.
+    ceiling_ft: Option<i64>,
+}
+
+/// Reach of each facility that works traffic.
+const REACHES: [Reach; 6] = [
+    Reach {
+        facility: Facility::Delivery,
+        radius_nm: Some(5.0),
+        ceiling_ft: Some(5_000),
+    },
+    Reach {
+        facility: Facility::Ground,
+        radius_nm: Some(5.0),
+        ceiling_ft: Some(5_000),
+    },
+    Reach {
+        facility: Facility::Tower,
+        radius_nm: Some(5.0),
+        ceiling_ft: Some(10_000),
+    },
+    Reach {
+        facility: Facility::Approach,
+        radius_nm: Some(40.0),
+        ceiling_ft: Some(18_000),
+    },
+    Reach {
+        facility: Facility::Center,
+        radius_nm: None,
+        ceiling_ft: None,
+    },
+    Reach {
+        facility: Facility::FlightService,
+        radius_nm: None,
+        ceiling_ft: None,
+    },
+];
+
+/// Where a controller is and how far they can see.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Position {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub visual_range_nm: f64,
+}
+
+impl Position {
+    /// Place a controller at their airport, if their callsign names a known one.
+    pub fn resolve(controller: &Controller) -> Option<Self> {
+        let airport = callsign::controller_airport(&controller.callsign)?;
+        #[allow(clippy::cast_precision_loss)]
+        let visual_range_nm = controller.visual_range.max(0) as f64;
+        Some(Self {
+            latitude: airport.latitude,
+            longitude: airport.longitude,
+            visual_range_nm,
+        })
+    }
+}
+
+/// Pilots close enough to, and low enough for, a facility at a position
+/// to plausibly be on its frequency, looked up in an index of the pilots'
+/// positions.
+///
+/// Facilities that don't work traffic, like observers, have none.
+pub fn plausible_traffic<'a>(
+    pilots: &'a [Pilot],
+    index: &GridIndex,
+    facility: Facility,
+    position: &Position,
+) -> Vec<&'a Pilot> {
+    let Some(reach) = REACHES.iter().find(|reach| reach.facility == facility) else {
+        return Vec::new();
+    };
+    let radius_nm = reach.radius_nm.unwrap_or(position.visual_range_nm);
+    index
+        .within(position.latitude, position.longitude, radius_nm)
+        .into_iter()
+        .filter_map(|i| pilots.get(i))
+        .filter(|pilot| {
+            reach
+                .ceiling_ft
+                .is_none_or(|ceiling| pilot.altitude <= ceiling)
+        })
+        .collect()
+}
+
+/// A controller's plausible traffic, or `None` if they can't be placed on
+/// the map or their facility isn't known.
+///
+/// The index is of the pilots by their position in the list.
+pub fn estimate<'a>(
+    controller: &Controller,
+    pilots: &'a [Pilot],
+    index: &GridIndex,
+) -> Option<Vec<&'a Pilot>> {
+    let facility = controller
+        .facility_enum()
+        .filter(|facility| !matches!(facility, Facility::Unknown(_)))?;
+    let position = Position::resolve(controller)?;
+    Some(plausible_traffic(pilots, index, facility, &position))
+}
+
+/// How busy an approach or center controller plausibly is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Load {
+    Low,
+    Medium,
+    High,
+}
+
+impl Load {
+    /// Bucket a controller's plausible traffic count.
+    ///
+    /// Approach covers less airspace than center, so it's busy with fewer
+    /// pilots. Other facilities have no load.
+    pub fn from_traffic(facility: Facility, count: usize) -> Option<Self> {
+        let (medium, high) = match facility {
+            Facility::Approach => (5, 15),
+            Facility::Center | Facility::FlightService => (10, 30),
+            _ => return None,
+        };
+        Some(if count >= high {
+            Load::High
+        } else if count >= medium {
+            Load::Medium
+        } else {
+            Load::Low
+        })
+    }
+}
+
+impl fmt::Display for Load {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Load::Low => "Low",
+            Load::Medium => "Medium",
+            Load::High => "High",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{geo, testing};
+
+    /// San Francisco's airport, from the airport list.
+    const KSFO: (f64, f64) = (37.62, -122.38);
+
+    /// A pilot due north of San Francisco.
+    fn north(callsign: &str, nm: f64, altitude: i64) -> Pilot {
+        Pilot {
+            latitude: KSFO.0 + nm / 60.0,
+            longitude: KSFO.1,
+            altitude,
+            ..testing::pilot(callsign)
+        }
+    }
+
+    fn pilots() -> Vec<Pilot> {
+        vec![
+            north("TAXI", 0.5, 10),
+            north("CLIMB", 4.0, 8_000),
+            north("FINAL", 12.0, 4_000),
+            north("ARRIVAL", 35.0, 15_000),
+            north("CRUISE", 45.0, 35_000),
+            north("FAR", 120.0, 2_000),
+        ]
+    }
+
+    /// Callsigns of the plausible traffic for a controller.
+    fn traffic(callsign: &str, facility: i64) -> Option<Vec<String>> {
+        let pilots = pilots();
+        let index = geo::pilot_index(&pilots);
+        let controller = testing::controller(callsign, facility);
+        estimate(&controller, &pilots, &index)
+            .map(|found| found.iter().map(|p| p.callsign.clone()).collect())
+    }
+
+    #[test]
+    fn each_facility_has_its_reach() {
+        let some = |callsigns: &[&str]| Some(callsigns.iter().map(|c| (*c).to_owned()).collect());
+        assert_eq!(traffic("SFO_DEL", 2), some(&["TAXI"]));
+        assert_eq!(traffic("SFO_GND", 3), some(&["TAXI"]));
+        assert_eq!(traffic("SFO_TWR", 4), some(&["TAXI", "CLIMB"]));
+        assert_eq!(
+            traffic("SFO_APP", 5),
+            some(&["TAXI", "CLIMB", "FINAL", "ARRIVAL"])
+        );
+        // centers see as far as their visual range, at any altitude
+        assert_eq!(
+            traffic("SFO_CTR", 6),
+            some(&["TAXI", "CLIMB", "FINAL", "ARRIVAL", "CRUISE"])
+        );
+        assert_eq!(traffic("SFO_OBS", 0), Some(Vec::new()));
+    }
+
+    #[test]
+    fn unresolved_positions_have_no_estimate() {
+        assert_eq!(traffic("XXXX_TWR", 4), None);
+        assert_eq!(traffic("LON_SC_CTR", 6), None);
+        assert_eq!(traffic("SFO_TWR", 9), None);
+    }
+
+    #[test]
+    fn resolves_to_the_airport_with_visual_range() {
+        let mut controller = testing::controller("SFO_1_TWR", 4);
+        controller.visual_range = -5;
+        let position = Position::resolve(&controller).unwrap();
+        assert_eq!(
+            (
+                position.latitude,
+                position.longitude,
+                position.visual_range_nm
+            ),
+            (KSFO.0, KSFO.1, 0.0)
+        );
+    }
+
+    #[test]
+    fn load_buckets_by_facility() {
+        assert_eq!(Load::from_traffic(Facility::Approach, 4), Some(Load::Low));
+        assert_eq!(
+            Load::from_traffic(Facility::Approach, 5),
+            Some(Load::Medium)
+        );
+        assert_eq!(Load::from_traffic(Facility::Approach, 15), Some(Load::High));
+        assert_eq!(Load::from_traffic(Facility::Center, 15), Some(Load::Medium));
+        assert_eq!(Load::from_traffic(Facility::Center, 30), Some(Load::High));
+        assert_eq!(Load::from_traffic(Facility::Tower, 100), None);
+        assert_eq!(Load::High.to_string(), "High");
+    }
+}
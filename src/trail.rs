@@ -0,0 +1,333 @@
+//! Where each pilot has been this session, from the positions in the
+//! data they've appeared in.
+
+use crate::{bounded::RingBuffer, geo, models::Pilot};
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+/// Positions kept per pilot.
+pub const TRAIL_LENGTH: usize = 20;
+
+/// A pilot's position at one update.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrailPoint {
+    pub time: DateTime<Utc>,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub altitude: i64,
+    pub groundspeed: i64,
+}
+
+impl TrailPoint {
+    /// A pilot's latest position, if their update time parses.
+    fn of(pilot: &Pilot) -> Option<Self> {
+        Some(Self {
+            time: pilot.last_update()?,
+            latitude: pilot.latitude,
+            longitude: pilot.longitude,
+            altitude: pilot.altitude,
+            groundspeed: pilot.groundspeed,
+        })
+    }
+}
+
+/// A pilot's recent positions, and how far they've gone since first seen.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trail {
+    points: RingBuffer<TrailPoint>,
+    /// Distance between every pair of updates, including those whose
+    /// positions have been dropped.
+    distance_nm: f64,
+    /// When the pilot was first seen.
+    started: DateTime<Utc>,
+}
+
+impl Trail {
+    /// A trail starting at a point, keeping at most `capacity` points.
+    pub fn new(point: TrailPoint, capacity: usize) -> Self {
+        let mut points = RingBuffer::new(capacity);
+        points.push(point);
+        Self {
+            points,
+            distance_nm: 0.0,
+            started: point.time,
+        }
+    }
+
+    /// Add a point, if it's newer than the last one.
+    pub fn push(&mut self, point: TrailPoint) {
+        if let Some(last) = self.points.last() {
+            if point.time <= last.time {
+                return;
+            }
+            self.distance_nm += geo::distance_nm(
+                last.latitude,
+                last.longitude,
+                point.latitude,
+                point.longitude,
+            );
+        }
+        self.points.push(point);
+    }
+
+    /// The kept points, oldest first.
+    pub fn points(&self) -> impl Iterator<Item = &TrailPoint> {
+        self.points.iter()
+    }
+
+    /// Distance covered since the pilot was first seen.
+    pub fn distance_nm(&self) -> f64 {
+        self.distance_nm
+    }
+
+    /// Average groundspeed in knots since the pilot was first seen, once
+    /// they've been seen at two different times.
+    pub fn average_groundspeed(&self) -> Option<f64> {
+        let elapsed = self.points.last()?.time - self.started;
+        #[allow(clippy::cast_precision_loss)]
+        let hours = elapsed.num_seconds() as f64 / 3600.0;
+        (hours > 0.0).then(|| self.distance_nm / hours)
+    }
+}
+
+/// Trails of the pilots online, by CID.
+#[derive(Debug, Clone, Default)]
+pub struct Trails {
+    by_cid: HashMap<i64, Trail>,
+}
+
+impl Trails {
+    /// Add the pilots' latest positions, forgetting pilots who are gone.
+    pub fn update<'a>(&mut self, pilots: impl IntoIterator<Item = &'a Pilot>) {
+        let mut trails = HashMap::new();
+        for pilot in pilots {
+            let Some(point) = TrailPoint::of(pilot) else {
+                continue;
+            };
+            let trail = match self.by_cid.remove(&pilot.cid) {
+                Some(mut trail) => {
+                    trail.push(point);
+                    trail
+                }
+                None => Trail::new(point, TRAIL_LENGTH),
+            };
+            let _ = trails.insert(pilot.cid, trail);
+        }
+        self.by_cid = trails;
+    }
+
+    /// A pilot's trail, if they've been seen.
+    pub fn get(&self, cid: i64) -> Option<&Trail> {
+        self.by_cid.get(&cid)
+    }
+
+    /// Number of positions kept across all the trails.
+    pub fn retained(&self) -> usize {
+        self.by_cid
+            .values()
+            .map(|trail| trail.points().count())
+            .sum()
+    }
+}
+
+/// The kept points as GeoJSON line coordinates, split where they cross
+/// the antimeridian so that maps don't draw the line around the world.
+fn lines(trail: &Trail) -> Vec<Vec<[f64; 2]>> {
+    let mut lines = vec![Vec::new()];
+    let mut last: Option<&TrailPoint> = None;
+    for point in trail.points() {
+        if let Some(last) = last {
+            let delta = point.longitude - last.longitude;
+            if delta.abs() > 180.0 {
+                // the meridian left from, and the one arrived at
+                let (leaving, entering) = if delta < 0.0 {
+                    (180.0, -180.0)
+                } else {
+                    (-180.0, 180.0)
+                };
+                let fraction = (leaving - last.longitude).abs() / (360.0 - delta.abs());
+                let latitude = last.latitude + (point.latitude - last.latitude) * fraction;
+                if let Some(line) = lines.last_mut() {
+                    line.push([leaving, latitude]);
+                }
+                lines.push(vec![[entering, latitude]]);
+            }
+        }
+        if let Some(line) = lines.last_mut() {
+            line.push([point.longitude, point.latitude]);
+        }
+        last = Some(point);
+    }
+    lines
+}
+
+/// A trail as a GeoJSON feature with a LineString of its kept points, or
+/// a MultiLineString if it crosses the antimeridian, and the callsign,
+/// altitudes, and groundspeeds as properties.
+///
+/// A trail of one point isn't a line yet, so it has none.
+pub fn geojson(callsign: &str, trail: &Trail) -> Option<Value> {
+    if trail.points().count() < 2 {
+        return None;
+    }
+    let mut lines = lines(trail);
+    let geometry = if lines.len() == 1 {
+        json!({ "type": "LineString", "coordinates": lines.remove(0) })
+    } else {
+        json!({ "type": "MultiLineString", "coordinates": lines })
+    };
+    Some(json!({
+        "type": "Feature",
+        "geometry": geometry,
+        "properties": {
+            "callsign": callsign,
+            "times": trail.points().map(|point| point.time.to_rfc3339()).collect::<Vec<_>>(),
+            "altitudes": trail.points().map(|point| point.altitude).collect::<Vec<_>>(),
+            "groundspeeds": trail.points().map(|point| point.groundspeed).collect::<Vec<_>>(),
+            "distance_nm": trail.distance_nm(),
+        },
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use chrono::TimeZone;
+
+    /// A point some minutes after noon.
+    fn point(minutes: i64, latitude: f64, longitude: f64) -> TrailPoint {
+        TrailPoint {
+            time: Utc.ymd(2022, 8, 1).and_hms(12, 0, 0) + chrono::Duration::minutes(minutes),
+            latitude,
+            longitude,
+            altitude: 35_000,
+            groundspeed: 480,
+        }
+    }
+
+    /// A trail through these points, a minute apart.
+    fn through(points: &[(f64, f64)], capacity: usize) -> Trail {
+        let mut minutes = 0..;
+        let mut points = points
+            .iter()
+            .map(|&(latitude, longitude)| point(minutes.next().unwrap(), latitude, longitude));
+        let mut trail = Trail::new(points.next().unwrap(), capacity);
+        for point in points {
+            trail.push(point);
+        }
+        trail
+    }
+
+    fn longitudes(trail: &Trail) -> Vec<f64> {
+        trail.points().map(|point| point.longitude).collect()
+    }
+
+    #[test]
+    fn oldest_points_are_evicted() {
+        let path: Vec<(f64, f64)> = (0..25).map(|i| (0.0, f64::from(i))).collect();
+        let trail = through(&path, 20);
+        let kept = longitudes(&trail);
+        assert_eq!(kept.len(), 20);
+        assert_eq!(kept.first(), Some(&5.0));
+        assert_eq!(kept.last(), Some(&24.0));
+        // the distance still counts the evicted points: 24 degrees of the
+        // equator is 1,440 nm
+        assert!(
+            (trail.distance_nm() - 1_440.0).abs() < 5.0,
+            "{}",
+            trail.distance_nm()
+        );
+    }
+
+    #[test]
+    fn stale_updates_are_skipped() {
+        let mut trail = Trail::new(point(10, 0.0, 0.0), 20);
+        trail.push(point(10, 0.0, 1.0));
+        trail.push(point(5, 0.0, 2.0));
+        assert_eq!(longitudes(&trail), [0.0]);
+        assert!(trail.distance_nm().abs() < f64::EPSILON);
+        assert_eq!(trail.average_groundspeed(), None);
+    }
+
+    #[test]
+    fn distance_across_the_antimeridian() {
+        let trail = through(&[(0.0, 179.5), (0.0, -179.5), (0.0, 179.5)], 20);
+        // a degree each way, not around the world
+        assert!(
+            (trail.distance_nm() - 120.0).abs() < 1.0,
+            "{}",
+            trail.distance_nm()
+        );
+        // over two minutes
+        let average = trail.average_groundspeed().unwrap();
+        assert!((average - 3_600.0).abs() < 30.0, "{}", average);
+    }
+
+    #[test]
+    fn trails_follow_the_pilots_online() {
+        let at = |callsign: &str, cid: i64, time: &str, longitude: f64| Pilot {
+            cid,
+            longitude,
+            last_updated: format!("2022-08-01T{}Z", time),
+            ..testing::pilot(callsign)
+        };
+        let mut trails = Trails::default();
+        trails.update(&[
+            at("BAW1", 1, "12:00:00", 0.0),
+            at("DLH1", 2, "12:00:00", 8.0),
+        ]);
+        trails.update(&[
+            at("BAW1", 1, "12:01:00", 1.0),
+            Pilot {
+                last_updated: "garbage".to_owned(),
+                ..at("AFR1", 3, "12:01:00", 2.0)
+            },
+        ]);
+        assert_eq!(longitudes(trails.get(1).unwrap()), [0.0, 1.0]);
+        assert!(trails.get(2).is_none());
+        assert!(trails.get(3).is_none());
+        assert_eq!(trails.retained(), 2);
+        trails.update(&[]);
+        assert_eq!(trails.retained(), 0);
+    }
+
+    #[test]
+    fn geojson_lines() {
+        let trail = through(&[(51.0, -1.0), (52.0, 0.0), (53.0, 1.0)], 20);
+        let feature = geojson("BAW1", &trail).unwrap();
+        assert_eq!(feature["type"], "Feature");
+        assert_eq!(feature["geometry"]["type"], "LineString");
+        assert_eq!(
+            feature["geometry"]["coordinates"],
+            json!([[-1.0, 51.0], [0.0, 52.0], [1.0, 53.0]])
+        );
+        assert_eq!(feature["properties"]["callsign"], "BAW1");
+        assert_eq!(
+            feature["properties"]["altitudes"],
+            json!([35_000, 35_000, 35_000])
+        );
+        assert_eq!(
+            feature["properties"]["times"][0],
+            "2022-08-01T12:00:00+00:00"
+        );
+        assert_eq!(geojson("BAW1", &through(&[(51.0, -1.0)], 20)), None);
+    }
+
+    #[test]
+    fn geojson_splits_at_the_antimeridian() {
+        // east across it, then back west
+        let trail = through(&[(10.0, 179.0), (12.0, -179.0), (14.0, 179.0)], 20);
+        let feature = geojson("QFA1", &trail).unwrap();
+        assert_eq!(feature["geometry"]["type"], "MultiLineString");
+        assert_eq!(
+            feature["geometry"]["coordinates"],
+            json!([
+                [[179.0, 10.0], [180.0, 11.0]],
+                [[-180.0, 11.0], [-179.0, 12.0], [-180.0, 13.0]],
+                [[180.0, 13.0], [179.0, 14.0]],
+            ])
+        );
+    }
+}
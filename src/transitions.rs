@@ -0,0 +1,158 @@
+//! Changes to who's connected as what, between two refreshes.
+
+use crate::diff::ChangeEvent;
+use std::fmt;
+
+/// Most callsign changes described in the status bar at once.
+const MAX_DESCRIBED: usize = 3;
+
+/// A client who reconnected under a different callsign, like a pilot
+/// correcting a typo or a controller moving from ground to tower.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallsignChange {
+    pub cid: i64,
+    pub from: String,
+    pub to: String,
+}
+
+impl fmt::Display for CallsignChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} \u{2192} {}", self.cid, self.from, self.to)
+    }
+}
+
+/// Pilots and controllers whose CID is still connected, but under another
+/// callsign, ordered by CID.
+pub fn callsign_changes(events: &[ChangeEvent]) -> Vec<CallsignChange> {
+    events
+        .iter()
+        .filter_map(|event| match event {
+            ChangeEvent::CallsignChanged { cid, from, to } => Some(CallsignChange {
+                cid: *cid,
+                from: from.clone(),
+                to: to.clone(),
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Describe callsign changes for the status bar, like
+/// "1234567: SFO_GND → SFO_TWR", with how many more there are past the first few.
+///
+/// Returns `None` if there are none.
+pub fn describe(changes: &[CallsignChange]) -> Option<String> {
+    if changes.is_empty() {
+        return None;
+    }
+    let mut parts: Vec<String> = changes
+        .iter()
+        .take(MAX_DESCRIBED)
+        .map(ToString::to_string)
+        .collect();
+    if changes.len() > MAX_DESCRIBED {
+        parts.push(format!("and {} more", changes.len() - MAX_DESCRIBED));
+    }
+    Some(parts.join("; "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        diff,
+        models::{Controller, Pilot, V3ResponseData},
+        testing,
+    };
+
+    fn pilot(cid: i64, callsign: &str) -> Pilot {
+        Pilot {
+            cid,
+            ..testing::pilot(callsign)
+        }
+    }
+
+    fn controller(cid: i64, callsign: &str) -> Controller {
+        Controller {
+            cid,
+            ..testing::controller(callsign, 4)
+        }
+    }
+
+    fn snapshot(pilots: Vec<Pilot>, controllers: Vec<Controller>) -> V3ResponseData {
+        V3ResponseData {
+            pilots,
+            controllers,
+            ..V3ResponseData::default()
+        }
+    }
+
+    fn changes(old: &V3ResponseData, new: &V3ResponseData) -> Vec<String> {
+        callsign_changes(&diff::diff_snapshots(old, new))
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn reconnects_under_another_callsign() {
+        let old = snapshot(vec![pilot(1, "BAW12")], vec![controller(2, "SFO_GND")]);
+        let new = snapshot(vec![pilot(1, "BAW123")], vec![controller(2, "SFO_TWR")]);
+        assert_eq!(
+            changes(&old, &new),
+            ["1: BAW12 \u{2192} BAW123", "2: SFO_GND \u{2192} SFO_TWR"]
+        );
+        // and not a disconnection plus a connection
+        assert_eq!(diff::diff_snapshots(&old, &new).len(), 2);
+        assert!(changes(&new, &new).is_empty());
+    }
+
+    #[test]
+    fn swapped_callsigns_are_two_changes() {
+        let old = snapshot(vec![pilot(1, "BAW1"), pilot(2, "BAW2")], Vec::new());
+        let new = snapshot(vec![pilot(1, "BAW2"), pilot(2, "BAW1")], Vec::new());
+        assert_eq!(
+            changes(&old, &new),
+            ["1: BAW1 \u{2192} BAW2", "2: BAW2 \u{2192} BAW1"]
+        );
+    }
+
+    #[test]
+    fn flying_and_controlling_at_once_is_not_a_change() {
+        let old = snapshot(vec![pilot(1, "BAW1")], Vec::new());
+        let new = snapshot(vec![pilot(1, "BAW1")], vec![controller(1, "SFO_TWR")]);
+        assert!(changes(&old, &new).is_empty());
+        assert!(changes(&new, &old).is_empty());
+        let moved = snapshot(vec![pilot(1, "BAW1")], vec![controller(1, "SFO_APP")]);
+        assert_eq!(changes(&new, &moved), ["1: SFO_TWR \u{2192} SFO_APP"]);
+    }
+
+    #[test]
+    fn cids_connected_twice_are_left_out() {
+        let old = snapshot(
+            Vec::new(),
+            vec![controller(1, "SFO_OBS"), controller(1, "SFO_TWR")],
+        );
+        let new = snapshot(Vec::new(), vec![controller(1, "SFO_APP")]);
+        assert!(changes(&old, &new).is_empty());
+    }
+
+    #[test]
+    fn describes_the_first_few() {
+        let change = |cid| CallsignChange {
+            cid,
+            from: "A".to_owned(),
+            to: "B".to_owned(),
+        };
+        assert_eq!(describe(&[]), None);
+        assert_eq!(
+            describe(&[change(1), change(2)]).as_deref(),
+            Some("1: A \u{2192} B; 2: A \u{2192} B")
+        );
+        let many: Vec<_> = (1..=5).map(change).collect();
+        assert_eq!(
+            describe(&many).as_deref(),
+            Some("1: A \u{2192} B; 2: A \u{2192} B; 3: A \u{2192} B; and 2 more")
+        );
+    }
+}
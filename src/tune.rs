@@ -0,0 +1,153 @@
+//! Tuning the controllers "tab" to one frequency, through a prompt.
+
+use crate::{action::Action, frequency, models::Controller, widgets::TextInput};
+
+/// Frequency typed into the tune prompt, with the last parse error.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TunePrompt {
+    pub input: TextInput,
+    pub error: Option<String>,
+}
+
+/// The frequency the controllers are filtered to, and the prompt to
+/// change it while it's open.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Tune {
+    /// Only show controllers on this frequency, in kHz.
+    tuned: Option<u32>,
+    prompt: Option<TunePrompt>,
+}
+
+impl Tune {
+    /// Open the prompt, starting from the tuned frequency.
+    pub fn open(&mut self) {
+        self.prompt = Some(TunePrompt {
+            input: TextInput::new(self.tuned.map(frequency::format_khz).unwrap_or_default()),
+            error: None,
+        });
+    }
+
+    /// The prompt, if it's open.
+    pub fn prompt(&self) -> Option<&TunePrompt> {
+        self.prompt.as_ref()
+    }
+
+    /// Type pasted text into the prompt.
+    pub fn paste(&mut self, text: &str) {
+        if let Some(prompt) = self.prompt.as_mut() {
+            prompt.input.paste(text);
+        }
+    }
+
+    /// Apply an action to the prompt, returning whether the tuned
+    /// frequency was set.
+    pub fn update(&mut self, action: Action) -> bool {
+        match action {
+            Action::Submit => return self.close(true),
+            Action::Cancel => return self.close(false),
+            _ => {}
+        }
+        let Some(prompt) = self.prompt.as_mut() else {
+            return false;
+        };
+        match action {
+            Action::Edit(edit) => prompt.input.edit(edit),
+            Action::InputChar(c) => prompt.input.insert(c),
+            _ => {}
+        }
+        false
+    }
+
+    /// Close the prompt, returning whether the tuned frequency was set.
+    ///
+    /// If `apply` is set, the typed frequency is tuned, or none if nothing
+    /// was typed. If the frequency doesn't parse, the prompt stays open
+    /// showing the error.
+    pub fn close(&mut self, apply: bool) -> bool {
+        if !apply {
+            self.prompt = None;
+            return false;
+        }
+        let Some(prompt) = self.prompt.as_mut() else {
+            return false;
+        };
+        let input = prompt.input.text().trim();
+        let tuned = if input.is_empty() {
+            None
+        } else if let Some(khz) = frequency::parse_khz(input) {
+            Some(khz)
+        } else {
+            prompt.error = Some(format!("\"{}\" isn't a frequency like 124.350", input));
+            return false;
+        };
+        self.prompt = None;
+        self.tuned = tuned;
+        true
+    }
+
+    /// Whether a controller is on the tuned frequency, or none is tuned.
+    pub fn matches(&self, controller: &Controller) -> bool {
+        self.tuned.is_none_or(|khz| {
+            frequency::parse_feed(&controller.frequency)
+                .is_some_and(|other| frequency::same_channel(khz, other))
+        })
+    }
+
+    /// The tuned frequency for the title, like "On 121.500".
+    pub fn label(&self) -> Option<String> {
+        self.tuned
+            .map(|khz| format!("On {}", frequency::format_khz(khz)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn type_text(tune: &mut Tune, text: &str) {
+        for c in text.chars() {
+            assert!(!tune.update(Action::InputChar(c)));
+        }
+    }
+
+    #[test]
+    fn prompt_tunes_and_starts_from_the_tuned_frequency() {
+        let mut tune = Tune::default();
+        assert!(!tune.update(Action::InputChar('1')));
+        tune.open();
+        type_text(&mut tune, "121.5");
+        assert!(tune.update(Action::Submit));
+        assert!(tune.prompt().is_none());
+        assert_eq!(tune.label().as_deref(), Some("On 121.500"));
+
+        tune.open();
+        assert_eq!(tune.prompt().unwrap().input.text(), "121.500");
+        assert!(!tune.update(Action::Cancel));
+        assert_eq!(tune.label().as_deref(), Some("On 121.500"));
+    }
+
+    #[test]
+    fn bad_frequencies_keep_the_prompt_open() {
+        let mut tune = Tune::default();
+        tune.open();
+        type_text(&mut tune, "12x");
+        assert!(!tune.update(Action::Submit));
+        assert!(tune.prompt().unwrap().error.is_some());
+        assert_eq!(tune.label(), None);
+    }
+
+    #[test]
+    fn matches_controllers_on_the_same_channel() {
+        let mut tune = Tune::default();
+        let mut controller = testing::controller("EGLL_N_APP", 5);
+        controller.frequency = "119.725".to_owned();
+        assert!(tune.matches(&controller));
+        tune.open();
+        type_text(&mut tune, "119.730");
+        assert!(tune.update(Action::Submit));
+        assert!(tune.matches(&controller));
+        controller.frequency = "118.500".to_owned();
+        assert!(!tune.matches(&controller));
+    }
+}
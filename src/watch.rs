@@ -0,0 +1,214 @@
+//! `watch`: print what changes on the network as it happens, one event a
+//! line, from the same change events the interface reacts to.
+
+use crate::{
+    api::{DataResponse, Vatsim},
+    diff::{self, ChangeEvent},
+    format::{self, DisplayZone, TimeStyle},
+    models::V3ResponseData,
+    privacy::Privacy,
+    report::{DataSource, Reporter},
+};
+use anyhow::Result;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::Serialize;
+use std::{thread, time::Duration};
+
+/// An event and when it was seen.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SeenEvent {
+    /// When the refresh that saw it finished, as an RFC 3339 timestamp.
+    pub time: String,
+    #[serde(flatten)]
+    pub event: ChangeEvent,
+}
+
+impl SeenEvent {
+    pub fn new(event: ChangeEvent, time: DateTime<Utc>) -> Self {
+        Self {
+            time: time.to_rfc3339_opts(SecondsFormat::Secs, true),
+            event,
+        }
+    }
+}
+
+/// Polls the network, turning each new snapshot into change events.
+#[derive(Debug)]
+pub struct Watcher {
+    api: Vatsim,
+    privacy: Privacy,
+    previous: Option<V3ResponseData>,
+}
+
+impl Watcher {
+    pub fn new(source: &DataSource) -> Result<Self> {
+        Ok(Self {
+            api: Vatsim::with_mirror(source.use_cache, false, source.user_agent, source.mirror)?,
+            privacy: source.privacy,
+            previous: None,
+        })
+    }
+
+    /// Whether a snapshot has been fetched to compare the next with.
+    pub fn started(&self) -> bool {
+        self.previous.is_some()
+    }
+
+    /// What changed since the last poll. The first gives nothing, as it
+    /// only has a snapshot to start from.
+    pub fn poll(&mut self, reporter: &mut Reporter) -> Result<Vec<ChangeEvent>> {
+        let response = self.api.get_data();
+        for fallback in self.api.last_fallbacks() {
+            reporter.warn(fallback);
+        }
+        let DataResponse::Changed(mut data) = response? else {
+            return Ok(Vec::new());
+        };
+        self.privacy.apply(&mut data);
+        let events = self
+            .previous
+            .as_ref()
+            .map(|previous| diff::diff_snapshots(previous, &data))
+            .unwrap_or_default();
+        self.previous = Some(data);
+        Ok(events)
+    }
+}
+
+/// An event as `watch` prints it: a time and a description, or a JSON
+/// object with the event's fields and its time.
+fn line(seen: &SeenEvent, json: bool, zone: DisplayZone, time: DateTime<Utc>) -> String {
+    if json {
+        serde_json::to_string(seen).unwrap_or_default()
+    } else {
+        format!(
+            "{} {}",
+            format::format_time(time, zone, TimeStyle::Clock),
+            seen.event
+        )
+    }
+}
+
+/// Refresh every `interval` until interrupted, handing each refresh's
+/// changes and when it finished to `changed`.
+///
+/// Failing to fetch the first snapshot is an error; later failures are
+/// warned about and the next refresh tried.
+pub fn each_refresh(
+    interval: Duration,
+    source: &DataSource,
+    reporter: &mut Reporter,
+    mut changed: impl FnMut(Vec<ChangeEvent>, DateTime<Utc>, &mut Reporter) -> Result<()>,
+) -> Result<()> {
+    let mut watcher = Watcher::new(source)?;
+    loop {
+        match watcher.poll(reporter) {
+            Ok(events) => changed(events, Utc::now(), reporter)?,
+            Err(e) if !watcher.started() => return Err(e),
+            Err(e) => reporter.warn(format!("Could not refresh: {:#}", e)),
+        }
+        thread::sleep(interval);
+    }
+}
+
+/// Print the changes every `interval`, until interrupted.
+pub fn run(
+    interval: Duration,
+    json: bool,
+    zone: DisplayZone,
+    source: &DataSource,
+    reporter: &mut Reporter,
+) -> Result<()> {
+    each_refresh(interval, source, reporter, |events, now, reporter| {
+        for event in events {
+            reporter.data(line(&SeenEvent::new(event, now), json, zone, now))?;
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        mirrors::{MirrorChoice, MirrorSelection},
+        models::Pilot,
+        report::Verbosity,
+        testing,
+    };
+    use chrono::TimeZone;
+    use reqwest::header::HeaderValue;
+
+    fn body(callsigns: &[&str]) -> String {
+        let data = V3ResponseData {
+            pilots: (1..)
+                .zip(callsigns)
+                .map(|(cid, callsign)| Pilot {
+                    cid,
+                    ..testing::pilot(callsign)
+                })
+                .collect(),
+            ..V3ResponseData::default()
+        };
+        serde_json::to_string(&data).unwrap()
+    }
+
+    #[test]
+    fn polls_give_the_changes_since_the_last() {
+        let mirror = MirrorSelection {
+            choice: MirrorChoice::Url(format!("{}/watch/data", mockito::server_url())),
+            ..MirrorSelection::default()
+        };
+        let user_agent = HeaderValue::from_static("test");
+        let source = DataSource {
+            use_cache: false,
+            mirror: &mirror,
+            user_agent: &user_agent,
+            privacy: Privacy::Full,
+        };
+        let (mut reporter, _, _) = Reporter::captured(Verbosity::Normal);
+        let mut watcher = Watcher::new(&source).unwrap();
+        assert!(!watcher.started());
+
+        let first = mockito::mock("GET", "/watch/data")
+            .with_body(body(&["BAW1"]))
+            .create();
+        assert_eq!(watcher.poll(&mut reporter).unwrap(), []);
+        assert!(watcher.started());
+        // the same body again is no change
+        assert_eq!(watcher.poll(&mut reporter).unwrap(), []);
+        drop(first);
+
+        let _second = mockito::mock("GET", "/watch/data")
+            .with_body(body(&["BAW1", "DLH4"]))
+            .create();
+        let events = watcher.poll(&mut reporter).unwrap();
+        assert_eq!(
+            events,
+            [ChangeEvent::PilotConnected {
+                cid: 2,
+                callsign: "DLH4".to_owned(),
+            }]
+        );
+    }
+
+    #[test]
+    fn lines_are_timed() {
+        let time = Utc.ymd(2022, 10, 16).and_hms(12, 5, 30);
+        let seen = SeenEvent::new(
+            ChangeEvent::ControllerOnline {
+                cid: 10,
+                callsign: "EGLL_TWR".to_owned(),
+            },
+            time,
+        );
+        assert_eq!(
+            line(&seen, false, DisplayZone::Utc, time),
+            "12:05z EGLL_TWR (10) online"
+        );
+        assert_eq!(
+            line(&seen, true, DisplayZone::Utc, time),
+            r#"{"time":"2022-10-16T12:05:30Z","event":"controller_online","cid":10,"callsign":"EGLL_TWR"}"#
+        );
+    }
+}
@@ -0,0 +1,413 @@
+//! Pieces of the interface shared by several views.
+
+use tui::{
+    style::{Modifier, Style},
+    text::{Span, Spans},
+};
+
+/// A change to a [`TextInput`] from an editing key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edit {
+    Left,
+    Right,
+    Home,
+    End,
+    /// Delete the character before the cursor.
+    Backspace,
+    /// Delete the character under the cursor.
+    Delete,
+    /// Delete the word before the cursor, like Ctrl+W in a shell.
+    DeleteWord,
+    /// Delete everything before the cursor, like Ctrl+U in a shell.
+    DeleteToStart,
+}
+
+impl Edit {
+    /// Whether the edit only moves the cursor.
+    pub fn is_movement(self) -> bool {
+        matches!(self, Edit::Left | Edit::Right | Edit::Home | Edit::End)
+    }
+}
+
+/// A line of text being typed into a prompt, with a cursor.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TextInput {
+    text: String,
+    /// Byte offset of the cursor, always on a character boundary.
+    cursor: usize,
+}
+
+impl TextInput {
+    /// An input holding some text, with the cursor at the end.
+    pub fn new(text: impl Into<String>) -> Self {
+        let text = text.into();
+        let cursor = text.len();
+        Self { text, cursor }
+    }
+
+    /// The text typed so far.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Replace the text, moving the cursor to the end.
+    pub fn set(&mut self, text: impl Into<String>) {
+        *self = Self::new(text);
+    }
+
+    /// Change the text directly, like completing a word, moving the cursor
+    /// to the end.
+    pub fn rewrite(&mut self, change: impl FnOnce(&mut String)) {
+        change(&mut self.text);
+        self.cursor = self.text.len();
+    }
+
+    /// Type a character at the cursor.
+    pub fn insert(&mut self, c: char) {
+        self.text.insert(self.cursor, c);
+        self.cursor += c.len_utf8();
+    }
+
+    /// Insert pasted text at the cursor, made to fit on one line.
+    pub fn paste(&mut self, text: &str) {
+        let text = single_line(text);
+        self.text.insert_str(self.cursor, &text);
+        self.cursor += text.len();
+    }
+
+    /// Apply an editing key.
+    pub fn edit(&mut self, edit: Edit) {
+        match edit {
+            Edit::Left => self.cursor = self.previous_boundary(),
+            Edit::Right => self.cursor = self.next_boundary(),
+            Edit::Home => self.cursor = 0,
+            Edit::End => self.cursor = self.text.len(),
+            Edit::Backspace => {
+                let start = self.previous_boundary();
+                self.delete_range(start, self.cursor);
+            }
+            Edit::Delete => {
+                let end = self.next_boundary();
+                self.delete_range(self.cursor, end);
+            }
+            Edit::DeleteWord => {
+                let before = self.text[..self.cursor].trim_end();
+                let start = before
+                    .char_indices()
+                    .rev()
+                    .find(|(_, c)| c.is_whitespace())
+                    .map_or(0, |(i, c)| i + c.len_utf8());
+                self.delete_range(start, self.cursor);
+            }
+            Edit::DeleteToStart => self.delete_range(0, self.cursor),
+        }
+    }
+
+    /// Start of the character before the cursor, or the cursor at the start.
+    fn previous_boundary(&self) -> usize {
+        self.text[..self.cursor]
+            .char_indices()
+            .next_back()
+            .map_or(0, |(i, _)| i)
+    }
+
+    /// End of the character under the cursor, or the cursor at the end.
+    fn next_boundary(&self) -> usize {
+        self.text[self.cursor..]
+            .chars()
+            .next()
+            .map_or(self.cursor, |c| self.cursor + c.len_utf8())
+    }
+
+    /// Remove the text between two boundaries, leaving the cursor at the start.
+    fn delete_range(&mut self, start: usize, end: usize) {
+        let _ = self.text.drain(start..end);
+        self.cursor = start;
+    }
+
+    /// The text as spans, with the character under the cursor (or a space
+    /// after the end) reversed so the cursor shows.
+    pub fn spans(&self, style: Style) -> Vec<Span<'_>> {
+        let (before, rest) = self.text.split_at(self.cursor);
+        let under = rest.chars().next().map_or(0, char::len_utf8);
+        let (under, after) = rest.split_at(under);
+        vec![
+            Span::styled(before, style),
+            Span::styled(
+                if under.is_empty() { " " } else { under },
+                style.add_modifier(Modifier::REVERSED),
+            ),
+            Span::styled(after, style),
+        ]
+    }
+}
+
+/// A list of items to tick or untick, with one of them selected.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CheckList {
+    /// Each item's label and whether it's ticked.
+    items: Vec<(String, bool)>,
+    selected: usize,
+}
+
+impl CheckList {
+    /// A list of labelled items, with the first one selected.
+    pub fn new(items: impl IntoIterator<Item = (String, bool)>) -> Self {
+        Self {
+            items: items.into_iter().collect(),
+            selected: 0,
+        }
+    }
+
+    /// Move the selection by some rows, stopping at either end.
+    pub fn move_selection(&mut self, rows: i32) {
+        let last = self.items.len().saturating_sub(1);
+        let selected = i64::try_from(self.selected).unwrap_or(i64::MAX) + i64::from(rows);
+        self.selected = usize::try_from(selected.max(0)).unwrap_or(0).min(last);
+    }
+
+    /// Tick the selected item, or untick it.
+    pub fn toggle(&mut self) {
+        if let Some((_, ticked)) = self.items.get_mut(self.selected) {
+            *ticked = !*ticked;
+        }
+    }
+
+    /// Labels of the items that aren't ticked.
+    pub fn unticked(&self) -> Vec<&str> {
+        self.items
+            .iter()
+            .filter(|(_, ticked)| !ticked)
+            .map(|(label, _)| label.as_str())
+            .collect()
+    }
+
+    /// A line per item with its box, the selected one reversed.
+    pub fn lines(&self, style: Style) -> Vec<Spans<'_>> {
+        self.items
+            .iter()
+            .enumerate()
+            .map(|(i, (label, ticked))| {
+                let text = format!("[{}] {}", if *ticked { 'x' } else { ' ' }, label);
+                Spans::from(Span::styled(
+                    text,
+                    if i == self.selected {
+                        style.add_modifier(Modifier::REVERSED)
+                    } else {
+                        style
+                    },
+                ))
+            })
+            .collect()
+    }
+}
+
+/// Pasted text on one line: trailing line breaks are dropped, other line
+/// breaks and tabs become spaces, and other control characters are removed.
+pub fn single_line(text: &str) -> String {
+    text.trim_end_matches(['\r', '\n'])
+        .replace("\r\n", " ")
+        .chars()
+        .filter_map(|c| match c {
+            '\n' | '\r' | '\t' => Some(' '),
+            c if c.is_control() => None,
+            c => Some(c),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An input with some text and the cursor at a byte offset.
+    fn at(text: &str, cursor: usize) -> TextInput {
+        let mut input = TextInput::new(text);
+        input.cursor = cursor;
+        input
+    }
+
+    fn edited(mut input: TextInput, edits: &[Edit]) -> TextInput {
+        for &edit in edits {
+            input.edit(edit);
+        }
+        input
+    }
+
+    #[test]
+    fn new_input_has_the_cursor_at_the_end() {
+        assert_eq!(TextInput::new("BAW1"), at("BAW1", 4));
+        assert_eq!(TextInput::default(), at("", 0));
+        let mut input = at("BAW1", 1);
+        input.set("DAL");
+        assert_eq!(input, at("DAL", 3));
+        input.rewrite(|text| text.push_str("123"));
+        assert_eq!(input, at("DAL123", 6));
+    }
+
+    #[test]
+    fn cursor_movement() {
+        let input = TextInput::new("BAW1");
+        assert_eq!(edited(input.clone(), &[Edit::Left]), at("BAW1", 3));
+        assert_eq!(edited(input.clone(), &[Edit::Home]), at("BAW1", 0));
+        assert_eq!(
+            edited(input.clone(), &[Edit::Home, Edit::Right]),
+            at("BAW1", 1)
+        );
+        assert_eq!(
+            edited(input.clone(), &[Edit::Home, Edit::End]),
+            at("BAW1", 4)
+        );
+        // stops at either end
+        assert_eq!(edited(input.clone(), &[Edit::Right]), at("BAW1", 4));
+        assert_eq!(
+            edited(input, &[Edit::Home, Edit::Left, Edit::Left]),
+            at("BAW1", 0)
+        );
+        assert!(Edit::Home.is_movement());
+        assert!(!Edit::Backspace.is_movement());
+    }
+
+    #[test]
+    fn typing_at_the_cursor() {
+        let mut input = at("BAW", 0);
+        input.insert('x');
+        assert_eq!(input, at("xBAW", 1));
+        input.edit(Edit::End);
+        input.insert('1');
+        assert_eq!(input, at("xBAW1", 5));
+        let mut middle = at("KFO", 1);
+        middle.insert('S');
+        assert_eq!(middle, at("KSFO", 2));
+    }
+
+    #[test]
+    fn deleting_characters() {
+        assert_eq!(
+            edited(TextInput::new("BAW1"), &[Edit::Backspace]),
+            at("BAW", 3)
+        );
+        assert_eq!(edited(at("BAW1", 2), &[Edit::Backspace]), at("BW1", 1));
+        assert_eq!(edited(at("BAW1", 2), &[Edit::Delete]), at("BA1", 2));
+        // nothing to delete at either end
+        assert_eq!(edited(at("BAW1", 0), &[Edit::Backspace]), at("BAW1", 0));
+        assert_eq!(
+            edited(TextInput::new("BAW1"), &[Edit::Delete]),
+            at("BAW1", 4)
+        );
+        assert_eq!(edited(TextInput::default(), &[Edit::Backspace]), at("", 0));
+    }
+
+    #[test]
+    fn deleting_words() {
+        assert_eq!(
+            edited(TextInput::new("dep:EGLL arr:KJFK"), &[Edit::DeleteWord]),
+            at("dep:EGLL ", 9)
+        );
+        // spaces before the cursor go with the word
+        assert_eq!(
+            edited(TextInput::new("dep:EGLL   "), &[Edit::DeleteWord]),
+            at("", 0)
+        );
+        assert_eq!(
+            edited(at("one two three", 7), &[Edit::DeleteWord]),
+            at("one  three", 4)
+        );
+        assert_eq!(
+            edited(at("one two", 5), &[Edit::DeleteToStart]),
+            at("wo", 0)
+        );
+        assert_eq!(edited(at("one", 0), &[Edit::DeleteWord]), at("one", 0));
+    }
+
+    #[test]
+    fn multibyte_characters_move_and_delete_whole() {
+        let input = TextInput::new("Zürich→");
+        let left = edited(input.clone(), &[Edit::Left]);
+        assert_eq!(left.cursor, "Zürich".len());
+        assert_eq!(edited(input.clone(), &[Edit::Backspace]).text(), "Zürich");
+        let mut middle = edited(input, &[Edit::Home, Edit::Right, Edit::Right]);
+        assert_eq!(middle.cursor, "Zü".len());
+        middle.edit(Edit::Backspace);
+        assert_eq!(middle, at("Zrich→", 1));
+        middle.edit(Edit::Delete);
+        assert_eq!(middle, at("Zich→", 1));
+        middle.insert('é');
+        assert_eq!(middle, at("Zéich→", 3));
+    }
+
+    #[test]
+    fn pasting_at_the_cursor() {
+        let mut input = at("dep: arr:KJFK", 4);
+        input.paste("EGLL");
+        assert_eq!(input, at("dep:EGLL arr:KJFK", 8));
+        let mut input = TextInput::default();
+        input.paste("BAW1\r\n");
+        assert_eq!(input, at("BAW1", 4));
+    }
+
+    #[test]
+    fn pastes_fit_on_one_line() {
+        assert_eq!(single_line("BAW1"), "BAW1");
+        assert_eq!(single_line("BAW1\n"), "BAW1");
+        assert_eq!(single_line("BAW1\r\n\r\n"), "BAW1");
+        assert_eq!(single_line("BAW1\r\nDAL2\nUAL3"), "BAW1 DAL2 UAL3");
+        assert_eq!(single_line("dep:\tEGLL"), "dep: EGLL");
+        assert_eq!(single_line("\u{1b}[31mKJFK\u{7}"), "[31mKJFK");
+        assert_eq!(single_line(""), "");
+    }
+
+    #[test]
+    fn cursor_is_drawn_reversed() {
+        let style = Style::default();
+        let reversed = style.add_modifier(Modifier::REVERSED);
+        let contents = |input: &TextInput| {
+            input
+                .spans(style)
+                .into_iter()
+                .map(|span| (span.content.into_owned(), span.style))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            contents(&at("BAW1", 1)),
+            [
+                ("B".to_owned(), style),
+                ("A".to_owned(), reversed),
+                ("W1".to_owned(), style)
+            ]
+        );
+        // past the end, a space stands in
+        assert_eq!(
+            contents(&TextInput::new("BAW1")),
+            [
+                ("BAW1".to_owned(), style),
+                (" ".to_owned(), reversed),
+                (String::new(), style)
+            ]
+        );
+        assert_eq!(contents(&at("ü", 0))[1], ("ü".to_owned(), reversed));
+    }
+
+    #[test]
+    fn check_list_selection_and_ticks() {
+        let mut list = CheckList::new(["A", "B", "C"].map(|l| (l.to_owned(), true)));
+        list.move_selection(-1);
+        list.toggle();
+        assert_eq!(list.unticked(), ["A"]);
+        list.move_selection(10);
+        list.toggle();
+        assert_eq!(list.unticked(), ["A", "C"]);
+        list.move_selection(-1);
+        list.toggle();
+        list.toggle();
+        assert_eq!(list.unticked(), ["A", "C"]);
+        let lines = list.lines(Style::default());
+        assert_eq!(lines[0].0[0].content, "[ ] A");
+        assert_eq!(lines[1].0[0].content, "[x] B");
+        assert_eq!(
+            lines[1].0[0].style,
+            Style::default().add_modifier(Modifier::REVERSED)
+        );
+        CheckList::default().toggle();
+    }
+}
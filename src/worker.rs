@@ -0,0 +1,579 @@
+//! Background data fetching.
+
+use crate::{
+    api::{DataResponse, FetchTimings, Vatsim},
+    dump::DumpSettings,
+    mirrors::{MirrorLatency, MirrorSelection},
+    models::{Event, V3ResponseData},
+    shared::{self, Reader, Sharing},
+};
+use anyhow::Result;
+use log::{debug, warn};
+use reqwest::header::HeaderValue;
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Data from a successful fetch.
+#[derive(Debug)]
+pub enum Fetched {
+    Data(V3ResponseData),
+    /// The data hasn't changed since the last fetch.
+    Unchanged,
+}
+
+/// Outcome of a fetch made on the worker thread.
+pub type FetchResult = Result<Fetched>;
+
+/// Something for the worker thread to fetch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Request {
+    Data,
+    /// Fetch the data from the next mirror.
+    NextMirror,
+    Events,
+}
+
+/// A completed fetch sent back from the worker thread.
+#[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
+pub enum Message {
+    /// The outcome of a data fetch, with the running totals.
+    Data(FetchResult, FetchStats),
+    /// The outcome of an events fetch.
+    Events(Result<Vec<Event>>),
+}
+
+/// Running totals of what the worker's fetches returned.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FetchStats {
+    pub fetches: usize,
+    pub failures: usize,
+    /// Responses where the server said nothing changed.
+    pub not_modified: usize,
+    /// Responses skipped because the body was identical to the last one.
+    pub skipped_identical: usize,
+    /// Times a mirror serving stale data was switched away from.
+    pub stale_switches: usize,
+    /// URL of the V3 mirror in use, if the API struct is set up.
+    pub mirror: Option<String>,
+    /// How quickly each mirror answered, if they were raced.
+    pub mirror_latencies: Vec<MirrorLatency>,
+    /// Time spent downloading and parsing in the last fetch.
+    pub timings: FetchTimings,
+    /// Raw bodies saved with `--debug-dump`.
+    pub dumps: usize,
+    /// Where the last raw body was saved.
+    pub last_dump: Option<PathBuf>,
+    /// V3 mirrors that failed since the last fetch that didn't, in the
+    /// order they were tried.
+    pub mirrors_tried: Vec<String>,
+}
+
+/// Fetches given up on by the startup timeout, whose results come in late.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Abandoned(usize);
+
+impl Abandoned {
+    /// Note that the fetch in progress was given up on.
+    pub fn add(&mut self) {
+        self.0 += 1;
+    }
+
+    /// Whether to use a fetch's result. The failure of an abandoned fetch
+    /// is stale and dropped, but data that comes in late is still worth
+    /// showing.
+    pub fn keep(&mut self, result: &FetchResult) -> bool {
+        if self.0 == 0 {
+            return true;
+        }
+        self.0 -= 1;
+        result.is_ok()
+    }
+}
+
+/// Settings for the worker's API requests.
+#[derive(Debug, Clone)]
+pub struct WorkerOptions {
+    /// Use the on-disk status cache until a fetch fails.
+    pub use_cache: bool,
+    /// Make requests that save bandwidth and parsing.
+    pub low_bandwidth: bool,
+    /// User agent to send with requests.
+    pub user_agent: HeaderValue,
+    /// V3 mirror to use.
+    pub mirror: MirrorSelection,
+    /// Where to save raw V3 bodies, if anywhere.
+    pub dumps: Option<DumpSettings>,
+    /// Whether to share the data with attached instances, or to read the
+    /// data shared by another instead of fetching it.
+    pub sharing: Sharing,
+}
+
+/// Handle to the background thread that talks to the VATSIM API.
+///
+/// The interface sends fetch requests and polls for results so that
+/// the network round trips never block drawing or input handling.
+pub struct Worker {
+    requests: Sender<Request>,
+    results: Receiver<Message>,
+    /// Set to stop the thread before it starts another request.
+    stop: Arc<AtomicBool>,
+}
+
+impl Worker {
+    /// Spawn the worker thread.
+    ///
+    /// The thread lives until the `Worker` is dropped or shut down.
+    pub fn spawn(options: WorkerOptions) -> Self {
+        let (request_tx, request_rx) = mpsc::channel::<Request>();
+        let (result_tx, result_rx) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopping = Arc::clone(&stop);
+        let _ = thread::spawn(move || {
+            let mut vatsim: Option<Vatsim> = None;
+            let mut use_cache = options.use_cache;
+            let mut stats = FetchStats::default();
+            let mut reader = match &options.sharing {
+                Sharing::Attach(path) => Some(Reader::new(path.clone())),
+                _ => None,
+            };
+            for request in request_rx {
+                // requests queued before shutting down aren't worth making
+                if stopping.load(Ordering::Relaxed) {
+                    break;
+                }
+                if request == Request::Events {
+                    let result = fetch_events(&mut vatsim, use_cache, &options);
+                    if result_tx.send(Message::Events(result)).is_err() {
+                        break;
+                    }
+                    continue;
+                }
+                stats.fetches += 1;
+                if let Some(reader) = &mut reader {
+                    let result = read_shared(reader, &mut stats);
+                    if result_tx
+                        .send(Message::Data(result, stats.clone()))
+                        .is_err()
+                    {
+                        break;
+                    }
+                    continue;
+                }
+                let next_mirror = request == Request::NextMirror;
+                let response = fetch(&mut vatsim, use_cache, next_mirror, &options, &mut stats);
+                if response.is_ok() {
+                    stats.mirrors_tried.clear();
+                }
+                if vatsim.as_mut().is_some_and(Vatsim::take_stale_switch) {
+                    stats.stale_switches += 1;
+                }
+                stats.mirror = vatsim.as_ref().map(|v| v.mirror_url().to_owned());
+                stats.mirror_latencies = vatsim
+                    .as_ref()
+                    .map(|v| v.mirror_latencies().to_vec())
+                    .unwrap_or_default();
+                stats.timings = vatsim
+                    .as_ref()
+                    .map(Vatsim::last_timings)
+                    .unwrap_or_default();
+                let result = match response {
+                    Ok(DataResponse::Changed(data)) => {
+                        if let Sharing::Publish(path) = &options.sharing {
+                            if let Err(e) = shared::publish(path, &data) {
+                                warn!("{:#}", e);
+                            }
+                        }
+                        Ok(Fetched::Data(data))
+                    }
+                    Ok(DataResponse::NotModified) => {
+                        stats.not_modified += 1;
+                        Ok(Fetched::Unchanged)
+                    }
+                    Ok(DataResponse::IdenticalBody) => {
+                        stats.skipped_identical += 1;
+                        Ok(Fetched::Unchanged)
+                    }
+                    Err(e) => {
+                        stats.failures += 1;
+                        // the cached V3 URLs might be the problem
+                        use_cache = false;
+                        Err(e)
+                    }
+                };
+                if result_tx
+                    .send(Message::Data(result, stats.clone()))
+                    .is_err()
+                {
+                    break;
+                }
+            }
+            debug!("Worker thread exiting");
+        });
+        Self {
+            requests: request_tx,
+            results: result_rx,
+            stop,
+        }
+    }
+
+    /// Ask the worker thread to fetch fresh data.
+    pub fn request(&self) {
+        self.send(Request::Data);
+    }
+
+    /// Ask the worker thread to fetch fresh data from the next mirror.
+    pub fn request_next_mirror(&self) {
+        self.send(Request::NextMirror);
+    }
+
+    /// Ask the worker thread to fetch the events list.
+    pub fn request_events(&self) {
+        self.send(Request::Events);
+    }
+
+    /// Send a request to the worker thread.
+    fn send(&self, request: Request) {
+        if self.requests.send(request).is_err() {
+            warn!("Worker thread is not running");
+        }
+    }
+
+    /// Stop the worker thread, waiting up to `timeout` for a fetch in
+    /// progress to finish so that it doesn't leave a file half-written.
+    /// Results that arrive meanwhile are dropped.
+    ///
+    /// Returns whether the thread stopped in time; if not, it's left to
+    /// end with the process.
+    pub fn shutdown(self, timeout: Duration) -> bool {
+        let Self {
+            requests,
+            results,
+            stop,
+        } = self;
+        stop.store(true, Ordering::Relaxed);
+        drop(requests);
+        wait_for_hang_up(&results, timeout)
+    }
+
+    /// Get the next completed fetch, if there is one.
+    pub fn try_recv(&self) -> Option<Message> {
+        match self.results.try_recv() {
+            Ok(message) => Some(message),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Message::Data(
+                Err(anyhow::anyhow!("Worker thread stopped unexpectedly")),
+                FetchStats::default(),
+            )),
+        }
+    }
+}
+
+/// Wait up to `timeout` for every sender on a channel to be dropped,
+/// discarding what they send. Returns whether they were.
+fn wait_for_hang_up<T>(receiver: &Receiver<T>, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        match receiver.recv_timeout(remaining) {
+            Ok(_) => {}
+            Err(RecvTimeoutError::Disconnected) => return true,
+            Err(RecvTimeoutError::Timeout) => return false,
+        }
+    }
+}
+
+/// Read the data another instance shares, in place of a fetch.
+fn read_shared(reader: &mut Reader, stats: &mut FetchStats) -> FetchResult {
+    match reader.read() {
+        Ok(Some(data)) => Ok(Fetched::Data(data)),
+        Ok(None) => {
+            stats.not_modified += 1;
+            Ok(Fetched::Unchanged)
+        }
+        Err(e) => {
+            stats.failures += 1;
+            Err(e)
+        }
+    }
+}
+
+/// Set up the API struct with the worker's settings.
+fn connect(use_cache: bool, options: &WorkerOptions) -> Result<Vatsim> {
+    let mut vatsim = Vatsim::with_mirror(
+        use_cache,
+        options.low_bandwidth,
+        &options.user_agent,
+        &options.mirror,
+    )?;
+    vatsim.set_dumps(options.dumps.clone());
+    Ok(vatsim)
+}
+
+/// Fetch the current data, setting up the API struct if needed and first
+/// moving on to the next mirror if asked, and count any raw body saved
+/// along the way.
+///
+/// On failure, the mirrors are noted as tried and the API struct is
+/// discarded, so that the next attempt starts over from the status
+/// endpoint and picks a new V3 URL. As the mirrors are shuffled again
+/// then, moving on skips every mirror tried since the last success by
+/// URL, so asking again never lands on one that just failed.
+fn fetch(
+    vatsim: &mut Option<Vatsim>,
+    use_cache: bool,
+    next_mirror: bool,
+    options: &WorkerOptions,
+    stats: &mut FetchStats,
+) -> Result<DataResponse> {
+    let connected = vatsim.is_some();
+    if !connected {
+        *vatsim = Some(connect(use_cache, options)?);
+    }
+    let api = vatsim.as_mut().expect("API struct was just created");
+    if next_mirror {
+        // a mirror still in use hasn't failed, but is being moved on from
+        if connected {
+            api.next_mirror();
+        }
+        api.skip_tried_mirrors(&stats.mirrors_tried);
+    }
+    let result = api.get_data();
+    if let Some(path) = api.take_dump() {
+        stats.dumps += 1;
+        stats.last_dump = Some(path);
+    }
+    if result.is_err() {
+        for url in api.last_failed_mirrors() {
+            if !stats.mirrors_tried.contains(url) {
+                stats.mirrors_tried.push(url.clone());
+            }
+        }
+        *vatsim = None;
+    }
+    result
+}
+
+/// Fetch the events list, setting up the API struct if needed.
+///
+/// Unlike a data fetch, a failure here keeps the API struct, since the
+/// events endpoint has nothing to do with the V3 mirrors.
+fn fetch_events(
+    vatsim: &mut Option<Vatsim>,
+    use_cache: bool,
+    options: &WorkerOptions,
+) -> Result<Vec<Event>> {
+    if vatsim.is_none() {
+        *vatsim = Some(connect(use_cache, options)?);
+    }
+    vatsim
+        .as_ref()
+        .expect("API struct was just created")
+        .get_events(use_cache)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        mirrors::MirrorChoice,
+        state::{App, LoadState},
+        testing,
+    };
+    use std::net::TcpListener;
+
+    #[test]
+    fn abandoned_failures_are_dropped_but_late_data_kept() {
+        let failed = || -> FetchResult { Err(anyhow::anyhow!("timed out")) };
+        let mut abandoned = Abandoned::default();
+        assert!(abandoned.keep(&failed()));
+        abandoned.add();
+        abandoned.add();
+        assert!(!abandoned.keep(&failed()));
+        assert!(abandoned.keep(&Ok(Fetched::Unchanged)));
+        assert_eq!(abandoned, Abandoned::default());
+        assert!(abandoned.keep(&failed()));
+    }
+
+    /// A fetch from a server that never answers times out on the loading
+    /// screen and is abandoned.
+    #[test]
+    fn slow_startup_fetch_is_abandoned() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/data", listener.local_addr().unwrap());
+        // accept connections and hold them open without answering
+        let _ = thread::spawn(move || {
+            let held: Vec<_> = listener.incoming().collect();
+            drop(held);
+        });
+        let worker = Worker::spawn(WorkerOptions {
+            use_cache: false,
+            low_bandwidth: false,
+            user_agent: HeaderValue::from_static("vatsim_online tests"),
+            mirror: MirrorSelection {
+                choice: MirrorChoice::Url(url),
+                ..MirrorSelection::default()
+            },
+            dumps: None,
+            sharing: Sharing::Off,
+        });
+        let mut app = App::new();
+        app.set_startup_timeout(Some(Duration::from_millis(50)));
+        app.set_loading();
+        worker.request();
+
+        let mut abandoned = Abandoned::default();
+        let start = Instant::now();
+        while !app.check_startup_deadline(Instant::now()) {
+            assert!(worker.try_recv().is_none());
+            assert!(start.elapsed() < Duration::from_secs(5));
+            thread::sleep(Duration::from_millis(10));
+        }
+        abandoned.add();
+        assert_eq!(
+            app.get_view_data().load_state,
+            LoadState::Failed("Timed out after 0s waiting for data".to_owned())
+        );
+        assert_ne!(abandoned, Abandoned::default());
+        // the request is still stuck, so the thread is left behind
+        assert!(!worker.shutdown(Duration::from_millis(50)));
+    }
+
+    fn options(url: String) -> WorkerOptions {
+        WorkerOptions {
+            use_cache: false,
+            low_bandwidth: false,
+            user_agent: HeaderValue::from_static("vatsim_online tests"),
+            mirror: MirrorSelection {
+                choice: MirrorChoice::Url(url),
+                ..MirrorSelection::default()
+            },
+            dumps: None,
+            sharing: Sharing::Off,
+        }
+    }
+
+    #[test]
+    fn waiting_for_hang_up_is_bounded() {
+        let (sender, receiver) = mpsc::channel::<()>();
+        let start = Instant::now();
+        // a sender that never hangs up
+        assert!(!wait_for_hang_up(&receiver, Duration::from_millis(100)));
+        let waited = start.elapsed();
+        assert!(waited >= Duration::from_millis(100), "{:?}", waited);
+        assert!(waited < Duration::from_secs(2), "{:?}", waited);
+
+        // one that keeps sending and then hangs up
+        let _ = thread::spawn(move || {
+            for _ in 0..5 {
+                sender.send(()).unwrap();
+                thread::sleep(Duration::from_millis(10));
+            }
+        });
+        assert!(wait_for_hang_up(&receiver, Duration::from_secs(5)));
+        assert!(receiver.try_recv().is_err());
+    }
+
+    /// The next data message from a worker.
+    fn next_data(worker: &Worker) -> (FetchResult, FetchStats) {
+        let start = Instant::now();
+        loop {
+            match worker.try_recv() {
+                Some(Message::Data(result, stats)) => return (result, stats),
+                Some(Message::Events(_)) | None => {}
+            }
+            assert!(start.elapsed() < Duration::from_secs(10));
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn failed_mirrors_are_listed_once() {
+        let url = testing::refused_url();
+        let worker = Worker::spawn(options(url.clone()));
+        worker.request();
+        let (result, stats) = next_data(&worker);
+        assert!(result.is_err());
+        assert_eq!(stats.mirrors_tried, std::slice::from_ref(&url));
+        // moving on with only one mirror comes back round to it
+        worker.request_next_mirror();
+        let (result, stats) = next_data(&worker);
+        assert!(result.is_err());
+        assert_eq!(stats.mirrors_tried, [url]);
+        assert_eq!(stats.fetches, 2);
+        assert!(worker.shutdown(Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn attached_workers_read_what_the_other_fetched() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("shared.json");
+        let data = V3ResponseData {
+            pilots: vec![testing::pilot("BAW1")],
+            ..V3ResponseData::default()
+        };
+        let _mirror = mockito::mock("GET", "/worker/shared")
+            .with_header("content-type", "application/json")
+            .with_body(serde_json::to_string(&data).unwrap())
+            .create();
+        let publishing = Worker::spawn(WorkerOptions {
+            sharing: Sharing::Publish(path.clone()),
+            ..options(format!("{}/worker/shared", mockito::server_url()))
+        });
+        let attached = Worker::spawn(WorkerOptions {
+            sharing: Sharing::Attach(path.clone()),
+            // never fetched from
+            ..options(testing::refused_url())
+        });
+        // nothing shared yet
+        attached.request();
+        assert!(matches!(next_data(&attached).0, Ok(Fetched::Unchanged)));
+
+        publishing.request();
+        assert!(matches!(next_data(&publishing).0, Ok(Fetched::Data(_))));
+        attached.request();
+        let Ok(Fetched::Data(read)) = next_data(&attached).0 else {
+            panic!("expected the shared data");
+        };
+        assert_eq!(read.pilots[0].callsign, "BAW1");
+        attached.request();
+        let (result, stats) = next_data(&attached);
+        assert!(matches!(result, Ok(Fetched::Unchanged)));
+        assert_eq!(
+            (stats.fetches, stats.not_modified, stats.failures),
+            (3, 2, 0)
+        );
+        assert_eq!(stats.mirror, None);
+        assert!(publishing.shutdown(Duration::from_secs(5)));
+        assert!(attached.shutdown(Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn idle_workers_stop_right_away() {
+        let worker = Worker::spawn(options(testing::refused_url()));
+        let start = Instant::now();
+        assert!(worker.shutdown(Duration::from_secs(5)));
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn a_worker_stuck_fetching_is_left_behind_in_time() {
+        let worker = Worker::spawn(options(testing::slow_server(200, Duration::from_secs(30))));
+        worker.request();
+        // queued behind the stuck fetch, and skipped
+        worker.request_events();
+        thread::sleep(Duration::from_millis(50));
+        let start = Instant::now();
+        assert!(!worker.shutdown(Duration::from_millis(200)));
+        let waited = start.elapsed();
+        assert!(waited < Duration::from_secs(2), "{:?}", waited);
+    }
+}